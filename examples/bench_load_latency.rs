@@ -0,0 +1,96 @@
+//! Event-loop latency while unit files are reloaded over and over
+//!
+//! Runs on a single-threaded runtime so any blocking filesystem call in the
+//! load path shows up directly as ticker lateness.
+//!
+//!     cargo run --release --example bench_load_latency -- [units] [rounds]
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const TICK: Duration = Duration::from_millis(1);
+
+fn write_units(dir: &Path, count: usize, round: usize) -> Vec<PathBuf> {
+    (0..count)
+        .map(|i| {
+            let path = dir.join(format!("bench-{}.service", i));
+            std::fs::write(
+                &path,
+                format!(
+                    "[Unit]\nDescription=Bench {} round {}\n\n[Service]\nExecStart=/bin/true\n",
+                    i, round
+                ),
+            )
+            .unwrap();
+            let dropin = dir.join(format!("bench-{}.service.d", i));
+            std::fs::create_dir_all(&dropin).unwrap();
+            std::fs::write(dropin.join("override.conf"), "[Service]\nNice=5\n").unwrap();
+            path
+        })
+        .collect()
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[((sorted.len() - 1) as f64 * p) as usize]
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args().skip(1);
+    let count: usize = args.next().and_then(|a| a.parse().ok()).unwrap_or(500);
+    let rounds: usize = args.next().and_then(|a| a.parse().ok()).unwrap_or(20);
+
+    let dir = std::env::temp_dir().join(format!("sysd-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+
+    let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel::<()>();
+    let ticker = tokio::spawn(async move {
+        let mut lateness = Vec::new();
+        loop {
+            let expected = Instant::now() + TICK;
+            tokio::select! {
+                _ = tokio::time::sleep(TICK) => {}
+                _ = &mut stop_rx => break,
+            }
+            lateness.push(Instant::now().saturating_duration_since(expected));
+        }
+        lateness
+    });
+
+    let started = Instant::now();
+    let mut loaded = 0;
+    for round in 0..rounds {
+        // Rewriting every file invalidates the parse cache: worst-case churn
+        let paths = write_units(&dir, count, round);
+        for path in &paths {
+            sysd::units::load_unit(path).await?;
+            loaded += 1;
+            // Let the ticker run between loads like other manager work would
+            tokio::task::yield_now().await;
+        }
+    }
+    let elapsed = started.elapsed();
+
+    let _ = stop_tx.send(());
+    let mut lateness = ticker.await?;
+    lateness.sort();
+    let _ = std::fs::remove_dir_all(&dir);
+
+    println!(
+        "{} loads in {:.2?} ({:.1} loads/s)",
+        loaded,
+        elapsed,
+        loaded as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "tick lateness over {} ticks: p50 {:?}  p99 {:?}  max {:?}",
+        lateness.len(),
+        percentile(&lateness, 0.50),
+        percentile(&lateness, 0.99),
+        lateness.last().copied().unwrap_or_default()
+    );
+    Ok(())
+}
@@ -0,0 +1,137 @@
+//! Socket-activated echo service
+//!
+//! Template for writing services (and tests) that are started by a socket
+//! unit. It takes its sockets from the manager the same way sd_listen_fds()
+//! does: LISTEN_PID must name this process, LISTEN_FDS counts the fds handed
+//! over starting at fd 3, and LISTEN_FDNAMES labels them.
+//!
+//! Each connection is greeted with one line describing what was passed in
+//! (`LISTEN_FDS=1 LISTEN_FDNAMES=echo`), then every line received is echoed
+//! back until the client hangs up.
+//!
+//! - Accept=no: fd 3 is the listening socket; connections are served one at a
+//!   time and the service exits after `--idle-sec` seconds without a new one,
+//!   so the socket unit starts it again on the next connection.
+//! - Accept=yes: fd 3 is the connection itself; the service exits when the
+//!   client hangs up.
+//!
+//! Example units (see also tests/socket_activation.rs):
+//!
+//! ```ini
+//! # echo.socket
+//! [Socket]
+//! ListenStream=/run/echo.sock
+//! FileDescriptorName=echo
+//!
+//! # echo.service
+//! [Service]
+//! ExecStart=/path/to/target/debug/examples/socket_echo --idle-sec 30
+//! ```
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::time::Duration;
+
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+fn main() {
+    let idle = idle_timeout();
+    let (count, names) = match listen_fds() {
+        Ok(fds) => fds,
+        Err(e) => {
+            eprintln!("socket_echo: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let greeting = format!("LISTEN_FDS={} LISTEN_FDNAMES={}\n", count, names);
+
+    if is_listening(SD_LISTEN_FDS_START) {
+        let listener = unsafe { UnixListener::from_raw_fd(SD_LISTEN_FDS_START) };
+        while wait_readable(SD_LISTEN_FDS_START, idle) {
+            match listener.accept() {
+                Ok((stream, _)) => serve(stream, &greeting),
+                Err(e) => eprintln!("socket_echo: accept: {}", e),
+            }
+        }
+    } else {
+        serve(
+            unsafe { UnixStream::from_raw_fd(SD_LISTEN_FDS_START) },
+            &greeting,
+        );
+    }
+}
+
+/// `--idle-sec N` (default 10)
+fn idle_timeout() -> Duration {
+    let args: Vec<String> = std::env::args().collect();
+    let secs = args
+        .iter()
+        .position(|arg| arg == "--idle-sec")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(10);
+    Duration::from_secs(secs)
+}
+
+/// Validate the socket activation environment like sd_listen_fds()
+fn listen_fds() -> Result<(usize, String), String> {
+    let pid = std::env::var("LISTEN_PID").map_err(|_| "LISTEN_PID not set")?;
+    if pid != std::process::id().to_string() {
+        return Err(format!("LISTEN_PID={} is not this process", pid));
+    }
+    let count: usize = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|count| count.parse().ok())
+        .filter(|&count| count > 0)
+        .ok_or("no sockets passed in LISTEN_FDS")?;
+    let names = std::env::var("LISTEN_FDNAMES").unwrap_or_default();
+    Ok((count, names))
+}
+
+fn is_listening(fd: RawFd) -> bool {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_ACCEPTCONN,
+            &mut value as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    ret == 0 && value != 0
+}
+
+/// Wait for a pending connection; false once the service has been idle too long
+fn wait_readable(fd: RawFd, idle: Duration) -> bool {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = idle.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+    unsafe { libc::poll(&mut pollfd, 1, timeout) > 0 }
+}
+
+fn serve(stream: UnixStream, greeting: &str) {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(e) => {
+            eprintln!("socket_echo: {}", e);
+            return;
+        }
+    };
+    if writer.write_all(greeting.as_bytes()).is_err() {
+        return;
+    }
+    for line in BufReader::new(stream).lines() {
+        let Ok(line) = line else {
+            return;
+        };
+        if writeln!(writer, "{}", line).is_err() {
+            return;
+        }
+    }
+}
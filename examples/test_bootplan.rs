@@ -2,7 +2,10 @@ use sysd::manager::Manager;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::ERROR)
+        .with_writer(std::io::stderr)
+        .init();
     let mut mgr = Manager::new();
     let plan = mgr.get_boot_plan("getty.target").await?;
 
@@ -0,0 +1,27 @@
+//! Raw bytes through the unit file parser and every unit type's loader
+//!
+//! Run with `cargo +nightly fuzz run parse_unit` from the fuzz/ directory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use sysd::units;
+
+fuzz_target!(|data: &[u8]| {
+    let parsed = match units::parse_bytes(data) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            if let units::ParseError::Syntax { line, .. } = e {
+                assert!(line <= data.split(|&b| b == b'\n').count());
+            }
+            return;
+        }
+    };
+    let _ = units::parse_service("fuzz.service", &parsed);
+    let _ = units::parse_socket("fuzz.socket", &parsed);
+    let _ = units::parse_timer("fuzz.timer", &parsed);
+    let _ = units::parse_target("fuzz.target", &parsed);
+    let _ = units::parse_mount("fuzz.mount", &parsed);
+    let _ = units::parse_path_unit("fuzz.path", &parsed);
+    let _ = units::parse_slice("fuzz.slice", &parsed);
+});
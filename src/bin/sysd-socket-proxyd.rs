@@ -0,0 +1,280 @@
+//! sysd-socket-proxyd - socket-activated connection forwarder
+//!
+//! Equivalent of systemd-socket-proxyd: accepts connections on the sockets
+//! passed in via LISTEN_FDS and forwards each one to a backend address, so
+//! daemons without native socket activation can still be started on demand.
+//!
+//! Typical setup: proxy.socket listens on the public port and activates
+//! proxy.service, which runs `sysd-socket-proxyd 127.0.0.1:8080` and is
+//! ordered After=/Requires= the real daemon listening on 8080.
+
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clap::Parser;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio::sync::Semaphore;
+
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+#[derive(Parser)]
+#[command(name = "sysd-socket-proxyd")]
+#[command(about = "Forward socket-activated connections to another address")]
+struct Args {
+    /// Backend to connect to: host:port, [v6]:port, /path or @abstract
+    backend: String,
+
+    /// Maximum number of simultaneous connections
+    #[arg(short = 'c', long = "connections-max", default_value_t = 256)]
+    connections_max: usize,
+
+    /// Exit after this long without connections (e.g. "30s", "5min")
+    #[arg(long = "exit-idle-time", value_parser = parse_idle_time)]
+    exit_idle_time: Option<Duration>,
+}
+
+fn parse_idle_time(s: &str) -> Result<Duration, String> {
+    sysd::units::parse_duration(s).ok_or_else(|| format!("invalid duration: {}", s))
+}
+
+/// Inherited socket: a listener, or a single connection (Accept=yes)
+enum Inherited {
+    TcpListener(TcpListener),
+    UnixListener(UnixListener),
+    TcpStream(TcpStream),
+    UnixStream(UnixStream),
+}
+
+/// Counts open connections and remembers when the last one closed
+struct Activity {
+    open: AtomicUsize,
+    last_closed: Mutex<Instant>,
+}
+
+impl Activity {
+    fn opened(&self) {
+        self.open.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn closed(&self) {
+        *self.last_closed.lock().unwrap() = Instant::now();
+        self.open.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        if self.open.load(Ordering::SeqCst) > 0 {
+            return None;
+        }
+        Some(self.last_closed.lock().unwrap().elapsed())
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let args = Args::parse();
+
+    let fds = listen_fds().unwrap_or_else(|e| {
+        eprintln!("sysd-socket-proxyd: {}", e);
+        exit(1);
+    });
+    let mut sockets = Vec::new();
+    for fd in fds {
+        match inherit_socket(fd) {
+            Ok(socket) => sockets.push(socket),
+            Err(e) => {
+                eprintln!("sysd-socket-proxyd: fd {}: {}", fd, e);
+                exit(1);
+            }
+        }
+    }
+
+    let backend: Arc<str> = Arc::from(args.backend.as_str());
+    let slots = Arc::new(Semaphore::new(args.connections_max.max(1)));
+    let activity = Arc::new(Activity {
+        open: AtomicUsize::new(0),
+        last_closed: Mutex::new(Instant::now()),
+    });
+
+    let mut tasks = Vec::new();
+    for socket in sockets {
+        let backend = Arc::clone(&backend);
+        let slots = Arc::clone(&slots);
+        let activity = Arc::clone(&activity);
+        tasks.push(tokio::spawn(serve(socket, backend, slots, activity)));
+    }
+
+    match args.exit_idle_time {
+        Some(idle_time) => wait_until_idle(&activity, idle_time).await,
+        None => {
+            for task in tasks {
+                let _ = task.await;
+            }
+        }
+    }
+}
+
+/// File descriptors passed by the service manager
+fn listen_fds() -> Result<Vec<RawFd>, String> {
+    let pid = std::env::var("LISTEN_PID").map_err(|_| "LISTEN_PID not set".to_string())?;
+    if pid.parse::<u32>().ok() != Some(std::process::id()) {
+        return Err(format!("LISTEN_PID={} is not our pid", pid));
+    }
+    let count = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<RawFd>().ok())
+        .filter(|n| *n > 0)
+        .ok_or_else(|| "no sockets passed in LISTEN_FDS".to_string())?;
+    Ok((SD_LISTEN_FDS_START..SD_LISTEN_FDS_START + count).collect())
+}
+
+fn inherit_socket(fd: RawFd) -> std::io::Result<Inherited> {
+    let listening = int_sockopt(fd, libc::SOL_SOCKET, libc::SO_ACCEPTCONN)? != 0;
+    let is_unix = int_sockopt(fd, libc::SOL_SOCKET, libc::SO_DOMAIN)? == libc::AF_UNIX;
+
+    // tokio requires non-blocking sockets
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(match (listening, is_unix) {
+        (true, true) => Inherited::UnixListener(UnixListener::from_std(unsafe {
+            std::os::unix::net::UnixListener::from_raw_fd(fd)
+        })?),
+        (true, false) => Inherited::TcpListener(TcpListener::from_std(unsafe {
+            std::net::TcpListener::from_raw_fd(fd)
+        })?),
+        (false, true) => Inherited::UnixStream(UnixStream::from_std(unsafe {
+            std::os::unix::net::UnixStream::from_raw_fd(fd)
+        })?),
+        (false, false) => Inherited::TcpStream(TcpStream::from_std(unsafe {
+            std::net::TcpStream::from_raw_fd(fd)
+        })?),
+    })
+}
+
+fn int_sockopt(fd: RawFd, level: libc::c_int, name: libc::c_int) -> std::io::Result<libc::c_int> {
+    let mut value: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            level,
+            name,
+            &mut value as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if result < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(value)
+}
+
+async fn serve(
+    socket: Inherited,
+    backend: Arc<str>,
+    slots: Arc<Semaphore>,
+    activity: Arc<Activity>,
+) {
+    // Accept=yes: the service owns exactly one connection
+    let single: Box<dyn Connection> = match socket {
+        Inherited::TcpStream(stream) => Box::new(stream),
+        Inherited::UnixStream(stream) => Box::new(stream),
+        Inherited::TcpListener(_) | Inherited::UnixListener(_) => {
+            return accept_loop(socket, backend, slots, activity).await;
+        }
+    };
+    activity.opened();
+    proxy(single, &backend).await;
+    activity.closed();
+}
+
+async fn accept_loop(
+    socket: Inherited,
+    backend: Arc<str>,
+    slots: Arc<Semaphore>,
+    activity: Arc<Activity>,
+) {
+    loop {
+        let Ok(permit) = Arc::clone(&slots).acquire_owned().await else {
+            return;
+        };
+        let accepted: std::io::Result<Box<dyn Connection>> = match &socket {
+            Inherited::TcpListener(listener) => listener
+                .accept()
+                .await
+                .map(|(client, _)| Box::new(client) as Box<dyn Connection>),
+            Inherited::UnixListener(listener) => listener
+                .accept()
+                .await
+                .map(|(client, _)| Box::new(client) as Box<dyn Connection>),
+            Inherited::TcpStream(_) | Inherited::UnixStream(_) => return,
+        };
+        match accepted {
+            Ok(client) => {
+                activity.opened();
+                let backend = Arc::clone(&backend);
+                let activity = Arc::clone(&activity);
+                tokio::spawn(async move {
+                    proxy(client, &backend).await;
+                    activity.closed();
+                    drop(permit);
+                });
+            }
+            Err(e) => eprintln!("sysd-socket-proxyd: accept failed: {}", e),
+        }
+    }
+}
+
+trait Connection: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Connection for T {}
+
+async fn connect_backend(backend: &str) -> std::io::Result<Box<dyn Connection>> {
+    if backend.starts_with('/') {
+        return Ok(Box::new(UnixStream::connect(backend).await?));
+    }
+    if let Some(name) = backend.strip_prefix('@') {
+        use std::os::linux::net::SocketAddrExt;
+        let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+        let stream = std::os::unix::net::UnixStream::connect_addr(&addr)?;
+        stream.set_nonblocking(true)?;
+        return Ok(Box::new(UnixStream::from_std(stream)?));
+    }
+    let stream = TcpStream::connect(backend).await?;
+    let _ = stream.set_nodelay(true);
+    Ok(Box::new(stream))
+}
+
+async fn proxy(mut client: Box<dyn Connection>, backend: &str) {
+    let mut server = match connect_backend(backend).await {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!(
+                "sysd-socket-proxyd: connecting to {} failed: {}",
+                backend, e
+            );
+            return;
+        }
+    };
+    if let Err(e) = tokio::io::copy_bidirectional(&mut client, &mut server).await {
+        eprintln!(
+            "sysd-socket-proxyd: connection to {} closed: {}",
+            backend, e
+        );
+    }
+}
+
+async fn wait_until_idle(activity: &Activity, idle_time: Duration) {
+    loop {
+        match activity.idle_for() {
+            Some(idle) if idle >= idle_time => return,
+            Some(idle) => tokio::time::sleep(idle_time - idle).await,
+            None => tokio::time::sleep(idle_time).await,
+        }
+    }
+}
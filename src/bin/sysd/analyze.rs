@@ -0,0 +1,345 @@
+//! `sysd analyze security`: how exposed services are, like
+//! `systemd-analyze security`, and `sysd analyze spawn`: where the last
+//! start of a service spent its time, and `sysd analyze features`: which
+//! optional kernel features the manager found, and `sysd analyze time` /
+//! `sysd analyze plot`: how long the boot took and when each unit came up,
+//! and `sysd analyze history`: the last state transitions of a unit
+//!
+//! Security works on the unit files without a running manager and honours
+//! --root, so images can be checked before they boot. Spawn, features,
+//! time, plot and history ask the running manager, which times every start,
+//! records every transition and probed the kernel when it came up. `plot
+//! --json` prints the rows of `systemd-analyze plot --json`, so boot-analysis
+//! tools built for systemd read them unchanged.
+
+use peercred_ipc::Client;
+use sysd::manager::Manager;
+use sysd::protocol::{
+    socket_path, BootTimesInfo, Request, Response, SpawnProfileInfo, TransitionInfo,
+};
+use sysd::units::SecurityReport;
+
+#[derive(clap::Subcommand)]
+pub(super) enum AnalyzeCommand {
+    /// Score the sandboxing of services (every installed service if none given)
+    Security { units: Vec<String> },
+    /// Show how long each step of the last start of a service took
+    Spawn { unit: String },
+    /// Show which optional kernel features units can use, and why the
+    /// others are missing
+    Features,
+    /// Show how long firmware, loader, kernel and userspace took to boot
+    Time,
+    /// List when each unit started and stopped, in microseconds since the
+    /// kernel started
+    Plot {
+        /// Print the rows as JSON, like systemd-analyze plot --json=
+        #[arg(long, value_name = "MODE", value_parser = ["pretty", "short", "off"])]
+        json: Option<String>,
+    },
+    /// List the last state transitions of a unit, with how long each state
+    /// lasted and why the unit exited or failed
+    History { unit: String },
+}
+
+type AnalyzeResult = Result<(), Box<dyn std::error::Error>>;
+
+/// Width of the bar of a step taking all of the time
+const BAR_WIDTH: f64 = 30.0;
+
+pub(super) async fn run_analyze_command(command: AnalyzeCommand, user_mode: bool) -> AnalyzeResult {
+    let units = match command {
+        AnalyzeCommand::Spawn { unit } => return run_spawn(unit, user_mode),
+        AnalyzeCommand::Features => return run_features(user_mode),
+        AnalyzeCommand::Time => return run_time(user_mode),
+        AnalyzeCommand::Plot { json } => return run_plot(json.as_deref(), user_mode),
+        AnalyzeCommand::History { unit } => return run_history(unit, user_mode),
+        AnalyzeCommand::Security { units } => units,
+    };
+    let mut manager = if user_mode {
+        Manager::new_user()
+    } else {
+        Manager::new()
+    };
+
+    if units.is_empty() {
+        print_overview(&manager.analyze_security_all().await);
+    }
+    for (i, unit) in units.iter().enumerate() {
+        let (name, report) = manager.analyze_security(unit).await?;
+        if i > 0 {
+            println!();
+        }
+        print_report(&name, &report);
+    }
+    Ok(())
+}
+
+fn run_spawn(unit: String, user_mode: bool) -> AnalyzeResult {
+    let request = Request::SpawnProfile { name: unit };
+    match Client::call(&socket_path(user_mode), &request) {
+        Ok(Response::SpawnProfile(profile)) => print_spawn_profile(&profile),
+        Ok(Response::Error(message)) => return Err(message.into()),
+        Ok(other) => return Err(format!("unexpected response: {:?}", other).into()),
+        Err(e) => return Err(format!("cannot reach the manager: {}", e).into()),
+    }
+    Ok(())
+}
+
+fn run_features(user_mode: bool) -> AnalyzeResult {
+    let features = match Client::call(&socket_path(user_mode), &Request::Features) {
+        Ok(Response::Features(features)) => features,
+        Ok(Response::Error(message)) => return Err(message.into()),
+        Ok(other) => return Err(format!("unexpected response: {:?}", other).into()),
+        Err(e) => return Err(format!("cannot reach the manager: {}", e).into()),
+    };
+    println!("{:<20} {:<10} REASON", "FEATURE", "STATE");
+    for (feature, missing) in features {
+        match missing {
+            None => println!("{:<20} {:<10}", feature, "available"),
+            Some(reason) => println!("{:<20} {:<10} {}", feature, "missing", reason),
+        }
+    }
+    Ok(())
+}
+
+fn fetch_boot_times(user_mode: bool) -> Result<BootTimesInfo, Box<dyn std::error::Error>> {
+    match Client::call(&socket_path(user_mode), &Request::BootTimes) {
+        Ok(Response::BootTimes(boot)) => Ok(boot),
+        Ok(Response::Error(message)) => Err(message.into()),
+        Ok(other) => Err(format!("unexpected response: {:?}", other).into()),
+        Err(e) => Err(format!("cannot reach the manager: {}", e).into()),
+    }
+}
+
+/// "Startup finished in ..." as systemd-analyze time prints it
+fn run_time(user_mode: bool) -> AnalyzeResult {
+    let boot = fetch_boot_times(user_mode)?;
+    let Some(finish) = boot.finish_usec else {
+        return Err("bootup is not yet finished, try again later".into());
+    };
+    let mut phases = Vec::new();
+    if let Some(firmware) = boot.firmware_usec {
+        phases.push(format!("{} (firmware)", seconds(firmware)));
+    }
+    if let Some(loader) = boot.loader_usec {
+        phases.push(format!("{} (loader)", seconds(loader)));
+    }
+    if !user_mode {
+        phases.push(format!("{} (kernel)", seconds(boot.userspace_usec)));
+    }
+    let userspace = finish.saturating_sub(boot.userspace_usec);
+    phases.push(format!("{} (userspace)", seconds(userspace)));
+    let mut total = boot.firmware_usec.unwrap_or(0) + boot.loader_usec.unwrap_or(0);
+    total += if user_mode { userspace } else { finish };
+    println!(
+        "Startup finished in {} = {}",
+        phases.join(" + "),
+        seconds(total)
+    );
+    if let Some(target) = boot.target {
+        println!(
+            "{} reached after {} in userspace.",
+            target,
+            seconds(userspace)
+        );
+    }
+    Ok(())
+}
+
+fn run_plot(json: Option<&str>, user_mode: bool) -> AnalyzeResult {
+    let boot = fetch_boot_times(user_mode)?;
+    match json {
+        Some("pretty") => println!("{}", plot_json(&boot, true)),
+        Some("short") => println!("{}", plot_json(&boot, false)),
+        _ => {
+            println!(
+                "{:<40} {:>12} {:>12} {:>10} {:>12} {:>12}",
+                "NAME", "ACTIVATING", "ACTIVATED", "TIME", "DEACTIVATING", "DEACTIVATED"
+            );
+            for unit in &boot.units {
+                println!(
+                    "{:<40} {:>12} {:>12} {:>10} {:>12} {:>12}",
+                    unit.name,
+                    millis(unit.activating),
+                    millis(unit.activated),
+                    millis(unit.time),
+                    millis(unit.deactivating),
+                    millis(unit.deactivated)
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_history(unit: String, user_mode: bool) -> AnalyzeResult {
+    let request = Request::StateHistory { name: unit };
+    let history = match Client::call(&socket_path(user_mode), &request) {
+        Ok(Response::StateHistory(history)) => history,
+        Ok(Response::Error(message)) => return Err(message.into()),
+        Ok(other) => return Err(format!("unexpected response: {:?}", other).into()),
+        Err(e) => return Err(format!("cannot reach the manager: {}", e).into()),
+    };
+    println!(
+        "{:<23} {:<12} {:<14} {:>10} REASON",
+        "TIME", "STATE", "SUB", "FOR"
+    );
+    for (i, transition) in history.iter().enumerate() {
+        // The last state is still going on
+        let lasted = history
+            .get(i + 1)
+            .map(|next| seconds(next.usec.saturating_sub(transition.usec)))
+            .unwrap_or_default();
+        let line = format!(
+            "{:<23} {:<12} {:<14} {:>10} {}",
+            timestamp(transition),
+            transition.active,
+            transition.sub,
+            lasted,
+            transition.reason.as_deref().unwrap_or("")
+        );
+        println!("{}", line.trim_end());
+    }
+    Ok(())
+}
+
+/// "2026-10-15 09:30:00.123" in local time
+fn timestamp(transition: &TransitionInfo) -> String {
+    match chrono::DateTime::from_timestamp_micros(transition.usec as i64) {
+        Some(at) => at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S%.3f")
+            .to_string(),
+        None => transition.usec.to_string(),
+    }
+}
+
+/// The array `systemd-analyze plot --json=` prints: one object per unit
+/// with its timestamps in microseconds, in systemd's field order
+fn plot_json(boot: &BootTimesInfo, pretty: bool) -> String {
+    let rows: Vec<String> = boot
+        .units
+        .iter()
+        .map(|unit| {
+            let fields = [
+                ("name", json_string(&unit.name)),
+                ("activated", unit.activated.to_string()),
+                ("activating", unit.activating.to_string()),
+                ("time", unit.time.to_string()),
+                ("deactivated", unit.deactivated.to_string()),
+                ("deactivating", unit.deactivating.to_string()),
+            ];
+            let fields = fields.iter().map(|(key, value)| {
+                if pretty {
+                    format!("\t\t\"{}\" : {}", key, value)
+                } else {
+                    format!("\"{}\":{}", key, value)
+                }
+            });
+            if pretty {
+                format!("\t{{\n{}\n\t}}", fields.collect::<Vec<_>>().join(",\n"))
+            } else {
+                format!("{{{}}}", fields.collect::<Vec<_>>().join(","))
+            }
+        })
+        .collect();
+    if !pretty {
+        format!("[{}]", rows.join(","))
+    } else if rows.is_empty() {
+        "[]".to_string()
+    } else {
+        format!("[\n{}\n]", rows.join(",\n"))
+    }
+}
+
+fn json_string(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn seconds(usec: u64) -> String {
+    format!("{:.3}s", usec as f64 / 1_000_000.0)
+}
+
+/// A timestamp in milliseconds, blank where the unit never got there
+fn millis(usec: u64) -> String {
+    if usec == 0 {
+        String::new()
+    } else {
+        format!("{:.3}ms", usec as f64 / 1000.0)
+    }
+}
+
+/// One row per step with its share of the total, then notes on what the
+/// timeline can not show
+fn print_spawn_profile(profile: &SpawnProfileInfo) {
+    let total = profile.total_usec.max(1) as f64;
+    println!("{:<12} {:>10} {:>6}", "STEP", "TIME", "SHARE");
+    for (step, usec) in &profile.steps {
+        let share = *usec as f64 / total;
+        println!(
+            "{:<12} {:>8.3}ms {:>5.1}% {}",
+            step,
+            *usec as f64 / 1000.0,
+            share * 100.0,
+            "█".repeat((share * BAR_WIDTH).round() as usize)
+        );
+    }
+    println!();
+    println!(
+        "→ {} spawned in {:.3}ms",
+        profile.name,
+        profile.total_usec as f64 / 1000.0
+    );
+    if profile.via_executor {
+        println!("  The steps after exec ran in sysd-executor, next to the manager's own.");
+    }
+    if !profile.complete {
+        println!("  The service had not exec'd yet, later steps are missing.");
+    }
+}
+
+fn print_overview(reports: &[(String, SecurityReport)]) {
+    println!("{:<40} {:>8} PREDICATE", "UNIT", "EXPOSURE");
+    for (name, report) in reports {
+        println!("{:<40} {:>8.1} {}", name, report.exposure, report.level());
+    }
+}
+
+/// Exposed directives first, the costliest on top, each with its fix
+fn print_report(name: &str, report: &SecurityReport) {
+    let mut checks: Vec<_> = report.checks.iter().collect();
+    checks.sort_by(|a, b| b.exposure.total_cmp(&a.exposure));
+
+    println!("  {:<26} {:<68} EXPOSURE", "NAME", "DESCRIPTION");
+    for check in checks {
+        match check.recommendation {
+            Some(fix) => {
+                println!(
+                    "✗ {:<26} {:<68} {:>8.1}",
+                    check.directive, check.description, check.exposure
+                );
+                println!("  {:<26} → {}", "", fix);
+            }
+            None => println!("✓ {:<26} {}", check.directive, check.description),
+        }
+    }
+    println!();
+    println!(
+        "→ Overall exposure level for {}: {:.1} {}",
+        name,
+        report.exposure,
+        report.level()
+    );
+}
@@ -0,0 +1,86 @@
+//! `sysd cgls`: the cgroup hierarchy with its processes, like systemd-cgls
+//!
+//! Useful for checking which slice, scope or service a process ended up in.
+//! Cgroups without processes anywhere below them are left out unless --all
+//! is given.
+
+use std::path::Path;
+
+use sysd::cgroups::{self, CgroupNode};
+
+#[derive(clap::Args)]
+pub(super) struct CglsArgs {
+    /// Show only this unit or cgroup path (e.g. nginx.service, /system.slice)
+    cgroup: Option<String>,
+    /// Also show empty cgroups
+    #[arg(long, short = 'a')]
+    all: bool,
+}
+
+type CglsResult = Result<(), Box<dyn std::error::Error>>;
+
+pub(super) fn run_cgls_command(args: CglsArgs) -> CglsResult {
+    let tree = cgroups::read_tree()?;
+    let (node, heading) = match &args.cgroup {
+        None => (&tree, "Control group /:".to_string()),
+        Some(wanted) => {
+            let node = find(&tree, &tree.path, wanted)
+                .ok_or_else(|| format!("no cgroup for {}", wanted))?;
+            let relative = node.path.strip_prefix(&tree.path).unwrap_or(&node.path);
+            let heading = match node.unit() {
+                Some(unit) => format!("Unit {} (/{}):", unit, relative.display()),
+                None => format!("Control group /{}:", relative.display()),
+            };
+            (node, heading)
+        }
+    };
+
+    let mut out = format!("{}\n{}\n", heading, node.name);
+    render_children(node, "", args.all, &mut out);
+    print!("{}", out);
+    Ok(())
+}
+
+/// Cgroup named `wanted`, either a unit name or a path below the root
+fn find<'a>(node: &'a CgroupNode, root: &Path, wanted: &str) -> Option<&'a CgroupNode> {
+    let path = wanted.trim_start_matches('/');
+    if node.name == wanted
+        || node
+            .path
+            .strip_prefix(root)
+            .is_ok_and(|rel| rel == Path::new(path))
+    {
+        return Some(node);
+    }
+    node.children
+        .iter()
+        .find_map(|child| find(child, root, wanted))
+}
+
+/// Processes of `node`, then its child cgroups, drawn with tree lines
+fn render_children(node: &CgroupNode, prefix: &str, all: bool, out: &mut String) {
+    let children: Vec<&CgroupNode> = node
+        .children
+        .iter()
+        .filter(|child| all || child.is_populated())
+        .collect();
+    let entries = node.processes.len() + children.len();
+
+    for (i, process) in node.processes.iter().enumerate() {
+        let branch = if i + 1 == entries { "└─" } else { "├─" };
+        out.push_str(&format!(
+            "{}{}{} {}\n",
+            prefix, branch, process.pid, process.comm
+        ));
+    }
+    for (i, child) in children.into_iter().enumerate() {
+        let last = node.processes.len() + i + 1 == entries;
+        let (branch, indent) = if last {
+            ("└─", "  ")
+        } else {
+            ("├─", "│ ")
+        };
+        out.push_str(&format!("{}{}{}\n", prefix, branch, child.name));
+        render_children(child, &format!("{}{}", prefix, indent), all, out);
+    }
+}
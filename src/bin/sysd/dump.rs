@@ -0,0 +1,21 @@
+//! `sysd dump`: the running manager's internal state, like
+//! `systemd-analyze dump`
+//!
+//! For debugging units stuck in a state: what each unit is doing, the file
+//! descriptors, timers and watchdogs the manager holds for it, and the jobs
+//! in flight. The text is meant to be read, not parsed.
+
+use peercred_ipc::Client;
+use sysd::protocol::{socket_path, Request, Response};
+
+pub(super) fn run_dump_command(user_mode: bool) -> Result<(), String> {
+    match Client::call(&socket_path(user_mode), &Request::Dump) {
+        Ok(Response::Dump(dump)) => {
+            print!("{}", dump);
+            Ok(())
+        }
+        Ok(Response::Error(message)) => Err(message),
+        Ok(other) => Err(format!("unexpected response: {:?}", other)),
+        Err(e) => Err(format!("cannot reach the manager: {}", e)),
+    }
+}
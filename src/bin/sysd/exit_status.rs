@@ -0,0 +1,32 @@
+//! `sysd exit-status`: what an exit status means, like
+//! `systemd-analyze exit-status`
+//!
+//! A unit that failed with "Exit code 226/NAMESPACE" died while sysd-executor
+//! set up its mount namespace; the table here gives the other numbers the
+//! same way, and looks statuses up by name as well.
+
+use sysd::executor::{find_exit_status, ExitStatusEntry, EXIT_STATUSES};
+
+#[derive(clap::Args)]
+pub(super) struct ExitStatusArgs {
+    /// Statuses to explain, by number or name (e.g. 203, NAMESPACE); all if none
+    statuses: Vec<String>,
+}
+
+pub(super) fn run_exit_status_command(args: ExitStatusArgs) -> Result<(), String> {
+    let entries: Vec<&ExitStatusEntry> = if args.statuses.is_empty() {
+        EXIT_STATUSES.iter().collect()
+    } else {
+        args.statuses
+            .iter()
+            .map(|status| {
+                find_exit_status(status).ok_or_else(|| format!("unknown exit status {}", status))
+            })
+            .collect::<Result<_, _>>()?
+    };
+    println!("{:<24} {:>6} CLASS", "NAME", "STATUS");
+    for entry in entries {
+        println!("{:<24} {:>6} {}", entry.name, entry.code, entry.class);
+    }
+    Ok(())
+}
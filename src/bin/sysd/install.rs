@@ -0,0 +1,112 @@
+//! `sysd enable`, `preset-all`, `set-default`, ...: unit file operations
+//! without a running manager, like systemctl's offline mode
+//!
+//! Combined with --root they provision an image from outside, e.g.
+//! `sysd --root /mnt enable sshd` in an installer or image builder. Only
+//! links below the unit directories change; nothing is started or stopped.
+
+use std::path::{Path, PathBuf};
+
+use sysd::manager::{enablement_succeeds, Manager};
+use sysd::units::PresetAction;
+
+#[derive(clap::Subcommand)]
+pub(super) enum InstallCommand {
+    /// Create the [Install] links of units (offline, honours --root)
+    Enable {
+        #[arg(required = true)]
+        units: Vec<String>,
+    },
+    /// Remove the [Install] links of units (offline, honours --root)
+    Disable {
+        #[arg(required = true)]
+        units: Vec<String>,
+    },
+    /// Print whether a unit is enabled (exit 1 unless it is)
+    IsEnabled { unit: String },
+    /// Enable or disable units as the *.preset files say
+    Preset {
+        #[arg(required = true)]
+        units: Vec<String>,
+    },
+    /// Apply the *.preset files to every installed unit file
+    PresetAll,
+    /// Make a target the one booted into (default.target)
+    SetDefault { target: String },
+    /// Print the target default.target points at
+    GetDefault,
+}
+
+type InstallResult = Result<(), Box<dyn std::error::Error>>;
+
+pub(super) async fn run_install_command(command: InstallCommand, user_mode: bool) -> InstallResult {
+    let mut manager = if user_mode {
+        Manager::new_user()
+    } else {
+        Manager::new()
+    };
+
+    match command {
+        // Braces name instances (`getty@{tty1,tty2}`); nothing is loaded
+        // yet for wildcards to match
+        InstallCommand::Enable { units } => {
+            for unit in manager.expand_unit_patterns(&units) {
+                print_created(&manager.enable(&unit).await?);
+            }
+        }
+        InstallCommand::Disable { units } => {
+            for unit in manager.expand_unit_patterns(&units) {
+                print_removed(&manager.disable(&unit).await?);
+            }
+        }
+        InstallCommand::IsEnabled { unit } => {
+            let state = manager.is_enabled(&unit).await?;
+            println!("{}", state);
+            if !enablement_succeeds(&state) {
+                std::process::exit(1);
+            }
+        }
+        InstallCommand::Preset { units } => {
+            for unit in &units {
+                let (action, links) = manager.preset(unit).await?;
+                print_links(action, &links);
+            }
+        }
+        InstallCommand::PresetAll => {
+            for (_, action, links) in manager.preset_all().await {
+                print_links(action, &links);
+            }
+        }
+        InstallCommand::SetDefault { target } => {
+            print_created(&[manager.set_default(&target).await?]);
+        }
+        InstallCommand::GetDefault => println!("{}", manager.get_default_target()?),
+    }
+    Ok(())
+}
+
+fn print_links(action: PresetAction, links: &[PathBuf]) {
+    match action {
+        PresetAction::Enable => print_created(links),
+        PresetAction::Disable => print_removed(links),
+        PresetAction::Ignore => {}
+    }
+}
+
+fn print_created(links: &[PathBuf]) {
+    for link in links {
+        let target = std::fs::read_link(link).unwrap_or_default();
+        println!("Created symlink {} → {}.", in_image(link), target.display());
+    }
+}
+
+fn print_removed(links: &[PathBuf]) {
+    for link in links {
+        println!("Removed \"{}\".", in_image(link));
+    }
+}
+
+/// Links are reported as the booted image will see them
+fn in_image(link: &Path) -> String {
+    sysd::root::unprefixed(link).display().to_string()
+}
@@ -0,0 +1,206 @@
+//! `sysd login`: loginctl-style session management
+//!
+//! Session, user and seat verbs are calls to org.freedesktop.login1
+//! (systemd-logind). Linger markers are files in /var/lib/sysd/linger,
+//! which the manager checks before tearing down a user's manager on logout.
+
+use std::collections::HashMap;
+
+use sysd::state_dir::StateDirs;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue};
+
+const LOGIN1_NAME: &str = "org.freedesktop.login1";
+const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+const LOGIN1_MANAGER: &str = "org.freedesktop.login1.Manager";
+
+type LoginResult = Result<(), Box<dyn std::error::Error>>;
+
+#[derive(clap::Subcommand)]
+pub(super) enum LoginCommand {
+    /// List current sessions
+    ListSessions,
+    /// List logged in users
+    ListUsers,
+    /// List seats
+    ListSeats,
+    /// Show properties of a session
+    ShowSession {
+        /// Session ID
+        id: String,
+    },
+    /// Terminate a session
+    TerminateSession {
+        /// Session ID
+        id: String,
+    },
+    /// Terminate all sessions of a user
+    TerminateUser {
+        /// User name or UID
+        user: String,
+    },
+    /// Lock a session (all sessions if no ID is given)
+    LockSession {
+        /// Session ID
+        id: Option<String>,
+    },
+    /// Keep the user's service manager running after logout
+    EnableLinger {
+        /// User names (default: the calling user)
+        users: Vec<String>,
+    },
+    /// Stop the user's service manager on logout again
+    DisableLinger {
+        /// User names (default: the calling user)
+        users: Vec<String>,
+    },
+}
+
+pub(super) async fn run_login_command(command: LoginCommand) -> LoginResult {
+    match command {
+        LoginCommand::EnableLinger { users } => return set_linger(&users, true),
+        LoginCommand::DisableLinger { users } => return set_linger(&users, false),
+        _ => {}
+    }
+
+    let conn = zbus::Connection::system().await?;
+    match command {
+        LoginCommand::ListSessions => {
+            let sessions: Vec<(String, u32, String, String, OwnedObjectPath)> =
+                call(&conn, "ListSessions", &()).await?;
+            println!(
+                "{:>10} {:>6} {:<16} {:<8}",
+                "SESSION", "UID", "USER", "SEAT"
+            );
+            for (id, uid, user, seat, _) in &sessions {
+                println!("{:>10} {:>6} {:<16} {:<8}", id, uid, user, seat);
+            }
+            println!("\n{} sessions listed.", sessions.len());
+        }
+        LoginCommand::ListUsers => {
+            let users: Vec<(u32, String, OwnedObjectPath)> = call(&conn, "ListUsers", &()).await?;
+            println!("{:>6} {:<16} {:<8}", "UID", "USER", "LINGER");
+            for (uid, user, _) in &users {
+                let linger = sysd::manager::Manager::is_lingering(user);
+                println!("{:>6} {:<16} {:<8}", uid, user, yes_no(linger));
+            }
+            println!("\n{} users listed.", users.len());
+        }
+        LoginCommand::ListSeats => {
+            let seats: Vec<(String, OwnedObjectPath)> = call(&conn, "ListSeats", &()).await?;
+            println!("SEAT");
+            for (seat, _) in &seats {
+                println!("{}", seat);
+            }
+            println!("\n{} seats listed.", seats.len());
+        }
+        LoginCommand::ShowSession { id } => {
+            let path: OwnedObjectPath = call(&conn, "GetSession", &(id.as_str(),)).await?;
+            let reply = conn
+                .call_method(
+                    Some(LOGIN1_NAME),
+                    path.as_str(),
+                    Some("org.freedesktop.DBus.Properties"),
+                    "GetAll",
+                    &("org.freedesktop.login1.Session",),
+                )
+                .await?;
+            let properties: HashMap<String, OwnedValue> = reply.body().deserialize()?;
+            let mut properties: Vec<_> = properties.into_iter().collect();
+            properties.sort_by(|a, b| a.0.cmp(&b.0));
+            for (name, value) in properties {
+                println!("{}={}", name, &*value);
+            }
+        }
+        LoginCommand::TerminateSession { id } => {
+            call::<()>(&conn, "TerminateSession", &(id.as_str(),)).await?
+        }
+        LoginCommand::TerminateUser { user } => {
+            call::<()>(&conn, "TerminateUser", &(resolve_uid(&user)?,)).await?
+        }
+        LoginCommand::LockSession { id: Some(id) } => {
+            call::<()>(&conn, "LockSession", &(id.as_str(),)).await?
+        }
+        LoginCommand::LockSession { id: None } => call::<()>(&conn, "LockSessions", &()).await?,
+        LoginCommand::EnableLinger { .. } | LoginCommand::DisableLinger { .. } => unreachable!(),
+    }
+    Ok(())
+}
+
+async fn call<R>(
+    conn: &zbus::Connection,
+    method: &str,
+    body: &(impl serde::Serialize + zbus::zvariant::DynamicType),
+) -> Result<R, Box<dyn std::error::Error>>
+where
+    R: for<'d> zbus::zvariant::DynamicDeserialize<'d>,
+{
+    let reply = conn
+        .call_method(
+            Some(LOGIN1_NAME),
+            LOGIN1_PATH,
+            Some(LOGIN1_MANAGER),
+            method,
+            body,
+        )
+        .await?;
+    Ok(reply.body().deserialize()?)
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+/// UID of a user given by name or number
+fn resolve_uid(user: &str) -> Result<u32, String> {
+    if let Ok(uid) = user.parse::<u32>() {
+        return Ok(uid);
+    }
+    nix::unistd::User::from_name(user)
+        .ok()
+        .flatten()
+        .map(|user| user.uid.as_raw())
+        .ok_or_else(|| format!("unknown user: {}", user))
+}
+
+/// Write or remove linger markers for `users` (the calling user if empty)
+fn set_linger(users: &[String], enable: bool) -> LoginResult {
+    let users = match users {
+        [] => vec![current_user_name()?],
+        users => users.to_vec(),
+    };
+    let dir = &StateDirs::system().linger();
+    for user in users {
+        let name = resolve_user_name(&user)?;
+        let marker = dir.join(&name);
+        if enable {
+            std::fs::create_dir_all(dir)?;
+            std::fs::write(&marker, "")?;
+        } else if let Err(e) = std::fs::remove_file(&marker) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn current_user_name() -> Result<String, String> {
+    resolve_user_name(&nix::unistd::getuid().as_raw().to_string())
+}
+
+/// Account name of a user given by name or number
+fn resolve_user_name(user: &str) -> Result<String, String> {
+    let found = match user.parse::<u32>() {
+        Ok(uid) => nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid)),
+        Err(_) => nix::unistd::User::from_name(user),
+    };
+    found
+        .ok()
+        .flatten()
+        .map(|user| user.name)
+        .ok_or_else(|| format!("unknown user: {}", user))
+}
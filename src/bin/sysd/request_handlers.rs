@@ -1,13 +1,31 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
 use log::info;
 use peercred_ipc::{CallerInfo, Connection};
 
 use super::SharedManager;
-use sysd::protocol::{Request, Response, UnitInfo};
+use sysd::manager::{
+    CleanWhat, Fault, KillWhom, Manager, SleepMode, StateView, Transition, UnitProperty,
+    UnitSnapshot,
+};
+use sysd::pid1::ShutdownType;
+use sysd::protocol::{
+    BootTimesInfo, Request, Response, SocketInfo, SpawnProfileInfo, StartGroupInfo, TransitionInfo,
+    UnitInfo, UnitTimesInfo,
+};
+
+/// StartMany batches by id, kept until their final progress was read
+static START_GROUPS: Mutex<BTreeMap<u32, StartGroupInfo>> = Mutex::new(BTreeMap::new());
+static NEXT_START_GROUP: AtomicU32 = AtomicU32::new(1);
 
 pub(super) async fn handle_connection(
     mut conn: Connection,
     caller: CallerInfo,
     manager: SharedManager,
+    states: StateView,
 ) {
     info!(
         "connection from uid={} pid={} exe={:?}",
@@ -21,36 +39,134 @@ pub(super) async fn handle_connection(
             return;
         }
     };
-    let response = handle_request(request, &manager).await;
+    let response = handle_request(request, &manager, &states).await;
     if let Err(e) = conn.write(&response).await {
         log::error!("write error: {}", e);
     }
 }
 
-async fn handle_request(request: Request, manager: &SharedManager) -> Response {
+async fn handle_request(request: Request, manager: &SharedManager, states: &StateView) -> Response {
+    let publish = changes_unit_states(&request);
+    let response = dispatch_request(request, manager, states).await;
+    if publish {
+        manager.read().await.publish_states();
+    }
+    response
+}
+
+/// Requests after which status readers should see fresh unit states right away
+fn changes_unit_states(request: &Request) -> bool {
+    matches!(
+        request,
+        Request::Start { .. }
+            | Request::StartAndWait { .. }
+            | Request::Stop { .. }
+            | Request::StopAndWait { .. }
+            | Request::Restart { .. }
+            | Request::RestartAndWait { .. }
+            | Request::RollingRestart { .. }
+            | Request::RollingRestartAndWait { .. }
+            | Request::Boot { dry_run: false }
+            | Request::ReloadUnitFiles
+            | Request::SyncUnits
+            | Request::SwitchTarget { .. }
+            | Request::ResetFailed
+            | Request::ResetFailedUnit { .. }
+            | Request::Sleep { .. }
+    )
+}
+
+async fn dispatch_request(
+    request: Request,
+    manager: &SharedManager,
+    states: &StateView,
+) -> Response {
     if let Some(response) = special_request_response(&request, manager).await {
         return response;
     }
     match request {
-        Request::List { user: _, unit_type } => list_response(manager, unit_type).await,
+        Request::List {
+            user: _,
+            unit_type,
+            patterns,
+        } => list_response(manager, states, unit_type, &patterns).await,
         Request::Start { name } => start_response(manager, &name).await,
-        Request::StartAndWait { name } => start_and_wait_response(manager, &name).await,
+        Request::StartAndWait { name } => start_and_wait_response(manager, states, &name).await,
         Request::Stop { name } => stop_response(manager, &name).await,
+        Request::StopAndWait { name } => stop_and_wait_response(manager, states, &name).await,
         Request::Restart { name } => restart_response(manager, &name).await,
+        Request::RestartAndWait { name } => restart_and_wait_response(manager, states, &name).await,
+        Request::RollingRestart { name } => rolling_restart_response(manager, &name).await,
+        Request::RollingRestartAndWait { name } => {
+            rolling_restart_and_wait_response(manager, states, &name).await
+        }
         Request::Enable { name } => enable_response(manager, &name).await,
         Request::Disable { name } => disable_response(manager, &name).await,
         Request::IsEnabled { name } => is_enabled_response(manager, &name).await,
-        Request::Status { name } => status_response(manager, &name).await,
+        Request::Status { name } => status_response(manager, states, &name),
         Request::Deps { name } => deps_response(manager, &name).await,
         Request::GetBootTarget => boot_target_response(manager).await,
+        Request::SetDefaultTarget { target } => set_default_target_response(manager, &target).await,
         Request::Boot { dry_run } => boot_response(manager, dry_run).await,
         Request::ReloadUnitFiles => reload_units_response(manager).await,
         Request::SyncUnits => sync_units_response(manager).await,
         Request::SwitchTarget { target } => switch_target_response(manager, &target).await,
-        Request::IsActive { name } => is_active_response(manager, &name).await,
+        Request::Snapshot { name } => {
+            let name = manager.write().await.create_snapshot(name.as_deref());
+            Response::UnitNames(vec![name])
+        }
+        Request::DeleteSnapshot { name } => {
+            to_ok_response(manager.write().await.delete_snapshot(&name))
+        }
+        Request::IsActive { name } => is_active_response(states, &name),
+        Request::ListSockets => list_sockets_response(manager).await,
+        Request::Kill { name, whom, signal } => kill_response(manager, &name, &whom, signal).await,
+        Request::Clean { name, what } => clean_response(manager, &name, &what).await,
+        Request::Sleep { mode } => sleep_response(manager, &mode).await,
+        Request::ScheduleShutdown {
+            kind,
+            usec,
+            message,
+        } => schedule_shutdown_response(manager, &kind, usec, message).await,
+        Request::CancelShutdown => {
+            manager.write().await.cancel_scheduled_shutdown();
+            Response::Ok
+        }
+        Request::ShowShutdown => scheduled_shutdown_response(manager).await,
+        Request::SetProperty {
+            name,
+            assignments,
+            runtime,
+        } => set_property_response(manager, &name, &assignments, runtime).await,
+        Request::SpawnProfile { name } => spawn_profile_response(manager, &name).await,
+        Request::StartMany { names } => start_many_response(manager, &names).await,
+        Request::ExpandUnits { patterns, states } => {
+            Response::UnitNames(manager.read().await.units_by_patterns(&patterns, &states))
+        }
+        Request::ResetFailedUnit { name } => {
+            manager.write().await.reset_failed_unit(&name);
+            Response::Ok
+        }
+        Request::StartGroupStatus { id } => start_group_status_response(id),
+        Request::Features => features_response(manager).await,
+        Request::Dump => Response::Dump(manager.read().await.dump()),
+        Request::Why { name } => match manager.read().await.why(&name) {
+            Ok(why) => Response::Why(why),
+            Err(e) => Response::Error(e.to_string()),
+        },
+        Request::StateHistory { name } => match manager.read().await.state_history(&name) {
+            Ok(history) => Response::StateHistory(history.iter().map(transition_info).collect()),
+            Err(e) => Response::Error(e.to_string()),
+        },
+        Request::BootTimes => boot_times_response(manager).await,
+        Request::InjectFault { name, faults } => {
+            inject_fault_response(manager, &name, &faults).await
+        }
         Request::Ping
         | Request::ImportEnvironment { .. }
         | Request::UnsetEnvironment { .. }
+        | Request::SetEnvironment { .. }
+        | Request::ShowEnvironment
         | Request::ResetFailed => unreachable!(),
     }
 }
@@ -68,6 +184,14 @@ async fn special_request_response(request: &Request, manager: &SharedManager) ->
             mgr.unset_environment(names);
             Some(Response::Ok)
         }
+        Request::SetEnvironment { assignments } => {
+            let mut mgr = manager.write().await;
+            Some(to_ok_response(mgr.set_environment(assignments)))
+        }
+        Request::ShowEnvironment => {
+            let mgr = manager.read().await;
+            Some(Response::Environment(mgr.show_environment()))
+        }
         Request::ResetFailed => {
             let mut mgr = manager.write().await;
             mgr.reset_failed();
@@ -77,36 +201,243 @@ async fn special_request_response(request: &Request, manager: &SharedManager) ->
     }
 }
 
-async fn list_response(manager: &SharedManager, unit_type: Option<String>) -> Response {
-    let mgr = manager.read().await;
-    let units: Vec<UnitInfo> = mgr
-        .list_units()
+async fn list_response(
+    manager: &SharedManager,
+    states: &StateView,
+    unit_type: Option<String>,
+    patterns: &[String],
+) -> Response {
+    let matching = if patterns.is_empty() {
+        None
+    } else {
+        Some(manager.read().await.expand_unit_patterns(patterns))
+    };
+    let units: Vec<UnitInfo> = states
+        .list()
         .into_iter()
-        .filter(|(_, unit, _)| {
+        .filter(|(name, _)| matching.as_ref().map_or(true, |names| names.contains(name)))
+        .filter(|(_, unit)| {
             unit_type
                 .as_ref()
-                .map_or(true, |unit_type| unit.unit_type() == unit_type.as_str())
+                .map_or(true, |unit_type| unit.unit_type == unit_type.as_str())
         })
-        .map(|(name, unit, state)| UnitInfo {
-            name: name.clone(),
-            unit_type: unit.unit_type().into(),
-            state: state
-                .map(|state| format!("{:?}", state.active))
-                .unwrap_or_else(|| "inactive".into()),
-            description: unit.unit_section().description.clone(),
+        .map(|(name, unit)| UnitInfo {
+            name,
+            unit_type: unit.unit_type.into(),
+            state: format!("{:?}", unit.active),
+            description: unit.description,
+            need_daemon_reload: unit.need_daemon_reload,
+            load_state: unit.load_state.into(),
+            unit_file_state: None,
+            fragment_path: unit.fragment_path.map(|path| path.display().to_string()),
+            documentation: unit.documentation,
+            names: unit.names,
+            condition_failure: unit.condition_failure,
+            failed_dependency: unit.failed_dependency,
+            ip_ingress_bytes: None,
+            ip_egress_bytes: None,
+            history: Vec::new(),
         })
         .collect();
     Response::Units(units)
 }
 
+async fn list_sockets_response(manager: &SharedManager) -> Response {
+    let sockets = manager
+        .read()
+        .await
+        .list_sockets()
+        .into_iter()
+        .map(|listing| SocketInfo {
+            listen: listing.listen,
+            socket_type: listing.socket_type.into(),
+            unit: listing.unit,
+            activates: listing.activates,
+        })
+        .collect();
+    Response::Sockets(sockets)
+}
+
 async fn start_response(manager: &SharedManager, name: &str) -> Response {
     let mut mgr = manager.write().await;
     to_ok_response(mgr.start(name).await)
 }
 
+async fn kill_response(manager: &SharedManager, name: &str, whom: &str, signal: i32) -> Response {
+    let Some(whom) = KillWhom::parse(whom) else {
+        return Response::Error(format!("invalid --kill-whom: {}", whom));
+    };
+    to_ok_response(manager.read().await.kill_unit(name, whom, signal))
+}
+
+async fn inject_fault_response(manager: &SharedManager, name: &str, faults: &[String]) -> Response {
+    let mut parsed = Vec::new();
+    for fault in faults {
+        match Fault::parse(fault) {
+            Some(fault) => parsed.push(fault),
+            None => return Response::Error(format!("invalid fault: {}", fault)),
+        }
+    }
+    let mut mgr = manager.write().await;
+    to_ok_response(
+        parsed
+            .into_iter()
+            .try_for_each(|fault| mgr.inject_fault(name, fault)),
+    )
+}
+
+async fn clean_response(manager: &SharedManager, name: &str, what: &[String]) -> Response {
+    let what = match CleanWhat::parse_list(what) {
+        Ok(what) => what,
+        Err(e) => return Response::Error(e),
+    };
+    to_ok_response(manager.write().await.clean_unit(name, &what))
+}
+
+async fn start_many_response(manager: &SharedManager, patterns: &[String]) -> Response {
+    let names = manager.read().await.expand_unit_patterns(patterns);
+    if names.is_empty() {
+        return Response::Error(format!("No units match {}", patterns.join(" ")));
+    }
+    let id = NEXT_START_GROUP.fetch_add(1, Ordering::Relaxed);
+    let group = StartGroupInfo {
+        total: names.len() as u32,
+        finished: Vec::new(),
+    };
+    START_GROUPS.lock().unwrap().insert(id, group);
+    let mut results = Manager::start_many(Arc::clone(manager), names);
+    tokio::spawn(async move {
+        while let Some(started) = results.recv().await {
+            let error = started.result.err().map(|e| e.to_string());
+            if let Some(group) = START_GROUPS.lock().unwrap().get_mut(&id) {
+                group.finished.push((started.name, error));
+            }
+        }
+    });
+    Response::StartGroup(id)
+}
+
+fn start_group_status_response(id: u32) -> Response {
+    let mut groups = START_GROUPS.lock().unwrap();
+    let Some(group) = groups.get(&id).cloned() else {
+        return Response::Error(format!("No such start group: {}", id));
+    };
+    if group.finished.len() as u32 == group.total {
+        groups.remove(&id);
+    }
+    Response::StartGroupProgress(group)
+}
+
+async fn features_response(manager: &SharedManager) -> Response {
+    let mgr = manager.read().await;
+    let features = mgr
+        .features()
+        .iter()
+        .map(|(feature, missing)| (feature.name().to_string(), missing.map(str::to_string)))
+        .collect();
+    Response::Features(features)
+}
+
+async fn spawn_profile_response(manager: &SharedManager, name: &str) -> Response {
+    let (name, profile) = match manager.write().await.spawn_profile(name) {
+        Ok(profile) => profile,
+        Err(e) => return Response::Error(e.to_string()),
+    };
+    Response::SpawnProfile(SpawnProfileInfo {
+        name,
+        steps: profile
+            .steps
+            .iter()
+            .map(|(step, took)| (step.to_string(), took.as_micros() as u64))
+            .collect(),
+        total_usec: profile.total.as_micros() as u64,
+        via_executor: profile.via_executor,
+        complete: profile.complete,
+    })
+}
+
+async fn boot_times_response(manager: &SharedManager) -> Response {
+    let mgr = manager.read().await;
+    let boot = mgr.boot_times();
+    let usec = |at: Option<std::time::Duration>| at.map_or(0, |at| at.as_micros() as u64);
+    let units = mgr
+        .unit_times()
+        .into_iter()
+        .map(|unit| UnitTimesInfo {
+            activating: usec(unit.activating),
+            activated: usec(unit.activated),
+            time: unit.time().as_micros() as u64,
+            deactivating: usec(unit.deactivating),
+            deactivated: usec(unit.deactivated),
+            name: unit.name,
+        })
+        .collect();
+    Response::BootTimes(BootTimesInfo {
+        firmware_usec: boot.firmware.map(|took| took.as_micros() as u64),
+        loader_usec: boot.loader.map(|took| took.as_micros() as u64),
+        userspace_usec: boot.userspace.as_micros() as u64,
+        finish_usec: boot.finish.map(|at| at.as_micros() as u64),
+        target: boot.target,
+        units,
+    })
+}
+
+async fn set_property_response(
+    manager: &SharedManager,
+    name: &str,
+    assignments: &[String],
+    runtime: bool,
+) -> Response {
+    let properties: Result<Vec<UnitProperty>, _> = assignments
+        .iter()
+        .map(|assignment| UnitProperty::parse(assignment))
+        .collect();
+    let properties = match properties {
+        Ok(properties) => properties,
+        Err(e) => return Response::Error(e.to_string()),
+    };
+    to_ok_response(
+        manager
+            .write()
+            .await
+            .set_unit_properties(name, &properties, runtime)
+            .await,
+    )
+}
+
+async fn sleep_response(manager: &SharedManager, mode: &str) -> Response {
+    let Some(mode) = SleepMode::parse(mode) else {
+        return Response::Error(format!("invalid sleep mode: {}", mode));
+    };
+    let result = Manager::sleep(manager, mode).await;
+    manager.write().await.publish_states();
+    to_ok_response(result)
+}
+
+async fn schedule_shutdown_response(
+    manager: &SharedManager,
+    kind: &str,
+    usec: u64,
+    message: Option<String>,
+) -> Response {
+    let Some(kind) = ShutdownType::from_name(kind) else {
+        return Response::Error(format!("invalid shutdown type: {}", kind));
+    };
+    let at = UNIX_EPOCH + Duration::from_micros(usec);
+    to_ok_response(manager.write().await.schedule_shutdown(kind, at, message))
+}
+
+async fn scheduled_shutdown_response(manager: &SharedManager) -> Response {
+    let scheduled = manager.read().await.scheduled_shutdown();
+    Response::ScheduledShutdown(scheduled.map(|(kind, at)| {
+        let since_epoch = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        (kind.as_str().to_string(), since_epoch.as_micros() as u64)
+    }))
+}
+
 async fn stop_response(manager: &SharedManager, name: &str) -> Response {
     let mut mgr = manager.write().await;
-    to_ok_response(mgr.stop(name).await)
+    to_ok_response(mgr.enqueue_stop(name).await)
 }
 
 async fn restart_response(manager: &SharedManager, name: &str) -> Response {
@@ -114,28 +445,115 @@ async fn restart_response(manager: &SharedManager, name: &str) -> Response {
     to_ok_response(mgr.restart(name).await)
 }
 
-async fn start_and_wait_response(manager: &SharedManager, name: &str) -> Response {
-    {
+async fn start_and_wait_response(
+    manager: &SharedManager,
+    states: &StateView,
+    name: &str,
+) -> Response {
+    // A target has no process: start its members and wait until it is reached
+    let result = {
         let mut mgr = manager.write().await;
-        if let Err(error) = mgr.start(name).await {
-            return Response::Error(error.to_string());
-        }
-    }
-    loop {
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-        let mgr = manager.read().await;
-        let Some(state) = mgr.status(name) else {
-            return Response::Error(format!("Unit {} not found", name));
+        let result = if name.ends_with(".target") {
+            mgr.start_with_deps(name).await.map(|_| ())
+        } else {
+            mgr.start(name).await
         };
-        use sysd::manager::ActiveState;
-        match state.active {
-            ActiveState::Inactive => return Response::Ok,
-            ActiveState::Failed => {
-                let exit_code = state.exit_code.unwrap_or(0);
-                return Response::Error(format!("Unit failed with exit code {}", exit_code));
-            }
-            _ => continue,
+        mgr.publish_states();
+        result
+    };
+    match result {
+        Ok(()) => wait_response(states, name, WaitFor::Started).await,
+        Err(error) => Response::Error(error.to_string()),
+    }
+}
+
+async fn stop_and_wait_response(
+    manager: &SharedManager,
+    states: &StateView,
+    name: &str,
+) -> Response {
+    let result = {
+        let mut mgr = manager.write().await;
+        let result = mgr.enqueue_stop(name).await;
+        mgr.publish_states();
+        result
+    };
+    match result {
+        Ok(()) => wait_response(states, name, WaitFor::Stopped).await,
+        Err(error) => Response::Error(error.to_string()),
+    }
+}
+
+async fn restart_and_wait_response(
+    manager: &SharedManager,
+    states: &StateView,
+    name: &str,
+) -> Response {
+    let result = {
+        let mut mgr = manager.write().await;
+        let result = mgr.restart(name).await;
+        mgr.publish_states();
+        result
+    };
+    match result {
+        Ok(()) => wait_response(states, name, WaitFor::Started).await,
+        Err(error) => Response::Error(error.to_string()),
+    }
+}
+
+async fn rolling_restart_response(manager: &SharedManager, name: &str) -> Response {
+    let mut mgr = manager.write().await;
+    to_ok_response(mgr.rolling_restart(name).await)
+}
+
+async fn rolling_restart_and_wait_response(
+    manager: &SharedManager,
+    states: &StateView,
+    name: &str,
+) -> Response {
+    let result = {
+        let mut mgr = manager.write().await;
+        let result = mgr.rolling_restart(name).await;
+        mgr.publish_states();
+        result
+    };
+    match result {
+        Ok(()) => wait_response(states, name, WaitFor::Started).await,
+        Err(error) => Response::Error(error.to_string()),
+    }
+}
+
+/// The end of a job a --wait request blocks for
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum WaitFor {
+    /// Active, or exited (oneshot) or failed
+    Started,
+    /// Inactive or failed
+    Stopped,
+}
+
+/// Answer once `name` reached the end of its job, following published states
+async fn wait_response(states: &StateView, name: &str, wait: WaitFor) -> Response {
+    use sysd::manager::ActiveState;
+    let done = |unit: &UnitSnapshot| match (wait, unit.active) {
+        (_, ActiveState::Inactive | ActiveState::Failed) => true,
+        (WaitFor::Started, ActiveState::Active) => true,
+        _ => false,
+    };
+    let Some(unit) = states.clone().wait_until(name, done).await else {
+        return Response::Error(format!("Unit {} not found", name));
+    };
+    let reason = match (unit.active, unit.failed_dependency) {
+        (ActiveState::Failed, _) => unit.error.unwrap_or_else(|| "failed".to_string()),
+        (_, Some(dependency)) if wait == WaitFor::Started => {
+            format!("dependency failed: {}", dependency)
         }
+        _ => return Response::Ok,
+    };
+    Response::UnitFailed {
+        name: name.to_string(),
+        reason,
+        exit_code: unit.exit_code,
     }
 }
 
@@ -165,7 +583,12 @@ async fn disable_response(manager: &SharedManager, name: &str) -> Response {
     }
 }
 
+/// Loaded units are answered under the read lock; only a unit that still has
+/// to be loaded from disk takes the write lock
 async fn is_enabled_response(manager: &SharedManager, name: &str) -> Response {
+    if let Some(state) = manager.read().await.loaded_enablement(name) {
+        return Response::EnabledState(state.to_string());
+    }
     let mut mgr = manager.write().await;
     match mgr.is_enabled(name).await {
         Ok(enabled_state) => Response::EnabledState(enabled_state),
@@ -173,16 +596,65 @@ async fn is_enabled_response(manager: &SharedManager, name: &str) -> Response {
     }
 }
 
-async fn status_response(manager: &SharedManager, name: &str) -> Response {
-    let mgr = manager.read().await;
-    match mgr.status(name) {
-        Some(svc_state) => Response::Status(UnitInfo {
-            name: name.to_string(),
-            unit_type: "service".into(),
-            state: format!("{:?}", svc_state.active),
-            description: None,
-        }),
-        None => Response::Error(format!("unit not found: {}", name)),
+/// Status comes from the published states; the unit file state and the
+/// history are only added when the manager is not busy, so status never
+/// waits on its lock
+fn status_response(manager: &SharedManager, states: &StateView, name: &str) -> Response {
+    let mgr = manager.try_read().ok();
+    let Some(unit) = states.get(name) else {
+        let load_state = mgr.as_ref().map(|mgr| mgr.load_state(name));
+        return match load_state {
+            Some(load_state @ ("masked" | "error")) => Response::Status(UnitInfo {
+                name: name.to_string(),
+                unit_type: String::new(),
+                state: "Inactive (dead)".into(),
+                description: None,
+                need_daemon_reload: false,
+                load_state: load_state.into(),
+                unit_file_state: mgr.and_then(|mgr| mgr.unit_file_state(name).map(String::from)),
+                fragment_path: None,
+                documentation: Vec::new(),
+                names: vec![name.to_string()],
+                condition_failure: None,
+                failed_dependency: None,
+                ip_ingress_bytes: None,
+                ip_egress_bytes: None,
+                history: Vec::new(),
+            }),
+            _ => Response::Error(format!("unit not found: {}", name)),
+        };
+    };
+    let ip_counters = mgr.as_ref().and_then(|mgr| mgr.ip_counters(name));
+    let history = mgr
+        .as_ref()
+        .and_then(|mgr| mgr.state_history(name).ok())
+        .unwrap_or_default();
+    Response::Status(UnitInfo {
+        name: name.to_string(),
+        unit_type: unit.unit_type.into(),
+        state: format!("{:?} ({})", unit.active, unit.sub.as_str()),
+        description: unit.description,
+        need_daemon_reload: unit.need_daemon_reload,
+        load_state: unit.load_state.into(),
+        unit_file_state: mgr.and_then(|mgr| mgr.unit_file_state(name).map(String::from)),
+        fragment_path: unit.fragment_path.map(|path| path.display().to_string()),
+        documentation: unit.documentation,
+        names: unit.names,
+        condition_failure: unit.condition_failure,
+        failed_dependency: unit.failed_dependency,
+        ip_ingress_bytes: ip_counters.map(|counters| counters.ingress_bytes),
+        ip_egress_bytes: ip_counters.map(|counters| counters.egress_bytes),
+        history: history.iter().map(transition_info).collect(),
+    })
+}
+
+fn transition_info(transition: &Transition) -> TransitionInfo {
+    let since_epoch = transition.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+    TransitionInfo {
+        usec: since_epoch.as_micros() as u64,
+        active: transition.active.as_str().to_string(),
+        sub: transition.sub.as_str().to_string(),
+        reason: transition.reason.clone(),
     }
 }
 
@@ -193,7 +665,9 @@ async fn deps_response(manager: &SharedManager, name: &str) -> Response {
             let mut deps = Vec::new();
             let section = unit.unit_section();
             deps.extend(section.requires.iter().cloned());
+            deps.extend(section.requires_dir.iter().cloned());
             deps.extend(section.wants.iter().cloned());
+            deps.extend(section.wants_dir.iter().cloned());
             deps.extend(section.after.iter().cloned());
             Response::Deps(deps)
         }
@@ -208,6 +682,20 @@ async fn boot_target_response(manager: &SharedManager) -> Response {
     }
 }
 
+async fn set_default_target_response(manager: &SharedManager, target: &str) -> Response {
+    let mut mgr = manager.write().await;
+    match mgr.set_default(target).await {
+        Ok(link) => {
+            info!("Created symlink: {}", link.display());
+            match mgr.get_default_target() {
+                Ok(target) => Response::BootTarget(target),
+                Err(error) => Response::Error(error.to_string()),
+            }
+        }
+        Err(error) => Response::Error(error.to_string()),
+    }
+}
+
 async fn boot_response(manager: &SharedManager, dry_run: bool) -> Response {
     let mut mgr = manager.write().await;
     let target = match mgr.get_default_target() {
@@ -267,9 +755,8 @@ async fn switch_target_response(manager: &SharedManager, target: &str) -> Respon
     }
 }
 
-async fn is_active_response(manager: &SharedManager, name: &str) -> Response {
-    let mgr = manager.read().await;
-    match mgr.status(name) {
+fn is_active_response(states: &StateView, name: &str) -> Response {
+    match states.get(name) {
         Some(state) => Response::ActiveState(format!("{:?}", state.active).to_lowercase()),
         None => Response::ActiveState("unknown".to_string()),
     }
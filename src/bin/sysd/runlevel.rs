@@ -0,0 +1,46 @@
+//! Runlevel records for SysV tools
+//!
+//! As PID 1, sysd writes a utmp RUN_LVL record whenever the deepest active
+//! runlevel target changes (graphical.target is 5, multi-user.target 3,
+//! rescue.target 1), and one for 0 or 6 when it shuts down, so `runlevel` and
+//! `who -r` keep answering for admins coming from sysvinit.
+
+use sysd::manager::{ActiveState, StateView};
+use sysd::pid1::{self, ShutdownType};
+
+pub(super) fn spawn_runlevel_recorder(mut states: StateView) {
+    tokio::spawn(async move {
+        let mut recorded = None;
+        loop {
+            let current = pid1::current_runlevel(|target| {
+                states
+                    .get(target)
+                    .is_some_and(|unit| unit.active == ActiveState::Active)
+            });
+            if let Some(runlevel) = current.filter(|_| current != recorded) {
+                record_runlevel(recorded, runlevel);
+                recorded = current;
+            }
+            if !states.changed().await {
+                break;
+            }
+        }
+    });
+}
+
+/// Record the runlevel the shutdown enters
+pub(super) fn record_shutdown_runlevel(shutdown_type: ShutdownType) {
+    let runlevel = match shutdown_type {
+        ShutdownType::Reboot => '6',
+        ShutdownType::Poweroff | ShutdownType::Halt => '0',
+    };
+    let previous = pid1::read_runlevel().map(|(_, current)| current);
+    record_runlevel(previous, runlevel);
+}
+
+fn record_runlevel(previous: Option<char>, runlevel: char) {
+    match pid1::write_runlevel_record(previous, runlevel) {
+        Ok(()) => log::debug!("Recorded runlevel {} in utmp", runlevel),
+        Err(e) => log::warn!("Failed to record runlevel {} in utmp: {}", runlevel, e),
+    }
+}
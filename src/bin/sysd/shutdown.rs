@@ -0,0 +1,124 @@
+//! `sysd shutdown`: power off, reboot or halt later, like shutdown(8)
+//!
+//! `sysd shutdown +10 "maintenance"` arms a poweroff in ten minutes; the
+//! manager warns logged-in users as it comes closer and keeps new logins
+//! out for the last five minutes. TIME is "now", "+MINUTES" or "HH:MM" (the
+//! next time the clock shows it), +1 by default as with shutdown(8).
+
+use chrono::{DateTime, Local, NaiveTime};
+use peercred_ipc::Client;
+use sysd::protocol::{socket_path, Request, Response};
+
+#[derive(clap::Args)]
+pub(super) struct ShutdownArgs {
+    /// Reboot instead of powering off
+    #[arg(short, long, conflicts_with = "halt")]
+    reboot: bool,
+    /// Halt instead of powering off
+    #[arg(short = 'H', long)]
+    halt: bool,
+    /// Power off (the default)
+    #[arg(short = 'P', long, conflicts_with_all = ["reboot", "halt"])]
+    poweroff: bool,
+    /// Cancel the scheduled shutdown
+    #[arg(short, long, conflicts_with = "show")]
+    cancel: bool,
+    /// Show the scheduled shutdown
+    #[arg(long)]
+    show: bool,
+    /// now, +MINUTES or HH:MM (default: +1)
+    time: Option<String>,
+    /// Message for the logged-in users
+    wall: Vec<String>,
+}
+
+pub(super) fn run_shutdown_command(args: ShutdownArgs, user_mode: bool) -> Result<(), String> {
+    if user_mode {
+        return Err("shutdown belongs to the system manager".into());
+    }
+    if args.cancel {
+        return match call(user_mode, Request::CancelShutdown)? {
+            Response::Ok => Ok(()),
+            other => Err(format!("unexpected response: {:?}", other)),
+        };
+    }
+    if args.show {
+        return match call(user_mode, Request::ShowShutdown)? {
+            Response::ScheduledShutdown(Some((kind, usec))) => {
+                println!("{} scheduled for {}", kind, format_usec(usec));
+                Ok(())
+            }
+            Response::ScheduledShutdown(None) => {
+                println!("No scheduled shutdown.");
+                Ok(())
+            }
+            other => Err(format!("unexpected response: {:?}", other)),
+        };
+    }
+
+    let spec = args.time.as_deref().unwrap_or("+1");
+    let at = parse_time(spec, Local::now()).ok_or_else(|| format!("invalid time: {}", spec))?;
+    let kind = match (args.reboot, args.halt) {
+        (true, _) => "reboot",
+        (_, true) => "halt",
+        _ => "poweroff",
+    };
+    let message = Some(args.wall.join(" ")).filter(|message| !message.is_empty());
+    let request = Request::ScheduleShutdown {
+        kind: kind.to_string(),
+        usec: at.timestamp_micros().max(0) as u64,
+        message,
+    };
+    match call(user_mode, request)? {
+        Response::Ok => {
+            println!(
+                "{} scheduled for {}, use 'sysd shutdown -c' to cancel.",
+                kind,
+                at.format("%a %Y-%m-%d %H:%M:%S %Z")
+            );
+            Ok(())
+        }
+        other => Err(format!("unexpected response: {:?}", other)),
+    }
+}
+
+fn call(user_mode: bool, request: Request) -> Result<Response, String> {
+    match Client::call(&socket_path(user_mode), &request) {
+        Ok(Response::Error(message)) => Err(message),
+        Ok(response) => Ok(response),
+        Err(e) => Err(format!("cannot reach the manager: {}", e)),
+    }
+}
+
+/// When `spec` (now, +MINUTES, HH:MM) is, seen from `now`
+fn parse_time(spec: &str, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    if spec == "now" {
+        return Some(now);
+    }
+    if let Some(minutes) = spec.strip_prefix('+') {
+        let minutes: u32 = minutes.parse().ok()?;
+        return Some(now + chrono::Duration::minutes(minutes.into()));
+    }
+    let (hour, minute) = spec.split_once(':')?;
+    let time = NaiveTime::from_hms_opt(hour.parse().ok()?, minute.parse().ok()?, 0)?;
+    let today = now
+        .date_naive()
+        .and_time(time)
+        .and_local_timezone(Local)
+        .earliest()?;
+    if today > now {
+        Some(today)
+    } else {
+        Some(today + chrono::Duration::days(1))
+    }
+}
+
+fn format_usec(usec: u64) -> String {
+    match DateTime::from_timestamp_micros(usec as i64) {
+        Some(at) => at
+            .with_timezone(&Local)
+            .format("%a %Y-%m-%d %H:%M:%S %Z")
+            .to_string(),
+        None => usec.to_string(),
+    }
+}
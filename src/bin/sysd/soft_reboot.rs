@@ -0,0 +1,23 @@
+//! `sysd soft-reboot`: restart userspace without rebooting the kernel, like
+//! `systemctl soft-reboot`
+//!
+//! Asks PID 1 with SIGRTMIN+7, the signal systemd soft-reboots on. Units with
+//! SurviveFinalKillSignal=yes keep running; a root file system mounted at
+//! /run/nextroot beforehand becomes the new root.
+
+pub(super) fn run_soft_reboot_command(user_mode: bool) -> Result<(), String> {
+    if user_mode {
+        return Err("soft-reboot belongs to the system manager".into());
+    }
+    if std::path::Path::new(sysd::pid1::NEXTROOT).is_dir() {
+        println!("Soft-rebooting into {}", sysd::pid1::NEXTROOT);
+    }
+    // nix's Signal has no real-time signals
+    if unsafe { libc::kill(1, libc::SIGRTMIN() + 7) } != 0 {
+        return Err(format!(
+            "failed to signal init: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! Reporting to whoever supervises sysd: READY=1 once the default target is
+//! reached, STATUS= with unit counts and WATCHDOG=1 pings (see sysd::sd_notify)
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use sysd::manager::{ActiveState, StateView};
+use sysd::sd_notify::Supervisor;
+
+/// How often unit counts are looked at when there is no faster watchdog
+const STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+pub(super) fn spawn_supervisor_notifier(supervisor: Arc<Supervisor>, states: StateView) {
+    // Ping at half the timeout, like sd_watchdog_enabled() callers do
+    let interval = supervisor
+        .watchdog_interval()
+        .map_or(STATUS_INTERVAL, |timeout| {
+            (timeout / 2).min(STATUS_INTERVAL)
+        });
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        let mut last_status = String::new();
+        loop {
+            ticks.tick().await;
+            let mut message = Vec::new();
+            let status = units_status(&states);
+            if status != last_status {
+                message.push(format!("STATUS={}", status));
+                last_status = status;
+            }
+            if supervisor.watchdog_interval().is_some() {
+                message.push("WATCHDOG=1".to_string());
+            }
+            if message.is_empty() {
+                continue;
+            }
+            if let Err(e) = supervisor.notify(&message.join("\n")) {
+                log::warn!("Failed to notify supervisor: {}", e);
+            }
+        }
+    });
+}
+
+/// READY=1, sent once booting is over
+pub(super) fn notify_ready(supervisor: Option<&Supervisor>) {
+    let Some(supervisor) = supervisor else {
+        return;
+    };
+    if let Err(e) = supervisor.notify("READY=1") {
+        log::warn!("Failed to notify supervisor: {}", e);
+    }
+}
+
+fn units_status(states: &StateView) -> String {
+    let units = states.list();
+    let count = |active| {
+        units
+            .iter()
+            .filter(|(_, unit)| unit.active == active)
+            .count()
+    };
+    format!(
+        "{} units loaded, {} active, {} failed",
+        units.len(),
+        count(ActiveState::Active),
+        count(ActiveState::Failed)
+    )
+}
@@ -0,0 +1,321 @@
+//! `sysd top`: live resource usage of units, like systemd-cgtop
+//!
+//! Each refresh reads the accounting files of every service and scope
+//! cgroup; CPU is the share of one CPU used since the previous refresh.
+//! Keys: c, m and t sort by CPU, memory or tasks, j/k or the arrow keys move
+//! the selection, Enter shows the selected unit's status, q quits.
+
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use sysd::cgroups;
+
+#[derive(clap::Args)]
+pub(super) struct TopArgs {
+    /// Sort by cpu, memory or tasks
+    #[arg(long, short = 'o', default_value = "cpu", value_parser = ["cpu", "memory", "tasks"])]
+    order: String,
+    /// Seconds between refreshes
+    #[arg(long, short = 'd', default_value_t = 1.0)]
+    delay: f64,
+    /// Print this many refreshes without interaction, then exit
+    #[arg(long, short = 'n')]
+    iterations: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SortOrder {
+    Cpu,
+    Memory,
+    Tasks,
+}
+
+impl SortOrder {
+    fn parse(order: &str) -> Self {
+        match order {
+            "memory" => Self::Memory,
+            "tasks" => Self::Tasks,
+            _ => Self::Cpu,
+        }
+    }
+}
+
+/// One unit in the table
+#[derive(Debug, Clone, PartialEq)]
+struct Row {
+    unit: String,
+    cpu_percent: Option<f64>,
+    memory_bytes: Option<u64>,
+    tasks: Option<u64>,
+}
+
+/// Keeps the previous CPU counters to turn them into rates
+#[derive(Default)]
+struct Sampler {
+    previous: HashMap<PathBuf, (u64, Instant)>,
+}
+
+impl Sampler {
+    fn sample(&mut self) -> Vec<Row> {
+        let now = Instant::now();
+        let mut current = HashMap::new();
+        let rows = cgroups::unit_cgroups()
+            .into_iter()
+            .map(|cgroup| {
+                let usage = cgroups::read_usage(&cgroup.path);
+                let cpu_percent = usage.cpu_usec.and_then(|usec| {
+                    current.insert(cgroup.path.clone(), (usec, now));
+                    let (last, at) = self.previous.get(&cgroup.path)?;
+                    cpu_percent(*last, usec, now.duration_since(*at))
+                });
+                Row {
+                    unit: cgroup.unit,
+                    cpu_percent,
+                    memory_bytes: usage.memory_bytes,
+                    tasks: usage.tasks,
+                }
+            })
+            .collect();
+        self.previous = current;
+        rows
+    }
+}
+
+enum Key {
+    Sort(SortOrder),
+    Up,
+    Down,
+    Status,
+    Quit,
+}
+
+/// Run `sysd top`; with `user`, Enter shows status from the user manager
+pub(super) fn run_top_command(args: TopArgs, user: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let mut order = SortOrder::parse(&args.order);
+    let delay = Duration::from_secs_f64(args.delay.max(0.1));
+    let mut sampler = Sampler::default();
+    sampler.sample();
+
+    let interactive = unsafe { libc::isatty(0) == 1 && libc::isatty(1) == 1 };
+    if args.iterations.is_some() || !interactive {
+        for i in 0..args.iterations.unwrap_or(1) {
+            std::thread::sleep(delay);
+            let mut rows = sampler.sample();
+            sort_rows(&mut rows, order);
+            if i > 0 {
+                println!();
+            }
+            print!("{}", render(&rows, order, None, usize::MAX));
+        }
+        return Ok(());
+    }
+
+    let mut terminal = RawTerminal::enter()?;
+    let mut selected = 0usize;
+    let mut rows = Vec::new();
+    let mut next_sample = Instant::now();
+    loop {
+        if Instant::now() >= next_sample {
+            rows = sampler.sample();
+            next_sample = Instant::now() + delay;
+        }
+        sort_rows(&mut rows, order);
+        selected = selected.min(rows.len().saturating_sub(1));
+        let height = terminal_height().saturating_sub(2);
+        terminal.draw(&render(&rows, order, Some(selected), height))?;
+
+        match read_key(next_sample.saturating_duration_since(Instant::now()))? {
+            Some(Key::Quit) => return Ok(()),
+            Some(Key::Sort(new_order)) => order = new_order,
+            Some(Key::Up) => selected = selected.saturating_sub(1),
+            Some(Key::Down) => selected += 1,
+            Some(Key::Status) => {
+                if let Some(row) = rows.get(selected) {
+                    terminal.suspend(|| show_status(&row.unit, user))?;
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/// Share of one CPU used between two cpu.stat readings
+fn cpu_percent(last_usec: u64, usec: u64, elapsed: Duration) -> Option<f64> {
+    let elapsed = elapsed.as_micros() as f64;
+    (elapsed > 0.0).then(|| usec.saturating_sub(last_usec) as f64 * 100.0 / elapsed)
+}
+
+/// Highest usage first; units without a value go last
+fn sort_rows(rows: &mut [Row], order: SortOrder) {
+    rows.sort_by(|a, b| {
+        let by_usage = match order {
+            SortOrder::Cpu => b
+                .cpu_percent
+                .partial_cmp(&a.cpu_percent)
+                .unwrap_or(std::cmp::Ordering::Equal),
+            SortOrder::Memory => b.memory_bytes.cmp(&a.memory_bytes),
+            SortOrder::Tasks => b.tasks.cmp(&a.tasks),
+        };
+        by_usage.then_with(|| a.unit.cmp(&b.unit))
+    });
+}
+
+/// Table with a header line, at most `height` rows
+fn render(rows: &[Row], order: SortOrder, selected: Option<usize>, height: usize) -> String {
+    let marker = |column: SortOrder| if column == order { "▼" } else { " " };
+    let mut out = format!(
+        "{:<48} {:>5}{} {:>9}{} {:>6}{}\n",
+        "UNIT",
+        "TASKS",
+        marker(SortOrder::Tasks),
+        "%CPU",
+        marker(SortOrder::Cpu),
+        "MEMORY",
+        marker(SortOrder::Memory)
+    );
+    let first = selected.map_or(0, |selected| (selected + 1).saturating_sub(height));
+    for (i, row) in rows.iter().enumerate().skip(first).take(height) {
+        let line = format!(
+            "{:<48} {:>5}  {:>9}  {:>6} ",
+            truncate(&row.unit, 48),
+            row.tasks.map_or("-".to_string(), |tasks| tasks.to_string()),
+            row.cpu_percent
+                .map_or("-".to_string(), |percent| format!("{:.1}", percent)),
+            row.memory_bytes.map_or("-".to_string(), format_bytes)
+        );
+        if selected == Some(i) {
+            out.push_str(&format!("\x1b[7m{}\x1b[0m\n", line));
+        } else {
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(width - 1).collect();
+    short.push('…');
+    short
+}
+
+/// Bytes with a binary unit suffix (e.g. 1.5G)
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", value, UNITS[unit])
+}
+
+fn show_status(unit: &str, user: bool) {
+    let mut command = std::process::Command::new("sysdctl");
+    if user {
+        command.arg("--user");
+    }
+    if let Err(e) = command.args(["status", unit]).status() {
+        println!("sysdctl: {}", e);
+    }
+    print!("\nPress any key to return");
+    let _ = std::io::stdout().flush();
+    let mut key = [0u8; 8];
+    let _ = std::io::stdin().read(&mut key);
+}
+
+/// Wait up to `timeout` for a key press
+fn read_key(timeout: Duration) -> std::io::Result<Option<Key>> {
+    let mut poll_fd = libc::pollfd {
+        fd: 0,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+    if unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) } <= 0 {
+        return Ok(None);
+    }
+    let mut buf = [0u8; 8];
+    let n = std::io::stdin().read(&mut buf)?;
+    Ok(match &buf[..n] {
+        b"q" | b"Q" | b"\x03" | [] => Some(Key::Quit),
+        b"c" => Some(Key::Sort(SortOrder::Cpu)),
+        b"m" => Some(Key::Sort(SortOrder::Memory)),
+        b"t" => Some(Key::Sort(SortOrder::Tasks)),
+        b"k" | b"\x1b[A" => Some(Key::Up),
+        b"j" | b"\x1b[B" => Some(Key::Down),
+        b"\r" | b"\n" => Some(Key::Status),
+        _ => None,
+    })
+}
+
+fn terminal_height() -> usize {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    match unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut size) } {
+        0 if size.ws_row > 0 => size.ws_row as usize,
+        _ => 24,
+    }
+}
+
+/// Non-canonical, no-echo terminal on the alternate screen; restored on drop
+struct RawTerminal {
+    saved: libc::termios,
+}
+
+impl RawTerminal {
+    fn enter() -> std::io::Result<Self> {
+        let mut saved: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(0, &mut saved) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let terminal = Self { saved };
+        terminal.activate()?;
+        Ok(terminal)
+    }
+
+    fn activate(&self) -> std::io::Result<()> {
+        let mut raw = self.saved;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(0, libc::TCSANOW, &raw) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        print!("\x1b[?1049h\x1b[?25l");
+        std::io::stdout().flush()
+    }
+
+    fn restore(&self) {
+        print!("\x1b[?25h\x1b[?1049l");
+        let _ = std::io::stdout().flush();
+        unsafe { libc::tcsetattr(0, libc::TCSANOW, &self.saved) };
+    }
+
+    fn draw(&mut self, screen: &str) -> std::io::Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        write!(stdout, "\x1b[H\x1b[2J{}", screen.replace('\n', "\r\n"))?;
+        stdout.flush()
+    }
+
+    /// Run `f` on the normal screen with line editing, then come back
+    fn suspend(&mut self, f: impl FnOnce()) -> std::io::Result<()> {
+        self.restore();
+        f();
+        self.activate()
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
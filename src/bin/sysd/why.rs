@@ -0,0 +1,31 @@
+//! `sysd why UNIT`: why a unit is in the state it is in
+//!
+//! Answers "why is this running?" and "why did this not start?" in plain
+//! sentences: which units pull it in (or would), which socket, timer or
+//! path activates it, which condition or dependency kept its last start
+//! from running, how its last run ended and whether the start limit or a
+//! mask holds it down.
+
+use peercred_ipc::Client;
+use sysd::protocol::{socket_path, Request, Response};
+
+#[derive(clap::Args)]
+pub(super) struct WhyArgs {
+    /// Unit to explain ("nginx" stands for nginx.service)
+    unit: String,
+}
+
+pub(super) fn run_why_command(args: WhyArgs, user_mode: bool) -> Result<(), String> {
+    let request = Request::Why { name: args.unit };
+    match Client::call(&socket_path(user_mode), &request) {
+        Ok(Response::Why(why)) => {
+            for line in why {
+                println!("{}", line);
+            }
+            Ok(())
+        }
+        Ok(Response::Error(message)) => Err(message),
+        Ok(other) => Err(format!("unexpected response: {:?}", other)),
+        Err(e) => Err(format!("cannot reach the manager: {}", e)),
+    }
+}
@@ -0,0 +1,213 @@
+//! PrivatePIDs=: run the service in a new PID namespace
+//!
+//! The executor, which is the main PID the manager tracks, unshares a PID
+//! namespace and forks. Its child is PID 1 of the new namespace: it mounts a
+//! /proc of its own, forks once more and from then on only reaps. The
+//! grandchild returns to the executor to set up the sandbox and exec the
+//! service. Both stubs pass the usual control signals on to their child and
+//! exit the way it did, so stopping the main PID stops the service and its
+//! exit status is the service's.
+//!
+//! When the service exits (or the executor dies), PID 1 of the namespace
+//! exits and the kernel kills whatever is left in it, however deeply it
+//! forked, and no PID inside can be mistaken for a reused one outside.
+
+use std::ffi::CString;
+use std::os::unix::io::RawFd;
+
+use sysd::executor::EXIT_NAMESPACE;
+
+use super::SetupError;
+
+/// Signals the stubs pass on instead of acting on
+const FORWARDED_SIGNALS: [libc::c_int; 9] = [
+    libc::SIGTERM,
+    libc::SIGINT,
+    libc::SIGHUP,
+    libc::SIGQUIT,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+    libc::SIGCONT,
+    libc::SIGALRM,
+    libc::SIGWINCH,
+];
+
+/// Move the rest of the spawn into a new PID namespace
+///
+/// Returns in the process that goes on to become the service; the two stubs
+/// above it stay in here until it exits. `profile_fd` is closed in the stubs
+/// so the manager sees the spawn finish when the service execs.
+pub(super) fn enter_private_pid_namespace(profile_fd: Option<RawFd>) -> Result<(), SetupError> {
+    let signals = stub_signal_set();
+    let mut original = empty_signal_set();
+    unsafe {
+        // An ignored SIGCHLD would reap the children before the stubs see them
+        libc::signal(libc::SIGCHLD, libc::SIG_DFL);
+        libc::sigprocmask(libc::SIG_BLOCK, &signals, &mut original);
+        if libc::unshare(libc::CLONE_NEWPID) != 0 {
+            return Err(namespace_error("Failed to create PID namespace"));
+        }
+    }
+
+    let init = fork()?;
+    if init > 0 {
+        close_profile(profile_fd);
+        supervise(init, &signals, true);
+    }
+
+    // PID 1 of the new namespace: die with the executor, so nothing in the
+    // namespace outlives the main PID
+    unsafe {
+        libc::prctl(
+            libc::PR_SET_PDEATHSIG,
+            libc::SIGKILL as libc::c_ulong,
+            0,
+            0,
+            0,
+        )
+    };
+    mount_private_proc()?;
+    let service = fork()?;
+    if service > 0 {
+        close_profile(profile_fd);
+        supervise(service, &signals, false);
+    }
+
+    unsafe {
+        libc::sigprocmask(libc::SIG_SETMASK, &original, std::ptr::null_mut());
+    }
+    Ok(())
+}
+
+fn fork() -> Result<libc::pid_t, SetupError> {
+    match unsafe { libc::fork() } {
+        -1 => Err(namespace_error("Failed to fork into the PID namespace")),
+        pid => Ok(pid),
+    }
+}
+
+fn namespace_error(what: &str) -> SetupError {
+    (
+        EXIT_NAMESPACE,
+        format!("{}: {}", what, std::io::Error::last_os_error()),
+    )
+}
+
+fn close_profile(profile_fd: Option<RawFd>) {
+    if let Some(fd) = profile_fd {
+        unsafe { libc::close(fd) };
+    }
+}
+
+/// A mount namespace whose /proc shows the new PID namespace; mounts of the
+/// host still propagate into it
+fn mount_private_proc() -> Result<(), SetupError> {
+    let root = CString::new("/").unwrap();
+    let proc = CString::new("proc").unwrap();
+    let target = CString::new("/proc").unwrap();
+    unsafe {
+        if libc::unshare(libc::CLONE_NEWNS) != 0 {
+            return Err(namespace_error("Failed to create mount namespace"));
+        }
+        libc::mount(
+            std::ptr::null(),
+            root.as_ptr(),
+            std::ptr::null(),
+            libc::MS_REC | libc::MS_SLAVE,
+            std::ptr::null(),
+        );
+        let flags = libc::MS_NOSUID | libc::MS_NODEV | libc::MS_NOEXEC;
+        if libc::mount(
+            proc.as_ptr(),
+            target.as_ptr(),
+            proc.as_ptr(),
+            flags,
+            std::ptr::null(),
+        ) != 0
+        {
+            return Err(namespace_error("Failed to mount /proc"));
+        }
+    }
+    Ok(())
+}
+
+fn empty_signal_set() -> libc::sigset_t {
+    let mut set = unsafe { std::mem::zeroed() };
+    unsafe { libc::sigemptyset(&mut set) };
+    set
+}
+
+/// The forwarded signals and SIGCHLD, which the stubs wait for
+fn stub_signal_set() -> libc::sigset_t {
+    let mut set = empty_signal_set();
+    for signal in FORWARDED_SIGNALS.into_iter().chain([libc::SIGCHLD]) {
+        unsafe { libc::sigaddset(&mut set, signal) };
+    }
+    set
+}
+
+/// Forward signals to `child` and reap every process that ends up here until
+/// `child` exits, then exit the way it did
+///
+/// PID 1 of a namespace cannot die of its own signals, so it exits with
+/// 128 + signal instead; the `outer` stub turns that back into the signal.
+fn supervise(child: libc::pid_t, signals: &libc::sigset_t, outer: bool) -> ! {
+    loop {
+        let signal = unsafe { libc::sigwaitinfo(signals, std::ptr::null_mut()) };
+        if signal < 0 {
+            continue;
+        }
+        if signal != libc::SIGCHLD {
+            unsafe { libc::kill(child, signal) };
+            continue;
+        }
+        loop {
+            let mut status = 0;
+            let pid = unsafe { libc::waitpid(-1, &mut status, libc::WNOHANG) };
+            if pid <= 0 {
+                break;
+            }
+            if pid == child {
+                exit_like(status, outer);
+            }
+        }
+    }
+}
+
+fn exit_like(status: libc::c_int, outer: bool) -> ! {
+    if libc::WIFSIGNALED(status) {
+        die_of(libc::WTERMSIG(status));
+    }
+    let code = libc::WEXITSTATUS(status);
+    let signal = code - 128;
+    if outer && signal > 0 && signal < libc::SIGRTMIN() && !stops_or_is_ignored(signal) {
+        die_of(signal);
+    }
+    std::process::exit(code)
+}
+
+/// Signals that cannot have killed a process when they reach it by default
+fn stops_or_is_ignored(signal: libc::c_int) -> bool {
+    matches!(
+        signal,
+        libc::SIGSTOP
+            | libc::SIGTSTP
+            | libc::SIGTTIN
+            | libc::SIGTTOU
+            | libc::SIGCHLD
+            | libc::SIGCONT
+            | libc::SIGURG
+            | libc::SIGWINCH
+    )
+}
+
+fn die_of(signal: libc::c_int) -> ! {
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        let mut set = empty_signal_set();
+        libc::sigaddset(&mut set, signal);
+        libc::sigprocmask(libc::SIG_UNBLOCK, &set, std::ptr::null_mut());
+        libc::kill(libc::getpid(), signal);
+    }
+    std::process::exit(128 + signal)
+}
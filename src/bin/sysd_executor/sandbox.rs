@@ -1,9 +1,18 @@
 use std::ffi::CString;
 
 use sysd::executor::{
-    DevicePolicyConfig, ProtectHomeConfig, ProtectProcConfig, ProtectSystemConfig, SandboxConfig,
+    DevicePolicyConfig, KeyringModeConfig, MacLabelConfig, ProtectHomeConfig, ProtectProcConfig,
+    ProtectSystemConfig, SandboxConfig, EXIT_APPARMOR_PROFILE, EXIT_CAPABILITIES, EXIT_KEYRING,
+    EXIT_NAMESPACE, EXIT_NETWORK, EXIT_NO_NEW_PRIVILEGES, EXIT_SECCOMP, EXIT_SELINUX_CONTEXT,
+    EXIT_SIGNAL_MASK, EXIT_SMACK_PROCESS_LABEL,
 };
-use sysd::sandbox_prctl::{apply_no_new_privileges, apply_private_network};
+use sysd::sandbox_prctl::{
+    apply_no_new_privileges, apply_private_ipc, apply_private_network, apply_session_keyring,
+    join_network_namespace, set_exec_apparmor_profile, set_exec_selinux_context,
+    set_smack_process_label,
+};
+
+use super::{failed_with, SetupError};
 
 const CAPABILITY_TABLE: &[(&str, u32)] = &[
     ("CHOWN", 0),
@@ -49,22 +58,34 @@ const CAPABILITY_TABLE: &[(&str, u32)] = &[
     ("CHECKPOINT_RESTORE", 40),
 ];
 
-pub(super) fn apply_sandbox_phase1(sandbox: &SandboxConfig) -> Result<(), String> {
+pub(super) fn apply_sandbox_phase1(sandbox: &SandboxConfig) -> Result<(), SetupError> {
     if sandbox.protect_kernel_modules {
-        drop_capability(16)?;
+        drop_capability(16).map_err(failed_with(EXIT_CAPABILITIES))?;
     }
-    apply_capability_bounding_set(&sandbox.capability_bounding_set)?;
-    if sandbox.private_network {
-        apply_private_network()?;
+    apply_capability_bounding_set(&sandbox.capability_bounding_set)
+        .map_err(failed_with(EXIT_CAPABILITIES))?;
+    // A namespace to join takes precedence over an isolated one
+    if let Some(path) = &sandbox.network_namespace_path {
+        join_network_namespace(path).map_err(failed_with(EXIT_NETWORK))?;
+    } else if sandbox.private_network {
+        apply_private_network().map_err(failed_with(EXIT_NETWORK))?;
+    }
+    if sandbox.private_ipc {
+        apply_private_ipc().map_err(failed_with(EXIT_NAMESPACE))?;
     }
     if sandbox.memory_deny_write_execute {
-        apply_memory_deny_write_execute()?;
+        apply_memory_deny_write_execute().map_err(failed_with(EXIT_SECCOMP))?;
     }
     if sandbox.ignore_sigpipe {
-        apply_ignore_sigpipe()?;
+        apply_ignore_sigpipe().map_err(failed_with(EXIT_SIGNAL_MASK))?;
     }
     if needs_mount_namespace(sandbox) {
-        apply_mount_namespace_settings(sandbox)?;
+        apply_mount_namespace_settings(sandbox).map_err(failed_with(EXIT_NAMESPACE))?;
+    }
+    // Still privileged: changing the Smack label takes CAP_MAC_ADMIN
+    if let Some(label) = &sandbox.smack_process_label {
+        apply_mac_label(label, set_smack_process_label)
+            .map_err(failed_with(EXIT_SMACK_PROCESS_LABEL))?;
     }
     Ok(())
 }
@@ -125,10 +146,30 @@ fn apply_kernel_protections(sandbox: &SandboxConfig) -> Result<(), String> {
     Ok(())
 }
 
-pub(super) fn apply_sandbox_phase2(sandbox: &SandboxConfig) -> Result<(), String> {
-    apply_ambient_capabilities(&sandbox.ambient_capabilities)?;
+pub(super) fn apply_sandbox_phase2(sandbox: &SandboxConfig) -> Result<(), SetupError> {
+    // After setuid() so the new session keyring belongs to the service user
+    match sandbox.keyring_mode {
+        KeyringModeConfig::Inherit => {}
+        KeyringModeConfig::Private => {
+            apply_session_keyring(false).map_err(failed_with(EXIT_KEYRING))?
+        }
+        KeyringModeConfig::Shared => {
+            apply_session_keyring(true).map_err(failed_with(EXIT_KEYRING))?
+        }
+    }
+    apply_ambient_capabilities(&sandbox.ambient_capabilities)
+        .map_err(failed_with(EXIT_CAPABILITIES))?;
+    // Take effect at the execve() of the service binary
+    if let Some(context) = &sandbox.selinux_context {
+        apply_mac_label(context, set_exec_selinux_context)
+            .map_err(failed_with(EXIT_SELINUX_CONTEXT))?;
+    }
+    if let Some(profile) = &sandbox.apparmor_profile {
+        apply_mac_label(profile, set_exec_apparmor_profile)
+            .map_err(failed_with(EXIT_APPARMOR_PROFILE))?;
+    }
     if sandbox.no_new_privileges {
-        apply_no_new_privileges()?;
+        apply_no_new_privileges().map_err(failed_with(EXIT_NO_NEW_PRIVILEGES))?;
     }
     let has_seccomp = sandbox.restrict_namespaces.is_some()
         || !sandbox.system_call_filter.is_empty()
@@ -140,11 +181,25 @@ pub(super) fn apply_sandbox_phase2(sandbox: &SandboxConfig) -> Result<(), String
         || sandbox.restrict_realtime
         || sandbox.lock_personality;
     if has_seccomp {
-        apply_seccomp(sandbox)?;
+        apply_seccomp(sandbox).map_err(failed_with(EXIT_SECCOMP))?;
     }
     Ok(())
 }
 
+/// A "-" prefixed label failing to apply only gets a warning
+fn apply_mac_label(
+    label: &MacLabelConfig,
+    apply: fn(&str) -> Result<(), String>,
+) -> Result<(), String> {
+    match apply(&label.label) {
+        Err(e) if label.ignore_failure => {
+            eprintln!("sysd-executor: {}, continuing without it", e);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
 fn drop_capability(cap: u32) -> Result<(), String> {
     unsafe {
         if libc::prctl(libc::PR_CAPBSET_DROP, cap as libc::c_ulong, 0, 0, 0) != 0 {
@@ -12,13 +12,22 @@
 // - Resource limits
 // - Security sandbox settings
 // - Socket activation FDs
+// - PrivatePIDs= (a new PID namespace under an init stub)
+//
+// A failing setup step makes it exit with the systemd status of that step
+// (203/EXEC, 226/NAMESPACE, ...) instead of 1.
 
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::os::unix::io::RawFd;
 
 // Import executor module from sysd lib
-use sysd::executor::{ExecConfig, StdInputConfig};
+use sysd::executor::{
+    mark_spawn_step, ExecConfig, SpawnStep, StdInputConfig, EXIT_CHDIR, EXIT_EXEC, EXIT_FDS,
+    EXIT_GROUP, EXIT_LIMITS, EXIT_OOM_ADJUST, EXIT_SECUREBITS, EXIT_STDIN, EXIT_USER,
+};
+use sysd::sandbox_prctl::apply_secure_bits;
+use sysd::tty::{TtyAcquire, TtyOptions};
 
 fn main() {
     // Parse arguments
@@ -40,12 +49,20 @@ fn main() {
     };
 
     // Apply config and exec
-    if let Err(e) = apply_and_exec(config) {
+    if let Err((status, e)) = apply_and_exec(config) {
         eprintln!("sysd-executor: {}", e);
-        std::process::exit(1);
+        std::process::exit(status);
     }
 }
 
+/// Why a setup step failed, and the exit status that reports it
+type SetupError = (i32, String);
+
+/// Attach the exit status `status` to the error of a step
+fn failed_with<E: ToString>(status: i32) -> impl FnOnce(E) -> SetupError {
+    move |error| (status, error.to_string())
+}
+
 fn parse_deserialize_fd(args: &[String]) -> Option<RawFd> {
     for arg in args.iter().skip(1) {
         if let Some(fd_str) = arg.strip_prefix("--deserialize=") {
@@ -55,45 +72,98 @@ fn parse_deserialize_fd(args: &[String]) -> Option<RawFd> {
     None
 }
 
-fn apply_and_exec(config: ExecConfig) -> Result<(), String> {
+fn apply_and_exec(config: ExecConfig) -> Result<(), SetupError> {
+    // 0. Wait until the manager moved us into the service cgroup and
+    // attached its BPF programs, so nothing below runs outside of them
+    if let Some(fd) = config.exec_gate_fd {
+        wait_for_exec_gate(fd).map_err(failed_with(libc::EXIT_FAILURE))?;
+    }
+
+    // The profile pipe must not leak into the service
+    let profile = config.profile_fd;
+    if let Some(fd) = profile {
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+    }
+    mark_spawn_step(profile, SpawnStep::Executor);
+
     // 1. Set up socket activation FDs (must be done early, before other setup)
-    setup_socket_fds(config.socket_fd_count, &config.socket_fd_names)?;
+    setup_socket_fds(config.socket_fd_count, &config.socket_fd_names)
+        .map_err(failed_with(EXIT_FDS))?;
 
     // 2. Set environment variables
-    setup_environment(&config.environment, &config.unset_environment)?;
+    setup_environment(&config.environment, &config.unset_environment)
+        .map_err(failed_with(libc::EXIT_FAILURE))?;
 
     // 3. Set resource limits
-    setup_rlimits(&config)?;
+    setup_rlimits(&config).map_err(failed_with(EXIT_LIMITS))?;
 
     // 4. Set OOM score adjust
     if let Some(score) = config.oom_score_adjust {
-        set_oom_score_adjust(score)?;
+        set_oom_score_adjust(score).map_err(failed_with(EXIT_OOM_ADJUST))?;
+    }
+
+    // 5. Set up TTY if needed (before credentials: stealing a terminal and
+    // vhangup need root)
+    setup_tty(&config).map_err(failed_with(EXIT_STDIN))?;
+    mark_spawn_step(profile, SpawnStep::Setup);
+
+    // 5b. PrivatePIDs=: continue as the child of an init stub in a new PID
+    // namespace (before the sandbox, whose mounts then see its /proc)
+    if config.sandbox.private_pids {
+        enter_private_pid_namespace(profile)?;
     }
 
-    // 5. Apply security sandbox PHASE 1: mount namespace, protections (before privileges)
+    // 6. Apply security sandbox PHASE 1: mount namespace, protections (before privileges)
     // This does NOT include: NoNewPrivileges, ambient caps, seccomp (those come later)
     apply_sandbox_phase1(&config.sandbox)?;
+    mark_spawn_step(profile, SpawnStep::Sandbox);
 
-    // 6. Set credentials (uid/gid)
+    // 7. Set credentials (uid/gid) and SecureBits=
     // Use SECBIT_KEEP_CAPS to preserve capabilities across setuid()
     let needs_caps = !config.sandbox.ambient_capabilities.is_empty();
-    set_credentials(config.gid, config.uid, needs_caps)?;
-
-    // 7. Apply security sandbox PHASE 2: capabilities, NoNewPrivileges, seccomp
+    set_credentials(
+        config.gid,
+        config.uid,
+        config.sandbox.secure_bits,
+        needs_caps,
+    )?;
+    mark_spawn_step(profile, SpawnStep::Credentials);
+
+    // 8. Apply security sandbox PHASE 2: keyring, capabilities, NoNewPrivileges, seccomp
     // Must be AFTER setuid() so ambient caps work correctly
     apply_sandbox_phase2(&config.sandbox)?;
+    mark_spawn_step(profile, SpawnStep::Sandbox);
 
-    // 8. Set working directory
+    // 9. Set working directory
     if let Some(ref wd) = config.working_directory {
-        std::env::set_current_dir(wd)
-            .map_err(|e| format!("Failed to set working directory: {}", e))?;
+        std::env::set_current_dir(wd).map_err(|e| {
+            (
+                EXIT_CHDIR,
+                format!("Failed to set working directory: {}", e),
+            )
+        })?;
     }
 
-    // 9. Set up TTY if needed
-    setup_tty(&config)?;
-
     // 10. Exec the target program
-    exec_program(&config.program, &config.args)
+    exec_program(&config.program, &config.args).map_err(failed_with(EXIT_EXEC))
+}
+
+fn wait_for_exec_gate(fd: RawFd) -> Result<(), String> {
+    let mut byte = 0u8;
+    let result = loop {
+        match unsafe { libc::read(fd, (&mut byte as *mut u8).cast(), 1) } {
+            1 => break Ok(()),
+            0 => break Err("manager gave up on the start before releasing it".to_string()),
+            _ => {
+                let e = std::io::Error::last_os_error();
+                if e.kind() != std::io::ErrorKind::Interrupted {
+                    break Err(format!("failed to wait for the manager: {}", e));
+                }
+            }
+        }
+    };
+    unsafe { libc::close(fd) };
+    result
 }
 
 fn setup_socket_fds(count: usize, names: &[String]) -> Result<(), String> {
@@ -225,23 +295,30 @@ fn set_oom_score_adjust(score: i32) -> Result<(), String> {
 }
 
 // Securebits constants for preserving capabilities across setuid
-const SECBIT_KEEP_CAPS: libc::c_ulong = 1 << 4;
-const SECBIT_NO_SETUID_FIXUP: libc::c_ulong = 1 << 2;
-
-fn set_credentials(gid: Option<u32>, uid: Option<u32>, needs_caps: bool) -> Result<(), String> {
-    // If we need to preserve capabilities across setuid(), set SECBIT_KEEP_CAPS
-    // This prevents the kernel from clearing the permitted capability set on setuid()
+const SECBIT_KEEP_CAPS: u32 = 1 << 4;
+const SECBIT_NO_SETUID_FIXUP: u32 = 1 << 2;
+
+fn set_credentials(
+    gid: Option<u32>,
+    uid: Option<u32>,
+    secure_bits: u32,
+    needs_caps: bool,
+) -> Result<(), SetupError> {
+    // SecureBits= of the unit, plus KEEP_CAPS and NO_SETUID_FIXUP when we need
+    // to preserve capabilities across setuid(). They stop the kernel from
+    // clearing the permitted capability set on setuid().
+    let mut bits = secure_bits;
     if needs_caps && uid.is_some() {
-        unsafe {
-            // Set KEEP_CAPS and NO_SETUID_FIXUP to preserve capabilities
-            let securebits = SECBIT_KEEP_CAPS | SECBIT_NO_SETUID_FIXUP;
-            if libc::prctl(libc::PR_SET_SECUREBITS, securebits, 0, 0, 0) != 0 {
-                eprintln!(
-                    "sysd-executor: warning: failed to set securebits: {}",
-                    std::io::Error::last_os_error()
-                );
-                // Continue anyway - caps might not work but we shouldn't fail the service
+        bits |= SECBIT_KEEP_CAPS | SECBIT_NO_SETUID_FIXUP;
+    }
+    if bits != 0 {
+        if let Err(e) = apply_secure_bits(bits) {
+            // Bits the unit asked for are a hard requirement
+            if secure_bits != 0 {
+                return Err((EXIT_SECUREBITS, e));
             }
+            // Continue anyway - caps might not work but we shouldn't fail the service
+            eprintln!("sysd-executor: warning: {}", e);
         }
     }
 
@@ -249,10 +326,13 @@ fn set_credentials(gid: Option<u32>, uid: Option<u32>, needs_caps: bool) -> Resu
     if let Some(gid) = gid {
         unsafe {
             if libc::setgid(gid) != 0 {
-                return Err(format!(
-                    "Failed to setgid({}): {}",
-                    gid,
-                    std::io::Error::last_os_error()
+                return Err((
+                    EXIT_GROUP,
+                    format!(
+                        "Failed to setgid({}): {}",
+                        gid,
+                        std::io::Error::last_os_error()
+                    ),
                 ));
             }
             // Also set supplementary groups to empty (like systemd does)
@@ -265,10 +345,13 @@ fn set_credentials(gid: Option<u32>, uid: Option<u32>, needs_caps: bool) -> Resu
     if let Some(uid) = uid {
         unsafe {
             if libc::setuid(uid) != 0 {
-                return Err(format!(
-                    "Failed to setuid({}): {}",
-                    uid,
-                    std::io::Error::last_os_error()
+                return Err((
+                    EXIT_USER,
+                    format!(
+                        "Failed to setuid({}): {}",
+                        uid,
+                        std::io::Error::last_os_error()
+                    ),
                 ));
             }
         }
@@ -278,75 +361,27 @@ fn set_credentials(gid: Option<u32>, uid: Option<u32>, needs_caps: bool) -> Resu
 }
 
 fn setup_tty(config: &ExecConfig) -> Result<(), String> {
-    if !matches!(
-        config.std_input,
-        StdInputConfig::Tty | StdInputConfig::TtyForce | StdInputConfig::TtyFail
-    ) {
-        return Ok(());
-    }
-
+    let acquire = match config.std_input {
+        StdInputConfig::Null => return Ok(()),
+        StdInputConfig::Tty => TtyAcquire::Try,
+        StdInputConfig::TtyForce => TtyAcquire::Force,
+        StdInputConfig::TtyFail => TtyAcquire::Fail,
+    };
     let path = match &config.tty_path {
         Some(p) => p,
         None => return Ok(()),
     };
-
-    if config.tty_reset {
-        let _ = std::fs::OpenOptions::new()
-            .read(true)
-            .write(true)
-            .open(path);
-    }
-
-    let tty_file = std::fs::OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(path);
-    let tty_file = match tty_file {
-        Ok(file) => file,
-        Err(error) if matches!(config.std_input, StdInputConfig::TtyFail) => {
-            return Err(format!("Failed to open TTY {:?}: {}", path, error));
-        }
-        Err(_) => return Ok(()),
+    let options = TtyOptions {
+        reset: config.tty_reset,
+        vhangup: config.tty_vhangup,
+        vt_disallocate: config.tty_vt_disallocate,
     };
-    configure_tty_fds(
-        tty_file,
-        matches!(config.std_input, StdInputConfig::TtyFail),
-    )
-}
-
-fn configure_tty_fds(tty_file: std::fs::File, fail_if_no_ctty: bool) -> Result<(), String> {
-    use std::os::unix::io::AsRawFd;
-
-    let fd = tty_file.as_raw_fd();
-    set_controlling_terminal(fd, fail_if_no_ctty)?;
-    dup_to_standard_streams(fd);
-    std::mem::forget(tty_file);
-    Ok(())
-}
-
-fn set_controlling_terminal(fd: RawFd, fail_if_no_ctty: bool) -> Result<(), String> {
-    unsafe {
-        if libc::ioctl(fd, libc::TIOCSCTTY, 0) >= 0 {
-            return Ok(());
-        }
-    }
-    if !fail_if_no_ctty {
-        return Ok(());
-    }
-    Err(format!(
-        "Failed to set controlling terminal: {}",
-        std::io::Error::last_os_error()
-    ))
-}
-
-fn dup_to_standard_streams(fd: RawFd) {
-    unsafe {
-        libc::dup2(fd, 0);
-        libc::dup2(fd, 1);
-        libc::dup2(fd, 2);
-        if fd > 2 {
-            libc::close(fd);
+    match sysd::tty::attach(path, acquire, options) {
+        Ok(()) => Ok(()),
+        Err(error) if acquire == TtyAcquire::Fail => {
+            Err(format!("Failed to set up TTY {:?}: {}", path, error))
         }
+        Err(_) => Ok(()),
     }
 }
 
@@ -375,6 +410,9 @@ fn exec_program(program: &str, args: &[String]) -> Result<(), String> {
     Err(format!("execv failed: {}", std::io::Error::last_os_error()))
 }
 
+#[path = "sysd_executor/pid_namespace.rs"]
+mod sysd_executor_pid_namespace;
 #[path = "sysd_executor/sandbox.rs"]
 mod sysd_executor_sandbox;
+use self::sysd_executor_pid_namespace::enter_private_pid_namespace;
 use self::sysd_executor_sandbox::{apply_sandbox_phase1, apply_sandbox_phase2};
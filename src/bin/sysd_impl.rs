@@ -8,6 +8,14 @@
 // - Reaps zombie processes
 // - Handles signals for shutdown
 //
+// Container mode (--container, or detected as PID 1 of a container):
+// - Leaves mounts and hardware to the container runtime
+// - SIGTERM (docker stop) and SIGINT power off, which exits sysd
+//
+// Outside PID 1:
+// - SIGTERM and SIGINT exit sysd, leaving services running (--stop-all
+//   stops them first); sockets, timers and the notify socket are released
+//
 // User mode (--user):
 // - Runs per-user service manager
 // - Uses ~/.config/systemd/user and /usr/lib/systemd/user
@@ -15,62 +23,64 @@
 
 use clap::Parser;
 use log::info;
-use std::fs::OpenOptions;
 use std::future::Future;
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::sync::mpsc;
 use tokio::sync::RwLock;
 
 use peercred_ipc::Server;
 use sysd::dbus::DbusServer;
-use sysd::manager::Manager;
-use sysd::pid1::{self, ShutdownType, SignalHandler, SysdSignal};
+use sysd::manager::{Manager, SleepMode, StateView};
+use sysd::pid1::{self, InputEvent, ShutdownType, SignalHandler, SysdSignal};
 use sysd::protocol::socket_path;
+use sysd::sd_notify::Supervisor;
+use sysd::units::{AgeRules, HandleAction, LoginConfig, LOGIN_CONFIG_PATH};
+use sysd_analyze::{run_analyze_command, AnalyzeCommand};
+use sysd_cgls::{run_cgls_command, CglsArgs};
+use sysd_dump::run_dump_command;
+use sysd_exit_status::{run_exit_status_command, ExitStatusArgs};
+use sysd_install::{run_install_command, InstallCommand};
+use sysd_login::{run_login_command, LoginCommand};
+use sysd_runlevel::{record_shutdown_runlevel, spawn_runlevel_recorder};
+use sysd_shutdown::{run_shutdown_command, ShutdownArgs};
+use sysd_soft_reboot::run_soft_reboot_command;
+use sysd_supervisor::{notify_ready, spawn_supervisor_notifier};
+use sysd_top::{run_top_command, TopArgs};
+use sysd_why::{run_why_command, WhyArgs};
+
+/// `path` below `--root` / $SYSD_ROOT, if one is in use
+fn rooted(path: &str) -> String {
+    sysd::root::path(path).to_string_lossy().into_owned()
+}
 
-/// Set up logging to both console and file
+/// Set up logging to the console and a log file (see sysd::logging for
+/// changing that at runtime)
 fn setup_logging(user_mode: bool) {
     let log_path = if user_mode {
         // User mode: /run/user/<uid>/sysd.log
         if let Some(uid) = std::env::var("XDG_RUNTIME_DIR").ok() {
             format!("{}/sysd.log", uid)
         } else {
-            format!("/run/user/{}/sysd.log", nix::unistd::getuid())
+            rooted(&format!("/run/user/{}/sysd.log", nix::unistd::getuid()))
         }
     } else {
         // System mode: /var/log/sysd.log
-        "/var/log/sysd.log".to_string()
+        rooted("/var/log/sysd.log")
     };
 
-    let mut dispatch = fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "[{}][{}] {}",
-                chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-                record.level(),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Debug)
-        .chain(std::io::stderr());
-
-    // Try to add file output
-    if let Ok(file) = OpenOptions::new().create(true).append(true).open(&log_path) {
-        dispatch = dispatch.chain(file);
+    if let Err(e) = sysd::logging::init(std::path::Path::new(&log_path)) {
+        eprintln!("sysd: Failed to set up logging: {}", e);
+        return;
+    }
+    if sysd::logging::has_log_file() {
         eprintln!("sysd: Logging to {}", log_path);
     } else {
-        eprintln!(
-            "sysd: Could not open log file {}, logging to stderr only",
-            log_path
-        );
-    }
-
-    if let Err(e) = dispatch.apply() {
-        eprintln!("sysd: Failed to set up logging: {}", e);
-        // Fall back to env_logger
-        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("debug")).init();
+        eprintln!("sysd: Could not open log file {}", log_path);
     }
+    sysd::logging::spawn_signal_handler();
 }
 
 #[derive(Parser)]
@@ -93,6 +103,30 @@ struct Args {
     #[arg(long)]
     no_boot: bool,
 
+    /// Reload units as soon as their files change on disk
+    /// (default: only flag them as needing daemon-reload)
+    #[arg(long)]
+    auto_reload_units: bool,
+
+    /// Run as the init of a container even if none is detected
+    #[arg(long)]
+    container: bool,
+
+    /// Stop all units when SIGTERM/SIGINT makes sysd exit (not as PID 1;
+    /// default: leave services running)
+    #[arg(long)]
+    stop_all: bool,
+
+    /// Operate on the tree below this directory instead of / (unit files,
+    /// /etc and /run, cgroups, sockets); defaults to $SYSD_ROOT
+    #[arg(long, value_name = "DIR")]
+    root: Option<std::path::PathBuf>,
+
+    /// Read unit files from below this directory, but keep using the host's
+    /// /run, cgroups and sockets (e.g. units shipped in a container volume)
+    #[arg(long, value_name = "DIR", conflicts_with = "root")]
+    unit_root: Option<std::path::PathBuf>,
+
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -101,6 +135,59 @@ struct Args {
 enum Command {
     /// Start daemon and boot to default target
     Boot,
+    /// Encrypt or decrypt credentials with the host key
+    #[command(subcommand)]
+    Creds(CredsCommand),
+    /// Manage login sessions and user lingering (like loginctl)
+    #[command(subcommand)]
+    Login(LoginCommand),
+    /// Show resource usage of units, like systemd-cgtop
+    Top(TopArgs),
+    /// Show the cgroup tree with its processes, like systemd-cgls
+    Cgls(CglsArgs),
+    /// Analyze units, like systemd-analyze
+    #[command(subcommand)]
+    Analyze(AnalyzeCommand),
+    /// Explain exit statuses (203/EXEC, 226/NAMESPACE, ...) by number or name
+    ExitStatus(ExitStatusArgs),
+    /// Show the running manager's internal state, for debugging
+    Dump,
+    /// Explain why a unit is (in)active: what pulls it in, what kept it
+    /// from starting, how its last run ended
+    Why(WhyArgs),
+    /// Restart userspace without rebooting the kernel, switching to the
+    /// root file system at /run/nextroot if one is mounted there
+    SoftReboot,
+    /// Power off, reboot or halt at a later time, warning logged-in users
+    /// (like shutdown(8))
+    Shutdown(ShutdownArgs),
+    #[command(flatten)]
+    Install(InstallCommand),
+}
+
+#[derive(clap::Subcommand)]
+enum CredsCommand {
+    /// Encrypt a credential for LoadCredentialEncrypted=
+    Encrypt {
+        /// Plaintext file ("-" for stdin)
+        input: String,
+        /// Output file ("-" for stdout)
+        output: String,
+        /// Credential name (default: output file name; required for stdout)
+        #[arg(long)]
+        name: Option<String>,
+    },
+    /// Decrypt a credential
+    Decrypt {
+        /// Encrypted file ("-" for stdin)
+        input: String,
+        /// Output file (default: stdout)
+        #[arg(default_value = "-")]
+        output: String,
+        /// Expected credential name (default: input file name; empty to skip)
+        #[arg(long)]
+        name: Option<String>,
+    },
 }
 
 /// Shared manager state accessible from IPC and D-Bus
@@ -109,13 +196,113 @@ type SharedManager = Arc<RwLock<Manager>>;
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args = Args::parse();
+    if let Some(root) = &args.root {
+        sysd::root::set(root);
+    }
+    if let Some(Command::Creds(command)) = &args.command {
+        if let Err(e) = run_creds_command(command) {
+            eprintln!("sysd creds: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Login(command)) = args.command {
+        if let Err(e) = run_login_command(command).await {
+            eprintln!("sysd login: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Top(top)) = args.command {
+        if let Err(e) = run_top_command(top, args.user) {
+            eprintln!("sysd top: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Cgls(cgls)) = args.command {
+        if let Err(e) = run_cgls_command(cgls) {
+            eprintln!("sysd cgls: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Analyze(command)) = args.command {
+        if let Err(e) = run_analyze_command(command, args.user).await {
+            eprintln!("sysd analyze: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::ExitStatus(exit_status)) = args.command {
+        if let Err(e) = run_exit_status_command(exit_status) {
+            eprintln!("sysd exit-status: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Dump) = args.command {
+        if let Err(e) = run_dump_command(args.user) {
+            eprintln!("sysd dump: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Why(why)) = args.command {
+        if let Err(e) = run_why_command(why, args.user) {
+            eprintln!("sysd why: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::SoftReboot) = args.command {
+        if let Err(e) = run_soft_reboot_command(args.user) {
+            eprintln!("sysd soft-reboot: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Shutdown(shutdown)) = args.command {
+        if let Err(e) = run_shutdown_command(shutdown, args.user) {
+            eprintln!("sysd shutdown: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(Command::Install(command)) = args.command {
+        if let Err(e) = run_install_command(command, args.user).await {
+            eprintln!("sysd: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
     let (is_pid1, user_mode, should_boot) = runtime_modes(&args);
-    initialize_environment(is_pid1, user_mode);
-    let mut manager = create_manager(user_mode);
+    let container = container_mode(&args, is_pid1, user_mode);
+    initialize_environment(is_pid1, user_mode, container);
+    // Before any unit is started, so none inherits NOTIFY_SOCKET
+    let supervisor = Supervisor::from_env().map(Arc::new);
+    let mut manager = create_manager(user_mode, container, args.unit_root.as_deref());
+    if is_pid1 {
+        manager.adopt_survivors().await;
+    }
+    manager.restore_state_history();
+    info!("Kernel features: {}", manager.features());
+    manager.set_auto_reload_units(args.auto_reload_units);
+    manager.set_shutdown_allowed(is_pid1 && !user_mode);
+    manager.start_unit_watcher();
+    let unit_files_rx = manager.take_unit_files_rx();
+    // Like systemd, only the system manager tracks the mount table
+    if !user_mode {
+        manager.start_mount_monitor();
+    }
+    let mount_table_rx = manager.take_mount_table_rx();
+    let mount_job_rx = manager.take_mount_job_rx();
     let socket_activation_rx = manager.take_socket_activation_rx();
     let timer_rx = manager.take_timer_rx();
     let path_rx = manager.take_path_rx();
     let oneshot_completion_rx = manager.take_oneshot_completion_rx();
+    let stop_event_rx = manager.take_stop_event_rx();
+    let states = manager.state_view();
     let manager: SharedManager = Arc::new(RwLock::new(manager));
     let shutdown_flag = Arc::new(AtomicBool::new(false));
     spawn_event_handlers(
@@ -124,7 +311,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         socket_activation_rx,
         timer_rx,
         oneshot_completion_rx,
+        stop_event_rx,
     );
+    if let Some(rx) = unit_files_rx {
+        spawn_manager_result_handler(
+            rx,
+            Arc::clone(&manager),
+            Arc::clone(&shutdown_flag),
+            "Unit file watcher handler stopping due to shutdown",
+            "Handling unit file changes failed",
+            |mgr, changed| Box::pin(mgr.handle_unit_files_changed(changed)),
+        );
+    }
+    if let Some(rx) = mount_table_rx {
+        spawn_manager_result_handler(
+            rx,
+            Arc::clone(&manager),
+            Arc::clone(&shutdown_flag),
+            "Mount table handler stopping due to shutdown",
+            "Handling mount table changes failed",
+            |mgr, changed| Box::pin(mgr.handle_mount_table_changed(changed)),
+        );
+    }
+    if let Some(rx) = mount_job_rx {
+        spawn_manager_result_handler(
+            rx,
+            Arc::clone(&manager),
+            Arc::clone(&shutdown_flag),
+            "Mount job handler stopping due to shutdown",
+            "Background mount failed",
+            |mgr, done| Box::pin(mgr.handle_mount_job_finished(done)),
+        );
+    }
     if let Some(rx) = path_rx {
         spawn_manager_result_handler(
             rx,
@@ -137,9 +355,99 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     spawn_dbus_retry_task(user_mode, Arc::clone(&manager), Arc::clone(&shutdown_flag));
     spawn_background_maintenance(Arc::clone(&manager));
-    spawn_signal_handler(is_pid1, Arc::clone(&manager), Arc::clone(&shutdown_flag));
-    maybe_spawn_boot_task(should_boot, Arc::clone(&manager));
-    serve_requests(user_mode, manager).await
+    if manager.read().await.persists_state_history() {
+        spawn_state_history_writer(Arc::clone(&manager));
+    }
+    spawn_signal_handler(
+        is_pid1,
+        container,
+        Arc::clone(&manager),
+        Arc::clone(&shutdown_flag),
+    );
+    if !is_pid1 {
+        spawn_exit_handler(
+            user_mode,
+            args.stop_all,
+            Arc::clone(&manager),
+            Arc::clone(&shutdown_flag),
+        );
+    }
+    let login_config = login_config(is_pid1 && !container);
+    spawn_input_handler(
+        login_config.clone(),
+        Arc::clone(&manager),
+        Arc::clone(&shutdown_flag),
+    );
+    spawn_autovt_handler(login_config, Arc::clone(&manager));
+    if let Some(supervisor) = &supervisor {
+        spawn_supervisor_notifier(Arc::clone(supervisor), states.clone());
+    }
+    if is_pid1 && !user_mode {
+        spawn_runlevel_recorder(states.clone());
+        spawn_shutdown_scheduler(Arc::clone(&manager), Arc::clone(&shutdown_flag), container);
+        spawn_tmpfiles_cleaner(Arc::clone(&manager));
+    }
+    maybe_spawn_boot_task(should_boot, Arc::clone(&manager), supervisor);
+    serve_requests(user_mode, manager, states).await
+}
+
+fn run_creds_command(command: &CredsCommand) -> Result<(), Box<dyn std::error::Error>> {
+    let host_key_path = &sysd::state_dir::StateDirs::system().credential_secret();
+    match command {
+        CredsCommand::Encrypt {
+            input,
+            output,
+            name,
+        } => {
+            let name = name.clone().unwrap_or_else(|| credential_name_of(output));
+            let host_key = sysd::creds::load_or_create_host_key(host_key_path)?;
+            let sealed = sysd::creds::encrypt(&host_key, &name, &read_input(input)?)?;
+            write_output(output, sealed.as_bytes())
+        }
+        CredsCommand::Decrypt {
+            input,
+            output,
+            name,
+        } => {
+            let name = name.clone().unwrap_or_else(|| credential_name_of(input));
+            let expected = Some(name.as_str()).filter(|name| !name.is_empty());
+            let host_key = sysd::creds::load_host_key(host_key_path)?;
+            let plaintext = sysd::creds::decrypt(&host_key, expected, &read_input(input)?)?;
+            write_output(output, &plaintext)
+        }
+    }
+}
+
+/// File name of `path` ("" for stdin/stdout)
+fn credential_name_of(path: &str) -> String {
+    match path {
+        "-" => String::new(),
+        _ => std::path::Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+    }
+}
+
+fn read_input(path: &str) -> std::io::Result<Vec<u8>> {
+    use std::io::Read;
+    match path {
+        "-" => {
+            let mut data = Vec::new();
+            std::io::stdin().read_to_end(&mut data)?;
+            Ok(data)
+        }
+        _ => std::fs::read(path),
+    }
+}
+
+fn write_output(path: &str, data: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+    use std::io::Write;
+    match path {
+        "-" => std::io::stdout().write_all(data)?,
+        _ => std::fs::write(path, data)?,
+    }
+    Ok(())
 }
 
 fn runtime_modes(args: &Args) -> (bool, bool, bool) {
@@ -149,15 +457,23 @@ fn runtime_modes(args: &Args) -> (bool, bool, bool) {
     (is_pid1, user_mode, should_boot)
 }
 
-fn initialize_environment(is_pid1: bool, user_mode: bool) {
+/// Container profile: forced with --container, or detected when PID 1
+fn container_mode(args: &Args, is_pid1: bool, user_mode: bool) -> bool {
+    !user_mode && (args.container || (is_pid1 && pid1::detect_container().is_some()))
+}
+
+fn initialize_environment(is_pid1: bool, user_mode: bool, container: bool) {
     setup_logging(user_mode);
     validate_mode(is_pid1, user_mode);
-    if is_pid1 {
+    if is_pid1 && container {
+        pid1::init_container();
+    } else if is_pid1 {
         initialize_pid1();
     }
     if user_mode {
         ensure_user_runtime_dir();
     }
+    prepare_state_dirs(user_mode);
 }
 
 fn validate_mode(is_pid1: bool, user_mode: bool) {
@@ -180,15 +496,32 @@ fn ensure_user_runtime_dir() {
     }
 }
 
-fn create_manager(user_mode: bool) -> Manager {
+/// Not fatal: /var may still be read-only this early, and everything that
+/// writes there creates its directory on demand as well
+fn prepare_state_dirs(user_mode: bool) {
+    if let Err(e) = sysd::state_dir::StateDirs::for_mode(user_mode).prepare() {
+        log::warn!("Failed to prepare state directories: {}", e);
+    }
+}
+
+fn create_manager(
+    user_mode: bool,
+    container: bool,
+    unit_root: Option<&std::path::Path>,
+) -> Manager {
     let mut manager = if user_mode {
         info!("Starting user service manager");
         Manager::new_user()
     } else {
         Manager::new()
     };
+    if let Some(unit_root) = unit_root {
+        manager.set_unit_root(unit_root);
+    }
     initialize_notify_socket(&mut manager);
-    if !user_mode {
+    manager.restore_scopes();
+    // Containers have no fstab to mount and no consoles for gettys
+    if !user_mode && !container {
         load_legacy_mount_and_getty_units(&mut manager);
     }
     manager
@@ -206,6 +539,7 @@ fn initialize_notify_socket(manager: &mut Manager) {
 fn load_legacy_mount_and_getty_units(manager: &mut Manager) {
     log_fstab_load_result(manager.load_fstab());
     log_getty_load_result(manager.load_gettys());
+    log_sysv_load_result(manager.load_sysv_scripts());
 }
 
 fn log_fstab_load_result(result: Result<usize, sysd::manager::ManagerError>) {
@@ -224,6 +558,14 @@ fn log_getty_load_result(result: Result<usize, sysd::manager::ManagerError>) {
     }
 }
 
+fn log_sysv_load_result(result: Result<usize, sysd::manager::ManagerError>) {
+    match result {
+        Ok(count) if count > 0 => info!("Loaded {} services from SysV init scripts", count),
+        Ok(_) => log::debug!("No SysV init scripts to load"),
+        Err(e) => log::warn!("Failed to load SysV init scripts: {}", e),
+    }
+}
+
 type ManagerResultFuture<'a> =
     Pin<Box<dyn Future<Output = Result<(), sysd::manager::ManagerError>> + Send + 'a>>;
 
@@ -233,6 +575,7 @@ fn spawn_event_handlers(
     socket_activation_rx: Option<mpsc::Receiver<sysd::manager::SocketActivation>>,
     timer_rx: Option<mpsc::Receiver<sysd::manager::TimerFired>>,
     oneshot_completion_rx: Option<mpsc::Receiver<sysd::manager::OneshotCompletion>>,
+    stop_event_rx: Option<mpsc::Receiver<sysd::manager::StopEvent>>,
 ) {
     spawn_socket_handler(
         socket_activation_rx,
@@ -240,7 +583,12 @@ fn spawn_event_handlers(
         Arc::clone(&shutdown_flag),
     );
     spawn_timer_handler(timer_rx, Arc::clone(&manager), Arc::clone(&shutdown_flag));
-    spawn_oneshot_handler(oneshot_completion_rx, manager, shutdown_flag);
+    spawn_oneshot_handler(
+        oneshot_completion_rx,
+        Arc::clone(&manager),
+        Arc::clone(&shutdown_flag),
+    );
+    spawn_stop_event_handler(stop_event_rx, manager, shutdown_flag);
 }
 
 fn spawn_socket_handler(
@@ -302,6 +650,29 @@ fn spawn_oneshot_handler(
     );
 }
 
+fn spawn_stop_event_handler(
+    stop_event_rx: Option<mpsc::Receiver<sysd::manager::StopEvent>>,
+    manager: SharedManager,
+    shutdown_flag: Arc<AtomicBool>,
+) {
+    let Some(rx) = stop_event_rx else {
+        return;
+    };
+    spawn_manager_result_handler(
+        rx,
+        manager,
+        shutdown_flag,
+        "Stop event handler stopping due to shutdown",
+        "Stop event handling failed",
+        |mgr, event| {
+            Box::pin(async move {
+                mgr.handle_stop_event(event).await;
+                Ok(())
+            })
+        },
+    );
+}
+
 fn spawn_manager_result_handler<T, F>(
     mut rx: mpsc::Receiver<T>,
     manager: SharedManager,
@@ -323,6 +694,7 @@ fn spawn_manager_result_handler<T, F>(
             if let Err(e) = handler(&mut mgr, msg).await {
                 log::error!("{}: {}", error_message, e);
             }
+            mgr.publish_states();
         }
     });
 }
@@ -396,13 +768,18 @@ async fn run_session_dbus_retry_loop(manager: SharedManager, shutdown_flag: Arc<
     }
 }
 
-fn spawn_signal_handler(is_pid1: bool, manager: SharedManager, shutdown_flag: Arc<AtomicBool>) {
+fn spawn_signal_handler(
+    is_pid1: bool,
+    container: bool,
+    manager: SharedManager,
+    shutdown_flag: Arc<AtomicBool>,
+) {
     let Some(mut signal_rx) = signal_receiver(is_pid1) else {
         return;
     };
     tokio::spawn(async move {
         while let Some(sig) = signal_rx.recv().await {
-            handle_signal(sig, &manager, &shutdown_flag).await;
+            handle_signal(sig, container, &manager, &shutdown_flag).await;
         }
     });
 }
@@ -420,21 +797,35 @@ fn signal_receiver(is_pid1: bool) -> Option<mpsc::Receiver<SysdSignal>> {
     }
 }
 
-async fn handle_signal(sig: SysdSignal, manager: &SharedManager, shutdown_flag: &Arc<AtomicBool>) {
+async fn handle_signal(
+    sig: SysdSignal,
+    container: bool,
+    manager: &SharedManager,
+    shutdown_flag: &Arc<AtomicBool>,
+) {
     match sig {
         SysdSignal::Child => {}
         SysdSignal::Term => {
-            shutdown_from_signal(manager, shutdown_flag, ShutdownType::Poweroff).await
+            shutdown_from_signal(manager, shutdown_flag, container, ShutdownType::Poweroff).await
+        }
+        // Ctrl-C in `docker run -it` stops the container rather than rebooting it
+        SysdSignal::Int if container => {
+            info!("Received SIGINT in container, initiating poweroff");
+            shutdown_system(manager, shutdown_flag, container, ShutdownType::Poweroff).await
+        }
+        SysdSignal::Int => {
+            shutdown_from_signal(manager, shutdown_flag, container, ShutdownType::Reboot).await
         }
-        SysdSignal::Int => shutdown_from_signal(manager, shutdown_flag, ShutdownType::Reboot).await,
         SysdSignal::Hup => reload_units_from_signal(manager).await,
         SysdSignal::Usr1 => dump_state_from_signal(manager).await,
+        SysdSignal::SoftReboot => soft_reboot_system(manager, shutdown_flag).await,
     }
 }
 
 async fn shutdown_from_signal(
     manager: &SharedManager,
     shutdown_flag: &Arc<AtomicBool>,
+    container: bool,
     shutdown_type: ShutdownType,
 ) {
     match shutdown_type {
@@ -442,11 +833,194 @@ async fn shutdown_from_signal(
         ShutdownType::Reboot => info!("Received SIGINT, initiating reboot"),
         ShutdownType::Halt => info!("Received signal requesting halt"),
     }
+    shutdown_system(manager, shutdown_flag, container, shutdown_type).await;
+}
+
+async fn shutdown_system(
+    manager: &SharedManager,
+    shutdown_flag: &Arc<AtomicBool>,
+    container: bool,
+    shutdown_type: ShutdownType,
+) {
     shutdown_flag.store(true, Ordering::Relaxed);
-    stop_all_services(manager).await;
+    record_shutdown_runlevel(shutdown_type);
+    stop_all_services(manager, &[]).await;
+    manager.read().await.save_state_history();
+    if container {
+        pid1::exit_container(shutdown_type).await;
+    }
     pid1::shutdown(shutdown_type).await;
 }
 
+/// Announce the shutdown `sysd shutdown` scheduled and carry it out once due
+fn spawn_shutdown_scheduler(
+    manager: SharedManager,
+    shutdown_flag: Arc<AtomicBool>,
+    container: bool,
+) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            let due = manager.write().await.process_scheduled_shutdown();
+            if let Some(shutdown_type) = due {
+                shutdown_system(&manager, &shutdown_flag, container, shutdown_type).await;
+            }
+        }
+    });
+}
+
+/// Stop everything but the units with SurviveFinalKillSignal=yes and
+/// re-execute sysd, which adopts them again
+async fn soft_reboot_system(manager: &SharedManager, shutdown_flag: &Arc<AtomicBool>) {
+    info!("Received SIGRTMIN+7, initiating soft-reboot");
+    shutdown_flag.store(true, Ordering::Relaxed);
+    let survivors = manager.read().await.soft_reboot_survivors();
+    if !survivors.is_empty() {
+        info!(
+            "Keeping {} running across soft-reboot",
+            survivors.join(", ")
+        );
+    }
+    stop_all_services(manager, &survivors).await;
+    manager.read().await.save_state_history();
+    let spared = manager.read().await.serialize_survivors();
+    pid1::soft_reboot(&spared).await;
+}
+
+/// Exit on SIGTERM/SIGINT when sysd is not PID 1
+fn spawn_exit_handler(
+    user_mode: bool,
+    stop_all: bool,
+    manager: SharedManager,
+    shutdown_flag: Arc<AtomicBool>,
+) {
+    let signals = signal(SignalKind::terminate())
+        .and_then(|sigterm| Ok((sigterm, signal(SignalKind::interrupt())?)));
+    let (mut sigterm, mut sigint) = match signals {
+        Ok(signals) => signals,
+        Err(e) => {
+            log::error!("Failed to set up exit signal handlers: {}", e);
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        let name = tokio::select! {
+            _ = sigterm.recv() => "SIGTERM",
+            _ = sigint.recv() => "SIGINT",
+        };
+        info!("Received {}, exiting", name);
+        exit_daemon(&manager, &shutdown_flag, user_mode, stop_all).await;
+    });
+}
+
+/// Release sockets and watchers first, so nothing is activated while units
+/// stop, then stop units if asked to and remove the control socket
+async fn exit_daemon(
+    manager: &SharedManager,
+    shutdown_flag: &Arc<AtomicBool>,
+    user_mode: bool,
+    stop_all: bool,
+) -> ! {
+    shutdown_flag.store(true, Ordering::Relaxed);
+    manager.write().await.release_for_exit();
+    if stop_all {
+        stop_all_services(manager, &[]).await;
+    } else {
+        info!("Leaving services running");
+    }
+    manager.read().await.save_state_history();
+    let _ = std::fs::remove_file(socket_path(user_mode));
+    std::process::exit(0);
+}
+
+/// logind.conf settings sysd acts on (PID 1 outside containers only)
+fn login_config(handles_hardware: bool) -> Option<LoginConfig> {
+    if !handles_hardware {
+        return None;
+    }
+    LoginConfig::load(std::path::Path::new(LOGIN_CONFIG_PATH))
+}
+
+/// Act on the power key and lid switch as configured in logind.conf
+fn spawn_input_handler(
+    config: Option<LoginConfig>,
+    manager: SharedManager,
+    shutdown_flag: Arc<AtomicBool>,
+) {
+    let Some(config) = config else {
+        return;
+    };
+    let Some(mut events) = pid1::spawn_input_watcher() else {
+        return;
+    };
+    tokio::spawn(async move {
+        while let Some(event) = events.recv().await {
+            let action = match event {
+                InputEvent::PowerKey => config.handle_power_key,
+                InputEvent::LidClosed => config.handle_lid_switch,
+            };
+            info!("{:?}: {:?}", event, action);
+            handle_input_action(action, &manager, &shutdown_flag).await;
+        }
+    });
+}
+
+async fn handle_input_action(
+    action: HandleAction,
+    manager: &SharedManager,
+    shutdown_flag: &Arc<AtomicBool>,
+) {
+    let mode = match action {
+        HandleAction::Ignore => return,
+        HandleAction::Poweroff => {
+            return shutdown_system(manager, shutdown_flag, false, ShutdownType::Poweroff).await
+        }
+        HandleAction::Reboot => {
+            return shutdown_system(manager, shutdown_flag, false, ShutdownType::Reboot).await
+        }
+        HandleAction::Halt => {
+            return shutdown_system(manager, shutdown_flag, false, ShutdownType::Halt).await
+        }
+        HandleAction::Suspend => SleepMode::Suspend,
+        HandleAction::Hibernate => SleepMode::Hibernate,
+        HandleAction::HybridSleep => SleepMode::HybridSleep,
+    };
+    if let Err(e) = Manager::sleep(manager, mode).await {
+        log::error!("Failed to {}: {}", mode.as_str(), e);
+    }
+    manager.write().await.publish_states();
+}
+
+/// Spawn gettys on VTs the user switches to, and on ReserveVT= right away
+fn spawn_autovt_handler(config: Option<LoginConfig>, manager: SharedManager) {
+    let Some(config) = config else {
+        return;
+    };
+    let switches = pid1::spawn_vt_watcher();
+    tokio::spawn(async move {
+        if config.reserve_vt > 0 {
+            start_autovt(&manager, config.reserve_vt).await;
+        }
+        let Some(mut switches) = switches else {
+            return;
+        };
+        while let Some(vt) = switches.recv().await {
+            if config.autovt_allowed(vt) {
+                start_autovt(&manager, vt).await;
+            }
+        }
+    });
+}
+
+async fn start_autovt(manager: &SharedManager, vt: u32) {
+    let mut mgr = manager.write().await;
+    if let Err(e) = mgr.start_autovt(vt).await {
+        log::warn!("Failed to start getty on VT {}: {}", vt, e);
+    }
+    mgr.publish_states();
+}
+
 async fn reload_units_from_signal(manager: &SharedManager) {
     info!("Received SIGHUP, reloading unit files");
     let mut mgr = manager.write().await;
@@ -473,18 +1047,69 @@ fn spawn_background_maintenance(manager: SharedManager) {
             mgr.process_notify().await;
             mgr.process_dbus_ready().await;
             mgr.process_watchdog().await;
+            mgr.process_health_checks().await;
+            mgr.process_endpoint_waits().await;
             mgr.reap().await;
             mgr.process_restarts().await;
+            mgr.process_rolling_restarts().await;
+            mgr.process_pending_targets();
+            mgr.publish_states();
+        }
+    });
+}
+
+/// Save the unit state histories every minute, so a crash loses little
+/// (PersistStateHistory=yes)
+fn spawn_state_history_writer(manager: SharedManager) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            manager.read().await.save_state_history();
         }
     });
 }
 
-fn maybe_spawn_boot_task(should_boot: bool, manager: SharedManager) {
+/// Clean tmpfiles.d directories by age 15 minutes after boot and daily after
+/// that, as systemd-tmpfiles-clean.timer does; left to that timer when it is
+/// installed
+fn spawn_tmpfiles_cleaner(manager: SharedManager) {
+    tokio::spawn(async move {
+        let installed = manager
+            .read()
+            .await
+            .unit_file_exists("systemd-tmpfiles-clean.timer");
+        if installed {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_secs(15 * 60)).await;
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(86400));
+        loop {
+            interval.tick().await;
+            let cleaned = tokio::task::spawn_blocking(|| {
+                AgeRules::load().clean(std::time::SystemTime::now())
+            })
+            .await;
+            if let Ok(removed) = cleaned {
+                info!("tmpfiles cleanup removed {} entries", removed);
+            }
+        }
+    });
+}
+
+fn maybe_spawn_boot_task(
+    should_boot: bool,
+    manager: SharedManager,
+    supervisor: Option<Arc<Supervisor>>,
+) {
     if !should_boot {
+        notify_ready(supervisor.as_deref());
         return;
     }
     tokio::spawn(async move {
         boot_to_default_target(&manager).await;
+        notify_ready(supervisor.as_deref());
     });
 }
 
@@ -508,6 +1133,14 @@ async fn resolve_boot_target_and_plan(manager: &SharedManager) -> Option<(String
         let mgr = manager.read().await;
         match mgr.get_default_target() {
             Ok(target) => target,
+            // A bare system without default.target boots the built-in one
+            Err(sysd::manager::ManagerError::NotFound(_)) if !mgr.is_user_mode() => {
+                log::warn!(
+                    "No default.target, booting {}",
+                    sysd::units::BUILTIN_DEFAULT_TARGET
+                );
+                sysd::units::BUILTIN_DEFAULT_TARGET.to_string()
+            }
             Err(e) => {
                 log::error!("No default target found: {}", e);
                 return None;
@@ -529,9 +1162,15 @@ async fn start_boot_plan_units(manager: &SharedManager, plan: &[String]) {
     for unit_name in plan {
         eprintln!("sysd: Starting {}", unit_name);
         log::info!("Starting {}", unit_name);
+        let endpoints = manager.read().await.endpoints_before(unit_name);
+        endpoints.await;
         let mut mgr = manager.write().await;
         match mgr.start(unit_name).await {
             Ok(()) => log::info!("Started {}", unit_name),
+            // Kept running across a soft-reboot
+            Err(sysd::manager::ManagerError::AlreadyActive(_)) => {
+                log::info!("{} already active", unit_name)
+            }
             Err(e) => {
                 eprintln!("sysd: FAILED to start {}: {}", unit_name, e);
                 log::warn!("Failed to start {}: {}", unit_name, e);
@@ -543,6 +1182,7 @@ async fn start_boot_plan_units(manager: &SharedManager, plan: &[String]) {
 async fn serve_requests(
     user_mode: bool,
     manager: SharedManager,
+    states: StateView,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let sock_path = socket_path(user_mode);
     let server = Server::bind(&sock_path)?;
@@ -556,7 +1196,10 @@ async fn serve_requests(
             Ok((conn, caller)) => {
                 let manager = Arc::clone(&manager);
                 tokio::spawn(sysd_request_handlers::handle_connection(
-                    conn, caller, manager,
+                    conn,
+                    caller,
+                    manager,
+                    states.clone(),
                 ));
             }
             Err(e) => log::error!("accept error: {}", e),
@@ -564,24 +1207,46 @@ async fn serve_requests(
     }
 }
 
-/// Stop all running services before shutdown
-async fn stop_all_services(manager: &SharedManager) {
-    let mgr = manager.read().await;
+/// Stop all running services but `keep` before shutdown, all at once; the
+/// manager is only locked to begin and to finish the mass stop
+async fn stop_all_services(manager: &SharedManager, keep: &[String]) {
+    let mut mgr = manager.write().await;
     let running: Vec<String> = mgr
         .list()
-        .filter(|(_, state)| state.is_active())
+        .filter(|(name, state)| state.is_active() && !keep.contains(name))
         .map(|(name, _)| name.clone())
         .collect();
+    let stop = mgr.begin_mass_stop(&running);
     drop(mgr);
 
-    for name in running {
-        info!("Stopping {} for shutdown", name);
-        let mut mgr = manager.write().await;
-        if let Err(e) = mgr.stop(&name).await {
-            log::warn!("Failed to stop {}: {}", name, e);
-        }
-    }
+    info!("Stopping {} service(s) for shutdown", stop.len());
+    let outcome = stop.run().await;
+    manager.write().await.finish_mass_stop(outcome).await;
 }
 
+#[path = "sysd/analyze.rs"]
+mod sysd_analyze;
+#[path = "sysd/cgls.rs"]
+mod sysd_cgls;
+#[path = "sysd/dump.rs"]
+mod sysd_dump;
+#[path = "sysd/exit_status.rs"]
+mod sysd_exit_status;
+#[path = "sysd/install.rs"]
+mod sysd_install;
+#[path = "sysd/login.rs"]
+mod sysd_login;
 #[path = "sysd/request_handlers.rs"]
 mod sysd_request_handlers;
+#[path = "sysd/runlevel.rs"]
+mod sysd_runlevel;
+#[path = "sysd/shutdown.rs"]
+mod sysd_shutdown;
+#[path = "sysd/soft_reboot.rs"]
+mod sysd_soft_reboot;
+#[path = "sysd/supervisor.rs"]
+mod sysd_supervisor;
+#[path = "sysd/top.rs"]
+mod sysd_top;
+#[path = "sysd/why.rs"]
+mod sysd_why;
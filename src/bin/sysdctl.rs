@@ -6,7 +6,10 @@
 use clap::{Parser, Subcommand};
 use peercred_ipc::Client;
 use std::path::PathBuf;
+use sysd::manager::enablement_succeeds;
+use sysd::pid1;
 use sysd::protocol::{socket_path, Request, Response};
+use sysd::units::has_glob_chars;
 
 #[derive(Parser)]
 #[command(name = "sysdctl")]
@@ -30,13 +33,22 @@ enum Command {
         /// Filter by unit type (service, socket, mount, slice, target)
         #[arg(short = 't', long = "type")]
         unit_type: Option<String>,
+        /// Only units matching these names or patterns ("getty@*")
+        patterns: Vec<String>,
     },
 
-    /// Start a unit
+    /// List listening sockets and the units they activate
+    ListSockets,
+
+    /// Start units (several are started one after the other, with progress)
     Start {
-        /// Unit name (e.g., "docker" or "docker.service")
-        name: String,
-        /// Wait for the unit to exit (become inactive or failed)
+        /// Unit names (e.g., "docker" or "docker.service"); braces expand
+        /// ("getty@{tty1,tty2}") and wildcards match loaded units
+        #[arg(required = true)]
+        names: Vec<String>,
+        /// Return only once the start finished (active, exited or failed;
+        /// a target once its required units are up), exiting with the
+        /// unit's exit code if it failed
         #[arg(long)]
         wait: bool,
         /// Job mode (fail, replace, replace-irreversibly, isolate, ignore-dependencies)
@@ -44,34 +56,85 @@ enum Command {
         job_mode: String,
     },
 
-    /// Stop a unit
+    /// Stop units
     Stop {
+        /// Unit names, with braces and wildcards as for start
+        #[arg(required = true)]
+        names: Vec<String>,
+        /// Return only once the units are inactive or failed
+        #[arg(long)]
+        wait: bool,
+    },
+
+    /// Send a signal to the processes of a unit
+    Kill {
         /// Unit name
         name: String,
+        /// Processes to signal: main, control or all
+        #[arg(long = "kill-whom", default_value = "all")]
+        kill_whom: String,
+        /// Signal name or number (e.g. SIGHUP, HUP, 1)
+        #[arg(short = 's', long, default_value = "SIGTERM", value_parser = parse_signal)]
+        signal: i32,
     },
 
-    /// Restart a unit
-    Restart {
+    /// Remove runtime, state, cache, log or configuration directories of a stopped unit
+    Clean {
         /// Unit name
         name: String,
+        /// What to remove: configuration, state, cache, logs, runtime, fdstore or all
+        /// (default: cache, runtime and fdstore)
+        #[arg(long, value_delimiter = ',')]
+        what: Vec<String>,
     },
 
-    /// Enable a unit to start at boot
-    Enable {
+    /// Change resource limits of a unit, e.g. MemoryMax=1G CPUQuota=50% TasksMax=64
+    SetProperty {
         /// Unit name
         name: String,
+        /// Property assignments (MemoryMax=, CPUQuota=, TasksMax=)
+        #[arg(required = true)]
+        assignments: Vec<String>,
+        /// Only until the next reboot (drop-in below /run)
+        #[arg(long)]
+        runtime: bool,
     },
 
-    /// Disable a unit from starting at boot
+    /// Restart units
+    Restart {
+        /// Unit names, with braces and wildcards as for start
+        #[arg(required = true)]
+        names: Vec<String>,
+        /// Return only once the start finished, as for start --wait
+        #[arg(long)]
+        wait: bool,
+        /// Start the new instance of a socket-activated service before
+        /// stopping the old one, once the new one is ready
+        #[arg(long)]
+        rolling: bool,
+    },
+
+    /// Enable units to start at boot
+    Enable {
+        /// Unit names, with braces and wildcards as for start
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+
+    /// Disable units from starting at boot
     Disable {
-        /// Unit name
-        name: String,
+        /// Unit names, with braces and wildcards as for start
+        #[arg(required = true)]
+        names: Vec<String>,
     },
 
-    /// Check if a unit is enabled
+    /// Check if a unit is enabled (exit 0 if enabled or static, 1 otherwise)
     IsEnabled {
         /// Unit name
         name: String,
+        /// Quiet mode - no output, just exit code
+        #[arg(short, long)]
+        quiet: bool,
     },
 
     /// Show unit status
@@ -87,20 +150,38 @@ enum Command {
     },
 
     /// Show the default boot target
+    #[command(visible_alias = "get-default")]
     GetBootTarget,
 
+    /// Make a target the default boot target (default.target)
+    SetDefault {
+        /// Target unit name
+        target: String,
+    },
+
     /// Reload unit files from disk
     Reload,
 
     /// Sync units (reload + restart changed)
     Sync,
 
-    /// Switch to a target (stop unrelated units)
+    /// Switch to a target (stop unrelated units), or back to the units of
+    /// a snapshot
+    #[command(alias = "isolate")]
     SwitchTarget {
-        /// Target name (e.g., "multi-user.target")
+        /// Target name (e.g., "multi-user.target") or NAME.snapshot
         target: String,
     },
 
+    /// Record the active units, to return to them with isolate NAME.snapshot
+    Snapshot {
+        /// Snapshot name (default: snapshot-N)
+        name: Option<String>,
+    },
+
+    /// Forget a snapshot
+    DeleteSnapshot { name: String },
+
     /// Parse a unit file locally (doesn't require daemon)
     Parse {
         /// Path to the unit file
@@ -119,8 +200,30 @@ enum Command {
         names: Vec<String>,
     },
 
-    /// Reset failed state of all units
-    ResetFailed,
+    /// Set environment variables in the service manager
+    SetEnvironment {
+        /// Assignments in KEY=VALUE form
+        #[arg(required = true)]
+        assignments: Vec<String>,
+    },
+
+    /// Show the service manager environment
+    ShowEnvironment,
+
+    /// Reset failed state of units (all if none given)
+    ResetFailed {
+        /// Unit names, with braces and wildcards as for start
+        names: Vec<String>,
+    },
+
+    /// Suspend the system
+    Suspend,
+
+    /// Hibernate the system
+    Hibernate,
+
+    /// Hibernate and suspend the system
+    HybridSleep,
 
     /// Check if a unit is active (exit 0 if active, 3 if inactive/failed)
     IsActive {
@@ -130,10 +233,41 @@ enum Command {
         #[arg(short, long)]
         quiet: bool,
     },
+
+    /// Check if a unit is failed (exit 0 if failed, 1 otherwise)
+    IsFailed {
+        /// Unit name
+        name: String,
+        /// Quiet mode - no output, just exit code
+        #[arg(short, long)]
+        quiet: bool,
+    },
+
+    /// Print the previous and current runlevel recorded in utmp (e.g. "N 5")
+    Runlevel,
+
+    /// Arm faults for a unit to test restart logic (sysd built with the
+    /// fault-injection feature only)
+    #[command(hide = true)]
+    InjectFault {
+        /// Unit name
+        name: String,
+        /// exit-code=N, delay-ready=SECS, drop-watchdog, fail-cgroup or clear
+        #[arg(required = true)]
+        faults: Vec<String>,
+    },
+
+    /// Change the runlevel like sysvinit's telinit: 0 powers off, 6 reboots,
+    /// 1 or S switches to rescue.target, 2-4 to multi-user.target, 5 to
+    /// graphical.target, and q reloads unit files
+    Telinit {
+        /// Runlevel (0-6 or S) or q
+        runlevel: char,
+    },
 }
 
 fn main() {
-    let args = Args::parse();
+    let args = Args::parse_from(sysv_arguments(std::env::args().collect()));
     let user_mode = args.user;
 
     if let Command::Parse { path } = args.command {
@@ -150,45 +284,288 @@ fn main() {
 
 fn build_request_or_exit(command: Command, user_mode: bool) -> Option<Request> {
     match command {
-        Command::IsActive { name, quiet } => handle_is_active_or_exit(user_mode, name, quiet),
+        Command::IsActive { name, quiet } => {
+            let request = Request::IsActive { name };
+            query_state_or_exit(user_mode, request, quiet, |state| state == "active", 3)
+        }
+        Command::IsFailed { name, quiet } => {
+            let request = Request::IsActive { name };
+            query_state_or_exit(user_mode, request, quiet, |state| state == "failed", 1)
+        }
+        Command::IsEnabled { name, quiet } => {
+            let request = Request::IsEnabled { name };
+            query_state_or_exit(user_mode, request, quiet, enablement_succeeds, 1)
+        }
         Command::Parse { .. } => unreachable!(),
+        Command::Runlevel => print_runlevel_and_exit(),
+        Command::Telinit { runlevel } => telinit_request(runlevel, user_mode),
+        Command::Start { names, wait, .. } if is_many_units(&names) => {
+            if wait {
+                return for_each_unit_or_exit(user_mode, names, &[], |name| {
+                    Request::StartAndWait { name }
+                });
+            }
+            start_many_or_exit(user_mode, names);
+            None
+        }
+        // Matches that are not running have nothing to stop
+        Command::Stop { names, wait } if is_many_units(&names) => {
+            let states = &["active", "activating"];
+            for_each_unit_or_exit(user_mode, names, states, |name| stop_request(name, wait))
+        }
+        Command::Restart {
+            names,
+            wait,
+            rolling,
+        } if is_many_units(&names) => for_each_unit_or_exit(user_mode, names, &[], |name| {
+            restart_request(name, wait, rolling)
+        }),
+        Command::Enable { names } if is_many_units(&names) => {
+            for_each_unit_or_exit(user_mode, names, &[], |name| Request::Enable { name })
+        }
+        Command::Disable { names } if is_many_units(&names) => {
+            for_each_unit_or_exit(user_mode, names, &[], |name| Request::Disable { name })
+        }
+        Command::ResetFailed { names } if !names.is_empty() => {
+            for_each_unit_or_exit(user_mode, names, &["failed"], |name| {
+                Request::ResetFailedUnit { name }
+            })
+        }
         command => Some(build_regular_request(command, user_mode)),
     }
 }
 
+/// Accept sysvinit's command lines: run as `telinit` or `runlevel` (through
+/// a symlink) the program is that command, and a bare runlevel
+/// (`sysdctl 5`) means `sysdctl telinit 5`
+fn sysv_arguments(mut args: Vec<String>) -> Vec<String> {
+    let program = args
+        .first()
+        .and_then(|arg| std::path::Path::new(arg).file_name())
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let command = match program {
+        "telinit" | "runlevel" => program.to_string(),
+        _ if args.get(1).is_some_and(|arg| is_runlevel_argument(arg)) => "telinit".to_string(),
+        _ => return args,
+    };
+    args.insert(1, command);
+    args
+}
+
+fn is_runlevel_argument(arg: &str) -> bool {
+    let mut chars = arg.chars();
+    matches!(
+        (chars.next(), chars.next()),
+        (Some('0'..='6' | 's' | 'S' | 'q' | 'Q'), None)
+    )
+}
+
+fn print_runlevel_and_exit() -> ! {
+    match pid1::read_runlevel() {
+        Some((previous, current)) => {
+            println!("{} {}", previous.unwrap_or('N'), current);
+            std::process::exit(0);
+        }
+        None => {
+            println!("unknown");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// What telinit does for `runlevel`: 0 and 6 ask init to power off or reboot
+/// (the SIGTERM and SIGINT it shuts down on), q reloads unit files, and the
+/// others switch to the target of the runlevel
+fn telinit_request(runlevel: char, user_mode: bool) -> Option<Request> {
+    use nix::sys::signal::{kill, Signal};
+
+    if matches!(runlevel, 'q' | 'Q') {
+        return Some(Request::ReloadUnitFiles);
+    }
+    let Some(target) = pid1::runlevel_target(runlevel) else {
+        print_error_and_exit(&format!("unknown runlevel: {}", runlevel));
+        return None;
+    };
+    if user_mode {
+        print_error_and_exit("runlevels belong to the system manager");
+    }
+    let signal = match runlevel {
+        '0' => Signal::SIGTERM,
+        '6' => Signal::SIGINT,
+        _ => {
+            return Some(Request::SwitchTarget {
+                target: target.to_string(),
+            })
+        }
+    };
+    if let Err(e) = kill(nix::unistd::Pid::from_raw(1), signal) {
+        print_error_and_exit(&format!("failed to signal init: {}", e));
+    }
+    None
+}
+
+/// Whether the unit arguments name more than one unit, or may
+fn is_many_units(names: &[String]) -> bool {
+    names.len() > 1
+        || names
+            .iter()
+            .any(|name| name.contains('{') || has_glob_chars(name))
+}
+
+/// Expand the patterns with the manager, then send one request per unit;
+/// failures are reported per unit and make sysdctl exit with 1 at the end
+/// (with the exit code of a unit that failed, for --wait requests)
+///
+/// With `states`, only units in one of them are acted on, and finding none
+/// is not an error.
+fn for_each_unit_or_exit(
+    user_mode: bool,
+    patterns: Vec<String>,
+    states: &[&str],
+    request: impl Fn(String) -> Request,
+) -> Option<Request> {
+    let sock_path = socket_path(user_mode);
+    let expand = Request::ExpandUnits {
+        patterns,
+        states: states.iter().map(|state| state.to_string()).collect(),
+    };
+    let names = match Client::call(&sock_path, &expand) {
+        Ok(Response::UnitNames(names)) => names,
+        Ok(response) => {
+            print_response(response);
+            return None;
+        }
+        Err(error) => {
+            handle_daemon_error(user_mode, &error.to_string());
+            return None;
+        }
+    };
+    if names.is_empty() && states.is_empty() {
+        print_error_and_exit("no units match");
+    }
+    let mut status = 0;
+    for name in names {
+        match Client::call(&sock_path, &request(name.clone())) {
+            Ok(Response::Error(message)) => {
+                eprintln!("{}: {}", name, message);
+                status = 1;
+            }
+            Ok(Response::UnitFailed {
+                reason, exit_code, ..
+            }) => {
+                eprintln!("{} failed: {}", name, reason);
+                status = unit_failed_status(exit_code);
+            }
+            Ok(response) => print_response(response),
+            Err(error) => handle_daemon_error(user_mode, &error.to_string()),
+        }
+    }
+    if status != 0 {
+        std::process::exit(status);
+    }
+    None
+}
+
 fn build_regular_request(command: Command, user_mode: bool) -> Request {
     match command {
         Command::List {
             user: list_user,
             unit_type,
+            patterns,
         } => Request::List {
             user: list_user || user_mode,
             unit_type,
+            patterns,
         },
         Command::Start {
-            name,
+            mut names,
             wait,
             job_mode,
-        } => start_request(name, wait, &job_mode),
-        Command::Stop { name } => Request::Stop { name },
-        Command::Restart { name } => Request::Restart { name },
-        Command::Enable { name } => Request::Enable { name },
-        Command::Disable { name } => Request::Disable { name },
-        Command::IsEnabled { name } => Request::IsEnabled { name },
+        } => start_request(names.remove(0), wait, &job_mode),
+        Command::ListSockets => Request::ListSockets,
+        Command::Stop { mut names, wait } => stop_request(names.remove(0), wait),
+        Command::Kill {
+            name,
+            kill_whom,
+            signal,
+        } => Request::Kill {
+            name,
+            whom: kill_whom,
+            signal,
+        },
+        Command::Clean { name, what } => Request::Clean { name, what },
+        Command::SetProperty {
+            name,
+            assignments,
+            runtime,
+        } => Request::SetProperty {
+            name,
+            assignments,
+            runtime,
+        },
+        Command::Restart {
+            mut names,
+            wait,
+            rolling,
+        } => restart_request(names.remove(0), wait, rolling),
+        Command::Enable { mut names } => Request::Enable {
+            name: names.remove(0),
+        },
+        Command::Disable { mut names } => Request::Disable {
+            name: names.remove(0),
+        },
         Command::Status { name } => Request::Status { name },
         Command::Deps { name } => Request::Deps { name },
         Command::GetBootTarget => Request::GetBootTarget,
+        Command::SetDefault { target } => Request::SetDefaultTarget { target },
         Command::Reload => Request::ReloadUnitFiles,
         Command::Sync => Request::SyncUnits,
         Command::SwitchTarget { target } => Request::SwitchTarget { target },
+        Command::Snapshot { name } => Request::Snapshot { name },
+        Command::DeleteSnapshot { name } => Request::DeleteSnapshot { name },
         Command::Ping => Request::Ping,
         Command::ImportEnvironment => Request::ImportEnvironment {
             vars: std::env::vars().collect(),
         },
         Command::UnsetEnvironment { names } => Request::UnsetEnvironment { names },
-        Command::ResetFailed => Request::ResetFailed,
-        Command::IsActive { .. } | Command::Parse { .. } => unreachable!(),
+        Command::SetEnvironment { assignments } => Request::SetEnvironment { assignments },
+        Command::ShowEnvironment => Request::ShowEnvironment,
+        Command::ResetFailed { .. } => Request::ResetFailed,
+        Command::InjectFault { name, faults } => Request::InjectFault { name, faults },
+        Command::Suspend => sleep_request("suspend"),
+        Command::Hibernate => sleep_request("hibernate"),
+        Command::HybridSleep => sleep_request("hybrid-sleep"),
+        Command::IsActive { .. }
+        | Command::IsFailed { .. }
+        | Command::IsEnabled { .. }
+        | Command::Runlevel
+        | Command::Telinit { .. }
+        | Command::Parse { .. } => unreachable!(),
+    }
+}
+
+fn sleep_request(mode: &str) -> Request {
+    Request::Sleep {
+        mode: mode.to_string(),
+    }
+}
+
+fn parse_signal(s: &str) -> Result<i32, String> {
+    use std::str::FromStr;
+
+    if let Ok(number) = s.parse::<i32>() {
+        return Ok(number);
     }
+    let name = s.to_uppercase();
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{}", name)
+    };
+    nix::sys::signal::Signal::from_str(&name)
+        .map(|signal| signal as i32)
+        .map_err(|_| format!("unknown signal: {}", s))
 }
 
 fn start_request(name: String, wait: bool, job_mode: &str) -> Request {
@@ -202,30 +579,73 @@ fn start_request(name: String, wait: bool, job_mode: &str) -> Request {
     }
 }
 
-fn handle_is_active_or_exit(user_mode: bool, name: String, quiet: bool) -> Option<Request> {
+fn stop_request(name: String, wait: bool) -> Request {
+    if wait {
+        Request::StopAndWait { name }
+    } else {
+        Request::Stop { name }
+    }
+}
+
+fn restart_request(name: String, wait: bool, rolling: bool) -> Request {
+    match (wait, rolling) {
+        (false, false) => Request::Restart { name },
+        (true, false) => Request::RestartAndWait { name },
+        (false, true) => Request::RollingRestart { name },
+        (true, true) => Request::RollingRestartAndWait { name },
+    }
+}
+
+/// Start a batch and report each unit as soon as its start finished
+fn start_many_or_exit(user_mode: bool, names: Vec<String>) {
     let sock_path = socket_path(user_mode);
-    let result = Client::call(&sock_path, &Request::IsActive { name: name.clone() });
-    match result {
-        Ok(Response::ActiveState(state)) => {
-            if !quiet {
-                println!("{}", state);
-            }
-            if state == "active" || state == "Active" {
-                std::process::exit(0);
-            }
-            std::process::exit(3);
+    let id = match Client::call(&sock_path, &Request::StartMany { names }) {
+        Ok(Response::StartGroup(id)) => id,
+        Ok(response) => return print_response(response),
+        Err(error) => return handle_daemon_error(user_mode, &error.to_string()),
+    };
+    let (mut shown, mut failed) = (0, false);
+    loop {
+        let progress = match Client::call(&sock_path, &Request::StartGroupStatus { id }) {
+            Ok(Response::StartGroupProgress(progress)) => progress,
+            Ok(response) => return print_response(response),
+            Err(error) => return handle_daemon_error(user_mode, &error.to_string()),
+        };
+        failed |= print_start_results(&progress, shown);
+        shown = progress.finished.len();
+        if shown as u32 == progress.total {
+            break;
         }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+/// Print the state token a query answers (unless quiet) and exit 0 when
+/// `succeeds` accepts it, `failure` otherwise
+fn query_state_or_exit(
+    user_mode: bool,
+    request: Request,
+    quiet: bool,
+    succeeds: fn(&str) -> bool,
+    failure: i32,
+) -> ! {
+    let sock_path = socket_path(user_mode);
+    let state = match Client::call(&sock_path, &request) {
+        Ok(Response::ActiveState(state) | Response::EnabledState(state)) => state,
         Ok(Response::Error(msg)) => {
             if !quiet {
                 eprintln!("error: {}", msg);
             }
-            std::process::exit(3);
+            std::process::exit(failure);
         }
         Ok(_) => {
             if !quiet {
                 eprintln!("unexpected response");
             }
-            std::process::exit(3);
+            std::process::exit(failure);
         }
         Err(error) => {
             if !quiet {
@@ -233,7 +653,11 @@ fn handle_is_active_or_exit(user_mode: bool, name: String, quiet: bool) -> Optio
             }
             std::process::exit(1);
         }
+    };
+    if !quiet {
+        println!("{}", state);
     }
+    std::process::exit(if succeeds(&state) { 0 } else { failure });
 }
 
 fn send_request_or_exit(user_mode: bool, request: Request) {
@@ -271,9 +695,66 @@ fn print_response(response: Response) {
         Response::BootPlan(units) => print_boot_plan(units),
         Response::EnabledState(state) => print_enabled_state(&state),
         Response::ActiveState(state) => print_active_state(&state),
+        Response::Sockets(sockets) => print_sockets(sockets),
+        Response::Environment(vars) => print_environment(vars),
+        Response::SpawnProfile(profile) => print_spawn_profile(profile),
+        Response::StartGroup(id) => println!("start group {}", id),
+        Response::StartGroupProgress(progress) => {
+            print_start_results(&progress, 0);
+        }
+        Response::UnitNames(names) => {
+            for name in names {
+                println!("{}", name);
+            }
+        }
+        Response::Features(features) => {
+            for (feature, missing) in features {
+                println!(
+                    "{:<20} {}",
+                    feature,
+                    missing.as_deref().unwrap_or("available")
+                );
+            }
+        }
+        Response::Dump(dump) => print!("{}", dump),
+        Response::Why(why) => {
+            for line in why {
+                println!("{}", line);
+            }
+        }
+        Response::StateHistory(history) => {
+            for transition in &history {
+                println!("{}", transition_line(transition));
+            }
+        }
+        Response::BootTimes(boot) => {
+            for unit in boot.units {
+                println!("{:>10.3}ms {}", unit.time as f64 / 1000.0, unit.name);
+            }
+        }
+        Response::ScheduledShutdown(scheduled) => {
+            if let Some((kind, usec)) = scheduled {
+                println!("{} at {}", kind, usec);
+            }
+        }
+        Response::UnitFailed {
+            name,
+            reason,
+            exit_code,
+        } => {
+            eprintln!("{} failed: {}", name, reason);
+            std::process::exit(unit_failed_status(exit_code));
+        }
     }
 }
 
+/// Exit status for a unit that failed: its own exit code where that is one
+fn unit_failed_status(exit_code: Option<i32>) -> i32 {
+    exit_code
+        .filter(|code| (1..=255).contains(code))
+        .unwrap_or(1)
+}
+
 fn print_error_and_exit(message: &str) {
     eprintln!("error: {}", message);
     std::process::exit(1);
@@ -293,13 +774,151 @@ fn print_units(units: Vec<sysd::protocol::UnitInfo>) {
     }
 }
 
+fn print_environment(vars: Vec<String>) {
+    for var in vars {
+        println!("{}", var);
+    }
+}
+
+fn print_spawn_profile(profile: sysd::protocol::SpawnProfileInfo) {
+    for (step, usec) in profile.steps {
+        println!("{:<12} {:>10}us", step, usec);
+    }
+    println!("{:<12} {:>10}us", "total", profile.total_usec);
+}
+
+/// One line per finished unit from index `from` on; true if any of them failed
+fn print_start_results(progress: &sysd::protocol::StartGroupInfo, from: usize) -> bool {
+    let mut failed = false;
+    for (index, (name, error)) in progress.finished.iter().enumerate().skip(from) {
+        let position = format!("[{}/{}]", index + 1, progress.total);
+        match error {
+            None => println!("{} {} started", position, name),
+            Some(error) => {
+                println!("{} {} failed: {}", position, name, error);
+                failed = true;
+            }
+        }
+    }
+    failed
+}
+
+fn print_sockets(sockets: Vec<sysd::protocol::SocketInfo>) {
+    let listen_width = sockets
+        .iter()
+        .map(|socket| socket.listen.len())
+        .max()
+        .unwrap_or(0)
+        .max("LISTEN".len());
+    let unit_width = sockets
+        .iter()
+        .map(|socket| socket.unit.len())
+        .max()
+        .unwrap_or(0)
+        .max("UNIT".len());
+    println!(
+        "{:<listen_width$} {:<8} {:<unit_width$} ACTIVATES",
+        "LISTEN", "TYPE", "UNIT"
+    );
+    for socket in &sockets {
+        println!(
+            "{:<listen_width$} {:<8} {:<unit_width$} {}",
+            socket.listen,
+            socket.socket_type,
+            socket.unit,
+            socket.activates.join(", ")
+        );
+    }
+    println!();
+    println!("{} sockets listed.", sockets.len());
+}
+
+/// Transitions status shows; `sysd analyze history` lists them all
+const STATUS_HISTORY: usize = 5;
+
 fn print_status(unit: sysd::protocol::UnitInfo) {
     println!("● {}", unit.name);
+    if !unit.load_state.is_empty() {
+        let mut loaded = unit.load_state.clone();
+        let details: Vec<&str> = unit
+            .fragment_path
+            .iter()
+            .chain(unit.unit_file_state.iter())
+            .map(String::as_str)
+            .collect();
+        if !details.is_empty() {
+            loaded = format!("{} ({})", loaded, details.join("; "));
+        }
+        println!("   Loaded: {}", loaded);
+    }
     println!("     Type: {}", unit.unit_type);
     println!("    State: {}", unit.state);
+    if let Some(reason) = &unit.condition_failure {
+        println!("Condition: start condition unmet");
+        println!("           └─ {}", reason);
+    }
+    if let Some(dependency) = &unit.failed_dependency {
+        println!("  Skipped: dependency {} failed", dependency);
+    }
     if let Some(desc) = unit.description {
         println!("    Desc:  {}", desc);
     }
+    if let (Some(ingress), Some(egress)) = (unit.ip_ingress_bytes, unit.ip_egress_bytes) {
+        println!(
+            "       IP: {} in, {} out",
+            format_bytes(ingress),
+            format_bytes(egress)
+        );
+    }
+    for (i, uri) in unit.documentation.iter().enumerate() {
+        let label = if i == 0 { "Docs:" } else { "" };
+        println!("     {:<5} {}", label, uri);
+    }
+    let recent = unit.history.len().saturating_sub(STATUS_HISTORY);
+    for (i, transition) in unit.history[recent..].iter().enumerate() {
+        let label = if i == 0 { "History:" } else { "" };
+        println!("{:>10} {}", label, transition_line(transition));
+    }
+    if unit.need_daemon_reload {
+        println!();
+        println!(
+            "Warning: The unit file, source configuration file or drop-ins of {} changed on disk. \
+             Run 'sysdctl reload' to reload units.",
+            unit.name
+        );
+    }
+}
+
+/// "Thu 2026-10-15 09:30:00 CEST failed (failed): Exit code 1"
+fn transition_line(transition: &sysd::protocol::TransitionInfo) -> String {
+    let at = match chrono::DateTime::from_timestamp_micros(transition.usec as i64) {
+        Some(at) => at
+            .with_timezone(&chrono::Local)
+            .format("%a %Y-%m-%d %H:%M:%S %Z")
+            .to_string(),
+        None => transition.usec.to_string(),
+    };
+    let mut line = format!("{} {} ({})", at, transition.active, transition.sub);
+    if let Some(reason) = &transition.reason {
+        line.push_str(": ");
+        line.push_str(reason);
+    }
+    line
+}
+
+/// Bytes with a binary unit suffix (e.g. 1.5G)
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 {
+        return format!("{}B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1}{}", value, UNITS[unit])
 }
 
 fn print_deps(deps: Vec<String>) {
@@ -324,7 +943,7 @@ fn print_boot_plan(units: Vec<String>) {
 
 fn print_enabled_state(state: &str) {
     println!("{}", state);
-    if state == "disabled" {
+    if !enablement_succeeds(state) {
         std::process::exit(1);
     }
 }
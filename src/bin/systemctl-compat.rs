@@ -4,14 +4,23 @@
 //! expecting systemctl (like niri-session) work with sysd.
 //!
 //! Supported commands (subset used by niri-session, etc.):
-//! - systemctl --user is-active [-q] <unit>
+//! - systemctl --user is-active | is-failed | is-enabled [-q] <unit>
 //! - systemctl --user reset-failed
 //! - systemctl --user import-environment
 //! - systemctl --user start [--wait] [--job-mode=...] <unit>
 //! - systemctl --user unset-environment <vars...>
+//! - systemctl --user set-environment <VAR=value...>
+//! - systemctl --user show-environment
 //! - systemctl --user stop <unit>
 //! - systemctl --user restart <unit>
 //! - systemctl --user status <unit>
+//! - systemctl suspend | hibernate | hybrid-sleep
+//! - systemctl set-property [--runtime] <unit> <Key=value...>
+//! - systemctl [--root=DIR] enable | disable | is-enabled | preset <unit>
+//! - systemctl [--root=DIR] preset-all | set-default <target> | get-default
+//!
+//! With --root, and for preset and preset-all which sysdctl lacks, the unit
+//! file commands run offline through `sysd` instead of asking the daemon.
 
 use std::env;
 use std::os::unix::process::CommandExt;
@@ -21,23 +30,56 @@ struct ParsedArgs {
     user_mode: bool,
     quiet: bool,
     wait: bool,
+    runtime: bool,
     job_mode: Option<String>,
+    root: Option<String>,
     command: String,
     positional: Vec<String>,
 }
 
+/// Unit file commands `sysd` can run without the daemon
+const OFFLINE_COMMANDS: [&str; 7] = [
+    "enable",
+    "disable",
+    "is-enabled",
+    "preset",
+    "preset-all",
+    "set-default",
+    "get-default",
+];
+
 fn main() {
     let parsed = parse_args(env::args().skip(1).collect());
-    let sysdctl_args = build_sysdctl_args(parsed);
+    let (program, args) = if runs_offline(&parsed) {
+        ("sysd", build_offline_args(parsed))
+    } else {
+        ("sysdctl", build_sysdctl_args(parsed))
+    };
 
-    // Execute sysdctl
-    let err = Command::new("sysdctl").args(&sysdctl_args).exec();
+    let err = Command::new(program).args(&args).exec();
 
     // exec() only returns on error
-    eprintln!("systemctl-compat: failed to exec sysdctl: {}", err);
+    eprintln!("systemctl-compat: failed to exec {}: {}", program, err);
     exit(1);
 }
 
+/// Whether to run the command through `sysd` (offline) rather than sysdctl
+fn runs_offline(parsed: &ParsedArgs) -> bool {
+    let command = parsed.command.as_str();
+    OFFLINE_COMMANDS.contains(&command)
+        && (parsed.root.is_some() || matches!(command, "preset" | "preset-all"))
+}
+
+fn build_offline_args(parsed: ParsedArgs) -> Vec<String> {
+    let mut args = build_base_args(parsed.user_mode);
+    if let Some(root) = parsed.root {
+        args.push(format!("--root={}", root));
+    }
+    args.push(parsed.command);
+    args.extend(parsed.positional);
+    args
+}
+
 fn parse_args(args: Vec<String>) -> ParsedArgs {
     if args.is_empty() {
         eprintln!("systemctl-compat: no command specified");
@@ -55,7 +97,9 @@ fn parse_args(args: Vec<String>) -> ParsedArgs {
         user_mode: state.user_mode,
         quiet: state.quiet,
         wait: state.wait,
+        runtime: state.runtime,
         job_mode: state.job_mode,
+        root: state.root,
         command,
         positional: state.positional,
     }
@@ -66,7 +110,9 @@ struct ParseState {
     user_mode: bool,
     quiet: bool,
     wait: bool,
+    runtime: bool,
     job_mode: Option<String>,
+    root: Option<String>,
     command: Option<String>,
     positional: Vec<String>,
 }
@@ -84,10 +130,15 @@ fn parse_argument(args: &[String], i: usize, state: &mut ParseState) -> usize {
         "--user" => state.user_mode = true,
         "-q" | "--quiet" => state.quiet = true,
         "--wait" => state.wait = true,
+        "--runtime" => state.runtime = true,
         s if s.starts_with("--job-mode=") => {
             state.job_mode = Some(s.trim_start_matches("--job-mode=").to_string());
         }
         "--job-mode" => return parse_job_mode_value(args, i, state),
+        s if s.starts_with("--root=") => {
+            state.root = Some(s.trim_start_matches("--root=").to_string());
+        }
+        "--root" => return parse_root_value(args, i, state),
         s if s.starts_with('-') => {}
         _ => push_command_or_positional(arg, state),
     }
@@ -102,6 +153,14 @@ fn parse_job_mode_value(args: &[String], i: usize, state: &mut ParseState) -> us
     i + 1
 }
 
+fn parse_root_value(args: &[String], i: usize, state: &mut ParseState) -> usize {
+    if i + 1 < args.len() {
+        state.root = Some(args[i + 1].clone());
+        return i + 2;
+    }
+    i + 1
+}
+
 fn push_command_or_positional(arg: &str, state: &mut ParseState) {
     if state.command.is_none() {
         state.command = Some(arg.to_string());
@@ -126,24 +185,29 @@ fn build_base_args(user_mode: bool) -> Vec<String> {
 
 fn append_command_args(sysdctl_args: &mut Vec<String>, parsed: ParsedArgs) {
     match parsed.command.as_str() {
-        "is-active" => append_is_active_args(sysdctl_args, &parsed),
+        "is-active" | "is-failed" | "is-enabled" => append_state_query_args(sysdctl_args, &parsed),
         "reset-failed" => sysdctl_args.push("reset-failed".to_string()),
         "import-environment" => sysdctl_args.push("import-environment".to_string()),
         "start" => append_start_args(sysdctl_args, parsed),
         "stop" | "restart" | "status" => append_single_unit_action(sysdctl_args, &parsed),
-        "unset-environment" => append_unset_environment_args(sysdctl_args, parsed),
+        "unset-environment" | "set-environment" => append_environment_args(sysdctl_args, parsed),
+        "show-environment" => sysdctl_args.push("show-environment".to_string()),
         "daemon-reload" => sysdctl_args.push("reload".to_string()),
-        "enable" | "disable" | "is-enabled" => append_optional_unit_action(sysdctl_args, parsed),
+        "enable" | "disable" => append_optional_unit_action(sysdctl_args, parsed),
+        "set-default" => append_single_unit_action(sysdctl_args, &parsed),
+        "get-default" => sysdctl_args.push("get-default".to_string()),
+        "suspend" | "hibernate" | "hybrid-sleep" => sysdctl_args.push(parsed.command.clone()),
+        "set-property" => append_set_property_args(sysdctl_args, parsed),
         _ => unsupported_command(&parsed.command),
     }
 }
 
-fn append_is_active_args(sysdctl_args: &mut Vec<String>, parsed: &ParsedArgs) {
-    sysdctl_args.push("is-active".to_string());
+fn append_state_query_args(sysdctl_args: &mut Vec<String>, parsed: &ParsedArgs) {
+    sysdctl_args.push(parsed.command.clone());
     if parsed.quiet {
         sysdctl_args.push("--quiet".to_string());
     }
-    push_required_unit(sysdctl_args, &parsed.positional, "is-active");
+    push_required_unit(sysdctl_args, &parsed.positional, &parsed.command);
 }
 
 fn append_start_args(sysdctl_args: &mut Vec<String>, parsed: ParsedArgs) {
@@ -162,8 +226,16 @@ fn append_single_unit_action(sysdctl_args: &mut Vec<String>, parsed: &ParsedArgs
     push_required_unit(sysdctl_args, &parsed.positional, &parsed.command);
 }
 
-fn append_unset_environment_args(sysdctl_args: &mut Vec<String>, parsed: ParsedArgs) {
-    sysdctl_args.push("unset-environment".to_string());
+fn append_environment_args(sysdctl_args: &mut Vec<String>, parsed: ParsedArgs) {
+    sysdctl_args.push(parsed.command.clone());
+    sysdctl_args.extend(parsed.positional);
+}
+
+fn append_set_property_args(sysdctl_args: &mut Vec<String>, parsed: ParsedArgs) {
+    sysdctl_args.push("set-property".to_string());
+    if parsed.runtime {
+        sysdctl_args.push("--runtime".to_string());
+    }
     sysdctl_args.extend(parsed.positional);
 }
 
@@ -190,7 +262,7 @@ fn push_optional_unit(args: &mut Vec<String>, positional: &[String]) {
 fn unsupported_command(command: &str) -> ! {
     eprintln!("systemctl-compat: unsupported command '{}'", command);
     eprintln!(
-        "Supported: is-active, reset-failed, import-environment, start, stop, restart, status, unset-environment, daemon-reload, enable, disable, is-enabled"
+        "Supported: is-active, is-failed, reset-failed, import-environment, start, stop, restart, status, unset-environment, set-environment, show-environment, daemon-reload, enable, disable, is-enabled, preset, preset-all, set-default, get-default, suspend, hibernate, hybrid-sleep, set-property"
     );
     exit(1);
 }
@@ -0,0 +1,136 @@
+//! Cgroup accounting readers
+//!
+//! Resource usage of unit cgroups from cpu.stat, memory.current and
+//! pids.current. A counter whose controller is not enabled for the cgroup
+//! has no file and reads as None.
+
+use std::path::{Path, PathBuf};
+
+use super::CGROUP_ROOT;
+
+/// Resource usage of one cgroup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CgroupUsage {
+    /// CPU time consumed so far in microseconds (cpu.stat usage_usec)
+    pub cpu_usec: Option<u64>,
+    /// Memory in bytes (memory.current)
+    pub memory_bytes: Option<u64>,
+    /// Number of tasks (pids.current)
+    pub tasks: Option<u64>,
+}
+
+/// Read the usage counters of a cgroup
+pub fn read_usage(cgroup_path: &Path) -> CgroupUsage {
+    let read = |file: &str| std::fs::read_to_string(cgroup_path.join(file)).ok();
+    CgroupUsage {
+        cpu_usec: read("cpu.stat").as_deref().and_then(parse_cpu_usage),
+        memory_bytes: read("memory.current").and_then(|value| value.trim().parse().ok()),
+        tasks: read("pids.current").and_then(|value| value.trim().parse().ok()),
+    }
+}
+
+/// usage_usec from cpu.stat (available even without the cpu controller)
+fn parse_cpu_usage(content: &str) -> Option<u64> {
+    content
+        .lines()
+        .find_map(|line| line.strip_prefix("usage_usec ")?.trim().parse().ok())
+}
+
+/// The cgroup of a unit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitCgroup {
+    pub unit: String,
+    pub path: PathBuf,
+}
+
+/// Service and scope cgroups below /sys/fs/cgroup
+pub fn unit_cgroups() -> Vec<UnitCgroup> {
+    unit_cgroups_in(&crate::root::path(CGROUP_ROOT))
+}
+
+/// Service and scope cgroups below `root`, sorted by path. Slices and
+/// services (user@.service delegates a tree to the user manager) are
+/// searched for further units.
+pub fn unit_cgroups_in(root: &Path) -> Vec<UnitCgroup> {
+    let mut found = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                continue;
+            }
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            let path = entry.path();
+            if name.ends_with(".slice") || name.ends_with(".service") {
+                pending.push(path.clone());
+            }
+            if name.ends_with(".service") || name.ends_with(".scope") {
+                found.push(UnitCgroup { unit: name, path });
+            }
+        }
+    }
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usage_is_read_from_accounting_files() {
+        let dir = std::env::temp_dir().join(format!("sysd-accounting-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("cpu.stat"),
+            "usage_usec 123456\nuser_usec 100000\nsystem_usec 23456\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("memory.current"), "4096\n").unwrap();
+
+        assert_eq!(
+            read_usage(&dir),
+            CgroupUsage {
+                cpu_usec: Some(123456),
+                memory_bytes: Some(4096),
+                tasks: None,
+            }
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unit_cgroups_are_found_in_slices_and_delegated_services() {
+        let root = std::env::temp_dir().join(format!("sysd-unit-cgroups-{}", std::process::id()));
+        for dir in [
+            "system.slice/nginx.service",
+            "user.slice/user-1000.slice/session-2.scope",
+            "user.slice/user-1000.slice/user@1000.service/app.slice/foot.service",
+            "init.scope",
+            "misc",
+        ] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+
+        let units: Vec<String> = unit_cgroups_in(&root)
+            .into_iter()
+            .map(|cgroup| cgroup.unit)
+            .collect();
+        assert_eq!(
+            units,
+            [
+                "init.scope",
+                "nginx.service",
+                "session-2.scope",
+                "user@1000.service",
+                "foot.service",
+            ]
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,385 @@
+//! Minimal cgroup eBPF support: the bpf() syscall, an instruction assembler
+//! and program attachment
+//!
+//! The firewalls build their programs at unit start from the unit's lists,
+//! so there is no compiled object to load and no libbpf dependency.
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+// bpf() commands, map/program types and flags from <linux/bpf.h>
+const BPF_MAP_CREATE: libc::c_long = 0;
+const BPF_MAP_LOOKUP_ELEM: libc::c_long = 1;
+const BPF_MAP_UPDATE_ELEM: libc::c_long = 2;
+const BPF_MAP_DELETE_ELEM: libc::c_long = 3;
+const BPF_PROG_LOAD: libc::c_long = 5;
+const BPF_PROG_ATTACH: libc::c_long = 8;
+const BPF_PROG_DETACH: libc::c_long = 9;
+const BPF_RAW_TRACEPOINT_OPEN: libc::c_long = 17;
+pub(super) const BPF_MAP_TYPE_HASH: u32 = 1;
+pub(super) const BPF_MAP_TYPE_ARRAY: u32 = 2;
+pub(super) const BPF_PROG_TYPE_CGROUP_SKB: u32 = 8;
+pub(super) const BPF_PROG_TYPE_CGROUP_SOCK_ADDR: u32 = 18;
+const BPF_PROG_TYPE_LSM: u32 = 29;
+const BPF_LSM_MAC: u32 = 27;
+/// Leave programs attached by others (e.g. inside a delegated subtree) alone
+const BPF_F_ALLOW_MULTI: u32 = 2;
+
+#[repr(C)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+    prog_name: [u8; 16],
+    prog_ifindex: u32,
+    expected_attach_type: u32,
+    prog_btf_fd: u32,
+    func_info_rec_size: u32,
+    func_info: u64,
+    func_info_cnt: u32,
+    line_info_rec_size: u32,
+    line_info: u64,
+    line_info_cnt: u32,
+    attach_btf_id: u32,
+}
+
+#[repr(C)]
+struct ProgAttachAttr {
+    target_fd: u32,
+    attach_bpf_fd: u32,
+    attach_type: u32,
+    attach_flags: u32,
+}
+
+#[repr(C)]
+struct RawTracepointOpenAttr {
+    name: u64,
+    prog_fd: u32,
+}
+
+#[repr(C)]
+struct MapCreateAttr {
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    map_flags: u32,
+}
+
+#[repr(C)]
+struct MapElemAttr {
+    map_fd: u32,
+    pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+fn bpf<T>(cmd: libc::c_long, attr: &T) -> io::Result<libc::c_long> {
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *const T,
+            std::mem::size_of::<T>(),
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(ret)
+}
+
+/// bpf() commands creating an object return its fd
+fn bpf_fd<T>(cmd: libc::c_long, attr: &T) -> io::Result<OwnedFd> {
+    let fd = bpf(cmd, attr)? as RawFd;
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Load a program named `name` (at most 15 characters)
+pub(super) fn load_program(
+    prog_type: u32,
+    expected_attach_type: u32,
+    name: &str,
+    insns: &[Insn],
+) -> io::Result<OwnedFd> {
+    load(prog_type, expected_attach_type, 0, name, insns)
+}
+
+/// Whether cgroup programs can be loaded at all (kernel support and
+/// privileges), tried with one that passes every packet
+pub fn probe_cgroup_programs() -> io::Result<()> {
+    let mut asm = Assembler::default();
+    asm.exit_with(1);
+    load_program(BPF_PROG_TYPE_CGROUP_SKB, 0, "sysd_probe", &asm.finish()).map(drop)
+}
+
+/// Load an LSM program for the hook with BTF id `hook`, attached until the
+/// returned link is closed
+pub(super) fn attach_lsm_program(hook: u32, name: &str, insns: &[Insn]) -> io::Result<OwnedFd> {
+    let program = load(BPF_PROG_TYPE_LSM, BPF_LSM_MAC, hook, name, insns)?;
+    let attr = RawTracepointOpenAttr {
+        name: 0,
+        prog_fd: program.as_raw_fd() as u32,
+    };
+    bpf_fd(BPF_RAW_TRACEPOINT_OPEN, &attr)
+}
+
+fn load(
+    prog_type: u32,
+    expected_attach_type: u32,
+    attach_btf_id: u32,
+    name: &str,
+    insns: &[Insn],
+) -> io::Result<OwnedFd> {
+    const LICENSE: &[u8] = b"GPL\0";
+    let mut prog_name = [0u8; 16];
+    let len = name.len().min(15);
+    prog_name[..len].copy_from_slice(&name.as_bytes()[..len]);
+    let attr = ProgLoadAttr {
+        prog_type,
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: LICENSE.as_ptr() as u64,
+        log_level: 0,
+        log_size: 0,
+        log_buf: 0,
+        kern_version: 0,
+        prog_flags: 0,
+        prog_name,
+        prog_ifindex: 0,
+        expected_attach_type,
+        prog_btf_fd: 0,
+        func_info_rec_size: 0,
+        func_info: 0,
+        func_info_cnt: 0,
+        line_info_rec_size: 0,
+        line_info: 0,
+        line_info_cnt: 0,
+        attach_btf_id,
+    };
+    bpf_fd(BPF_PROG_LOAD, &attr)
+}
+
+pub(super) fn create_map(
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+) -> io::Result<OwnedFd> {
+    let attr = MapCreateAttr {
+        map_type,
+        key_size,
+        value_size,
+        max_entries,
+        map_flags: 0,
+    };
+    bpf_fd(BPF_MAP_CREATE, &attr)
+}
+
+fn map_elem<K, V>(map: &OwnedFd, key: &K, value: *const V) -> MapElemAttr {
+    MapElemAttr {
+        map_fd: map.as_raw_fd() as u32,
+        pad: 0,
+        key: key as *const K as u64,
+        value: value as u64,
+        flags: 0,
+    }
+}
+
+/// Copy the value under `key` into `value`
+pub(super) fn map_lookup<K, V>(map: &OwnedFd, key: &K, value: &mut V) -> io::Result<()> {
+    bpf(BPF_MAP_LOOKUP_ELEM, &map_elem(map, key, value as *mut V))?;
+    Ok(())
+}
+
+pub(super) fn map_update<K, V>(map: &OwnedFd, key: &K, value: &V) -> io::Result<()> {
+    bpf(BPF_MAP_UPDATE_ELEM, &map_elem(map, key, value as *const V))?;
+    Ok(())
+}
+
+pub(super) fn map_delete<K>(map: &OwnedFd, key: &K) -> io::Result<()> {
+    bpf(
+        BPF_MAP_DELETE_ELEM,
+        &map_elem::<K, u8>(map, key, std::ptr::null()),
+    )?;
+    Ok(())
+}
+
+/// A program attached to a cgroup; dropping it detaches the program
+#[derive(Debug)]
+pub(super) struct Attachment {
+    cgroup: OwnedFd,
+    program: OwnedFd,
+    attach_type: u32,
+}
+
+impl Attachment {
+    pub(super) fn new(cgroup: &OwnedFd, program: OwnedFd, attach_type: u32) -> io::Result<Self> {
+        let attachment = Self {
+            cgroup: cgroup.try_clone()?,
+            program,
+            attach_type,
+        };
+        bpf(BPF_PROG_ATTACH, &attachment.attr(BPF_F_ALLOW_MULTI))?;
+        Ok(attachment)
+    }
+
+    fn attr(&self, attach_flags: u32) -> ProgAttachAttr {
+        ProgAttachAttr {
+            target_fd: self.cgroup.as_raw_fd() as u32,
+            attach_bpf_fd: self.program.as_raw_fd() as u32,
+            attach_type: self.attach_type,
+            attach_flags,
+        }
+    }
+}
+
+impl Drop for Attachment {
+    fn drop(&mut self) {
+        // Fails harmlessly once the cgroup is removed, which detaches too
+        let _ = bpf(BPF_PROG_DETACH, &self.attr(0));
+    }
+}
+
+/// struct bpf_insn
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Insn {
+    pub(super) code: u8,
+    regs: u8,
+    pub(super) off: i16,
+    pub(super) imm: i32,
+}
+
+// Instruction classes, sizes, modes and operations
+pub(super) const BPF_LD: u8 = 0x00;
+pub(super) const BPF_LDX: u8 = 0x01;
+pub(super) const BPF_ST: u8 = 0x02;
+pub(super) const BPF_STX: u8 = 0x03;
+pub(super) const BPF_ALU: u8 = 0x04;
+pub(super) const BPF_JMP: u8 = 0x05;
+pub(super) const BPF_ALU64: u8 = 0x07;
+pub(super) const BPF_W: u8 = 0x00;
+pub(super) const BPF_DW: u8 = 0x18;
+pub(super) const BPF_IMM: u8 = 0x00;
+pub(super) const BPF_MEM: u8 = 0x60;
+pub(super) const BPF_XADD: u8 = 0xc0;
+pub(super) const BPF_K: u8 = 0x00;
+pub(super) const BPF_X: u8 = 0x08;
+pub(super) const BPF_ADD: u8 = 0x00;
+pub(super) const BPF_AND: u8 = 0x50;
+pub(super) const BPF_MOV: u8 = 0xb0;
+pub(super) const BPF_END: u8 = 0xd0;
+pub(super) const BPF_TO_BE: u8 = 0x08;
+pub(super) const BPF_JA: u8 = 0x00;
+pub(super) const BPF_JEQ: u8 = 0x10;
+pub(super) const BPF_JGT: u8 = 0x20;
+pub(super) const BPF_JNE: u8 = 0x50;
+pub(super) const BPF_JLT: u8 = 0xa0;
+pub(super) const BPF_CALL: u8 = 0x80;
+pub(super) const BPF_EXIT: u8 = 0x90;
+pub(super) const BPF_PSEUDO_MAP_FD: u8 = 1;
+
+// Helper functions
+pub(super) const BPF_FUNC_MAP_LOOKUP_ELEM: i32 = 1;
+pub(super) const BPF_FUNC_GET_CURRENT_CGROUP_ID: i32 = 80;
+
+// Registers: R0 result, R1-R5 arguments, R6 saved context, R10 frame pointer
+pub(super) const R0: u8 = 0;
+pub(super) const R1: u8 = 1;
+pub(super) const R2: u8 = 2;
+pub(super) const R3: u8 = 3;
+pub(super) const R4: u8 = 4;
+pub(super) const R6: u8 = 6;
+pub(super) const R7: u8 = 7;
+pub(super) const R10: u8 = 10;
+
+pub(super) fn insn(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> Insn {
+    let regs = if cfg!(target_endian = "little") {
+        (src << 4) | dst
+    } else {
+        (dst << 4) | src
+    };
+    Insn {
+        code,
+        regs,
+        off,
+        imm,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct Label(usize);
+
+/// Instructions with forward jumps to labels, resolved by `finish`
+#[derive(Default)]
+pub(super) struct Assembler {
+    insns: Vec<Insn>,
+    labels: Vec<Option<usize>>,
+    jumps: Vec<(usize, Label)>,
+}
+
+impl Assembler {
+    pub(super) fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() - 1)
+    }
+
+    pub(super) fn bind(&mut self, label: Label) {
+        self.labels[label.0] = Some(self.insns.len());
+    }
+
+    pub(super) fn emit(&mut self, insn: Insn) {
+        self.insns.push(insn);
+    }
+
+    pub(super) fn jump(&mut self, code: u8, dst: u8, src: u8, imm: i32, target: Label) {
+        self.jumps.push((self.insns.len(), target));
+        self.emit(insn(BPF_JMP | code, dst, src, 0, imm));
+    }
+
+    /// dst = the map behind fd `map`, a two-slot instruction
+    pub(super) fn load_map(&mut self, dst: u8, map: RawFd) {
+        self.emit(insn(
+            BPF_LD | BPF_DW | BPF_IMM,
+            dst,
+            BPF_PSEUDO_MAP_FD,
+            0,
+            map,
+        ));
+        self.emit(insn(0, 0, 0, 0, 0));
+    }
+
+    /// r0 = value; exit
+    pub(super) fn exit_with(&mut self, value: i32) {
+        self.emit(insn(BPF_ALU64 | BPF_MOV | BPF_K, R0, 0, 0, value));
+        self.emit(insn(BPF_JMP | BPF_EXIT, 0, 0, 0, 0));
+    }
+
+    pub(super) fn finish(mut self) -> Vec<Insn> {
+        for (at, label) in self.jumps {
+            let target = self.labels[label.0].expect("jump to an unbound label");
+            self.insns[at].off = (target - at - 1) as i16;
+        }
+        self.insns
+    }
+}
+
+/// Where each jump of `insns` lands (test helper)
+#[cfg(test)]
+pub(super) fn jump_targets(insns: &[Insn]) -> Vec<usize> {
+    insns
+        .iter()
+        .enumerate()
+        .filter(|(_, insn)| insn.code & 0x07 == BPF_JMP)
+        .filter(|(_, insn)| !matches!(insn.code & 0xf0, BPF_CALL | BPF_EXIT))
+        .map(|(at, insn)| (at as i64 + 1 + i64::from(insn.off)) as usize)
+        .collect()
+}
@@ -0,0 +1,212 @@
+//! Just enough of the kernel's BTF (/sys/kernel/btf/vmlinux) to attach LSM
+//! programs: the type id of a hook function and struct member offsets
+//!
+//! Programs are assembled at runtime, so the offsets they dereference come
+//! from here instead of being relocated by a loader.
+
+use std::io;
+
+const VMLINUX_BTF: &str = "/sys/kernel/btf/vmlinux";
+const BTF_MAGIC: u16 = 0xeb9f;
+
+// Kinds from <linux/btf.h>
+const BTF_KIND_STRUCT: u32 = 4;
+const BTF_KIND_UNION: u32 = 5;
+const BTF_KIND_FUNC: u32 = 12;
+
+/// Parsed type section: where each type starts, by id
+pub(super) struct Btf {
+    data: Vec<u8>,
+    types: Vec<usize>,
+    strings: usize,
+}
+
+impl Btf {
+    pub(super) fn load() -> io::Result<Self> {
+        let data = std::fs::read(VMLINUX_BTF).map_err(missing_btf)?;
+        Self::parse(data)
+    }
+
+    /// Whether the kernel exposes its BTF, without reading it
+    pub(super) fn probe() -> io::Result<()> {
+        std::fs::metadata(VMLINUX_BTF)
+            .map(drop)
+            .map_err(missing_btf)
+    }
+
+    pub(super) fn parse(data: Vec<u8>) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed BTF");
+        let u32_at = |at: usize| -> Option<u32> {
+            Some(u32::from_ne_bytes(data.get(at..at + 4)?.try_into().ok()?))
+        };
+        if data.get(..2) != Some(&BTF_MAGIC.to_ne_bytes()[..]) {
+            return Err(invalid());
+        }
+        let header = |field: usize| u32_at(4 + 4 * field).map(|value| value as usize);
+        let [Some(hdr_len), Some(type_off), Some(type_len), Some(str_off)] =
+            [0, 1, 2, 3].map(header)
+        else {
+            return Err(invalid());
+        };
+
+        // Type ids start at 1; id 0 is void
+        let mut types = vec![0];
+        let mut at = hdr_len + type_off;
+        let end = at + type_len;
+        if end > data.len() {
+            return Err(invalid());
+        }
+        while at < end {
+            let info = u32_at(at + 4).ok_or_else(invalid)?;
+            let vlen = (info & 0xffff) as usize;
+            let extra = match (info >> 24) & 0x1f {
+                2 | 7..=12 | 16 | 18 => 0,
+                1 | 14 | 17 => 4,
+                3 => 12,
+                4 | 5 | 15 | 19 => 12 * vlen,
+                6 | 13 => 8 * vlen,
+                _ => return Err(invalid()),
+            };
+            types.push(at);
+            at += 12 + extra;
+        }
+        if at != end || data.len() < hdr_len + str_off {
+            return Err(invalid());
+        }
+        let strings = hdr_len + str_off;
+        Ok(Self {
+            data,
+            types,
+            strings,
+        })
+    }
+
+    fn u32_at(&self, at: usize) -> u32 {
+        u32::from_ne_bytes(self.data[at..at + 4].try_into().unwrap())
+    }
+
+    fn name(&self, name_off: u32) -> &[u8] {
+        let start = self.strings + name_off as usize;
+        let tail = self.data.get(start..).unwrap_or_default();
+        let len = tail.iter().position(|&b| b == 0).unwrap_or(tail.len());
+        &tail[..len]
+    }
+
+    fn kind(&self, id: usize) -> u32 {
+        (self.u32_at(self.types[id] + 4) >> 24) & 0x1f
+    }
+
+    fn find(&self, kind: u32, name: &str) -> Option<usize> {
+        (1..self.types.len()).find(|&id| {
+            self.kind(id) == kind && self.name(self.u32_at(self.types[id])) == name.as_bytes()
+        })
+    }
+
+    /// Type id of the function `name`, the attach target of a hook program
+    pub(super) fn function(&self, name: &str) -> Option<u32> {
+        self.find(BTF_KIND_FUNC, name).map(|id| id as u32)
+    }
+
+    /// Byte offset of `member` in `struct name`, looking into anonymous
+    /// structs and unions
+    pub(super) fn member_offset(&self, name: &str, member: &str) -> Option<i16> {
+        let id = self.find(BTF_KIND_STRUCT, name)?;
+        let bits = self.member_bits(id, member.as_bytes())?;
+        i16::try_from(bits / 8).ok()
+    }
+
+    fn member_bits(&self, id: usize, member: &[u8]) -> Option<u32> {
+        let at = self.types[id];
+        let info = self.u32_at(at + 4);
+        let kind_flag = info & (1 << 31) != 0;
+        for index in 0..(info & 0xffff) as usize {
+            let entry = at + 12 + 12 * index;
+            let name = self.name(self.u32_at(entry));
+            let member_type = self.u32_at(entry + 4) as usize;
+            let offset = self.u32_at(entry + 8);
+            // With kind_flag set the top byte holds a bitfield size
+            let bits = if kind_flag {
+                offset & 0xff_ffff
+            } else {
+                offset
+            };
+            if name == member {
+                return Some(bits);
+            }
+            let nested = member_type < self.types.len()
+                && matches!(self.kind(member_type), BTF_KIND_STRUCT | BTF_KIND_UNION);
+            if name.is_empty() && nested {
+                if let Some(inner) = self.member_bits(member_type, member) {
+                    return Some(bits + inner);
+                }
+            }
+        }
+        None
+    }
+}
+
+fn missing_btf(e: io::Error) -> io::Error {
+    io::Error::new(e.kind(), format!("no kernel BTF at {}: {}", VMLINUX_BTF, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// BTF for: struct file { int x; union { int y; void *f_inode; }; };
+    /// and a function bpf_lsm_file_open
+    fn sample() -> Vec<u8> {
+        let strings = b"\0int\0file\0x\0y\0f_inode\0bpf_lsm_file_open\0";
+        let name = |s: &str| {
+            let needle = format!("\0{}\0", s);
+            let at = strings
+                .windows(needle.len())
+                .position(|w| w == needle.as_bytes())
+                .unwrap();
+            at as u32 + 1
+        };
+        let mut types: Vec<u32> = Vec::new();
+        // 1: int
+        types.extend([name("int"), 1 << 24, 4, 32]);
+        // 2: anonymous union { int y; void *f_inode; }
+        types.extend([0, (BTF_KIND_UNION << 24) | 2, 8]);
+        types.extend([name("y"), 1, 0, name("f_inode"), 0, 0]);
+        // 3: struct file { int x; union at byte 8 }
+        types.extend([name("file"), (BTF_KIND_STRUCT << 24) | 2, 16]);
+        types.extend([name("x"), 1, 0, 0, 2, 64]);
+        // 4: func bpf_lsm_file_open
+        types.extend([name("bpf_lsm_file_open"), BTF_KIND_FUNC << 24, 0]);
+
+        let type_len = types.len() as u32 * 4;
+        let mut data = Vec::new();
+        data.extend(BTF_MAGIC.to_ne_bytes());
+        data.extend([1, 0]);
+        for field in [24, 0, type_len, type_len, strings.len() as u32] {
+            data.extend(field.to_ne_bytes());
+        }
+        for word in types {
+            data.extend(word.to_ne_bytes());
+        }
+        data.extend(strings);
+        data
+    }
+
+    #[test]
+    fn functions_and_nested_members_are_found() {
+        let btf = Btf::parse(sample()).unwrap();
+        assert_eq!(btf.function("bpf_lsm_file_open"), Some(4));
+        assert_eq!(btf.function("bpf_lsm_file_close"), None);
+        assert_eq!(btf.member_offset("file", "x"), Some(0));
+        assert_eq!(btf.member_offset("file", "f_inode"), Some(8));
+        assert_eq!(btf.member_offset("file", "f_mode"), None);
+        assert_eq!(btf.member_offset("inode", "i_sb"), None);
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert!(Btf::parse(b"not btf at all".to_vec()).is_err());
+        let mut truncated = sample();
+        truncated.truncate(40);
+        assert!(Btf::parse(truncated).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+//! Waiting for many cgroups to empty at once
+//!
+//! The kernel rewrites `cgroup.events` when a cgroup's `populated` flag
+//! changes, so one inotify instance watching the `cgroup.events` file of
+//! each cgroup notices every cgroup emptying as it happens, however many
+//! there are. The files are also rechecked on a timer in case an event was
+//! missed, and polled alone where inotify is unavailable.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+
+/// Recheck interval next to inotify
+const RECHECK: Duration = Duration::from_secs(1);
+/// Poll interval without inotify
+const POLL: Duration = Duration::from_millis(50);
+
+/// Watches a set of cgroups until each of them has no processes left
+pub struct EmptyWatcher {
+    empty: HashMap<PathBuf, watch::Receiver<bool>>,
+    task: AbortHandle,
+}
+
+impl EmptyWatcher {
+    /// Start watching `cgroups`
+    pub fn spawn(cgroups: Vec<PathBuf>) -> Self {
+        let mut empty = HashMap::new();
+        let mut pending = Vec::new();
+        for cgroup in cgroups {
+            let (tx, rx) = watch::channel(false);
+            empty.insert(cgroup.clone(), rx);
+            pending.push((cgroup, tx));
+        }
+        let task = tokio::spawn(watch_cgroups(pending)).abort_handle();
+        Self { empty, task }
+    }
+
+    /// Wait until `cgroup` is empty or `deadline` passes; true if it emptied.
+    /// A cgroup not given to `spawn` is polled.
+    pub async fn wait(&self, cgroup: &Path, deadline: Instant) -> bool {
+        let Some(rx) = self.empty.get(cgroup) else {
+            return poll_empty(cgroup, deadline).await;
+        };
+        let mut rx = rx.clone();
+        let emptied = rx.wait_for(|&empty| empty);
+        match tokio::time::timeout_at(deadline.into(), emptied).await {
+            Ok(Ok(_)) => true,
+            // The watcher is gone; look for ourselves
+            Ok(Err(_)) => is_empty(cgroup),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Drop for EmptyWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Whether `cgroup` and its children have no processes; a cgroup that is
+/// gone is empty
+fn is_empty(cgroup: &Path) -> bool {
+    match std::fs::read_to_string(cgroup.join("cgroup.events")) {
+        Ok(events) => events.lines().any(|line| line.trim() == "populated 0"),
+        Err(_) => std::fs::read_to_string(cgroup.join("cgroup.procs"))
+            .map_or(true, |procs| procs.trim().is_empty()),
+    }
+}
+
+async fn poll_empty(cgroup: &Path, deadline: Instant) -> bool {
+    loop {
+        if is_empty(cgroup) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(POLL).await;
+    }
+}
+
+/// Flag each cgroup of `pending` empty once it is, until all are
+async fn watch_cgroups(mut pending: Vec<(PathBuf, watch::Sender<bool>)>) {
+    use futures_lite::StreamExt;
+
+    let inotify = inotify::Inotify::init()
+        .inspect_err(|e| log::debug!("No inotify for cgroup events ({}), polling", e))
+        .ok();
+    if let Some(inotify) = inotify.as_ref() {
+        for (cgroup, _) in &pending {
+            let events = cgroup.join("cgroup.events");
+            if let Err(e) = inotify.watches().add(&events, inotify::WatchMask::MODIFY) {
+                log::debug!("Cannot watch {}: {}", events.display(), e);
+            }
+        }
+    }
+    let mut buffer = [0; 1024];
+    let mut stream = inotify.and_then(|inotify| inotify.into_event_stream(&mut buffer).ok());
+    let interval = if stream.is_some() { RECHECK } else { POLL };
+
+    loop {
+        pending.retain(|(cgroup, tx)| {
+            if !is_empty(cgroup) {
+                return true;
+            }
+            let _ = tx.send(true);
+            false
+        });
+        if pending.is_empty() {
+            return;
+        }
+        match stream.as_mut() {
+            Some(events) => {
+                let _ = tokio::time::timeout(interval, events.next()).await;
+            }
+            None => tokio::time::sleep(interval).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cgroup_dir(test: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sysd-empty-watch-{}-{}", test, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn emptiness_comes_from_events_then_procs() {
+        let dir = cgroup_dir("is-empty");
+        std::fs::write(dir.join("cgroup.procs"), "42\n").unwrap();
+        assert!(!is_empty(&dir));
+        std::fs::write(dir.join("cgroup.events"), "populated 0\nfrozen 0\n").unwrap();
+        assert!(is_empty(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(is_empty(&dir));
+    }
+
+    #[tokio::test]
+    async fn wait_returns_when_cgroup_empties_or_deadline_passes() {
+        let busy = cgroup_dir("busy");
+        let draining = cgroup_dir("draining");
+        for dir in [&busy, &draining] {
+            std::fs::write(dir.join("cgroup.events"), "populated 1\n").unwrap();
+        }
+        let watcher = EmptyWatcher::spawn(vec![busy.clone(), draining.clone()]);
+
+        let soon = Instant::now() + Duration::from_millis(50);
+        assert!(!watcher.wait(&draining, soon).await);
+
+        let events = draining.join("cgroup.events");
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            std::fs::write(events, "populated 0\n").unwrap();
+        });
+        let later = Instant::now() + Duration::from_secs(5);
+        assert!(watcher.wait(&draining, later).await);
+        assert!(!watcher.wait(&busy, Instant::now()).await);
+
+        for dir in [busy, draining] {
+            std::fs::remove_dir_all(dir).unwrap();
+        }
+    }
+}
@@ -0,0 +1,318 @@
+//! IPAddressAllow=/IPAddressDeny= firewall and IP accounting via cgroup eBPF
+//!
+//! One cgroup/skb program per direction is attached to the service cgroup.
+//! It adds the packet to the unit's counters, then looks at the peer address
+//! (source on ingress, destination on egress): an address in the allow list
+//! passes, otherwise one in the deny list is dropped, and anything else
+//! passes, as in systemd. The lists are compiled into the program as
+//! mask-and-compare sequences, so only the counters need a map.
+//!
+//! Needs a kernel with cgroup-bpf (CONFIG_CGROUP_BPF) and CAP_BPF or root;
+//! without them attaching fails and the caller runs the unit unfiltered.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::path::Path;
+
+use super::bpf::*;
+use crate::units::IpPrefix;
+
+/// IP traffic of a unit since its firewall was attached
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IpCounters {
+    pub ingress_bytes: u64,
+    pub ingress_packets: u64,
+    pub egress_bytes: u64,
+    pub egress_packets: u64,
+}
+
+/// Firewall programs attached to one cgroup and the map they count into
+///
+/// Dropping it detaches the programs. The kernel also releases them with the
+/// cgroup, so counters stay readable after the unit's cgroup is gone.
+#[derive(Debug)]
+pub struct IpFirewall {
+    counters: OwnedFd,
+    _programs: Vec<Attachment>,
+}
+
+impl IpFirewall {
+    /// Load the programs for `allow`/`deny` and attach them to `cgroup_path`
+    pub fn attach(cgroup_path: &Path, allow: &[IpPrefix], deny: &[IpPrefix]) -> io::Result<Self> {
+        let cgroup = OwnedFd::from(File::open(cgroup_path)?);
+        let counters = create_counter_map()?;
+        let mut programs = Vec::new();
+        for direction in [Direction::Ingress, Direction::Egress] {
+            let insns = build_program(direction, counters.as_raw_fd(), allow, deny);
+            let program = load_program(BPF_PROG_TYPE_CGROUP_SKB, 0, "sysd_ip_fw", &insns)?;
+            programs.push(Attachment::new(&cgroup, program, direction as u32)?);
+        }
+        Ok(Self {
+            counters,
+            _programs: programs,
+        })
+    }
+
+    /// Bytes and packets counted so far
+    pub fn counters(&self) -> io::Result<IpCounters> {
+        let [ingress_bytes, ingress_packets] = self.read_counter(Direction::Ingress)?;
+        let [egress_bytes, egress_packets] = self.read_counter(Direction::Egress)?;
+        Ok(IpCounters {
+            ingress_bytes,
+            ingress_packets,
+            egress_bytes,
+            egress_packets,
+        })
+    }
+
+    fn read_counter(&self, direction: Direction) -> io::Result<[u64; 2]> {
+        let mut value = [0u64; 2];
+        map_lookup(&self.counters, &(direction as u32), &mut value)?;
+        Ok(value)
+    }
+}
+
+/// Attach point, also the index of the direction's counters in the map
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Ingress = 0, // BPF_CGROUP_INET_INGRESS
+    Egress = 1,  // BPF_CGROUP_INET_EGRESS
+}
+
+impl Direction {
+    /// Offset of the peer address in the IPv4 and IPv6 header
+    fn peer_offsets(self) -> (i32, i32) {
+        match self {
+            Direction::Ingress => (12, 8),
+            Direction::Egress => (16, 24),
+        }
+    }
+}
+
+/// Array of two entries (ingress, egress), each a bytes and a packets count
+fn create_counter_map() -> io::Result<OwnedFd> {
+    create_map(BPF_MAP_TYPE_ARRAY, 4, 16, 2)
+}
+
+const BPF_FUNC_SKB_LOAD_BYTES: i32 = 26;
+
+// struct __sk_buff fields
+const SKB_LEN: i16 = 0;
+const SKB_PROTOCOL: i16 = 16;
+
+// Stack slots below the frame pointer
+const ADDRESS_SLOT: i16 = -16;
+const KEY_SLOT: i16 = -20;
+
+/// The program for one direction: count, then decide on the peer address
+fn build_program<'a>(
+    direction: Direction,
+    counters: RawFd,
+    allow: &'a [IpPrefix],
+    deny: &'a [IpPrefix],
+) -> Vec<Insn> {
+    let mut asm = Assembler::default();
+    let (pass, drop, ipv4, ipv6) = (asm.label(), asm.label(), asm.label(), asm.label());
+
+    asm.emit(insn(BPF_ALU64 | BPF_MOV | BPF_X, R6, R1, 0, 0));
+    emit_count(&mut asm, counters, direction);
+
+    let ethertype = |protocol: i32| i32::from((protocol as u16).to_be());
+    asm.emit(insn(BPF_LDX | BPF_MEM | BPF_W, R2, R6, SKB_PROTOCOL, 0));
+    asm.jump(BPF_JEQ | BPF_K, R2, 0, ethertype(libc::ETH_P_IP), ipv4);
+    asm.jump(BPF_JEQ | BPF_K, R2, 0, ethertype(libc::ETH_P_IPV6), ipv6);
+    asm.jump(BPF_JA, 0, 0, 0, pass);
+
+    let (ipv4_offset, ipv6_offset) = direction.peer_offsets();
+    for (label, offset, is_ipv6) in [(ipv4, ipv4_offset, false), (ipv6, ipv6_offset, true)] {
+        let family = |prefixes: &'a [IpPrefix]| {
+            prefixes
+                .iter()
+                .filter(move |prefix| prefix.address.is_ipv6() == is_ipv6)
+        };
+        asm.bind(label);
+        let checks = AddressChecks {
+            offset,
+            len: if is_ipv6 { 16 } else { 4 },
+            pass,
+            drop,
+        };
+        checks.emit(&mut asm, family(allow), family(deny));
+    }
+
+    asm.bind(pass);
+    asm.exit_with(1);
+    asm.bind(drop);
+    asm.exit_with(0);
+    asm.finish()
+}
+
+/// counters[direction] += (skb->len, 1)
+fn emit_count(asm: &mut Assembler, counters: RawFd, direction: Direction) {
+    let done = asm.label();
+    asm.emit(insn(
+        BPF_ST | BPF_MEM | BPF_W,
+        R10,
+        0,
+        KEY_SLOT,
+        direction as i32,
+    ));
+    asm.load_map(R1, counters);
+    asm.emit(insn(BPF_ALU64 | BPF_MOV | BPF_X, R2, R10, 0, 0));
+    asm.emit(insn(
+        BPF_ALU64 | BPF_ADD | BPF_K,
+        R2,
+        0,
+        0,
+        i32::from(KEY_SLOT),
+    ));
+    asm.emit(insn(BPF_JMP | BPF_CALL, 0, 0, 0, BPF_FUNC_MAP_LOOKUP_ELEM));
+    asm.jump(BPF_JEQ | BPF_K, R0, 0, 0, done);
+    asm.emit(insn(BPF_LDX | BPF_MEM | BPF_W, R1, R6, SKB_LEN, 0));
+    asm.emit(insn(BPF_STX | BPF_XADD | BPF_DW, R0, R1, 0, 0));
+    asm.emit(insn(BPF_ALU64 | BPF_MOV | BPF_K, R1, 0, 0, 1));
+    asm.emit(insn(BPF_STX | BPF_XADD | BPF_DW, R0, R1, 8, 0));
+    asm.bind(done);
+}
+
+/// Where the peer address of one family is and where its verdicts go
+struct AddressChecks {
+    offset: i32,
+    len: i32,
+    pass: Label,
+    drop: Label,
+}
+
+impl AddressChecks {
+    /// Copy the peer address to the stack, then jump to `drop` or `pass`
+    fn emit<'a>(
+        &self,
+        asm: &mut Assembler,
+        allow: impl Iterator<Item = &'a IpPrefix>,
+        deny: impl Iterator<Item = &'a IpPrefix>,
+    ) {
+        asm.emit(insn(BPF_ALU64 | BPF_MOV | BPF_X, R1, R6, 0, 0));
+        asm.emit(insn(BPF_ALU64 | BPF_MOV | BPF_K, R2, 0, 0, self.offset));
+        asm.emit(insn(BPF_ALU64 | BPF_MOV | BPF_X, R3, R10, 0, 0));
+        asm.emit(insn(
+            BPF_ALU64 | BPF_ADD | BPF_K,
+            R3,
+            0,
+            0,
+            i32::from(ADDRESS_SLOT),
+        ));
+        asm.emit(insn(BPF_ALU64 | BPF_MOV | BPF_K, R4, 0, 0, self.len));
+        asm.emit(insn(BPF_JMP | BPF_CALL, 0, 0, 0, BPF_FUNC_SKB_LOAD_BYTES));
+        // Truncated header: nothing to match against
+        asm.jump(BPF_JNE | BPF_K, R0, 0, 0, self.pass);
+
+        for prefix in allow {
+            emit_prefix_match(asm, prefix, self.pass);
+        }
+        for prefix in deny {
+            emit_prefix_match(asm, prefix, self.drop);
+        }
+        asm.jump(BPF_JA, 0, 0, 0, self.pass);
+    }
+}
+
+/// Jump to `on_match` if the address on the stack is in `prefix`
+fn emit_prefix_match(asm: &mut Assembler, prefix: &IpPrefix, on_match: Label) {
+    let next = asm.label();
+    for (index, (mask, value)) in prefix_words(prefix).into_iter().enumerate() {
+        let slot = ADDRESS_SLOT + 4 * index as i16;
+        asm.emit(insn(BPF_LDX | BPF_MEM | BPF_W, R1, R10, slot, 0));
+        if mask != u32::MAX {
+            asm.emit(insn(BPF_ALU | BPF_AND | BPF_K, R1, 0, 0, mask as i32));
+        }
+        // 32-bit moves zero-extend, matching the zero-extended load
+        asm.emit(insn(BPF_ALU | BPF_MOV | BPF_K, R2, 0, 0, value as i32));
+        asm.jump(BPF_JNE | BPF_X, R1, R2, 0, next);
+    }
+    asm.jump(BPF_JA, 0, 0, 0, on_match);
+    asm.bind(next);
+}
+
+/// (mask, masked address) of each 32-bit word the prefix covers, as the
+/// program loads them from the network-order address on the stack
+fn prefix_words(prefix: &IpPrefix) -> Vec<(u32, u32)> {
+    let octets = prefix.octets();
+    let prefix_len = u32::from(prefix.prefix_len);
+    octets
+        .chunks(4)
+        .enumerate()
+        .map_while(|(index, chunk)| {
+            let bits = prefix_len
+                .checked_sub(32 * index as u32)
+                .filter(|&bits| bits > 0)?;
+            let mask = u32::MAX
+                .checked_shl(32 - bits.min(32))
+                .unwrap_or(0)
+                .to_be_bytes();
+            let word: [u8; 4] = chunk.try_into().ok()?;
+            let value = std::array::from_fn(|i| word[i] & mask[i]);
+            Some((u32::from_ne_bytes(mask), u32::from_ne_bytes(value)))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefix(s: &str) -> IpPrefix {
+        IpPrefix::parse(s).unwrap()
+    }
+
+    #[test]
+    fn prefixes_become_masked_words_in_network_order() {
+        assert_eq!(
+            prefix_words(&prefix("10.1.2.3/8")),
+            [(
+                u32::from_ne_bytes([255, 0, 0, 0]),
+                u32::from_ne_bytes([10, 0, 0, 0])
+            )]
+        );
+        assert_eq!(
+            prefix_words(&prefix("192.168.1.7")),
+            [(u32::MAX, u32::from_ne_bytes([192, 168, 1, 7]))]
+        );
+        let link_local = prefix_words(&prefix("fe80::1/64"));
+        assert_eq!(link_local.len(), 2);
+        assert_eq!(link_local[0].1, u32::from_ne_bytes([0xfe, 0x80, 0, 0]));
+        assert_eq!(link_local[1], (u32::MAX, 0));
+        // /0 matches everything without a comparison
+        assert!(prefix_words(&prefix("0.0.0.0/0")).is_empty());
+    }
+
+    #[test]
+    fn jumps_land_on_their_labels() {
+        let allow = [prefix("127.0.0.0/8")];
+        let deny = [prefix("0.0.0.0/0"), prefix("::/0")];
+        let insns = build_program(Direction::Egress, 3, &allow, &deny);
+
+        // The program ends in pass (r0 = 1) and drop (r0 = 0) exits
+        let exit = insn(BPF_JMP | BPF_EXIT, 0, 0, 0, 0);
+        assert_eq!(insns[insns.len() - 1], exit);
+        assert_eq!(insns[insns.len() - 3], exit);
+        let drop_at = insns.len() - 2;
+        let pass_at = insns.len() - 4;
+        assert_eq!(insns[pass_at].imm, 1);
+        assert_eq!(insns[drop_at].imm, 0);
+
+        let targets = jump_targets(&insns);
+        assert!(targets.iter().all(|&target| target < insns.len()));
+        // "any" in the deny lists makes both families jump to drop
+        assert_eq!(
+            targets.iter().filter(|&&target| target == drop_at).count(),
+            2
+        );
+    }
+
+    #[test]
+    fn attaching_to_a_plain_directory_fails() {
+        let dir = std::env::temp_dir();
+        assert!(IpFirewall::attach(&dir, &[], &[prefix("0.0.0.0/0")]).is_err());
+    }
+}
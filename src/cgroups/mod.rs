@@ -9,30 +9,57 @@
 //! └── user.slice/             # User sessions (managed by logind)
 //!     └── user-1000.slice/
 //!         ├── session-1.scope/    # Login session
-//!         └── user@1000.service/  # User manager
+//!         └── user@1000.service/  # User manager, delegated to it
+//!             ├── init.scope/     # The user manager itself
+//!             └── app.slice/      # User services
+//!
+//! Units go into `system.slice` (`app.slice` for the user manager) unless
+//! they set Slice= or the manager's DefaultSlice= names another slice.
+
+mod accounting;
+mod bpf;
+mod btf;
+mod empty_watch;
+mod ip_firewall;
+mod restrict_fs;
+mod socket_bind;
+mod tree;
+
+pub use accounting::{read_usage, unit_cgroups, unit_cgroups_in, CgroupUsage, UnitCgroup};
+pub use bpf::probe_cgroup_programs;
+pub use empty_watch::EmptyWatcher;
+pub use ip_firewall::{IpCounters, IpFirewall};
+pub use restrict_fs::FileSystemRestrictor;
+pub use socket_bind::SocketBindFilter;
+pub use tree::{read_tree, read_tree_at, CgroupNode, CgroupProcess};
 
 use std::io;
 use std::path::{Path, PathBuf};
 
 const CGROUP_ROOT: &str = "/sys/fs/cgroup";
 const SYSTEM_SLICE: &str = "system.slice";
+/// Default slice of the user manager
+const APP_SLICE: &str = "app.slice";
 
 #[derive(Clone)]
 pub struct CgroupManager {
     root: PathBuf,
+    /// Slice of units without Slice=
+    default_slice: String,
 }
 
 impl Default for CgroupManager {
     fn default() -> Self {
         Self {
-            root: PathBuf::from(CGROUP_ROOT),
+            root: crate::root::path(CGROUP_ROOT),
+            default_slice: SYSTEM_SLICE.to_string(),
         }
     }
 }
 
 impl CgroupManager {
     pub fn new() -> io::Result<Self> {
-        let root = PathBuf::from(CGROUP_ROOT);
+        let root = crate::root::path(CGROUP_ROOT);
 
         // Verify cgroup2 is mounted
         if !root.join("cgroup.controllers").exists() {
@@ -42,7 +69,54 @@ impl CgroupManager {
             ));
         }
 
-        Ok(Self { root })
+        Ok(Self {
+            root,
+            default_slice: SYSTEM_SLICE.to_string(),
+        })
+    }
+
+    /// Cgroup manager of the user manager of `uid`: the user@UID.service
+    /// cgroup the system manager delegated to it. The user manager moves
+    /// itself into init.scope there, as a cgroup with processes of its own
+    /// cannot pass controllers on, and puts units next to it in app.slice.
+    pub fn delegated(uid: u32) -> io::Result<Self> {
+        let own = std::fs::read_to_string("/proc/self/cgroup")?;
+        let own = own
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, "not in a cgroup v2 hierarchy")
+            })?;
+        let service = delegated_user_service(own, uid).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} is not the cgroup of user@{}.service", own, uid),
+            )
+        })?;
+        let root = crate::root::path(CGROUP_ROOT).join(service.trim_start_matches('/'));
+
+        let init_scope = root.join("init.scope");
+        std::fs::create_dir_all(&init_scope)?;
+        std::fs::write(
+            init_scope.join("cgroup.procs"),
+            std::process::id().to_string(),
+        )?;
+        log::debug!("Managing delegated cgroup {}", root.display());
+        Ok(Self {
+            root,
+            default_slice: APP_SLICE.to_string(),
+        })
+    }
+
+    /// Put units without Slice= into `slice` (DefaultSlice=)
+    pub fn with_default_slice(mut self, slice: &str) -> Self {
+        self.default_slice = slice.to_string();
+        self
+    }
+
+    /// Slice of units without Slice=
+    pub fn default_slice(&self) -> &str {
+        &self.default_slice
     }
 
     /// Create a cgroup for a unit
@@ -50,7 +124,7 @@ impl CgroupManager {
     pub fn create_cgroup(&self, slice: Option<&str>, unit_name: &str) -> io::Result<PathBuf> {
         let path = match slice {
             Some(s) => self.root.join(s).join(unit_name),
-            None => self.root.join("system.slice").join(unit_name),
+            None => self.root.join(&self.default_slice).join(unit_name),
         };
 
         // Create parent slice if needed
@@ -118,31 +192,17 @@ impl CgroupManager {
 
     /// Set memory limit for a cgroup
     pub fn set_memory_max(&self, cgroup_path: &Path, bytes: u64) -> io::Result<()> {
-        let file = cgroup_path.join("memory.max");
-        std::fs::write(&file, bytes.to_string())?;
-        Ok(())
+        Ok(CgroupWriter::new(cgroup_path).memory_max(bytes).apply()?)
     }
 
     /// Set CPU quota for a cgroup (percentage, e.g., 50 = 50%)
     pub fn set_cpu_quota(&self, cgroup_path: &Path, percent: u32) -> io::Result<()> {
-        // CPU quota is expressed as microseconds per 100ms period
-        // 50% = 50000us per 100000us period
-        let quota_us = percent as u64 * 1000;
-        let file = cgroup_path.join("cpu.max");
-        std::fs::write(&file, format!("{} 100000", quota_us))?;
-        Ok(())
+        Ok(CgroupWriter::new(cgroup_path).cpu_quota(percent).apply()?)
     }
 
     /// Set max number of tasks/processes
     pub fn set_tasks_max(&self, cgroup_path: &Path, max: u64) -> io::Result<()> {
-        let file = cgroup_path.join("pids.max");
-        let value = if max == u64::MAX {
-            "max".to_string()
-        } else {
-            max.to_string()
-        };
-        std::fs::write(&file, value)?;
-        Ok(())
+        Ok(CgroupWriter::new(cgroup_path).tasks_max(max).apply()?)
     }
 
     /// Watch for cgroup becoming empty (polls cgroup.events)
@@ -179,9 +239,146 @@ pub struct CgroupLimits {
     // Note: DeviceAllow is handled via mount namespace isolation in sandbox.rs
 }
 
+/// Writes a set of cgroup attribute files in one pass
+///
+/// The cgroup directory is opened once and each attribute is written relative
+/// to it. Every write is attempted; failures are collected into one error.
+#[derive(Debug)]
+pub struct CgroupWriter {
+    dir: PathBuf,
+    writes: Vec<(&'static str, String)>,
+}
+
+impl CgroupWriter {
+    pub fn new(cgroup_path: &Path) -> Self {
+        Self {
+            dir: cgroup_path.to_path_buf(),
+            writes: Vec::new(),
+        }
+    }
+
+    /// Queue a raw attribute write (e.g. "io.weight", "default 100")
+    pub fn set(mut self, file: &'static str, value: impl Into<String>) -> Self {
+        self.writes.push((file, value.into()));
+        self
+    }
+
+    pub fn memory_max(self, bytes: u64) -> Self {
+        self.set("memory.max", bytes.to_string())
+    }
+
+    /// CPU quota as a percentage of one CPU, written as microseconds per 100ms period
+    pub fn cpu_quota(self, percent: u32) -> Self {
+        let quota_us = percent as u64 * 1000;
+        self.set("cpu.max", format!("{} 100000", quota_us))
+    }
+
+    pub fn tasks_max(self, max: u64) -> Self {
+        let value = if max == u64::MAX {
+            "max".to_string()
+        } else {
+            max.to_string()
+        };
+        self.set("pids.max", value)
+    }
+
+    /// Queue every limit that is set in `limits`
+    pub fn limits(mut self, limits: &CgroupLimits) -> Self {
+        if let Some(bytes) = limits.memory_max {
+            self = self.memory_max(bytes);
+        }
+        if let Some(percent) = limits.cpu_quota {
+            self = self.cpu_quota(percent);
+        }
+        if let Some(tasks) = limits.tasks_max {
+            self = self.tasks_max(tasks as u64);
+        }
+        self
+    }
+
+    pub fn apply(self) -> Result<(), CgroupWriteError> {
+        if self.writes.is_empty() {
+            return Ok(());
+        }
+        let dir = match std::fs::File::open(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) => {
+                return Err(CgroupWriteError {
+                    path: self.dir,
+                    failures: vec![(".", e)],
+                })
+            }
+        };
+
+        let failures: Vec<_> = self
+            .writes
+            .iter()
+            .filter_map(|(file, value)| write_at(&dir, file, value).err().map(|e| (*file, e)))
+            .collect();
+        if failures.is_empty() {
+            return Ok(());
+        }
+        Err(CgroupWriteError {
+            path: self.dir,
+            failures,
+        })
+    }
+}
+
+fn write_at(dir: &std::fs::File, file: &str, value: &str) -> io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    let name = std::ffi::CString::new(file)?;
+    let fd = unsafe {
+        libc::openat(
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC | libc::O_CLOEXEC,
+            0o644,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut attr = unsafe { std::fs::File::from_raw_fd(fd) };
+    attr.write_all(value.as_bytes())
+}
+
+/// Attribute writes that failed in a `CgroupWriter::apply`
+#[derive(Debug)]
+pub struct CgroupWriteError {
+    pub path: PathBuf,
+    pub failures: Vec<(&'static str, io::Error)>,
+}
+
+impl std::fmt::Display for CgroupWriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: ", self.path.display())?;
+        for (i, (file, error)) in self.failures.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}: {}", file, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CgroupWriteError {}
+
+impl From<CgroupWriteError> for io::Error {
+    fn from(error: CgroupWriteError) -> Self {
+        match error.failures.as_slice() {
+            [(_, only)] => io::Error::new(only.kind(), error.to_string()),
+            _ => io::Error::other(error),
+        }
+    }
+}
+
 impl CgroupManager {
     /// Create a cgroup for a service, move the PID into it, and apply limits
-    /// If slice is None, defaults to system.slice
+    /// If slice is None, defaults to the default slice
     pub fn setup_service_cgroup(
         &self,
         service_name: &str,
@@ -189,33 +386,44 @@ impl CgroupManager {
         limits: &CgroupLimits,
         slice: Option<&str>,
     ) -> io::Result<PathBuf> {
-        // Create the cgroup in the specified slice (or system.slice by default)
-        let slice = slice.unwrap_or(SYSTEM_SLICE);
+        // Create the cgroup in the specified slice (or the default slice)
+        let slice = slice.unwrap_or(&self.default_slice);
         let cgroup_path = self.create_cgroup(Some(slice), service_name)?;
 
         // Move the process into the cgroup
         self.add_pid(&cgroup_path, pid)?;
 
         // Apply resource limits
-        if let Some(mem) = limits.memory_max {
-            if let Err(e) = self.set_memory_max(&cgroup_path, mem) {
-                log::warn!("Failed to set memory limit for {}: {}", service_name, e);
-            }
+        if let Err(e) = CgroupWriter::new(&cgroup_path).limits(limits).apply() {
+            log::warn!(
+                "Failed to apply resource limits for {}: {}",
+                service_name,
+                e
+            );
         }
 
-        if let Some(cpu) = limits.cpu_quota {
-            if let Err(e) = self.set_cpu_quota(&cgroup_path, cpu) {
-                log::warn!("Failed to set CPU quota for {}: {}", service_name, e);
-            }
-        }
+        Ok(cgroup_path)
+    }
 
-        if let Some(tasks) = limits.tasks_max {
-            if let Err(e) = self.set_tasks_max(&cgroup_path, tasks as u64) {
-                log::warn!("Failed to set tasks limit for {}: {}", service_name, e);
-            }
-        }
+    /// Enable `controllers` (e.g. "memory", "cpu") in the root and every slice
+    /// down to `slice`, so service cgroups created there get accounting files
+    pub fn enable_controllers(&self, slice: Option<&str>, controllers: &[&str]) -> io::Result<()> {
+        let slice_path = self.root.join(slice.unwrap_or(&self.default_slice));
+        std::fs::create_dir_all(&slice_path)?;
 
-        Ok(cgroup_path)
+        let enable_str = controllers
+            .iter()
+            .map(|c| format!("+{}", c))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let relative = slice_path.strip_prefix(&self.root).unwrap_or(Path::new(""));
+        let mut level = self.root.clone();
+        std::fs::write(level.join("cgroup.subtree_control"), &enable_str)?;
+        for component in relative.components() {
+            level.push(component);
+            std::fs::write(level.join("cgroup.subtree_control"), &enable_str)?;
+        }
+        Ok(())
     }
 
     /// M19: Enable cgroup delegation for a service
@@ -264,13 +472,13 @@ impl CgroupManager {
     }
 
     /// Clean up a service cgroup (remove if empty)
-    /// If slice is None, defaults to system.slice
+    /// If slice is None, defaults to the default slice
     pub fn cleanup_service_cgroup(
         &self,
         service_name: &str,
         slice: Option<&str>,
     ) -> io::Result<()> {
-        let slice = slice.unwrap_or(SYSTEM_SLICE);
+        let slice = slice.unwrap_or(&self.default_slice);
         let cgroup_path = self.root.join(slice).join(service_name);
 
         if !cgroup_path.exists() {
@@ -298,10 +506,18 @@ impl CgroupManager {
 
     /// Get the cgroup path for a service
     pub fn service_cgroup_path(&self, service_name: &str) -> PathBuf {
-        self.root.join(SYSTEM_SLICE).join(service_name)
+        self.root.join(&self.default_slice).join(service_name)
     }
 }
 
+/// The user@UID.service cgroup that `cgroup` (from /proc/self/cgroup) is,
+/// or is the init.scope of
+fn delegated_user_service(cgroup: &str, uid: u32) -> Option<&str> {
+    let service = cgroup.strip_suffix("/init.scope").unwrap_or(cgroup);
+    let name = format!("user@{}.service", uid);
+    (service.rsplit('/').next() == Some(name.as_str())).then_some(service)
+}
+
 /// Create a scope for a logind session
 pub async fn create_session_scope(
     cgroup_manager: &CgroupManager,
@@ -346,7 +562,10 @@ mod tests {
             std::env::temp_dir().join(format!("sysd-cgroup-test-{}-{id}", std::process::id()));
         let _ = std::fs::remove_dir_all(&dir);
         std::fs::create_dir_all(&dir).unwrap();
-        let manager = CgroupManager { root: dir.clone() };
+        let manager = CgroupManager {
+            root: dir.clone(),
+            default_slice: SYSTEM_SLICE.to_string(),
+        };
         (TempRoot(dir), manager)
     }
 
@@ -386,6 +605,26 @@ mod tests {
         );
     }
 
+    #[test]
+    fn default_slice_places_units_without_slice() {
+        let (_root, manager) = temp_manager();
+        let manager = manager.with_default_slice("batch.slice");
+        let path = manager.create_cgroup(None, "job.service").unwrap();
+        assert_eq!(path, manager.root.join("batch.slice").join("job.service"));
+        assert_eq!(manager.service_cgroup_path("job.service"), path);
+    }
+
+    #[test]
+    fn user_manager_cgroup_is_its_user_service_or_init_scope() {
+        let service = "/user.slice/user-1000.slice/user@1000.service";
+        assert_eq!(delegated_user_service(service, 1000), Some(service));
+        let init_scope = format!("{}/init.scope", service);
+        assert_eq!(delegated_user_service(&init_scope, 1000), Some(service));
+        assert_eq!(delegated_user_service(service, 1001), None);
+        let session = "/user.slice/user-1000.slice/session-2.scope";
+        assert_eq!(delegated_user_service(session, 1000), None);
+    }
+
     #[test]
     fn test_cgroup_manager_new_on_linux() {
         // This test verifies that CgroupManager::new() works on systems with cgroups
@@ -398,6 +637,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn enable_controllers_writes_root_and_slice_subtree_control() {
+        let (_root, manager) = temp_manager();
+
+        manager
+            .enable_controllers(Some("app.slice"), &["memory", "cpu"])
+            .unwrap();
+
+        for dir in [manager.root.clone(), manager.root.join("app.slice")] {
+            assert_eq!(
+                std::fs::read_to_string(dir.join("cgroup.subtree_control")).unwrap(),
+                "+memory +cpu"
+            );
+        }
+    }
+
     #[test]
     fn create_cgroup_uses_default_or_explicit_slice() {
         let (_dir, manager) = temp_manager();
@@ -489,6 +744,28 @@ mod tests {
         );
     }
 
+    #[test]
+    fn cgroup_writer_attempts_every_write_and_reports_all_failures() {
+        let (_dir, manager) = temp_manager();
+        let cgroup = manager.create_cgroup(None, "demo.service").unwrap();
+
+        let error = CgroupWriter::new(&cgroup)
+            .set("missing/io.weight", "default 100")
+            .memory_max(4096)
+            .set("missing/cpu.weight", "50")
+            .apply()
+            .unwrap_err();
+
+        assert_eq!(
+            std::fs::read_to_string(cgroup.join("memory.max")).unwrap(),
+            "4096"
+        );
+        let failed: Vec<&str> = error.failures.iter().map(|(file, _)| *file).collect();
+        assert_eq!(failed, ["missing/io.weight", "missing/cpu.weight"]);
+        assert!(error.to_string().contains("missing/cpu.weight"));
+        assert_eq!(io::Error::from(error).kind(), io::ErrorKind::Other);
+    }
+
     #[test]
     fn setup_service_cgroup_applies_limits_and_cleanup_skips_non_empty_cgroup() {
         let (_dir, manager) = temp_manager();
@@ -0,0 +1,367 @@
+//! RestrictFileSystems= via the BPF LSM
+//!
+//! A single program on the file_open LSM hook serves every unit. It looks up
+//! the cgroup of the opening task in a map of restricted cgroups, and for
+//! those checks the superblock magic of the file against the unit's list:
+//! outside an allow list, or inside a deny list, the open fails with EPERM.
+//!
+//! Needs the bpf LSM (lsm=...,bpf on the kernel command line) and kernel BTF,
+//! which supplies the hook and the struct offsets the program follows.
+
+use std::collections::{BTreeSet, HashMap};
+use std::io;
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use super::bpf::*;
+use super::btf::Btf;
+
+const LSM_LIST: &str = "/sys/kernel/security/lsm";
+
+/// Cgroups the maps can hold at once
+const MAX_CGROUPS: u32 = 1024;
+const MAX_ENTRIES: u32 = 16384;
+
+const ALLOW_LIST: u32 = 0;
+const DENY_LIST: u32 = 1;
+
+/// The loaded program and the lists of the units it enforces
+#[derive(Debug)]
+pub struct FileSystemRestrictor {
+    _link: OwnedFd,
+    /// cgroup id -> ALLOW_LIST or DENY_LIST
+    cgroups: OwnedFd,
+    /// (cgroup id, magic) -> listed
+    file_systems: OwnedFd,
+    /// What each unit put in the maps, removed on its next start
+    units: HashMap<String, (u64, Vec<u64>)>,
+}
+
+impl FileSystemRestrictor {
+    /// Whether the program could be loaded: the bpf LSM is enabled and the
+    /// kernel has BTF
+    pub fn probe() -> io::Result<()> {
+        check_bpf_lsm()?;
+        Btf::probe()
+    }
+
+    /// Load and attach the program; fails on kernels without the bpf LSM
+    pub fn load() -> io::Result<Self> {
+        check_bpf_lsm()?;
+        let btf = Btf::load()?;
+        let missing = |what: &str| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} not in kernel BTF", what),
+            )
+        };
+        let hook = btf
+            .function("bpf_lsm_file_open")
+            .ok_or_else(|| missing("bpf_lsm_file_open"))?;
+        let offsets = Offsets {
+            file_inode: btf
+                .member_offset("file", "f_inode")
+                .ok_or_else(|| missing("file.f_inode"))?,
+            inode_sb: btf
+                .member_offset("inode", "i_sb")
+                .ok_or_else(|| missing("inode.i_sb"))?,
+            sb_magic: btf
+                .member_offset("super_block", "s_magic")
+                .ok_or_else(|| missing("super_block.s_magic"))?,
+        };
+
+        let cgroups = create_map(BPF_MAP_TYPE_HASH, 8, 4, MAX_CGROUPS)?;
+        let file_systems = create_map(BPF_MAP_TYPE_HASH, 16, 1, MAX_ENTRIES)?;
+        let insns = build_program(cgroups.as_raw_fd(), file_systems.as_raw_fd(), &offsets);
+        let link = attach_lsm_program(hook, "sysd_restrict_fs", &insns)?;
+        Ok(Self {
+            _link: link,
+            cgroups,
+            file_systems,
+            units: HashMap::new(),
+        })
+    }
+
+    /// Enforce the RestrictFileSystems= list of `unit` on its cgroup,
+    /// replacing the list of an earlier run
+    pub fn restrict(&mut self, unit: &str, cgroup_path: &Path, list: &[String]) -> io::Result<()> {
+        self.release(unit);
+        let cgroup_id = std::fs::metadata(cgroup_path)?.ino();
+        let (deny, magics) = resolve_file_systems(list);
+        let magics: Vec<u64> = magics.into_iter().collect();
+        for &magic in &magics {
+            map_update(&self.file_systems, &[cgroup_id, magic], &1u8)?;
+        }
+        let mode = if deny { DENY_LIST } else { ALLOW_LIST };
+        self.units.insert(unit.to_string(), (cgroup_id, magics));
+        map_update(&self.cgroups, &cgroup_id, &mode)
+    }
+
+    /// Drop the list of `unit` from the maps
+    pub fn release(&mut self, unit: &str) {
+        let Some((cgroup_id, magics)) = self.units.remove(unit) else {
+            return;
+        };
+        let _ = map_delete(&self.cgroups, &cgroup_id);
+        for magic in magics {
+            let _ = map_delete(&self.file_systems, &[cgroup_id, magic]);
+        }
+    }
+}
+
+fn check_bpf_lsm() -> io::Result<()> {
+    let lsms = std::fs::read_to_string(LSM_LIST).unwrap_or_default();
+    if lsms.trim().split(',').any(|lsm| lsm == "bpf") {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "the bpf LSM is not enabled (add bpf to lsm= on the kernel command line)",
+    ))
+}
+
+/// Superblock magics by file system type (<linux/magic.h>)
+const FILE_SYSTEM_MAGICS: &[(&str, u64)] = &[
+    ("anon_inodefs", 0x0904_1934),
+    ("autofs", 0x0187),
+    ("binfmt_misc", 0x4249_4e4d),
+    ("bpf", 0xcafe_4a11),
+    ("btrfs", 0x9123_683e),
+    ("ceph", 0x00c3_6400),
+    ("cgroup", 0x0027_e0eb),
+    ("cgroup2", 0x6367_7270),
+    ("cifs", 0xff53_4d42),
+    ("configfs", 0x6265_6570),
+    ("debugfs", 0x6462_6720),
+    ("devpts", 0x1cd1),
+    ("devtmpfs", 0x0102_1994),
+    ("efivarfs", 0xde5e_81e4),
+    ("erofs", 0xe0f5_e1e2),
+    ("exfat", 0x2011_bab0),
+    ("ext2", 0xef53),
+    ("ext3", 0xef53),
+    ("ext4", 0xef53),
+    ("f2fs", 0xf2f5_2010),
+    ("fuse", 0x6573_5546),
+    ("fusectl", 0x6573_5543),
+    ("hugetlbfs", 0x9584_58f6),
+    ("iso9660", 0x9660),
+    ("mqueue", 0x1980_0202),
+    ("nfs", 0x6969),
+    ("nfs4", 0x6969),
+    ("ntfs3", 0x7366_746e),
+    ("overlay", 0x794c_7630),
+    ("pipefs", 0x5049_5045),
+    ("proc", 0x9fa0),
+    ("pstore", 0x6165_676c),
+    ("ramfs", 0x8584_58f6),
+    ("securityfs", 0x7363_6673),
+    ("smb3", 0xfe53_4d42),
+    ("sockfs", 0x534f_434b),
+    ("squashfs", 0x7371_7368),
+    ("sysfs", 0x6265_6572),
+    ("tmpfs", 0x0102_1994),
+    ("tracefs", 0x7472_6163),
+    ("udf", 0x1501_3346),
+    ("vfat", 0x4d44),
+    ("xfs", 0x5846_5342),
+];
+
+/// Named sets as in systemd; @known is every type above
+const FILE_SYSTEM_GROUPS: &[(&str, &[&str])] = &[
+    ("anonymous", &["anon_inodefs", "pipefs", "sockfs"]),
+    ("application", &["autofs", "fuse", "overlay"]),
+    (
+        "auxiliary-api",
+        &[
+            "binfmt_misc",
+            "configfs",
+            "efivarfs",
+            "fusectl",
+            "hugetlbfs",
+            "securityfs",
+        ],
+    ),
+    (
+        "basic-api",
+        &[
+            "cgroup", "cgroup2", "devpts", "devtmpfs", "mqueue", "proc", "sysfs",
+        ],
+    ),
+    (
+        "common-block",
+        &[
+            "btrfs", "erofs", "exfat", "ext4", "f2fs", "iso9660", "ntfs3", "squashfs", "udf",
+            "vfat", "xfs",
+        ],
+    ),
+    ("network", &["ceph", "cifs", "nfs", "nfs4", "smb3"]),
+    ("privileged-api", &["bpf", "debugfs", "pstore", "tracefs"]),
+    ("temporary", &["ramfs", "tmpfs"]),
+];
+
+fn file_system_magics(name: &str) -> Vec<u64> {
+    if let Some(group) = name.strip_prefix('@') {
+        if group == "known" {
+            return FILE_SYSTEM_MAGICS.iter().map(|&(_, magic)| magic).collect();
+        }
+        let Some((_, members)) = FILE_SYSTEM_GROUPS.iter().find(|(g, _)| *g == group) else {
+            log::warn!("Unknown file system group @{}", group);
+            return Vec::new();
+        };
+        return members.iter().flat_map(|m| file_system_magics(m)).collect();
+    }
+    match FILE_SYSTEM_MAGICS.iter().find(|(n, _)| *n == name) {
+        Some(&(_, magic)) => vec![magic],
+        None => {
+            log::warn!("Unknown file system type {}", name);
+            Vec::new()
+        }
+    }
+}
+
+/// Whether `list` is a deny list (leading ~) and the magics it names
+fn resolve_file_systems(list: &[String]) -> (bool, BTreeSet<u64>) {
+    let deny = list.first().is_some_and(|first| first.starts_with('~'));
+    let magics = list
+        .iter()
+        .map(|name| name.strip_prefix('~').unwrap_or(name))
+        .filter(|name| !name.is_empty())
+        .flat_map(file_system_magics)
+        .collect();
+    (deny, magics)
+}
+
+/// Where the program finds the superblock magic of the opened file
+struct Offsets {
+    file_inode: i16,
+    inode_sb: i16,
+    sb_magic: i16,
+}
+
+// Stack slots of the (cgroup id, magic) key
+const CGROUP_SLOT: i16 = -16;
+const MAGIC_SLOT: i16 = -8;
+
+/// int file_open(struct file *file): 0 or -EPERM
+fn build_program(cgroups: RawFd, file_systems: RawFd, offsets: &Offsets) -> Vec<Insn> {
+    let mut asm = Assembler::default();
+    let (permit, refuse, unlisted) = (asm.label(), asm.label(), asm.label());
+
+    asm.emit(insn(BPF_LDX | BPF_MEM | BPF_DW, R6, R1, 0, 0));
+    asm.emit(insn(
+        BPF_JMP | BPF_CALL,
+        0,
+        0,
+        0,
+        BPF_FUNC_GET_CURRENT_CGROUP_ID,
+    ));
+    asm.emit(insn(BPF_STX | BPF_MEM | BPF_DW, R10, R0, CGROUP_SLOT, 0));
+    emit_lookup(&mut asm, cgroups);
+    asm.jump(BPF_JEQ | BPF_K, R0, 0, 0, permit);
+    asm.emit(insn(BPF_LDX | BPF_MEM | BPF_W, R7, R0, 0, 0));
+
+    // file->f_inode->i_sb->s_magic
+    asm.emit(insn(
+        BPF_LDX | BPF_MEM | BPF_DW,
+        R1,
+        R6,
+        offsets.file_inode,
+        0,
+    ));
+    asm.emit(insn(
+        BPF_LDX | BPF_MEM | BPF_DW,
+        R1,
+        R1,
+        offsets.inode_sb,
+        0,
+    ));
+    asm.emit(insn(
+        BPF_LDX | BPF_MEM | BPF_DW,
+        R1,
+        R1,
+        offsets.sb_magic,
+        0,
+    ));
+    asm.emit(insn(BPF_STX | BPF_MEM | BPF_DW, R10, R1, MAGIC_SLOT, 0));
+    emit_lookup(&mut asm, file_systems);
+    asm.jump(BPF_JEQ | BPF_K, R0, 0, 0, unlisted);
+    asm.jump(BPF_JEQ | BPF_K, R7, 0, ALLOW_LIST as i32, permit);
+    asm.jump(BPF_JA, 0, 0, 0, refuse);
+    asm.bind(unlisted);
+    asm.jump(BPF_JEQ | BPF_K, R7, 0, DENY_LIST as i32, permit);
+
+    asm.bind(refuse);
+    asm.exit_with(-libc::EPERM);
+    asm.bind(permit);
+    asm.exit_with(0);
+    asm.finish()
+}
+
+/// r0 = lookup(map, the key at CGROUP_SLOT)
+fn emit_lookup(asm: &mut Assembler, map: RawFd) {
+    asm.load_map(R1, map);
+    asm.emit(insn(BPF_ALU64 | BPF_MOV | BPF_X, R2, R10, 0, 0));
+    asm.emit(insn(
+        BPF_ALU64 | BPF_ADD | BPF_K,
+        R2,
+        0,
+        0,
+        i32::from(CGROUP_SLOT),
+    ));
+    asm.emit(insn(BPF_JMP | BPF_CALL, 0, 0, 0, BPF_FUNC_MAP_LOOKUP_ELEM));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn lists_resolve_to_magics_and_groups_expand() {
+        let (deny, magics) = resolve_file_systems(&list(&["ext4", "@temporary", "nope"]));
+        assert!(!deny);
+        assert_eq!(magics, BTreeSet::from([0xef53, 0x0102_1994, 0x8584_58f6]));
+
+        let (deny, magics) = resolve_file_systems(&list(&["~@network", "fuse"]));
+        assert!(deny);
+        assert!(magics.contains(&0x6969));
+        assert!(magics.contains(&0x6573_5546));
+
+        let (_, known) = resolve_file_systems(&list(&["@known"]));
+        for (_, members) in FILE_SYSTEM_GROUPS {
+            for member in *members {
+                assert!(file_system_magics(member).iter().all(|m| known.contains(m)));
+            }
+        }
+    }
+
+    #[test]
+    fn program_ends_in_refuse_and_permit_exits() {
+        let offsets = Offsets {
+            file_inode: 32,
+            inode_sb: 40,
+            sb_magic: 96,
+        };
+        let insns = build_program(3, 4, &offsets);
+        let refuse_at = insns.len() - 4;
+        let permit_at = insns.len() - 2;
+        assert_eq!(insns[refuse_at].imm, -libc::EPERM);
+        assert_eq!(insns[permit_at].imm, 0);
+
+        let targets = jump_targets(&insns);
+        assert!(targets.iter().all(|&target| target < insns.len()));
+        assert!(targets.contains(&refuse_at));
+        assert!(targets.contains(&permit_at));
+        let loads: Vec<_> = insns
+            .iter()
+            .filter(|insn| insn.code == BPF_LDX | BPF_MEM | BPF_DW)
+            .map(|insn| insn.off)
+            .collect();
+        assert_eq!(loads, [0, 32, 40, 96]);
+    }
+}
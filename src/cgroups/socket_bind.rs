@@ -0,0 +1,155 @@
+//! SocketBindAllow=/SocketBindDeny= via cgroup eBPF
+//!
+//! One cgroup/bind4 and one cgroup/bind6 program go on the service cgroup.
+//! A bind matching an allow rule succeeds, otherwise one matching a deny rule
+//! fails with EPERM, and anything else succeeds, as in systemd. Port 0 asks
+//! the kernel for an ephemeral port and is never refused.
+
+use std::fs::File;
+use std::io;
+use std::os::fd::OwnedFd;
+use std::path::Path;
+
+use super::bpf::*;
+use crate::units::{BindFamily, BindProtocol, SocketBindRule};
+
+/// Bind filters attached to one cgroup; dropping it detaches them
+#[derive(Debug)]
+pub struct SocketBindFilter {
+    _programs: Vec<Attachment>,
+}
+
+impl SocketBindFilter {
+    /// Load the programs for `allow`/`deny` and attach them to `cgroup_path`
+    pub fn attach(
+        cgroup_path: &Path,
+        allow: &[SocketBindRule],
+        deny: &[SocketBindRule],
+    ) -> io::Result<Self> {
+        let cgroup = OwnedFd::from(File::open(cgroup_path)?);
+        let mut programs = Vec::new();
+        for (family, attach_type) in [
+            (BindFamily::Ipv4, BPF_CGROUP_INET4_BIND),
+            (BindFamily::Ipv6, BPF_CGROUP_INET6_BIND),
+        ] {
+            let insns = build_program(family, allow, deny);
+            let program = load_program(
+                BPF_PROG_TYPE_CGROUP_SOCK_ADDR,
+                attach_type,
+                "sysd_sock_bind",
+                &insns,
+            )?;
+            programs.push(Attachment::new(&cgroup, program, attach_type)?);
+        }
+        Ok(Self {
+            _programs: programs,
+        })
+    }
+}
+
+const BPF_CGROUP_INET4_BIND: u32 = 8;
+const BPF_CGROUP_INET6_BIND: u32 = 9;
+
+// struct bpf_sock_addr fields
+const SOCK_ADDR_USER_PORT: i16 = 24;
+const SOCK_ADDR_PROTOCOL: i16 = 36;
+
+/// The program for one family: r2 holds the port, r3 the protocol
+fn build_program(
+    family: BindFamily,
+    allow: &[SocketBindRule],
+    deny: &[SocketBindRule],
+) -> Vec<Insn> {
+    let mut asm = Assembler::default();
+    let (permit, refuse) = (asm.label(), asm.label());
+    let applies = |rule: &&SocketBindRule| rule.family.is_none_or(|f| f == family);
+
+    asm.emit(insn(BPF_ALU64 | BPF_MOV | BPF_X, R6, R1, 0, 0));
+    // user_port is in network order
+    asm.emit(insn(
+        BPF_LDX | BPF_MEM | BPF_W,
+        R2,
+        R6,
+        SOCK_ADDR_USER_PORT,
+        0,
+    ));
+    asm.emit(insn(BPF_ALU | BPF_END | BPF_TO_BE, R2, 0, 0, 16));
+    asm.emit(insn(
+        BPF_LDX | BPF_MEM | BPF_W,
+        R3,
+        R6,
+        SOCK_ADDR_PROTOCOL,
+        0,
+    ));
+    asm.jump(BPF_JEQ | BPF_K, R2, 0, 0, permit);
+
+    for rule in allow.iter().filter(applies) {
+        emit_rule_match(&mut asm, rule, permit);
+    }
+    for rule in deny.iter().filter(applies) {
+        emit_rule_match(&mut asm, rule, refuse);
+    }
+
+    asm.bind(permit);
+    asm.exit_with(1);
+    asm.bind(refuse);
+    asm.exit_with(0);
+    asm.finish()
+}
+
+/// Jump to `on_match` if the bind in r2/r3 falls under `rule`
+fn emit_rule_match(asm: &mut Assembler, rule: &SocketBindRule, on_match: Label) {
+    let next = asm.label();
+    if let Some(protocol) = rule.protocol {
+        let number = match protocol {
+            BindProtocol::Tcp => libc::IPPROTO_TCP,
+            BindProtocol::Udp => libc::IPPROTO_UDP,
+        };
+        asm.jump(BPF_JNE | BPF_K, R3, 0, number, next);
+    }
+    if let Some((low, high)) = rule.ports {
+        asm.jump(BPF_JLT | BPF_K, R2, 0, i32::from(low), next);
+        asm.jump(BPF_JGT | BPF_K, R2, 0, i32::from(high), next);
+    }
+    asm.jump(BPF_JA, 0, 0, 0, on_match);
+    asm.bind(next);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(s: &str) -> SocketBindRule {
+        SocketBindRule::parse(s).unwrap()
+    }
+
+    #[test]
+    fn rules_of_the_other_family_are_left_out() {
+        let allow = [rule("ipv6:tcp:8000-8080")];
+        let deny = [rule("any")];
+        let ipv4 = build_program(BindFamily::Ipv4, &allow, &deny);
+        let ipv6 = build_program(BindFamily::Ipv6, &allow, &deny);
+        // Protocol and both range bounds are only checked on IPv6
+        assert_eq!(ipv6.len(), ipv4.len() + 4);
+
+        let range: Vec<_> = ipv6
+            .iter()
+            .filter(|insn| matches!(insn.code & 0xf0, BPF_JLT | BPF_JGT))
+            .map(|insn| insn.imm)
+            .collect();
+        assert_eq!(range, [8000, 8080]);
+
+        // "any" in the deny list sends every other port to the EPERM exit
+        let refuse_at = ipv4.len() - 2;
+        assert_eq!(ipv4[refuse_at].imm, 0);
+        let targets = jump_targets(&ipv4);
+        assert!(targets.iter().all(|&target| target < ipv4.len()));
+        assert!(targets.contains(&refuse_at));
+    }
+
+    #[test]
+    fn attaching_to_a_plain_directory_fails() {
+        let dir = std::env::temp_dir();
+        assert!(SocketBindFilter::attach(&dir, &[], &[rule("any")]).is_err());
+    }
+}
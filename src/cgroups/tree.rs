@@ -0,0 +1,116 @@
+//! Cgroup hierarchy snapshot for `sysd cgls`
+//!
+//! Walks the cgroup tree and records the processes of each cgroup, so the
+//! containment of slices, scopes and services can be inspected.
+
+use std::path::{Path, PathBuf};
+
+use super::CGROUP_ROOT;
+
+/// A process in a cgroup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgroupProcess {
+    pub pid: u32,
+    /// Name from /proc/<pid>/comm (empty if the process already exited)
+    pub comm: String,
+}
+
+/// A cgroup and its descendants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CgroupNode {
+    /// Directory name ("-.slice" for the root)
+    pub name: String,
+    pub path: PathBuf,
+    pub processes: Vec<CgroupProcess>,
+    /// Child cgroups sorted by name
+    pub children: Vec<CgroupNode>,
+}
+
+impl CgroupNode {
+    /// Unit owning this cgroup, if the directory is named after one
+    pub fn unit(&self) -> Option<&str> {
+        const SUFFIXES: [&str; 3] = [".slice", ".scope", ".service"];
+        SUFFIXES
+            .iter()
+            .any(|suffix| self.name.ends_with(suffix))
+            .then_some(self.name.as_str())
+    }
+
+    /// Whether this cgroup or a descendant contains processes
+    pub fn is_populated(&self) -> bool {
+        !self.processes.is_empty() || self.children.iter().any(CgroupNode::is_populated)
+    }
+}
+
+/// The whole hierarchy below /sys/fs/cgroup
+pub fn read_tree() -> std::io::Result<CgroupNode> {
+    read_tree_at(&crate::root::path(CGROUP_ROOT), "-.slice")
+}
+
+/// The hierarchy below `path`, with `name` for the top node
+pub fn read_tree_at(path: &Path, name: &str) -> std::io::Result<CgroupNode> {
+    let mut children = Vec::new();
+    for entry in std::fs::read_dir(path)?.flatten() {
+        if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+            continue;
+        }
+        let child_name = entry.file_name().to_string_lossy().into_owned();
+        // Cgroups can disappear while walking
+        if let Ok(child) = read_tree_at(&entry.path(), &child_name) {
+            children.push(child);
+        }
+    }
+    children.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(CgroupNode {
+        name: name.to_string(),
+        path: path.to_path_buf(),
+        processes: read_processes(path),
+        children,
+    })
+}
+
+fn read_processes(path: &Path) -> Vec<CgroupProcess> {
+    let Ok(content) = std::fs::read_to_string(path.join("cgroup.procs")) else {
+        return Vec::new();
+    };
+    content
+        .lines()
+        .filter_map(|line| line.trim().parse().ok())
+        .map(|pid| CgroupProcess {
+            pid,
+            comm: std::fs::read_to_string(format!("/proc/{}/comm", pid))
+                .map(|comm| comm.trim_end().to_string())
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_records_children_and_processes() {
+        let root = std::env::temp_dir().join(format!("sysd-cgroup-tree-{}", std::process::id()));
+        let scope = root.join("user.slice/user-1000.slice/session-2.scope");
+        std::fs::create_dir_all(&scope).unwrap();
+        std::fs::create_dir_all(root.join("system.slice")).unwrap();
+        std::fs::create_dir_all(root.join("init.scope")).unwrap();
+        let pid = std::process::id();
+        std::fs::write(scope.join("cgroup.procs"), format!("{}\n", pid)).unwrap();
+
+        let tree = read_tree_at(&root, "-.slice").unwrap();
+        let names: Vec<&str> = tree.children.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["init.scope", "system.slice", "user.slice"]);
+        assert_eq!(tree.unit(), Some("-.slice"));
+        assert!(tree.is_populated());
+        assert!(!tree.children[1].is_populated());
+
+        let session = &tree.children[2].children[0].children[0];
+        assert_eq!(session.unit(), Some("session-2.scope"));
+        assert_eq!(session.processes.len(), 1);
+        assert_eq!(session.processes[0].pid, pid);
+        assert!(!session.processes[0].comm.is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,266 @@
+//! Encrypted credentials (`sysd creds`, LoadCredentialEncrypted=)
+//!
+//! Credentials are sealed with AES-256-GCM under a key derived from a random
+//! host secret, so an encrypted credential can be stored on disk (or in a unit
+//! file) and is only readable on the machine holding the secret. The
+//! credential name is bound into the ciphertext: a blob encrypted as "tls.key"
+//! does not decrypt as any other credential. Every credential has a name;
+//! blobs without one are neither written nor opened.
+//!
+//! Blob layout (base64 encoded when written by `sysd creds encrypt`):
+//!
+//! ```text
+//! "SYSDCRD1" | name length (u16 LE) | name | nonce (12 bytes) | ciphertext + tag
+//! ```
+
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::Engine;
+use sha2::{Digest, Sha256};
+
+pub use crate::state_dir::HOST_KEY_PATH;
+
+const MAGIC: &[u8; 8] = b"SYSDCRD1";
+const HOST_KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CredsError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("Host key {0} is malformed")]
+    BadHostKey(String),
+
+    #[error("Not an encrypted credential")]
+    Format,
+
+    #[error("Credential is named {found:?}, expected {expected:?}")]
+    NameMismatch { expected: String, found: String },
+
+    #[error("Credential has no name")]
+    NoName,
+
+    #[error("Encryption failed")]
+    Encrypt,
+
+    #[error("Decryption failed (wrong host key or corrupted credential)")]
+    Decrypt,
+}
+
+/// Read the host secret, creating it (mode 0400) if it does not exist yet
+pub fn load_or_create_host_key(path: &Path) -> Result<Vec<u8>, CredsError> {
+    match load_host_key(path) {
+        Err(CredsError::Io(e)) if e.kind() == io::ErrorKind::NotFound => {}
+        result => return result,
+    }
+    let secret = random_bytes(HOST_KEY_LEN)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o400)
+        .open(path)?;
+    file.write_all(&secret)?;
+    log::info!("Created credential host key {}", path.display());
+    Ok(secret)
+}
+
+/// Read the host secret
+pub fn load_host_key(path: &Path) -> Result<Vec<u8>, CredsError> {
+    let secret = std::fs::read(path)?;
+    if secret.len() < HOST_KEY_LEN {
+        return Err(CredsError::BadHostKey(path.display().to_string()));
+    }
+    Ok(secret)
+}
+
+/// Seal `plaintext` as credential `name`; returns base64 text
+pub fn encrypt(host_key: &[u8], name: &str, plaintext: &[u8]) -> Result<String, CredsError> {
+    let header = header(name)?;
+    let nonce = random_bytes(NONCE_LEN)?;
+    let ciphertext = cipher(host_key)
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: plaintext,
+                aad: &header,
+            },
+        )
+        .map_err(|_| CredsError::Encrypt)?;
+
+    let mut blob = header;
+    blob.extend_from_slice(&nonce);
+    blob.extend_from_slice(&ciphertext);
+    Ok(base64::engine::general_purpose::STANDARD.encode(blob) + "\n")
+}
+
+/// Open a credential produced by `encrypt` (raw or base64). With
+/// `expected_name`, the embedded name must match.
+pub fn decrypt(
+    host_key: &[u8],
+    expected_name: Option<&str>,
+    data: &[u8],
+) -> Result<Vec<u8>, CredsError> {
+    let blob = match data.starts_with(MAGIC) {
+        true => data.to_vec(),
+        false => {
+            let text: Vec<u8> = data
+                .iter()
+                .copied()
+                .filter(|b| !b.is_ascii_whitespace())
+                .collect();
+            base64::engine::general_purpose::STANDARD
+                .decode(text)
+                .map_err(|_| CredsError::Format)?
+        }
+    };
+
+    let rest = blob
+        .strip_prefix(MAGIC.as_slice())
+        .ok_or(CredsError::Format)?;
+    let (len, rest) = rest.split_first_chunk::<2>().ok_or(CredsError::Format)?;
+    let name_len = u16::from_le_bytes(*len) as usize;
+    if rest.len() < name_len + NONCE_LEN {
+        return Err(CredsError::Format);
+    }
+    let (name, rest) = rest.split_at(name_len);
+    let name = std::str::from_utf8(name).map_err(|_| CredsError::Format)?;
+    if name.is_empty() {
+        return Err(CredsError::NoName);
+    }
+    if let Some(expected) = expected_name {
+        if name != expected {
+            return Err(CredsError::NameMismatch {
+                expected: expected.to_string(),
+                found: name.to_string(),
+            });
+        }
+    }
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let header_len = MAGIC.len() + 2 + name_len;
+    cipher(host_key)
+        .decrypt(
+            Nonce::from_slice(nonce),
+            Payload {
+                msg: ciphertext,
+                aad: &blob[..header_len],
+            },
+        )
+        .map_err(|_| CredsError::Decrypt)
+}
+
+fn header(name: &str) -> Result<Vec<u8>, CredsError> {
+    if name.is_empty() {
+        return Err(CredsError::NoName);
+    }
+    let len = u16::try_from(name.len()).map_err(|_| CredsError::Format)?;
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&len.to_le_bytes());
+    header.extend_from_slice(name.as_bytes());
+    Ok(header)
+}
+
+fn cipher(host_key: &[u8]) -> Aes256Gcm {
+    let key = Sha256::new()
+        .chain_update(b"sysd credential key\0")
+        .chain_update(host_key)
+        .finalize();
+    Aes256Gcm::new(&key)
+}
+
+fn random_bytes(len: usize) -> io::Result<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &[u8] = &[7u8; 32];
+
+    #[test]
+    fn roundtrip_binds_name_and_key() {
+        let sealed = encrypt(KEY, "tls.key", b"secret").unwrap();
+        assert!(!sealed.contains("secret"));
+
+        assert_eq!(
+            decrypt(KEY, Some("tls.key"), sealed.as_bytes()).unwrap(),
+            b"secret"
+        );
+        assert_eq!(decrypt(KEY, None, sealed.as_bytes()).unwrap(), b"secret");
+        assert!(matches!(
+            decrypt(KEY, Some("other"), sealed.as_bytes()),
+            Err(CredsError::NameMismatch { .. })
+        ));
+        assert!(matches!(
+            decrypt(&[8u8; 32], Some("tls.key"), sealed.as_bytes()),
+            Err(CredsError::Decrypt)
+        ));
+    }
+
+    #[test]
+    fn decrypt_accepts_raw_blobs_and_rejects_tampering() {
+        let sealed = encrypt(KEY, "db", b"data").unwrap();
+        let mut raw = base64::engine::general_purpose::STANDARD
+            .decode(sealed.trim())
+            .unwrap();
+        assert_eq!(decrypt(KEY, Some("db"), &raw).unwrap(), b"data");
+
+        let last = raw.len() - 1;
+        raw[last] ^= 1;
+        assert!(matches!(decrypt(KEY, None, &raw), Err(CredsError::Decrypt)));
+        assert!(matches!(
+            decrypt(KEY, None, b"plain text"),
+            Err(CredsError::Format)
+        ));
+    }
+
+    #[test]
+    fn credentials_without_a_name_are_refused() {
+        assert!(matches!(encrypt(KEY, "", b"data"), Err(CredsError::NoName)));
+
+        // A well-formed blob whose header carries an empty name, sealed with
+        // that header as AAD, so only the name check can reject it
+        let header = MAGIC.iter().copied().chain([0, 0]).collect::<Vec<u8>>();
+        let nonce = [0u8; NONCE_LEN];
+        let payload = Payload {
+            msg: b"data".as_slice(),
+            aad: &header,
+        };
+        let ciphertext = cipher(KEY)
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .unwrap();
+        let blob = [header, nonce.to_vec(), ciphertext].concat();
+        for expected in [None, Some("anything")] {
+            assert!(matches!(
+                decrypt(KEY, expected, &blob),
+                Err(CredsError::NoName)
+            ));
+        }
+    }
+
+    #[test]
+    fn host_key_is_created_once_with_private_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("sysd-creds-key-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("credential.secret");
+
+        let created = load_or_create_host_key(&path).unwrap();
+        assert_eq!(created.len(), HOST_KEY_LEN);
+        assert_eq!(load_or_create_host_key(&path).unwrap(), created);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o400);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,85 @@
+//! D-Bus errors with systemd's error names
+//!
+//! systemctl and other clients look at the error name rather than the
+//! message, e.g. to print "Unit foo.service not found." for NoSuchUnit.
+//! Manager errors without a systemd counterpart become the generic
+//! org.freedesktop.DBus.Error.InvalidArgs or Failed.
+
+use zbus::fdo;
+
+use crate::manager::ManagerError;
+
+#[derive(Debug, zbus::DBusError)]
+#[zbus(prefix = "org.freedesktop.systemd1")]
+pub enum BusError {
+    #[zbus(error)]
+    ZBus(zbus::Error),
+    NoSuchUnit(String),
+    LoadFailed(String),
+    UnitMasked(String),
+    UnitInactive(String),
+    UnitBusy(String),
+    JobTypeNotApplicable(String),
+    TransactionOrderIsCyclic(String),
+}
+
+impl From<fdo::Error> for BusError {
+    fn from(e: fdo::Error) -> Self {
+        BusError::ZBus(zbus::Error::FDO(Box::new(e)))
+    }
+}
+
+impl From<ManagerError> for BusError {
+    fn from(e: ManagerError) -> Self {
+        let message = e.to_string();
+        match e {
+            ManagerError::NotFound(_) => BusError::NoSuchUnit(message),
+            ManagerError::Parse(_) | ManagerError::InsecureUnitFile(_) => {
+                BusError::LoadFailed(message)
+            }
+            ManagerError::Masked(_) => BusError::UnitMasked(message),
+            ManagerError::NotActive(_) => BusError::UnitInactive(message),
+            ManagerError::CleanWhileActive(_) => BusError::UnitBusy(message),
+            ManagerError::IsTarget(_) => BusError::JobTypeNotApplicable(message),
+            ManagerError::Cycle(_) => BusError::TransactionOrderIsCyclic(message),
+            ManagerError::InvalidSignal(_)
+            | ManagerError::InvalidEnvironment(_)
+            | ManagerError::InvalidProperty(_)
+            | ManagerError::NotATarget(_)
+            | ManagerError::NotAService(_)
+            | ManagerError::UnsafePath(_) => fdo::Error::InvalidArgs(message).into(),
+            _ => fdo::Error::Failed(message).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zbus::DBusError;
+
+    #[test]
+    fn manager_errors_map_to_systemd_error_names() {
+        let name = |e: ManagerError| BusError::from(e).name().to_string();
+        assert_eq!(
+            name(ManagerError::NotFound("demo.service".to_string())),
+            "org.freedesktop.systemd1.NoSuchUnit"
+        );
+        assert_eq!(
+            name(ManagerError::Masked("demo.service".to_string())),
+            "org.freedesktop.systemd1.UnitMasked"
+        );
+        assert_eq!(
+            name(ManagerError::Cycle(vec!["a.service".to_string()])),
+            "org.freedesktop.systemd1.TransactionOrderIsCyclic"
+        );
+        assert_eq!(
+            name(ManagerError::InvalidSignal(99)),
+            "org.freedesktop.DBus.Error.InvalidArgs"
+        );
+        assert_eq!(
+            name(ManagerError::Io("disk full".to_string())),
+            "org.freedesktop.DBus.Error.Failed"
+        );
+    }
+}
@@ -0,0 +1,1468 @@
+//! org.freedesktop.login1.Manager subset (logind-lite)
+//!
+//! Served on machines without systemd-logind, so pam_systemd registers login
+//! sessions with sysd. CreateSession moves the session leader into
+//! `session-<id>.scope` under `user-<uid>.slice` and hands pam_systemd a
+//! FIFO; once it is closed at logout the session is closing, and it is
+//! forgotten with its scope when the scope has no processes left (or right
+//! away without cgroups). The user's runtime directory and user manager are
+//! not started from here.
+//!
+//! There is one seat, seat0; sessions asking for another one are refused.
+//! Screen lockers and idle daemons report a graphical session's idleness
+//! with its SetIdleHint(). Like logind, a seat and the whole machine are idle
+//! once all their sessions are: IdleHint, with IdleSinceHint when the last
+//! session became idle (or the first one became busy).
+//!
+//! Suspend(), Hibernate() and HybridSleep() honor inhibitor locks taken with
+//! Inhibit(): a block lock on "sleep" refuses them, while delay locks get
+//! PrepareForSleep(true) and up to `INHIBIT_DELAY_MAX` to let go before the
+//! machine sleeps (`Manager::sleep_now`); PrepareForSleep(false) follows on
+//! resume. A block lock on "idle" keeps the machine's IdleHint false. A lock
+//! is held until the returned file descriptor is closed. `Manager::sleep`
+//! (sysdctl suspend, HandleLidSwitch=) comes through here too.
+
+use std::collections::{BTreeMap, HashMap};
+use std::os::fd::OwnedFd;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::AsyncReadExt;
+use tokio::sync::{Mutex, Notify, RwLock};
+use tokio::task::AbortHandle;
+use zbus::{
+    fdo, interface,
+    message::Header,
+    object_server::SignalEmitter,
+    zvariant::{self, OwnedObjectPath, OwnedValue},
+    Connection,
+};
+
+use super::polkit::{Authorizer, HIBERNATE, MANAGE_SESSIONS, SUSPEND};
+use crate::cgroups::EmptyWatcher;
+use crate::manager::{KillWhom, Manager, SleepMode};
+
+pub const LOGIN1_PATH: &str = "/org/freedesktop/login1";
+pub const SEAT0: &str = "seat0";
+/// How long one wait for a scope to empty lasts before it is renewed
+const EMPTY_WAIT: Duration = Duration::from_secs(3600);
+/// Audit session id of processes outside any audit session
+const NO_AUDIT_SESSION: &str = "4294967295";
+/// How long delay inhibitors may hold up a sleep (logind's
+/// InhibitDelayMaxSec= default)
+const INHIBIT_DELAY_MAX: Duration = Duration::from_secs(5);
+/// What inhibitors can lock, in the order logind lists them
+const INHIBIT_WHATS: [&str; 7] = [
+    "shutdown",
+    "sleep",
+    "idle",
+    "handle-power-key",
+    "handle-suspend-key",
+    "handle-hibernate-key",
+    "handle-lid-switch",
+];
+
+/// A point in time as (CLOCK_REALTIME, CLOCK_MONOTONIC) microseconds, the
+/// pair logind's *Hint timestamps come in
+pub type Timestamp = (u64, u64);
+
+/// A login session registered by pam_systemd
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    pub id: String,
+    pub uid: u32,
+    pub user: String,
+    pub leader: u32,
+    pub service: String,
+    pub session_type: String,
+    pub class: String,
+    pub desktop: String,
+    /// seat0 or empty
+    pub seat: String,
+    pub vtnr: u32,
+    pub tty: String,
+    pub display: String,
+    pub remote: bool,
+    pub remote_user: String,
+    pub remote_host: String,
+    /// The session FIFO was closed (logout); processes may linger
+    pub closing: bool,
+    pub idle: bool,
+    /// When `idle` last changed
+    pub idle_since: Timestamp,
+}
+
+impl Session {
+    fn scope_name(&self) -> String {
+        format!("session-{}.scope", self.id)
+    }
+
+    /// Only graphical sessions report their idleness; logind finds that of
+    /// text sessions itself
+    fn is_graphical(&self) -> bool {
+        matches!(self.session_type.as_str(), "x11" | "wayland" | "mir")
+    }
+
+    fn state(&self) -> &'static str {
+        match self.closing {
+            true => "closing",
+            false => "active",
+        }
+    }
+}
+
+/// An inhibitor lock taken with Inhibit()
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inhibitor {
+    /// e.g. ["sleep", "idle"]
+    pub what: Vec<String>,
+    pub who: String,
+    pub why: String,
+    /// "block" or "delay"
+    pub mode: String,
+    pub uid: u32,
+    pub pid: u32,
+}
+
+/// logind's rule for a seat or the machine: idle once every session is,
+/// since the last one became idle; busy since the first one became busy
+pub fn aggregate_idle<'a>(sessions: impl Iterator<Item = &'a Session>) -> (bool, Timestamp) {
+    let mut idle = true;
+    let mut since = (0, 0);
+    for session in sessions {
+        if idle && !session.idle {
+            idle = false;
+            since = session.idle_since;
+        } else if session.idle == idle && session.idle_since > since {
+            since = session.idle_since;
+        }
+    }
+    (idle, since)
+}
+
+/// Now, as a `Timestamp`
+pub fn now() -> Timestamp {
+    let realtime = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64;
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    (
+        realtime,
+        ts.tv_sec as u64 * 1_000_000 + ts.tv_nsec as u64 / 1_000,
+    )
+}
+
+/// Registered sessions, shared by the interfaces, the session and seat
+/// objects, their scope and FIFO watchers and the bus connection
+#[derive(Clone, Default)]
+pub struct Logins {
+    inner: Arc<Mutex<Registry>>,
+    /// Notified whenever an inhibitor is released
+    released: Arc<Notify>,
+}
+
+#[derive(Default)]
+struct Registry {
+    sessions: BTreeMap<String, Session>,
+    /// Tasks waiting for a session's scope cgroup to empty
+    watchers: HashMap<String, AbortHandle>,
+    /// Sessions handed out without an audit session id ("c1", "c2", ...)
+    next_id: u64,
+    inhibitors: BTreeMap<u64, Inhibitor>,
+    next_inhibitor: u64,
+    /// Between PrepareForSleep(true) and PrepareForSleep(false)
+    preparing_for_sleep: bool,
+    connection: Option<Connection>,
+}
+
+impl Logins {
+    /// Serve seat0 and the Session objects on `conn`, now and as sessions
+    /// register
+    pub fn serve(&self, conn: Connection) {
+        let logins = self.clone();
+        tokio::spawn(async move {
+            let seat = SeatObject {
+                logins: logins.clone(),
+                id: SEAT0.to_string(),
+            };
+            if let Err(e) = conn.object_server().at(seat_object_path(SEAT0), seat).await {
+                log::warn!("Failed to serve {}: {}", SEAT0, e);
+            }
+            let ids: Vec<String> = {
+                let mut registry = logins.inner.lock().await;
+                registry.connection = Some(conn.clone());
+                registry.sessions.keys().cloned().collect()
+            };
+            for id in ids {
+                serve_session(&conn, &logins, &id).await;
+            }
+        });
+    }
+
+    async fn get(&self, id: &str) -> Option<Session> {
+        self.inner.lock().await.sessions.get(id).cloned()
+    }
+
+    pub async fn sessions(&self) -> Vec<Session> {
+        self.inner.lock().await.sessions.values().cloned().collect()
+    }
+
+    /// Session id for a new session led by `leader`: its audit session if it
+    /// has a free one, else the next "c<n>"
+    async fn new_id(&self, leader: u32) -> String {
+        let audit = std::fs::read_to_string(format!("/proc/{}/sessionid", leader))
+            .ok()
+            .map(|id| id.trim().to_string())
+            .filter(|id| !id.is_empty() && id != NO_AUDIT_SESSION);
+        let mut registry = self.inner.lock().await;
+        if let Some(id) = audit.filter(|id| !registry.sessions.contains_key(id)) {
+            return id;
+        }
+        loop {
+            registry.next_id += 1;
+            let id = format!("c{}", registry.next_id);
+            if !registry.sessions.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
+    async fn insert(&self, session: Session) {
+        let id = session.id.clone();
+        let connection = {
+            let mut registry = self.inner.lock().await;
+            registry.sessions.insert(id.clone(), session);
+            registry.connection.clone()
+        };
+        // Session objects read the registry; it must not be held here
+        if let Some(conn) = connection {
+            serve_session(&conn, self, &id).await;
+        }
+    }
+
+    /// Forget session `id`, its object and its scope watcher
+    async fn remove(&self, id: &str) -> Option<Session> {
+        let (session, connection) = {
+            let mut registry = self.inner.lock().await;
+            let session = registry.sessions.remove(id)?;
+            if let Some(watcher) = registry.watchers.remove(id) {
+                watcher.abort();
+            }
+            (session, registry.connection.clone())
+        };
+        if let Some(conn) = connection {
+            let path = session_object_path(id);
+            let _ = conn.object_server().remove::<SessionObject, _>(path).await;
+        }
+        Some(session)
+    }
+
+    /// Forget session `id` and drop its scope
+    async fn remove_with_scope(&self, id: &str, manager: &RwLock<Manager>) {
+        let Some(session) = self.remove(id).await else {
+            return;
+        };
+        let mut mgr = manager.write().await;
+        if let Err(e) = mgr.unregister_scope(&session.scope_name()).await {
+            log::debug!("Dropping the scope of session {}: {}", id, e);
+        }
+        mgr.publish_states();
+    }
+
+    /// Mark session `id` closing; without a scope watcher to forget it
+    /// later, forget it now
+    async fn close(&self, id: &str, manager: &RwLock<Manager>) {
+        let watched = {
+            let mut registry = self.inner.lock().await;
+            let Some(session) = registry.sessions.get_mut(id) else {
+                return;
+            };
+            log::info!("Session {} of {} closing", id, session.user);
+            session.closing = true;
+            registry.watchers.contains_key(id)
+        };
+        if !watched {
+            self.remove_with_scope(id, manager).await;
+        }
+        self.emit_idle_changed().await;
+    }
+
+    /// Close session `id` once pam_systemd closes its end of `fifo`
+    fn watch_fifo(&self, id: &str, fifo: OwnedFd, manager: Arc<RwLock<Manager>>) {
+        let logins = self.clone();
+        let id = id.to_string();
+        tokio::spawn(async move {
+            closed(fifo).await;
+            logins.close(&id, &manager).await;
+        });
+    }
+
+    /// Forget session `id` and drop its scope once `cgroup` has no processes
+    /// left
+    async fn watch_scope(&self, id: &str, cgroup: PathBuf, manager: Arc<RwLock<Manager>>) {
+        let logins = self.clone();
+        let id = id.to_string();
+        // Held until the watcher is recorded, so it cannot finish before that
+        let mut registry = self.inner.lock().await;
+        let watcher = tokio::spawn({
+            let id = id.clone();
+            async move {
+                let empty = EmptyWatcher::spawn(vec![cgroup.clone()]);
+                while !empty.wait(&cgroup, Instant::now() + EMPTY_WAIT).await {}
+                // Dropping the handle does not abort this task
+                logins.inner.lock().await.watchers.remove(&id);
+                log::info!("Session {} has no processes left", id);
+                logins.remove_with_scope(&id, &manager).await;
+                logins.emit_idle_changed().await;
+            }
+        });
+        registry.watchers.insert(id, watcher.abort_handle());
+    }
+
+    /// Set the idle hint of session `id`; false if it is unknown
+    async fn set_idle(&self, id: &str, idle: bool) -> bool {
+        let mut registry = self.inner.lock().await;
+        let Some(session) = registry.sessions.get_mut(id) else {
+            return false;
+        };
+        if session.idle != idle {
+            session.idle = idle;
+            session.idle_since = now();
+        }
+        true
+    }
+
+    /// IdleHint and IdleSinceHint of the sessions on `seat`, or of all of
+    /// them
+    async fn idle_hint(&self, seat: Option<&str>) -> (bool, Timestamp) {
+        let registry = self.inner.lock().await;
+        let sessions = registry
+            .sessions
+            .values()
+            .filter(|session| seat.is_none_or(|seat| session.seat == seat));
+        aggregate_idle(sessions)
+    }
+
+    /// Hold `inhibitor` until the other end of `fd` is closed
+    async fn inhibit(&self, inhibitor: Inhibitor, fd: OwnedFd) {
+        let id = {
+            let mut registry = self.inner.lock().await;
+            registry.next_inhibitor += 1;
+            let id = registry.next_inhibitor;
+            registry.inhibitors.insert(id, inhibitor);
+            id
+        };
+        self.emit_inhibitors_changed().await;
+        let logins = self.clone();
+        tokio::spawn(async move {
+            closed(fd).await;
+            let released = logins.inner.lock().await.inhibitors.remove(&id);
+            if let Some(inhibitor) = released {
+                log::debug!(
+                    "{} released its {} inhibitor",
+                    inhibitor.who,
+                    inhibitor.mode
+                );
+            }
+            logins.released.notify_one();
+            logins.emit_inhibitors_changed().await;
+        });
+    }
+
+    pub async fn inhibitors(&self) -> Vec<Inhibitor> {
+        self.inner
+            .lock()
+            .await
+            .inhibitors
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// Whether a `mode` ("block" or "delay") inhibitor locks `what`
+    async fn inhibited(&self, what: &str, mode: &str) -> bool {
+        self.inner
+            .lock()
+            .await
+            .inhibitors
+            .values()
+            .any(|inhibitor| inhibitor.mode == mode && inhibitor.what.iter().any(|w| w == what))
+    }
+
+    /// What `mode` inhibitors lock, colon-separated (BlockInhibited,
+    /// DelayInhibited)
+    async fn inhibited_whats(&self, mode: &str) -> String {
+        let mut whats = Vec::new();
+        for what in INHIBIT_WHATS {
+            if self.inhibited(what, mode).await {
+                whats.push(what);
+            }
+        }
+        whats.join(":")
+    }
+
+    /// Start preparing for sleep; false if a sleep is under way already
+    async fn begin_sleep(&self) -> bool {
+        let mut registry = self.inner.lock().await;
+        !std::mem::replace(&mut registry.preparing_for_sleep, true)
+    }
+
+    async fn end_sleep(&self) {
+        self.inner.lock().await.preparing_for_sleep = false;
+    }
+
+    async fn preparing_for_sleep(&self) -> bool {
+        self.inner.lock().await.preparing_for_sleep
+    }
+
+    /// Wait until no delay inhibitor locks sleep, for at most `timeout`
+    async fn wait_for_delay_inhibitors(&self, timeout: Duration) {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.inhibited("sleep", "delay").await {
+            let released = self.released.notified();
+            if tokio::time::timeout_at(deadline, released).await.is_err() {
+                log::warn!("Delay inhibitors still held, sleeping anyway");
+                return;
+            }
+        }
+    }
+
+    /// PrepareForSleep(`start`)
+    async fn emit_prepare_for_sleep(&self, start: bool) {
+        let Some(conn) = self.inner.lock().await.connection.clone() else {
+            return;
+        };
+        let Ok(ctx) = SignalEmitter::new(&conn, LOGIN1_PATH) else {
+            return;
+        };
+        if let Err(e) = LoginManagerInterface::prepare_for_sleep(&ctx, start).await {
+            log::warn!("Failed to emit PrepareForSleep: {}", e);
+        }
+    }
+
+    /// Tell watchers of the manager that BlockInhibited, DelayInhibited and
+    /// IdleHint may have moved
+    async fn emit_inhibitors_changed(&self) {
+        let Some(conn) = self.inner.lock().await.connection.clone() else {
+            return;
+        };
+        if let Ok(manager) = conn
+            .object_server()
+            .interface::<_, LoginManagerInterface>(LOGIN1_PATH)
+            .await
+        {
+            let emitter = manager.signal_emitter();
+            let manager = manager.get().await;
+            let _ = manager.block_inhibited_changed(emitter).await;
+            let _ = manager.delay_inhibited_changed(emitter).await;
+        }
+        self.emit_idle_changed().await;
+    }
+
+    /// Tell watchers of seat0 and the manager that their idle hint may
+    /// have moved
+    async fn emit_idle_changed(&self) {
+        let Some(conn) = self.inner.lock().await.connection.clone() else {
+            return;
+        };
+        let server = conn.object_server();
+        if let Ok(seat) = server
+            .interface::<_, SeatObject>(seat_object_path(SEAT0))
+            .await
+        {
+            let emitter = seat.signal_emitter();
+            let seat = seat.get().await;
+            let _ = seat.idle_hint_changed(emitter).await;
+            let _ = seat.idle_since_hint_changed(emitter).await;
+            let _ = seat.idle_since_hint_monotonic_changed(emitter).await;
+        }
+        if let Ok(manager) = server
+            .interface::<_, LoginManagerInterface>(LOGIN1_PATH)
+            .await
+        {
+            let emitter = manager.signal_emitter();
+            let manager = manager.get().await;
+            let _ = manager.idle_hint_changed(emitter).await;
+            let _ = manager.idle_since_hint_changed(emitter).await;
+            let _ = manager.idle_since_hint_monotonic_changed(emitter).await;
+        }
+    }
+}
+
+/// Wait until the other end of the pipe `fd` is closed
+async fn closed(fd: OwnedFd) {
+    if let Ok(mut pipe) = tokio::net::unix::pipe::Receiver::from_owned_fd(fd) {
+        let mut buf = [0u8; 64];
+        while pipe.read(&mut buf).await.is_ok_and(|n| n > 0) {}
+    }
+}
+
+async fn serve_session(conn: &Connection, logins: &Logins, id: &str) {
+    let object = SessionObject {
+        logins: logins.clone(),
+        id: id.to_string(),
+        authorizer: Authorizer::attached(conn),
+    };
+    if let Err(e) = conn
+        .object_server()
+        .at(session_object_path(id), object)
+        .await
+    {
+        log::warn!("Failed to serve session {}: {}", id, e);
+    }
+}
+
+/// org.freedesktop.login1.Session of one registered session
+struct SessionObject {
+    logins: Logins,
+    id: String,
+    authorizer: Authorizer,
+}
+
+impl SessionObject {
+    async fn session(&self) -> Session {
+        self.logins.get(&self.id).await.unwrap_or_default()
+    }
+}
+
+#[interface(name = "org.freedesktop.login1.Session")]
+impl SessionObject {
+    /// Report whether the user is idle in this (graphical) session; only
+    /// its user and root may
+    async fn set_idle_hint(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(signal_context)] ctx: SignalEmitter<'_>,
+        idle: bool,
+    ) -> fdo::Result<()> {
+        let session = self.session().await;
+        let caller = self.authorizer.caller_uid(&header).await?;
+        if caller != 0 && caller != session.uid {
+            return Err(fdo::Error::AccessDenied(
+                "Only the session's user may set its idle hint".to_string(),
+            ));
+        }
+        if !session.is_graphical() {
+            return Err(fdo::Error::NotSupported(
+                "Idle hint control is not supported on non-graphical sessions".to_string(),
+            ));
+        }
+        if !self.logins.set_idle(&self.id, idle).await {
+            return Err(no_such_session(&self.id));
+        }
+        let _ = self.idle_hint_changed(&ctx).await;
+        let _ = self.idle_since_hint_changed(&ctx).await;
+        let _ = self.idle_since_hint_monotonic_changed(&ctx).await;
+        self.logins.emit_idle_changed().await;
+        Ok(())
+    }
+
+    #[zbus(property)]
+    async fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    #[zbus(property)]
+    async fn name(&self) -> String {
+        self.session().await.user
+    }
+
+    #[zbus(property)]
+    async fn seat(&self) -> (String, OwnedObjectPath) {
+        let seat = self.session().await.seat;
+        let path = match seat.is_empty() {
+            true => OwnedObjectPath::try_from("/").unwrap(),
+            false => seat_object_path(&seat),
+        };
+        (seat, path)
+    }
+
+    #[zbus(property, name = "VTNr")]
+    async fn vtnr(&self) -> u32 {
+        self.session().await.vtnr
+    }
+
+    #[zbus(property, name = "TTY")]
+    async fn tty(&self) -> String {
+        self.session().await.tty
+    }
+
+    #[zbus(property)]
+    async fn display(&self) -> String {
+        self.session().await.display
+    }
+
+    #[zbus(property)]
+    async fn remote(&self) -> bool {
+        self.session().await.remote
+    }
+
+    #[zbus(property)]
+    async fn remote_user(&self) -> String {
+        self.session().await.remote_user
+    }
+
+    #[zbus(property)]
+    async fn remote_host(&self) -> String {
+        self.session().await.remote_host
+    }
+
+    #[zbus(property)]
+    async fn service(&self) -> String {
+        self.session().await.service
+    }
+
+    #[zbus(property)]
+    async fn desktop(&self) -> String {
+        self.session().await.desktop
+    }
+
+    #[zbus(property, name = "Type")]
+    async fn session_type(&self) -> String {
+        self.session().await.session_type
+    }
+
+    #[zbus(property)]
+    async fn class(&self) -> String {
+        self.session().await.class
+    }
+
+    #[zbus(property)]
+    async fn leader(&self) -> u32 {
+        self.session().await.leader
+    }
+
+    #[zbus(property)]
+    async fn scope(&self) -> String {
+        self.session().await.scope_name()
+    }
+
+    #[zbus(property)]
+    async fn state(&self) -> String {
+        self.session().await.state().to_string()
+    }
+
+    #[zbus(property)]
+    async fn active(&self) -> bool {
+        !self.session().await.closing
+    }
+
+    #[zbus(property)]
+    async fn idle_hint(&self) -> bool {
+        self.session().await.idle
+    }
+
+    #[zbus(property)]
+    async fn idle_since_hint(&self) -> u64 {
+        self.session().await.idle_since.0
+    }
+
+    #[zbus(property)]
+    async fn idle_since_hint_monotonic(&self) -> u64 {
+        self.session().await.idle_since.1
+    }
+}
+
+/// org.freedesktop.login1.Seat of seat0
+struct SeatObject {
+    logins: Logins,
+    id: String,
+}
+
+#[interface(name = "org.freedesktop.login1.Seat")]
+impl SeatObject {
+    #[zbus(property)]
+    async fn id(&self) -> String {
+        self.id.clone()
+    }
+
+    #[zbus(property)]
+    async fn sessions(&self) -> Vec<(String, OwnedObjectPath)> {
+        self.logins
+            .sessions()
+            .await
+            .into_iter()
+            .filter(|session| session.seat == self.id)
+            .map(|session| (session.id.clone(), session_object_path(&session.id)))
+            .collect()
+    }
+
+    #[zbus(property)]
+    async fn idle_hint(&self) -> bool {
+        self.logins.idle_hint(Some(&self.id)).await.0
+    }
+
+    #[zbus(property)]
+    async fn idle_since_hint(&self) -> u64 {
+        let (_, (realtime, _)) = self.logins.idle_hint(Some(&self.id)).await;
+        realtime
+    }
+
+    #[zbus(property)]
+    async fn idle_since_hint_monotonic(&self) -> u64 {
+        let (_, (_, monotonic)) = self.logins.idle_hint(Some(&self.id)).await;
+        monotonic
+    }
+}
+
+/// CreateSession reply: (session id, object path, runtime path, FIFO, uid,
+/// seat, VT, whether the session existed already)
+type CreatedSession = (
+    String,
+    OwnedObjectPath,
+    String,
+    zvariant::OwnedFd,
+    u32,
+    String,
+    u32,
+    bool,
+);
+
+pub struct LoginManagerInterface {
+    manager: Arc<RwLock<Manager>>,
+    logins: Logins,
+    authorizer: Authorizer,
+}
+
+impl LoginManagerInterface {
+    pub fn new(manager: Arc<RwLock<Manager>>) -> Self {
+        Self {
+            manager,
+            logins: Logins::default(),
+            authorizer: Authorizer::new(),
+        }
+    }
+
+    /// Check calls with `authorizer` rather than letting everyone through
+    pub fn with_authorizer(mut self, authorizer: Authorizer) -> Self {
+        self.authorizer = authorizer;
+        self
+    }
+
+    /// The registry, to serve the seat and Session objects once the bus is
+    /// connected
+    pub fn logins(&self) -> Logins {
+        self.logins.clone()
+    }
+
+    /// Fail unless the call comes from root or the manager's own user, who
+    /// run pam_systemd
+    async fn require_privileged(&self, header: &Header<'_>) -> fdo::Result<()> {
+        let caller = self.authorizer.caller_uid(header).await?;
+        if caller == 0 || caller == nix::unistd::geteuid().as_raw() {
+            return Ok(());
+        }
+        Err(fdo::Error::AccessDenied(
+            "Only root may register sessions".to_string(),
+        ))
+    }
+
+    /// Sleep in the background once delay inhibitors let go, announcing it
+    /// with PrepareForSleep; refused while a block inhibitor locks sleep
+    async fn request_sleep(&self, header: &Header<'_>, mode: SleepMode) -> fdo::Result<()> {
+        let action = match mode {
+            SleepMode::Suspend => SUSPEND,
+            SleepMode::Hibernate | SleepMode::HybridSleep => HIBERNATE,
+        };
+        self.authorizer.authorize(header, action).await?;
+        if self.logins.inhibited("sleep", "block").await {
+            return Err(fdo::Error::AccessDenied(
+                "Operation denied due to active block inhibitor".to_string(),
+            ));
+        }
+        if !mode.supported() {
+            return Err(fdo::Error::NotSupported(format!(
+                "Sleep verb \"{}\" not supported",
+                mode.as_str()
+            )));
+        }
+        if !self.logins.begin_sleep().await {
+            return Err(fdo::Error::Failed(
+                "There's already a sleep operation in progress".to_string(),
+            ));
+        }
+        log::info!("login1: {}", mode.as_str());
+        let manager = Arc::clone(&self.manager);
+        let logins = self.logins.clone();
+        tokio::spawn(async move {
+            logins.emit_prepare_for_sleep(true).await;
+            logins.wait_for_delay_inhibitors(INHIBIT_DELAY_MAX).await;
+            if let Err(e) = Manager::sleep_now(&manager, mode).await {
+                log::error!("Failed to {}: {}", mode.as_str(), e);
+            }
+            manager.read().await.publish_states();
+            logins.end_sleep().await;
+            logins.emit_prepare_for_sleep(false).await;
+        });
+        Ok(())
+    }
+
+    /// CanSuspend() and friends: "yes", or "na" without kernel support
+    fn can_sleep(mode: SleepMode) -> String {
+        match mode.supported() {
+            true => "yes".to_string(),
+            false => "na".to_string(),
+        }
+    }
+}
+
+#[interface(name = "org.freedesktop.login1.Manager")]
+impl LoginManagerInterface {
+    /// Register the session pam_systemd opens for `uid`, led by `pid`
+    #[allow(clippy::too_many_arguments)]
+    async fn create_session(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        uid: u32,
+        pid: u32,
+        service: &str,
+        session_type: &str,
+        class: &str,
+        desktop: &str,
+        seat_id: &str,
+        vtnr: u32,
+        tty: &str,
+        display: &str,
+        remote: bool,
+        remote_user: &str,
+        remote_host: &str,
+        _properties: Vec<(String, OwnedValue)>,
+    ) -> fdo::Result<CreatedSession> {
+        self.require_privileged(&header).await?;
+        validate_session(pid, seat_id, session_type, class)?;
+        let sessions = self.logins.sessions().await;
+        if sessions.iter().any(|session| session.leader == pid) {
+            return Err(fdo::Error::Failed(format!(
+                "PID {} already runs in a session",
+                pid
+            )));
+        }
+
+        let id = self.logins.new_id(pid).await;
+        let user = nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+            .ok()
+            .flatten()
+            .map(|user| user.name)
+            .unwrap_or_else(|| uid.to_string());
+        log::info!(
+            "CreateSession: {} of {} (service={} type={} tty={} leader={})",
+            id,
+            user,
+            service,
+            session_type,
+            tty,
+            pid
+        );
+        let session = Session {
+            id: id.clone(),
+            uid,
+            user,
+            leader: pid,
+            service: service.to_string(),
+            session_type: or_default(session_type, "unspecified"),
+            class: or_default(class, "user"),
+            desktop: desktop.to_string(),
+            seat: seat_id.to_string(),
+            vtnr: if seat_id.is_empty() { 0 } else { vtnr },
+            tty: tty.to_string(),
+            display: display.to_string(),
+            remote,
+            remote_user: remote_user.to_string(),
+            remote_host: remote_host.to_string(),
+            closing: false,
+            idle: false,
+            idle_since: now(),
+        };
+
+        let (fifo, pam_end) = nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC)
+            .map_err(|e| fdo::Error::Failed(format!("Cannot create session FIFO: {}", e)))?;
+        let slice = format!("user-{}.slice", uid);
+        let description = format!("Session {} of User {}", id, session.user);
+        let cgroup = {
+            let mut mgr = self.manager.write().await;
+            let cgroup = mgr
+                .register_scope(
+                    &session.scope_name(),
+                    Some(&slice),
+                    Some(&description),
+                    &[pid],
+                )
+                .await
+                .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+            mgr.publish_states();
+            // Without a cgroup hierarchy there is nothing to watch
+            mgr.cgroup_manager().map(|_| cgroup)
+        };
+        let reply = (
+            id.clone(),
+            session_object_path(&id),
+            format!("/run/user/{}", uid),
+            zvariant::OwnedFd::from(pam_end),
+            uid,
+            session.seat.clone(),
+            session.vtnr,
+            false,
+        );
+        self.logins.insert(session).await;
+        if let Some(cgroup) = cgroup {
+            let manager = Arc::clone(&self.manager);
+            self.logins.watch_scope(&id, cgroup, manager).await;
+        }
+        self.logins.watch_fifo(&id, fifo, Arc::clone(&self.manager));
+        self.logins.emit_idle_changed().await;
+        Ok(reply)
+    }
+
+    /// pam_systemd is done with session `id`; it is closing
+    async fn release_session(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+    ) -> fdo::Result<()> {
+        self.require_privileged(&header).await?;
+        if self.logins.get(id).await.is_none() {
+            return Err(no_such_session(id));
+        }
+        self.logins.close(id, &self.manager).await;
+        Ok(())
+    }
+
+    /// Kill all processes of session `id` and forget it
+    async fn terminate_session(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        id: &str,
+    ) -> fdo::Result<()> {
+        self.authorizer.authorize(&header, MANAGE_SESSIONS).await?;
+        log::info!("TerminateSession: {}", id);
+        let session = self
+            .logins
+            .remove(id)
+            .await
+            .ok_or_else(|| no_such_session(id))?;
+        let scope = session.scope_name();
+        let mut mgr = self.manager.write().await;
+        if let Err(e) = mgr.kill_unit(&scope, KillWhom::All, libc::SIGTERM) {
+            log::debug!("TerminateSession {}: {}", id, e);
+        }
+        let result = mgr.unregister_scope(&scope).await;
+        mgr.publish_states();
+        drop(mgr);
+        self.logins.emit_idle_changed().await;
+        result.map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Sessions as (id, uid, user, seat, object path)
+    async fn list_sessions(&self) -> Vec<(String, u32, String, String, OwnedObjectPath)> {
+        self.logins
+            .sessions()
+            .await
+            .into_iter()
+            .map(|session| {
+                let path = session_object_path(&session.id);
+                (session.id, session.uid, session.user, session.seat, path)
+            })
+            .collect()
+    }
+
+    /// Object path of session `id`
+    async fn get_session(&self, id: &str) -> fdo::Result<OwnedObjectPath> {
+        match self.logins.get(id).await {
+            Some(_) => Ok(session_object_path(id)),
+            None => Err(no_such_session(id)),
+        }
+    }
+
+    /// Object path of the session process `pid` belongs to
+    #[zbus(name = "GetSessionByPID")]
+    async fn get_session_by_pid(&self, pid: u32) -> fdo::Result<OwnedObjectPath> {
+        let sessions = self.logins.sessions().await;
+        let cgroup = std::fs::read_to_string(format!("/proc/{}/cgroup", pid)).unwrap_or_default();
+        sessions
+            .iter()
+            .find(|session| {
+                session.leader == pid || session_of_cgroup(&cgroup) == Some(session.id.as_str())
+            })
+            .map(|session| session_object_path(&session.id))
+            .ok_or_else(|| {
+                fdo::Error::InvalidArgs(format!("PID {} does not belong to any session", pid))
+            })
+    }
+
+    /// Seats as (id, object path); there is only seat0
+    async fn list_seats(&self) -> Vec<(String, OwnedObjectPath)> {
+        vec![(SEAT0.to_string(), seat_object_path(SEAT0))]
+    }
+
+    /// Object path of seat `id`
+    async fn get_seat(&self, id: &str) -> fdo::Result<OwnedObjectPath> {
+        match id {
+            SEAT0 => Ok(seat_object_path(SEAT0)),
+            _ => Err(no_such_seat(id)),
+        }
+    }
+
+    async fn suspend(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        _interactive: bool,
+    ) -> fdo::Result<()> {
+        self.request_sleep(&header, SleepMode::Suspend).await
+    }
+
+    async fn hibernate(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        _interactive: bool,
+    ) -> fdo::Result<()> {
+        self.request_sleep(&header, SleepMode::Hibernate).await
+    }
+
+    async fn hybrid_sleep(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        _interactive: bool,
+    ) -> fdo::Result<()> {
+        self.request_sleep(&header, SleepMode::HybridSleep).await
+    }
+
+    async fn can_suspend(&self) -> String {
+        Self::can_sleep(SleepMode::Suspend)
+    }
+
+    async fn can_hibernate(&self) -> String {
+        Self::can_sleep(SleepMode::Hibernate)
+    }
+
+    async fn can_hybrid_sleep(&self) -> String {
+        Self::can_sleep(SleepMode::HybridSleep)
+    }
+
+    /// Take an inhibitor lock on the colon-separated `what`, held until the
+    /// returned file descriptor is closed
+    async fn inhibit(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        what: &str,
+        who: &str,
+        why: &str,
+        mode: &str,
+    ) -> fdo::Result<zvariant::OwnedFd> {
+        let what = parse_inhibit(what, mode)?;
+        for action in what.iter().map(|what| inhibit_action(what, mode)) {
+            self.authorizer.authorize(&header, &action).await?;
+        }
+        let inhibitor = Inhibitor {
+            what,
+            who: who.to_string(),
+            why: why.to_string(),
+            mode: mode.to_string(),
+            uid: self.authorizer.caller_uid(&header).await?,
+            pid: self.authorizer.caller_pid(&header).await?,
+        };
+        log::debug!(
+            "{} takes a {} inhibitor on {}: {}",
+            who,
+            mode,
+            inhibitor.what.join(":"),
+            why
+        );
+        let (ours, theirs) = nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC)
+            .map_err(|e| fdo::Error::Failed(format!("Cannot create inhibitor pipe: {}", e)))?;
+        self.logins.inhibit(inhibitor, ours).await;
+        Ok(zvariant::OwnedFd::from(theirs))
+    }
+
+    /// Inhibitors as (what, who, why, mode, uid, pid)
+    async fn list_inhibitors(&self) -> Vec<(String, String, String, String, u32, u32)> {
+        self.logins
+            .inhibitors()
+            .await
+            .into_iter()
+            .map(|inhibitor| {
+                (
+                    inhibitor.what.join(":"),
+                    inhibitor.who,
+                    inhibitor.why,
+                    inhibitor.mode,
+                    inhibitor.uid,
+                    inhibitor.pid,
+                )
+            })
+            .collect()
+    }
+
+    /// Sent with true before the machine sleeps and false after resume
+    #[zbus(signal)]
+    async fn prepare_for_sleep(emitter: &SignalEmitter<'_>, start: bool) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    async fn block_inhibited(&self) -> String {
+        self.logins.inhibited_whats("block").await
+    }
+
+    #[zbus(property)]
+    async fn delay_inhibited(&self) -> String {
+        self.logins.inhibited_whats("delay").await
+    }
+
+    #[zbus(property, name = "InhibitDelayMaxUSec")]
+    async fn inhibit_delay_max_usec(&self) -> u64 {
+        INHIBIT_DELAY_MAX.as_micros() as u64
+    }
+
+    #[zbus(property)]
+    async fn preparing_for_sleep(&self) -> bool {
+        self.logins.preparing_for_sleep().await
+    }
+
+    /// Idle once all sessions are, unless a block inhibitor locks idle
+    #[zbus(property)]
+    async fn idle_hint(&self) -> bool {
+        self.logins.idle_hint(None).await.0 && !self.logins.inhibited("idle", "block").await
+    }
+
+    #[zbus(property)]
+    async fn idle_since_hint(&self) -> u64 {
+        let (_, (realtime, _)) = self.logins.idle_hint(None).await;
+        realtime
+    }
+
+    #[zbus(property)]
+    async fn idle_since_hint_monotonic(&self) -> u64 {
+        let (_, (_, monotonic)) = self.logins.idle_hint(None).await;
+        monotonic
+    }
+}
+
+fn or_default(value: &str, default: &str) -> String {
+    match value.is_empty() {
+        true => default.to_string(),
+        false => value.to_string(),
+    }
+}
+
+fn no_such_session(id: &str) -> fdo::Error {
+    fdo::Error::InvalidArgs(format!("No session '{}' known", id))
+}
+
+fn no_such_seat(id: &str) -> fdo::Error {
+    fdo::Error::InvalidArgs(format!("No seat '{}' known", id))
+}
+
+/// The values logind accepts for a new session
+fn validate_session(pid: u32, seat: &str, session_type: &str, class: &str) -> fdo::Result<()> {
+    if pid <= 1 {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Invalid leader PID: {}",
+            pid
+        )));
+    }
+    if !seat.is_empty() && seat != SEAT0 {
+        return Err(no_such_seat(seat));
+    }
+    if !matches!(
+        session_type,
+        "" | "unspecified" | "tty" | "x11" | "wayland" | "mir" | "web"
+    ) {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Invalid session type: {}",
+            session_type
+        )));
+    }
+    if !matches!(
+        class,
+        "" | "user" | "greeter" | "lock-screen" | "background"
+    ) {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Invalid session class: {}",
+            class
+        )));
+    }
+    Ok(())
+}
+
+/// The locks of an Inhibit() call, checked like logind does
+fn parse_inhibit(what: &str, mode: &str) -> fdo::Result<Vec<String>> {
+    let whats: Vec<String> = what.split(':').map(str::to_string).collect();
+    if let Some(unknown) = whats.iter().find(|w| !INHIBIT_WHATS.contains(&w.as_str())) {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Invalid what specification {}",
+            unknown
+        )));
+    }
+    match mode {
+        "block" => Ok(whats),
+        "delay" if whats.iter().all(|w| w == "sleep" || w == "shutdown") => Ok(whats),
+        "delay" => Err(fdo::Error::InvalidArgs(
+            "Delay inhibitors only supported for shutdown and sleep".to_string(),
+        )),
+        _ => Err(fdo::Error::InvalidArgs(format!(
+            "Invalid mode specification {}",
+            mode
+        ))),
+    }
+}
+
+/// polkit action guarding a `mode` inhibitor on `what`
+fn inhibit_action(what: &str, mode: &str) -> String {
+    match what {
+        "shutdown" | "sleep" => format!("org.freedesktop.login1.inhibit-{}-{}", mode, what),
+        "idle" => "org.freedesktop.login1.inhibit-block-idle".to_string(),
+        _ => format!("org.freedesktop.login1.inhibit-{}", what),
+    }
+}
+
+/// Id of the session scope in a /proc/<pid>/cgroup listing, if any
+fn session_of_cgroup(cgroup: &str) -> Option<&str> {
+    cgroup
+        .lines()
+        .find_map(|line| line.strip_prefix("0::"))?
+        .split('/')
+        .find_map(|part| part.strip_prefix("session-")?.strip_suffix(".scope"))
+}
+
+/// sd_bus_path_encode: characters other than ASCII letters and digits, and
+/// a leading digit, become _xx
+fn login_object_path(kind: &str, id: &str) -> OwnedObjectPath {
+    let mut escaped = String::new();
+    for (i, c) in id.chars().enumerate() {
+        if c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()) {
+            escaped.push(c);
+        } else {
+            escaped.push_str(&format!("_{:02x}", c as u32));
+        }
+    }
+    if escaped.is_empty() {
+        escaped.push('_');
+    }
+    zvariant::ObjectPath::try_from(format!("{}/{}/{}", LOGIN1_PATH, kind, escaped))
+        .unwrap()
+        .into()
+}
+
+/// e.g. "3" -> "/org/freedesktop/login1/session/_33"
+fn session_object_path(id: &str) -> OwnedObjectPath {
+    login_object_path("session", id)
+}
+
+fn seat_object_path(id: &str) -> OwnedObjectPath {
+    login_object_path("seat", id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbus::test_call;
+
+    fn session(id: &str, idle: bool, since: u64) -> Session {
+        Session {
+            id: id.to_string(),
+            seat: SEAT0.to_string(),
+            session_type: "wayland".to_string(),
+            idle,
+            idle_since: (since, since),
+            ..Session::default()
+        }
+    }
+
+    #[test]
+    fn idle_once_every_session_is() {
+        assert_eq!(aggregate_idle([].iter()), (true, (0, 0)));
+
+        let both_idle = [session("1", true, 10), session("2", true, 30)];
+        assert_eq!(aggregate_idle(both_idle.iter()), (true, (30, 30)));
+
+        let one_busy = [
+            session("1", true, 10),
+            session("2", false, 20),
+            session("3", false, 5),
+        ];
+        assert_eq!(aggregate_idle(one_busy.iter()), (false, (20, 20)));
+    }
+
+    #[test]
+    fn object_paths_cgroups_and_sessions_are_checked() {
+        assert_eq!(
+            session_object_path("3").as_str(),
+            "/org/freedesktop/login1/session/_33"
+        );
+        assert_eq!(
+            session_object_path("c1").as_str(),
+            "/org/freedesktop/login1/session/c1"
+        );
+        assert_eq!(
+            seat_object_path(SEAT0).as_str(),
+            "/org/freedesktop/login1/seat/seat0"
+        );
+        let cgroup = "0::/user.slice/user-1000.slice/session-c2.scope\n";
+        assert_eq!(session_of_cgroup(cgroup), Some("c2"));
+        assert_eq!(session_of_cgroup("0::/system.slice/sshd.service\n"), None);
+
+        assert!(validate_session(1234, "", "tty", "").is_ok());
+        assert!(validate_session(1234, "seat1", "x11", "user").is_err());
+        assert!(validate_session(1, "seat0", "x11", "user").is_err());
+        assert!(validate_session(1234, "seat0", "vnc", "user").is_err());
+    }
+
+    #[tokio::test]
+    async fn sessions_register_report_idleness_and_close() {
+        let manager = Arc::new(RwLock::new(Manager::new_user()));
+        let iface = LoginManagerInterface::new(Arc::clone(&manager));
+        let call = test_call("CreateSession");
+        let uid = nix::unistd::geteuid().as_raw();
+
+        let created = iface
+            .create_session(
+                call.header(),
+                uid,
+                999_997,
+                "login",
+                "wayland",
+                "user",
+                "",
+                SEAT0,
+                1,
+                "",
+                "",
+                false,
+                "",
+                "",
+                Vec::new(),
+            )
+            .await
+            .unwrap();
+        let id = created.0.clone();
+        assert_eq!(created.5, SEAT0);
+        assert!(manager
+            .read()
+            .await
+            .scope_manager()
+            .exists(&format!("session-{}.scope", id)));
+        assert_eq!(iface.list_sessions().await.len(), 1);
+        assert!(!iface.idle_hint().await);
+
+        let logins = iface.logins();
+        let object = SessionObject {
+            logins: logins.clone(),
+            id: id.clone(),
+            authorizer: Authorizer::new(),
+        };
+        assert!(logins.set_idle(&id, true).await);
+        assert!(object.idle_hint().await);
+        assert!(iface.idle_hint().await);
+        assert!(iface.idle_since_hint().await > 0);
+
+        // Closing the FIFO is logging out; without cgroups the session goes
+        drop(created.3);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !iface.list_sessions().await.is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(iface.list_sessions().await.is_empty());
+        assert!(iface.get_session(&id).await.is_err());
+    }
+
+    #[test]
+    fn inhibitors_are_checked_like_logind() {
+        assert_eq!(
+            parse_inhibit("sleep:idle", "block").unwrap(),
+            vec!["sleep", "idle"]
+        );
+        assert!(parse_inhibit("sleep:shutdown", "delay").is_ok());
+        assert!(parse_inhibit("idle", "delay").is_err());
+        assert!(parse_inhibit("coffee", "block").is_err());
+        assert!(parse_inhibit("sleep", "maybe").is_err());
+        assert_eq!(
+            inhibit_action("sleep", "delay"),
+            "org.freedesktop.login1.inhibit-delay-sleep"
+        );
+        assert_eq!(
+            inhibit_action("handle-lid-switch", "block"),
+            "org.freedesktop.login1.inhibit-handle-lid-switch"
+        );
+    }
+
+    #[tokio::test]
+    async fn block_inhibitors_refuse_sleep_until_released() {
+        let manager = Arc::new(RwLock::new(Manager::new_user()));
+        let iface = LoginManagerInterface::new(manager);
+        let call = test_call("Inhibit");
+
+        let fd = iface
+            .inhibit(
+                call.header(),
+                "sleep:idle",
+                "test",
+                "Burning a disc",
+                "block",
+            )
+            .await
+            .unwrap();
+        let inhibitors = iface.list_inhibitors().await;
+        assert_eq!(inhibitors.len(), 1);
+        assert_eq!(inhibitors[0].0, "sleep:idle");
+        assert_eq!(inhibitors[0].3, "block");
+        assert_eq!(iface.block_inhibited().await, "sleep:idle");
+        assert_eq!(iface.delay_inhibited().await, "");
+        // No sessions are idle, but the idle lock would win anyway
+        assert!(!iface.idle_hint().await);
+
+        let refused = iface
+            .request_sleep(test_call("Suspend").header(), SleepMode::Suspend)
+            .await;
+        assert!(matches!(refused, Err(fdo::Error::AccessDenied(_))));
+        assert!(!iface.preparing_for_sleep().await);
+
+        drop(fd);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !iface.list_inhibitors().await.is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(iface.list_inhibitors().await.is_empty());
+        assert_eq!(iface.block_inhibited().await, "");
+        assert!(iface.idle_hint().await);
+    }
+
+    #[tokio::test]
+    async fn sleep_waits_for_delay_inhibitors_at_most_the_delay() {
+        let logins = Logins::default();
+        let (ours, theirs) = nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC).unwrap();
+        let inhibitor = Inhibitor {
+            what: vec!["sleep".to_string()],
+            who: "test".to_string(),
+            why: "Saving state".to_string(),
+            mode: "delay".to_string(),
+            uid: 0,
+            pid: 1,
+        };
+        logins.inhibit(inhibitor, ours).await;
+        assert!(logins.inhibited("sleep", "delay").await);
+
+        let started = Instant::now();
+        logins
+            .wait_for_delay_inhibitors(Duration::from_millis(100))
+            .await;
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        let waiting = tokio::spawn({
+            let logins = logins.clone();
+            async move {
+                logins
+                    .wait_for_delay_inhibitors(Duration::from_secs(30))
+                    .await
+            }
+        });
+        drop(theirs);
+        tokio::time::timeout(Duration::from_secs(5), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!logins.inhibited("sleep", "delay").await);
+    }
+}
@@ -0,0 +1,576 @@
+//! org.freedesktop.machine1.Manager subset (machined-lite)
+//!
+//! Container and VM managers register their machines here; each machine gets
+//! a `machine-<name>.scope` under machine.slice containing its leader process
+//! and an org.freedesktop.machine1.Machine object at the path RegisterMachine
+//! returns. A machine whose scope cgroup empties (the container exited
+//! without TerminateMachine) is forgotten and its scope dropped.
+//!
+//! Key methods:
+//! - RegisterMachine / TerminateMachine
+//! - ListMachines
+//! - GetMachineAddresses: read from /proc/<leader>/net, which shows the
+//!   leader's network namespace
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::AbortHandle;
+use zbus::{fdo, interface, message::Header, zvariant::OwnedObjectPath, Connection};
+
+use super::polkit::{Authorizer, CREATE_MACHINE, MANAGE_MACHINES};
+use crate::cgroups::EmptyWatcher;
+use crate::manager::{KillWhom, Manager};
+
+const MACHINE_SLICE: &str = "machine.slice";
+const AF_INET: i32 = 2;
+const AF_INET6: i32 = 10;
+/// How long one wait for a scope to empty lasts before it is renewed
+const EMPTY_WAIT: Duration = Duration::from_secs(3600);
+
+/// A registered machine
+#[derive(Debug, Clone, PartialEq)]
+pub struct Machine {
+    pub name: String,
+    pub class: String,
+    pub service: String,
+    pub leader: u32,
+    pub root_directory: String,
+}
+
+impl Machine {
+    fn scope_name(&self) -> String {
+        format!("machine-{}.scope", self.name)
+    }
+}
+
+/// Registered machines, shared by the interface, their scope watchers and
+/// the bus connection their objects are served on
+#[derive(Clone, Default)]
+pub struct Machines {
+    inner: Arc<Mutex<Registry>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    machines: BTreeMap<String, Machine>,
+    /// Tasks waiting for a machine's scope cgroup to empty
+    watchers: HashMap<String, AbortHandle>,
+    connection: Option<Connection>,
+}
+
+impl Machines {
+    /// Serve the Machine objects on `conn`, now and as machines register
+    pub fn serve(&self, conn: Connection) {
+        let machines = self.clone();
+        tokio::spawn(async move {
+            let mut registry = machines.inner.lock().await;
+            for machine in registry.machines.values() {
+                serve_machine(&conn, machine).await;
+            }
+            registry.connection = Some(conn);
+        });
+    }
+
+    async fn insert(&self, machine: Machine) {
+        let mut registry = self.inner.lock().await;
+        if let Some(conn) = &registry.connection {
+            serve_machine(conn, &machine).await;
+        }
+        registry.machines.insert(machine.name.clone(), machine);
+    }
+
+    /// Forget machine `name`, its object and its scope watcher
+    async fn remove(&self, name: &str) -> Option<Machine> {
+        let mut registry = self.inner.lock().await;
+        let machine = registry.machines.remove(name)?;
+        if let Some(watcher) = registry.watchers.remove(name) {
+            watcher.abort();
+        }
+        if let Some(conn) = &registry.connection {
+            let path = machine_object_path(name);
+            let _ = conn.object_server().remove::<MachineObject, _>(path).await;
+        }
+        Some(machine)
+    }
+
+    /// Forget machine `name` and drop its scope once `cgroup` has no
+    /// processes left
+    async fn watch_scope(&self, name: &str, cgroup: PathBuf, manager: Arc<RwLock<Manager>>) {
+        let machines = self.clone();
+        let name = name.to_string();
+        // Held until the watcher is recorded, so it cannot finish before that
+        let mut registry = self.inner.lock().await;
+        let watcher = tokio::spawn({
+            let name = name.clone();
+            async move {
+                let empty = EmptyWatcher::spawn(vec![cgroup.clone()]);
+                while !empty.wait(&cgroup, Instant::now() + EMPTY_WAIT).await {}
+                // Dropping the handle does not abort this task
+                machines.inner.lock().await.watchers.remove(&name);
+                let Some(machine) = machines.remove(&name).await else {
+                    return;
+                };
+                log::info!("Machine {} has no processes left", name);
+                let mut mgr = manager.write().await;
+                if let Err(e) = mgr.unregister_scope(&machine.scope_name()).await {
+                    log::debug!("Dropping the scope of machine {}: {}", name, e);
+                }
+                mgr.publish_states();
+            }
+        });
+        registry.watchers.insert(name, watcher.abort_handle());
+    }
+}
+
+async fn serve_machine(conn: &Connection, machine: &Machine) {
+    let object = MachineObject {
+        machine: machine.clone(),
+    };
+    let path = machine_object_path(&machine.name);
+    if let Err(e) = conn.object_server().at(path, object).await {
+        log::warn!("Failed to serve machine {}: {}", machine.name, e);
+    }
+}
+
+/// org.freedesktop.machine1.Machine of one registered machine
+struct MachineObject {
+    machine: Machine,
+}
+
+#[interface(name = "org.freedesktop.machine1.Machine")]
+impl MachineObject {
+    #[zbus(property)]
+    fn name(&self) -> String {
+        self.machine.name.clone()
+    }
+
+    #[zbus(property)]
+    fn class(&self) -> String {
+        self.machine.class.clone()
+    }
+
+    #[zbus(property)]
+    fn service(&self) -> String {
+        self.machine.service.clone()
+    }
+
+    #[zbus(property)]
+    fn leader(&self) -> u32 {
+        self.machine.leader
+    }
+
+    #[zbus(property)]
+    fn root_directory(&self) -> String {
+        self.machine.root_directory.clone()
+    }
+
+    #[zbus(property)]
+    fn unit(&self) -> String {
+        self.machine.scope_name()
+    }
+
+    #[zbus(property)]
+    fn state(&self) -> String {
+        "running".to_string()
+    }
+}
+
+pub struct MachineManagerInterface {
+    manager: Arc<RwLock<Manager>>,
+    machines: Machines,
+    authorizer: Authorizer,
+}
+
+impl MachineManagerInterface {
+    pub fn new(manager: Arc<RwLock<Manager>>) -> Self {
+        Self {
+            manager,
+            machines: Machines::default(),
+            authorizer: Authorizer::new(),
+        }
+    }
+
+    /// Check calls with `authorizer` rather than letting everyone through
+    pub fn with_authorizer(mut self, authorizer: Authorizer) -> Self {
+        self.authorizer = authorizer;
+        self
+    }
+
+    /// The registry, to serve the Machine objects once the bus is connected
+    pub fn machines(&self) -> Machines {
+        self.machines.clone()
+    }
+}
+
+#[interface(name = "org.freedesktop.machine1.Manager")]
+impl MachineManagerInterface {
+    /// Register a machine and move its leader into machine-<name>.scope
+    async fn register_machine(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        name: &str,
+        _id: Vec<u8>,
+        service: &str,
+        class: &str,
+        leader: u32,
+        root_directory: &str,
+    ) -> fdo::Result<OwnedObjectPath> {
+        self.authorizer.authorize(&header, CREATE_MACHINE).await?;
+        log::info!(
+            "RegisterMachine: name={} class={} service={} leader={}",
+            name,
+            class,
+            service,
+            leader
+        );
+        validate_machine(name, class, leader)?;
+        if self.machines.inner.lock().await.machines.contains_key(name) {
+            return Err(fdo::Error::FileExists(format!(
+                "Machine '{}' already exists",
+                name
+            )));
+        }
+
+        let machine = Machine {
+            name: name.to_string(),
+            class: class.to_string(),
+            service: service.to_string(),
+            leader,
+            root_directory: root_directory.to_string(),
+        };
+        let description = format!("Virtual Machine and Container: {}", name);
+        let cgroup = {
+            let mut mgr = self.manager.write().await;
+            let cgroup = mgr
+                .register_scope(
+                    &machine.scope_name(),
+                    Some(MACHINE_SLICE),
+                    Some(&description),
+                    &[leader],
+                )
+                .await
+                .map_err(|e| fdo::Error::Failed(e.to_string()))?;
+            mgr.publish_states();
+            // Without a cgroup hierarchy there is nothing to watch
+            mgr.cgroup_manager().map(|_| cgroup)
+        };
+        self.machines.insert(machine).await;
+        if let Some(cgroup) = cgroup {
+            let manager = Arc::clone(&self.manager);
+            self.machines.watch_scope(name, cgroup, manager).await;
+        }
+        Ok(machine_object_path(name))
+    }
+
+    /// Kill all processes of a machine and forget it
+    async fn terminate_machine(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        name: &str,
+    ) -> fdo::Result<()> {
+        self.authorizer.authorize(&header, MANAGE_MACHINES).await?;
+        log::info!("TerminateMachine: {}", name);
+        let machine = self
+            .machines
+            .remove(name)
+            .await
+            .ok_or_else(|| no_such_machine(name))?;
+        let scope = machine.scope_name();
+        let mut mgr = self.manager.write().await;
+        if let Err(e) = mgr.kill_unit(&scope, KillWhom::All, libc::SIGTERM) {
+            log::debug!("TerminateMachine {}: {}", name, e);
+        }
+        let result = mgr.unregister_scope(&scope).await;
+        mgr.publish_states();
+        result.map_err(|e| fdo::Error::Failed(e.to_string()))
+    }
+
+    /// Registered machines as (name, class, service, object path)
+    async fn list_machines(&self) -> Vec<(String, String, String, OwnedObjectPath)> {
+        self.machines
+            .inner
+            .lock()
+            .await
+            .machines
+            .values()
+            .map(|machine| {
+                (
+                    machine.name.clone(),
+                    machine.class.clone(),
+                    machine.service.clone(),
+                    machine_object_path(&machine.name),
+                )
+            })
+            .collect()
+    }
+
+    /// Object path of a registered machine
+    async fn get_machine(&self, name: &str) -> fdo::Result<OwnedObjectPath> {
+        match self.machines.inner.lock().await.machines.contains_key(name) {
+            true => Ok(machine_object_path(name)),
+            false => Err(no_such_machine(name)),
+        }
+    }
+
+    /// Addresses of a machine as (address family, raw address)
+    async fn get_machine_addresses(&self, name: &str) -> fdo::Result<Vec<(i32, Vec<u8>)>> {
+        let leader = self
+            .machines
+            .inner
+            .lock()
+            .await
+            .machines
+            .get(name)
+            .map(|machine| machine.leader)
+            .ok_or_else(|| no_such_machine(name))?;
+        let net = std::path::PathBuf::from(format!("/proc/{}/net", leader));
+        let ipv4 = std::fs::read_to_string(net.join("fib_trie")).unwrap_or_default();
+        let ipv6 = std::fs::read_to_string(net.join("if_inet6")).unwrap_or_default();
+        let mut addresses: Vec<(i32, Vec<u8>)> = parse_fib_trie_local(&ipv4)
+            .into_iter()
+            .map(|address| (AF_INET, address.to_vec()))
+            .collect();
+        addresses.extend(
+            parse_if_inet6(&ipv6)
+                .into_iter()
+                .map(|address| (AF_INET6, address.to_vec())),
+        );
+        Ok(addresses)
+    }
+}
+
+fn no_such_machine(name: &str) -> fdo::Error {
+    fdo::Error::InvalidArgs(format!("No machine '{}' known", name))
+}
+
+/// Machine names follow hostname rules; only containers and VMs register
+fn validate_machine(name: &str, class: &str, leader: u32) -> fdo::Result<()> {
+    let valid_name = !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid_name {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Invalid machine name: {}",
+            name
+        )));
+    }
+    if !matches!(class, "container" | "vm" | "") {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Invalid machine class: {}",
+            class
+        )));
+    }
+    if leader <= 1 {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "Invalid leader PID: {}",
+            leader
+        )));
+    }
+    Ok(())
+}
+
+/// e.g. "web-1" -> "/org/freedesktop/machine1/machine/web_2d1"
+fn machine_object_path(name: &str) -> OwnedObjectPath {
+    let escaped: String = name
+        .chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c.to_string(),
+            false => format!("_{:02x}", c as u32),
+        })
+        .collect();
+    zbus::zvariant::ObjectPath::try_from(format!("/org/freedesktop/machine1/machine/{}", escaped))
+        .unwrap()
+        .into()
+}
+
+/// Local, non-loopback IPv4 addresses from /proc/net/fib_trie
+fn parse_fib_trie_local(content: &str) -> Vec<[u8; 4]> {
+    let mut addresses = Vec::new();
+    let mut last_leaf: Option<std::net::Ipv4Addr> = None;
+    for line in content.lines() {
+        let line = line.trim();
+        if let Some(leaf) = line.strip_prefix("|-- ") {
+            last_leaf = leaf.parse().ok();
+        } else if line == "/32 host LOCAL" {
+            if let Some(address) = last_leaf.filter(|address| !address.is_loopback()) {
+                if !addresses.contains(&address.octets()) {
+                    addresses.push(address.octets());
+                }
+            }
+        }
+    }
+    addresses
+}
+
+/// Non-loopback IPv6 addresses from /proc/net/if_inet6
+fn parse_if_inet6(content: &str) -> Vec<[u8; 16]> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (hex, interface) = (fields.first()?, fields.last()?);
+            if *interface == "lo" || hex.len() != 32 {
+                return None;
+            }
+            let mut address = [0u8; 16];
+            for (i, byte) in address.iter_mut().enumerate() {
+                *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+            }
+            Some(address)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbus::test_call;
+
+    const FIB_TRIE: &str = "\
+Main:
+  +-- 0.0.0.0/0 3 0 5
+     |-- 10.0.0.0
+        /24 link UNICAST
+     |-- 10.0.0.5
+        /32 host LOCAL
+     |-- 127.0.0.1
+        /32 host LOCAL
+Local:
+  +-- 0.0.0.0/0 3 0 5
+     |-- 10.0.0.5
+        /32 host LOCAL
+";
+
+    #[test]
+    fn fib_trie_yields_local_non_loopback_addresses_once() {
+        assert_eq!(parse_fib_trie_local(FIB_TRIE), [[10, 0, 0, 5]]);
+    }
+
+    #[test]
+    fn if_inet6_skips_loopback_and_malformed_lines() {
+        let content = "\
+00000000000000000000000000000001 01 80 10 80       lo
+fe800000000000000000000000000001 02 40 20 80     eth0
+garbage
+";
+        let addresses = parse_if_inet6(content);
+        assert_eq!(addresses.len(), 1);
+        assert_eq!(addresses[0][0..2], [0xfe, 0x80]);
+        assert_eq!(addresses[0][15], 1);
+    }
+
+    #[test]
+    fn machine_names_classes_and_leaders_are_validated() {
+        assert!(validate_machine("web-1", "container", 1234).is_ok());
+        assert!(validate_machine("", "container", 1234).is_err());
+        assert!(validate_machine("a/b", "container", 1234).is_err());
+        assert!(validate_machine(".hidden", "vm", 1234).is_err());
+        assert!(validate_machine("web", "pod", 1234).is_err());
+        assert!(validate_machine("web", "vm", 1).is_err());
+        assert_eq!(
+            machine_object_path("web-1").as_str(),
+            "/org/freedesktop/machine1/machine/web_2d1"
+        );
+    }
+
+    #[tokio::test]
+    async fn register_list_and_terminate_machine() {
+        let manager = Arc::new(RwLock::new(Manager::new_user()));
+        let iface = MachineManagerInterface::new(Arc::clone(&manager));
+        let call = test_call("RegisterMachine");
+
+        let path = iface
+            .register_machine(
+                call.header(),
+                "web",
+                Vec::new(),
+                "test",
+                "container",
+                999_999,
+                "/",
+            )
+            .await
+            .unwrap();
+        assert_eq!(path.as_str(), "/org/freedesktop/machine1/machine/web");
+        assert!(manager
+            .read()
+            .await
+            .scope_manager()
+            .exists("machine-web.scope"));
+        assert!(iface
+            .register_machine(
+                call.header(),
+                "web",
+                Vec::new(),
+                "test",
+                "container",
+                999_999,
+                "/",
+            )
+            .await
+            .is_err());
+
+        let machines = iface.list_machines().await;
+        assert_eq!(machines.len(), 1);
+        assert_eq!(machines[0].0, "web");
+        assert_eq!(machines[0].1, "container");
+
+        iface.terminate_machine(call.header(), "web").await.unwrap();
+        assert!(iface.list_machines().await.is_empty());
+        assert!(!manager
+            .read()
+            .await
+            .scope_manager()
+            .exists("machine-web.scope"));
+        assert!(iface.terminate_machine(call.header(), "web").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn a_machine_is_forgotten_once_its_scope_empties() {
+        let manager = Arc::new(RwLock::new(Manager::new_user()));
+        let iface = MachineManagerInterface::new(Arc::clone(&manager));
+        let call = test_call("RegisterMachine");
+        iface
+            .register_machine(
+                call.header(),
+                "db",
+                Vec::new(),
+                "test",
+                "container",
+                999_998,
+                "/",
+            )
+            .await
+            .unwrap();
+        let cgroup = std::env::temp_dir().join(format!("sysd-machine-{}", std::process::id()));
+        std::fs::create_dir_all(&cgroup).unwrap();
+        std::fs::write(cgroup.join("cgroup.events"), "populated 1\n").unwrap();
+
+        let machines = iface.machines();
+        machines
+            .watch_scope("db", cgroup.clone(), Arc::clone(&manager))
+            .await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(iface.list_machines().await.len(), 1);
+
+        std::fs::write(cgroup.join("cgroup.events"), "populated 0\n").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !iface.list_machines().await.is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert!(iface.list_machines().await.is_empty());
+        assert!(iface.get_machine("db").await.is_err());
+        assert!(!manager
+            .read()
+            .await
+            .scope_manager()
+            .exists("machine-db.scope"));
+        let _ = std::fs::remove_dir_all(&cgroup);
+    }
+}
@@ -6,24 +6,47 @@
 // - Kill processes in units (KillUnit)
 // - Subscribe to signals (Subscribe)
 
-use std::os::fd::AsRawFd;
-use std::os::unix::fs::PermissionsExt;
+use std::os::fd::{AsFd, AsRawFd};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
 use std::os::unix::process::CommandExt;
 use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 use tokio::runtime::Handle;
 use tokio::sync::RwLock;
+use tracing::Instrument;
 use zbus::{
     fdo, interface,
+    message::Header,
     object_server::SignalEmitter,
     zvariant::{ObjectPath, OwnedObjectPath, OwnedValue, Value},
 };
 
-use super::unit_object_path;
-use crate::manager::Manager;
+use super::polkit::{self, Authorizer};
+use super::unit::UnitObjects;
+use super::{unit_object_path, BusError};
+use crate::manager::{
+    CleanWhat, KillWhom, Manager, ManagerError, StateView, UnitProperty, UnitSnapshot,
+};
+use crate::pid1::ShutdownType;
 
 /// Job counter for generating unique job IDs
 static JOB_COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(1);
 
+/// ListUnits entry: (name, description, load state, active state, sub state,
+/// following, unit path, job id, job type, job path)
+type UnitListing = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    OwnedObjectPath,
+    u32,
+    String,
+    OwnedObjectPath,
+);
+
 fn next_job_id() -> u32 {
     JOB_COUNTER.fetch_add(1, std::sync::atomic::Ordering::SeqCst)
 }
@@ -34,19 +57,49 @@ fn job_path(id: u32) -> OwnedObjectPath {
         .into()
 }
 
+/// Span of the job a method call queued, for the messages logged while it
+/// runs
+fn job_span(method: &str, job_id: u32) -> tracing::Span {
+    tracing::info_span!("dbus", method, job = job_id)
+}
+
 pub struct ManagerInterface {
     manager: Arc<RwLock<Manager>>,
+    /// Published unit states, read without taking the manager lock
+    states: StateView,
+    /// Unit objects GetUnit and LoadUnit hand out paths of
+    unit_objects: UnitObjects,
     handle: Handle,
+    authorizer: Authorizer,
 }
 
 impl ManagerInterface {
-    pub fn new(manager: Arc<RwLock<Manager>>) -> Self {
+    pub fn new(manager: Arc<RwLock<Manager>>, states: StateView) -> Self {
+        let unit_objects = UnitObjects::new(Arc::clone(&manager), states.clone());
         Self {
             manager,
+            states,
+            unit_objects,
             handle: Handle::current(),
+            authorizer: Authorizer::new(),
         }
     }
 
+    /// The unit objects, to serve once the bus is connected
+    pub fn unit_objects(&self) -> UnitObjects {
+        self.unit_objects.clone()
+    }
+
+    /// Check calls with `authorizer` rather than letting everyone through
+    pub fn with_authorizer(mut self, authorizer: Authorizer) -> Self {
+        self.authorizer = authorizer;
+        self
+    }
+
+    async fn authorize(&self, header: &Header<'_>, action: &str) -> fdo::Result<()> {
+        self.authorizer.authorize(header, action).await
+    }
+
     /// Emit JobRemoved signal
     pub async fn emit_job_removed(
         ctx: &zbus::object_server::SignalEmitter<'_>,
@@ -58,6 +111,31 @@ impl ManagerInterface {
         Self::job_removed(ctx, job_id, job.as_ref(), unit, result).await
     }
 
+    /// ListUnits entries of the loaded units `keep` accepts
+    fn unit_listings(&self, keep: impl Fn(&str, &UnitSnapshot) -> bool) -> Vec<UnitListing> {
+        let no_job: OwnedObjectPath = ObjectPath::try_from("/").unwrap().into();
+        self.states
+            .list()
+            .into_iter()
+            .filter(|(name, unit)| keep(name, unit))
+            .map(|(name, unit)| {
+                let path = ObjectPath::try_from(unit_object_path(&name)).unwrap();
+                (
+                    name,
+                    unit.description.unwrap_or_default(),
+                    unit.load_state.to_string(),
+                    unit.active.as_str().to_string(),
+                    unit.sub.as_str().to_string(),
+                    String::new(),
+                    path.into(),
+                    0,
+                    String::new(),
+                    no_job.clone(),
+                )
+            })
+            .collect()
+    }
+
     /// Emit UnitRemoved signal
     pub async fn emit_unit_removed(
         ctx: &zbus::object_server::SignalEmitter<'_>,
@@ -75,10 +153,12 @@ impl ManagerInterface {
     async fn start_unit(
         &self,
         #[zbus(signal_context)] ctx: zbus::object_server::SignalEmitter<'_>,
+        #[zbus(header)] header: Header<'_>,
         name: &str,
         mode: &str,
     ) -> fdo::Result<OwnedObjectPath> {
-        log::info!("D-Bus StartUnit: {} mode={}", name, mode);
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        tracing::info!("D-Bus StartUnit: {} mode={}", name, mode);
 
         let job_id = next_job_id();
         let job = job_path(job_id);
@@ -86,43 +166,205 @@ impl ManagerInterface {
         let unit_name = name.to_string();
         let conn = ctx.connection().clone();
 
-        self.handle.spawn(async move {
+        let task = async move {
             let job_result = resolve_start_unit_result(manager, &unit_name).await;
             emit_job_removed_signal(&conn, job_id, &unit_name, job_result, "StartUnit").await;
-        });
+        };
+        self.handle
+            .spawn(task.instrument(job_span("StartUnit", job_id)));
+
+        Ok(job)
+    }
+
+    /// Start several units as one job. Names may be brace patterns and
+    /// wildcards (`getty@{tty1,tty2}`, `vm@*`). JobGroupProgress reports each
+    /// unit as its start finishes; JobRemoved, with an empty unit name, ends
+    /// the job ("failed" if any unit failed).
+    async fn start_units(
+        &self,
+        #[zbus(signal_context)] ctx: zbus::object_server::SignalEmitter<'_>,
+        #[zbus(header)] header: Header<'_>,
+        names: Vec<String>,
+        mode: &str,
+    ) -> fdo::Result<OwnedObjectPath> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        tracing::info!("D-Bus StartUnits: {:?} mode={}", names, mode);
+
+        let job_id = next_job_id();
+        let job = job_path(job_id);
+        let manager = Arc::clone(&self.manager);
+        let conn = ctx.connection().clone();
+
+        let task = async move {
+            let names = manager.read().await.expand_unit_patterns(&names);
+            let total = names.len() as u32;
+            let mut results = Manager::start_many(manager, names);
+            let (mut finished, mut failed) = (0, false);
+            while let Some(started) = results.recv().await {
+                finished += 1;
+                let result = match &started.result {
+                    Ok(()) => "done",
+                    Err(e) => {
+                        tracing::error!("StartUnits {} failed: {}", started.name, e);
+                        failed = true;
+                        "failed"
+                    }
+                };
+                emit_job_group_progress_signal(
+                    &conn,
+                    job_id,
+                    &started.name,
+                    result,
+                    finished,
+                    total,
+                )
+                .await;
+            }
+            let job_result = if failed { "failed" } else { "done" };
+            emit_job_removed_signal(&conn, job_id, "", job_result, "StartUnits").await;
+        };
+        self.handle
+            .spawn(task.instrument(job_span("StartUnits", job_id)));
 
         Ok(job)
     }
 
     /// Stop a unit by name
-    async fn stop_unit(&self, name: &str, mode: &str) -> fdo::Result<OwnedObjectPath> {
-        log::info!("D-Bus StopUnit: {} mode={}", name, mode);
+    async fn stop_unit(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        name: &str,
+        mode: &str,
+    ) -> fdo::Result<OwnedObjectPath> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        tracing::info!("D-Bus StopUnit: {} mode={}", name, mode);
+        let job_id = next_job_id();
         let manager = Arc::clone(&self.manager);
         let name = name.to_string();
-        self.handle.spawn(async move {
+        let task = async move {
+            if stop_special_user_unit(&name).await {
+                return;
+            }
             let mut mgr = manager.write().await;
-            if let Err(e) = mgr.stop(&name).await {
-                log::error!("StopUnit {} failed: {}", name, e);
+            if let Err(e) = mgr.enqueue_stop(&name).await {
+                tracing::error!("StopUnit {} failed: {}", name, e);
             }
-        });
-        Ok(job_path(next_job_id()))
+            mgr.publish_states();
+        };
+        self.handle
+            .spawn(task.instrument(job_span("StopUnit", job_id)));
+        Ok(job_path(job_id))
     }
 
-    /// Kill processes in a unit (whom: "main", "control", "all")
-    async fn kill_unit(&self, name: &str, whom: &str, signal: i32) -> fdo::Result<()> {
-        log::info!("D-Bus KillUnit: {} whom={} signal={}", name, whom, signal);
-        // Get the process and send signal
-        let manager = self.manager.read().await;
-        if let Some(state) = manager.status(name) {
-            if let Some(pid) = state.main_pid {
-                unsafe {
-                    libc::kill(pid as i32, signal);
+    /// Stop several units, with patterns as for StartUnits
+    async fn stop_units(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        names: Vec<String>,
+        mode: &str,
+    ) -> fdo::Result<OwnedObjectPath> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        tracing::info!("D-Bus StopUnits: {:?} mode={}", names, mode);
+        let job_id = next_job_id();
+        let manager = Arc::clone(&self.manager);
+        let task = async move {
+            let mut mgr = manager.write().await;
+            let running = ["active".to_string(), "activating".to_string()];
+            for name in mgr.units_by_patterns(&names, &running) {
+                if let Err(e) = mgr.enqueue_stop(&name).await {
+                    tracing::error!("StopUnits {} failed: {}", name, e);
                 }
             }
+            mgr.publish_states();
+        };
+        self.handle
+            .spawn(task.instrument(job_span("StopUnits", job_id)));
+        Ok(job_path(job_id))
+    }
+
+    /// Kill processes in a unit (whom: "main", "control", "all")
+    async fn kill_unit(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        name: &str,
+        whom: &str,
+        signal: i32,
+    ) -> fdo::Result<()> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        log::info!("D-Bus KillUnit: {} whom={} signal={}", name, whom, signal);
+        let whom = KillWhom::parse(whom)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Invalid kill target: {}", whom)))?;
+        // logind kills scopes that may already be gone; that is not an error
+        if self.states.get(name).is_none() {
+            log::debug!("KillUnit {}: unit not loaded", name);
+            return Ok(());
         }
+        if let Err(e) = self.manager.read().await.kill_unit(name, whom, signal) {
+            log::debug!("KillUnit {}: {}", name, e);
+        }
+        Ok(())
+    }
+
+    /// Remove per-unit directories of a stopped unit (mask: "cache", "state", "all", ...)
+    async fn clean_unit(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        name: &str,
+        mask: Vec<String>,
+    ) -> Result<(), BusError> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        log::info!("D-Bus CleanUnit: {} mask={:?}", name, mask);
+        let what = CleanWhat::parse_list(&mask).map_err(fdo::Error::InvalidArgs)?;
+        self.manager.write().await.clean_unit(name, &what)?;
+        Ok(())
+    }
+
+    /// Add KEY=VALUE assignments to the environment of spawned services
+    async fn set_environment(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        assignments: Vec<String>,
+    ) -> fdo::Result<()> {
+        self.authorize(&header, polkit::SET_ENVIRONMENT).await?;
+        log::info!("D-Bus SetEnvironment: {:?}", assignments);
+        self.manager
+            .write()
+            .await
+            .set_environment(&assignments)
+            .map_err(|e| fdo::Error::InvalidArgs(e.to_string()))
+    }
+
+    /// Remove variables from the environment of spawned services
+    async fn unset_environment(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        names: Vec<String>,
+    ) -> fdo::Result<()> {
+        self.authorize(&header, polkit::SET_ENVIRONMENT).await?;
+        log::info!("D-Bus UnsetEnvironment: {:?}", names);
+        self.manager.write().await.unset_environment(&names);
         Ok(())
     }
 
+    /// Unset `names`, then apply `assignments`, under one lock
+    async fn unset_and_set_environment(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        names: Vec<String>,
+        assignments: Vec<String>,
+    ) -> fdo::Result<()> {
+        self.authorize(&header, polkit::SET_ENVIRONMENT).await?;
+        log::info!(
+            "D-Bus UnsetAndSetEnvironment: unset {:?} set {:?}",
+            names,
+            assignments
+        );
+        let mut mgr = self.manager.write().await;
+        mgr.unset_environment(&names);
+        mgr.set_environment(&assignments)
+            .map_err(|e| fdo::Error::InvalidArgs(e.to_string()))
+    }
+
     /// Create and start a transient unit (used by logind for session scopes)
     ///
     /// Logind uses this to create session scopes like "session-1.scope".
@@ -130,12 +372,16 @@ impl ManagerInterface {
     async fn start_transient_unit(
         &self,
         #[zbus(signal_context)] ctx: zbus::object_server::SignalEmitter<'_>,
+        #[zbus(header)] header: Header<'_>,
         name: &str,
         mode: &str,
         properties: Vec<(String, OwnedValue)>,
         _aux: Vec<(String, Vec<(String, OwnedValue)>)>,
     ) -> fdo::Result<OwnedObjectPath> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
         let (slice, description, pids) = parse_scope_properties(&properties);
+        let controller = parse_scope_controller(&properties);
+        let (fd_store_max, stored_fds) = parse_fd_store_properties(&properties)?;
         log_scope_start(name, mode, slice.as_deref(), description.as_deref(), &pids);
 
         let job_id = next_job_id();
@@ -146,13 +392,22 @@ impl ManagerInterface {
 
         self.handle.spawn(async move {
             let job_result = register_scope_job(
-                manager,
+                Arc::clone(&manager),
                 &unit_name,
                 slice.as_deref(),
                 description.as_deref(),
                 &pids,
             )
             .await;
+            if let (Some(controller), "done") = (&controller, job_result) {
+                manager.write().await.set_scope_controller(&unit_name, controller);
+            }
+            if job_result == "done" && !stored_fds.is_empty() {
+                let mut mgr = manager.write().await;
+                if let Err(e) = mgr.store_fds(&unit_name, fd_store_max, stored_fds) {
+                    log::warn!("Cannot store FDs of {}: {}", unit_name, e);
+                }
+            }
             emit_job_removed_signal(&conn, job_id, &unit_name, job_result, "StartTransientUnit")
                 .await;
             if job_result == "done" {
@@ -170,21 +425,256 @@ impl ManagerInterface {
     }
 
     /// Reload daemon configuration
-    async fn reload(&self) -> fdo::Result<()> {
+    async fn reload(&self, #[zbus(header)] header: Header<'_>) -> fdo::Result<()> {
+        self.authorize(&header, polkit::RELOAD_DAEMON).await?;
         log::info!("Reload called");
         Ok(())
     }
 
-    /// Get unit by name, returns object path
-    async fn get_unit(&self, name: &str) -> fdo::Result<OwnedObjectPath> {
-        let path = unit_object_path(name);
-        Ok(ObjectPath::try_from(path).unwrap().into())
+    /// Object path of a loaded unit; NoSuchUnit if it is not loaded
+    async fn get_unit(&self, name: &str) -> Result<OwnedObjectPath, BusError> {
+        self.unit_objects
+            .path(name)
+            .await
+            .ok_or_else(|| ManagerError::NotFound(name.to_string()).into())
+    }
+
+    /// Load a unit (if it is not loaded yet) and return its object path
+    async fn load_unit(&self, name: &str) -> Result<OwnedObjectPath, BusError> {
+        {
+            let mut mgr = self.manager.write().await;
+            if self.states.get(name).is_none() {
+                mgr.load(name).await?;
+                mgr.publish_states();
+            }
+        }
+        self.get_unit(name).await
+    }
+
+    /// Change resource limits of a unit (MemoryMax, CPUQuotaPerSecUSec, TasksMax)
+    async fn set_unit_properties(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        name: &str,
+        runtime: bool,
+        properties: Vec<(String, OwnedValue)>,
+    ) -> Result<(), BusError> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        log::info!("SetUnitProperties: {} (runtime={})", name, runtime);
+        let properties = properties
+            .iter()
+            .map(|(key, value)| parse_unit_property(key, value))
+            .collect::<fdo::Result<Vec<_>>>()?;
+        self.manager
+            .write()
+            .await
+            .set_unit_properties(name, &properties, runtime)
+            .await?;
+        Ok(())
+    }
+
+    /// Loaded units with their load, active and sub states
+    async fn list_units(&self) -> Vec<UnitListing> {
+        self.unit_listings(|_, _| true)
+    }
+
+    /// Loaded units in one of `states` (active states; all if empty) matching
+    /// one of `patterns` (all if empty)
+    async fn list_units_by_patterns(
+        &self,
+        states: Vec<String>,
+        patterns: Vec<String>,
+    ) -> Vec<UnitListing> {
+        let matching = if patterns.is_empty() {
+            None
+        } else {
+            Some(self.manager.read().await.expand_unit_patterns(&patterns))
+        };
+        self.unit_listings(|name, unit| {
+            matching
+                .as_ref()
+                .map_or(true, |names| names.iter().any(|matched| matched == name))
+                && (states.is_empty() || states.iter().any(|state| state == unit.active.as_str()))
+        })
+    }
+
+    /// Reset the failed state of every unit
+    async fn reset_failed(&self, #[zbus(header)] header: Header<'_>) -> fdo::Result<()> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        let mut mgr = self.manager.write().await;
+        mgr.reset_failed();
+        mgr.publish_states();
+        Ok(())
+    }
+
+    /// Reset the failed state of the units `name` stands for (a name or a
+    /// pattern as for StartUnits)
+    async fn reset_failed_unit(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        name: &str,
+    ) -> fdo::Result<()> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        let mut mgr = self.manager.write().await;
+        for name in mgr.units_by_patterns(&[name.to_string()], &["failed".to_string()]) {
+            mgr.reset_failed_unit(&name);
+        }
+        mgr.publish_states();
+        Ok(())
+    }
+
+    /// Unit file state: "enabled", "disabled", "static", "masked", "linked", ...
+    async fn get_unit_file_state(&self, file: &str) -> Result<String, BusError> {
+        let mut mgr = self.manager.write().await;
+        if mgr.load_state(file) == "stub" {
+            if let Err(e) = mgr.load(file).await {
+                log::debug!("GetUnitFileState {}: {}", file, e);
+            }
+        }
+        mgr.unit_file_state(file)
+            .map(String::from)
+            .ok_or_else(|| ManagerError::NotFound(file.to_string()).into())
+    }
+
+    /// Point default.target at `name`; the change is reported as
+    /// ("symlink", link, target) like systemd. Links are always replaced, so
+    /// `force` has no effect.
+    async fn set_default_target(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        name: &str,
+        _force: bool,
+    ) -> Result<Vec<(String, String, String)>, BusError> {
+        self.authorize(&header, polkit::MANAGE_UNIT_FILES).await?;
+        log::info!("SetDefaultTarget: {}", name);
+        let link = self.manager.write().await.set_default(name).await?;
+        let target = std::fs::read_link(&link).unwrap_or_default();
+        Ok(vec![(
+            "symlink".to_string(),
+            link.display().to_string(),
+            target.display().to_string(),
+        )])
+    }
+
+    /// Target default.target points at
+    async fn get_default_target(&self) -> Result<String, BusError> {
+        Ok(self.manager.read().await.get_default_target()?)
+    }
+
+    /// Listening sockets as (listen, type, unit, activated units); a sysd
+    /// extension so monitoring tools need not walk every socket unit
+    async fn list_sockets(&self) -> Vec<(String, String, String, Vec<String>)> {
+        self.manager
+            .read()
+            .await
+            .list_sockets()
+            .into_iter()
+            .map(|listing| {
+                (
+                    listing.listen,
+                    listing.socket_type.to_string(),
+                    listing.unit,
+                    listing.activates,
+                )
+            })
+            .collect()
     }
 
-    /// Load a unit file
-    async fn load_unit(&self, name: &str) -> fdo::Result<OwnedObjectPath> {
-        let path = unit_object_path(name);
-        Ok(ObjectPath::try_from(path).unwrap().into())
+    /// Names and duplicates of the FDs a unit keeps in its FD store
+    async fn get_unit_file_descriptor_store(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        name: &str,
+    ) -> Result<Vec<(String, zbus::zvariant::OwnedFd)>, BusError> {
+        self.authorize(&header, polkit::MANAGE_UNITS).await?;
+        let entries = self.manager.read().await.fd_store_entries(name)?;
+        Ok(entries
+            .into_iter()
+            .map(|(fd_name, fd)| (fd_name, fd.into()))
+            .collect())
+    }
+
+    /// The manager's internal state as text, like systemd's Dump
+    async fn dump(&self) -> String {
+        self.manager.read().await.dump()
+    }
+
+    /// The last state transitions of a unit as (CLOCK_REALTIME usec,
+    /// active state, sub state, reason), oldest first; a sysd extension
+    async fn get_unit_state_history(
+        &self,
+        name: &str,
+    ) -> Result<Vec<(u64, String, String, String)>, BusError> {
+        let history = self.manager.read().await.state_history(name)?;
+        Ok(history
+            .into_iter()
+            .map(|transition| {
+                let since_epoch = transition.at.duration_since(UNIX_EPOCH).unwrap_or_default();
+                (
+                    since_epoch.as_micros() as u64,
+                    transition.active.as_str().to_string(),
+                    transition.sub.as_str().to_string(),
+                    transition.reason.unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
+
+    /// Change the manager's log level ("debug", "info", "warning", ...)
+    ///
+    /// systemd only lets root do this; polkit's reload-daemon action is the
+    /// closest match for everyone else.
+    async fn set_log_level(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        level: &str,
+    ) -> fdo::Result<()> {
+        self.authorize(&header, polkit::RELOAD_DAEMON).await?;
+        crate::logging::set_level(level).map_err(|e| fdo::Error::InvalidArgs(e.to_string()))
+    }
+
+    /// Send the manager's messages elsewhere ("console", "journal", "kmsg",
+    /// ...)
+    async fn set_log_target(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        target: &str,
+    ) -> fdo::Result<()> {
+        self.authorize(&header, polkit::RELOAD_DAEMON).await?;
+        crate::logging::set_target(target).map_err(|e| fdo::Error::InvalidArgs(e.to_string()))
+    }
+
+    /// Power off, reboot or halt ("poweroff", "reboot", "halt") at `usec`
+    /// (CLOCK_REALTIME), warning logged-in users; like logind's method
+    async fn schedule_shutdown(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+        kind: &str,
+        usec: u64,
+    ) -> Result<(), BusError> {
+        let shutdown_type = ShutdownType::from_name(kind)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("Unknown shutdown type: {}", kind)))?;
+        let action = shutdown_action(shutdown_type);
+        self.authorize(&header, action).await?;
+        let at = UNIX_EPOCH + Duration::from_micros(usec);
+        self.manager
+            .write()
+            .await
+            .schedule_shutdown(shutdown_type, at, None)?;
+        Ok(())
+    }
+
+    /// Disarm the scheduled shutdown; false if there was none
+    async fn cancel_scheduled_shutdown(
+        &self,
+        #[zbus(header)] header: Header<'_>,
+    ) -> fdo::Result<bool> {
+        let Some((shutdown_type, _)) = self.manager.read().await.scheduled_shutdown() else {
+            return Ok(false);
+        };
+        let action = shutdown_action(shutdown_type);
+        self.authorize(&header, action).await?;
+        Ok(self.manager.write().await.cancel_scheduled_shutdown())
     }
 
     // ==================== Signals ====================
@@ -199,6 +689,18 @@ impl ManagerInterface {
         result: &str,
     ) -> zbus::Result<()>;
 
+    /// Emitted when one unit of a StartUnits job finished starting
+    #[zbus(signal)]
+    async fn job_group_progress(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        job: ObjectPath<'_>,
+        unit: &str,
+        result: &str,
+        finished: u32,
+        total: u32,
+    ) -> zbus::Result<()>;
+
     /// Emitted when a unit is removed/unloaded
     #[zbus(signal)]
     async fn unit_removed(
@@ -217,6 +719,53 @@ impl ManagerInterface {
     async fn version(&self) -> String {
         "sysd 0.1.0".to_string()
     }
+
+    /// Optional kernel features, "+cgroups -seccomp ..."
+    #[zbus(property)]
+    async fn features(&self) -> String {
+        self.manager.read().await.features().to_string()
+    }
+
+    /// Manager environment block (read by `systemctl show-environment`)
+    #[zbus(property)]
+    async fn environment(&self) -> Vec<String> {
+        self.manager.read().await.show_environment()
+    }
+
+    /// Level of the manager's own messages (`systemd-analyze log-level`)
+    #[zbus(property)]
+    async fn log_level(&self) -> String {
+        crate::logging::level().to_string()
+    }
+
+    /// Where the manager's own messages go (`systemd-analyze log-target`)
+    #[zbus(property)]
+    async fn log_target(&self) -> String {
+        crate::logging::target().to_string()
+    }
+
+    /// Kind and CLOCK_REALTIME usec of the scheduled shutdown, ("", 0) if
+    /// there is none
+    #[zbus(property)]
+    async fn scheduled_shutdown(&self) -> (String, u64) {
+        match self.manager.read().await.scheduled_shutdown() {
+            Some((shutdown_type, at)) => {
+                let since_epoch = at.duration_since(UNIX_EPOCH).unwrap_or_default();
+                let usec = since_epoch.as_micros() as u64;
+                (shutdown_type.as_str().to_string(), usec)
+            }
+            None => (String::new(), 0),
+        }
+    }
+}
+
+/// The polkit action logind guards a `shutdown_type` shutdown with
+fn shutdown_action(shutdown_type: ShutdownType) -> &'static str {
+    match shutdown_type {
+        ShutdownType::Poweroff => polkit::POWER_OFF,
+        ShutdownType::Reboot => polkit::REBOOT,
+        ShutdownType::Halt => polkit::HALT,
+    }
 }
 
 const USER_RUNTIME_DIR_PREFIX: &str = "user-runtime-dir@";
@@ -246,11 +795,17 @@ fn start_user_runtime_dir(unit_name: &str) -> &'static str {
         return "failed";
     };
 
-    let runtime_dir = format!("/run/user/{}", uid);
+    let runtime_dir = user_runtime_dir(uid);
+    let created = !std::path::Path::new(&runtime_dir).exists();
     if let Err(e) = std::fs::create_dir_all(&runtime_dir) {
         log::error!("Failed to create {}: {}", runtime_dir, e);
         return "failed";
     }
+    // Like systemd-user-runtime-dir, back a fresh directory with a tmpfs so
+    // it goes away on logout; never shadow an existing one
+    if created {
+        mount_runtime_dir_tmpfs(&runtime_dir, uid);
+    }
 
     if let Err(e) =
         std::fs::set_permissions(&runtime_dir, std::fs::Permissions::from_mode(0o700))
@@ -260,7 +815,7 @@ fn start_user_runtime_dir(unit_name: &str) -> &'static str {
 
     match std::ffi::CString::new(runtime_dir.as_str()) {
         Ok(runtime_dir_cstr) => {
-            unsafe { libc::chown(runtime_dir_cstr.as_ptr(), uid, uid) };
+            unsafe { libc::chown(runtime_dir_cstr.as_ptr(), uid, user_gid(uid)) };
         }
         Err(e) => {
             log::error!("Failed to build runtime path for chown: {}", e);
@@ -272,6 +827,140 @@ fn start_user_runtime_dir(unit_name: &str) -> &'static str {
     "done"
 }
 
+fn mount_runtime_dir_tmpfs(runtime_dir: &str, uid: u32) {
+    use nix::mount::{mount, MsFlags};
+
+    let options = format!("mode=0700,uid={},gid={},size=10%", uid, user_gid(uid));
+    match mount(
+        Some("tmpfs"),
+        runtime_dir,
+        Some("tmpfs"),
+        MsFlags::MS_NODEV | MsFlags::MS_NOSUID,
+        Some(options.as_str()),
+    ) {
+        Ok(()) => log::info!("Mounted tmpfs on {}", runtime_dir),
+        Err(e) => log::warn!("Failed to mount tmpfs on {}: {}", runtime_dir, e),
+    }
+}
+
+/// /run/user/<uid> (below `--root`)
+fn user_runtime_dir(uid: u32) -> String {
+    crate::root::path(format!("/run/user/{}", uid))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Primary group of `uid` (falls back to a group with the same id)
+fn user_gid(uid: u32) -> u32 {
+    nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+        .ok()
+        .flatten()
+        .map(|user| user.gid.as_raw())
+        .unwrap_or(uid)
+}
+
+fn user_is_lingering(uid: u32) -> bool {
+    nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid))
+        .ok()
+        .flatten()
+        .is_some_and(|user| Manager::is_lingering(&user.name))
+}
+
+/// StopUnit for user-runtime-dir@UID.service and user@UID.service (sent by
+/// logind on the user's last logout). Returns false for other units.
+async fn stop_special_user_unit(unit_name: &str) -> bool {
+    if let Some(uid) = parse_uid_from_unit(unit_name, USER_RUNTIME_DIR_PREFIX) {
+        if !user_is_lingering(uid) {
+            // Walks a directory tree the user filled; keep it off the runtime
+            let _ = tokio::task::spawn_blocking(move || stop_user_runtime_dir(uid)).await;
+        }
+        return true;
+    }
+    if let Some(uid) = parse_uid_from_unit(unit_name, USER_MANAGER_PREFIX) {
+        if !user_is_lingering(uid) {
+            stop_user_manager(uid);
+        }
+        return true;
+    }
+    false
+}
+
+fn stop_user_runtime_dir(uid: u32) {
+    let runtime_dir = user_runtime_dir(uid);
+    let path = std::path::Path::new(&runtime_dir);
+    let Ok(meta) = path.symlink_metadata() else {
+        return;
+    };
+    if !meta.is_dir() {
+        log::warn!("Leaving {} alone: not a directory", runtime_dir);
+        return;
+    }
+    // The tmpfs from start_user_runtime_dir; if it cannot go, neither does
+    // anything below it
+    let parent_dev = path.parent().and_then(|p| p.metadata().ok()).map(|m| m.dev());
+    if parent_dev.is_some_and(|dev| dev != meta.dev()) {
+        if let Err(e) = nix::mount::umount2(path, nix::mount::MntFlags::MNT_DETACH) {
+            log::warn!("Leaving {} alone: cannot unmount it: {}", runtime_dir, e);
+            return;
+        }
+    }
+    let Ok(meta) = path.symlink_metadata() else {
+        return;
+    };
+    match remove_dir_on_device(path, meta.dev()) {
+        Ok(()) => log::info!("Removed user runtime directory: {}", runtime_dir),
+        Err(e) => log::warn!("Failed to remove {}: {}", runtime_dir, e),
+    }
+}
+
+/// remove_dir_all that neither follows symlinks nor descends into other
+/// filesystems mounted below `dir`; those and their parents stay
+fn remove_dir_on_device(dir: &std::path::Path, dev: u64) -> std::io::Result<()> {
+    let mut result = Ok(());
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let meta = path.symlink_metadata()?;
+        let removed = if !meta.is_dir() {
+            std::fs::remove_file(&path)
+        } else if meta.dev() == dev {
+            remove_dir_on_device(&path, dev)
+        } else {
+            Err(std::io::Error::other(format!(
+                "{} is another mount",
+                path.display()
+            )))
+        };
+        result = result.and(removed);
+    }
+    result?;
+    std::fs::remove_dir(dir)
+}
+
+/// Terminate the user's sysd and session bus, found through their sockets
+fn stop_user_manager(uid: u32) {
+    let runtime_dir = format!("/run/user/{}", uid);
+    for socket in ["sysd.sock", "bus"] {
+        let path = format!("{}/{}", runtime_dir, socket);
+        let Some(pid) = socket_peer_pid(&path, uid) else {
+            continue;
+        };
+        log::info!("Stopping {} owner (PID {}) for uid {}", path, pid, uid);
+        let _ = nix::sys::signal::kill(
+            nix::unistd::Pid::from_raw(pid),
+            nix::sys::signal::Signal::SIGTERM,
+        );
+    }
+}
+
+/// PID of the process listening on `path`, if it runs as `uid`
+fn socket_peer_pid(path: &str, uid: u32) -> Option<i32> {
+    use nix::sys::socket::{getsockopt, sockopt::PeerCredentials};
+
+    let stream = std::os::unix::net::UnixStream::connect(path).ok()?;
+    let credentials = getsockopt(&stream, PeerCredentials).ok()?;
+    (credentials.uid() == uid && credentials.pid() > 1).then(|| credentials.pid())
+}
+
 fn start_user_manager_unit(unit_name: &str) -> &'static str {
     let Some(uid) = parse_uid_from_unit(unit_name, USER_MANAGER_PREFIX) else {
         log::error!("Invalid uid in {}: {}", USER_MANAGER_PREFIX, unit_name);
@@ -314,7 +1003,7 @@ fn ensure_user_session_bus(uid: u32, runtime_dir: &str, bus_path: &str) -> bool
             "--nopidfile",
         ])
         .uid(uid)
-        .gid(uid)
+        .gid(user_gid(uid))
         .env("XDG_RUNTIME_DIR", runtime_dir)
         .stdin(std::process::Stdio::null())
         .stdout(std::process::Stdio::null())
@@ -344,7 +1033,7 @@ fn start_user_sysd(uid: u32, runtime_dir: &str, sysd_socket: &str, bus_path: &st
     let spawn_result = std::process::Command::new("/usr/bin/sysd")
         .args(["--user"])
         .uid(uid)
-        .gid(uid)
+        .gid(user_gid(uid))
         .env("XDG_RUNTIME_DIR", runtime_dir)
         .env("DBUS_SESSION_BUS_ADDRESS", &dbus_addr)
         .stdin(std::process::Stdio::null())
@@ -372,7 +1061,9 @@ fn start_user_sysd(uid: u32, runtime_dir: &str, sysd_socket: &str, bus_path: &st
 
 async fn start_regular_unit(manager: Arc<RwLock<Manager>>, unit_name: &str) -> &'static str {
     let mut mgr = manager.write().await;
-    match mgr.start(unit_name).await {
+    let result = mgr.start(unit_name).await;
+    mgr.publish_states();
+    match result {
         Ok(()) => "done",
         Err(e) => {
             log::error!("StartUnit {} failed: {}", unit_name, e);
@@ -407,7 +1098,9 @@ async fn register_scope_job(
 ) -> &'static str {
     let result = {
         let mut mgr = manager.write().await;
-        mgr.register_scope(unit_name, slice, description, pids).await
+        let result = mgr.register_scope(unit_name, slice, description, pids).await;
+        mgr.publish_states();
+        result
     };
 
     match result {
@@ -452,6 +1145,43 @@ async fn emit_job_removed_signal(
     }
 }
 
+async fn emit_job_group_progress_signal(
+    conn: &zbus::Connection,
+    job_id: u32,
+    unit_name: &str,
+    unit_result: &str,
+    finished: u32,
+    total: u32,
+) {
+    log::info!(
+        "StartUnits job {}: {} {} ({}/{})",
+        job_id,
+        unit_name,
+        unit_result,
+        finished,
+        total
+    );
+
+    let Ok(ctx) = SignalEmitter::new(conn, "/org/freedesktop/systemd1") else {
+        log::error!("Failed to create SignalEmitter for JobGroupProgress");
+        return;
+    };
+
+    if let Err(e) = ManagerInterface::job_group_progress(
+        &ctx,
+        job_id,
+        job_path(job_id).as_ref(),
+        unit_name,
+        unit_result,
+        finished,
+        total,
+    )
+    .await
+    {
+        log::warn!("Failed to emit JobGroupProgress signal: {}", e);
+    }
+}
+
 fn parse_string_property(value: &OwnedValue) -> Option<String> {
     let Ok(value) = value.downcast_ref::<Value<'_>>() else {
         return None;
@@ -500,6 +1230,34 @@ fn collect_pidfds(value: &OwnedValue, pids: &mut Vec<u32>) {
     }
 }
 
+fn parse_u64_property(value: &OwnedValue) -> Option<u64> {
+    match value.downcast_ref::<Value<'_>>().ok()? {
+        Value::U64(number) => Some(number),
+        _ => None,
+    }
+}
+
+/// SetUnitProperties entry; u64::MAX stands for infinity as in systemd
+fn parse_unit_property(key: &str, value: &OwnedValue) -> fdo::Result<UnitProperty> {
+    let number = parse_u64_property(value)
+        .ok_or_else(|| fdo::Error::InvalidArgs(format!("{} must be of type t", key)))?;
+    let limit = Some(number).filter(|&number| number != u64::MAX);
+    match key {
+        "MemoryMax" => Ok(UnitProperty::MemoryMax(limit)),
+        // Microseconds of CPU time per second; 10000 = 1% of one CPU
+        "CPUQuotaPerSecUSec" => Ok(UnitProperty::CpuQuota(
+            limit.map(|usec| u32::try_from(usec / 10_000).unwrap_or(u32::MAX)),
+        )),
+        "TasksMax" => Ok(UnitProperty::TasksMax(
+            limit.map(|max| u32::try_from(max).unwrap_or(u32::MAX)),
+        )),
+        _ => Err(fdo::Error::InvalidArgs(format!(
+            "Cannot set property {} at runtime",
+            key
+        ))),
+    }
+}
+
 /// Parse properties from StartTransientUnit call
 fn parse_scope_properties(
     properties: &[(String, OwnedValue)],
@@ -521,6 +1279,71 @@ fn parse_scope_properties(
     (slice, description, pids)
 }
 
+/// FileDescriptorStoreMax= and FileDescriptorStore= (a(sh): name and FD) of
+/// a StartTransientUnit call; the FDs are duplicated, as the message owns
+/// them
+fn parse_fd_store_properties(
+    properties: &[(String, OwnedValue)],
+) -> fdo::Result<(usize, Vec<(String, std::os::fd::OwnedFd)>)> {
+    let mut max = 0;
+    let mut fds = Vec::new();
+    for (key, value) in properties {
+        match key.as_str() {
+            "FileDescriptorStoreMax" => match value.downcast_ref::<Value<'_>>() {
+                Ok(Value::U32(number)) => max = number as usize,
+                _ => {
+                    return Err(fdo::Error::InvalidArgs(
+                        "FileDescriptorStoreMax must be of type u".into(),
+                    ))
+                }
+            },
+            "FileDescriptorStore" => collect_stored_fds(value, &mut fds)?,
+            _ => {}
+        }
+    }
+    if fds.len() > max {
+        return Err(fdo::Error::InvalidArgs(format!(
+            "{} FDs passed but FileDescriptorStoreMax={}",
+            fds.len(),
+            max
+        )));
+    }
+    Ok((max, fds))
+}
+
+fn collect_stored_fds(
+    value: &OwnedValue,
+    fds: &mut Vec<(String, std::os::fd::OwnedFd)>,
+) -> fdo::Result<()> {
+    let invalid = || fdo::Error::InvalidArgs("FileDescriptorStore must be of type a(sh)".into());
+    let Ok(Value::Array(entries)) = value.downcast_ref::<Value<'_>>() else {
+        return Err(invalid());
+    };
+    for entry in entries.iter() {
+        let Value::Structure(entry) = entry else {
+            return Err(invalid());
+        };
+        let [Value::Str(name), Value::Fd(fd)] = entry.fields() else {
+            return Err(invalid());
+        };
+        let fd = fd
+            .as_fd()
+            .try_clone_to_owned()
+            .map_err(|e| fdo::Error::Failed(format!("Cannot duplicate FD: {}", e)))?;
+        fds.push((name.to_string(), fd));
+    }
+    Ok(())
+}
+
+/// Controller= of a StartTransientUnit call (D-Bus name managing the scope)
+fn parse_scope_controller(properties: &[(String, OwnedValue)]) -> Option<String> {
+    properties
+        .iter()
+        .find(|(key, _)| key == "Controller")
+        .and_then(|(_, value)| parse_string_property(value))
+        .filter(|controller| !controller.is_empty())
+}
+
 /// Convert a PIDFD (process file descriptor) to a PID
 fn pidfd_to_pid(pidfd: std::os::unix::io::RawFd) -> Result<u32, std::io::Error> {
     // Read /proc/self/fdinfo/<fd> and parse the Pid: line
@@ -1,4 +1,5 @@
 use super::*;
+use crate::dbus::test_call;
 use crate::manager::{ActiveState, Manager, SubState};
 use std::os::fd::{FromRawFd, OwnedFd};
 use std::sync::Arc;
@@ -70,6 +71,26 @@ fn special_user_unit_detection_routes_only_user_units() {
     );
 }
 
+#[tokio::test]
+async fn special_user_unit_stops_route_only_user_units() {
+    assert!(!stop_special_user_unit("not-special.service").await);
+    assert!(!stop_special_user_unit("user-runtime-dir@invalid.service").await);
+    assert!(stop_special_user_unit("user@4294967294.service").await);
+}
+
+#[test]
+fn socket_peer_pid_requires_a_listener_owned_by_the_user() {
+    let root = temp_dir("peer-socket");
+    let path = root.0.join("sysd.sock");
+    let _listener = std::os::unix::net::UnixListener::bind(&path).unwrap();
+    let path = path.to_string_lossy();
+    let uid = unsafe { libc::geteuid() };
+
+    assert_eq!(socket_peer_pid(&path, uid), Some(std::process::id() as i32));
+    assert_eq!(socket_peer_pid(&path, uid.wrapping_add(1)), None);
+    assert_eq!(socket_peer_pid("/nonexistent/sysd.sock", uid), None);
+}
+
 #[test]
 fn user_runtime_dir_unit_accepts_current_user() {
     let uid = unsafe { libc::geteuid() };
@@ -78,6 +99,27 @@ fn user_runtime_dir_unit_accepts_current_user() {
     assert_eq!(start_user_runtime_dir(&unit), "done");
 }
 
+#[test]
+fn runtime_dir_removal_leaves_symlink_targets_alone() {
+    let root = temp_dir("runtime-dir");
+    let outside = root.0.join("outside");
+    std::fs::create_dir_all(&outside).unwrap();
+    std::fs::write(outside.join("keep"), "").unwrap();
+    let runtime_dir = root.0.join("1000");
+    std::fs::create_dir_all(runtime_dir.join("sub")).unwrap();
+    std::fs::write(runtime_dir.join("sub/file"), "").unwrap();
+    std::os::unix::fs::symlink(&outside, runtime_dir.join("link")).unwrap();
+    let dev = runtime_dir.metadata().unwrap().dev();
+
+    remove_dir_on_device(&runtime_dir, dev).unwrap();
+    assert!(!runtime_dir.exists());
+    assert!(outside.join("keep").exists());
+
+    std::fs::create_dir_all(runtime_dir.join("sub")).unwrap();
+    assert!(remove_dir_on_device(&runtime_dir, dev.wrapping_add(1)).is_err());
+    assert!(runtime_dir.join("sub").exists());
+}
+
 #[test]
 fn user_session_bus_reports_spawn_error_for_invalid_paths() {
     assert!(!ensure_user_session_bus(
@@ -159,6 +201,22 @@ fn parse_scope_properties_defaults_when_properties_are_missing_or_wrong_type() {
     assert!(pids.is_empty());
 }
 
+#[test]
+fn parse_scope_controller_reads_non_empty_controller() {
+    let properties = vec![
+        ("Slice".to_string(), string_value("user-1000.slice")),
+        ("Controller".to_string(), string_value(":1.42")),
+    ];
+    assert_eq!(
+        parse_scope_controller(&properties).as_deref(),
+        Some(":1.42")
+    );
+
+    let empty = vec![("Controller".to_string(), string_value(""))];
+    assert_eq!(parse_scope_controller(&empty), None);
+    assert_eq!(parse_scope_controller(&[]), None);
+}
+
 #[test]
 fn parse_scope_properties_collects_pidfds() {
     let properties = vec![("PIDFDs".to_string(), pidfd_array_value())];
@@ -183,6 +241,50 @@ fn parse_scope_properties_ignores_fd_values_that_are_not_pidfds() {
     assert!(pids.is_empty());
 }
 
+fn stored_fds_value(name: &'static str, owned_fd: OwnedFd) -> OwnedValue {
+    let mut array = Array::new(<(String, Fd<'_>) as Type>::SIGNATURE);
+    array
+        .append(Value::from((Str::from_static(name), Fd::from(owned_fd))))
+        .unwrap();
+    OwnedValue::try_from(Value::Array(array)).unwrap()
+}
+
+#[test]
+fn parse_fd_store_properties_duplicates_fds_within_the_limit() {
+    use std::io::{Read, Write};
+
+    let (ours, theirs) = std::os::unix::net::UnixStream::pair().unwrap();
+    let properties = vec![
+        ("FileDescriptorStoreMax".to_string(), OwnedValue::from(2u32)),
+        (
+            "FileDescriptorStore".to_string(),
+            stored_fds_value("conn", theirs.into()),
+        ),
+    ];
+
+    let (max, fds) = parse_fd_store_properties(&properties).unwrap();
+    assert_eq!(max, 2);
+    assert_eq!(fds.len(), 1);
+    assert_eq!(fds[0].0, "conn");
+    let (_, fd) = fds.into_iter().next().unwrap();
+    std::os::unix::net::UnixStream::from(fd)
+        .write_all(b"ok")
+        .unwrap();
+    let mut buf = [0; 2];
+    (&ours).read_exact(&mut buf).unwrap();
+    assert_eq!(&buf, b"ok");
+
+    let (_, theirs) = std::os::unix::net::UnixStream::pair().unwrap();
+    let unlimited = vec![(
+        "FileDescriptorStore".to_string(),
+        stored_fds_value("conn", theirs.into()),
+    )];
+    assert!(parse_fd_store_properties(&unlimited).is_err());
+    let mistyped = vec![("FileDescriptorStore".to_string(), string_value("conn"))];
+    assert!(parse_fd_store_properties(&mistyped).is_err());
+    assert_eq!(parse_fd_store_properties(&[]).unwrap().0, 0);
+}
+
 #[test]
 fn pidfd_to_pid_reports_missing_fd() {
     let error = pidfd_to_pid(-1).unwrap_err();
@@ -242,35 +344,67 @@ fn log_scope_start_accepts_all_optional_inputs() {
 
 #[tokio::test]
 async fn manager_interface_reports_static_paths_and_version() {
-    let interface = ManagerInterface::new(Arc::new(RwLock::new(Manager::new_user())));
+    let manager = Manager::new_user();
+    let states = manager.state_view();
+    let interface = ManagerInterface::new(Arc::new(RwLock::new(manager)), states);
 
     assert_eq!(interface.version().await, "sysd 0.1.0");
-    assert_eq!(
-        interface.get_unit("sshd.service").await.unwrap().as_str(),
-        "/org/freedesktop/systemd1/unit/sshd_2eservice"
-    );
-    assert_eq!(
-        interface
-            .load_unit("session-2.scope")
-            .await
-            .unwrap()
-            .as_str(),
-        "/org/freedesktop/systemd1/unit/session_2d2_2escope"
-    );
+    // The user manager has no cgroups, and so no cgroup-bpf either
+    let features = interface.features().await;
+    assert!(features.starts_with("-cgroups "));
+    assert!(features.contains("-cgroup-bpf"));
+    assert!(matches!(
+        interface.get_unit("sshd.service").await,
+        Err(BusError::NoSuchUnit(_))
+    ));
+    assert!(matches!(
+        interface.load_unit("session-2.scope").await,
+        Err(BusError::NoSuchUnit(_))
+    ));
     assert_eq!(interface.subscribe().await, Ok(()));
-    assert_eq!(interface.reload().await, Ok(()));
+    assert_eq!(interface.reload(test_call("Reload").header()).await, Ok(()));
+    assert!(interface.dump().await.contains("(user manager)"));
+    assert!(matches!(
+        interface
+            .set_log_level(test_call("SetLogLevel").header(), "loud")
+            .await,
+        Err(fdo::Error::InvalidArgs(_))
+    ));
+    assert!(matches!(
+        interface
+            .set_log_target(test_call("SetLogTarget").header(), "printer")
+            .await,
+        Err(fdo::Error::InvalidArgs(_))
+    ));
 }
 
 #[tokio::test]
 async fn stop_unit_returns_job_path_even_when_unit_is_missing() {
-    let interface = ManagerInterface::new(Arc::new(RwLock::new(Manager::new_user())));
+    let manager = Manager::new_user();
+    let states = manager.state_view();
+    let interface = ManagerInterface::new(Arc::new(RwLock::new(manager)), states);
 
     let job = interface
-        .stop_unit("definitely-missing.service", "replace")
+        .stop_unit(
+            test_call("StopUnit").header(),
+            "definitely-missing.service",
+            "replace",
+        )
         .await
         .unwrap();
 
     assert!(job.as_str().starts_with("/org/freedesktop/systemd1/job/"));
+    let batch_job = interface
+        .stop_units(
+            test_call("StopUnits").header(),
+            vec!["missing@{a,b}".to_string(), "none@*".to_string()],
+            "replace",
+        )
+        .await
+        .unwrap();
+    assert!(batch_job
+        .as_str()
+        .starts_with("/org/freedesktop/systemd1/job/"));
     tokio::time::sleep(std::time::Duration::from_millis(20)).await;
 }
 
@@ -281,19 +415,39 @@ async fn start_unit_and_transient_unit_return_job_paths_with_signal_context() {
     };
     let ctx = zbus::object_server::SignalEmitter::new(&conn, "/org/freedesktop/systemd1").unwrap();
     let manager = Arc::new(RwLock::new(Manager::new_user()));
-    let interface = ManagerInterface::new(Arc::clone(&manager));
+    let states = manager.read().await.state_view();
+    let interface = ManagerInterface::new(Arc::clone(&manager), states);
 
     let start_job = interface
-        .start_unit(ctx.clone(), "definitely-missing.service", "replace")
+        .start_unit(
+            ctx.clone(),
+            test_call("StartUnit").header(),
+            "definitely-missing.service",
+            "replace",
+        )
         .await
         .unwrap();
     assert!(start_job
         .as_str()
         .starts_with("/org/freedesktop/systemd1/job/"));
 
+    let batch_job = interface
+        .start_units(
+            ctx.clone(),
+            test_call("StartUnits").header(),
+            vec!["definitely-missing.service".to_string()],
+            "replace",
+        )
+        .await
+        .unwrap();
+    assert!(batch_job
+        .as_str()
+        .starts_with("/org/freedesktop/systemd1/job/"));
+
     let transient_job = interface
         .start_transient_unit(
             ctx,
+            test_call("StartTransientUnit").header(),
             "session-signal.scope",
             "replace",
             vec![
@@ -330,15 +484,22 @@ async fn signal_helpers_emit_job_and_unit_removed_when_session_bus_is_available(
         .await
         .unwrap();
     emit_job_removed_signal(&conn, 43, "demo.service", "failed", "Test").await;
+    emit_job_group_progress_signal(&conn, 44, "demo.service", "done", 1, 2).await;
 }
 
 #[tokio::test]
 async fn kill_unit_ignores_missing_units_and_checks_scope_main_pid() {
     let manager = Arc::new(RwLock::new(Manager::new_user()));
-    let interface = ManagerInterface::new(Arc::clone(&manager));
+    let states = manager.read().await.state_view();
+    let interface = ManagerInterface::new(Arc::clone(&manager), states);
 
     interface
-        .kill_unit("definitely-missing.scope", "all", 0)
+        .kill_unit(
+            test_call("KillUnit").header(),
+            "definitely-missing.scope",
+            "all",
+            0,
+        )
         .await
         .unwrap();
 
@@ -355,7 +516,12 @@ async fn kill_unit_ignores_missing_units_and_checks_scope_main_pid() {
     );
 
     interface
-        .kill_unit("session-kill.scope", "all", 0)
+        .kill_unit(
+            test_call("KillUnit").header(),
+            "session-kill.scope",
+            "all",
+            0,
+        )
         .await
         .unwrap();
 }
@@ -439,3 +605,285 @@ async fn register_scope_job_uses_explicit_slice() {
         "/sys/fs/cgroup/user-1000.slice/session-77.scope"
     );
 }
+
+#[tokio::test]
+async fn clean_unit_rejects_unknown_masks_and_missing_units() {
+    let manager = Arc::new(RwLock::new(Manager::new_user()));
+    let states = manager.read().await.state_view();
+    let interface = ManagerInterface::new(Arc::clone(&manager), states);
+
+    assert!(matches!(
+        interface
+            .clean_unit(test_call("CleanUnit").header(), "demo.service", vec!["bogus".to_string()])
+            .await,
+        Err(BusError::ZBus(zbus::Error::FDO(e))) if matches!(*e, fdo::Error::InvalidArgs(_))
+    ));
+    assert!(matches!(
+        interface
+            .clean_unit(
+                test_call("CleanUnit").header(),
+                "definitely-missing.service",
+                vec!["all".to_string()]
+            )
+            .await,
+        Err(BusError::NoSuchUnit(_))
+    ));
+}
+
+#[tokio::test]
+async fn environment_methods_update_manager_block() {
+    let manager = Arc::new(RwLock::new(Manager::new_user()));
+    let states = manager.read().await.state_view();
+    let interface = ManagerInterface::new(Arc::clone(&manager), states);
+
+    interface
+        .set_environment(
+            test_call("SetEnvironment").header(),
+            vec![
+                "DISPLAY=:0".to_string(),
+                "XDG_SESSION_TYPE=wayland".to_string(),
+            ],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        interface.environment().await,
+        ["DISPLAY=:0", "XDG_SESSION_TYPE=wayland"]
+    );
+
+    interface
+        .unset_and_set_environment(
+            test_call("UnsetAndSetEnvironment").header(),
+            vec!["DISPLAY".to_string()],
+            vec!["LANG=C".to_string()],
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        interface.environment().await,
+        ["LANG=C", "XDG_SESSION_TYPE=wayland"]
+    );
+
+    assert!(matches!(
+        interface
+            .set_environment(
+                test_call("SetEnvironment").header(),
+                vec!["1BAD=x".to_string()]
+            )
+            .await,
+        Err(fdo::Error::InvalidArgs(_))
+    ));
+    interface
+        .unset_environment(
+            test_call("UnsetEnvironment").header(),
+            vec!["LANG".to_string()],
+        )
+        .await
+        .unwrap();
+    assert_eq!(interface.environment().await, ["XDG_SESSION_TYPE=wayland"]);
+}
+
+#[tokio::test]
+async fn list_units_and_unit_file_state_report_load_state() {
+    let root = temp_dir("file-state");
+    let mut manager = Manager::new_user();
+    manager.set_unit_root(&root.0);
+    let dir = manager.enable_dir();
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("demo.service"),
+        "[Unit]\nDescription=Demo\n[Service]\nExecStart=/bin/true\n",
+    )
+    .unwrap();
+    std::os::unix::fs::symlink("/dev/null", dir.join("masked.service")).unwrap();
+
+    let states = manager.state_view();
+    let manager = Arc::new(RwLock::new(manager));
+    let interface = ManagerInterface::new(Arc::clone(&manager), states);
+
+    assert_eq!(
+        interface.get_unit_file_state("demo.service").await.unwrap(),
+        "static"
+    );
+    assert_eq!(
+        interface
+            .get_unit_file_state("masked.service")
+            .await
+            .unwrap(),
+        "masked"
+    );
+    assert!(matches!(
+        interface.get_unit_file_state("missing.service").await,
+        Err(BusError::NoSuchUnit(_))
+    ));
+
+    manager.read().await.publish_states();
+    assert_eq!(
+        interface.get_unit("demo").await.unwrap().as_str(),
+        "/org/freedesktop/systemd1/unit/demo_2eservice"
+    );
+    assert!(matches!(
+        interface.get_unit("other.service").await,
+        Err(BusError::NoSuchUnit(_))
+    ));
+    std::fs::write(
+        dir.join("other.service"),
+        "[Service]\nExecStart=/bin/true\n",
+    )
+    .unwrap();
+    assert_eq!(
+        interface.load_unit("other").await.unwrap().as_str(),
+        "/org/freedesktop/systemd1/unit/other_2eservice"
+    );
+    assert!(interface.get_unit("other.service").await.is_ok());
+    let units = interface.list_units().await;
+    let demo = units.iter().find(|unit| unit.0 == "demo.service").unwrap();
+    assert_eq!(demo.1, "Demo");
+    assert_eq!(demo.2, "loaded");
+    assert_eq!(demo.3, "inactive");
+    assert_eq!(demo.4, "dead");
+
+    let matching = interface
+        .list_units_by_patterns(vec!["inactive".into()], vec!["dem*".into()])
+        .await;
+    let names: Vec<&str> = matching.iter().map(|unit| unit.0.as_str()).collect();
+    assert_eq!(names, ["demo.service"]);
+    assert!(interface
+        .list_units_by_patterns(vec!["failed".into()], vec!["dem*".into()])
+        .await
+        .is_empty());
+}
+
+#[test]
+fn set_unit_properties_values_map_to_resource_limits() {
+    assert_eq!(
+        parse_unit_property("MemoryMax", &OwnedValue::from(1024u64)).unwrap(),
+        UnitProperty::MemoryMax(Some(1024))
+    );
+    assert_eq!(
+        parse_unit_property("MemoryMax", &OwnedValue::from(u64::MAX)).unwrap(),
+        UnitProperty::MemoryMax(None)
+    );
+    assert_eq!(
+        parse_unit_property("CPUQuotaPerSecUSec", &OwnedValue::from(500_000u64)).unwrap(),
+        UnitProperty::CpuQuota(Some(50))
+    );
+    assert_eq!(
+        parse_unit_property("TasksMax", &OwnedValue::from(64u64)).unwrap(),
+        UnitProperty::TasksMax(Some(64))
+    );
+    assert!(matches!(
+        parse_unit_property("TasksMax", &string_value("64")),
+        Err(fdo::Error::InvalidArgs(_))
+    ));
+    assert!(matches!(
+        parse_unit_property("Nice", &OwnedValue::from(5u64)),
+        Err(fdo::Error::InvalidArgs(_))
+    ));
+}
+
+#[tokio::test]
+async fn default_target_can_be_set_and_read_back() {
+    let root = temp_dir("default-target");
+    let unit_dir = root.0.join("etc/systemd/user");
+    std::fs::create_dir_all(&unit_dir).unwrap();
+    std::fs::write(unit_dir.join("graphical.target"), "[Unit]\n").unwrap();
+    std::fs::write(
+        unit_dir.join("app.service"),
+        "[Service]\nExecStart=/bin/true\n",
+    )
+    .unwrap();
+    let mut manager = Manager::new_user();
+    manager.set_unit_root(&root.0);
+    let states = manager.state_view();
+    let interface = ManagerInterface::new(Arc::new(RwLock::new(manager)), states);
+
+    let changes = interface
+        .set_default_target(
+            test_call("SetDefaultTarget").header(),
+            "graphical.target",
+            false,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].0, "symlink");
+    assert!(changes[0].1.ends_with("/default.target"));
+    assert_eq!(
+        changes[0].2,
+        unit_dir.join("graphical.target").display().to_string()
+    );
+    assert_eq!(
+        interface.get_default_target().await.unwrap(),
+        "graphical.target"
+    );
+    assert!(interface
+        .set_default_target(test_call("SetDefaultTarget").header(), "app.service", false)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn shutdowns_are_scheduled_and_cancelled_like_with_logind() {
+    let mut manager = Manager::new();
+    manager.set_shutdown_allowed(true);
+    let states = manager.state_view();
+    let interface = ManagerInterface::new(Arc::new(RwLock::new(manager)), states);
+    let call = test_call("ScheduleShutdown");
+
+    assert!(matches!(
+        interface.schedule_shutdown(call.header(), "suspend", 0).await,
+        Err(BusError::ZBus(zbus::Error::FDO(e))) if matches!(*e, fdo::Error::InvalidArgs(_))
+    ));
+    let in_an_hour = std::time::SystemTime::now() + Duration::from_secs(3600);
+    let usec = in_an_hour.duration_since(UNIX_EPOCH).unwrap().as_micros() as u64;
+    interface
+        .schedule_shutdown(call.header(), "reboot", usec)
+        .await
+        .unwrap();
+    assert_eq!(
+        interface.scheduled_shutdown().await,
+        ("reboot".to_string(), usec)
+    );
+
+    let call = test_call("CancelScheduledShutdown");
+    assert!(interface
+        .cancel_scheduled_shutdown(call.header())
+        .await
+        .unwrap());
+    assert!(!interface
+        .cancel_scheduled_shutdown(call.header())
+        .await
+        .unwrap());
+    assert_eq!(interface.scheduled_shutdown().await, (String::new(), 0));
+}
+
+#[tokio::test]
+async fn unit_state_history_lists_transitions_of_known_units() {
+    let manager = Arc::new(RwLock::new(Manager::new_user()));
+    let states = manager.read().await.state_view();
+    let interface = ManagerInterface::new(Arc::clone(&manager), states);
+    register_scope_job(
+        Arc::clone(&manager),
+        "session-56.scope",
+        None,
+        None,
+        &[std::process::id()],
+    )
+    .await;
+
+    let history = interface
+        .get_unit_state_history("session-56.scope")
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 1);
+    let (usec, active, sub, reason) = &history[0];
+    assert!(*usec > 0);
+    assert_eq!((active.as_str(), sub.as_str()), ("active", "running"));
+    assert!(reason.is_empty());
+    assert!(matches!(
+        interface.get_unit_state_history("missing.service").await,
+        Err(BusError::NoSuchUnit(_))
+    ));
+}
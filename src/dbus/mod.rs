@@ -4,14 +4,26 @@
 //!
 //! Key interfaces:
 //! - Manager: StartUnit, StopUnit, StartTransientUnit, etc.
-//! - Unit: ActiveState, SubState properties
+//! - Unit: ActiveState, SubState, LoadState, ... of every loaded unit
 //! - Scope: Abandon method
+//! - machine1 Manager: RegisterMachine, TerminateMachine, ListMachines
+//! - login1 Manager: sessions, seat0 and idle hints where logind is absent
+//!
+//! Methods that change state are authorized through polkit (see `polkit`).
 
+mod error;
+pub mod login;
+pub mod machine;
 mod manager;
+pub mod polkit;
 pub mod scope;
 pub mod unit;
 
+pub use error::BusError;
+pub use login::LoginManagerInterface;
+pub use machine::MachineManagerInterface;
 pub use manager::ManagerInterface;
+pub use polkit::Authorizer;
 pub use scope::ScopeInterface;
 pub use unit::UnitInterface;
 
@@ -34,19 +46,55 @@ impl DbusServer {
 
     /// Start the D-Bus server on the system bus
     pub async fn new_system(manager: Arc<RwLock<Manager>>) -> zbus::Result<Self> {
-        let manager_iface = ManagerInterface::new(manager.clone());
+        let states = manager.read().await.state_view();
+        let authorizer = Authorizer::new();
+        let manager_iface =
+            ManagerInterface::new(manager.clone(), states).with_authorizer(authorizer.clone());
+        let unit_objects = manager_iface.unit_objects();
+        let machine_iface =
+            MachineManagerInterface::new(manager.clone()).with_authorizer(authorizer.clone());
+        let machines = machine_iface.machines();
+        let login_iface =
+            LoginManagerInterface::new(manager.clone()).with_authorizer(authorizer.clone());
+        let logins = login_iface.logins();
+        let logind_installed = manager
+            .read()
+            .await
+            .unit_file_exists("systemd-logind.service");
 
-        let connection = Builder::system()?
-            .name("org.freedesktop.systemd1")?
-            .serve_at("/org/freedesktop/systemd1", manager_iface)?
-            .build()
+        let connection = Builder::system()?.build().await?;
+        // Only take calls once they can be authorized
+        authorizer.attach(&connection);
+        let server = connection.object_server();
+        server
+            .at("/org/freedesktop/systemd1", manager_iface)
             .await?;
+        server
+            .at("/org/freedesktop/machine1", machine_iface)
+            .await?;
+        server.at(login::LOGIN1_PATH, login_iface).await?;
+        connection.request_name("org.freedesktop.systemd1").await?;
+
+        // Coexist with systemd-machined: only claim machine1 if it is free
+        if let Err(e) = connection.request_name("org.freedesktop.machine1").await {
+            log::info!("Not providing org.freedesktop.machine1: {}", e);
+        }
+        // Only stand in for systemd-logind where it is not installed
+        if logind_installed {
+            log::info!("Not providing org.freedesktop.login1: systemd-logind is installed");
+        } else if let Err(e) = connection.request_name("org.freedesktop.login1").await {
+            log::info!("Not providing org.freedesktop.login1: {}", e);
+        }
 
         // Set the D-Bus connection on the Manager for scope registration
         {
             let mut mgr = manager.write().await;
             mgr.set_dbus_connection(connection.clone());
+            mgr.register_scope_dbus_objects().await;
         }
+        unit_objects.serve(connection.clone());
+        machines.serve(connection.clone());
+        logins.serve(connection.clone());
 
         Ok(Self { connection })
     }
@@ -56,19 +104,27 @@ impl DbusServer {
     /// In user mode, we connect to the session bus and provide the same
     /// org.freedesktop.systemd1 interface that user-level tools expect.
     pub async fn new_session(manager: Arc<RwLock<Manager>>) -> zbus::Result<Self> {
-        let manager_iface = ManagerInterface::new(manager.clone());
+        let states = manager.read().await.state_view();
+        let authorizer = Authorizer::new();
+        let manager_iface =
+            ManagerInterface::new(manager.clone(), states).with_authorizer(authorizer.clone());
+        let unit_objects = manager_iface.unit_objects();
 
-        let connection = Builder::session()?
-            .name("org.freedesktop.systemd1")?
-            .serve_at("/org/freedesktop/systemd1", manager_iface)?
-            .build()
+        let connection = Builder::session()?.build().await?;
+        authorizer.attach(&connection);
+        connection
+            .object_server()
+            .at("/org/freedesktop/systemd1", manager_iface)
             .await?;
+        connection.request_name("org.freedesktop.systemd1").await?;
 
         // Set the D-Bus connection on the Manager
         {
             let mut mgr = manager.write().await;
             mgr.set_dbus_connection(connection.clone());
+            mgr.register_scope_dbus_objects().await;
         }
+        unit_objects.serve(connection.clone());
 
         Ok(Self { connection })
     }
@@ -132,6 +188,16 @@ pub fn unit_object_path(unit_id: &str) -> String {
     format!("/org/freedesktop/systemd1/unit/{}", escaped)
 }
 
+/// A method call as it arrives from the bus, for calling interface methods
+/// directly in tests
+#[cfg(test)]
+fn test_call(member: &str) -> zbus::Message {
+    zbus::Message::method_call("/org/freedesktop/systemd1", member)
+        .unwrap()
+        .build(&())
+        .unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,252 @@
+//! polkit authorization of privileged method calls
+//!
+//! Methods that change state (starting and stopping units, the manager
+//! environment, machines, ...) ask polkit whether the caller may perform the
+//! action systemd guards them with, e.g. org.freedesktop.systemd1.manage-units,
+//! so existing polkit rules keep working. root and the user the manager runs
+//! as are always allowed, which is all a user manager on the session bus
+//! sees. Without polkit on the bus, nobody else is.
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use zbus::message::{Flags, Header};
+use zbus::names::UniqueName;
+use zbus::zvariant::Value;
+use zbus::{fdo, Connection};
+
+/// Start, stop, kill, clean and reset units, change their properties
+pub const MANAGE_UNITS: &str = "org.freedesktop.systemd1.manage-units";
+/// Change unit files (SetDefaultTarget)
+pub const MANAGE_UNIT_FILES: &str = "org.freedesktop.systemd1.manage-unit-files";
+/// Reload the manager
+pub const RELOAD_DAEMON: &str = "org.freedesktop.systemd1.reload-daemon";
+/// Change the manager environment
+pub const SET_ENVIRONMENT: &str = "org.freedesktop.systemd1.set-environment";
+/// Register a machine
+pub const CREATE_MACHINE: &str = "org.freedesktop.machine1.create-machine";
+/// Terminate a machine
+pub const MANAGE_MACHINES: &str = "org.freedesktop.machine1.manage-machines";
+/// Schedule a poweroff, reboot or halt (logind's actions, which guard
+/// ScheduleShutdown there)
+pub const POWER_OFF: &str = "org.freedesktop.login1.power-off";
+pub const REBOOT: &str = "org.freedesktop.login1.reboot";
+pub const HALT: &str = "org.freedesktop.login1.halt";
+/// Terminate login sessions
+pub const MANAGE_SESSIONS: &str = "org.freedesktop.login1.manage";
+/// Suspend, or hibernate (and hybrid-sleep), through login1
+pub const SUSPEND: &str = "org.freedesktop.login1.suspend";
+pub const HIBERNATE: &str = "org.freedesktop.login1.hibernate";
+
+const POLKIT_NAME: &str = "org.freedesktop.PolicyKit1";
+const POLKIT_PATH: &str = "/org/freedesktop/PolicyKit1/Authority";
+const POLKIT_INTERFACE: &str = "org.freedesktop.PolicyKit1.Authority";
+/// CheckAuthorization flag letting polkit ask the user for a password
+const ALLOW_USER_INTERACTION: u32 = 1;
+
+/// Decides whether the sender of a method call may perform an action
+///
+/// Interfaces get one before the connection they are served on exists; the
+/// connection is attached before they are served on it, so every call
+/// arriving over the bus is checked. A call with a sender but no connection
+/// to ask about it is refused; calls made in-process (tests) have neither
+/// and are the manager's own.
+#[derive(Clone, Default)]
+pub struct Authorizer {
+    connection: Arc<OnceLock<Connection>>,
+}
+
+impl Authorizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An authorizer for calls arriving on `connection`
+    pub fn attached(connection: &Connection) -> Self {
+        let authorizer = Self::new();
+        authorizer.attach(connection);
+        authorizer
+    }
+
+    /// Check calls against the bus `connection` is on
+    pub fn attach(&self, connection: &Connection) {
+        let _ = self.connection.set(connection.clone());
+    }
+
+    /// The bus connection and sender of the call `header` belongs to; None
+    /// for in-process calls
+    fn sender<'h>(
+        &self,
+        header: &'h Header<'_>,
+    ) -> fdo::Result<Option<(&Connection, &'h UniqueName<'h>)>> {
+        match (self.connection.get(), header.sender()) {
+            (None, None) => Ok(None),
+            (Some(connection), Some(sender)) => Ok(Some((connection, sender))),
+            (None, Some(_)) => Err(fdo::Error::AccessDenied(
+                "Cannot identify the caller before the bus is connected".to_string(),
+            )),
+            (Some(_), None) => Err(fdo::Error::AccessDenied("Call has no sender".to_string())),
+        }
+    }
+
+    /// uid of the sender of the call `header` belongs to; in-process calls
+    /// are the manager's own
+    pub async fn caller_uid(&self, header: &Header<'_>) -> fdo::Result<u32> {
+        let Some((connection, sender)) = self.sender(header)? else {
+            return Ok(nix::unistd::geteuid().as_raw());
+        };
+        Ok(fdo::DBusProxy::new(connection)
+            .await?
+            .get_connection_unix_user(sender.clone().into())
+            .await?)
+    }
+
+    /// pid of the sender of the call `header` belongs to; in-process calls
+    /// are the manager's own
+    pub async fn caller_pid(&self, header: &Header<'_>) -> fdo::Result<u32> {
+        let Some((connection, sender)) = self.sender(header)? else {
+            return Ok(std::process::id());
+        };
+        Ok(fdo::DBusProxy::new(connection)
+            .await?
+            .get_connection_unix_process_id(sender.clone().into())
+            .await?)
+    }
+
+    /// Fail unless the sender of the call `header` belongs to may perform
+    /// `action`; in-process calls are the manager's own and may
+    pub async fn authorize(&self, header: &Header<'_>, action: &str) -> fdo::Result<()> {
+        let Some((connection, sender)) = self.sender(header)? else {
+            return Ok(());
+        };
+        let uid = self.caller_uid(header).await?;
+        if uid == 0 || uid == nix::unistd::geteuid().as_raw() {
+            return Ok(());
+        }
+        let interactive = header
+            .primary()
+            .flags()
+            .contains(Flags::AllowInteractiveAuth);
+        let reply = check_authorization(connection, sender.as_str(), action, interactive).await;
+        authorization_result(action, reply)
+    }
+}
+
+/// polkit's verdict: (authorized, the user could authenticate to be)
+type Verdict = (bool, bool, HashMap<String, String>);
+
+async fn check_authorization(
+    connection: &Connection,
+    sender: &str,
+    action: &str,
+    interactive: bool,
+) -> zbus::Result<Verdict> {
+    let subject = (
+        "system-bus-name",
+        HashMap::from([("name", Value::from(sender))]),
+    );
+    let details: HashMap<&str, &str> = HashMap::new();
+    let flags = if interactive {
+        ALLOW_USER_INTERACTION
+    } else {
+        0
+    };
+    let reply = connection
+        .call_method(
+            Some(POLKIT_NAME),
+            POLKIT_PATH,
+            Some(POLKIT_INTERFACE),
+            "CheckAuthorization",
+            &(subject, action, details, flags, ""),
+        )
+        .await?;
+    reply.body().deserialize()
+}
+
+fn polkit_absent(error: &zbus::Error) -> bool {
+    matches!(
+        error,
+        zbus::Error::MethodError(name, _, _)
+            if name.as_str() == "org.freedesktop.DBus.Error.ServiceUnknown"
+                || name.as_str() == "org.freedesktop.DBus.Error.NameHasNoOwner"
+    )
+}
+
+fn authorization_result(action: &str, reply: zbus::Result<Verdict>) -> fdo::Result<()> {
+    match reply {
+        Ok((true, _, _)) => Ok(()),
+        // systemctl retries these with a polkit agent running
+        Ok((false, true, _)) => Err(fdo::Error::InteractiveAuthorizationRequired(format!(
+            "Interactive authentication required for {}",
+            action
+        ))),
+        Ok((false, false, _)) => Err(fdo::Error::AccessDenied(format!(
+            "Not authorized for {}",
+            action
+        ))),
+        Err(e) if polkit_absent(&e) => Err(fdo::Error::AccessDenied(format!(
+            "Not authorized for {} (polkit is not available, only root is)",
+            action
+        ))),
+        Err(e) => Err(fdo::Error::AccessDenied(format!(
+            "Checking authorization for {} failed: {}",
+            action, e
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dbus::test_call;
+
+    #[test]
+    fn polkit_verdicts_become_access_errors() {
+        let verdict = |authorized, challenge| Ok((authorized, challenge, HashMap::new()));
+        assert_eq!(
+            authorization_result(MANAGE_UNITS, verdict(true, false)),
+            Ok(())
+        );
+        assert!(matches!(
+            authorization_result(MANAGE_UNITS, verdict(false, true)),
+            Err(fdo::Error::InteractiveAuthorizationRequired(_))
+        ));
+        assert!(matches!(
+            authorization_result(MANAGE_UNITS, verdict(false, false)),
+            Err(fdo::Error::AccessDenied(message)) if message.contains("manage-units")
+        ));
+        assert!(matches!(
+            authorization_result(
+                RELOAD_DAEMON,
+                Err(zbus::Error::Failure("no bus".to_string()))
+            ),
+            Err(fdo::Error::AccessDenied(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn calls_made_in_process_are_not_checked() {
+        let message = test_call("StopUnit");
+        let authorizer = Authorizer::new();
+        assert_eq!(
+            authorizer.authorize(&message.header(), MANAGE_UNITS).await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test]
+    async fn bus_calls_are_refused_until_a_connection_is_attached() {
+        let message = zbus::Message::method_call("/org/freedesktop/systemd1", "StopUnit")
+            .unwrap()
+            .sender(":1.42")
+            .unwrap()
+            .build(&())
+            .unwrap();
+        let authorizer = Authorizer::new();
+        assert!(matches!(
+            authorizer.authorize(&message.header(), MANAGE_UNITS).await,
+            Err(fdo::Error::AccessDenied(_))
+        ));
+        assert!(authorizer.caller_uid(&message.header()).await.is_err());
+    }
+}
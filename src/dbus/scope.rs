@@ -8,8 +8,9 @@
 
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use zbus::{fdo, interface};
+use zbus::{fdo, interface, message::Header};
 
+use super::polkit::{Authorizer, MANAGE_UNITS};
 use crate::cgroups::CgroupManager;
 
 /// State for a scope unit
@@ -22,6 +23,7 @@ pub struct ScopeState {
 pub struct ScopeInterface {
     state: Arc<RwLock<ScopeState>>,
     _cgroup_manager: Arc<CgroupManager>,
+    authorizer: Authorizer,
 }
 
 impl ScopeInterface {
@@ -29,8 +31,15 @@ impl ScopeInterface {
         Self {
             state,
             _cgroup_manager: cgroup_manager,
+            authorizer: Authorizer::new(),
         }
     }
+
+    /// Check calls with `authorizer` rather than letting everyone through
+    pub fn with_authorizer(mut self, authorizer: Authorizer) -> Self {
+        self.authorizer = authorizer;
+        self
+    }
 }
 
 #[interface(name = "org.freedesktop.systemd1.Scope")]
@@ -41,7 +50,8 @@ impl ScopeInterface {
     /// - We stop monitoring the cgroup
     /// - The scope will be cleaned up when empty
     /// - logind calls this when a session ends
-    async fn abandon(&self) -> fdo::Result<()> {
+    async fn abandon(&self, #[zbus(header)] header: Header<'_>) -> fdo::Result<()> {
+        self.authorizer.authorize(&header, MANAGE_UNITS).await?;
         let mut state = self.state.write().await;
 
         if state.abandoned {
@@ -67,6 +77,7 @@ impl ScopeInterface {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dbus::test_call;
 
     #[tokio::test]
     async fn scope_interface_abandon_is_idempotent_and_reports_controller() {
@@ -78,9 +89,9 @@ mod tests {
         let scope = ScopeInterface::new(state.clone(), Arc::new(CgroupManager::default()));
 
         assert_eq!(scope.controller().await, "scope");
-        scope.abandon().await.unwrap();
+        scope.abandon(test_call("Abandon").header()).await.unwrap();
         assert!(state.read().await.abandoned);
-        scope.abandon().await.unwrap();
+        scope.abandon(test_call("Abandon").header()).await.unwrap();
         assert!(state.read().await.abandoned);
     }
 }
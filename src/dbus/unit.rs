@@ -2,26 +2,77 @@
 //!
 //! Properties that logind queries:
 //! - ActiveState: "active", "inactive", "failed", etc.
+//! - NeedDaemonReload: unit file changed on disk since it was loaded
+//!
+//! LoadState, UnitFileState, FragmentPath, Documentation and Names are read by
+//! frontends such as cockpit.
+//!
+//! Every loaded unit gets an object (see `UnitObjects`) that reads the
+//! states the manager last published, so it follows state changes and
+//! daemon-reloads without being updated. Transient scopes keep their own
+//! `UnitState`, registered by the scope manager.
 
+use std::collections::HashSet;
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use zbus::interface;
+use tokio::sync::{Mutex, RwLock};
+use zbus::{interface, zvariant::OwnedObjectPath, Connection};
+
+use super::make_object_path;
+use crate::manager::{Manager, StateView, UnitSnapshot};
 
 /// Runtime state for a unit's D-Bus interface
+#[derive(Debug, Clone)]
 pub struct UnitState {
     pub name: String,
     pub description: String,
     pub active_state: String,
     pub sub_state: String,
+    pub need_daemon_reload: bool,
+    pub load_state: String,
+    pub unit_file_state: String,
+    pub fragment_path: String,
+    pub documentation: Vec<String>,
+    /// Every name the unit is known by, its id first
+    pub names: Vec<String>,
+    /// False when the last start was skipped for an unmet condition
+    pub condition_result: bool,
 }
 
 impl UnitState {
     pub fn new(name: String, description: String) -> Self {
         Self {
+            names: vec![name.clone()],
             name,
             description,
             active_state: "inactive".into(),
             sub_state: "dead".into(),
+            need_daemon_reload: false,
+            load_state: "loaded".into(),
+            unit_file_state: String::new(),
+            fragment_path: String::new(),
+            documentation: Vec::new(),
+            condition_result: true,
+        }
+    }
+
+    /// State of the loaded unit `name` as published by the manager
+    /// (UnitFileState is looked up separately, it needs the unit files)
+    pub fn from_snapshot(name: &str, unit: UnitSnapshot) -> Self {
+        Self {
+            name: name.to_string(),
+            description: unit.description.unwrap_or_else(|| name.to_string()),
+            active_state: unit.active.as_str().into(),
+            sub_state: unit.sub.as_str().into(),
+            need_daemon_reload: unit.need_daemon_reload,
+            load_state: unit.load_state.into(),
+            unit_file_state: String::new(),
+            fragment_path: unit
+                .fragment_path
+                .map(|path| path.display().to_string())
+                .unwrap_or_default(),
+            documentation: unit.documentation,
+            names: unit.names,
+            condition_result: unit.condition_failure.is_none(),
         }
     }
 
@@ -41,13 +92,162 @@ impl UnitState {
     }
 }
 
+/// Where a Unit object takes its properties from
+enum Source {
+    /// State kept by the owner of the object (transient scopes)
+    Owned(Arc<RwLock<UnitState>>),
+    /// The states the manager publishes, for a loaded unit
+    Published {
+        name: String,
+        states: StateView,
+        manager: Arc<RwLock<Manager>>,
+    },
+}
+
 pub struct UnitInterface {
-    state: Arc<RwLock<UnitState>>,
+    source: Source,
 }
 
 impl UnitInterface {
     pub fn new(state: Arc<RwLock<UnitState>>) -> Self {
-        Self { state }
+        Self {
+            source: Source::Owned(state),
+        }
+    }
+
+    /// Object of the loaded unit `name`, reading what `states` publishes
+    pub fn published(name: &str, states: StateView, manager: Arc<RwLock<Manager>>) -> Self {
+        Self {
+            source: Source::Published {
+                name: name.to_string(),
+                states,
+                manager,
+            },
+        }
+    }
+
+    async fn state(&self) -> UnitState {
+        match &self.source {
+            Source::Owned(state) => state.read().await.clone(),
+            Source::Published { name, states, .. } => match states.get(name) {
+                Some(unit) => UnitState::from_snapshot(name, unit),
+                None => UnitState {
+                    load_state: "not-found".into(),
+                    ..UnitState::new(name.clone(), name.clone())
+                },
+            },
+        }
+    }
+}
+
+/// The Unit objects of the loaded units, kept in line with the states the
+/// manager publishes: objects are added for units that get loaded and
+/// removed for those a daemon-reload dropped
+#[derive(Clone)]
+pub struct UnitObjects {
+    manager: Arc<RwLock<Manager>>,
+    states: StateView,
+    served: Arc<Mutex<Served>>,
+}
+
+#[derive(Default)]
+struct Served {
+    /// None until the bus is connected
+    connection: Option<Connection>,
+    names: HashSet<String>,
+}
+
+impl UnitObjects {
+    pub fn new(manager: Arc<RwLock<Manager>>, states: StateView) -> Self {
+        Self {
+            manager,
+            states,
+            served: Arc::default(),
+        }
+    }
+
+    /// Serve the objects on `conn` for as long as the manager publishes states
+    pub fn serve(&self, conn: Connection) {
+        let objects = self.clone();
+        let mut states = self.states.clone();
+        tokio::spawn(async move {
+            objects.served.lock().await.connection = Some(conn);
+            loop {
+                objects.sync().await;
+                if !states.changed().await {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// Object path of the loaded unit `name` (an alias or a name without a
+    /// suffix also do), registering its object if the last sync has not yet;
+    /// None for units that are not loaded
+    pub async fn path(&self, name: &str) -> Option<OwnedObjectPath> {
+        let unit = self.states.get(name)?;
+        let name = unit.names.first()?;
+        // Scopes are served by the scope manager
+        if unit.unit_type != "scope" && !self.serve_unit(name).await {
+            return None;
+        }
+        Some(make_object_path(name).into())
+    }
+
+    async fn serve_unit(&self, name: &str) -> bool {
+        let mut served = self.served.lock().await;
+        let Some(conn) = served.connection.clone() else {
+            // No bus to serve on, the path is where the object will be
+            return true;
+        };
+        if served.names.contains(name) {
+            return true;
+        }
+        let iface = UnitInterface::published(name, self.states.clone(), Arc::clone(&self.manager));
+        match conn.object_server().at(make_object_path(name), iface).await {
+            Ok(_) => {
+                served.names.insert(name.to_string());
+                true
+            }
+            Err(e) => {
+                log::warn!("Failed to register D-Bus object for {}: {}", name, e);
+                false
+            }
+        }
+    }
+
+    /// Bring the objects in line with the published units
+    async fn sync(&self) {
+        let mut served = self.served.lock().await;
+        let Some(conn) = served.connection.clone() else {
+            return;
+        };
+        let loaded: HashSet<String> = self
+            .states
+            .list()
+            .into_iter()
+            .filter(|(_, unit)| unit.unit_type != "scope")
+            .map(|(name, _)| name)
+            .collect();
+        let server = conn.object_server();
+        for name in served.names.difference(&loaded) {
+            let _ = server
+                .remove::<UnitInterface, _>(make_object_path(name))
+                .await;
+        }
+        let mut unserved = Vec::new();
+        for name in loaded.difference(&served.names) {
+            let iface =
+                UnitInterface::published(name, self.states.clone(), Arc::clone(&self.manager));
+            if let Err(e) = server.at(make_object_path(name), iface).await {
+                log::warn!("Failed to register D-Bus object for {}: {}", name, e);
+                unserved.push(name.clone());
+            }
+        }
+        served.names = loaded;
+        for name in unserved {
+            served.names.remove(&name);
+        }
     }
 }
 
@@ -56,38 +256,84 @@ impl UnitInterface {
     /// Unit identifier (e.g., "docker.service")
     #[zbus(property)]
     async fn id(&self) -> String {
-        self.state.read().await.name.clone()
+        self.state().await.name
     }
 
     /// Human-readable description
     #[zbus(property)]
     async fn description(&self) -> String {
-        self.state.read().await.description.clone()
+        self.state().await.description
     }
 
     /// High-level state: "active", "inactive", "activating", "deactivating", "failed"
     /// This is what logind checks to see if a scope is running
     #[zbus(property)]
     async fn active_state(&self) -> String {
-        self.state.read().await.active_state.clone()
+        self.state().await.active_state
     }
 
     /// More detailed state: "running", "dead", "failed", "waiting", etc.
     #[zbus(property)]
     async fn sub_state(&self) -> String {
-        self.state.read().await.sub_state.clone()
+        self.state().await.sub_state
     }
 
-    /// Load state: "loaded", "not-found", "error", etc.
+    /// Load state: "loaded", "not-found", "masked", "error", etc.
     #[zbus(property)]
     async fn load_state(&self) -> String {
-        "loaded".to_string()
+        self.state().await.load_state
+    }
+
+    /// Unit file state: "enabled", "disabled", "static", "masked", "linked",
+    /// "transient", etc.
+    #[zbus(property)]
+    async fn unit_file_state(&self) -> String {
+        match &self.source {
+            Source::Owned(state) => state.read().await.unit_file_state.clone(),
+            Source::Published { name, manager, .. } => manager
+                .read()
+                .await
+                .unit_file_state(name)
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+
+    /// Unit file the unit was loaded from, empty for transient units
+    #[zbus(property)]
+    async fn fragment_path(&self) -> String {
+        self.state().await.fragment_path
+    }
+
+    /// Documentation= URIs
+    #[zbus(property)]
+    async fn documentation(&self) -> Vec<String> {
+        self.state().await.documentation
+    }
+
+    /// Id followed by the unit's aliases
+    #[zbus(property)]
+    async fn names(&self) -> Vec<String> {
+        self.state().await.names
+    }
+
+    /// False if the last start was skipped because a Condition*= was not met
+    #[zbus(property)]
+    async fn condition_result(&self) -> bool {
+        self.state().await.condition_result
+    }
+
+    /// Whether the unit file changed on disk since it was loaded
+    #[zbus(property)]
+    async fn need_daemon_reload(&self) -> bool {
+        self.state().await.need_daemon_reload
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::manager::UnitFilesChanged;
 
     #[tokio::test]
     async fn unit_state_transitions_and_interface_properties_are_reported() {
@@ -102,6 +348,11 @@ mod tests {
         assert_eq!(interface.active_state().await, "inactive");
         assert_eq!(interface.sub_state().await, "dead");
         assert_eq!(interface.load_state().await, "loaded");
+        assert_eq!(interface.unit_file_state().await, "");
+        assert_eq!(interface.fragment_path().await, "");
+        assert!(interface.documentation().await.is_empty());
+        assert_eq!(interface.names().await, ["demo.service"]);
+        assert!(!interface.need_daemon_reload().await);
 
         state.write().await.set_active();
         assert_eq!(interface.active_state().await, "active");
@@ -115,4 +366,114 @@ mod tests {
         assert_eq!(interface.active_state().await, "inactive");
         assert_eq!(interface.sub_state().await, "dead");
     }
+
+    #[tokio::test]
+    async fn loaded_units_report_what_the_manager_published() {
+        let root = std::env::temp_dir().join(format!("sysd-dbus-unit-{}", std::process::id()));
+        let mut manager = Manager::new_user();
+        manager.set_unit_root(&root);
+        let dir = manager.enable_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("demo.service");
+        std::fs::write(
+            &file,
+            "[Unit]\nDescription=Demo\nDocumentation=man:demo(8)\n[Service]\nExecStart=/bin/true\n",
+        )
+        .unwrap();
+        manager.load("demo").await.unwrap();
+        manager.publish_states();
+        let states = manager.state_view();
+        let manager = Arc::new(RwLock::new(manager));
+        let interface = UnitInterface::published("demo.service", states, Arc::clone(&manager));
+
+        assert_eq!(interface.id().await, "demo.service");
+        assert_eq!(interface.description().await, "Demo");
+        assert_eq!(interface.active_state().await, "inactive");
+        assert_eq!(interface.sub_state().await, "dead");
+        assert_eq!(interface.load_state().await, "loaded");
+        assert_eq!(interface.unit_file_state().await, "static");
+        assert_eq!(interface.fragment_path().await, file.display().to_string());
+        assert_eq!(interface.documentation().await, ["man:demo(8)"]);
+        assert_eq!(interface.names().await, ["demo.service"]);
+        assert!(interface.condition_result().await);
+        assert!(!interface.need_daemon_reload().await);
+
+        std::fs::write(
+            &file,
+            "[Unit]\nDescription=Reloaded\n[Service]\nExecStart=/bin/true\n",
+        )
+        .unwrap();
+        {
+            let mut manager = manager.write().await;
+            manager.reload_units().await.unwrap();
+            manager.publish_states();
+        }
+        assert_eq!(interface.description().await, "Reloaded");
+        assert!(interface.documentation().await.is_empty());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn condition_result_is_false_after_a_start_skipped_by_a_condition() {
+        let root = std::env::temp_dir().join(format!("sysd-dbus-cond-{}", std::process::id()));
+        let mut manager = Manager::new_user();
+        manager.set_unit_root(&root);
+        let dir = manager.enable_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("cond.service"),
+            "[Unit]\nConditionPathExists=/nonexistent/sysd\n[Service]\nExecStart=/bin/true\n",
+        )
+        .unwrap();
+        manager.load("cond").await.unwrap();
+        manager.publish_states();
+        let states = manager.state_view();
+        let manager = Arc::new(RwLock::new(manager));
+        let interface = UnitInterface::published("cond.service", states, Arc::clone(&manager));
+        assert!(interface.condition_result().await);
+
+        {
+            let mut manager = manager.write().await;
+            manager.start("cond").await.unwrap();
+            manager.publish_states();
+        }
+        assert!(!interface.condition_result().await);
+        assert_eq!(interface.active_state().await, "inactive");
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[tokio::test]
+    async fn need_daemon_reload_follows_the_unit_file_watcher() {
+        let root = std::env::temp_dir().join(format!("sysd-dbus-reload-{}", std::process::id()));
+        let mut manager = Manager::new_user();
+        manager.set_unit_root(&root);
+        let dir = manager.enable_dir();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("demo.service"), "[Service]\nExecStart=/bin/true\n").unwrap();
+        manager.load("demo").await.unwrap();
+        manager.publish_states();
+        let states = manager.state_view();
+        let manager = Arc::new(RwLock::new(manager));
+        let interface = UnitInterface::published("demo.service", states, Arc::clone(&manager));
+        assert!(!interface.need_daemon_reload().await);
+
+        {
+            let mut manager = manager.write().await;
+            let changed = UnitFilesChanged {
+                units: vec!["demo.service".to_string()],
+            };
+            manager.handle_unit_files_changed(changed).await.unwrap();
+            manager.publish_states();
+            assert!(manager.needs_daemon_reload("demo.service"));
+        }
+        assert!(interface.need_daemon_reload().await);
+
+        {
+            let mut manager = manager.write().await;
+            manager.reload_units().await.unwrap();
+            manager.publish_states();
+        }
+        assert!(!interface.need_daemon_reload().await);
+        let _ = std::fs::remove_dir_all(&root);
+    }
 }
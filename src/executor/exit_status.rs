@@ -0,0 +1,177 @@
+//! Exit statuses of failed spawns
+//!
+//! When setting up a service process fails before its own program runs,
+//! sysd-executor exits with the status systemd uses for the failing step
+//! (200/CHDIR, 203/EXEC, 226/NAMESPACE, ...), so a failed unit tells which
+//! step broke and scripts written against systemd see the same numbers.
+//! The table also names the generic libc, LSB and BSD statuses for
+//! `sysd exit-status`.
+
+/// The working directory could not be entered
+pub const EXIT_CHDIR: i32 = 200;
+/// Socket activation file descriptors could not be passed
+pub const EXIT_FDS: i32 = 202;
+/// The program could not be executed
+pub const EXIT_EXEC: i32 = 203;
+/// A resource limit could not be set
+pub const EXIT_LIMITS: i32 = 205;
+/// OOMScoreAdjust= could not be applied
+pub const EXIT_OOM_ADJUST: i32 = 206;
+/// The signal mask or dispositions could not be set
+pub const EXIT_SIGNAL_MASK: i32 = 207;
+/// Standard input (the TTY) could not be set up
+pub const EXIT_STDIN: i32 = 208;
+/// SecureBits= could not be applied
+pub const EXIT_SECUREBITS: i32 = 213;
+/// The group could not be changed
+pub const EXIT_GROUP: i32 = 216;
+/// The user could not be changed
+pub const EXIT_USER: i32 = 217;
+/// Capabilities could not be dropped or raised
+pub const EXIT_CAPABILITIES: i32 = 218;
+/// The network namespace could not be set up
+pub const EXIT_NETWORK: i32 = 225;
+/// The mount namespace could not be set up
+pub const EXIT_NAMESPACE: i32 = 226;
+/// NoNewPrivileges= could not be applied
+pub const EXIT_NO_NEW_PRIVILEGES: i32 = 227;
+/// The seccomp filter (or MemoryDenyWriteExecute=) could not be applied
+pub const EXIT_SECCOMP: i32 = 228;
+/// SELinuxContext= could not be applied
+pub const EXIT_SELINUX_CONTEXT: i32 = 229;
+/// AppArmorProfile= could not be applied
+pub const EXIT_APPARMOR_PROFILE: i32 = 231;
+/// SmackProcessLabel= could not be applied
+pub const EXIT_SMACK_PROCESS_LABEL: i32 = 236;
+/// The session keyring could not be set up
+pub const EXIT_KEYRING: i32 = 237;
+
+/// A named exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatusEntry {
+    pub code: i32,
+    pub name: &'static str,
+    /// Where the status comes from: "libc", "LSB", "BSD" or "systemd"
+    pub class: &'static str,
+}
+
+const fn entry(code: i32, name: &'static str, class: &'static str) -> ExitStatusEntry {
+    ExitStatusEntry { code, name, class }
+}
+
+/// Every named exit status, in numeric order
+pub const EXIT_STATUSES: &[ExitStatusEntry] = &[
+    entry(0, "SUCCESS", "libc"),
+    entry(1, "FAILURE", "libc"),
+    entry(2, "INVALIDARGUMENT", "LSB"),
+    entry(3, "NOTIMPLEMENTED", "LSB"),
+    entry(4, "NOPERMISSION", "LSB"),
+    entry(5, "NOTINSTALLED", "LSB"),
+    entry(6, "NOTCONFIGURED", "LSB"),
+    entry(7, "NOTRUNNING", "LSB"),
+    entry(64, "USAGE", "BSD"),
+    entry(65, "DATAERR", "BSD"),
+    entry(66, "NOINPUT", "BSD"),
+    entry(67, "NOUSER", "BSD"),
+    entry(68, "NOHOST", "BSD"),
+    entry(69, "UNAVAILABLE", "BSD"),
+    entry(70, "SOFTWARE", "BSD"),
+    entry(71, "OSERR", "BSD"),
+    entry(72, "OSFILE", "BSD"),
+    entry(73, "CANTCREAT", "BSD"),
+    entry(74, "IOERR", "BSD"),
+    entry(75, "TEMPFAIL", "BSD"),
+    entry(76, "PROTOCOL", "BSD"),
+    entry(77, "NOPERM", "BSD"),
+    entry(78, "CONFIG", "BSD"),
+    entry(EXIT_CHDIR, "CHDIR", "systemd"),
+    entry(201, "NICE", "systemd"),
+    entry(EXIT_FDS, "FDS", "systemd"),
+    entry(EXIT_EXEC, "EXEC", "systemd"),
+    entry(204, "MEMORY", "systemd"),
+    entry(EXIT_LIMITS, "LIMITS", "systemd"),
+    entry(EXIT_OOM_ADJUST, "OOM_ADJUST", "systemd"),
+    entry(EXIT_SIGNAL_MASK, "SIGNAL_MASK", "systemd"),
+    entry(EXIT_STDIN, "STDIN", "systemd"),
+    entry(209, "STDOUT", "systemd"),
+    entry(210, "CHROOT", "systemd"),
+    entry(211, "IOPRIO", "systemd"),
+    entry(212, "TIMERSLACK", "systemd"),
+    entry(EXIT_SECUREBITS, "SECUREBITS", "systemd"),
+    entry(214, "SETSCHEDULER", "systemd"),
+    entry(215, "CPUAFFINITY", "systemd"),
+    entry(EXIT_GROUP, "GROUP", "systemd"),
+    entry(EXIT_USER, "USER", "systemd"),
+    entry(EXIT_CAPABILITIES, "CAPABILITIES", "systemd"),
+    entry(219, "CGROUP", "systemd"),
+    entry(220, "SETSID", "systemd"),
+    entry(221, "CONFIRM", "systemd"),
+    entry(222, "STDERR", "systemd"),
+    entry(224, "PAM", "systemd"),
+    entry(EXIT_NETWORK, "NETWORK", "systemd"),
+    entry(EXIT_NAMESPACE, "NAMESPACE", "systemd"),
+    entry(EXIT_NO_NEW_PRIVILEGES, "NO_NEW_PRIVILEGES", "systemd"),
+    entry(EXIT_SECCOMP, "SECCOMP", "systemd"),
+    entry(EXIT_SELINUX_CONTEXT, "SELINUX_CONTEXT", "systemd"),
+    entry(230, "PERSONALITY", "systemd"),
+    entry(EXIT_APPARMOR_PROFILE, "APPARMOR_PROFILE", "systemd"),
+    entry(232, "ADDRESS_FAMILIES", "systemd"),
+    entry(233, "RUNTIME_DIRECTORY", "systemd"),
+    entry(235, "CHOWN", "systemd"),
+    entry(EXIT_SMACK_PROCESS_LABEL, "SMACK_PROCESS_LABEL", "systemd"),
+    entry(EXIT_KEYRING, "KEYRING", "systemd"),
+    entry(238, "STATE_DIRECTORY", "systemd"),
+    entry(239, "CACHE_DIRECTORY", "systemd"),
+    entry(240, "LOGS_DIRECTORY", "systemd"),
+    entry(241, "CONFIGURATION_DIRECTORY", "systemd"),
+    entry(242, "NUMA_POLICY", "systemd"),
+    entry(243, "CREDENTIALS", "systemd"),
+    entry(245, "BPF", "systemd"),
+];
+
+/// The status `text` names: a number, or a name with or without the
+/// `EXIT_` prefix, in any case
+pub fn find_exit_status(text: &str) -> Option<&'static ExitStatusEntry> {
+    if let Ok(code) = text.parse::<i32>() {
+        return EXIT_STATUSES.iter().find(|entry| entry.code == code);
+    }
+    let upper = text.to_ascii_uppercase();
+    let name = upper.strip_prefix("EXIT_").unwrap_or(&upper);
+    EXIT_STATUSES.iter().find(|entry| entry.name == name)
+}
+
+/// A main process exit code for failure messages: "203/EXEC" for the
+/// statuses sysd-executor sets, the bare number for the service's own
+pub fn exit_code_text(code: i32) -> String {
+    match EXIT_STATUSES
+        .iter()
+        .find(|entry| entry.code == code && entry.class == "systemd")
+    {
+        Some(entry) => format!("{}/{}", code, entry.name),
+        None => code.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_are_found_by_number_or_name() {
+        assert_eq!(find_exit_status("203").unwrap().name, "EXEC");
+        assert_eq!(find_exit_status("namespace").unwrap().code, 226);
+        assert_eq!(find_exit_status("EXIT_CHDIR").unwrap().code, 200);
+        assert_eq!(find_exit_status("78").unwrap().class, "BSD");
+        assert!(find_exit_status("199").is_none());
+        assert!(find_exit_status("BOGUS").is_none());
+        assert!(EXIT_STATUSES.windows(2).all(|w| w[0].code < w[1].code));
+    }
+
+    #[test]
+    fn only_spawn_failures_are_named_in_exit_code_text() {
+        assert_eq!(exit_code_text(EXIT_EXEC), "203/EXEC");
+        assert_eq!(exit_code_text(EXIT_NAMESPACE), "226/NAMESPACE");
+        assert_eq!(exit_code_text(3), "3");
+        assert_eq!(exit_code_text(-9), "-9");
+    }
+}
@@ -7,6 +7,12 @@
 //! we serialize all execution config and spawn a small executor binary
 //! that deserializes and applies the config before exec'ing the target.
 
+mod exit_status;
+mod spawn_profile;
+
+pub use exit_status::*;
+pub use spawn_profile::{mark_spawn_step, monotonic_ns, read_spawn_steps, SpawnStep};
+
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::os::unix::io::RawFd;
@@ -62,9 +68,21 @@ pub struct ExecConfig {
     pub tty_path: Option<PathBuf>,
     /// Reset TTY before use
     pub tty_reset: bool,
+    /// Hang up other users of the TTY before use
+    pub tty_vhangup: bool,
+    /// Deallocate (or clear) the virtual console before use
+    pub tty_vt_disallocate: bool,
 
     // Security/Sandbox settings
     pub sandbox: SandboxConfig,
+
+    /// Pipe to report setup steps on (see [`SpawnStep`])
+    #[serde(default)]
+    pub profile_fd: Option<RawFd>,
+    /// Pipe to wait on for a byte before anything else, sent once the
+    /// manager moved the executor into the service cgroup
+    #[serde(default)]
+    pub exec_gate_fd: Option<RawFd>,
 }
 
 /// StandardInput configuration
@@ -89,12 +107,25 @@ pub struct SandboxConfig {
     pub private_tmp: bool,
     pub private_devices: bool,
     pub private_network: bool,
+    /// Run the service as a child of an init stub in a new PID namespace
+    pub private_pids: bool,
+    pub private_ipc: bool,
+    pub network_namespace_path: Option<PathBuf>,
     pub protect_kernel_modules: bool,
     pub protect_proc: ProtectProcConfig,
 
+    // MAC labels the service is executed with
+    pub selinux_context: Option<MacLabelConfig>,
+    pub apparmor_profile: Option<MacLabelConfig>,
+    pub smack_process_label: Option<MacLabelConfig>,
+
     // Capabilities
     pub capability_bounding_set: Vec<String>,
     pub ambient_capabilities: Vec<String>,
+    pub secure_bits: u32,
+
+    // Session keyring
+    pub keyring_mode: KeyringModeConfig,
 
     // Namespace restrictions
     pub restrict_namespaces: Option<Vec<String>>,
@@ -154,6 +185,22 @@ pub enum ProtectProcConfig {
     NoAccess,
 }
 
+/// SELinuxContext= / AppArmorProfile= / SmackProcessLabel=
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MacLabelConfig {
+    pub label: String,
+    /// Run without the label when it cannot be applied
+    pub ignore_failure: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum KeyringModeConfig {
+    #[default]
+    Inherit,
+    Private,
+    Shared,
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub enum DevicePolicyConfig {
     #[default]
@@ -249,11 +296,15 @@ mod tests {
             std_input: StdInputConfig::Null,
             tty_path: None,
             tty_reset: false,
+            tty_vhangup: false,
+            tty_vt_disallocate: false,
             sandbox: SandboxConfig {
                 no_new_privileges: true,
                 private_tmp: true,
                 ..Default::default()
             },
+            profile_fd: Some(9),
+            exec_gate_fd: Some(10),
         };
 
         let data = config.serialize().unwrap();
@@ -262,6 +313,8 @@ mod tests {
         assert_eq!(config.program, config2.program);
         assert_eq!(config.args, config2.args);
         assert_eq!(config.uid, config2.uid);
+        assert_eq!(config.profile_fd, config2.profile_fd);
+        assert_eq!(config.exec_gate_fd, config2.exec_gate_fd);
         assert_eq!(
             config.sandbox.no_new_privileges,
             config2.sandbox.no_new_privileges
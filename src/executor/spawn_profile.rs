@@ -0,0 +1,117 @@
+//! Timestamps a spawned child reports about its own setup
+//!
+//! The manager hands the child the write end of a pipe. After each step the
+//! pre_exec hook (or sysd-executor) writes one fixed-size record with the
+//! step and a CLOCK_MONOTONIC time; a single write(2) of a few bytes is safe
+//! between fork and exec and atomic on a pipe. The manager reads the records
+//! back whenever the profile is asked for.
+
+use std::os::unix::io::RawFd;
+
+/// Setup step a record marks the end of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnStep {
+    /// The child runs: fork (and for sysd-executor, the hook before its exec)
+    Forked = 0,
+    /// sysd-executor was executed and read its configuration
+    Executor = 1,
+    /// Socket fds, environment, resource limits and the terminal
+    Setup = 2,
+    /// Namespaces, mounts, seccomp and the other sandbox settings
+    Sandbox = 3,
+    /// User, groups, capabilities and the keyring
+    Credentials = 4,
+}
+
+impl SpawnStep {
+    pub fn name(self) -> &'static str {
+        match self {
+            SpawnStep::Forked => "fork",
+            SpawnStep::Executor => "executor",
+            SpawnStep::Setup => "setup",
+            SpawnStep::Sandbox => "sandbox",
+            SpawnStep::Credentials => "credentials",
+        }
+    }
+
+    fn from_code(code: u64) -> Option<Self> {
+        [
+            SpawnStep::Forked,
+            SpawnStep::Executor,
+            SpawnStep::Setup,
+            SpawnStep::Sandbox,
+            SpawnStep::Credentials,
+        ]
+        .into_iter()
+        .find(|step| *step as u64 == code)
+    }
+}
+
+/// CLOCK_MONOTONIC in nanoseconds (async-signal-safe)
+pub fn monotonic_ns() -> u64 {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64
+}
+
+/// Record the end of `step` on the profile pipe, if there is one
+///
+/// Best effort: a full or closed pipe loses the record, never the spawn.
+pub fn mark_spawn_step(fd: Option<RawFd>, step: SpawnStep) {
+    let Some(fd) = fd else { return };
+    let mut record = [0u8; 16];
+    record[..8].copy_from_slice(&(step as u64).to_ne_bytes());
+    record[8..].copy_from_slice(&monotonic_ns().to_ne_bytes());
+    unsafe { libc::write(fd, record.as_ptr().cast(), record.len()) };
+}
+
+/// Records read so far from the non-blocking read end `fd`, and whether
+/// the child is done writing (every write end closed)
+pub fn read_spawn_steps(fd: RawFd) -> (Vec<(SpawnStep, u64)>, bool) {
+    let mut steps = Vec::new();
+    let mut record = [0u8; 16];
+    loop {
+        let n = unsafe { libc::read(fd, record.as_mut_ptr().cast(), record.len()) };
+        if n != record.len() as isize {
+            return (steps, n == 0);
+        }
+        let code = u64::from_ne_bytes(record[..8].try_into().unwrap());
+        let at = u64::from_ne_bytes(record[8..].try_into().unwrap());
+        if let Some(step) = SpawnStep::from_code(code) {
+            steps.push((step, at));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_come_back_in_order_until_the_writer_closes() {
+        let mut fds = [0; 2];
+        assert_eq!(
+            unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) },
+            0
+        );
+        let [read_end, write_end] = fds;
+
+        mark_spawn_step(Some(write_end), SpawnStep::Forked);
+        mark_spawn_step(Some(write_end), SpawnStep::Sandbox);
+        let (steps, done) = read_spawn_steps(read_end);
+        assert_eq!(
+            steps.iter().map(|(step, _)| *step).collect::<Vec<_>>(),
+            [SpawnStep::Forked, SpawnStep::Sandbox]
+        );
+        assert!(steps[0].1 <= steps[1].1);
+        assert!(!done);
+
+        unsafe { libc::close(write_end) };
+        assert_eq!(read_spawn_steps(read_end), (Vec::new(), true));
+        unsafe { libc::close(read_end) };
+        mark_spawn_step(None, SpawnStep::Setup);
+    }
+}
@@ -81,12 +81,6 @@ impl FstabEntry {
         // Set unit section
         mount.unit.description = Some(format!("Mount {}", self.mount_point));
 
-        // Add dependencies based on mount type
-        if self.is_network() {
-            mount.unit.after.push("network-online.target".to_string());
-            mount.unit.wants.push("network-online.target".to_string());
-        }
-
         // Root filesystem comes first
         if self.mount_point == "/" {
             mount.unit.default_dependencies = false;
@@ -103,6 +97,8 @@ impl FstabEntry {
             mount.unit.after.push(source_mount);
         }
 
+        // Parent mounts, bind/loop sources and network ordering
+        mount.add_implicit_dependencies();
         mount
     }
 }
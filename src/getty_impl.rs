@@ -84,10 +84,13 @@ impl ConsoleParam {
     fn configure_service_section(&self, svc: &mut Service) {
         svc.service.service_type = ServiceType::Idle;
         svc.service.restart = crate::units::RestartPolicy::Always;
-        svc.service.restart_sec = std::time::Duration::from_secs(0);
+        svc.service.restart_sec = Some(std::time::Duration::ZERO);
         svc.service.exec_start = vec![self.agetty_command()];
         svc.service.tty_path = Some(PathBuf::from(format!("/dev/{}", self.tty)));
         svc.service.tty_reset = true;
+        svc.service.tty_vhangup = true;
+        // Like getty@.service: clear the VT's scrollback after logout
+        svc.service.tty_vt_disallocate = !self.is_serial();
         svc.service.standard_input = StdInput::Tty;
         svc.service.standard_output = StdOutput::Inherit;
     }
@@ -168,6 +171,16 @@ pub fn generate_getty_services(cmdline_path: &Path) -> std::io::Result<Vec<Servi
     Ok(services)
 }
 
+/// Generate the getty for virtual console `vt` (autovt)
+pub fn generate_vt_getty(vt: u32) -> Service {
+    ConsoleParam {
+        tty: format!("tty{}", vt),
+        baud: None,
+        options: None,
+    }
+    .to_service()
+}
+
 /// Generate default virtual console gettys (tty1-tty6)
 pub fn generate_default_gettys() -> Vec<Service> {
     (1..=6)
@@ -261,6 +274,8 @@ mod tests {
         assert!(svc.service.exec_start[0].contains("115200"));
         assert!(svc.service.exec_start[0].contains("ttyS0"));
         assert_eq!(svc.service.tty_path, Some(PathBuf::from("/dev/ttyS0")));
+        assert!(svc.service.tty_vhangup);
+        assert!(!svc.service.tty_vt_disallocate);
     }
 
     #[test]
@@ -275,6 +290,7 @@ mod tests {
         assert_eq!(svc.name, "getty@tty1.service");
         assert!(svc.service.exec_start[0].contains("--noclear"));
         assert!(svc.service.exec_start[0].contains("tty1"));
+        assert!(svc.service.tty_vt_disallocate);
     }
 
     #[test]
@@ -18,14 +18,21 @@
 //! ```
 
 pub mod cgroups;
+pub mod creds;
 pub mod dbus;
 pub mod executor;
 pub mod fstab;
 pub mod getty;
+pub mod logging;
 pub mod manager;
 pub mod pid1;
 pub mod protocol;
+pub mod root;
 pub mod sandbox_prctl;
+pub mod sd_notify;
+pub mod state_dir;
+pub mod sysv;
+pub mod tty;
 pub mod units;
 
 // Re-exports for D-Bus interfaces
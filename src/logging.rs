@@ -0,0 +1,465 @@
+//! Where sysd's own log messages go, and how many of them
+//!
+//! Messages are tracing events; the `log::` calls of the rest of the tree are
+//! forwarded into them. Starting units, running jobs and handling D-Bus calls
+//! happen in spans, so every line they produce says which unit and which job
+//! it belongs to:
+//!
+//! ```text
+//! INFO transaction{unit="web.target"}:job{unit="db.service" job="start"}: Started db.service
+//! ```
+//!
+//! Like in systemd, level and target start out from $SYSTEMD_LOG_LEVEL and
+//! $SYSTEMD_LOG_TARGET and can be changed while running, through D-Bus
+//! (SetLogLevel, SetLogTarget) or signals:
+//!
+//! - SIGRTMIN+22: log at debug level
+//! - SIGRTMIN+23: back to the level sysd started with
+//! - SIGRTMIN+26: log to the journal (kmsg while journald is not listening)
+//! - SIGRTMIN+27: log to the console
+//! - SIGRTMIN+28: log to kmsg
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+use tracing::level_filters::LevelFilter;
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, reload, Registry};
+
+/// Socket journald reads native messages from
+const JOURNAL_SOCKET: &str = "/run/systemd/journal/socket";
+const KMSG: &str = "/dev/kmsg";
+/// Level without $SYSTEMD_LOG_LEVEL
+const DEFAULT_LEVEL: LevelFilter = LevelFilter::DEBUG;
+
+/// Where log lines are written (systemd's LogTarget)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogTarget {
+    /// The console and the log file
+    Auto,
+    /// The console (stderr) only
+    Console,
+    /// The log file only
+    File,
+    /// The kernel log buffer
+    Kmsg,
+    /// journald, or kmsg while it is not listening
+    Journal,
+    /// Nowhere
+    Null,
+}
+
+impl LogTarget {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "auto" => Some(Self::Auto),
+            "console" => Some(Self::Console),
+            "file" => Some(Self::File),
+            "kmsg" => Some(Self::Kmsg),
+            "journal" | "journal-or-kmsg" => Some(Self::Journal),
+            "null" => Some(Self::Null),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Console => "console",
+            Self::File => "file",
+            Self::Kmsg => "kmsg",
+            Self::Journal => "journal",
+            Self::Null => "null",
+        }
+    }
+
+    /// The journal and kmsg time stamp messages themselves
+    fn stamps_lines(&self) -> bool {
+        !matches!(self, Self::Kmsg | Self::Journal)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LogError {
+    #[error("Invalid log level: {0}")]
+    InvalidLevel(String),
+
+    #[error("Invalid log target: {0}")]
+    InvalidTarget(String),
+
+    #[error("Logging is already set up: {0}")]
+    Init(String),
+}
+
+/// The level filter, changed in place by `set_level`
+static LEVEL: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+/// The level sysd started with, restored by SIGRTMIN+23
+static STARTUP_LEVEL: OnceLock<LevelFilter> = OnceLock::new();
+static OUTPUT: Mutex<Output> = Mutex::new(Output {
+    target: LogTarget::Auto,
+    file: None,
+    kmsg: None,
+    journal: None,
+});
+
+/// The current target and what it writes to, opened as needed
+struct Output {
+    target: LogTarget,
+    file: Option<File>,
+    kmsg: Option<File>,
+    journal: Option<UnixDatagram>,
+}
+
+/// Install sysd's logger, appending to `log_file` for the targets that
+/// write one
+///
+/// A log file that cannot be opened is left out; see `has_log_file`.
+pub fn init(log_file: &Path) -> Result<(), LogError> {
+    let level = env_level()?;
+    let target = env_target()?;
+    let (filter, handle) = reload::Layer::new(level);
+    let lines = fmt::layer()
+        .with_ansi(false)
+        .with_target(false)
+        .with_timer(Timestamp)
+        .with_writer(Lines);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(lines)
+        .try_init()
+        .map_err(|e| LogError::Init(e.to_string()))?;
+    let _ = LEVEL.set(handle);
+    let _ = STARTUP_LEVEL.set(level);
+    log::set_max_level(log_level_filter(level));
+    let mut output = output();
+    output.target = target;
+    let file = OpenOptions::new().create(true).append(true).open(log_file);
+    output.file = file.ok();
+    Ok(())
+}
+
+/// Whether the log file given to `init` could be opened
+pub fn has_log_file() -> bool {
+    output().file.is_some()
+}
+
+/// The current level, as systemd names it ("info", "debug", ...)
+pub fn level() -> &'static str {
+    let level = LEVEL
+        .get()
+        .and_then(|handle| handle.clone_current())
+        .unwrap_or(DEFAULT_LEVEL);
+    level_name(level)
+}
+
+/// Change the level: a syslog name ("err", "warning", "info", ...) or
+/// number (0-7)
+pub fn set_level(name: &str) -> Result<(), LogError> {
+    let level = parse_level(name).ok_or_else(|| LogError::InvalidLevel(name.to_string()))?;
+    apply_level(level);
+    Ok(())
+}
+
+fn apply_level(level: LevelFilter) {
+    if let Some(handle) = LEVEL.get() {
+        let _ = handle.modify(|current| *current = level);
+    }
+    log::set_max_level(log_level_filter(level));
+    tracing::info!("Log level set to {}", level_name(level));
+}
+
+/// The current target ("console", "journal", ...)
+pub fn target() -> &'static str {
+    output().target.as_str()
+}
+
+/// Send further messages to the target named `name`
+pub fn set_target(name: &str) -> Result<(), LogError> {
+    let target = LogTarget::parse(name).ok_or_else(|| LogError::InvalidTarget(name.to_string()))?;
+    output().target = target;
+    tracing::info!("Log target set to {}", target.as_str());
+    Ok(())
+}
+
+/// Handle the signals changing level and target, as systemd does
+pub fn spawn_signal_handler() {
+    let actions: [(i32, fn()); 5] = [
+        (22, || apply_level(LevelFilter::DEBUG)),
+        (23, restore_level),
+        (26, || set_output_target(LogTarget::Journal)),
+        (27, || set_output_target(LogTarget::Console)),
+        (28, || set_output_target(LogTarget::Kmsg)),
+    ];
+    for (offset, action) in actions {
+        let kind = tokio::signal::unix::SignalKind::from_raw(libc::SIGRTMIN() + offset);
+        let mut signal = match tokio::signal::unix::signal(kind) {
+            Ok(signal) => signal,
+            Err(e) => {
+                log::warn!("Cannot handle SIGRTMIN+{}: {}", offset, e);
+                continue;
+            }
+        };
+        tokio::spawn(async move {
+            while signal.recv().await.is_some() {
+                action();
+            }
+        });
+    }
+}
+
+fn restore_level() {
+    apply_level(*STARTUP_LEVEL.get().unwrap_or(&DEFAULT_LEVEL));
+}
+
+fn set_output_target(target: LogTarget) {
+    let _ = set_target(target.as_str());
+}
+
+fn env_level() -> Result<LevelFilter, LogError> {
+    match std::env::var("SYSTEMD_LOG_LEVEL") {
+        Ok(name) => parse_level(&name).ok_or(LogError::InvalidLevel(name)),
+        Err(_) => Ok(DEFAULT_LEVEL),
+    }
+}
+
+fn env_target() -> Result<LogTarget, LogError> {
+    match std::env::var("SYSTEMD_LOG_TARGET") {
+        Ok(name) => LogTarget::parse(&name).ok_or(LogError::InvalidTarget(name)),
+        Err(_) => Ok(LogTarget::Auto),
+    }
+}
+
+fn output() -> std::sync::MutexGuard<'static, Output> {
+    OUTPUT
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn parse_level(name: &str) -> Option<LevelFilter> {
+    match name {
+        "emerg" | "alert" | "crit" | "err" | "0" | "1" | "2" | "3" => Some(LevelFilter::ERROR),
+        "warning" | "4" => Some(LevelFilter::WARN),
+        "notice" | "info" | "5" | "6" => Some(LevelFilter::INFO),
+        "debug" | "7" => Some(LevelFilter::DEBUG),
+        _ => None,
+    }
+}
+
+fn level_name(level: LevelFilter) -> &'static str {
+    match level.into_level() {
+        Some(Level::ERROR) | None => "err",
+        Some(Level::WARN) => "warning",
+        Some(Level::INFO) => "info",
+        Some(_) => "debug",
+    }
+}
+
+fn log_level_filter(level: LevelFilter) -> log::LevelFilter {
+    match level.into_level() {
+        None => log::LevelFilter::Off,
+        Some(Level::ERROR) => log::LevelFilter::Error,
+        Some(Level::WARN) => log::LevelFilter::Warn,
+        Some(Level::INFO) => log::LevelFilter::Info,
+        Some(Level::DEBUG) => log::LevelFilter::Debug,
+        Some(Level::TRACE) => log::LevelFilter::Trace,
+    }
+}
+
+/// Syslog priority of `level`
+fn priority(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        _ => 7,
+    }
+}
+
+/// "2026-01-02 10:00:00", left out for targets stamping lines themselves
+struct Timestamp;
+
+impl FormatTime for Timestamp {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        if !output().target.stamps_lines() {
+            return Ok(());
+        }
+        write!(w, "{}", chrono::Local::now().format("%Y-%m-%d %H:%M:%S"))
+    }
+}
+
+/// Hands out one `Line` per message
+struct Lines;
+
+impl<'a> MakeWriter<'a> for Lines {
+    type Writer = Line;
+
+    fn make_writer(&'a self) -> Line {
+        Line::new(Level::INFO)
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Line {
+        Line::new(*meta.level())
+    }
+}
+
+/// A formatted message, written to the current target once complete
+struct Line {
+    level: Level,
+    text: Vec<u8>,
+}
+
+impl Line {
+    fn new(level: Level) -> Self {
+        Self {
+            level,
+            text: Vec::new(),
+        }
+    }
+}
+
+impl Write for Line {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.text.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Line {
+    fn drop(&mut self) {
+        if !self.text.is_empty() {
+            output().write(self.level, &self.text);
+        }
+    }
+}
+
+impl Output {
+    /// Errors are dropped: there is nowhere left to report them
+    fn write(&mut self, level: Level, line: &[u8]) {
+        match self.target {
+            LogTarget::Auto => {
+                let _ = io::stderr().write_all(line);
+                self.write_file(line);
+            }
+            LogTarget::Console => {
+                let _ = io::stderr().write_all(line);
+            }
+            LogTarget::File => self.write_file(line),
+            LogTarget::Kmsg => self.write_kmsg(level, line),
+            LogTarget::Journal => {
+                if self.write_journal(level, line).is_err() {
+                    self.write_kmsg(level, line);
+                }
+            }
+            LogTarget::Null => {}
+        }
+    }
+
+    fn write_file(&mut self, line: &[u8]) {
+        if let Some(file) = &mut self.file {
+            let _ = file.write_all(line);
+        }
+    }
+
+    fn write_kmsg(&mut self, level: Level, line: &[u8]) {
+        if self.kmsg.is_none() {
+            self.kmsg = OpenOptions::new()
+                .write(true)
+                .open(crate::root::path(KMSG))
+                .ok();
+        }
+        if let Some(kmsg) = &mut self.kmsg {
+            let _ = kmsg.write_all(&kmsg_record(level, message_of(line)));
+        }
+    }
+
+    fn write_journal(&mut self, level: Level, line: &[u8]) -> io::Result<()> {
+        if self.journal.is_none() {
+            self.journal = Some(UnixDatagram::unbound()?);
+        }
+        let Some(journal) = &self.journal else {
+            return Ok(());
+        };
+        let record = journal_record(level, message_of(line));
+        journal.send_to(&record, crate::root::path(JOURNAL_SOCKET))?;
+        Ok(())
+    }
+}
+
+/// `line` without the padding left by the missing time stamp and the
+/// final newline
+fn message_of(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let start = line.iter().position(|b| *b != b' ').unwrap_or(line.len());
+    &line[start..]
+}
+
+/// "<6>sysd[1]: message" as /dev/kmsg takes it
+fn kmsg_record(level: Level, message: &[u8]) -> Vec<u8> {
+    let mut record = format!("<{}>sysd[{}]: ", priority(level), std::process::id()).into_bytes();
+    record.extend_from_slice(message);
+    record.push(b'\n');
+    record
+}
+
+/// A message in journald's native protocol; MESSAGE= goes in the binary
+/// form so newlines in it survive
+fn journal_record(level: Level, message: &[u8]) -> Vec<u8> {
+    let mut record = format!(
+        "PRIORITY={}\nSYSLOG_IDENTIFIER=sysd\nSYSLOG_PID={}\n",
+        priority(level),
+        std::process::id()
+    )
+    .into_bytes();
+    record.extend_from_slice(b"MESSAGE\n");
+    record.extend_from_slice(&(message.len() as u64).to_le_bytes());
+    record.extend_from_slice(message);
+    record.push(b'\n');
+    record
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_and_targets_use_systemd_names() {
+        assert_eq!(parse_level("warning"), Some(LevelFilter::WARN));
+        assert_eq!(parse_level("7"), Some(LevelFilter::DEBUG));
+        assert_eq!(parse_level("crit"), Some(LevelFilter::ERROR));
+        assert_eq!(parse_level("verbose"), None);
+        assert_eq!(level_name(LevelFilter::INFO), "info");
+        assert_eq!(level_name(LevelFilter::ERROR), "err");
+        assert_eq!(
+            LogTarget::parse("journal-or-kmsg"),
+            Some(LogTarget::Journal)
+        );
+        assert_eq!(LogTarget::parse("syslog"), None);
+        assert_eq!(LogTarget::Kmsg.as_str(), "kmsg");
+    }
+
+    #[test]
+    fn kmsg_and_journal_records_carry_the_priority() {
+        let message = message_of(b" WARN job{unit=\"a.service\"}: late\n");
+        assert_eq!(message, b"WARN job{unit=\"a.service\"}: late");
+        let kmsg = kmsg_record(Level::WARN, message);
+        assert!(kmsg.starts_with(b"<4>sysd["));
+        assert!(kmsg.ends_with(b"]: WARN job{unit=\"a.service\"}: late\n"));
+
+        let journal = journal_record(Level::ERROR, b"two\nlines");
+        let text = String::from_utf8_lossy(&journal);
+        assert!(text.starts_with("PRIORITY=3\nSYSLOG_IDENTIFIER=sysd\n"));
+        let binary = b"MESSAGE\n\x09\0\0\0\0\0\0\0two\nlines\n";
+        assert!(journal.ends_with(binary));
+    }
+}
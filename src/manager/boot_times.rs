@@ -0,0 +1,218 @@
+//! How long the boot took and when each unit came up (`sysd analyze time`,
+//! `sysd analyze plot`)
+//!
+//! Firmware and loader times are the LoaderTimeInitUSec/LoaderTimeExecUSec
+//! EFI variables a boot loader implementing the Boot Loader Interface
+//! (systemd-boot, ...) leaves in /sys/firmware; without them the boot
+//! starts with the kernel. Unit intervals are the state timestamps of each
+//! unit. Everything is on CLOCK_MONOTONIC like systemd's
+//! *TimestampMonotonic properties, so 0 is when the kernel started.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::executor::monotonic_ns;
+
+use super::Manager;
+
+/// Vendor GUID of the Boot Loader Interface variables
+const LOADER_GUID: &str = "4a67b082-0a4c-41cf-b6c7-440b29bb8c4f";
+
+const EFIVARS_DIR: &str = "/sys/firmware/efi/efivars";
+
+/// Phases of the boot up to now
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BootTimes {
+    /// Time spent in the firmware before the boot loader ran
+    pub firmware: Option<Duration>,
+    /// Time spent in the boot loader before the kernel ran
+    pub loader: Option<Duration>,
+    /// When the manager came up
+    pub userspace: Duration,
+    /// When the default target was reached, if it was
+    pub finish: Option<Duration>,
+    /// The default target
+    pub target: Option<String>,
+}
+
+/// When a unit crossed each edge of its last run; None where it never did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitTimes {
+    pub name: String,
+    pub activating: Option<Duration>,
+    pub activated: Option<Duration>,
+    pub deactivating: Option<Duration>,
+    pub deactivated: Option<Duration>,
+}
+
+impl UnitTimes {
+    /// How long the unit took to come up, or to give up if it never did
+    /// (systemd-analyze's "time" column)
+    pub fn time(&self) -> Duration {
+        let Some(activating) = self.activating else {
+            return Duration::ZERO;
+        };
+        self.activated
+            .filter(|&at| at >= activating)
+            .or(self.deactivated.filter(|&at| at >= activating))
+            .map_or(Duration::ZERO, |at| at - activating)
+    }
+}
+
+impl Manager {
+    /// Firmware, loader and userspace times of this boot
+    pub fn boot_times(&self) -> BootTimes {
+        let target = self.get_default_target().ok();
+        let finish = target
+            .as_ref()
+            .and_then(|name| self.states.get(name))
+            .and_then(|state| state.timestamps.active_enter)
+            .map(monotonic);
+        let (firmware, loader) = if self.user_mode {
+            (None, None)
+        } else {
+            loader_times(Path::new(EFIVARS_DIR))
+        };
+        BootTimes {
+            firmware,
+            loader,
+            userspace: monotonic(self.boot_time),
+            finish,
+            target,
+        }
+    }
+
+    /// Intervals of every unit that started since the manager came up, in
+    /// the order they started
+    pub fn unit_times(&self) -> Vec<UnitTimes> {
+        let mut times: Vec<UnitTimes> = self
+            .states
+            .iter()
+            .filter(|(_, state)| state.timestamps.inactive_exit.is_some())
+            .map(|(name, state)| {
+                let stamps = &state.timestamps;
+                UnitTimes {
+                    name: name.clone(),
+                    activating: stamps.inactive_exit.map(monotonic),
+                    activated: stamps.active_enter.map(monotonic),
+                    deactivating: stamps.active_exit.map(monotonic),
+                    deactivated: stamps.inactive_enter.map(monotonic),
+                }
+            })
+            .collect();
+        times.sort_by(|a, b| a.activating.cmp(&b.activating).then(a.name.cmp(&b.name)));
+        times
+    }
+}
+
+/// `at` as time since the kernel started
+fn monotonic(at: Instant) -> Duration {
+    Duration::from_nanos(monotonic_ns()).saturating_sub(at.elapsed())
+}
+
+/// Firmware and loader times from the Boot Loader Interface variables in
+/// `efivars`; both None unless the loader set both
+fn loader_times(efivars: &Path) -> (Option<Duration>, Option<Duration>) {
+    let read = |name: &str| {
+        let path = efivars.join(format!("{}-{}", name, LOADER_GUID));
+        std::fs::read(path)
+            .ok()
+            .and_then(|data| parse_usec_variable(&data))
+    };
+    match (read("LoaderTimeInitUSec"), read("LoaderTimeExecUSec")) {
+        (Some(init), Some(exec)) if exec >= init => (
+            Some(Duration::from_micros(init)),
+            Some(Duration::from_micros(exec - init)),
+        ),
+        _ => (None, None),
+    }
+}
+
+/// Microseconds in an EFI variable as efivarfs shows it: 4 bytes of
+/// attributes, then a NUL-terminated UTF-16LE decimal string
+fn parse_usec_variable(data: &[u8]) -> Option<u64> {
+    let text = data.get(4..)?;
+    let units: Vec<u16> = text
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .take_while(|&unit| unit != 0)
+        .collect();
+    String::from_utf16(&units).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+
+    fn efi_variable(value: &str) -> Vec<u8> {
+        let mut data = vec![0x07, 0, 0, 0];
+        for unit in value.encode_utf16().chain([0]) {
+            data.extend(unit.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn loader_variables_split_firmware_and_loader_time() {
+        assert_eq!(
+            parse_usec_variable(&efi_variable("1500000")),
+            Some(1_500_000)
+        );
+        assert_eq!(parse_usec_variable(&[7, 0, 0, 0]), None);
+        assert_eq!(parse_usec_variable(&efi_variable("soon")), None);
+
+        let dir = std::env::temp_dir().join(format!("sysd-efivars-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        assert_eq!(loader_times(&dir), (None, None));
+        let var = |name: &str| dir.join(format!("{}-{}", name, LOADER_GUID));
+        std::fs::write(var("LoaderTimeInitUSec"), efi_variable("1500000")).unwrap();
+        std::fs::write(var("LoaderTimeExecUSec"), efi_variable("2000000")).unwrap();
+        assert_eq!(
+            loader_times(&dir),
+            (
+                Some(Duration::from_millis(1500)),
+                Some(Duration::from_millis(500))
+            )
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unit_time_is_activation_or_time_to_failure() {
+        let at = Duration::from_millis;
+        let mut times = UnitTimes {
+            name: "demo.service".into(),
+            activating: Some(at(100)),
+            activated: Some(at(350)),
+            deactivating: None,
+            deactivated: None,
+        };
+        assert_eq!(times.time(), at(250));
+        times.activated = None;
+        times.deactivated = Some(at(400));
+        assert_eq!(times.time(), at(300));
+        times.activating = None;
+        assert_eq!(times.time(), Duration::ZERO);
+    }
+
+    #[test]
+    fn only_started_units_have_times() {
+        let mut manager = Manager::new_user();
+        let mut running = ServiceState::new();
+        running.set_starting();
+        running.set_running(42);
+        manager.states.insert("up.service".into(), running);
+        manager
+            .states
+            .insert("never.service".into(), ServiceState::new());
+
+        let times = manager.unit_times();
+        assert_eq!(times.len(), 1);
+        let up = &times[0];
+        assert_eq!(up.name, "up.service");
+        assert!(up.activated >= up.activating);
+        assert!(up.activated.unwrap() >= manager.boot_times().userspace);
+        assert_eq!(up.deactivating, None);
+    }
+}
@@ -0,0 +1,334 @@
+//! Removing a unit's per-unit resources (`sysdctl clean`, D-Bus CleanUnit)
+//!
+//! Like `systemctl clean`, this deletes the directories configured with
+//! RuntimeDirectory=, StateDirectory=, CacheDirectory=, LogsDirectory= and
+//! ConfigurationDirectory= (and the file descriptor store) of a unit that is
+//! not running. Directory names come from unit files, so anything that could
+//! point outside its base directory is refused before touching the disk,
+//! and the directories on the way down are opened without following
+//! symlinks, so a link planted by the service cannot lead elsewhere either.
+
+use std::ffi::{CString, OsStr};
+use std::io::ErrorKind;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Component, Path, PathBuf};
+
+use crate::units::ServiceSection;
+
+use super::{ActiveState, Manager, ManagerError};
+
+/// Resource selected with --what=
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CleanWhat {
+    Configuration,
+    State,
+    Cache,
+    Logs,
+    Runtime,
+    FdStore,
+}
+
+impl CleanWhat {
+    const ALL: [CleanWhat; 6] = [
+        Self::Configuration,
+        Self::State,
+        Self::Cache,
+        Self::Logs,
+        Self::Runtime,
+        Self::FdStore,
+    ];
+
+    /// What `systemctl clean` removes without --what=
+    pub const DEFAULT: [CleanWhat; 3] = [Self::Cache, Self::Runtime, Self::FdStore];
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "configuration" => Some(Self::Configuration),
+            "state" => Some(Self::State),
+            "cache" => Some(Self::Cache),
+            "logs" => Some(Self::Logs),
+            "runtime" => Some(Self::Runtime),
+            "fdstore" => Some(Self::FdStore),
+            _ => None,
+        }
+    }
+
+    /// Parse --what= values (comma separated, possibly repeated, "all" for
+    /// everything). An empty list means the default set.
+    pub fn parse_list<S: AsRef<str>>(values: &[S]) -> Result<Vec<Self>, String> {
+        let mut what = Vec::new();
+        for value in values.iter().flat_map(|v| v.as_ref().split(',')) {
+            let value = value.trim();
+            let kinds: Vec<Self> = match value {
+                "" => continue,
+                "all" => Self::ALL.to_vec(),
+                _ => vec![Self::parse(value).ok_or_else(|| format!("unknown --what: {}", value))?],
+            };
+            for kind in kinds {
+                if !what.contains(&kind) {
+                    what.push(kind);
+                }
+            }
+        }
+        if what.is_empty() {
+            what = Self::DEFAULT.to_vec();
+        }
+        Ok(what)
+    }
+
+    /// Base directory and configured names, or None for the fd store
+    fn directories(self, service: &ServiceSection) -> Option<(&'static str, &[String])> {
+        match self {
+            Self::Configuration => Some(("etc", &service.configuration_directory)),
+            Self::State => Some(("var/lib", &service.state_directory)),
+            Self::Cache => Some(("var/cache", &service.cache_directory)),
+            Self::Logs => Some(("var/log", &service.logs_directory)),
+            Self::Runtime => Some(("run", &service.runtime_directory)),
+            Self::FdStore => None,
+        }
+    }
+}
+
+/// Directories `what` selects for `service`, as a base directory (below
+/// the root) and a path relative to it. Fails if any configured name is
+/// absolute or contains `..`.
+fn clean_targets(
+    service: &ServiceSection,
+    service_name: &str,
+    what: &[CleanWhat],
+) -> Result<Vec<(PathBuf, PathBuf)>, ManagerError> {
+    let base_name = service_name
+        .strip_suffix(".service")
+        .unwrap_or(service_name);
+    let mut targets = Vec::new();
+    for (base, names) in what.iter().filter_map(|kind| kind.directories(service)) {
+        for name in names {
+            let dir_name = if name.is_empty() { base_name } else { name };
+            let relative = Path::new(dir_name);
+            let is_contained = relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+            if !is_contained || relative.as_os_str().is_empty() {
+                return Err(ManagerError::UnsafePath(format!(
+                    "{}: {} is not below /{}",
+                    service_name, dir_name, base
+                )));
+            }
+            let base = crate::root::path(Path::new("/").join(base));
+            targets.push((base, relative.to_path_buf()));
+        }
+    }
+    Ok(targets)
+}
+
+/// Remove `targets`, returning the ones that existed. Symlinks are removed,
+/// never followed, including symlinked directories on the way down from the
+/// base.
+fn remove_targets(targets: Vec<(PathBuf, PathBuf)>) -> Result<Vec<PathBuf>, ManagerError> {
+    let mut removed = Vec::new();
+    for (base, relative) in targets {
+        let path = base.join(&relative);
+        if !remove_below(&base, &relative)? {
+            continue;
+        }
+        log::info!("Removed {}", path.display());
+        removed.push(path);
+    }
+    Ok(removed)
+}
+
+/// Remove `relative` inside `base`, opening each directory on the way with
+/// O_NOFOLLOW so none of them can lead elsewhere; false if it does not exist
+fn remove_below(base: &Path, relative: &Path) -> std::io::Result<bool> {
+    let mut names: Vec<CString> = relative
+        .iter()
+        .map(|name| CString::new(name.as_bytes()))
+        .collect::<Result<_, _>>()?;
+    let Some(last) = names.pop() else {
+        return Ok(false);
+    };
+    let mut dir = match open_dir(None, &CString::new(base.as_os_str().as_bytes())?) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        dir => dir?,
+    };
+    for name in &names {
+        dir = match open_dir(Some(&dir), name) {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => {
+                return Err(std::io::Error::new(
+                    e.kind(),
+                    format!("{}: {}", base.join(relative).display(), e),
+                ))
+            }
+            Ok(dir) => dir,
+        };
+    }
+
+    let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+    let flags = libc::AT_SYMLINK_NOFOLLOW;
+    if unsafe { libc::fstatat(dir.as_raw_fd(), last.as_ptr(), &mut stat, flags) } != 0 {
+        let e = std::io::Error::last_os_error();
+        return match e.kind() {
+            ErrorKind::NotFound => Ok(false),
+            _ => Err(e),
+        };
+    }
+    if stat.st_mode & libc::S_IFMT == libc::S_IFDIR {
+        // Through the already opened parent, not the path that was checked
+        let parent = PathBuf::from(format!("/proc/self/fd/{}", dir.as_raw_fd()));
+        std::fs::remove_dir_all(parent.join(OsStr::from_bytes(last.as_bytes())))?;
+    } else if unsafe { libc::unlinkat(dir.as_raw_fd(), last.as_ptr(), 0) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(true)
+}
+
+/// Open directory `name` (in `parent`), failing on a symlink
+fn open_dir(parent: Option<&OwnedFd>, name: &CString) -> std::io::Result<OwnedFd> {
+    let parent = parent.map_or(libc::AT_FDCWD, |fd| fd.as_raw_fd());
+    let flags = libc::O_RDONLY | libc::O_DIRECTORY | libc::O_NOFOLLOW | libc::O_CLOEXEC;
+    let fd = unsafe { libc::openat(parent, name.as_ptr(), flags) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+impl Manager {
+    /// Remove the resources of an inactive unit selected by `what`.
+    /// Returns the directories that were removed.
+    pub fn clean_unit(
+        &mut self,
+        name: &str,
+        what: &[CleanWhat],
+    ) -> Result<Vec<PathBuf>, ManagerError> {
+        let name = self.normalize_name(name);
+        let service = self
+            .units
+            .get(&name)
+            .and_then(|unit| unit.as_service())
+            .ok_or_else(|| ManagerError::NotFound(name.clone()))?;
+        if let Some(state) = self.states.get(&name) {
+            if !matches!(state.active, ActiveState::Inactive | ActiveState::Failed) {
+                return Err(ManagerError::CleanWhileActive(name));
+            }
+        }
+
+        let targets = clean_targets(&service.service, &name, what)?;
+        let removed = remove_targets(targets)?;
+        if what.contains(&CleanWhat::FdStore) {
+            self.close_stored_fds_after_stop(&name);
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+    use crate::units::{Service, Unit};
+
+    fn service_section(configure: impl FnOnce(&mut ServiceSection)) -> ServiceSection {
+        let mut service = Service::new("demo.service".to_string());
+        configure(&mut service.service);
+        service.service
+    }
+
+    #[test]
+    fn what_lists_accept_commas_repeats_and_all() {
+        assert_eq!(
+            CleanWhat::parse_list(&["state,cache", "state"]).unwrap(),
+            [CleanWhat::State, CleanWhat::Cache]
+        );
+        assert_eq!(
+            CleanWhat::parse_list::<&str>(&[]).unwrap(),
+            CleanWhat::DEFAULT
+        );
+        assert_eq!(CleanWhat::parse_list(&["all"]).unwrap().len(), 6);
+        assert!(CleanWhat::parse_list(&["state,bogus"]).is_err());
+    }
+
+    #[test]
+    fn clean_targets_resolve_names_and_refuse_traversal() {
+        let service = service_section(|service| {
+            service.state_directory = vec![String::new(), "demo/extra".to_string()];
+            service.runtime_directory = vec!["demo".to_string()];
+        });
+
+        let targets = clean_targets(&service, "demo.service", &[CleanWhat::State]).unwrap();
+        let base = crate::root::path("/var/lib");
+        assert_eq!(
+            targets,
+            [
+                (base.clone(), PathBuf::from("demo")),
+                (base, PathBuf::from("demo/extra"))
+            ]
+        );
+
+        for bad in ["../etc", "/etc", "demo/../../etc", "."] {
+            let service = service_section(|service| service.cache_directory = vec![bad.into()]);
+            assert!(matches!(
+                clean_targets(&service, "demo.service", &[CleanWhat::Cache]),
+                Err(ManagerError::UnsafePath(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn remove_targets_deletes_directories_without_following_symlinks() {
+        let root = std::env::temp_dir().join(format!("sysd-clean-{}", std::process::id()));
+        let keep = root.join("keep");
+        let base = root.join("var/cache");
+        std::fs::create_dir_all(&keep).unwrap();
+        std::fs::create_dir_all(base.join("demo/sub")).unwrap();
+        std::fs::create_dir_all(keep.join("extra")).unwrap();
+        std::fs::write(keep.join("file"), "x").unwrap();
+        std::os::unix::fs::symlink(&keep, base.join("link")).unwrap();
+        let target = |relative: &str| (base.clone(), PathBuf::from(relative));
+
+        assert!(remove_targets(vec![target("link/extra")]).is_err());
+        assert!(keep.join("extra").exists());
+
+        let removed =
+            remove_targets(vec![target("demo"), target("link"), target("missing")]).unwrap();
+
+        assert_eq!(removed, [base.join("demo"), base.join("link")]);
+        assert!(!base.join("demo").exists());
+        assert!(std::fs::symlink_metadata(base.join("link")).is_err());
+        assert!(keep.join("file").exists());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn clean_refuses_active_and_unknown_units() {
+        let mut manager = Manager::new_user();
+        manager.units.insert(
+            "demo.service".to_string(),
+            Unit::Service(Service::new("demo.service".to_string())),
+        );
+        let mut running = ServiceState::new();
+        running.set_running(1);
+        manager.states.insert("demo.service".to_string(), running);
+
+        assert!(matches!(
+            manager.clean_unit("demo", &CleanWhat::DEFAULT),
+            Err(ManagerError::CleanWhileActive(_))
+        ));
+        assert!(matches!(
+            manager.clean_unit("missing.service", &CleanWhat::DEFAULT),
+            Err(ManagerError::NotFound(_))
+        ));
+
+        manager
+            .states
+            .get_mut("demo.service")
+            .unwrap()
+            .set_stopped(0);
+        assert_eq!(
+            manager.clean_unit("demo", &[CleanWhat::FdStore]).unwrap(),
+            Vec::<PathBuf>::new()
+        );
+    }
+}
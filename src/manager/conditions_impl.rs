@@ -2,7 +2,7 @@
 //
 // Implements ConditionPathExists=, ConditionVirtualization=, ConditionCapability=, etc.
 
-use crate::units::Unit;
+use crate::units::{Unit, UnitSection};
 
 use super::{Manager, VirtualizationType};
 
@@ -132,12 +132,63 @@ fn check_triggers(
     ))
 }
 
+/// Host properties consulted by condition checks
+///
+/// Detected once when the manager is created; they don't change while it runs
+/// short of a container/VM migration, so `refresh_host_facts` re-reads them on demand.
+#[derive(Debug, Clone, Default)]
+pub(super) struct HostFacts {
+    virtualization: Option<VirtualizationType>,
+    /// CapEff mask from /proc/self/status
+    effective_caps: Option<u64>,
+    kernel_cmdline: String,
+}
+
+impl HostFacts {
+    pub(super) fn detect() -> Self {
+        Self {
+            virtualization: detect_container().or_else(detect_vm),
+            effective_caps: read_effective_caps(),
+            kernel_cmdline: std::fs::read_to_string("/proc/cmdline").unwrap_or_default(),
+        }
+    }
+}
+
+fn read_effective_caps() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:\t"))
+        .and_then(|hex| u64::from_str_radix(hex.trim(), 16).ok())
+}
+
 impl Manager {
+    /// Re-detect virtualization, capabilities and the kernel command line
+    pub fn refresh_host_facts(&mut self) {
+        self.host_facts = HostFacts::detect();
+    }
+
     /// Check if unit conditions are met.
     /// Returns None if all conditions pass, or Some(reason) if a condition fails.
-    pub(super) fn check_conditions(&self, unit: &Unit) -> Option<String> {
-        let section = unit.unit_section();
-        let virtualization = self.detect_virtualization();
+    /// Conditions stat and read files, so they are checked on the blocking pool.
+    pub(super) async fn check_conditions(&self, unit: &Unit) -> Option<String> {
+        let facts = self.host_facts.clone();
+        let section = unit.unit_section().clone();
+        tokio::task::spawn_blocking(move || facts.check_conditions(&section))
+            .await
+            .unwrap_or_else(|e| Some(format!("Condition check failed: {}", e)))
+    }
+
+    /// Detected virtualization type
+    pub(super) fn detect_virtualization(&self) -> Option<VirtualizationType> {
+        self.host_facts.virtualization.clone()
+    }
+}
+
+impl HostFacts {
+    /// Check the conditions of a `[Unit]` section against this host
+    pub(super) fn check_conditions(&self, section: &UnitSection) -> Option<String> {
+        let virtualization = self.virtualization.clone();
         let matched_virtualization = format!("matched {:?}", virtualization);
         let detected_virtualization = format!("detected {:?}", virtualization);
 
@@ -149,6 +200,13 @@ impl Manager {
                 "path missing",
                 |path| std::path::Path::new(path).exists(),
             ),
+            check_condition_list(
+                &section.condition_path_exists_glob,
+                "ConditionPathExistsGlob",
+                "matching path exists",
+                "no matching path",
+                crate::units::path_glob_matches_any,
+            ),
             check_condition_list(
                 &section.condition_directory_not_empty,
                 "ConditionDirectoryNotEmpty",
@@ -228,35 +286,20 @@ impl Manager {
         None
     }
 
-    /// Detected virtualization type
-    pub(super) fn detect_virtualization(&self) -> Option<VirtualizationType> {
-        detect_container().or_else(detect_vm)
-    }
-
     /// Check if process has a specific capability
     fn check_capability(&self, cap_name: &str) -> bool {
         let Some(cap_num) = capability_number(cap_name) else {
             return false;
         };
 
-        let Ok(status) = std::fs::read_to_string("/proc/self/status") else {
-            return false;
-        };
-
-        status
-            .lines()
-            .find_map(|line| line.strip_prefix("CapEff:\t"))
-            .and_then(|hex| u64::from_str_radix(hex.trim(), 16).ok())
+        self.effective_caps
             .map(|caps| (caps & (1u64 << cap_num)) != 0)
             .unwrap_or(false)
     }
 
     /// Check if kernel command line contains parameter
     fn check_kernel_cmdline(&self, param: &str) -> bool {
-        let Ok(cmdline) = std::fs::read_to_string("/proc/cmdline") else {
-            return false;
-        };
-
+        let cmdline = &self.kernel_cmdline;
         if param.contains('=') {
             cmdline.split_whitespace().any(|p| p == param)
         } else {
@@ -653,13 +696,13 @@ mod tests {
         let qemu = Some(VirtualizationType::Qemu);
         let none = None;
 
-        assert!(manager.check_virtualization_match_with(&docker, "yes"));
-        assert!(manager.check_virtualization_match_with(&none, "no"));
-        assert!(manager.check_virtualization_match_with(&docker, "container"));
-        assert!(!manager.check_virtualization_match_with(&docker, "vm"));
-        assert!(manager.check_virtualization_match_with(&qemu, "vm"));
-        assert!(manager.check_virtualization_match_with(&qemu, "kvm"));
-        assert!(!manager.check_virtualization_match_with(&none, "container"));
+        assert!(manager.host_facts.check_virtualization_match_with(&docker, "yes"));
+        assert!(manager.host_facts.check_virtualization_match_with(&none, "no"));
+        assert!(manager.host_facts.check_virtualization_match_with(&docker, "container"));
+        assert!(!manager.host_facts.check_virtualization_match_with(&docker, "vm"));
+        assert!(manager.host_facts.check_virtualization_match_with(&qemu, "vm"));
+        assert!(manager.host_facts.check_virtualization_match_with(&qemu, "kvm"));
+        assert!(!manager.host_facts.check_virtualization_match_with(&none, "container"));
     }
 
     #[test]
@@ -681,12 +724,12 @@ mod tests {
             service.unit.condition_directory_not_empty =
                 vec![full_dir.to_string_lossy().to_string()];
         });
-        assert_eq!(manager.check_conditions(&passing), None);
+        assert_eq!(manager.host_facts.check_conditions(passing.unit_section()), None);
 
         let missing = service_unit(|service| {
             service.unit.condition_path_exists = vec![missing_path.to_string_lossy().to_string()];
         });
-        let missing_failure = manager.check_conditions(&missing).unwrap();
+        let missing_failure = manager.host_facts.check_conditions(missing.unit_section()).unwrap();
         assert!(missing_failure.contains("ConditionPathExists="));
         assert!(missing_failure.contains("path missing"));
 
@@ -694,11 +737,36 @@ mod tests {
             service.unit.condition_directory_not_empty =
                 vec![empty_dir.to_string_lossy().to_string()];
         });
-        let empty_failure = manager.check_conditions(&empty).unwrap();
+        let empty_failure = manager.host_facts.check_conditions(empty.unit_section()).unwrap();
         assert!(empty_failure.contains("ConditionDirectoryNotEmpty="));
         assert!(empty_failure.contains("empty or missing"));
     }
 
+    #[test]
+    fn check_conditions_matches_path_globs() {
+        let manager = Manager::new();
+        let root = temp_dir("path-glob");
+        std::fs::write(root.path().join("demo.conf"), "data").unwrap();
+        let base = root.path().to_string_lossy().to_string();
+
+        let passing = service_unit(|service| {
+            service.unit.condition_path_exists_glob = vec![format!("{}/*.conf", base)];
+        });
+        assert_eq!(manager.host_facts.check_conditions(passing.unit_section()), None);
+
+        let negated = service_unit(|service| {
+            service.unit.condition_path_exists_glob = vec![format!("!{}/*.missing", base)];
+        });
+        assert_eq!(manager.host_facts.check_conditions(negated.unit_section()), None);
+
+        let failing = service_unit(|service| {
+            service.unit.condition_path_exists_glob = vec![format!("{}/*.missing", base)];
+        });
+        let failure = manager.host_facts.check_conditions(failing.unit_section()).unwrap();
+        assert!(failure.contains("ConditionPathExistsGlob="));
+        assert!(failure.contains("no matching path"));
+    }
+
     #[test]
     fn check_conditions_respects_negated_path_conditions() {
         let manager = Manager::new();
@@ -711,13 +779,13 @@ mod tests {
             service.unit.condition_path_exists =
                 vec![format!("!{}", missing_path.to_string_lossy())];
         });
-        assert_eq!(manager.check_conditions(&passing), None);
+        assert_eq!(manager.host_facts.check_conditions(passing.unit_section()), None);
 
         let failing = service_unit(|service| {
             service.unit.condition_path_exists =
                 vec![format!("!{}", existing_path.to_string_lossy())];
         });
-        let failure = manager.check_conditions(&failing).unwrap();
+        let failure = manager.host_facts.check_conditions(failing.unit_section()).unwrap();
         assert!(failure.contains("ConditionPathExists=!"));
         assert!(failure.contains("path exists"));
     }
@@ -31,7 +31,8 @@ fn check_conditions_reports_capability_kernel_security_and_needs_update_failures
         service.unit.condition_capability = vec!["CAP_DOES_NOT_EXIST".to_string()];
     });
     assert!(manager
-        .check_conditions(&missing_capability)
+        .host_facts
+        .check_conditions(missing_capability.unit_section())
         .unwrap()
         .contains("ConditionCapability=CAP_DOES_NOT_EXIST"));
 
@@ -40,7 +41,8 @@ fn check_conditions_reports_capability_kernel_security_and_needs_update_failures
             vec!["definitely_missing_sysd_param".to_string()];
     });
     assert!(manager
-        .check_conditions(&missing_kernel_param)
+        .host_facts
+        .check_conditions(missing_kernel_param.unit_section())
         .unwrap()
         .contains("ConditionKernelCommandLine=definitely_missing_sysd_param"));
 
@@ -48,7 +50,8 @@ fn check_conditions_reports_capability_kernel_security_and_needs_update_failures
         service.unit.condition_security = vec!["definitely-missing-framework".to_string()];
     });
     assert!(manager
-        .check_conditions(&missing_security)
+        .host_facts
+        .check_conditions(missing_security.unit_section())
         .unwrap()
         .contains("ConditionSecurity=definitely-missing-framework"));
 
@@ -56,7 +59,8 @@ fn check_conditions_reports_capability_kernel_security_and_needs_update_failures
         service.unit.condition_needs_update = vec!["/definitely-missing".to_string()];
     });
     assert!(manager
-        .check_conditions(&missing_update_path)
+        .host_facts
+        .check_conditions(missing_update_path.unit_section())
         .unwrap()
         .contains("ConditionNeedsUpdate=/definitely-missing"));
 }
@@ -64,12 +68,15 @@ fn check_conditions_reports_capability_kernel_security_and_needs_update_failures
 #[test]
 fn first_boot_condition_reports_opposite_of_detected_state() {
     let manager = Manager::new();
-    let detected_first_boot = manager.check_first_boot();
+    let detected_first_boot = manager.host_facts.check_first_boot();
 
     let unit = service_unit(|service| {
         service.unit.condition_first_boot = Some(!detected_first_boot);
     });
-    let failure = manager.check_conditions(&unit).unwrap();
+    let failure = manager
+        .host_facts
+        .check_conditions(unit.unit_section())
+        .unwrap();
 
     if detected_first_boot {
         assert!(failure.contains("ConditionFirstBoot=no failed"));
@@ -92,8 +99,8 @@ fn capability_checks_match_current_process_status() {
         .map(|caps| (caps & 1) != 0)
         .unwrap_or(false);
 
-    assert_eq!(manager.check_capability("CAP_CHOWN"), expected);
-    assert!(!manager.check_capability("CAP_NOT_REAL"));
+    assert_eq!(manager.host_facts.check_capability("CAP_CHOWN"), expected);
+    assert!(!manager.host_facts.check_capability("CAP_NOT_REAL"));
 }
 
 #[test]
@@ -101,24 +108,29 @@ fn security_framework_checks_follow_kernel_probe_files() {
     let manager = Manager::new();
 
     assert_eq!(
-        manager.check_security_framework("audit"),
+        manager.host_facts.check_security_framework("audit"),
         std::path::Path::new("/proc/self/loginuid").exists()
     );
     assert_eq!(
-        manager.check_security_framework("tpm2"),
+        manager.host_facts.check_security_framework("tpm2"),
         std::fs::read_to_string("/sys/class/tpm/tpm0/tpm_version_major")
             .map(|v| v.trim() == "2")
             .unwrap_or(false)
     );
     assert_eq!(
-        manager.check_security_framework("uefi-secureboot"),
+        manager
+            .host_facts
+            .check_security_framework("uefi-secureboot"),
         has_efi_var_prefix("SecureBoot-")
     );
     assert_eq!(
-        manager.check_security_framework("measured-uki"),
+        manager.host_facts.check_security_framework("measured-uki"),
         has_efi_var_prefix("StubInfo-4a67b082-0a4c-41cf-b6c7-440b29bb8c4f")
     );
-    assert_eq!(manager.check_security_framework("cvm"), is_confidential_vm());
+    assert_eq!(
+        manager.host_facts.check_security_framework("cvm"),
+        is_confidential_vm()
+    );
 }
 
 #[test]
@@ -126,11 +138,11 @@ fn needs_update_matches_update_flag_timestamps() {
     let manager = Manager::new();
 
     assert_eq!(
-        manager.check_needs_update("/etc", false),
+        manager.host_facts.check_needs_update("/etc", false),
         expected_needs_update("/etc", "/var/lib/systemd/update-done.d/etc", false)
     );
     assert_eq!(
-        manager.check_needs_update("/var", true),
+        manager.host_facts.check_needs_update("/var", true),
         expected_needs_update("/var", "/var/lib/systemd/update-done.d/var", true)
     );
 }
@@ -152,3 +164,24 @@ fn expected_needs_update(path: &str, flag_file: &str, trigger: bool) -> bool {
 
     dir_mtime > flag_mtime
 }
+
+#[test]
+fn condition_checks_use_cached_host_facts_until_refreshed() {
+    let mut manager = Manager::new();
+    manager.host_facts = HostFacts {
+        virtualization: Some(VirtualizationType::Qemu),
+        effective_caps: Some(1 << 21),
+        kernel_cmdline: "quiet sysd.cached=1".to_string(),
+    };
+
+    assert_eq!(
+        manager.detect_virtualization(),
+        Some(VirtualizationType::Qemu)
+    );
+    assert!(manager.host_facts.check_capability("CAP_SYS_ADMIN"));
+    assert!(!manager.host_facts.check_capability("CAP_CHOWN"));
+    assert!(manager.host_facts.check_kernel_cmdline("sysd.cached"));
+
+    manager.refresh_host_facts();
+    assert!(!manager.host_facts.check_kernel_cmdline("sysd.cached"));
+}
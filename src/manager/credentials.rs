@@ -0,0 +1,437 @@
+//! Service credentials (LoadCredential=, SetCredential=, ImportCredential=)
+//!
+//! Like systemd, credentials are collected when a service starts and written
+//! to a per-service directory below /run/credentials. The system manager
+//! mounts a private ramfs there (tmpfs with noswap if ramfs is unavailable),
+//! nosuid, nodev, noexec and 0700, so credentials never reach swap or disk and
+//! no other unit shares the file system. The files are read-only for the
+//! service user and the directory is exported as $CREDENTIALS_DIRECTORY. The
+//! file system is unmounted and the directory removed when the unit stops.
+//!
+//! Sources in order of precedence: LoadCredential= and LoadCredentialEncrypted=
+//! (falling back to a SetCredential= of the same ID when the file cannot be
+//! read or decrypted), then SetCredential=, then ImportCredential= from the
+//! manager's own credentials. Encrypted credentials are opened with the host
+//! key (see `crate::creds`).
+
+use std::collections::BTreeMap;
+use std::os::unix::fs::{chown, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use nix::mount::{mount, umount2, MntFlags, MsFlags};
+
+use crate::creds;
+use crate::units::{Service, ServiceSection};
+
+use super::process::{self, SpawnOptions};
+use super::{Manager, ManagerError};
+
+const SYSTEM_CREDENTIALS_ROOT: &str = "/run/credentials";
+/// Credentials passed to the system manager itself
+const SYSTEM_MANAGER_CREDENTIALS: &str = "/run/credentials/@system";
+
+/// Whether the service uses any credential directive
+fn has_credentials(service: &ServiceSection) -> bool {
+    !service.load_credential.is_empty()
+        || !service.load_credential_encrypted.is_empty()
+        || !service.set_credential.is_empty()
+        || !service.import_credential.is_empty()
+}
+
+/// Read every credential of `service` as (ID, contents), sorted by ID
+fn collect_credentials(
+    service: &ServiceSection,
+    manager_dir: Option<&Path>,
+    host_key_path: &Path,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let mut credentials = BTreeMap::new();
+
+    for (id, value) in &service.set_credential {
+        credentials.insert(id.clone(), value.clone().into_bytes());
+    }
+
+    let plain = service.load_credential.iter().map(|entry| (entry, false));
+    let encrypted = service
+        .load_credential_encrypted
+        .iter()
+        .map(|entry| (entry, true));
+    for ((id, source), is_encrypted) in plain.chain(encrypted) {
+        let path = match (source.is_empty(), manager_dir) {
+            (false, _) if Path::new(source).is_absolute() => PathBuf::from(source),
+            (true, Some(dir)) => dir.join(id),
+            (false, Some(dir)) => dir.join(source),
+            (_, None) => {
+                if credentials.contains_key(id) {
+                    continue;
+                }
+                return Err(format!("{}: no manager credentials to load from", id));
+            }
+        };
+        let loaded = load_credential_path(id, &path)
+            .map_err(|e| e.to_string())
+            .and_then(|loaded| match is_encrypted {
+                true => decrypt_credentials(loaded, host_key_path),
+                false => Ok(loaded),
+            });
+        match loaded {
+            Ok(loaded) => credentials.extend(loaded),
+            // SetCredential= of the same ID is the fallback
+            Err(_) if credentials.contains_key(id) => {
+                log::debug!(
+                    "Credential {}: {} unreadable, using SetCredential=",
+                    id,
+                    path.display()
+                );
+            }
+            Err(e) => return Err(format!("{}: {}: {}", id, path.display(), e)),
+        }
+    }
+
+    if let Some(dir) = manager_dir.filter(|_| !service.import_credential.is_empty()) {
+        for (id, contents) in import_credentials(dir, &service.import_credential) {
+            credentials.entry(id).or_insert(contents);
+        }
+    }
+
+    Ok(credentials.into_iter().collect())
+}
+
+fn decrypt_credentials(
+    sealed: Vec<(String, Vec<u8>)>,
+    host_key_path: &Path,
+) -> Result<Vec<(String, Vec<u8>)>, String> {
+    let host_key = creds::load_host_key(host_key_path).map_err(|e| e.to_string())?;
+    sealed
+        .into_iter()
+        .map(|(id, data)| {
+            creds::decrypt(&host_key, Some(&id), &data)
+                .map(|plaintext| (id, plaintext))
+                .map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+/// A file is one credential; a directory yields one credential per regular
+/// file, named ID_filename
+fn load_credential_path(id: &str, path: &Path) -> std::io::Result<Vec<(String, Vec<u8>)>> {
+    if !path.is_dir() {
+        return Ok(vec![(id.to_string(), std::fs::read(path)?)]);
+    }
+    let mut loaded = Vec::new();
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        loaded.push((format!("{}_{}", id, name), std::fs::read(entry.path())?));
+    }
+    Ok(loaded)
+}
+
+fn import_credentials(dir: &Path, patterns: &[String]) -> Vec<(String, Vec<u8>)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let patterns: Vec<glob::Pattern> = patterns
+        .iter()
+        .filter_map(|pattern| glob::Pattern::new(pattern).ok())
+        .collect();
+    entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_file()))
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !patterns.iter().any(|pattern| pattern.matches(&name)) {
+                return None;
+            }
+            std::fs::read(entry.path())
+                .ok()
+                .map(|contents| (name, contents))
+        })
+        .collect()
+}
+
+/// Whether `dir` is the root of a file system mounted on it
+fn is_mount_point(dir: &Path) -> bool {
+    let (Ok(own), Some(Ok(parent))) = (
+        std::fs::symlink_metadata(dir),
+        dir.parent().map(std::fs::symlink_metadata),
+    ) else {
+        return false;
+    };
+    own.is_dir() && own.dev() != parent.dev()
+}
+
+/// Mount a private ramfs on `dir` for one unit's credentials, or tmpfs with
+/// noswap where ramfs cannot be mounted; left as is if already mounted (a
+/// restart)
+fn mount_credentials_fs(dir: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    if is_mount_point(dir) {
+        return Ok(());
+    }
+    let flags = MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC;
+    mount(Some("ramfs"), dir, Some("ramfs"), flags, Some("mode=0700"))
+        .or_else(|_| {
+            mount(
+                Some("tmpfs"),
+                dir,
+                Some("tmpfs"),
+                flags,
+                Some("mode=0700,noswap"),
+            )
+        })
+        .map_err(std::io::Error::from)
+}
+
+/// Replace the contents of `dir` with `credentials`: files 0400 and the
+/// directory 0500, both owned by the service user
+fn write_credentials(
+    dir: &Path,
+    credentials: &[(String, Vec<u8>)],
+    uid: Option<u32>,
+    gid: Option<u32>,
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    // The directory itself may be a mount point, so only its entries go
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        match std::fs::symlink_metadata(&path)?.is_dir() {
+            true => std::fs::remove_dir_all(&path)?,
+            false => std::fs::remove_file(&path)?,
+        }
+    }
+    for (id, contents) in credentials {
+        let path = dir.join(id);
+        std::fs::write(&path, contents)?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o400))?;
+        if uid.is_some() || gid.is_some() {
+            chown(&path, uid, gid)?;
+        }
+    }
+    if uid.is_some() || gid.is_some() {
+        chown(dir, uid, gid)?;
+    }
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o500))?;
+    Ok(())
+}
+
+impl Manager {
+    fn credentials_root(&self) -> PathBuf {
+        if !self.user_mode {
+            return crate::root::path(SYSTEM_CREDENTIALS_ROOT);
+        }
+        let runtime_dir = std::env::var("XDG_RUNTIME_DIR")
+            .unwrap_or_else(|_| format!("/run/user/{}", nix::unistd::getuid()));
+        Path::new(&runtime_dir).join("credentials")
+    }
+
+    /// $CREDENTIALS_DIRECTORY of `name`, if its unit uses credentials
+    fn credentials_directory(&self, name: &str, service: &ServiceSection) -> Option<PathBuf> {
+        has_credentials(service).then(|| self.credentials_root().join(name))
+    }
+
+    /// Credentials the manager itself received; LoadCredential=ID and
+    /// ImportCredential= read from here
+    fn manager_credentials_dir(&self) -> Option<PathBuf> {
+        match std::env::var_os("CREDENTIALS_DIRECTORY") {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None if self.user_mode => None,
+            None => Some(crate::root::path(SYSTEM_MANAGER_CREDENTIALS)),
+        }
+    }
+
+    /// Collect and write the credentials of a starting service; returns the
+    /// directory to export as $CREDENTIALS_DIRECTORY
+    pub(super) fn setup_service_credentials(
+        &self,
+        name: &str,
+        service: &Service,
+        options: &SpawnOptions,
+    ) -> Result<Option<PathBuf>, ManagerError> {
+        let Some(dir) = self.credentials_directory(name, &service.service) else {
+            return Ok(None);
+        };
+        let manager_dir = self.manager_credentials_dir();
+        let host_key = crate::state_dir::StateDirs::system().credential_secret();
+        let credentials = collect_credentials(&service.service, manager_dir.as_deref(), &host_key)
+            .map_err(|e| ManagerError::StartFailed(format!("{}: credential {}", name, e)))?;
+        let (uid, gid) = process::resolve_uid_gid(service, options);
+        if !self.user_mode && nix::unistd::geteuid().is_root() {
+            mount_credentials_fs(&dir).map_err(|e| {
+                ManagerError::StartFailed(format!(
+                    "{}: cannot mount credentials on {}: {}",
+                    name,
+                    dir.display(),
+                    e
+                ))
+            })?;
+        }
+        write_credentials(&dir, &credentials, uid, gid)?;
+        log::debug!(
+            "Wrote {} credentials to {}",
+            credentials.len(),
+            dir.display()
+        );
+        Ok(Some(dir))
+    }
+
+    /// Unmount and remove the credentials directory of a stopped unit
+    pub(super) fn remove_credentials_after_stop(&self, name: &str) {
+        let dir = self.credentials_root().join(name);
+        if !dir.exists() {
+            return;
+        }
+        if is_mount_point(&dir) {
+            // The ramfs takes the credentials with it
+            if let Err(e) = umount2(&dir, MntFlags::MNT_DETACH) {
+                log::warn!("Failed to unmount {}: {}", dir.display(), e);
+            }
+        }
+        let _ = std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700));
+        match std::fs::remove_dir_all(&dir) {
+            Ok(()) => log::debug!("Removed credentials of {}", name),
+            Err(e) => log::warn!("Failed to remove {}: {}", dir.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Service;
+
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::set_permissions(&self.0, std::fs::Permissions::from_mode(0o700));
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn temp_dir(label: &str) -> TempDir {
+        let dir =
+            std::env::temp_dir().join(format!("sysd-credentials-{}-{}", label, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    fn section(configure: impl FnOnce(&mut ServiceSection)) -> ServiceSection {
+        let mut service = Service::new("creds.service".to_string());
+        configure(&mut service.service);
+        service.service
+    }
+
+    fn entry(id: &str, value: &str) -> (String, String) {
+        (id.to_string(), value.to_string())
+    }
+
+    const NO_KEY: &Path = Path::new("/nonexistent/credential.secret");
+
+    #[test]
+    fn load_beats_set_and_import_fills_remaining_ids() {
+        let root = temp_dir("collect");
+        let manager_dir = root.0.join("manager");
+        std::fs::create_dir_all(&manager_dir).unwrap();
+        std::fs::write(root.0.join("key.pem"), "secret").unwrap();
+        std::fs::write(manager_dir.join("token"), "from-manager").unwrap();
+        std::fs::write(manager_dir.join("demo.a"), "a").unwrap();
+        std::fs::write(manager_dir.join("other"), "skip").unwrap();
+        let key_path = root.0.join("key.pem").display().to_string();
+
+        let service = section(|service| {
+            service.load_credential = vec![
+                entry("key", &key_path),
+                entry("token", ""),
+                entry("fallback", "/nonexistent/file"),
+            ];
+            service.set_credential = vec![entry("key", "ignored"), entry("fallback", "inline")];
+            service.import_credential = vec!["demo.*".to_string(), "token".to_string()];
+        });
+
+        let credentials = collect_credentials(&service, Some(&manager_dir), NO_KEY).unwrap();
+        assert_eq!(
+            credentials,
+            [
+                ("demo.a".to_string(), b"a".to_vec()),
+                ("fallback".to_string(), b"inline".to_vec()),
+                ("key".to_string(), b"secret".to_vec()),
+                ("token".to_string(), b"from-manager".to_vec()),
+            ]
+        );
+
+        let missing =
+            section(|service| service.load_credential = vec![entry("gone", "/nonexistent")]);
+        assert!(collect_credentials(&missing, Some(&manager_dir), NO_KEY).is_err());
+    }
+
+    #[test]
+    fn encrypted_credentials_are_opened_with_the_host_key() {
+        let root = temp_dir("encrypted");
+        let host_key = root.0.join("credential.secret");
+        let key = creds::load_or_create_host_key(&host_key).unwrap();
+        let sealed = root.0.join("db.cred");
+        std::fs::write(&sealed, creds::encrypt(&key, "db", b"hunter2").unwrap()).unwrap();
+        std::fs::write(
+            root.0.join("wrong.cred"),
+            creds::encrypt(&key, "other", b"x").unwrap(),
+        )
+        .unwrap();
+        let source = |name: &str| root.0.join(name).display().to_string();
+
+        let service = section(|service| {
+            service.load_credential_encrypted = vec![entry("db", &source("db.cred"))];
+        });
+        assert_eq!(
+            collect_credentials(&service, None, &host_key).unwrap(),
+            [("db".to_string(), b"hunter2".to_vec())]
+        );
+        assert!(collect_credentials(&service, None, NO_KEY).is_err());
+
+        let renamed = section(|service| {
+            service.load_credential_encrypted = vec![entry("db", &source("wrong.cred"))];
+        });
+        assert!(collect_credentials(&renamed, None, &host_key).is_err());
+    }
+
+    #[test]
+    fn directory_sources_load_each_file() {
+        let root = temp_dir("dir-source");
+        std::fs::write(root.0.join("cert"), "c").unwrap();
+        std::fs::write(root.0.join("key"), "k").unwrap();
+        let source = root.0.display().to_string();
+        let service = section(|service| service.load_credential = vec![entry("tls", &source)]);
+
+        let credentials = collect_credentials(&service, None, NO_KEY).unwrap();
+        assert_eq!(
+            credentials,
+            [
+                ("tls_cert".to_string(), b"c".to_vec()),
+                ("tls_key".to_string(), b"k".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_credentials_replaces_directory_with_read_only_files() {
+        let root = temp_dir("write");
+        let dir = root.0.join("creds.service");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("stale"), "old").unwrap();
+
+        write_credentials(&dir, &[("token".to_string(), b"abc".to_vec())], None, None).unwrap();
+
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(std::fs::read(dir.join("token")).unwrap(), b"abc");
+        assert_eq!(mode(&dir.join("token")), 0o400);
+        assert_eq!(mode(&dir), 0o500);
+        assert!(!dir.join("stale").exists());
+        assert!(!is_mount_point(&dir));
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+//! Releasing what the manager holds when the daemon exits
+//!
+//! Outside PID 1 sysd can exit and leave its services running (SIGTERM
+//! without --stop-all). Socket, timer and path watchers would otherwise keep
+//! listening sockets bound until the process is gone and could still start a
+//! unit halfway through the exit, and the notify socket would be left behind
+//! in /run.
+
+use std::os::unix::io::RawFd;
+
+use tokio::task::AbortHandle;
+
+use crate::units::{Socket, Unit};
+
+use super::Manager;
+
+impl Manager {
+    /// Remember a socket, timer or path watcher task of unit `name`
+    pub(super) fn track_unit_task(&mut self, name: &str, task: AbortHandle) {
+        let tasks = self.unit_tasks.entry(name.to_string()).or_default();
+        tasks.retain(|task| !task.is_finished());
+        tasks.push(task);
+    }
+
+    /// Cancel the watcher tasks of unit `name`
+    pub(super) fn abort_unit_tasks(&mut self, name: &str) {
+        for task in self.unit_tasks.remove(name).into_iter().flatten() {
+            task.abort();
+        }
+    }
+
+    /// Cancel every watcher and background mount, close listening sockets and
+    /// remove the notify socket
+    ///
+    /// Services are left alone. Watchdog deadlines are dropped so nothing is
+    /// killed for a missed ping while the daemon goes away.
+    pub fn release_for_exit(&mut self) {
+        for (_, tasks) in self.unit_tasks.drain() {
+            tasks.iter().for_each(AbortHandle::abort);
+        }
+        for (_, job) in self.mount_jobs.drain() {
+            job.abort();
+        }
+
+        let sockets: Vec<(String, Socket)> = self
+            .socket_fds
+            .keys()
+            .filter_map(|name| match self.units.get(name) {
+                Some(Unit::Socket(socket)) => Some((name.clone(), socket.clone())),
+                _ => None,
+            })
+            .collect();
+        for (name, socket) in &sockets {
+            self.close_listeners(name, socket);
+        }
+        let leftover = self.socket_fds.drain().flat_map(|(_, fds)| fds);
+        let connections = self.connection_fds.drain().map(|(_, fd)| fd);
+        for fd in leftover.chain(connections).collect::<Vec<RawFd>>() {
+            unsafe { libc::close(fd) };
+        }
+        self.armed_sockets.clear();
+
+        self.watchdog_deadlines.clear();
+        self.notify_rx = None;
+        // Dropping the listener stops its receiver and removes the socket file
+        self.notify_listener = None;
+        log::info!("Released sockets and watchers for exit");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::AsyncNotifyListener;
+
+    #[tokio::test]
+    async fn release_for_exit_cancels_watchers_and_removes_notify_socket() {
+        let mut manager = Manager::new_user();
+        let notify_path = std::env::temp_dir().join(format!(
+            "sysd-daemon-exit-notify-{}.sock",
+            std::process::id()
+        ));
+        let (listener, rx) = AsyncNotifyListener::new(&notify_path).unwrap();
+        manager.notify_listener = Some(listener);
+        manager.notify_rx = Some(rx);
+        let watcher = tokio::spawn(std::future::pending::<()>());
+        manager.track_unit_task("demo.timer", watcher.abort_handle());
+        let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0) };
+        assert!(fd >= 0);
+        manager
+            .socket_fds
+            .insert("demo.socket".to_string(), vec![fd]);
+        manager.armed_sockets.insert("demo.socket".to_string());
+        manager
+            .watchdog_deadlines
+            .insert("demo.service".to_string(), std::time::Instant::now());
+
+        manager.release_for_exit();
+
+        assert!(watcher.await.unwrap_err().is_cancelled());
+        assert!(manager.unit_tasks.is_empty());
+        assert!(manager.socket_fds.is_empty());
+        assert!(manager.armed_sockets.is_empty());
+        assert!(manager.watchdog_deadlines.is_empty());
+        assert!(manager.notify_socket_path().is_none());
+        assert!(!notify_path.exists());
+    }
+}
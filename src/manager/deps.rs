@@ -1,22 +1,98 @@
 //! Dependency resolution for unit ordering
 //!
-//! Builds a directed acyclic graph from unit dependencies and performs
-//! topological sort to determine start order.
+//! Builds a directed graph from unit dependencies and performs topological
+//! sort to determine start order. Ordering cycles are broken like systemd
+//! does: edges that only exist because of Wants= go first, then plain
+//! After=/Before= ordering; a cycle made only of Requires= edges is an error.
+//!
+//! The graph also keeps a reverse index of requirement dependencies (which
+//! units have Requires=, BindsTo=, ... on a unit), maintained as units are
+//! indexed and unindexed, so dependents are found without a scan.
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+
+use crate::units::{Service, Unit, UnitSection};
+
+/// Why one unit is ordered after another, weakest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    /// Wants= (or a .wants/ directory) implying After=
+    Wants,
+    /// Explicit or default After=/Before=
+    Order,
+    /// Requires= implying After=
+    Requires,
+}
+
+impl std::fmt::Display for EdgeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EdgeKind::Wants => "Wants=",
+            EdgeKind::Order => "After=",
+            EdgeKind::Requires => "Requires=",
+        })
+    }
+}
+
+/// A dependency the reverse index answers for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum DependencyType {
+    /// Requires= or a .requires/ directory
+    Requires,
+    Requisite,
+    /// Wants= or a .wants/ directory
+    Wants,
+    BindsTo,
+    PartOf,
+    Conflicts,
+}
 
-use std::collections::{HashMap, HashSet, VecDeque};
+impl DependencyType {
+    pub const ALL: [DependencyType; 6] = [
+        DependencyType::Requires,
+        DependencyType::Requisite,
+        DependencyType::Wants,
+        DependencyType::BindsTo,
+        DependencyType::PartOf,
+        DependencyType::Conflicts,
+    ];
+
+    /// The units `section` depends on this way
+    fn targets(self, section: &UnitSection) -> Vec<&String> {
+        match self {
+            DependencyType::Requires => section
+                .requires
+                .iter()
+                .chain(&section.requires_dir)
+                .collect(),
+            DependencyType::Requisite => section.requisite.iter().collect(),
+            DependencyType::Wants => section.wants.iter().chain(&section.wants_dir).collect(),
+            DependencyType::BindsTo => section.binds_to.iter().collect(),
+            DependencyType::PartOf => section.part_of.iter().collect(),
+            DependencyType::Conflicts => section.conflicts.iter().collect(),
+        }
+    }
+}
+
+type Edges = HashMap<String, HashMap<String, EdgeKind>>;
 
-use crate::units::{Service, Unit};
+/// unit -> dependency type -> units depending on it that way
+type ReverseIndex = HashMap<String, HashMap<DependencyType, BTreeSet<String>>>;
 
 /// Dependency graph for ordering service startup
 #[derive(Debug, Default)]
 pub struct DepGraph {
     /// Edges: node -> nodes that must start BEFORE this node
-    /// (i.e., this node is After= those nodes)
-    edges: HashMap<String, HashSet<String>>,
+    /// (i.e., this node is After= those nodes), with the strongest reason
+    edges: Edges,
     /// All known nodes
     nodes: HashSet<String>,
     /// Alias resolution: symlink name -> canonical name
     aliases: HashMap<String, String>,
+    /// Reverse index of the units passed to `index_unit`
+    dependents: ReverseIndex,
+    /// What each indexed unit depends on (resolved), to unindex it
+    indexed: HashMap<String, Vec<(DependencyType, String)>>,
 }
 
 impl DepGraph {
@@ -26,12 +102,72 @@ impl DepGraph {
 
     /// Register an alias (symlink name -> canonical name)
     pub fn add_alias(&mut self, alias: &str, canonical: &str) {
-        if alias != canonical {
-            self.aliases
-                .insert(alias.to_string(), canonical.to_string());
+        if alias == canonical {
+            return;
+        }
+        self.aliases
+            .insert(alias.to_string(), canonical.to_string());
+        // Dependents indexed under the alias now depend on the canonical unit
+        let Some(by_type) = self.dependents.remove(alias) else {
+            return;
+        };
+        for (kind, names) in by_type {
+            for name in &names {
+                let targets = self.indexed.get_mut(name).into_iter().flatten();
+                for (_, target) in targets.filter(|(_, target)| target == alias) {
+                    *target = canonical.to_string();
+                }
+            }
+            let entry = self.dependents.entry(canonical.to_string()).or_default();
+            entry.entry(kind).or_default().extend(names);
         }
     }
 
+    /// Add `unit`, loaded as `name`, to the reverse index, replacing what
+    /// was indexed for `name` before
+    pub fn index_unit(&mut self, name: &str, unit: &Unit) {
+        self.unindex_unit(name);
+        let section = unit.unit_section();
+        let mut targets = Vec::new();
+        for kind in DependencyType::ALL {
+            for target in kind.targets(section) {
+                let target = self.resolve(target);
+                let entry = self.dependents.entry(target.clone()).or_default();
+                entry.entry(kind).or_default().insert(name.to_string());
+                targets.push((kind, target));
+            }
+        }
+        self.indexed.insert(name.to_string(), targets);
+    }
+
+    /// Drop `name` from the reverse index (unit unloaded or replaced)
+    pub fn unindex_unit(&mut self, name: &str) {
+        for (kind, target) in self.indexed.remove(name).unwrap_or_default() {
+            let Some(by_type) = self.dependents.get_mut(&target) else {
+                continue;
+            };
+            if let Some(names) = by_type.get_mut(&kind) {
+                names.remove(name);
+                if names.is_empty() {
+                    by_type.remove(&kind);
+                }
+            }
+            if by_type.is_empty() {
+                self.dependents.remove(&target);
+            }
+        }
+    }
+
+    /// Indexed units that depend on `name` (or a name aliased to it) as
+    /// `kind`, sorted by name
+    pub fn dependents(&self, name: &str, kind: DependencyType) -> impl Iterator<Item = &String> {
+        self.dependents
+            .get(&self.resolve(name))
+            .and_then(|by_type| by_type.get(&kind))
+            .into_iter()
+            .flatten()
+    }
+
     /// Pre-register a node (unit that was loaded)
     pub fn add_node(&mut self, name: &str) {
         self.nodes.insert(name.to_string());
@@ -53,7 +189,7 @@ impl DepGraph {
         // After=X means X must start before us
         // So we have an edge: name depends on X
         for dep in &service.unit.after {
-            self.add_edge(name, dep);
+            self.add_edge(name, dep, EdgeKind::Order);
         }
 
         // Before=X means we must start before X
@@ -64,11 +200,11 @@ impl DepGraph {
         // Requires=X and Wants=X imply After=X for ordering purposes
         // (though Requires also means fail if X fails)
         for dep in &service.unit.requires {
-            self.add_edge(name, dep);
+            self.add_edge(name, dep, EdgeKind::Requires);
         }
 
         for dep in &service.unit.wants {
-            self.add_edge(name, dep);
+            self.add_edge(name, dep, EdgeKind::Wants);
         }
     }
 
@@ -88,44 +224,75 @@ impl DepGraph {
         }
 
         for dep in &section.after {
-            self.add_edge(name, dep);
+            self.add_edge(name, dep, EdgeKind::Order);
         }
         for dep in &section.before {
             self.add_reverse_edge(name, dep);
         }
         for dep in &section.requires {
-            self.add_edge(name, dep);
+            self.add_edge(name, dep, EdgeKind::Requires);
         }
         for dep in &section.wants {
-            self.add_edge(name, dep);
+            self.add_edge(name, dep, EdgeKind::Wants);
         }
         for dep in unit.wants_dir() {
-            self.add_edge(name, dep);
+            self.add_edge(name, dep, EdgeKind::Wants);
+        }
+        for dep in unit.requires_dir() {
+            self.add_edge(name, dep, EdgeKind::Requires);
+        }
+        self.add_socket_dependencies(name, unit);
+    }
+
+    /// Implicit dependencies between sockets and their services: a socket
+    /// starts Before= the service it activates, and a service Requires= and
+    /// is After= the sockets of its Sockets= (without it, the socket of the
+    /// same name). Unlike default dependencies these are always added.
+    fn add_socket_dependencies(&mut self, name: &str, unit: &Unit) {
+        match unit {
+            // Accept=yes instances are not started through the graph
+            Unit::Socket(socket) if !socket.socket.accept => {
+                self.add_reverse_edge(name, &socket.service_name());
+            }
+            Unit::Service(service) if service.service.sockets.is_empty() => {
+                if let Some(base) = name.strip_suffix(".service") {
+                    self.add_edge(name, &format!("{}.socket", base), EdgeKind::Requires);
+                }
+            }
+            Unit::Service(service) => {
+                for socket in &service.service.sockets {
+                    self.add_edge(name, socket, EdgeKind::Requires);
+                }
+            }
+            _ => {}
         }
     }
 
     /// Add implicit ordering dependencies based on unit type
     fn add_default_dependencies(&mut self, name: &str, unit: &Unit) {
         if unit.is_socket() {
-            self.add_edge(name, "sysinit.target");
+            self.add_edge(name, "sysinit.target", EdgeKind::Order);
             self.add_reverse_edge(name, "sockets.target");
         } else {
-            self.add_edge(name, "basic.target");
+            self.add_edge(name, "basic.target", EdgeKind::Order);
         }
         self.add_reverse_edge(name, "shutdown.target");
     }
 
     /// Add a directed edge: `from` depends on `to` (to must start first)
     /// Only creates edge if `to` is already a known node (loaded unit)
-    fn add_edge(&mut self, from: &str, to: &str) {
+    fn add_edge(&mut self, from: &str, to: &str, kind: EdgeKind) {
         let resolved_to = self.resolve(to);
         if !self.nodes.contains(&resolved_to) {
             return;
         }
-        self.edges
+        let existing = self
+            .edges
             .entry(from.to_string())
             .or_default()
-            .insert(resolved_to);
+            .entry(resolved_to)
+            .or_insert(kind);
+        *existing = (*existing).max(kind);
     }
 
     /// Add a reverse edge: `dependent` must start before `target`
@@ -136,13 +303,14 @@ impl DepGraph {
             self.edges
                 .entry(resolved)
                 .or_default()
-                .insert(dependent.to_string());
+                .entry(dependent.to_string())
+                .or_insert(EdgeKind::Order);
         }
     }
 
     /// Get direct dependencies of a node (nodes that must start before it)
     pub fn dependencies(&self, name: &str) -> impl Iterator<Item = &String> {
-        self.edges.get(name).into_iter().flat_map(|s| s.iter())
+        self.edges.get(name).into_iter().flat_map(|s| s.keys())
     }
 
     /// Topological sort using Kahn's algorithm
@@ -182,11 +350,9 @@ impl DepGraph {
         }
 
         while let Some(node) = to_visit.pop_front() {
-            if let Some(deps) = self.edges.get(&node) {
-                for dep in deps {
-                    if self.nodes.contains(dep) && needed.insert(dep.clone()) {
-                        to_visit.push_back(dep.clone());
-                    }
+            for dep in self.dependencies(&node) {
+                if self.nodes.contains(dep) && needed.insert(dep.clone()) {
+                    to_visit.push_back(dep.clone());
                 }
             }
         }
@@ -194,21 +360,19 @@ impl DepGraph {
         needed
     }
 
-    /// Toposort a subset of the graph, breaking cycles if needed
+    /// Toposort a subset of the graph, deleting edges to break cycles
     fn toposort_subset(&self, subset: &HashSet<String>) -> Result<Vec<String>, CycleError> {
+        let mut edges = self.edges.clone();
         let mut in_degree = self.compute_in_degree(subset);
         let mut result = Vec::new();
 
         loop {
-            let before = result.len();
-            kahn_drain(&self.edges, &mut in_degree, &mut result);
+            kahn_drain(&edges, &mut in_degree, &mut result);
 
             if result.len() >= subset.len() {
                 break;
             }
-            if result.len() == before && !break_cycle(&mut in_degree, &result) {
-                break;
-            }
+            break_cycle(&mut edges, &mut in_degree, &result)?;
         }
 
         Ok(result)
@@ -220,7 +384,7 @@ impl DepGraph {
 
         for (from, deps) in &self.edges {
             if nodes.contains(from) {
-                let count = deps.iter().filter(|d| nodes.contains(*d)).count();
+                let count = deps.keys().filter(|d| nodes.contains(*d)).count();
                 *in_degree.entry(from.clone()).or_default() = count;
             }
         }
@@ -230,11 +394,7 @@ impl DepGraph {
 }
 
 /// Run Kahn's BFS: pop zero-in-degree nodes, decrement dependents
-fn kahn_drain(
-    edges: &HashMap<String, HashSet<String>>,
-    in_degree: &mut HashMap<String, usize>,
-    result: &mut Vec<String>,
-) {
+fn kahn_drain(edges: &Edges, in_degree: &mut HashMap<String, usize>, result: &mut Vec<String>) {
     let mut emitted: HashSet<String> = result.iter().cloned().collect();
     let mut queued: HashSet<String> = HashSet::new();
     let mut queue = initial_zero_in_degree_queue(in_degree, &emitted, &mut queued);
@@ -267,7 +427,7 @@ fn initial_zero_in_degree_queue(
 }
 
 fn queue_newly_unblocked_nodes(
-    edges: &HashMap<String, HashSet<String>>,
+    edges: &Edges,
     in_degree: &mut HashMap<String, usize>,
     node: &str,
     emitted: &HashSet<String>,
@@ -275,7 +435,7 @@ fn queue_newly_unblocked_nodes(
     queue: &mut VecDeque<String>,
 ) {
     for (dependent, deps) in edges {
-        if !deps.contains(node) || emitted.contains(dependent) {
+        if !deps.contains_key(node) || emitted.contains(dependent) {
             continue;
         }
         let Some(degree) = in_degree.get_mut(dependent) else {
@@ -288,36 +448,88 @@ fn queue_newly_unblocked_nodes(
     }
 }
 
-/// Break a cycle by picking the best candidate to start early.
-/// Returns false if no candidates remain.
-fn break_cycle(in_degree: &mut HashMap<String, usize>, result: &[String]) -> bool {
-    let remaining: Vec<_> = in_degree
+/// Break one ordering cycle among the nodes Kahn's algorithm got stuck on
+/// by deleting its weakest edge.
+///
+/// Wants= edges go before After= edges; among equally weak edges, the one
+/// whose dependent should start earliest by unit type is deleted. A cycle
+/// made only of Requires= edges cannot be broken.
+fn break_cycle(
+    edges: &mut Edges,
+    in_degree: &mut HashMap<String, usize>,
+    result: &[String],
+) -> Result<(), CycleError> {
+    let cycle = find_cycle(edges, in_degree, result);
+    let links: Vec<(&String, &String, EdgeKind)> = cycle
         .iter()
-        .filter(|(n, &deg)| deg > 0 && !result.contains(n))
+        .zip(cycle.iter().cycle().skip(1))
+        .map(|(from, to)| (from, to, edges[from][to]))
         .collect();
 
-    if remaining.is_empty() {
-        return false;
-    }
-
-    let (cycle_node, _) = remaining
+    let (from, to, kind) = links
         .iter()
-        .min_by_key(|(name, &deg)| (unit_type_priority(name), deg))
-        .unwrap();
+        .min_by_key(|&&(from, _, kind)| (kind, unit_type_priority(from), from))
+        .map(|&(from, to, kind)| (from.clone(), to.clone(), kind))
+        .ok_or_else(|| CycleError {
+            nodes: cycle.clone(),
+        })?;
+    if kind == EdgeKind::Requires {
+        log::error!("Ordering cycle of required units: {}", cycle.join(" -> "));
+        return Err(CycleError { nodes: cycle });
+    }
 
-    let cycle_units: Vec<_> = remaining.iter().map(|(n, _)| n.as_str()).collect();
     log::warn!(
-        "Breaking ordering cycle: starting {} early (cycle involves: {})",
-        cycle_node,
-        cycle_units.join(", ")
+        "Breaking ordering cycle {}: deleted {} edge {} -> {}",
+        cycle.join(" -> "),
+        kind,
+        from,
+        to
     );
     eprintln!(
-        "sysd: WARNING: Breaking ordering cycle by starting {} early",
-        cycle_node
+        "sysd: WARNING: Breaking ordering cycle by dropping {}{} of {}",
+        kind, to, from
     );
 
-    in_degree.insert(cycle_node.to_string(), 0);
-    true
+    if let Some(deps) = edges.get_mut(&from) {
+        deps.remove(&to);
+    }
+    if let Some(degree) = in_degree.get_mut(&from) {
+        *degree = degree.saturating_sub(1);
+    }
+    Ok(())
+}
+
+/// A cycle among the blocked nodes, each node depending on the next and
+/// the last on the first. Every blocked node still waits on another
+/// blocked node, so following those dependencies must revisit a node.
+fn find_cycle(edges: &Edges, in_degree: &HashMap<String, usize>, result: &[String]) -> Vec<String> {
+    let blocked =
+        |name: &String| in_degree.get(name).is_some_and(|&deg| deg > 0) && !result.contains(name);
+    let Some(start) = in_degree
+        .keys()
+        .filter(|name| blocked(*name))
+        .min_by_key(|&name| (unit_type_priority(name), name))
+    else {
+        return Vec::new();
+    };
+
+    let mut path: Vec<String> = Vec::new();
+    let mut current = start.clone();
+    while !path.contains(&current) {
+        let next = edges
+            .get(&current)
+            .into_iter()
+            .flat_map(|deps| deps.keys())
+            .filter(|dep| blocked(*dep))
+            .min();
+        path.push(current);
+        match next {
+            Some(next) => current = next.clone(),
+            None => return Vec::new(),
+        }
+    }
+    let start = path.iter().position(|name| *name == current).unwrap_or(0);
+    path.split_off(start)
 }
 
 /// Priority for cycle breaking: lower = start earlier
@@ -385,7 +597,7 @@ mod tests {
         graph.add_alias("alias.service", "canonical.service");
         graph.add_alias("same.service", "same.service");
 
-        graph.add_edge("consumer.service", "alias.service");
+        graph.add_edge("consumer.service", "alias.service", EdgeKind::Order);
 
         let deps: Vec<&str> = graph
             .dependencies("consumer.service")
@@ -433,6 +645,43 @@ mod tests {
         assert!(graph.dependencies("multi-user.target").next().is_none());
     }
 
+    #[test]
+    fn sockets_start_before_the_services_that_require_them() {
+        let mut graph = DepGraph::new();
+        for name in [
+            "api.socket",
+            "api.service",
+            "web-http.socket",
+            "web-https.socket",
+            "web.service",
+        ] {
+            graph.add_node(name);
+        }
+        let mut units = vec![
+            Unit::Socket(Socket::new("api.socket".to_string())),
+            Unit::Service(Service::new("api.service".to_string())),
+        ];
+        for name in ["web-http.socket", "web-https.socket"] {
+            let mut socket = Socket::new(name.to_string());
+            socket.socket.service = Some("web.service".to_string());
+            units.push(Unit::Socket(socket));
+        }
+        let mut web = Service::new("web.service".to_string());
+        web.service.sockets = vec!["web-http.socket".into(), "web-https.socket".into()];
+        units.push(Unit::Service(web));
+        for unit in &units {
+            graph.add_unit(unit);
+        }
+
+        assert_eq!(graph.edges["api.service"]["api.socket"], EdgeKind::Requires);
+        for socket in ["web-http.socket", "web-https.socket"] {
+            assert_eq!(graph.edges["web.service"][socket], EdgeKind::Requires);
+        }
+        let order = graph.toposort().unwrap();
+        assert!(position(&order, "api.socket") < position(&order, "api.service"));
+        assert!(position(&order, "web-https.socket") < position(&order, "web.service"));
+    }
+
     #[test]
     fn add_unit_with_name_uses_explicit_instance_name_and_wants_dir() {
         let mut graph = DepGraph::new();
@@ -440,7 +689,7 @@ mod tests {
             graph.add_node(name);
         }
         let mut target = Target::new("group.target".to_string());
-        target.wants_dir = vec!["alpha.service".to_string(), "beta.service".to_string()];
+        target.unit.wants_dir = vec!["alpha.service".to_string(), "beta.service".to_string()];
         target.unit.default_dependencies = false;
 
         graph.add_unit_with_name("template@one.service", &Unit::Target(target));
@@ -454,6 +703,50 @@ mod tests {
         assert!(deps.contains(&"beta.service"));
     }
 
+    #[test]
+    fn reverse_index_follows_reindexing_unindexing_and_aliases() {
+        let mut graph = DepGraph::new();
+        let mut web = Service::new("web.service".to_string());
+        web.unit.binds_to = vec!["db-alias.service".to_string()];
+        web.unit.requires_dir = vec!["cache.service".to_string()];
+        let mut worker = Service::new("worker.service".to_string());
+        worker.unit.part_of = vec!["db.service".to_string()];
+        graph.index_unit("web.service", &Unit::Service(web.clone()));
+        graph.index_unit("worker.service", &Unit::Service(worker));
+
+        let dependents = |graph: &DepGraph, name, kind| -> Vec<String> {
+            graph.dependents(name, kind).cloned().collect()
+        };
+        assert_eq!(
+            dependents(&graph, "cache.service", DependencyType::Requires),
+            ["web.service"]
+        );
+        assert!(dependents(&graph, "db.service", DependencyType::BindsTo).is_empty());
+
+        graph.add_alias("db-alias.service", "db.service");
+        assert_eq!(
+            dependents(&graph, "db.service", DependencyType::BindsTo),
+            ["web.service"]
+        );
+        assert_eq!(
+            dependents(&graph, "db-alias.service", DependencyType::PartOf),
+            ["worker.service"]
+        );
+
+        // Re-indexing replaces the old entries, unindexing drops them
+        web.unit.binds_to.clear();
+        graph.index_unit("web.service", &Unit::Service(web));
+        assert!(dependents(&graph, "db.service", DependencyType::BindsTo).is_empty());
+        assert_eq!(
+            dependents(&graph, "cache.service", DependencyType::Requires),
+            ["web.service"]
+        );
+        graph.unindex_unit("web.service");
+        graph.unindex_unit("worker.service");
+        assert!(graph.dependents.is_empty());
+        assert!(graph.indexed.is_empty());
+    }
+
     #[test]
     fn cycle_error_display_lists_nodes() {
         let error = CycleError {
@@ -576,11 +869,11 @@ mod tests {
         graph.add_node("basic.target");
 
         // dbus-broker.service After=dbus.socket (service depends on socket)
-        graph.add_edge("dbus-broker.service", "dbus.socket");
+        graph.add_edge("dbus-broker.service", "dbus.socket", EdgeKind::Order);
         // dbus.socket After=basic.target - socket waits for target
-        graph.add_edge("dbus.socket", "basic.target");
+        graph.add_edge("dbus.socket", "basic.target", EdgeKind::Order);
         // basic.target depends on service (creating cycle)
-        graph.add_edge("basic.target", "dbus-broker.service");
+        graph.add_edge("basic.target", "dbus-broker.service", EdgeKind::Order);
 
         // Use start_order_for which breaks cycles via toposort_subset
         let order = graph.start_order_for("dbus-broker.service").unwrap();
@@ -611,9 +904,9 @@ mod tests {
         graph.add_node("dbus-broker.service");
 
         // Create cycle: all depend on each other
-        graph.add_edge("dbus-broker.service", "dbus.socket");
-        graph.add_edge("dbus.socket", "sysinit.target");
-        graph.add_edge("sysinit.target", "dbus-broker.service");
+        graph.add_edge("dbus-broker.service", "dbus.socket", EdgeKind::Order);
+        graph.add_edge("dbus.socket", "sysinit.target", EdgeKind::Order);
+        graph.add_edge("sysinit.target", "dbus-broker.service", EdgeKind::Order);
 
         // Use start_order_for which breaks cycles via toposort_subset
         let order = graph.start_order_for("dbus-broker.service").unwrap();
@@ -638,4 +931,67 @@ mod tests {
             service_pos
         );
     }
+
+    fn position(order: &[String], name: &str) -> usize {
+        order.iter().position(|s| s == name).unwrap()
+    }
+
+    #[test]
+    fn cycle_breaking_drops_wants_edges_before_ordering() {
+        let mut graph = DepGraph::new();
+        for name in ["app.target", "db.service", "web.service"] {
+            graph.add_node(name);
+        }
+        // web.service After=db.service, db.service After=app.target,
+        // app.target Wants=web.service: only the Wants= edge may go
+        graph.add_edge("web.service", "db.service", EdgeKind::Order);
+        graph.add_edge("db.service", "app.target", EdgeKind::Order);
+        graph.add_edge("app.target", "web.service", EdgeKind::Wants);
+
+        let order = graph.start_order_for("app.target").unwrap();
+        assert!(position(&order, "db.service") < position(&order, "web.service"));
+        assert!(position(&order, "web.service") < position(&order, "app.target"));
+        assert!(!graph.edges["app.target"].is_empty());
+    }
+
+    #[test]
+    fn cycle_breaking_keeps_required_edges() {
+        let mut a = Service::new("a.service".to_string());
+        a.unit.requires = vec!["b.service".to_string()];
+        let mut b = Service::new("b.service".to_string());
+        b.unit.requires = vec!["c.service".to_string()];
+        let mut c = Service::new("c.service".to_string());
+        c.unit.wants = vec!["a.service".to_string()];
+        c.unit.after = vec!["a.service".to_string()];
+        let mut graph = DepGraph::new();
+        for name in ["a.service", "b.service", "c.service"] {
+            graph.add_node(name);
+        }
+        for service in [&a, &b, &c] {
+            graph.add_service(service);
+        }
+
+        assert_eq!(graph.edges["c.service"]["a.service"], EdgeKind::Order);
+        let order = graph.start_order_for("a.service").unwrap();
+        assert_eq!(order, ["c.service", "b.service", "a.service"]);
+    }
+
+    #[test]
+    fn cycle_of_required_edges_is_an_error() {
+        let mut a = Service::new("a.service".to_string());
+        a.unit.requires = vec!["b.service".to_string()];
+        let mut b = Service::new("b.service".to_string());
+        b.unit.requires = vec!["a.service".to_string()];
+        b.unit.after = vec!["a.service".to_string()];
+        let mut graph = DepGraph::new();
+        graph.add_node("a.service");
+        graph.add_node("b.service");
+        graph.add_service(&a);
+        graph.add_service(&b);
+
+        let err = graph.start_order_for("a.service").unwrap_err();
+        let mut nodes = err.nodes;
+        nodes.sort();
+        assert_eq!(nodes, ["a.service", "b.service"]);
+    }
 }
@@ -0,0 +1,266 @@
+//! Text dump of the manager's internal state (`sysd dump`)
+//!
+//! Like `systemd-analyze dump`, meant for debugging units that are stuck:
+//! every unit with its state, processes, file descriptors, timers and
+//! watchdog, then the jobs still in flight. The format is for people and
+//! may change at any time.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+use std::time::{Duration, Instant};
+
+use super::{ActiveState, Manager};
+
+impl Manager {
+    /// The whole state of the manager as text
+    pub fn dump(&self) -> String {
+        let now = Instant::now();
+        let mut out = String::new();
+        let mode = if self.user_mode { "user" } else { "system" };
+        out.push_str(&format!(
+            "sysd {} ({} manager)\n",
+            env!("CARGO_PKG_VERSION"),
+            mode
+        ));
+        out.push_str(&format!("Uptime: {}\n", span(now - self.boot_time)));
+        out.push_str(&format!("Features: {}\n", self.features));
+        out.push_str(&format!("Unit Paths: {}\n", join_paths(&self.unit_paths)));
+        let names: BTreeSet<&String> = self.units.keys().chain(self.states.keys()).collect();
+        for name in names {
+            self.dump_unit(&mut out, name, now);
+        }
+        self.dump_jobs(&mut out);
+        out
+    }
+
+    fn dump_unit(&self, out: &mut String, name: &str, now: Instant) {
+        let unit = self.units.get(name);
+        out.push_str(&format!("-> Unit {}:\n", name));
+        if let Some(description) = unit.and_then(|u| u.unit_section().description.as_ref()) {
+            field(out, "Description", description);
+        }
+        if let Some(unit) = unit {
+            field(out, "Type", unit.unit_type());
+        }
+        field(out, "Load State", self.load_state(name));
+        if let Some(path) = self.fragment_paths.get(name) {
+            field(out, "Fragment Path", path.display());
+        }
+        if let Some(template) = self.instance_templates.get(name) {
+            field(out, "Template", template);
+        }
+        if self.need_daemon_reload.contains(name) {
+            field(out, "Need Daemon Reload", "yes");
+        }
+        if let Some(state) = self.states.get(name) {
+            field(out, "Active State", state.active.as_str());
+            field(out, "Sub State", state.sub.as_str());
+            field(
+                out,
+                "State Change",
+                format!("{} ago", span(now - state.state_change_time)),
+            );
+            if let Some(pid) = state.main_pid {
+                field(out, "Main PID", pid);
+            }
+            if let Some(code) = state.exit_code {
+                field(out, "Exit Code", code);
+            }
+            if let Some(error) = &state.error {
+                field(out, "Error", error);
+            }
+            if state.restart_count > 0 {
+                field(out, "Restart Count", state.restart_count);
+            }
+            if let Some(at) = state.restart_at {
+                field(
+                    out,
+                    "Restart",
+                    format!("in {}", span(at.saturating_duration_since(now))),
+                );
+            }
+            if let Some(condition) = &state.condition_failure {
+                field(out, "Condition Failed", condition);
+            }
+            if let Some(dependency) = &state.failed_dependency {
+                field(out, "Dependency Failed", dependency);
+            }
+        }
+        if let Some(pid) = self.control_pids.lock().unwrap().get(name) {
+            field(out, "Control PID", pid);
+        }
+        if let Some(cgroup) = self.cgroup_paths.get(name) {
+            field(out, "CGroup", cgroup.display());
+        }
+        if let Some(uid) = self.dynamic_uids.get(name) {
+            field(out, "Dynamic UID", uid);
+        }
+        if let Some(deadline) = self.watchdog_deadlines.get(name) {
+            field(
+                out,
+                "Watchdog Deadline",
+                format!("in {}", span(deadline.saturating_duration_since(now))),
+            );
+        }
+        if let Some(elapse) = self.timer_elapse.get(name) {
+            field(
+                out,
+                "Next Elapse",
+                format!("in {}", span(elapse.saturating_duration_since(now))),
+            );
+        }
+        if let Some(fds) = self.socket_fds.get(name) {
+            let fds: Vec<String> = fds.iter().map(i32::to_string).collect();
+            let armed = if self.armed_sockets.contains(name) {
+                " (waiting for a connection)"
+            } else {
+                ""
+            };
+            field(out, "Listening FDs", format!("{}{}", fds.join(" "), armed));
+        }
+        if let Some(fd) = self.connection_fds.get(name) {
+            field(out, "Connection FD", fd);
+        }
+        if let Some(stored) = self.fd_store.get(name) {
+            let stored: Vec<String> = stored
+                .iter()
+                .map(|(fd_name, fd)| format!("{}={}", fd_name, fd))
+                .collect();
+            field(out, "Stored FDs", stored.join(" "));
+        }
+        if let Some(tasks) = self.unit_tasks.get(name) {
+            field(out, "Watcher Tasks", tasks.len());
+        }
+    }
+
+    /// Starts and stops in flight, targets waiting for their units, network
+    /// mounts being retried and oneshot services between two commands
+    fn dump_jobs(&self, out: &mut String) {
+        out.push_str("-> Jobs:\n");
+        field(out, "Active Jobs", self.active_jobs);
+        let mut changing: Vec<(&String, &str)> = self
+            .states
+            .iter()
+            .filter_map(|(name, state)| match state.active {
+                ActiveState::Activating => Some((name, "start")),
+                ActiveState::Deactivating => Some((name, "stop")),
+                _ => None,
+            })
+            .collect();
+        changing.sort();
+        for (name, job) in changing {
+            out.push_str(&format!("\t{} {}\n", job, name));
+        }
+        for name in sorted(self.pending_targets.iter()) {
+            out.push_str(&format!("\tstart {} (waiting for required units)\n", name));
+        }
+        for name in sorted(self.mount_jobs.keys()) {
+            out.push_str(&format!("\tstart {} (retrying in the background)\n", name));
+        }
+        for name in sorted(self.pending_oneshot_cmds.keys()) {
+            let (next, total, _) = self.pending_oneshot_cmds[name];
+            out.push_str(&format!(
+                "\tstart {} (command {} of {})\n",
+                name,
+                next + 1,
+                total
+            ));
+        }
+        for (bus_name, service) in sorted(self.waiting_bus_name.iter()) {
+            out.push_str(&format!("\tstart {} (waiting for {})\n", service, bus_name));
+        }
+    }
+}
+
+/// One "\tKey: value" line of a unit
+fn field(out: &mut String, key: &str, value: impl Display) {
+    out.push_str(&format!("\t{}: {}\n", key, value));
+}
+
+fn sorted<T: Ord>(items: impl Iterator<Item = T>) -> Vec<T> {
+    let mut items: Vec<T> = items.collect();
+    items.sort();
+    items
+}
+
+fn join_paths(paths: &[std::path::PathBuf]) -> String {
+    let paths: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+    paths.join(" ")
+}
+
+/// "1h 2min 3s", "250ms"
+pub(super) fn span(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs == 0 {
+        return format!("{}ms", duration.as_millis());
+    }
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    let mut parts = Vec::new();
+    if hours > 0 {
+        parts.push(format!("{}h", hours));
+    }
+    if minutes > 0 {
+        parts.push(format!("{}min", minutes));
+    }
+    if seconds > 0 || parts.is_empty() {
+        parts.push(format!("{}s", seconds));
+    }
+    parts.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+    use crate::units::{Service, Unit};
+
+    #[test]
+    fn dump_lists_units_fds_timers_and_jobs() {
+        let mut manager = Manager::new_user();
+        let mut service = Service::new("web.service".to_string());
+        service.unit.description = Some("Web server".to_string());
+        manager
+            .units
+            .insert("web.service".to_string(), Unit::Service(service));
+        let mut state = ServiceState::new();
+        state.set_running(42);
+        manager.states.insert("web.service".to_string(), state);
+        let mut starting = ServiceState::new();
+        starting.set_starting();
+        manager.states.insert("db.service".to_string(), starting);
+        let later = Instant::now() + Duration::from_secs(90);
+        manager
+            .watchdog_deadlines
+            .insert("web.service".to_string(), later);
+        manager
+            .timer_elapse
+            .insert("backup.timer".to_string(), later);
+        manager
+            .states
+            .insert("web.socket".to_string(), ServiceState::new());
+        manager
+            .socket_fds
+            .insert("web.socket".to_string(), vec![7, 8]);
+        manager
+            .fd_store
+            .insert("web.service".to_string(), vec![("conn".to_string(), 9)]);
+
+        let dump = manager.dump();
+        assert!(dump.starts_with("sysd "));
+        assert!(dump.contains("-> Unit web.service:\n\tDescription: Web server\n"));
+        assert!(dump.contains("\tMain PID: 42\n"));
+        assert!(dump.contains("\tWatchdog Deadline: in 1min "));
+        assert!(dump.contains("\tListening FDs: 7 8\n"));
+        assert!(dump.contains("\tStored FDs: conn=9\n"));
+        assert!(dump.contains("-> Jobs:\n\tActive Jobs: 0\n\tstart db.service\n"));
+        // Only loaded units and units with a state are listed
+        assert!(!dump.contains("-> Unit backup.timer"));
+    }
+
+    #[test]
+    fn spans_read_like_systemd_timespans() {
+        assert_eq!(span(Duration::from_millis(250)), "250ms");
+        assert_eq!(span(Duration::from_secs(3723)), "1h 2min 3s");
+        assert_eq!(span(Duration::from_secs(120)), "2min");
+    }
+}
@@ -1,11 +1,24 @@
 // Unit enable/disable operations
 //
-// Handles symlink creation/removal for WantedBy=, RequiredBy=, Also=, and Alias=.
+// Handles symlink creation/removal for WantedBy=, RequiredBy=, Also=, and Alias=,
+// presets and the default.target link.
 
 use std::collections::HashSet;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use super::{Manager, ManagerError};
+use crate::units::{self, PresetAction, Presets};
+
+/// Whether an is-enabled answer counts as enabled for the exit code: static
+/// units are started by whatever pulls them in, as with systemctl
+pub fn enablement_succeeds(state: &str) -> bool {
+    matches!(state, "enabled" | "static")
+}
+
+/// Unit types presets are applied to
+const PRESET_SUFFIXES: [&str; 7] = [
+    ".service", ".socket", ".target", ".mount", ".timer", ".path", ".slice",
+];
 
 struct InstallInfo {
     also: Vec<String>,
@@ -106,7 +119,7 @@ impl Manager {
         !(info.wanted_by.is_empty() && info.required_by.is_empty() && info.aliases.is_empty())
     }
 
-    fn create_enable_links(
+    async fn create_enable_links(
         &self,
         unit_name: &str,
         info: &InstallInfo,
@@ -114,19 +127,25 @@ impl Manager {
         let mut links = Vec::new();
 
         for target in &info.wanted_by {
-            links.push(self.create_dep_link(unit_name, target, &info.unit_path, "wants")?);
+            links.push(
+                self.create_dep_link(unit_name, target, &info.unit_path, "wants")
+                    .await?,
+            );
         }
         for target in &info.required_by {
-            links.push(self.create_dep_link(unit_name, target, &info.unit_path, "requires")?);
+            links.push(
+                self.create_dep_link(unit_name, target, &info.unit_path, "requires")
+                    .await?,
+            );
         }
         for alias in &info.aliases {
-            links.push(self.create_alias_link(alias, &info.unit_path)?);
+            links.push(self.create_alias_link(alias, &info.unit_path).await?);
         }
 
         Ok(links)
     }
 
-    fn remove_enable_links(
+    async fn remove_enable_links(
         &self,
         unit_name: &str,
         info: &InstallInfo,
@@ -134,17 +153,17 @@ impl Manager {
         let mut links = Vec::new();
 
         for target in &info.wanted_by {
-            if let Some(link) = self.remove_dep_link(unit_name, target, "wants")? {
+            if let Some(link) = self.remove_dep_link(unit_name, target, "wants").await? {
                 links.push(link);
             }
         }
         for target in &info.required_by {
-            if let Some(link) = self.remove_dep_link(unit_name, target, "requires")? {
+            if let Some(link) = self.remove_dep_link(unit_name, target, "requires").await? {
                 links.push(link);
             }
         }
         for alias in &info.aliases {
-            if let Some(link) = self.remove_alias_link(alias)? {
+            if let Some(link) = self.remove_alias_link(alias).await? {
                 links.push(link);
             }
         }
@@ -163,7 +182,7 @@ impl Manager {
                 continue;
             }
 
-            created.extend(self.create_enable_links(&unit_name, &info)?);
+            created.extend(self.create_enable_links(&unit_name, &info).await?);
         }
 
         if created.is_empty() {
@@ -179,13 +198,13 @@ impl Manager {
         let units = self.collect_install_units(&initial_name, false).await?;
 
         for (unit_name, info) in units {
-            removed.extend(self.remove_enable_links(&unit_name, &info)?);
+            removed.extend(self.remove_enable_links(&unit_name, &info).await?);
         }
 
         Ok(removed)
     }
 
-    pub(super) fn create_dep_link(
+    pub(super) async fn create_dep_link(
         &self,
         unit_name: &str,
         target: &str,
@@ -193,20 +212,17 @@ impl Manager {
         suffix: &str,
     ) -> Result<PathBuf, ManagerError> {
         let dir = self.enable_dir().join(format!("{}.{}", target, suffix));
-        std::fs::create_dir_all(&dir).map_err(|e| ManagerError::Io(e.to_string()))?;
+        tokio::fs::create_dir_all(&dir)
+            .await
+            .map_err(|e| ManagerError::Io(e.to_string()))?;
 
         let link_path = dir.join(unit_name);
-        if link_path.exists() || link_path.is_symlink() {
-            std::fs::remove_file(&link_path).map_err(|e| ManagerError::Io(e.to_string()))?;
-        }
-
-        std::os::unix::fs::symlink(unit_path, &link_path)
-            .map_err(|e| ManagerError::Io(e.to_string()))?;
+        replace_symlink(unit_path, &link_path).await?;
 
         Ok(link_path)
     }
 
-    pub(super) fn remove_dep_link(
+    pub(super) async fn remove_dep_link(
         &self,
         unit_name: &str,
         target: &str,
@@ -217,40 +233,25 @@ impl Manager {
             .join(format!("{}.{}", target, suffix))
             .join(unit_name);
 
-        if link_path.exists() || link_path.is_symlink() {
-            std::fs::remove_file(&link_path).map_err(|e| ManagerError::Io(e.to_string()))?;
-            Ok(Some(link_path))
-        } else {
-            Ok(None)
-        }
+        remove_link(link_path).await
     }
 
-    pub(super) fn create_alias_link(
+    pub(super) async fn create_alias_link(
         &self,
         alias: &str,
         unit_path: &PathBuf,
     ) -> Result<PathBuf, ManagerError> {
         let link_path = self.enable_dir().join(alias);
-
-        if link_path.exists() || link_path.is_symlink() {
-            std::fs::remove_file(&link_path).map_err(|e| ManagerError::Io(e.to_string()))?;
-        }
-
-        std::os::unix::fs::symlink(unit_path, &link_path)
-            .map_err(|e| ManagerError::Io(e.to_string()))?;
+        replace_symlink(unit_path, &link_path).await?;
 
         Ok(link_path)
     }
 
-    pub(super) fn remove_alias_link(&self, alias: &str) -> Result<Option<PathBuf>, ManagerError> {
-        let link_path = self.enable_dir().join(alias);
-
-        if link_path.exists() || link_path.is_symlink() {
-            std::fs::remove_file(&link_path).map_err(|e| ManagerError::Io(e.to_string()))?;
-            Ok(Some(link_path))
-        } else {
-            Ok(None)
-        }
+    pub(super) async fn remove_alias_link(
+        &self,
+        alias: &str,
+    ) -> Result<Option<PathBuf>, ManagerError> {
+        remove_link(self.enable_dir().join(alias)).await
     }
 
     pub async fn is_enabled(&mut self, name: &str) -> Result<String, ManagerError> {
@@ -260,50 +261,173 @@ impl Manager {
             self.load(&name).await?;
         }
 
-        let unit = self
-            .units
-            .get(&name)
-            .ok_or_else(|| ManagerError::NotFound(name.clone()))?;
+        self.enablement(&name)
+            .map(String::from)
+            .ok_or(ManagerError::NotFound(name))
+    }
+
+    /// `is_enabled` for a unit that is already loaded, without touching the
+    /// unit files (None when `name` is not loaded)
+    pub fn loaded_enablement(&self, name: &str) -> Option<&'static str> {
+        self.enablement(&self.normalize_name(name))
+    }
 
+    /// "enabled", "disabled" or "static" for a loaded unit
+    pub(super) fn enablement(&self, name: &str) -> Option<&'static str> {
+        let unit = self.units.get(name)?;
         let Some(install) = unit.install_section() else {
-            return Ok("static".to_string());
+            return Some("static");
         };
 
         if install.wanted_by.is_empty()
             && install.required_by.is_empty()
             && install.alias.is_empty()
         {
-            return Ok("static".to_string());
+            return Some("static");
         }
 
-        for target in &install.wanted_by {
-            if self.has_enable_link(&name, &format!("{}.wants", target)) {
-                return Ok("enabled".to_string());
+        let base = self.enable_dir();
+        let has_link = |path: PathBuf| path.symlink_metadata().is_ok();
+        let enabled = install
+            .wanted_by
+            .iter()
+            .any(|target| has_link(base.join(format!("{}.wants", target)).join(name)))
+            || install
+                .required_by
+                .iter()
+                .any(|target| has_link(base.join(format!("{}.requires", target)).join(name)))
+            || install.alias.iter().any(|alias| has_link(base.join(alias)));
+        Some(if enabled { "enabled" } else { "disabled" })
+    }
+
+    /// Enable or disable `name` as the preset files say, returning what was
+    /// done and the links created or removed
+    pub async fn preset(
+        &mut self,
+        name: &str,
+    ) -> Result<(PresetAction, Vec<PathBuf>), ManagerError> {
+        let name = self.normalize_name(name);
+        let action = Presets::load(self.user_mode).action(&name);
+        let links = self.apply_preset(&name, action).await?;
+        Ok((action, links))
+    }
+
+    /// Apply the presets to every unit file in the search paths
+    ///
+    /// Units that fail to load are logged and skipped so one broken file
+    /// does not stop an image from being provisioned.
+    pub async fn preset_all(&mut self) -> Vec<(String, PresetAction, Vec<PathBuf>)> {
+        let presets = Presets::load(self.user_mode);
+        self.apply_presets(&presets).await
+    }
+
+    async fn apply_presets(
+        &mut self,
+        presets: &Presets,
+    ) -> Vec<(String, PresetAction, Vec<PathBuf>)> {
+        let mut applied = Vec::new();
+        for name in self.preset_unit_files() {
+            let action = presets.action(&name);
+            match self.apply_preset(&name, action).await {
+                Ok(links) => applied.push((name, action, links)),
+                Err(e) => log::warn!("Skipping preset of {}: {}", name, e),
             }
         }
+        applied
+    }
 
-        for target in &install.required_by {
-            if self.has_enable_link(&name, &format!("{}.requires", target)) {
-                return Ok("enabled".to_string());
-            }
+    async fn apply_preset(
+        &mut self,
+        name: &str,
+        action: PresetAction,
+    ) -> Result<Vec<PathBuf>, ManagerError> {
+        match action {
+            PresetAction::Enable => match self.enable(name).await {
+                Err(ManagerError::NoInstallSection(_)) => Ok(Vec::new()),
+                result => result,
+            },
+            PresetAction::Disable => self.disable(name).await,
+            PresetAction::Ignore => Ok(Vec::new()),
         }
+    }
 
-        for alias in &install.alias {
-            if self.has_enable_link(alias, "") {
-                return Ok("enabled".to_string());
+    /// Unit files presets apply to: regular files only, since links are
+    /// aliases, masks or enablement, and no templates
+    pub(super) fn preset_unit_files(&self) -> Vec<String> {
+        let mut seen = HashSet::new();
+        let mut names = Vec::new();
+        for dir in &self.unit_paths {
+            let Some(entries) = units::list_directory(dir) else {
+                continue;
+            };
+            for name in entries.iter() {
+                let is_unit = PRESET_SUFFIXES.iter().any(|suffix| name.ends_with(suffix));
+                // The first directory providing a name decides what it is
+                if !is_unit || !seen.insert(name.clone()) {
+                    continue;
+                }
+                let is_file = dir
+                    .join(name)
+                    .symlink_metadata()
+                    .is_ok_and(|meta| meta.is_file());
+                if is_file && !name.contains("@.") {
+                    names.push(name.clone());
+                }
             }
         }
+        names.sort();
+        names
+    }
 
-        Ok("disabled".to_string())
+    /// Installed service unit files (regular files, no templates)
+    pub fn service_unit_files(&self) -> Vec<String> {
+        let mut names = self.preset_unit_files();
+        names.retain(|name| name.ends_with(".service"));
+        names
     }
 
-    fn has_enable_link(&self, entry: &str, dir: &str) -> bool {
-        let base = self.enable_dir();
-        let link_path = if dir.is_empty() {
-            base.join(entry)
-        } else {
-            base.join(dir).join(entry)
-        };
-        link_path.exists() || link_path.is_symlink()
+    /// Point default.target at `target`, returning the link
+    pub async fn set_default(&mut self, target: &str) -> Result<PathBuf, ManagerError> {
+        let target = self.normalize_name(target);
+        if !target.ends_with(".target") || target == "default.target" {
+            return Err(ManagerError::NotATarget(target));
+        }
+        let unit_path = self.find_unit(&target)?;
+        if crate::root::resolve(&unit_path) == Path::new("/dev/null") {
+            return Err(ManagerError::Masked(target));
+        }
+        let link_path = self.enable_dir().join("default.target");
+        tokio::fs::create_dir_all(self.enable_dir())
+            .await
+            .map_err(|e| ManagerError::Io(e.to_string()))?;
+        replace_symlink(&unit_path, &link_path).await?;
+        Ok(link_path)
+    }
+}
+
+/// Whether anything (including a dangling symlink) exists at `path`
+async fn link_exists(path: &Path) -> bool {
+    tokio::fs::symlink_metadata(path).await.is_ok()
+}
+
+async fn replace_symlink(unit_path: &Path, link_path: &Path) -> Result<(), ManagerError> {
+    if link_exists(link_path).await {
+        tokio::fs::remove_file(link_path)
+            .await
+            .map_err(|e| ManagerError::Io(e.to_string()))?;
+    }
+    // Below --root the link must name the unit as the booted image sees it
+    tokio::fs::symlink(crate::root::unprefixed(unit_path), link_path)
+        .await
+        .map_err(|e| ManagerError::Io(e.to_string()))
+}
+
+async fn remove_link(link_path: PathBuf) -> Result<Option<PathBuf>, ManagerError> {
+    if !link_exists(&link_path).await {
+        return Ok(None);
     }
+    tokio::fs::remove_file(&link_path)
+        .await
+        .map_err(|e| ManagerError::Io(e.to_string()))?;
+    Ok(Some(link_path))
 }
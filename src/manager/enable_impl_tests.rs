@@ -228,10 +228,18 @@ ExecStart=/bin/true
     std::fs::create_dir_all(wants_link.parent().unwrap()).unwrap();
     std::os::unix::fs::symlink(&enabled_path, &wants_link).unwrap();
     let mut manager = manager_with_unit_dir(&root);
+    assert_eq!(manager.loaded_enablement("enabled"), None);
 
     assert_eq!(manager.is_enabled("enabled").await.unwrap(), "enabled");
     assert_eq!(manager.is_enabled("disabled").await.unwrap(), "disabled");
     assert_eq!(manager.is_enabled("static").await.unwrap(), "static");
+    assert_eq!(manager.loaded_enablement("enabled"), Some("enabled"));
+    assert_eq!(
+        manager.loaded_enablement("disabled.service"),
+        Some("disabled")
+    );
+    assert!(enablement_succeeds("enabled") && enablement_succeeds("static"));
+    assert!(!enablement_succeeds("disabled"));
 }
 
 #[tokio::test]
@@ -268,3 +276,55 @@ Alias=aliased-short.service
     assert_eq!(manager.is_enabled("required.service").await.unwrap(), "enabled");
     assert_eq!(manager.is_enabled("aliased.service").await.unwrap(), "enabled");
 }
+
+#[tokio::test]
+async fn preset_all_follows_the_rules_and_skips_links_and_templates() {
+    let root = temp_dir("preset-all");
+    let install = "[Service]\nExecStart=/bin/true\n\n[Install]\nWantedBy=multi-user.target\n";
+    let wanted_path = write_unit(&root, "wanted.service", install);
+    write_unit(&root, "unwanted.service", install);
+    write_unit(&root, "worker@.service", install);
+    write_unit(&root, "static.service", "[Service]\nExecStart=/bin/true\n");
+    std::os::unix::fs::symlink(&wanted_path, root.0.join("wanted-alias.service")).unwrap();
+    let unwanted_link = root.0.join("multi-user.target.wants/unwanted.service");
+    std::fs::create_dir_all(unwanted_link.parent().unwrap()).unwrap();
+    std::os::unix::fs::symlink(root.0.join("unwanted.service"), &unwanted_link).unwrap();
+    let mut presets = Presets::default();
+    presets.add_rules(Path::new("test.preset"), "disable unwanted.service\n");
+    let mut manager = manager_with_unit_dir(&root);
+
+    let applied = manager.apply_presets(&presets).await;
+
+    let summary: Vec<(&str, PresetAction, usize)> = applied
+        .iter()
+        .map(|(name, action, links)| (name.as_str(), *action, links.len()))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("static.service", PresetAction::Enable, 0),
+            ("unwanted.service", PresetAction::Disable, 1),
+            ("wanted.service", PresetAction::Enable, 1),
+        ]
+    );
+    assert!(unwanted_link.symlink_metadata().is_err());
+    assert_eq!(manager.is_enabled("wanted").await.unwrap(), "enabled");
+}
+
+#[tokio::test]
+async fn set_default_links_default_target_and_rejects_other_units() {
+    let root = temp_dir("set-default");
+    let target_path = write_unit(&root, "graphical.target", "[Unit]\nDescription=Graphical\n");
+    write_unit(&root, "app.service", "[Service]\nExecStart=/bin/true\n");
+    let mut manager = manager_with_unit_dir(&root);
+
+    let link = manager.set_default("graphical.target").await.unwrap();
+
+    assert_eq!(link, root.0.join("default.target"));
+    assert_eq!(link_target(&link), target_path);
+    assert_eq!(manager.get_default_target().unwrap(), "graphical.target");
+    let err = manager.set_default("app.service").await.unwrap_err();
+    assert!(matches!(err, ManagerError::NotATarget(name) if name == "app.service"));
+    let err = manager.set_default("missing.target").await.unwrap_err();
+    assert!(matches!(err, ManagerError::NotFound(_)));
+}
@@ -0,0 +1,247 @@
+//! Waiting for a service's network endpoint (WaitForEndpoint=, a sysd
+//! extension)
+//!
+//! Some daemons report READY=1 (or are "ready" as soon as they are spawned)
+//! long before they accept traffic. A service with
+//! `WaitForEndpoint=tcp://HOST:PORT` or `http://HOST[:PORT]/PATH` is probed
+//! from the moment it is spawned, with a delay doubling from 100ms up to 5s
+//! between attempts, until a TCP connect succeeds or the GET answers 2xx.
+//! Units ordered after it (After= on their side, Before= on its side) are
+//! not started until then. A Type=simple or idle service stays activating
+//! meanwhile. If the endpoint is not up within TimeoutStartSec= (default
+//! 90s), the service fails with result timeout and its dependents go ahead.
+
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use tokio::sync::watch;
+use tokio::task::AbortHandle;
+
+use crate::units::{HealthCheck, Service, ServiceType, Unit};
+
+use super::health_check::probe;
+use super::{ActiveState, Manager, ServiceResult, SubState};
+
+/// TimeoutStartSec= default, as in systemd
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(90);
+const FIRST_RETRY: Duration = Duration::from_millis(100);
+const MAX_RETRY: Duration = Duration::from_secs(5);
+
+/// A running endpoint probe; its value is set once the endpoint is up
+/// (true) or the timeout passed (false)
+#[derive(Debug)]
+pub(crate) struct EndpointWait {
+    up: watch::Receiver<Option<bool>>,
+    task: AbortHandle,
+}
+
+impl Drop for EndpointWait {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl Manager {
+    /// Start probing the WaitForEndpoint= of `name`, just spawned
+    pub(super) fn begin_endpoint_wait(&mut self, name: &str, service: &Service) {
+        let Some(endpoint) = service.service.wait_for_endpoint.clone() else {
+            return;
+        };
+        let timeout = service.service.timeout_start_sec.unwrap_or(DEFAULT_TIMEOUT);
+        let (tx, up) = watch::channel(None);
+        let task = tokio::spawn(async move {
+            let _ = tx.send(Some(wait_until_up(endpoint, timeout).await));
+        })
+        .abort_handle();
+        self.endpoint_waits
+            .insert(name.to_string(), EndpointWait { up, task });
+    }
+
+    /// Whether `service` becomes active only once its endpoint is up
+    pub(super) fn waits_for_endpoint(service: &Service) -> bool {
+        service.service.wait_for_endpoint.is_some()
+            && matches!(
+                service.service.service_type,
+                ServiceType::Simple | ServiceType::Idle
+            )
+    }
+
+    /// Resolves once every unit `name` is ordered after has its endpoint
+    /// up or gave up on it; needs no access to the manager
+    pub fn endpoints_before(&self, name: &str) -> impl Future<Output = ()> + Send + 'static {
+        let after = self
+            .units
+            .get(name)
+            .map(|unit| unit.unit_section().after.clone())
+            .unwrap_or_default();
+        let waits: Vec<_> = self
+            .endpoint_waits
+            .iter()
+            .filter(|(dep, _)| {
+                after.iter().any(|a| &self.normalize_name(a) == *dep)
+                    || self
+                        .units
+                        .get(*dep)
+                        .is_some_and(|u| u.unit_section().before.iter().any(|b| b == name))
+            })
+            .map(|(dep, wait)| (dep.clone(), wait.up.clone()))
+            .collect();
+        let name = name.to_string();
+        async move {
+            for (dep, mut up) in waits {
+                if up.borrow().is_none() {
+                    log::info!("{} waits for the endpoint of {}", name, dep);
+                }
+                let _ = up.wait_for(Option::is_some).await;
+            }
+        }
+    }
+
+    /// Mark services active whose endpoint came up, fail those whose
+    /// endpoint did not in time
+    pub async fn process_endpoint_waits(&mut self) {
+        let done: Vec<(String, bool)> = self
+            .endpoint_waits
+            .iter()
+            .filter_map(|(name, wait)| Some((name.clone(), (*wait.up.borrow())?)))
+            .collect();
+        for (name, up) in done {
+            self.endpoint_waits.remove(&name);
+            let Some(state) = self.states.get(&name) else {
+                continue;
+            };
+            let starting =
+                state.active == ActiveState::Activating && state.sub == SubState::Starting;
+            if !state.is_active() || state.sub == SubState::AutoRestart {
+                continue;
+            }
+            if !up {
+                log::warn!("{}: endpoint not up in time", name);
+                self.enqueue_failure_stop(&name, ServiceResult::Timeout, None);
+                continue;
+            }
+            log::info!("{}: endpoint is up", name);
+            let service = self.units.get(&name).and_then(Unit::as_service).cloned();
+            let pid = self.processes.get(&name).and_then(|child| child.id());
+            if let (true, Some(service), Some(pid)) = (starting, service, pid) {
+                if Self::waits_for_endpoint(&service) {
+                    self.mark_running_start(&name, pid, &service);
+                }
+            }
+        }
+    }
+}
+
+/// Probe `endpoint` with growing pauses until it is up; false once
+/// `timeout` passed
+async fn wait_until_up(endpoint: HealthCheck, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    let mut retry = FIRST_RETRY;
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return false;
+        }
+        if probe(endpoint.clone(), Vec::new(), left.min(MAX_RETRY)).await {
+            return true;
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        tokio::time::sleep(retry.min(left)).await;
+        retry = (retry * 2).min(MAX_RETRY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::{ServiceState, StopEvent};
+    use crate::units::Target;
+
+    fn unused_port() -> String {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().to_string()
+    }
+
+    #[tokio::test]
+    async fn endpoint_wait_retries_until_up_or_timeout() {
+        let address = unused_port();
+        let closed = HealthCheck::Tcp(address.clone());
+        assert!(!wait_until_up(closed.clone(), Duration::from_millis(300)).await);
+
+        let late = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(150)).await;
+            std::net::TcpListener::bind(address).unwrap()
+        });
+        assert!(wait_until_up(closed, Duration::from_secs(5)).await);
+        drop(late.await.unwrap());
+    }
+
+    fn manager_with_db(endpoint: HealthCheck) -> (Manager, Service) {
+        let mut manager = Manager::new_user();
+        let mut db = Service::new("db.service".to_string());
+        db.service.wait_for_endpoint = Some(endpoint);
+        db.service.timeout_start_sec = Some(Duration::from_millis(200));
+        manager.insert_unit("db.service".into(), Unit::Service(db.clone()));
+        let mut app = Target::new("app.target".to_string());
+        app.unit.after = vec!["db".to_string()];
+        manager.insert_unit("app.target".into(), Unit::Target(app));
+        let mut state = ServiceState::new();
+        state.set_starting();
+        manager.states.insert("db.service".into(), state);
+        (manager, db)
+    }
+
+    #[tokio::test]
+    async fn dependents_wait_until_the_endpoint_gives_up_and_the_service_fails() {
+        let (mut manager, db) = manager_with_db(HealthCheck::Tcp(unused_port()));
+
+        manager.begin_endpoint_wait("db.service", &db);
+        let started = Instant::now();
+        manager.endpoints_before("app.target").await;
+        assert!(started.elapsed() >= Duration::from_millis(150));
+
+        let mut stop_events = manager.take_stop_event_rx().unwrap();
+        manager.process_endpoint_waits().await;
+        assert_eq!(
+            manager.states["db.service"].active,
+            ActiveState::Deactivating
+        );
+        while let Some(event) = stop_events.recv().await {
+            let finished = matches!(event, StopEvent::Finished { .. });
+            manager.handle_stop_event(event).await;
+            if finished {
+                break;
+            }
+        }
+        let state = &manager.states["db.service"];
+        assert_eq!(state.active, ActiveState::Failed);
+        assert_eq!(state.error.as_deref(), Some("timeout"));
+        assert!(manager.endpoint_waits.is_empty());
+    }
+
+    #[tokio::test]
+    async fn service_becomes_active_once_its_endpoint_is_up() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (mut manager, db) = manager_with_db(HealthCheck::Tcp(address));
+        let child = tokio::process::Command::new("/bin/sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        let pid = child.id().unwrap();
+        manager.processes.insert("db.service".into(), child);
+
+        manager.begin_endpoint_wait("db.service", &db);
+        manager.endpoints_before("app.target").await;
+        manager.process_endpoint_waits().await;
+        let state = &manager.states["db.service"];
+        assert_eq!(state.sub, SubState::Running);
+        assert_eq!(state.main_pid, Some(pid));
+        let _ = manager
+            .processes
+            .get_mut("db.service")
+            .unwrap()
+            .kill()
+            .await;
+    }
+}
@@ -0,0 +1,98 @@
+//! Holding sysd-executor back until the service cgroup is ready
+//!
+//! The manager moves a spawned service into its cgroup and attaches the
+//! IPAddressAllow=/SocketBindAllow=/RestrictFileSystems= BPF programs to
+//! that cgroup only once it knows the PID. sysd-executor gets the read end
+//! of a pipe and blocks on it before doing anything else, so neither it nor
+//! the service runs a single instruction outside the cgroup or unfiltered.
+//! The manager writes one byte once the cgroup is set up; if it drops the
+//! gate without doing so, the executor sees EOF and exits without exec'ing.
+//!
+//! Without sysd-executor (tests only) `spawn` returns after the exec, so
+//! there is nothing to hold back and no gate is made.
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::io::RawFd;
+
+/// Keep the read end clear of the fds a child gets remapped to 3, 4, ...
+const GATE_FD_MIN: RawFd = 256;
+
+/// A closed gate for one spawn
+#[derive(Debug)]
+pub(super) struct ExecGate {
+    reader: OwnedFd,
+    writer: OwnedFd,
+}
+
+impl ExecGate {
+    /// A gate for a spawn through sysd-executor; None without the executor
+    /// or if no pipe can be made, in which case the child runs right away
+    pub(super) fn new(via_executor: bool) -> Option<Self> {
+        if !via_executor {
+            return None;
+        }
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+            log::warn!(
+                "Cannot hold the executor back: {}",
+                std::io::Error::last_os_error()
+            );
+            return None;
+        }
+        let [reader, writer] = fds.map(|fd| unsafe { OwnedFd::from_raw_fd(fd) });
+        let moved = unsafe { libc::fcntl(reader.as_raw_fd(), libc::F_DUPFD_CLOEXEC, GATE_FD_MIN) };
+        if moved < 0 {
+            return None;
+        }
+        Some(Self {
+            reader: unsafe { OwnedFd::from_raw_fd(moved) },
+            writer,
+        })
+    }
+
+    /// Read end for the child (cloexec is cleared after the fork)
+    pub(super) fn child_fd(&self) -> RawFd {
+        self.reader.as_raw_fd()
+    }
+
+    /// Let the child go on
+    pub(super) fn release(self) {
+        let byte = 1u8;
+        let written =
+            unsafe { libc::write(self.writer.as_raw_fd(), (&byte as *const u8).cast(), 1) };
+        if written != 1 {
+            log::warn!(
+                "Failed to release the executor: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_gate(fd: RawFd) -> isize {
+        let mut byte = 0u8;
+        unsafe { libc::read(fd, (&mut byte as *mut u8).cast(), 1) }
+    }
+
+    #[test]
+    fn the_child_reads_a_byte_once_released_and_eof_when_dropped() {
+        assert!(ExecGate::new(false).is_none());
+
+        let gate = ExecGate::new(true).unwrap();
+        assert!(gate.child_fd() >= GATE_FD_MIN);
+        let fd = unsafe { libc::dup(gate.child_fd()) };
+        gate.release();
+        assert_eq!(read_gate(fd), 1);
+        unsafe { libc::close(fd) };
+
+        let gate = ExecGate::new(true).unwrap();
+        let fd = unsafe { libc::dup(gate.child_fd()) };
+        drop(gate);
+        assert_eq!(read_gate(fd), 0);
+        unsafe { libc::close(fd) };
+    }
+}
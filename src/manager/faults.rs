@@ -0,0 +1,215 @@
+//! Fault injection for testing restart logic (`sysdctl inject-fault`)
+//!
+//! A fault is armed for one unit and fires where the real failure would, so
+//! restart, start rate limiting and the watchdog can be driven into every
+//! branch without writing misbehaving services. Only a sysd built with the
+//! `fault-injection` feature accepts faults; otherwise none is ever armed and
+//! the hooks below find nothing to do.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::{Manager, ManagerError};
+
+/// A failure to provoke, as given to `sysdctl inject-fault`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// "exit-code=N": the next exit of the main process reports N (a
+    /// negative N: killed by signal -N)
+    ExitCode(i32),
+    /// "delay-ready=SECS": hold the next READY=1 back this long
+    DelayReady(Duration),
+    /// "drop-watchdog": ignore WATCHDOG=1 pings until cleared
+    DropWatchdog,
+    /// "fail-cgroup": setting up the cgroup of the next start fails
+    FailCgroup,
+    /// "clear": disarm all faults of the unit
+    Clear,
+}
+
+impl Fault {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.split_once('=') {
+            Some(("exit-code", code)) => code.parse().ok().map(Self::ExitCode),
+            Some(("delay-ready", secs)) => secs
+                .parse::<f64>()
+                .ok()
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .map(Self::DelayReady),
+            None if s == "drop-watchdog" => Some(Self::DropWatchdog),
+            None if s == "fail-cgroup" => Some(Self::FailCgroup),
+            None if s == "clear" => Some(Self::Clear),
+            _ => None,
+        }
+    }
+}
+
+/// Faults armed across all units
+#[derive(Debug, Default)]
+pub(crate) struct Faults {
+    units: HashMap<String, UnitFaults>,
+    /// READY=1 messages held back: when to act on them, for which unit
+    delayed_ready: Vec<(Instant, String)>,
+}
+
+#[derive(Debug, Default)]
+struct UnitFaults {
+    exit_code: Option<i32>,
+    ready_delay: Option<Duration>,
+    drop_watchdog: bool,
+    fail_cgroup: bool,
+}
+
+impl Manager {
+    /// Arm `fault` for unit `name`
+    pub fn inject_fault(&mut self, name: &str, fault: Fault) -> Result<(), ManagerError> {
+        if !cfg!(feature = "fault-injection") {
+            return Err(ManagerError::FaultInjectionDisabled);
+        }
+        let name = self.normalize_name(name);
+        if !self.units.contains_key(&name) {
+            return Err(ManagerError::NotFound(name));
+        }
+        log::warn!("{}: injecting fault {:?}", name, fault);
+        if fault == Fault::Clear {
+            self.faults.units.remove(&name);
+            self.faults.delayed_ready.retain(|(_, unit)| *unit != name);
+            return Ok(());
+        }
+        let faults = self.faults.units.entry(name).or_default();
+        match fault {
+            Fault::ExitCode(code) => faults.exit_code = Some(code),
+            Fault::DelayReady(delay) => faults.ready_delay = Some(delay),
+            Fault::DropWatchdog => faults.drop_watchdog = true,
+            Fault::FailCgroup => faults.fail_cgroup = true,
+            Fault::Clear => {}
+        }
+        Ok(())
+    }
+
+    /// The exit code to report instead of `code` for the main process of
+    /// `name`
+    pub(super) fn injected_exit_code(&mut self, name: &str, code: i32) -> i32 {
+        let faults = self.faults.units.get_mut(name);
+        match faults.and_then(|faults| faults.exit_code.take()) {
+            Some(injected) => {
+                log::warn!(
+                    "{}: exited with {}, reporting {} (injected)",
+                    name,
+                    code,
+                    injected
+                );
+                injected
+            }
+            None => code,
+        }
+    }
+
+    /// Hold back READY=1 of `name` if a delay is armed; true if it was
+    pub(super) fn delay_injected_ready(&mut self, name: &str) -> bool {
+        let faults = self.faults.units.get_mut(name);
+        let Some(delay) = faults.and_then(|faults| faults.ready_delay.take()) else {
+            return false;
+        };
+        log::warn!("{}: holding READY=1 back for {:?} (injected)", name, delay);
+        let due = Instant::now() + delay;
+        self.faults.delayed_ready.push((due, name.to_string()));
+        true
+    }
+
+    /// Units whose held back READY=1 is due now
+    pub(super) fn take_due_ready(&mut self) -> Vec<String> {
+        let now = Instant::now();
+        let (due, waiting): (Vec<_>, Vec<_>) = std::mem::take(&mut self.faults.delayed_ready)
+            .into_iter()
+            .partition(|(at, _)| *at <= now);
+        self.faults.delayed_ready = waiting;
+        due.into_iter().map(|(_, name)| name).collect()
+    }
+
+    pub(super) fn drops_watchdog_pings(&self, name: &str) -> bool {
+        self.faults
+            .units
+            .get(name)
+            .is_some_and(|faults| faults.drop_watchdog)
+    }
+
+    /// Whether setting up the cgroup of `name` is to fail this time
+    pub(super) fn take_injected_cgroup_failure(&mut self, name: &str) -> bool {
+        self.faults
+            .units
+            .get_mut(name)
+            .is_some_and(|faults| std::mem::take(&mut faults.fail_cgroup))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Service, Unit};
+
+    #[test]
+    fn faults_parse_like_the_cli_writes_them() {
+        assert_eq!(Fault::parse("exit-code=-9"), Some(Fault::ExitCode(-9)));
+        assert_eq!(
+            Fault::parse("delay-ready=1.5"),
+            Some(Fault::DelayReady(Duration::from_millis(1500)))
+        );
+        assert_eq!(Fault::parse("drop-watchdog"), Some(Fault::DropWatchdog));
+        assert_eq!(Fault::parse("fail-cgroup"), Some(Fault::FailCgroup));
+        assert_eq!(Fault::parse("clear"), Some(Fault::Clear));
+        assert_eq!(Fault::parse("exit-code"), None);
+        assert_eq!(Fault::parse("delay-ready=-1"), None);
+        assert_eq!(Fault::parse("crash"), None);
+    }
+
+    fn manager_with(name: &str) -> Manager {
+        let mut manager = Manager::new_user();
+        let service = Service::new(name.to_string());
+        manager
+            .units
+            .insert(name.to_string(), Unit::Service(service));
+        manager
+    }
+
+    #[cfg(not(feature = "fault-injection"))]
+    #[test]
+    fn faults_are_refused_without_the_feature() {
+        let mut manager = manager_with("web.service");
+        assert!(matches!(
+            manager.inject_fault("web.service", Fault::ExitCode(1)),
+            Err(ManagerError::FaultInjectionDisabled)
+        ));
+        assert_eq!(manager.injected_exit_code("web.service", 0), 0);
+    }
+
+    #[cfg(feature = "fault-injection")]
+    #[test]
+    fn armed_faults_fire_once_where_they_apply() {
+        let mut manager = manager_with("web.service");
+        assert!(matches!(
+            manager.inject_fault("db.service", Fault::FailCgroup),
+            Err(ManagerError::NotFound(_))
+        ));
+        for fault in [
+            Fault::ExitCode(3),
+            Fault::DelayReady(Duration::ZERO),
+            Fault::DropWatchdog,
+            Fault::FailCgroup,
+        ] {
+            manager.inject_fault("web", fault).unwrap();
+        }
+
+        assert_eq!(manager.injected_exit_code("web.service", 0), 3);
+        assert_eq!(manager.injected_exit_code("web.service", 0), 0);
+        assert!(manager.take_injected_cgroup_failure("web.service"));
+        assert!(!manager.take_injected_cgroup_failure("web.service"));
+        assert!(manager.delay_injected_ready("web.service"));
+        assert!(!manager.delay_injected_ready("web.service"));
+        assert_eq!(manager.take_due_ready(), vec!["web.service".to_string()]);
+        assert!(manager.drops_watchdog_pings("web.service"));
+
+        manager.inject_fault("web.service", Fault::Clear).unwrap();
+        assert!(!manager.drops_watchdog_pings("web.service"));
+    }
+}
@@ -0,0 +1,106 @@
+//! The file descriptor store from outside the unit
+//! (`GetUnitFileDescriptorStore`, FileDescriptorStore= of StartTransientUnit)
+//!
+//! Services fill their store with FDSTORE=1. A client can also look at what
+//! a unit holds, as duplicates of its own, and hand FDs to a transient unit
+//! as it is created, for socket proxies and test harnesses that layer on
+//! top of sysd.
+
+use std::os::fd::{AsRawFd, BorrowedFd, IntoRawFd, OwnedFd};
+
+use super::runtime::store_fd_entries;
+use super::{Manager, ManagerError};
+
+impl Manager {
+    /// Duplicates of the FDs `name` has stored, with their names
+    pub fn fd_store_entries(&self, name: &str) -> Result<Vec<(String, OwnedFd)>, ManagerError> {
+        let name = self.fd_store_unit(name)?;
+        let Some(stored) = self.fd_store.get(&name) else {
+            return Ok(Vec::new());
+        };
+        stored
+            .iter()
+            .map(|(fd_name, fd)| {
+                // The store keeps its FD open for as long as the entry exists
+                let fd = unsafe { BorrowedFd::borrow_raw(*fd) }.try_clone_to_owned()?;
+                Ok((fd_name.clone(), fd))
+            })
+            .collect()
+    }
+
+    /// Add `fds` to the store of `name`, which holds at most `max`; FDs
+    /// beyond that are closed
+    pub fn store_fds(
+        &mut self,
+        name: &str,
+        max: usize,
+        fds: Vec<(String, OwnedFd)>,
+    ) -> Result<(), ManagerError> {
+        let name = self.fd_store_unit(name)?;
+        let store = self.fd_store.entry(name.clone()).or_default();
+        for (fd_name, fd) in fds {
+            log::debug!(
+                "{}: storing passed FD {} as '{}'",
+                name,
+                fd.as_raw_fd(),
+                fd_name
+            );
+            store_fd_entries(store, &name, &fd_name, &[fd.into_raw_fd()], max);
+        }
+        Ok(())
+    }
+
+    /// Canonical name of a known unit or scope
+    fn fd_store_unit(&self, name: &str) -> Result<String, ManagerError> {
+        if self.states.contains_key(name) {
+            return Ok(name.to_string());
+        }
+        let name = self.normalize_name(name);
+        if self.units.contains_key(&name) || self.states.contains_key(&name) {
+            Ok(name)
+        } else {
+            Err(ManagerError::NotFound(name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+
+    use super::*;
+    use crate::manager::ServiceState;
+
+    #[test]
+    fn stored_fds_come_back_as_working_duplicates() {
+        let mut manager = Manager::new_user();
+        manager
+            .states
+            .insert("proxy.scope".into(), ServiceState::running_scope());
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let (_, extra) = UnixStream::pair().unwrap();
+        manager
+            .store_fds(
+                "proxy.scope",
+                1,
+                vec![
+                    ("conn".to_string(), OwnedFd::from(theirs)),
+                    ("extra".to_string(), OwnedFd::from(extra)),
+                ],
+            )
+            .unwrap();
+
+        let entries = manager.fd_store_entries("proxy.scope").unwrap();
+        assert_eq!(entries.len(), 1);
+        let (fd_name, fd) = entries.into_iter().next().unwrap();
+        assert_eq!(fd_name, "conn");
+        UnixStream::from(fd).write_all(b"hi").unwrap();
+        let mut buf = [0; 2];
+        (&ours).read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"hi");
+
+        assert!(manager.fd_store_entries("web").is_err());
+        assert!(manager.store_fds("web", 1, Vec::new()).is_err());
+    }
+}
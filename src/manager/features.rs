@@ -0,0 +1,299 @@
+//! Optional kernel features and what the manager does without them
+//!
+//! Probed once when the manager is created. A unit asking for something the
+//! kernel cannot do still starts: settings that would make the spawn fail
+//! (namespaces, seccomp) are dropped, cgroup and BPF settings are not
+//! enforced, and either way a warning names the unit, the settings and the
+//! missing feature. `sysd analyze features` and the D-Bus Features property
+//! report the probe results.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::cgroups::{probe_cgroup_programs, FileSystemRestrictor};
+use crate::units::Service;
+
+use super::Manager;
+
+/// A kernel feature units can depend on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    /// cgroup v2 mounted and writable by the manager
+    Cgroups,
+    /// Mount namespaces (ProtectSystem=, PrivateTmp=, ReadOnlyPaths=, ...)
+    MountNamespaces,
+    /// Network namespaces (PrivateNetwork=, NetworkNamespacePath=)
+    NetworkNamespaces,
+    /// PID namespaces (PrivatePIDs=)
+    PidNamespaces,
+    /// IPC namespaces (PrivateIPC=)
+    IpcNamespaces,
+    /// seccomp filters (SystemCallFilter=, RestrictNamespaces=, ...)
+    Seccomp,
+    /// cgroup eBPF programs (IPAddressDeny=, SocketBindDeny=)
+    CgroupBpf,
+    /// The bpf LSM with kernel BTF (RestrictFileSystems=)
+    BpfLsm,
+    /// SELinux enabled (SELinuxContext=)
+    Selinux,
+    /// AppArmor enabled (AppArmorProfile=)
+    AppArmor,
+    /// Smack enabled (SmackProcessLabel=, SmackLabel= of sockets)
+    Smack,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 11] = [
+        Feature::Cgroups,
+        Feature::MountNamespaces,
+        Feature::NetworkNamespaces,
+        Feature::PidNamespaces,
+        Feature::IpcNamespaces,
+        Feature::Seccomp,
+        Feature::CgroupBpf,
+        Feature::BpfLsm,
+        Feature::Selinux,
+        Feature::AppArmor,
+        Feature::Smack,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Cgroups => "cgroups",
+            Feature::MountNamespaces => "mount-namespaces",
+            Feature::NetworkNamespaces => "network-namespaces",
+            Feature::PidNamespaces => "pid-namespaces",
+            Feature::IpcNamespaces => "ipc-namespaces",
+            Feature::Seccomp => "seccomp",
+            Feature::CgroupBpf => "cgroup-bpf",
+            Feature::BpfLsm => "bpf-lsm",
+            Feature::Selinux => "selinux",
+            Feature::AppArmor => "apparmor",
+            Feature::Smack => "smack",
+        }
+    }
+}
+
+/// Probe results: every feature is available unless listed as missing
+#[derive(Debug, Clone, Default)]
+pub struct FeatureSet {
+    missing: BTreeMap<Feature, String>,
+}
+
+impl FeatureSet {
+    /// Probe the running kernel; `cgroups` is how setting up the cgroup
+    /// manager went, which the BPF features depend on
+    pub fn probe(cgroups: Result<(), String>) -> Self {
+        let mut features = Self::default();
+        let has_cgroups = cgroups.is_ok();
+        if let Err(reason) = cgroups {
+            features.missing.insert(Feature::Cgroups, reason);
+        }
+        let probes = [
+            (Feature::MountNamespaces, path_exists("/proc/self/ns/mnt")),
+            (Feature::NetworkNamespaces, path_exists("/proc/self/ns/net")),
+            (Feature::PidNamespaces, path_exists("/proc/self/ns/pid")),
+            (Feature::IpcNamespaces, path_exists("/proc/self/ns/ipc")),
+            (
+                Feature::Seccomp,
+                path_exists("/proc/sys/kernel/seccomp/actions_avail"),
+            ),
+            (
+                Feature::CgroupBpf,
+                if has_cgroups {
+                    probe_cgroup_programs().map_err(|e| e.to_string())
+                } else {
+                    Err("needs cgroups".to_string())
+                },
+            ),
+            (
+                Feature::BpfLsm,
+                FileSystemRestrictor::probe().map_err(|e| e.to_string()),
+            ),
+            // Where ConditionSecurity= looks for them
+            (Feature::Selinux, path_exists("/sys/fs/selinux")),
+            (
+                Feature::AppArmor,
+                path_exists("/sys/kernel/security/apparmor"),
+            ),
+            (Feature::Smack, path_exists("/sys/fs/smackfs")),
+        ];
+        for (feature, result) in probes {
+            if let Err(reason) = result {
+                features.missing.insert(feature, reason);
+            }
+        }
+        features
+    }
+
+    pub fn available(&self, feature: Feature) -> bool {
+        !self.missing.contains_key(&feature)
+    }
+
+    /// Why `feature` is unavailable, None if it is available
+    pub fn missing(&self, feature: Feature) -> Option<&str> {
+        self.missing.get(&feature).map(String::as_str)
+    }
+
+    /// Every feature with the reason it is missing, in `Feature::ALL` order
+    pub fn iter(&self) -> impl Iterator<Item = (Feature, Option<&str>)> + '_ {
+        Feature::ALL
+            .into_iter()
+            .map(|feature| (feature, self.missing(feature)))
+    }
+}
+
+/// "+cgroups -seccomp ...", like the feature string of `systemctl --version`
+impl fmt::Display for FeatureSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags: Vec<String> = self
+            .iter()
+            .map(|(feature, missing)| {
+                let sign = if missing.is_some() { '-' } else { '+' };
+                format!("{}{}", sign, feature.name())
+            })
+            .collect();
+        f.write_str(&flags.join(" "))
+    }
+}
+
+fn path_exists(path: &str) -> Result<(), String> {
+    if Path::new(path).exists() {
+        Ok(())
+    } else {
+        Err(format!("{} does not exist", path))
+    }
+}
+
+impl Manager {
+    /// Which optional kernel features were found when the manager came up
+    pub fn features(&self) -> &FeatureSet {
+        &self.features
+    }
+
+    /// Whether `feature` is there for `settings` of unit `name`; warns that
+    /// they are not enforced if it is not
+    pub(super) fn can_enforce(&self, name: &str, feature: Feature, settings: &str) -> bool {
+        let Some(reason) = self.features.missing(feature) else {
+            return true;
+        };
+        log::warn!(
+            "{}: {} not enforced, {} unavailable: {}",
+            name,
+            settings,
+            feature.name(),
+            reason
+        );
+        false
+    }
+
+    /// Drop the sandboxing of `service` the kernel cannot provide, so the
+    /// unit starts without it instead of failing in the child
+    pub(super) fn degrade_sandbox(&self, name: &str, service: &mut Service) {
+        let section = &mut service.service;
+        if section.private_network || section.network_namespace_path.is_some() {
+            let settings = "PrivateNetwork=/NetworkNamespacePath=";
+            if !self.can_enforce(name, Feature::NetworkNamespaces, settings) {
+                section.private_network = false;
+                section.network_namespace_path = None;
+            }
+        }
+        if section.private_pids && !self.can_enforce(name, Feature::PidNamespaces, "PrivatePIDs=") {
+            section.private_pids = false;
+        }
+        if section.private_ipc && !self.can_enforce(name, Feature::IpcNamespaces, "PrivateIPC=") {
+            section.private_ipc = false;
+        }
+        if section.selinux_context.is_some()
+            && !self.can_enforce(name, Feature::Selinux, "SELinuxContext=")
+        {
+            section.selinux_context = None;
+        }
+        if section.apparmor_profile.is_some()
+            && !self.can_enforce(name, Feature::AppArmor, "AppArmorProfile=")
+        {
+            section.apparmor_profile = None;
+        }
+        if section.smack_process_label.is_some()
+            && !self.can_enforce(name, Feature::Smack, "SmackProcessLabel=")
+        {
+            section.smack_process_label = None;
+        }
+        if super::sandbox::has_seccomp_settings(section)
+            && !self.can_enforce(name, Feature::Seccomp, "SystemCallFilter= and friends")
+        {
+            section.restrict_namespaces = None;
+            section.system_call_filter.clear();
+            section.system_call_architectures.clear();
+            section.restrict_address_families = None;
+            section.restrict_realtime = false;
+            section.restrict_suid_sgid = false;
+            section.protect_clock = false;
+            section.protect_hostname = false;
+            section.lock_personality = false;
+        }
+        if super::sandbox::needs_mount_namespace(section)
+            && !self.can_enforce(name, Feature::MountNamespaces, "file system protection")
+        {
+            section.protect_system = Default::default();
+            section.protect_home = Default::default();
+            section.protect_proc = Default::default();
+            section.device_policy = Default::default();
+            section.private_tmp = false;
+            section.private_devices = false;
+            section.read_only_paths.clear();
+            section.read_write_paths.clear();
+            section.inaccessible_paths.clear();
+            section.protect_control_groups = false;
+            section.protect_kernel_tunables = false;
+            section.protect_kernel_logs = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{parse_file, parse_service};
+
+    #[test]
+    fn bpf_features_need_cgroups_and_flags_list_every_feature() {
+        let features = FeatureSet::probe(Err("no cgroup2 mount".to_string()));
+        assert_eq!(features.missing(Feature::Cgroups), Some("no cgroup2 mount"));
+        assert_eq!(features.missing(Feature::CgroupBpf), Some("needs cgroups"));
+        assert!(!features.available(Feature::CgroupBpf));
+
+        let flags = features.to_string();
+        assert!(flags.starts_with("-cgroups "));
+        assert!(flags.contains("-cgroup-bpf"));
+        assert_eq!(flags.split(' ').count(), Feature::ALL.len());
+    }
+
+    #[test]
+    fn sandboxing_without_kernel_support_is_dropped() {
+        let mut manager = Manager::new_user();
+        let unit = "[Service]\nExecStart=/bin/true\nPrivateNetwork=yes\nPrivatePIDs=yes\n\
+                    SystemCallFilter=@system-service\nProtectSystem=strict\n\
+                    SELinuxContext=system_u:system_r:box_t:s0\n";
+        let mut service = parse_service("box.service", &parse_file(unit).unwrap()).unwrap();
+        manager.features = FeatureSet::default();
+        for feature in [
+            Feature::NetworkNamespaces,
+            Feature::PidNamespaces,
+            Feature::Seccomp,
+            Feature::Selinux,
+        ] {
+            manager.features.missing.insert(feature, "test".to_string());
+        }
+
+        manager.degrade_sandbox("box.service", &mut service);
+        assert!(!service.service.private_network);
+        assert!(!service.service.private_pids);
+        assert!(service.service.system_call_filter.is_empty());
+        assert_eq!(service.service.selinux_context, None);
+        assert!(crate::manager::sandbox::needs_mount_namespace(
+            &service.service
+        ));
+    }
+}
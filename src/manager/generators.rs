@@ -1,6 +1,7 @@
 //! Built-in generator support
 //!
-//! Replaces systemd-fstab-generator and systemd-getty-generator with built-in parsing.
+//! Replaces systemd-fstab-generator, systemd-getty-generator and
+//! systemd-sysv-generator with built-in parsing.
 
 use std::path::Path;
 
@@ -12,7 +13,7 @@ impl Manager {
     /// Replaces systemd-fstab-generator - parses fstab directly and creates
     /// Mount units for entries that should be mounted at boot.
     pub fn load_fstab(&mut self) -> Result<usize, ManagerError> {
-        self.load_fstab_from(Path::new("/etc/fstab"))
+        self.load_fstab_from(&crate::root::path("/etc/fstab"))
     }
 
     /// Load mount units from a specific fstab file (for testing)
@@ -42,7 +43,7 @@ impl Manager {
             log::debug!("Loading mount from fstab: {}", name);
             local_fs_mounts.push(name.clone());
             self.states.insert(name.clone(), ServiceState::new());
-            self.units.insert(name, Unit::Mount(mount));
+            self.insert_unit(name, Unit::Mount(mount));
         }
 
         // Add fstab mounts to local-fs.target's requirements
@@ -60,6 +61,7 @@ impl Manager {
                     "Added {} fstab mounts to local-fs.target requirements",
                     local_fs_mounts.len()
                 );
+                self.reindex_unit("local-fs.target");
             } else {
                 // local-fs.target not loaded yet - store for later
                 // For now, we'll load it first
@@ -109,7 +111,7 @@ impl Manager {
 
             log::debug!("Loading getty from cmdline: {}", name);
             self.states.insert(name.clone(), ServiceState::new());
-            self.units.insert(name, Unit::Service(svc));
+            self.insert_unit(name, Unit::Service(svc));
         }
 
         log::info!("Loaded {} getty units from {}", count, path.display());
@@ -131,12 +133,131 @@ impl Manager {
             }
 
             self.states.insert(name.clone(), ServiceState::new());
-            self.units.insert(name, Unit::Service(svc));
+            self.insert_unit(name, Unit::Service(svc));
         }
 
         log::info!("Loaded {} default getty units", count);
         Ok(count)
     }
+
+    /// Load wrapper services for the SysV init scripts in /etc/init.d
+    ///
+    /// Replaces systemd-sysv-generator - scripts with an LSB header and no
+    /// native unit become services, wanted by the targets of the runlevels
+    /// (2-5) they start in.
+    pub fn load_sysv_scripts(&mut self) -> Result<usize, ManagerError> {
+        self.load_sysv_scripts_from(
+            &crate::root::path("/etc/init.d"),
+            &crate::root::path("/etc"),
+        )
+    }
+
+    /// Load SysV services from a specific init.d directory, with the rcN.d
+    /// directories below `rc_root` (for testing)
+    pub fn load_sysv_scripts_from(
+        &mut self,
+        init_d: &Path,
+        rc_root: &Path,
+    ) -> Result<usize, ManagerError> {
+        use crate::pid1::runlevel_target;
+        use crate::sysv::{find_sysv_scripts, generate_sysv_services, script_runlevels};
+
+        if !init_d.is_dir() {
+            log::debug!("No init scripts at {}, skipping", init_d.display());
+            return Ok(0);
+        }
+        let scripts: Vec<_> = find_sysv_scripts(init_d)
+            .into_iter()
+            .filter(|script| {
+                let name = script.service_name();
+                let native = self.units.contains_key(&name) || self.find_unit(&name).is_ok();
+                if native {
+                    log::debug!("{} has a native unit, ignoring its init script", name);
+                }
+                !native
+            })
+            .collect();
+        let services = generate_sysv_services(&scripts);
+        let count = services.len();
+
+        for (script, svc) in scripts.iter().zip(services) {
+            let name = svc.name.clone();
+            let targets = script_runlevels(rc_root, script)
+                .into_iter()
+                .filter(|runlevel| ('2'..='5').contains(runlevel))
+                .filter_map(runlevel_target);
+            for target in targets {
+                self.add_generated_want(target, &name);
+            }
+            log::debug!("Loading {} from {}", name, script.path.display());
+            self.states.insert(name.clone(), ServiceState::new());
+            self.insert_unit(name, Unit::Service(svc));
+        }
+
+        log::info!("Loaded {} SysV services from {}", count, init_d.display());
+        Ok(count)
+    }
+
+    /// Make `target` want the generated unit `name`, now if it is loaded and
+    /// whenever it is parsed again
+    fn add_generated_want(&mut self, target: &str, name: &str) {
+        let wanted = self.generated_wants.entry(target.to_string()).or_default();
+        if wanted.iter().any(|unit| unit == name) {
+            return;
+        }
+        wanted.push(name.to_string());
+        if let Some(unit) = self.units.get_mut(target) {
+            unit.unit_section_mut().wants_dir.push(name.to_string());
+            self.reindex_unit(target);
+        }
+    }
+
+    /// Add the generated units `unit` wants, like .wants/ links would
+    pub(super) fn add_generated_wants(&self, unit: &mut Unit) {
+        let Some(wanted) = self.generated_wants.get(unit.name()) else {
+            return;
+        };
+        let section = unit.unit_section_mut();
+        for name in wanted {
+            if !section.wants_dir.contains(name) {
+                section.wants_dir.push(name.clone());
+            }
+        }
+    }
+
+    /// Start a getty on virtual console `vt` (logind's autovt): autovt@ttyN,
+    /// else getty@ttyN, generated if no unit file provides it
+    pub async fn start_autovt(&mut self, vt: u32) -> Result<(), ManagerError> {
+        let name = self.autovt_unit(vt);
+        if self
+            .states
+            .get(&name)
+            .is_some_and(|state| state.is_active())
+        {
+            return Ok(());
+        }
+        log::info!("Starting {} for VT {}", name, vt);
+        self.start_with_deps(&name).await.map(|_| ())
+    }
+
+    fn autovt_unit(&mut self, vt: u32) -> String {
+        let candidates = [
+            format!("autovt@tty{}.service", vt),
+            format!("getty@tty{}.service", vt),
+        ];
+        if let Some(name) = candidates
+            .iter()
+            .find(|name| self.units.contains_key(*name) || self.find_unit(name).is_ok())
+        {
+            return name.clone();
+        }
+
+        let svc = crate::getty::generate_vt_getty(vt);
+        let name = svc.name.clone();
+        self.states.insert(name.clone(), ServiceState::new());
+        self.insert_unit(name.clone(), Unit::Service(svc));
+        name
+    }
 }
 
 #[cfg(test)]
@@ -185,16 +306,27 @@ mod tests {
         .unwrap();
 
         assert_eq!(manager.load_fstab_from(&fstab).unwrap(), 2);
-        assert!(matches!(manager.units.get("boot.mount"), Some(Unit::Mount(_))));
+        assert!(matches!(
+            manager.units.get("boot.mount"),
+            Some(Unit::Mount(_))
+        ));
         assert!(matches!(
             manager.units.get("mnt-share.mount"),
             Some(Unit::Mount(_))
         ));
         assert!(!manager.units.contains_key("home.mount"));
 
-        let local_fs = manager.units.get("local-fs.target").unwrap().as_target().unwrap();
+        let local_fs = manager
+            .units
+            .get("local-fs.target")
+            .unwrap()
+            .as_target()
+            .unwrap();
         assert!(local_fs.unit.requires.contains(&"boot.mount".to_string()));
-        assert!(local_fs.unit.requires.contains(&"mnt-share.mount".to_string()));
+        assert!(local_fs
+            .unit
+            .requires
+            .contains(&"mnt-share.mount".to_string()));
     }
 
     #[test]
@@ -213,13 +345,72 @@ mod tests {
         assert!(!manager.states.contains_key("boot.mount"));
     }
 
+    #[test]
+    fn load_sysv_scripts_skips_native_units_and_wires_runlevel_targets() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root = temp_dir("sysv");
+        let units = root.0.join("units");
+        let init_d = root.0.join("init.d");
+        std::fs::create_dir_all(&units).unwrap();
+        std::fs::create_dir_all(&init_d).unwrap();
+        let header = "#!/bin/sh\n### BEGIN INIT INFO\n# Provides: legacy\n\
+                      # Default-Start: 2 3 4 5\n### END INIT INFO\n";
+        for name in ["legacy", "native"] {
+            let path = init_d.join(name);
+            std::fs::write(&path, header).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let native = "[Service]\nExecStart=/bin/true\n";
+        std::fs::write(units.join("native.service"), native).unwrap();
+        let mut manager = Manager::new();
+        manager.unit_paths = vec![units];
+        manager.units.insert(
+            "multi-user.target".to_string(),
+            Unit::Target(Target::new("multi-user.target".to_string())),
+        );
+
+        assert_eq!(manager.load_sysv_scripts_from(&init_d, &root.0).unwrap(), 1);
+        assert!(matches!(
+            manager.units.get("legacy.service"),
+            Some(Unit::Service(_))
+        ));
+        assert!(!manager.units.contains_key("native.service"));
+        let multi_user = manager.units.get("multi-user.target").unwrap();
+        assert_eq!(multi_user.unit_section().wants_dir, ["legacy.service"]);
+
+        let mut graphical = Unit::Target(Target::new("graphical.target".to_string()));
+        manager.add_generated_wants(&mut graphical);
+        assert_eq!(graphical.unit_section().wants_dir, ["legacy.service"]);
+    }
+
+    #[test]
+    fn autovt_prefers_unit_files_and_generates_missing_gettys() {
+        let root = temp_dir("autovt");
+        let mut manager = Manager::new();
+        manager.unit_paths = vec![root.0.clone()];
+
+        assert_eq!(manager.autovt_unit(9), "getty@tty9.service");
+        assert!(manager.units.contains_key("getty@tty9.service"));
+
+        std::fs::write(
+            root.0.join("autovt@.service"),
+            "[Service]\nExecStart=/sbin/agetty %I\n",
+        )
+        .unwrap();
+        assert_eq!(manager.autovt_unit(3), "autovt@tty3.service");
+    }
+
     #[test]
     fn load_gettys_uses_defaults_for_missing_or_consoleless_cmdline() {
         let root = temp_dir("getty-defaults");
         let cmdline = root.0.join("cmdline");
         let mut manager = Manager::new();
 
-        assert_eq!(manager.load_gettys_from(&root.0.join("missing")).unwrap(), 6);
+        assert_eq!(
+            manager.load_gettys_from(&root.0.join("missing")).unwrap(),
+            6
+        );
         assert!(manager.units.contains_key("getty@tty1.service"));
         assert!(manager.units.contains_key("getty@tty6.service"));
 
@@ -0,0 +1,249 @@
+//! Health checks of running services (ExecHealthCheck=, a sysd extension)
+//!
+//! Not every daemon can be taught to ping a watchdog. A service with
+//! ExecHealthCheck= is probed every HealthCheckIntervalSec= once it runs:
+//! by a command that must exit 0, a TCP connect (tcp://HOST:PORT) or an
+//! HTTP GET that must answer 2xx (http://HOST[:PORT]/PATH). A probe not done
+//! within the interval failed. After HealthCheckRetries= failures in a row
+//! the service is stopped in the background (ExecStop=, KillSignal=,
+//! ExecStopPost=), fails with result health-check and Restart=on-failure/always
+//! restarts it, like a missed watchdog ping.
+
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::task::JoinHandle;
+
+use crate::units::HealthCheck;
+
+use super::{run_simple_command_with_env, ActiveState, Manager, ServiceResult, SubState};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Health of one running service
+#[derive(Debug)]
+pub(crate) struct HealthState {
+    /// When the next probe starts
+    next: Instant,
+    /// Failed probes in a row
+    failures: u32,
+    probe: Option<JoinHandle<bool>>,
+}
+
+impl Drop for HealthState {
+    fn drop(&mut self) {
+        if let Some(probe) = &self.probe {
+            probe.abort();
+        }
+    }
+}
+
+/// A running service with a health check
+struct Checked {
+    name: String,
+    check: HealthCheck,
+    interval: Duration,
+    retries: u32,
+    main_pid: Option<u32>,
+}
+
+impl Manager {
+    /// Start due health checks of running services, count their results and
+    /// fail the services that ran out of retries
+    pub async fn process_health_checks(&mut self) {
+        let checked = self.health_checked_services();
+        self.health_checks
+            .retain(|name, _| checked.iter().any(|c| &c.name == name));
+
+        let now = Instant::now();
+        let mut unhealthy = Vec::new();
+        for checked in checked {
+            let health = self
+                .health_checks
+                .entry(checked.name.clone())
+                .or_insert_with(|| HealthState {
+                    next: now + checked.interval,
+                    failures: 0,
+                    probe: None,
+                });
+            if let Some(probe) = health.probe.take_if(|probe| probe.is_finished()) {
+                if probe.await.unwrap_or(false) {
+                    if health.failures > 0 {
+                        log::info!("{} is healthy again", checked.name);
+                    }
+                    health.failures = 0;
+                } else {
+                    health.failures += 1;
+                    log::warn!(
+                        "{} health check failed ({}/{})",
+                        checked.name,
+                        health.failures,
+                        checked.retries
+                    );
+                    if health.failures >= checked.retries {
+                        unhealthy.push(checked.name);
+                        continue;
+                    }
+                }
+            }
+            if health.probe.is_none() && now >= health.next {
+                health.next = now + checked.interval;
+                let env: Vec<(String, String)> = checked
+                    .main_pid
+                    .map(|pid| ("MAINPID".to_string(), pid.to_string()))
+                    .into_iter()
+                    .collect();
+                let probe = probe(checked.check, env, checked.interval);
+                health.probe = Some(tokio::spawn(probe));
+            }
+        }
+
+        for name in unhealthy {
+            self.health_checks.remove(&name);
+            log::warn!("{} is unhealthy, stopping it", name);
+            self.enqueue_failure_stop(&name, ServiceResult::HealthCheck, None);
+        }
+    }
+
+    /// Services with ExecHealthCheck= that are up and running
+    fn health_checked_services(&self) -> Vec<Checked> {
+        self.units
+            .iter()
+            .filter_map(|(name, unit)| {
+                let service = unit.as_service()?;
+                let check = service.service.exec_health_check.clone()?;
+                let state = self.states.get(name)?;
+                if state.active != ActiveState::Active || state.sub != SubState::Running {
+                    return None;
+                }
+                Some(Checked {
+                    name: name.clone(),
+                    check,
+                    interval: service
+                        .service
+                        .health_check_interval_sec
+                        .unwrap_or(DEFAULT_INTERVAL),
+                    retries: service
+                        .service
+                        .health_check_retries
+                        .unwrap_or(DEFAULT_RETRIES),
+                    main_pid: state.main_pid.filter(|&pid| pid != 0),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Run one probe; false if it fails or takes longer than `timeout`
+pub(super) async fn probe(
+    check: HealthCheck,
+    env: Vec<(String, String)>,
+    timeout: Duration,
+) -> bool {
+    let probe = async {
+        match &check {
+            HealthCheck::Exec(command) => run_simple_command_with_env(command, &env).await.is_ok(),
+            HealthCheck::Tcp(address) => TcpStream::connect(address.as_str()).await.is_ok(),
+            HealthCheck::Http {
+                address,
+                host,
+                path,
+            } => http_get_succeeds(address, host, path).await,
+        }
+    };
+    tokio::time::timeout(timeout, probe).await.unwrap_or(false)
+}
+
+async fn http_get_succeeds(address: &str, host: &str, path: &str) -> bool {
+    let Ok(mut stream) = TcpStream::connect(address).await else {
+        return false;
+    };
+    let request = format!(
+        "GET {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+    if stream.write_all(request.as_bytes()).await.is_err() {
+        return false;
+    }
+    // "HTTP/1.1 200" is all we need of the answer
+    let mut status_line = [0; 12];
+    if stream.read_exact(&mut status_line).await.is_err() {
+        return false;
+    }
+    is_success_status(&status_line)
+}
+
+fn is_success_status(status_line: &[u8]) -> bool {
+    let status_line = String::from_utf8_lossy(status_line);
+    let mut words = status_line.split_whitespace();
+    let version = words.next().unwrap_or_default();
+    let code = words.next().unwrap_or_default();
+    version.starts_with("HTTP/") && code.len() == 3 && code.starts_with('2')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::{ServiceState, StopEvent};
+    use crate::units::{RestartPolicy, Service, Unit};
+
+    #[test]
+    fn only_2xx_status_lines_are_healthy() {
+        assert!(is_success_status(b"HTTP/1.1 200"));
+        assert!(is_success_status(b"HTTP/1.0 204"));
+        assert!(!is_success_status(b"HTTP/1.1 503"));
+        assert!(!is_success_status(b"SSH-2.0-Open"));
+    }
+
+    #[tokio::test]
+    async fn probes_pass_and_fail() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let open = listener.local_addr().unwrap().to_string();
+        let timeout = Duration::from_secs(5);
+        assert!(probe(HealthCheck::Tcp(open), Vec::new(), timeout).await);
+        drop(listener);
+
+        let env = vec![("MAINPID".to_string(), "7".to_string())];
+        let check = HealthCheck::Exec("/bin/sh -c 'test $1 = 7' sh $MAINPID".to_string());
+        assert!(probe(check, env.clone(), timeout).await);
+        let check = HealthCheck::Exec("/bin/sh -c 'exit 1'".to_string());
+        assert!(!probe(check, env.clone(), timeout).await);
+        let check = HealthCheck::Exec("/bin/sleep 1".to_string());
+        assert!(!probe(check, env, Duration::from_millis(50)).await);
+    }
+
+    #[tokio::test]
+    async fn service_failing_its_checks_fails_and_is_restarted() {
+        let mut manager = Manager::new_user();
+        let mut service = Service::new("app.service".to_string());
+        service.service.exec_health_check = Some(HealthCheck::Exec("/bin/false".to_string()));
+        service.service.health_check_interval_sec = Some(Duration::from_millis(10));
+        service.service.health_check_retries = Some(2);
+        service.service.restart = RestartPolicy::OnFailure;
+        manager.insert_unit("app.service".into(), Unit::Service(service));
+        let mut state = ServiceState::new();
+        state.set_running(0);
+        manager.states.insert("app.service".into(), state);
+
+        let mut stop_events = manager.take_stop_event_rx().unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while manager.states["app.service"].sub == SubState::Running && Instant::now() < deadline {
+            manager.process_health_checks().await;
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        while let Some(event) = stop_events.recv().await {
+            let finished = matches!(event, StopEvent::Finished { .. });
+            manager.handle_stop_event(event).await;
+            if finished {
+                break;
+            }
+        }
+
+        let state = &manager.states["app.service"];
+        assert_eq!(state.sub, SubState::AutoRestart);
+        assert!(manager.health_checks.is_empty());
+    }
+}
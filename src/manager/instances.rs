@@ -0,0 +1,173 @@
+//! Several units in one argument, and the templates instances come from
+//!
+//! `list`/`start`/`stop`/`restart`/`enable`/`reset-failed` take brace
+//! patterns and wildcards (`foo@{a,b}`, `getty@*`): braces name units that
+//! need not be loaded yet, so instances of a template are loaded from it on
+//! demand; wildcards match loaded units and the unit files on disk. The CLI
+//! and D-Bus (StartUnits, StopUnits, ListUnitsByPatterns, ResetFailedUnit)
+//! all go through `units_by_patterns`.
+//!
+//! The manager remembers which template file each instance was loaded from,
+//! so daemon-reload re-reads the instance from it under the instance name
+//! and template edits flag it.
+
+use crate::units;
+
+use super::Manager;
+
+impl Manager {
+    /// Unit names `patterns` stand for, in order without duplicates; a
+    /// wildcard matching no unit contributes nothing
+    pub fn expand_unit_patterns(&self, patterns: &[String]) -> Vec<String> {
+        self.units_by_patterns(patterns, &[])
+    }
+
+    /// Like `expand_unit_patterns`, keeping only units in one of the active
+    /// states `states` ("active", "failed", ...; all if empty), as for
+    /// ListUnitsByPatterns. Units that are not loaded count as inactive.
+    pub fn units_by_patterns(&self, patterns: &[String], states: &[String]) -> Vec<String> {
+        let in_state = |name: &String| {
+            let state = self
+                .states
+                .get(name)
+                .map_or("inactive", |s| s.active.as_str());
+            states.is_empty() || states.iter().any(|wanted| wanted == state)
+        };
+        let mut names: Vec<String> = Vec::new();
+        let mut add = |name: String| {
+            if !names.contains(&name) && in_state(&name) {
+                names.push(name);
+            }
+        };
+        let files = self.preset_unit_files();
+        for pattern in patterns {
+            for expanded in units::expand_unit_braces(pattern) {
+                if !units::has_glob_chars(&expanded) {
+                    add(self.normalize_name(&expanded));
+                    continue;
+                }
+                let mut matched: Vec<&String> = self
+                    .units
+                    .keys()
+                    .chain(&files)
+                    .filter(|name| units::unit_name_matches(&expanded, name))
+                    .collect();
+                matched.sort();
+                matched.dedup();
+                matched.into_iter().cloned().for_each(&mut add);
+            }
+        }
+        names
+    }
+
+    /// Template unit `name` was loaded from, None unless it is an instance
+    /// without a unit file of its own
+    pub fn instance_template(&self, name: &str) -> Option<&str> {
+        self.instance_templates.get(name).map(String::as_str)
+    }
+
+    /// Remember whether the instance `name` comes from a template, given the
+    /// file `path` it was just parsed from
+    pub(super) fn record_instance_template(&mut self, name: &str, path: &std::path::Path) {
+        let own_file = path.file_name().is_some_and(|file| file == name);
+        match units::get_template_name(name) {
+            Some(template) if !own_file && !units::is_bare_template(name) => {
+                self.instance_templates.insert(name.to_string(), template);
+            }
+            _ => {
+                self.instance_templates.remove(name);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::UnitFilesChanged;
+
+    fn temp_dir(label: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sysd-instances-{}-{}", label, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[tokio::test]
+    async fn braces_load_instances_and_wildcards_match_loaded_units() {
+        let dir = temp_dir("expand");
+        std::fs::write(
+            dir.join("vm@.service"),
+            "[Service]\nExecStart=/bin/echo %i\n",
+        )
+        .unwrap();
+        let mut manager = Manager::new_user();
+        manager.unit_paths = vec![dir.clone()];
+
+        let names = manager.expand_unit_patterns(&["vm@{b,a}".to_string(), "vm@b".to_string()]);
+        assert_eq!(names, ["vm@b.service", "vm@a.service"]);
+        for name in &names {
+            manager.load(name).await.unwrap();
+        }
+        assert_eq!(
+            manager.instance_template("vm@a.service"),
+            Some("vm@.service")
+        );
+        assert_eq!(
+            manager.expand_unit_patterns(&["vm@*".to_string(), "none@*".to_string()]),
+            ["vm@a.service", "vm@b.service"]
+        );
+
+        // Wildcards also find unit files not loaded yet, which are inactive
+        std::fs::write(dir.join("web.service"), "[Service]\nExecStart=/bin/true\n").unwrap();
+        assert_eq!(
+            manager.expand_unit_patterns(&["web*".to_string()]),
+            ["web.service"]
+        );
+        let running = ["active".to_string()];
+        assert!(manager
+            .units_by_patterns(&["web*".to_string(), "vm@*".to_string()], &running)
+            .is_empty());
+
+        // An instance file of its own takes over at daemon-reload
+        std::fs::write(dir.join("vm@a.service"), "[Service]\nExecStart=/bin/true\n").unwrap();
+        manager.reload_units().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(manager.instance_template("vm@a.service"), None);
+        assert_eq!(
+            manager.instance_template("vm@b.service"),
+            Some("vm@.service")
+        );
+        assert_eq!(
+            manager.get_unit("vm@b.service").unwrap().name(),
+            "vm@b.service"
+        );
+    }
+
+    #[tokio::test]
+    async fn instances_of_an_aliased_template_follow_its_target() {
+        let dir = temp_dir("alias");
+        std::fs::write(
+            dir.join("real@.service"),
+            "[Service]\nExecStart=/bin/true\n",
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(dir.join("real@.service"), dir.join("link@.service")).unwrap();
+        let mut manager = Manager::new_user();
+        manager.unit_paths = vec![dir.clone()];
+
+        let name = manager.load("link@x.service").await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        assert_eq!(name, "real@x.service");
+        assert_eq!(manager.instance_template(&name), Some("real@.service"));
+
+        manager
+            .handle_unit_files_changed(UnitFilesChanged {
+                units: vec!["real@.service".into()],
+            })
+            .await
+            .unwrap();
+        assert!(manager.needs_daemon_reload("real@x.service"));
+    }
+}
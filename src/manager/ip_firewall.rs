@@ -0,0 +1,69 @@
+//! IPAddressAllow=/IPAddressDeny= of services
+//!
+//! The firewall goes on the service cgroup while sysd-executor waits at its
+//! exec gate (see `exec_gate`), so the service never sends a packet
+//! unfiltered. Without cgroups or cgroup-bpf the service runs unfiltered
+//! with a warning, like systemd on kernels lacking BPF firewall support.
+
+use crate::cgroups::{IpCounters, IpFirewall};
+use crate::units::Service;
+
+use super::{Feature, Manager};
+
+impl Manager {
+    /// Attach the firewall of `service` to its cgroup, replacing the one of an
+    /// earlier run
+    pub(super) fn attach_ip_firewall(&mut self, name: &str, service: &Service) {
+        self.ip_firewalls.remove(name);
+        let allow = &service.service.ip_address_allow;
+        let deny = &service.service.ip_address_deny;
+        if allow.is_empty() && deny.is_empty() {
+            return;
+        }
+        if !self.can_enforce(name, Feature::CgroupBpf, "IPAddressAllow=/IPAddressDeny=") {
+            return;
+        }
+        let Some(cgroup_path) = self.cgroup_paths.get(name) else {
+            log::warn!(
+                "{}: no cgroup, IPAddressAllow=/IPAddressDeny= not enforced",
+                name
+            );
+            return;
+        };
+        match IpFirewall::attach(cgroup_path, allow, deny) {
+            Ok(firewall) => {
+                log::debug!("Attached IP firewall to {}", cgroup_path.display());
+                self.ip_firewalls.insert(name.to_string(), firewall);
+            }
+            Err(e) => log::warn!(
+                "{}: IPAddressAllow=/IPAddressDeny= not enforced, cgroup BPF unavailable: {}",
+                name,
+                e
+            ),
+        }
+    }
+
+    /// IPIngressBytes/IPEgressBytes (and packets) of the current or last run
+    /// of `name`; None for units without IPAddressAllow=/IPAddressDeny=
+    pub fn ip_counters(&self, name: &str) -> Option<IpCounters> {
+        self.ip_firewalls.get(name)?.counters().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{parse_file, parse_service};
+
+    #[test]
+    fn firewall_is_skipped_without_a_cgroup() {
+        let mut manager = Manager::new_user();
+        let parsed = parse_file("[Service]\nExecStart=/bin/true\nIPAddressDeny=any\n").unwrap();
+        let service = parse_service("fw.service", &parsed).unwrap();
+        assert_eq!(service.service.ip_address_deny.len(), 2);
+
+        manager.attach_ip_firewall("fw.service", &service);
+        assert!(manager.ip_firewalls.is_empty());
+        assert_eq!(manager.ip_counters("fw.service"), None);
+    }
+}
@@ -0,0 +1,185 @@
+//! Sending signals to unit processes (`sysdctl kill`, D-Bus KillUnit)
+//!
+//! Like `systemctl kill`, the signal goes to the main process, the control
+//! process (a running ExecStop=/ExecStopPost= command) or every process in
+//! the unit's cgroup.
+
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+
+use super::{Manager, ManagerError};
+
+/// Which processes of a unit receive the signal (--kill-whom=)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KillWhom {
+    Main,
+    Control,
+    /// Main, control and every other process in the unit's cgroup
+    #[default]
+    All,
+}
+
+impl KillWhom {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "main" => Some(Self::Main),
+            "control" => Some(Self::Control),
+            "all" => Some(Self::All),
+            _ => None,
+        }
+    }
+}
+
+impl Manager {
+    /// Send `signal` to the processes of `name` selected by `whom`; signal 0
+    /// only checks that they exist. Returns the PIDs that were signalled.
+    pub fn kill_unit(
+        &self,
+        name: &str,
+        whom: KillWhom,
+        signal: i32,
+    ) -> Result<Vec<u32>, ManagerError> {
+        let is_known =
+            |name: &str| self.units.contains_key(name) || self.scope_manager.exists(name);
+        let name = match is_known(name) {
+            true => name.to_string(),
+            false => self.normalize_name(name),
+        };
+        if !is_known(&name) {
+            return Err(ManagerError::NotFound(name));
+        }
+        let signal = match signal {
+            0 => None,
+            n => Some(Signal::try_from(n).map_err(|_| ManagerError::InvalidSignal(n))?),
+        };
+
+        let pids = self.unit_pids(&name, whom);
+        if pids.is_empty() {
+            return Err(ManagerError::NotActive(name));
+        }
+        for &pid in &pids {
+            let signal_name = signal.map_or("signal 0", |signal| signal.as_str());
+            log::info!("Sending {} to {} (PID {})", signal_name, name, pid);
+            if let Err(e) = signal::kill(Pid::from_raw(pid as i32), signal) {
+                log::warn!("Failed to signal PID {} of {}: {}", pid, name, e);
+            }
+        }
+        Ok(pids)
+    }
+
+    fn unit_pids(&self, name: &str, whom: KillWhom) -> Vec<u32> {
+        let main = self
+            .states
+            .get(name)
+            .and_then(|state| state.main_pid)
+            .or_else(|| self.processes.get(name).and_then(|child| child.id()));
+        let control = self.control_pids.lock().unwrap().get(name).copied();
+
+        match whom {
+            KillWhom::Main => main.into_iter().collect(),
+            KillWhom::Control => control.into_iter().collect(),
+            KillWhom::All => {
+                let mut pids: Vec<u32> = main.into_iter().chain(control).collect();
+                let cgroup_path = self
+                    .cgroup_paths
+                    .get(name)
+                    .or_else(|| self.scope_manager.get_cgroup_path(name));
+                let cgroup_pids = self
+                    .cgroup_manager
+                    .as_ref()
+                    .or(self.scope_manager.cgroup_manager())
+                    .zip(cgroup_path)
+                    .and_then(|(cgroup_mgr, path)| cgroup_mgr.get_pids(path).ok())
+                    .unwrap_or_default();
+                for pid in cgroup_pids {
+                    if !pids.contains(&pid) {
+                        pids.push(pid);
+                    }
+                }
+                pids
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+    use crate::units::{Service, Unit};
+    use std::os::unix::process::ExitStatusExt;
+
+    fn manager_with(name: &str) -> Manager {
+        let mut manager = Manager::new_user();
+        manager.units.insert(
+            name.to_string(),
+            Unit::Service(Service::new(name.to_string())),
+        );
+        manager.states.insert(name.to_string(), ServiceState::new());
+        manager
+    }
+
+    fn sleeper() -> tokio::process::Child {
+        tokio::process::Command::new("/bin/sleep")
+            .arg("5")
+            .spawn()
+            .unwrap()
+    }
+
+    #[test]
+    fn kill_whom_parses_systemctl_values() {
+        assert_eq!(KillWhom::parse("main"), Some(KillWhom::Main));
+        assert_eq!(KillWhom::parse("Control"), Some(KillWhom::Control));
+        assert_eq!(KillWhom::parse("all"), Some(KillWhom::All));
+        assert_eq!(KillWhom::parse("cgroup"), None);
+    }
+
+    #[tokio::test]
+    async fn kill_signals_main_or_control_process() {
+        let mut manager = manager_with("web.service");
+        let mut main = sleeper();
+        let mut control = sleeper();
+        let main_pid = main.id().unwrap();
+        let control_pid = control.id().unwrap();
+        manager
+            .states
+            .get_mut("web.service")
+            .unwrap()
+            .set_running(main_pid);
+        manager
+            .control_pids
+            .lock()
+            .unwrap()
+            .insert("web.service".to_string(), control_pid);
+
+        let signalled = manager
+            .kill_unit("web", KillWhom::Control, libc::SIGTERM)
+            .unwrap();
+        assert_eq!(signalled, [control_pid]);
+        assert_eq!(control.wait().await.unwrap().signal(), Some(libc::SIGTERM));
+        manager.control_pids.lock().unwrap().clear();
+
+        let signalled = manager
+            .kill_unit("web.service", KillWhom::All, libc::SIGKILL)
+            .unwrap();
+        assert_eq!(signalled, [main_pid]);
+        assert_eq!(main.wait().await.unwrap().signal(), Some(libc::SIGKILL));
+    }
+
+    #[test]
+    fn kill_rejects_unknown_units_bad_signals_and_idle_units() {
+        let manager = manager_with("idle.service");
+        assert!(matches!(
+            manager.kill_unit("missing.service", KillWhom::All, libc::SIGTERM),
+            Err(ManagerError::NotFound(_))
+        ));
+        assert!(matches!(
+            manager.kill_unit("idle.service", KillWhom::All, 999),
+            Err(ManagerError::InvalidSignal(999))
+        ));
+        assert!(matches!(
+            manager.kill_unit("idle.service", KillWhom::Main, libc::SIGTERM),
+            Err(ManagerError::NotActive(_))
+        ));
+    }
+}
@@ -0,0 +1,167 @@
+//! LoadState, UnitFileState and FragmentPath of units
+//!
+//! Frontends such as cockpit read these properties from every unit:
+//! LoadState says whether the unit file was found and parsed, UnitFileState
+//! whether it is enabled, masked or linked in from outside the unit search
+//! path, and FragmentPath which file it was loaded from.
+
+use std::path::{Path, PathBuf};
+
+use super::Manager;
+
+impl Manager {
+    /// Unit file a loaded unit was parsed from (None for scopes and units
+    /// created from the mount table)
+    pub fn fragment_path(&self, name: &str) -> Option<&Path> {
+        self.fragment_paths
+            .get(&self.normalize_name(name))
+            .map(PathBuf::as_path)
+    }
+
+    /// "loaded", "stub" (unit file exists but was never loaded),
+    /// "not-found", "masked" or "error"
+    pub fn load_state(&self, name: &str) -> &'static str {
+        let name = self.normalize_name(name);
+        if self.units.contains_key(&name)
+            || (self.states.contains_key(&name) && !self.placeholders.contains(&name))
+        {
+            return "loaded";
+        }
+        if self.load_errors.contains(&name) {
+            return "error";
+        }
+        match self.find_unit(&name) {
+            Ok(path) if is_masked(&path) => "masked",
+            Ok(_) => "stub",
+            Err(_) if self.has_builtin_unit(&name) => "stub",
+            Err(_) => "not-found",
+        }
+    }
+
+    /// "enabled", "disabled", "static", "masked", "linked", "transient" or
+    /// "generated"; None if the unit is neither loaded nor masked
+    pub fn unit_file_state(&self, name: &str) -> Option<&'static str> {
+        let name = self.normalize_name(name);
+        let Ok(path) = self.find_unit(&name) else {
+            if name.ends_with(".scope") && self.states.contains_key(&name) {
+                return Some("transient");
+            }
+            if self.has_builtin_unit(&name) {
+                return Some("static");
+            }
+            return self.units.contains_key(&name).then_some("generated");
+        };
+        if is_masked(&path) {
+            return Some("masked");
+        }
+        match self.enablement(&name)? {
+            "enabled" => Some("enabled"),
+            _ if self.is_linked(&path) => Some("linked"),
+            state => Some(state),
+        }
+    }
+
+    /// Whether `path` is a symlink to a unit file outside the search path
+    /// (`systemctl link`)
+    fn is_linked(&self, path: &Path) -> bool {
+        let Ok(target) = std::fs::read_link(path) else {
+            return false;
+        };
+        let target = match path.parent() {
+            Some(dir) if target.is_relative() => dir.join(target),
+            _ => target,
+        };
+        target
+            .parent()
+            .is_some_and(|dir| !self.unit_paths.iter().any(|unit_dir| unit_dir == dir))
+    }
+}
+
+/// Masked units are symlinks to /dev/null
+fn is_masked(path: &Path) -> bool {
+    std::fs::read_link(path).is_ok_and(|target| target == Path::new("/dev/null"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn temp_dir(name: &str) -> TempDir {
+        let dir =
+            std::env::temp_dir().join(format!("sysd-load-state-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+
+    #[tokio::test]
+    async fn load_and_unit_file_states_follow_the_unit_files() {
+        let dir = temp_dir("states");
+        let units = dir.0.join("units");
+        let outside = dir.0.join("outside");
+        std::fs::create_dir_all(&units).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(
+            units.join("plain.service"),
+            "[Service]\nExecStart=/bin/true\n",
+        )
+        .unwrap();
+        std::fs::write(
+            units.join("installable.service"),
+            "[Service]\nExecStart=/bin/true\n[Install]\nWantedBy=multi-user.target\n",
+        )
+        .unwrap();
+        std::fs::write(
+            outside.join("linked.service"),
+            "[Service]\nExecStart=/bin/true\n[Install]\nWantedBy=multi-user.target\n",
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(outside.join("linked.service"), units.join("linked.service"))
+            .unwrap();
+        std::os::unix::fs::symlink("/dev/null", units.join("masked.service")).unwrap();
+
+        let mut manager = Manager::new_user();
+        manager.unit_paths = vec![units.clone()];
+
+        assert_eq!(manager.load_state("plain.service"), "stub");
+        assert_eq!(manager.load_state("missing.service"), "not-found");
+        assert_eq!(manager.load_state("masked.service"), "masked");
+        assert_eq!(manager.unit_file_state("masked.service"), Some("masked"));
+        assert_eq!(manager.unit_file_state("missing.service"), None);
+
+        for name in ["plain.service", "installable.service", "linked.service"] {
+            manager.load(name).await.unwrap();
+        }
+        assert_eq!(manager.load_state("plain"), "loaded");
+        assert_eq!(
+            manager.fragment_path("plain"),
+            Some(units.join("plain.service").as_path())
+        );
+        assert_eq!(manager.unit_file_state("plain.service"), Some("static"));
+        assert_eq!(
+            manager.unit_file_state("installable.service"),
+            Some("disabled")
+        );
+        assert_eq!(manager.unit_file_state("linked.service"), Some("linked"));
+
+        let wants = units.join("multi-user.target.wants");
+        std::fs::create_dir_all(&wants).unwrap();
+        std::os::unix::fs::symlink(
+            units.join("installable.service"),
+            wants.join("installable.service"),
+        )
+        .unwrap();
+        assert_eq!(
+            manager.unit_file_state("installable.service"),
+            Some("enabled")
+        );
+    }
+}
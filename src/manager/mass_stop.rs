@@ -0,0 +1,168 @@
+//! Stopping every unit at once (shutdown, soft-reboot, daemon exit)
+//!
+//! Stopping services one after another waits for each cgroup to empty in
+//! turn. A mass stop runs the stop sequences of all services side by side
+//! instead, so every main process gets its SIGTERM up front and each
+//! sequence escalates to SIGKILL at its own TimeoutStopSec= deadline. One
+//! `EmptyWatcher` over all their cgroups tells the sequences when leftover
+//! processes are gone. Other units (sockets, timers, mounts, ...) are
+//! stopped once the services are down.
+
+use std::sync::Arc;
+
+use crate::cgroups::EmptyWatcher;
+
+use super::stop_job::{StopJob, StopOutcome};
+use super::{Manager, ManagerError, ServiceResult};
+
+/// Stop sequences collected by `Manager::begin_mass_stop`, to be run
+/// without holding the manager
+pub struct MassStop {
+    jobs: Vec<(String, StopJob)>,
+    others: Vec<String>,
+}
+
+/// How a `MassStop` went, for `Manager::finish_mass_stop`
+pub struct MassStopOutcome {
+    services: Vec<(String, StopOutcome)>,
+    others: Vec<String>,
+}
+
+impl Manager {
+    /// Mark the services among `names` deactivating and collect their stop
+    /// sequences; the other units are stopped by `finish_mass_stop`
+    pub fn begin_mass_stop(&mut self, names: &[String]) -> MassStop {
+        let mut jobs = Vec::new();
+        let mut others = Vec::new();
+        for name in names {
+            let name = self.normalize_name(name);
+            let is_service = self
+                .units
+                .get(&name)
+                .is_some_and(|u| u.as_service().is_some());
+            if !is_service {
+                others.push(name);
+                continue;
+            }
+            if let Err(e) = self.mark_unit_stopping(&name) {
+                log::debug!("Not stopping {}: {}", name, e);
+                continue;
+            }
+            log::info!("Stopping {}", name);
+            let job = self.stop_job(&name, None);
+            jobs.push((name, job));
+        }
+
+        let cgroups = jobs
+            .iter()
+            .filter_map(|(_, job)| job.cgroup_path().map(|path| path.to_path_buf()))
+            .collect::<Vec<_>>();
+        if !cgroups.is_empty() {
+            let watcher = Arc::new(EmptyWatcher::spawn(cgroups));
+            jobs = jobs
+                .into_iter()
+                .map(|(name, job)| (name, job.with_empty_watcher(watcher.clone())))
+                .collect();
+        }
+        MassStop { jobs, others }
+    }
+
+    /// Record how the services of a mass stop ended, then stop the other units
+    pub async fn finish_mass_stop(&mut self, outcome: MassStopOutcome) {
+        for (name, outcome) in outcome.services {
+            self.finish_stop(&name, outcome);
+        }
+        for name in outcome.others {
+            match self.stop(&name).await {
+                Ok(()) | Err(ManagerError::NotActive(_)) => {}
+                Err(e) => log::warn!("Failed to stop {}: {}", name, e),
+            }
+        }
+    }
+}
+
+impl MassStop {
+    /// Services being stopped
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// Run all stop sequences concurrently
+    pub async fn run(self) -> MassStopOutcome {
+        let tasks: Vec<_> = self
+            .jobs
+            .into_iter()
+            .map(|(name, job)| (name, tokio::spawn(job.run())))
+            .collect();
+        let mut services = Vec::with_capacity(tasks.len());
+        for (name, task) in tasks {
+            let outcome = task.await.unwrap_or_else(|e| {
+                log::error!("Stop sequence of {} failed: {}", name, e);
+                (ServiceResult::Resources, None)
+            });
+            services.push((name, outcome));
+        }
+        MassStopOutcome {
+            services,
+            others: self.others,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+    use crate::manager::{ActiveState, ServiceState};
+    use crate::units::{KillMode, Service, Timer, Unit};
+
+    fn insert_running(manager: &mut Manager, unit: Unit) {
+        let name = unit.name().to_string();
+        manager.units.insert(name.clone(), unit);
+        let mut state = ServiceState::new();
+        state.set_running(0);
+        manager.states.insert(name, state);
+    }
+
+    #[tokio::test]
+    async fn services_time_out_side_by_side_then_other_units_stop() {
+        let mut manager = Manager::new_user();
+        for name in ["a.service", "b.service"] {
+            let mut service = Service::new(name.to_string());
+            service.service.timeout_stop_sec = Some(Duration::from_millis(400));
+            service.service.kill_mode = KillMode::None;
+            insert_running(&mut manager, Unit::Service(service));
+            let child = tokio::process::Command::new("/bin/sleep")
+                .arg("5")
+                .spawn()
+                .unwrap();
+            manager.processes.insert(name.to_string(), child);
+        }
+        insert_running(&mut manager, Unit::Timer(Timer::new("c.timer".into())));
+
+        let names = ["a", "b.service", "c.timer"].map(String::from);
+        let stop = manager.begin_mass_stop(&names);
+        assert_eq!(stop.len(), 2);
+        assert_eq!(
+            manager.states["a.service"].active,
+            ActiveState::Deactivating
+        );
+
+        let started = Instant::now();
+        let outcome = stop.run().await;
+        assert!(started.elapsed() < Duration::from_millis(750));
+        manager.finish_mass_stop(outcome).await;
+
+        for name in ["a.service", "b.service"] {
+            let state = &manager.states[name];
+            assert_eq!(state.active, ActiveState::Inactive, "{}", name);
+            assert_eq!(state.exit_code, Some(-9), "{}", name);
+        }
+        assert_eq!(manager.states["c.timer"].active, ActiveState::Inactive);
+    }
+}
@@ -2,37 +2,94 @@
 //
 // Loads, starts, stops, and monitors services and targets.
 
+mod boot_times;
+mod clean;
 mod conditions;
+mod credentials;
+mod daemon_exit;
 mod deps;
+mod dump;
 mod dynamic_user;
 mod enable;
+mod endpoint_wait;
+mod exec_gate;
+mod faults;
+mod fd_store;
+mod features;
 mod generators;
+mod health_check;
+mod instances;
+mod ip_firewall;
+mod kill;
+mod load_state;
+mod mass_stop;
+mod mount_monitor;
 mod mount_ops;
 mod notify;
 mod path_ops;
 mod path_watcher;
 mod process;
+mod remove_ipc;
+mod restrict_fs;
+mod reverse_deps;
+mod rolling_restart;
 mod runtime;
 pub mod sandbox;
+mod scheduled_shutdown;
 pub mod scope;
+mod security;
+mod set_property;
+mod sleep;
 mod slice_ops;
+mod snapshot;
+mod snapshot_units;
+mod soft_reboot;
+mod socket_bind;
+mod socket_labels;
 mod socket_ops;
 mod socket_watcher;
+mod spawn_profile;
+mod start_many;
 mod state;
+mod state_history;
+mod stop_job;
+mod target_jobs;
 mod timer_ops;
 mod timer_scheduler;
+mod trigger_limit;
+mod unit_file_permissions;
+mod unit_watcher;
 mod virtualization;
+mod why;
 
-pub use deps::{CycleError, DepGraph};
+pub use boot_times::{BootTimes, UnitTimes};
+pub use clean::CleanWhat;
+pub use deps::{CycleError, DepGraph, DependencyType};
+pub use enable::enablement_succeeds;
+pub use faults::Fault;
+pub use features::{Feature, FeatureSet};
+pub use kill::KillWhom;
+pub use mass_stop::{MassStop, MassStopOutcome};
+pub use mount_monitor::{MountInfoEntry, MountTableChanged};
+pub use mount_ops::MountJobFinished;
 pub use notify::{AsyncNotifyListener, NotifyMessage, NOTIFY_SOCKET_PATH};
-pub use process::{SpawnError, SpawnOptions};
+pub use process::{ExecPhase, SpawnError, SpawnOptions};
 pub use sandbox::apply_sandbox;
-pub use scope::ScopeManager;
-pub use socket_watcher::SocketActivation;
-pub use state::{ActiveState, ServiceState, SubState};
+pub use scope::{ScopeManager, SCOPE_STATE_DIR};
+pub use set_property::UnitProperty;
+pub use sleep::SleepMode;
+pub use snapshot::{StateView, UnitSnapshot};
+pub use socket_ops::SocketListing;
+pub use socket_watcher::{AcceptedConnection, SocketActivation};
+pub use spawn_profile::SpawnProfile;
+pub use start_many::UnitStartResult;
+pub use state::{ActiveState, ServiceResult, ServiceState, SubState, Transition};
 pub use timer_scheduler::TimerFired;
+pub use unit_watcher::UnitFilesChanged;
 pub use virtualization::VirtualizationType;
 
+pub use crate::state_dir::LINGER_DIR;
+
 use std::collections::{HashMap, HashSet};
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::io::RawFd;
@@ -40,7 +97,11 @@ use std::path::PathBuf;
 use tokio::process::Child;
 use tokio::sync::mpsc;
 
-use crate::cgroups::{CgroupLimits, CgroupManager};
+use crate::cgroups::{
+    CgroupLimits, CgroupManager, FileSystemRestrictor, IpFirewall, SocketBindFilter,
+};
+use crate::executor::exit_code_text;
+use crate::state_dir::StateDirs;
 use crate::units::{self, KillMode, Service, ServiceType, Unit};
 
 /// Message sent when a oneshot command completes
@@ -60,6 +121,26 @@ pub struct OneshotCompletion {
     pub remain_after_exit: bool,
 }
 
+/// Progress message from a background stop (see `Manager::enqueue_stop`)
+#[derive(Debug)]
+pub enum StopEvent {
+    /// The stop sequence entered a new sub-state (stop-sigterm, final-sigkill, ...)
+    Phase { service_name: String, sub: SubState },
+    /// The main process is gone, ExecStopPost= ran and leftover processes were killed
+    Finished {
+        service_name: String,
+        result: ServiceResult,
+        exit_code: Option<i32>,
+    },
+    /// ExecStopPost= of a service that exited on its own is done (see
+    /// `Manager::run_stop_post_commands`)
+    StopPostFinished { service_name: String },
+}
+
+/// PID of the ExecStop=/ExecStopPost= command currently running for a unit,
+/// shared with background stop jobs
+type ControlPids = std::sync::Arc<std::sync::Mutex<HashMap<String, u32>>>;
+
 /// Service manager that tracks and controls units (services and targets)
 pub struct Manager {
     /// Loaded unit definitions (services and targets)
@@ -78,8 +159,19 @@ pub struct Manager {
     waiting_ready: HashMap<u32, String>,
     /// Cgroup manager (None if cgroups unavailable)
     cgroup_manager: Option<CgroupManager>,
+    /// Optional kernel features found at startup
+    features: FeatureSet,
     /// Active cgroup paths for services
     cgroup_paths: HashMap<String, PathBuf>,
+    /// IPAddressAllow=/IPAddressDeny= programs of services, kept after the
+    /// service stops so its IP counters stay readable
+    ip_firewalls: HashMap<String, IpFirewall>,
+    /// SocketBindAllow=/SocketBindDeny= programs of services
+    socket_bind_filters: HashMap<String, SocketBindFilter>,
+    /// RestrictFileSystems= program, loaded when first needed
+    file_system_restrictor: Option<FileSystemRestrictor>,
+    /// Timing of the last start of each service (`sysd analyze spawn`)
+    spawn_profiles: HashMap<String, spawn_profile::SpawnRecorder>,
     /// PIDFile paths for Type=forking services
     pid_files: HashMap<String, PathBuf>,
     /// Count of active jobs (for Type=idle)
@@ -88,16 +180,40 @@ pub struct Manager {
     waiting_bus_name: HashMap<String, String>,
     /// Watchdog deadlines for services (service_name -> deadline)
     watchdog_deadlines: HashMap<String, std::time::Instant>,
+    /// Health of running services with ExecHealthCheck=
+    health_checks: HashMap<String, health_check::HealthState>,
+    /// Old main processes of services in a rolling restart
+    rolling_restarts: HashMap<String, rolling_restart::OldInstance>,
+    /// Services whose WaitForEndpoint= is being probed
+    endpoint_waits: HashMap<String, endpoint_wait::EndpointWait>,
+    /// Snapshots by name, with the units that were active (`sysdctl snapshot`)
+    snapshots: HashMap<String, Vec<String>>,
+    /// Shutdown armed by `sysd shutdown` or ScheduleShutdown
+    scheduled_shutdown: Option<scheduled_shutdown::ScheduledShutdown>,
+    /// Whether shutdowns may be scheduled (the system manager as PID 1)
+    shutdown_allowed: bool,
+    /// Unit state histories a previous manager saved (PersistStateHistory=)
+    restored_history: HashMap<String, Vec<state::Transition>>,
     /// Active listening sockets (socket unit name -> file descriptors)
     socket_fds: HashMap<String, Vec<RawFd>>,
     /// Channel for socket activation messages
     socket_activation_tx: mpsc::Sender<socket_watcher::SocketActivation>,
     /// Receiver for socket activation messages
     socket_activation_rx: Option<mpsc::Receiver<socket_watcher::SocketActivation>>,
+    /// Sockets whose watcher is waiting for the next connection
+    armed_sockets: HashSet<String>,
+    /// Accepted connections waiting to be passed to their Accept=yes instance
+    connection_fds: HashMap<String, RawFd>,
+    /// Peers of those connections, for REMOTE_ADDR/REMOTE_PORT
+    connection_peers: HashMap<String, std::net::SocketAddr>,
+    /// Service instances started for a single Accept=yes connection
+    connection_instances: HashSet<String>,
     /// Channel for timer fired messages
     timer_tx: mpsc::Sender<timer_scheduler::TimerFired>,
     /// Receiver for timer fired messages
     timer_rx: Option<mpsc::Receiver<timer_scheduler::TimerFired>>,
+    /// When each active timer fires next
+    timer_elapse: HashMap<String, std::time::Instant>,
     /// Channel for path triggered messages
     path_tx: mpsc::Sender<path_watcher::PathTriggered>,
     /// Receiver for path triggered messages
@@ -121,11 +237,73 @@ pub struct Manager {
     oneshot_completion_tx: mpsc::Sender<OneshotCompletion>,
     /// Receiver for oneshot completion messages
     oneshot_completion_rx: Option<mpsc::Receiver<OneshotCompletion>>,
+    /// Channel for background stop progress
+    stop_event_tx: mpsc::Sender<StopEvent>,
+    /// Receiver for background stop progress
+    stop_event_rx: Option<mpsc::Receiver<StopEvent>>,
+    /// Channel for unit file change notifications
+    unit_files_tx: mpsc::Sender<unit_watcher::UnitFilesChanged>,
+    /// Receiver for unit file change notifications
+    unit_files_rx: Option<mpsc::Receiver<unit_watcher::UnitFilesChanged>>,
+    /// Loaded units whose files changed on disk since the last reload
+    need_daemon_reload: HashSet<String>,
+    /// Unit file each loaded unit was parsed from (FragmentPath)
+    fragment_paths: HashMap<String, PathBuf>,
+    /// Template each instance without a unit file of its own was loaded from
+    instance_templates: HashMap<String, String>,
+    /// Generated services pulled in by targets (SysV scripts started in a
+    /// runlevel), added to the target's Wants= each time it is parsed
+    generated_wants: HashMap<String, Vec<String>>,
+    /// Other names of loaded units (alias symlinks, Alias=) -> canonical name
+    aliases: HashMap<String, String>,
+    /// Which loaded units depend on which (see `reverse_deps`)
+    reverse_deps: deps::DepGraph,
+    /// Units whose unit file failed to parse (LoadState=error)
+    load_errors: HashSet<String>,
+    /// Dependencies that could not be loaded, kept as inactive placeholders
+    /// so they show up in status and fail the units requiring them
+    placeholders: HashSet<String>,
+    /// Targets whose start job waits for required units still activating
+    pending_targets: HashSet<String>,
+    /// Services started while stopping; each starts once its stop finishes
+    queued_starts: HashSet<String>,
+    /// Services being stopped because they failed (see `enqueue_failure_stop`)
+    failure_stops: HashSet<String>,
+    /// Services whose ExecStopPost= still runs; starts wait until it is done
+    stop_post_running: HashSet<String>,
+    /// Reload units as soon as their files change
+    auto_reload_units: bool,
+    /// Channel for mount table changes
+    mount_table_tx: mpsc::Sender<mount_monitor::MountTableChanged>,
+    /// Receiver for mount table changes
+    mount_table_rx: Option<mpsc::Receiver<mount_monitor::MountTableChanged>>,
+    /// Channel for background network mount results
+    mount_job_tx: mpsc::Sender<mount_ops::MountJobFinished>,
+    /// Receiver for background network mount results
+    mount_job_rx: Option<mpsc::Receiver<mount_ops::MountJobFinished>>,
+    /// Network mounts still being retried in the background
+    mount_jobs: HashMap<String, tokio::task::AbortHandle>,
+    /// Socket, timer and path watcher tasks of active units
+    unit_tasks: HashMap<String, Vec<tokio::task::AbortHandle>>,
+    /// Mount units created for mounts found in the mount table (no unit file)
+    mountinfo_units: HashSet<String>,
+    /// Control processes (ExecStop=/ExecStopPost=) running per unit
+    control_pids: ControlPids,
+    /// Failures armed by `sysdctl inject-fault`
+    faults: faults::Faults,
+    /// Recent activations by socket, path and timer units
+    trigger_limits: trigger_limit::TriggerLimits,
+    /// Default*= settings from system.conf / user.conf
+    config: units::ManagerConfig,
+    /// Published copy of unit states for lock-free readers (see `state_view`)
+    state_tx: tokio::sync::watch::Sender<std::sync::Arc<snapshot::StateTable>>,
     /// Pending oneshot services (services waiting for next command to start)
     /// Map of service_name -> (next_cmd_idx, total_cmds, remain_after_exit)
     pending_oneshot_cmds: HashMap<String, (usize, usize, bool)>,
     /// Imported environment variables (for user session management)
     user_environment: HashMap<String, String>,
+    /// Virtualization, capabilities and kernel command line for condition checks
+    host_facts: conditions::HostFacts,
     /// Whether running in user mode (vs system mode)
     user_mode: bool,
 }
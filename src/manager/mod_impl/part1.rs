@@ -11,11 +11,23 @@ impl Manager {
 
     /// Create a service manager with explicit mode
     fn with_mode(user_mode: bool) -> Self {
-        let cgroup_manager = Self::init_cgroup_manager(user_mode);
+        let config = units::ManagerConfig::load(&crate::root::path(if user_mode {
+            units::USER_CONFIG_PATH
+        } else {
+            units::SYSTEM_CONFIG_PATH
+        }));
+        let cgroup_manager = Self::init_cgroup_manager(user_mode, config.default_slice.as_deref());
+        let features = FeatureSet::probe(cgroup_manager.as_ref().map(drop).map_err(String::clone));
+        let cgroup_manager = cgroup_manager.ok();
         let (socket_activation_tx, socket_activation_rx) = mpsc::channel(32);
         let (timer_tx, timer_rx) = mpsc::channel(32);
         let (path_tx, path_rx) = mpsc::channel(32);
         let (oneshot_completion_tx, oneshot_completion_rx) = mpsc::channel(32);
+        let (stop_event_tx, stop_event_rx) = mpsc::channel(32);
+        let (unit_files_tx, unit_files_rx) = mpsc::channel(32);
+        let (mount_table_tx, mount_table_rx) = mpsc::channel(32);
+        let (mount_job_tx, mount_job_rx) = mpsc::channel(32);
+        let state_tx = snapshot::state_sender();
         let unit_paths = Self::unit_paths_for_mode(user_mode);
         let scope_manager = ScopeManager::new(cgroup_manager.clone());
         let executor_path = Self::resolve_executor_path();
@@ -24,49 +36,91 @@ impl Manager {
             units: HashMap::new(), states: HashMap::new(), processes: HashMap::new(),
             unit_paths,
             notify_listener: None, notify_rx: None, waiting_ready: HashMap::new(),
-            cgroup_manager, cgroup_paths: HashMap::new(), pid_files: HashMap::new(),
+            cgroup_manager, features, cgroup_paths: HashMap::new(), ip_firewalls: HashMap::new(),
+            socket_bind_filters: HashMap::new(), file_system_restrictor: None,
+            spawn_profiles: HashMap::new(),
+            pid_files: HashMap::new(),
             active_jobs: 0,
             waiting_bus_name: HashMap::new(), watchdog_deadlines: HashMap::new(),
+            health_checks: HashMap::new(), rolling_restarts: HashMap::new(),
+            endpoint_waits: HashMap::new(), snapshots: HashMap::new(),
+            scheduled_shutdown: None, shutdown_allowed: false, restored_history: HashMap::new(),
             socket_fds: HashMap::new(), socket_activation_tx, socket_activation_rx: Some(socket_activation_rx),
-            timer_tx, timer_rx: Some(timer_rx), path_tx, path_rx: Some(path_rx),
+            armed_sockets: HashSet::new(), connection_fds: HashMap::new(),
+            connection_peers: HashMap::new(), connection_instances: HashSet::new(),
+            timer_tx, timer_rx: Some(timer_rx), timer_elapse: HashMap::new(),
+            faults: faults::Faults::default(), trigger_limits: trigger_limit::TriggerLimits::default(),
+            path_tx, path_rx: Some(path_rx),
             boot_time: std::time::Instant::now(),
             scope_manager, dynamic_user_manager: dynamic_user::DynamicUserManager::new(),
             dynamic_uids: HashMap::new(), fd_store: HashMap::new(),
             executor_path,
             pid_to_service: HashMap::new(), oneshot_completion_tx,
             oneshot_completion_rx: Some(oneshot_completion_rx),
+            stop_event_tx, stop_event_rx: Some(stop_event_rx),
+            unit_files_tx, unit_files_rx: Some(unit_files_rx),
+            need_daemon_reload: HashSet::new(), auto_reload_units: false,
+            fragment_paths: HashMap::new(), aliases: HashMap::new(), load_errors: HashSet::new(),
+            placeholders: HashSet::new(), instance_templates: HashMap::new(),
+            generated_wants: HashMap::new(),
+            pending_targets: HashSet::new(),
+            queued_starts: HashSet::new(),
+            stop_post_running: HashSet::new(),
+            failure_stops: HashSet::new(),
+            mount_table_tx, mount_table_rx: Some(mount_table_rx),
+            mount_job_tx, mount_job_rx: Some(mount_job_rx), mount_jobs: HashMap::new(),
+            unit_tasks: HashMap::new(), reverse_deps: deps::DepGraph::new(),
+            mountinfo_units: HashSet::new(), control_pids: ControlPids::default(), config, state_tx,
             pending_oneshot_cmds: HashMap::new(), user_environment: HashMap::new(),
+            host_facts: conditions::HostFacts::detect(),
             user_mode,
         }
     }
 
-    fn init_cgroup_manager(user_mode: bool) -> Option<CgroupManager> {
-        if user_mode {
-            return None;
-        }
-        match CgroupManager::new() {
+    /// The cgroup manager, or why there is none. The user manager manages
+    /// the cgroup of its user@UID.service when it runs as that service.
+    fn init_cgroup_manager(
+        user_mode: bool,
+        default_slice: Option<&str>,
+    ) -> Result<CgroupManager, String> {
+        let cgroup_manager = if user_mode {
+            CgroupManager::delegated(unsafe { libc::getuid() })
+                .map_err(|e| format!("the user manager has no delegated cgroup ({})", e))
+        } else {
+            CgroupManager::new().map_err(|e| e.to_string())
+        };
+        match cgroup_manager {
             Ok(mgr) => {
-                log::debug!("Cgroup manager initialized");
-                Some(mgr)
+                let mgr = match default_slice {
+                    Some(slice) => mgr.with_default_slice(slice),
+                    None => mgr,
+                };
+                log::debug!(
+                    "Cgroup manager initialized, default slice {}",
+                    mgr.default_slice()
+                );
+                Ok(mgr)
             }
             Err(e) => {
                 log::debug!(
                     "Cgroup manager unavailable: {} (running without cgroups)",
                     e
                 );
-                None
+                Err(e)
             }
         }
     }
 
     fn unit_paths_for_mode(user_mode: bool) -> Vec<PathBuf> {
-        if user_mode {
-            return Self::user_unit_paths();
-        }
-        vec![
-            PathBuf::from("/etc/systemd/system"),
-            PathBuf::from("/usr/lib/systemd/system"),
-        ]
+        let paths = if user_mode {
+            Self::user_unit_paths()
+        } else {
+            vec![
+                PathBuf::from("/etc/systemd/system"),
+                PathBuf::from("/usr/lib/systemd/system"),
+            ]
+        };
+        paths.into_iter().map(crate::root::path).collect()
     }
 
     fn resolve_executor_path() -> String {
@@ -109,9 +163,14 @@ impl Manager {
         paths
     }
 
-    /// Check if user has lingering enabled
+    /// Check if user has lingering enabled (sysd or systemd-logind marker)
     pub fn is_lingering(username: &str) -> bool {
-        std::path::Path::new(&format!("/var/lib/systemd/linger/{}", username)).exists()
+        [
+            StateDirs::system().linger(),
+            crate::root::path("/var/lib/systemd/linger"),
+        ]
+        .iter()
+        .any(|dir| dir.join(username).exists())
     }
 
     /// Get the current user's runtime directory
@@ -121,7 +180,7 @@ impl Manager {
             .map(PathBuf::from)
             .or_else(|| {
                 let uid = unsafe { libc::getuid() };
-                let path = PathBuf::from(format!("/run/user/{}", uid));
+                let path = crate::root::path(format!("/run/user/{}", uid));
                 if path.exists() {
                     Some(path)
                 } else {
@@ -133,7 +192,7 @@ impl Manager {
     /// Ensure XDG_RUNTIME_DIR exists and has correct permissions
     pub fn ensure_runtime_dir() -> std::io::Result<PathBuf> {
         let uid = unsafe { libc::getuid() };
-        let runtime_dir = PathBuf::from(format!("/run/user/{}", uid));
+        let runtime_dir = crate::root::path(format!("/run/user/{}", uid));
 
         if !runtime_dir.exists() {
             std::fs::create_dir_all(&runtime_dir)?;
@@ -159,6 +218,17 @@ impl Manager {
         self.user_mode
     }
 
+    /// Look for unit files below `root` instead of / (`sysd --unit-root`);
+    /// runtime paths, cgroups and sockets stay on the host. For a root
+    /// covering every path the manager touches, see `crate::root`.
+    pub fn set_unit_root(&mut self, root: &std::path::Path) {
+        self.unit_paths = self
+            .unit_paths
+            .iter()
+            .map(|path| root.join(path.strip_prefix("/").unwrap_or(path)))
+            .collect();
+    }
+
     /// Get the directory for enable/disable symlinks
     ///
     /// In system mode: /etc/systemd/system
@@ -169,9 +239,9 @@ impl Manager {
             self.unit_paths
                 .first()
                 .cloned()
-                .unwrap_or_else(|| PathBuf::from("/etc/systemd/user"))
+                .unwrap_or_else(|| crate::root::path("/etc/systemd/user"))
         } else {
-            PathBuf::from("/etc/systemd/system")
+            crate::root::path("/etc/systemd/system")
         }
     }
 
@@ -187,14 +257,11 @@ impl Manager {
 
     /// Get the notify socket path based on mode (system vs user)
     fn notify_socket_path_for_mode(&self) -> String {
-        if self.user_mode {
-            // User mode: /run/user/<uid>/sysd/notify
-            let uid = nix::unistd::getuid().as_raw();
-            format!("/run/user/{}/sysd/notify", uid)
-        } else {
-            // System mode: /run/sysd/notify
-            NOTIFY_SOCKET_PATH.to_string()
-        }
+        // /run/sysd/notify, or /run/user/<uid>/sysd/notify in user mode
+        StateDirs::for_mode(self.user_mode)
+            .notify_socket()
+            .to_string_lossy()
+            .into_owned()
     }
 
     /// Get the notify socket path (if initialized)
@@ -215,17 +282,39 @@ impl Manager {
             return Ok(name);
         }
 
-        let path = self.find_unit(&name)?;
+        let path = match self.find_unit(&name) {
+            Err(ManagerError::NotFound(_)) if self.has_builtin_unit(&name) => {
+                return self.load_builtin_unit(name).await;
+            }
+            result => result?,
+        };
         let canonical_name = self.resolve_canonical_unit_name(&name, &path)?;
 
         if self.units.contains_key(&canonical_name) {
+            self.clear_placeholder(&name);
+            self.register_alias(&name, &canonical_name);
             return Ok(canonical_name);
         }
 
-        let mut unit = self.parse_unit_file(&path).await?;
+        let mut unit = match self.parse_unit_file(&path).await {
+            Ok(unit) => unit,
+            Err(e) => {
+                self.load_errors.insert(canonical_name);
+                return Err(e);
+            }
+        };
         self.apply_canonical_name(&mut unit, &canonical_name);
+        self.load_errors.remove(&canonical_name);
+        self.clear_placeholder(&name);
+        self.clear_placeholder(&canonical_name);
+        self.record_instance_template(&canonical_name, &path);
+        self.fragment_paths.insert(canonical_name.clone(), path);
         self.states.insert(canonical_name.clone(), ServiceState::new());
-        self.units.insert(canonical_name.clone(), unit);
+        let declared = declared_aliases(&canonical_name, &unit);
+        self.insert_unit(canonical_name.clone(), unit);
+        self.register_alias(&name, &canonical_name);
+        self.register_declared_aliases(declared);
+        self.register_symlinked_aliases(&canonical_name);
 
         Ok(canonical_name)
     }
@@ -252,15 +341,64 @@ impl Manager {
         }
 
         let stored_name = name.clone();
+        self.fragment_paths.insert(name.clone(), path);
         self.states.insert(name.clone(), ServiceState::new());
-        self.units.insert(name, unit);
+        self.insert_unit(name, unit);
         Ok(LoadNameResolution::AlreadyLoaded(stored_name))
     }
 
+    /// Whether `name` is one of the standard targets compiled into sysd
+    fn has_builtin_unit(&self, name: &str) -> bool {
+        !self.user_mode && units::builtin_unit(name).is_some()
+    }
+
+    /// Load a compiled-in target no unit file overrides
+    async fn load_builtin_unit(&mut self, name: String) -> Result<String, ManagerError> {
+        let unit = match self.parse_builtin_unit(&name).await {
+            Ok(unit) => unit,
+            Err(e) => {
+                self.load_errors.insert(name);
+                return Err(e);
+            }
+        };
+        log::debug!("Using built-in {}", name);
+        self.load_errors.remove(&name);
+        self.clear_placeholder(&name);
+        self.states.insert(name.clone(), ServiceState::new());
+        self.insert_unit(name.clone(), unit);
+        Ok(name)
+    }
+
+    /// Drop the placeholder left for `name` by a failed dependency load
+    fn clear_placeholder(&mut self, name: &str) {
+        if self.placeholders.remove(name) && !self.units.contains_key(name) {
+            self.states.remove(name);
+        }
+    }
+
+    async fn parse_builtin_unit(&self, name: &str) -> Result<Unit, ManagerError> {
+        let content =
+            units::builtin_unit(name).ok_or_else(|| ManagerError::NotFound(name.to_string()))?;
+        let mut unit = units::load_builtin_target(name, content)
+            .await
+            .map(Unit::Target)
+            .map_err(|e| ManagerError::Parse(e.to_string()))?;
+        self.config.apply_to(&mut unit);
+        self.add_generated_wants(&mut unit);
+        Ok(unit)
+    }
+
+    /// Load a unit file and fill in the manager's Default*= settings
     async fn parse_unit_file(&self, path: &std::path::Path) -> Result<Unit, ManagerError> {
-        units::load_unit(path)
+        let mut unit = units::load_unit_checked(path, self.unit_file_check())
             .await
-            .map_err(|e| ManagerError::Parse(e.to_string()))
+            .map_err(|e| match e {
+                units::ParseError::Refused(message) => ManagerError::InsecureUnitFile(message),
+                e => ManagerError::Parse(e.to_string()),
+            })?;
+        self.config.apply_to(&mut unit);
+        self.add_generated_wants(&mut unit);
+        Ok(unit)
     }
 
     fn resolve_canonical_unit_name(
@@ -272,13 +410,18 @@ impl Manager {
             return Ok(requested_name.to_string());
         }
 
-        let Ok(target) = std::fs::read_link(path) else {
+        let Ok(link) = std::fs::read_link(path) else {
             return Ok(requested_name.to_string());
         };
-        if target.as_os_str() == "/dev/null" {
+        if link.as_os_str() == "/dev/null" {
             log::debug!("{} is masked, skipping", requested_name);
             return Err(ManagerError::Masked(requested_name.to_string()));
         }
+        // Alias symlinks may point at other aliases; the last file is the unit
+        let target = match crate::root::get() {
+            Some(_) => crate::root::resolve(path),
+            None => std::fs::canonicalize(path).unwrap_or(link),
+        };
 
         let target_name = target
             .file_name()
@@ -307,19 +450,90 @@ impl Manager {
         unit.set_name(canonical_name.to_string());
     }
 
+    /// Record `alias` as another name of the loaded unit `canonical`
+    ///
+    /// A separate entry loaded under the alias earlier is folded into the
+    /// canonical one, keeping whichever state is active.
+    fn register_alias(&mut self, alias: &str, canonical: &str) {
+        if alias == canonical || !self.units.contains_key(canonical) {
+            return;
+        }
+        if self.remove_unit(alias).is_some() {
+            log::debug!("Merging {} into {}", alias, canonical);
+            self.fragment_paths.remove(alias);
+            self.instance_templates.remove(alias);
+            self.need_daemon_reload.remove(alias);
+            if let Some(state) = self.states.remove(alias) {
+                if !self
+                    .states
+                    .get(canonical)
+                    .is_some_and(ServiceState::is_active)
+                {
+                    self.states.insert(canonical.to_string(), state);
+                }
+            }
+            if let Some(process) = self.processes.remove(alias) {
+                self.processes
+                    .entry(canonical.to_string())
+                    .or_insert(process);
+            }
+        }
+        self.reverse_deps.add_alias(alias, canonical);
+        self.aliases
+            .insert(alias.to_string(), canonical.to_string());
+    }
+
+    /// Register Alias= names, unless a unit of that name is loaded itself
+    fn register_declared_aliases(&mut self, declared: Vec<(String, String)>) {
+        for (alias, canonical) in declared {
+            if self.units.contains_key(&alias) {
+                log::warn!(
+                    "{} declares Alias={}, but a unit of that name is loaded",
+                    canonical,
+                    alias
+                );
+                continue;
+            }
+            self.register_alias(&alias, &canonical);
+        }
+    }
+
+    /// Register the alias symlinks in the search path that point at the unit
+    /// file of `canonical`, so its names are complete before anything asks
+    /// for it by an alias
+    fn register_symlinked_aliases(&mut self, canonical: &str) {
+        let instance = units::extract_instance(canonical);
+        let file_name = match &instance {
+            Some(_) => units::get_template_name(canonical).unwrap_or_else(|| canonical.to_string()),
+            None => canonical.to_string(),
+        };
+        for alias in units::unit_file_aliases(&self.unit_paths, &file_name) {
+            let alias = match &instance {
+                Some(instance) => units::instantiate_template(&alias, instance).unwrap_or(alias),
+                None => alias,
+            };
+            if !units::is_bare_template(&alias) && !self.units.contains_key(&alias) {
+                self.register_alias(&alias, canonical);
+            }
+        }
+    }
+
     /// Load a unit from a specific path
     pub async fn load_from_path(&mut self, path: &std::path::Path) -> Result<(), ManagerError> {
-        let unit = units::load_unit(path)
-            .await
-            .map_err(|e| ManagerError::Parse(e.to_string()))?;
+        let unit = self.parse_unit_file(path).await?;
 
         let name = unit.name().to_string();
         self.states.insert(name.clone(), ServiceState::new());
-        self.units.insert(name, unit);
+        self.insert_unit(name, unit);
 
         Ok(())
     }
 
+    /// Whether a unit file for `name` is installed
+    pub fn unit_file_exists(&self, name: &str) -> bool {
+        self.find_unit(name).is_ok()
+    }
+
     /// Find a unit file in search paths
     fn find_unit(&self, name: &str) -> Result<PathBuf, ManagerError> {
         if let Some(path) = self.search_unit_paths(name) {
@@ -336,20 +550,7 @@ impl Manager {
     }
 
     fn search_unit_paths(&self, name: &str) -> Option<PathBuf> {
-        for base in &self.unit_paths {
-            let path = base.join(name);
-            if path.exists() {
-                return Some(path);
-            }
-            if path.is_symlink() {
-                if let Ok(target) = std::fs::read_link(&path) {
-                    if target.exists() {
-                        return Some(path);
-                    }
-                }
-            }
-        }
-        None
+        units::find_unit_file(&self.unit_paths, name)
     }
 
     /// Start a single service (no dependency resolution)
@@ -357,33 +558,84 @@ impl Manager {
         let name = self.normalize_name(name);
         match self.start_single(&name).await {
             Ok(()) => Ok(()),
-            Err(ManagerError::IsTarget(_)) => {
-                // Targets are synchronization points - just mark as active
-                if let Some(state) = self.states.get_mut(&name) {
-                    state.set_running(0);
-                }
-                log::debug!("Target {} reached", name);
-                Ok(())
-            }
+            Err(ManagerError::IsTarget(_)) => self.start_target(&name),
             Err(e) => Err(e),
         }
     }
 
     /// Start a unit with all its dependencies
+    ///
+    /// Units whose Requires=/BindsTo= dependency failed in this transaction,
+    /// or whose Requisite= is not active, are not started but marked
+    /// dependency-failed; failed Wants= dependencies are only logged.
+    #[tracing::instrument(name = "transaction", skip_all, fields(unit = name))]
     pub async fn start_with_deps(&mut self, name: &str) -> Result<Vec<String>, ManagerError> {
         let name = self.normalize_name(name);
         let order = self.resolve_start_order(&name).await?;
-        log::info!("Start order for {}: {:?}", name, order);
+        tracing::info!("Start order for {}: {:?}", name, order);
 
         let mut started = Vec::new();
+        let mut failed = HashSet::new();
         for unit_name in &order {
-            self.start_dependency_unit(&name, unit_name, &mut started)
-                .await?;
+            if let Some(dependency) = self.failed_requirement(unit_name, &failed) {
+                tracing::warn!("Dependency failed for {}: {}", unit_name, dependency);
+                self.states
+                    .entry(unit_name.clone())
+                    .or_default()
+                    .set_dependency_failed(dependency.clone());
+                if *unit_name == name {
+                    return Err(ManagerError::DependencyFailed(name, dependency));
+                }
+                failed.insert(unit_name.clone());
+                continue;
+            }
+            // The probes run in their own tasks, so this cannot deadlock
+            self.endpoints_before(unit_name).await;
+            if let Err(e) = self.start_dependency_unit(unit_name, &mut started).await {
+                if *unit_name == name {
+                    return Err(e);
+                }
+                tracing::warn!("Dependency {} of {} failed: {}", unit_name, name, e);
+                failed.insert(unit_name.clone());
+            }
         }
 
         Ok(started)
     }
 
+    /// Requires=/BindsTo=/Sockets= dependency of `name` that failed in this
+    /// transaction or could not be loaded, the same-named socket of a service
+    /// without Sockets= if it failed, or Requisite= dependency that is not
+    /// active (see `add_socket_dependencies` for the socket requirements)
+    fn failed_requirement(&self, name: &str, failed: &HashSet<String>) -> Option<String> {
+        let unit = self.units.get(name)?;
+        let section = unit.unit_section();
+        let is_active = |dep: &String| self.states.get(dep).is_some_and(ServiceState::is_active);
+        let sockets = unit
+            .as_service()
+            .map(|s| s.service.sockets.as_slice())
+            .unwrap_or_default();
+        let mut requires = section
+            .requires
+            .iter()
+            .chain(&section.requires_dir)
+            .chain(&section.binds_to)
+            .chain(sockets)
+            .map(|dep| self.normalize_name(dep));
+        // Only required when it exists, so a missing one is fine
+        let own_socket = match unit.as_service() {
+            Some(_) if sockets.is_empty() => name
+                .strip_suffix(".service")
+                .map(|base| format!("{}.socket", base)),
+            _ => None,
+        };
+        let mut requisite = section.requisite.iter().map(|dep| self.normalize_name(dep));
+        requires
+            .find(|dep| failed.contains(dep) || self.placeholders.contains(dep))
+            .or_else(|| own_socket.filter(|socket| failed.contains(socket)))
+            .or_else(|| requisite.find(|dep| !is_active(dep)))
+    }
+
     /// Resolve start order for a unit and its dependencies
     async fn resolve_start_order(&mut self, name: &str) -> Result<Vec<String>, ManagerError> {
         self.ensure_unit_loaded(name).await?;
@@ -396,7 +648,6 @@ impl Manager {
 
     async fn start_dependency_unit(
         &mut self,
-        root_name: &str,
         unit_name: &str,
         started: &mut Vec<String>,
     ) -> Result<(), ManagerError> {
@@ -407,39 +658,21 @@ impl Manager {
 
         match self.start_single(unit_name).await {
             Ok(()) => {
-                started.push(unit_name.to_string());
-                Ok(())
-            }
-            Err(ManagerError::IsTarget(_)) => {
-                if let Some(state) = self.states.get_mut(unit_name) {
-                    state.set_running(0);
+                // Units skipped for an unmet condition do not hold up dependents
+                let skipped = self
+                    .states
+                    .get(unit_name)
+                    .is_some_and(|state| !state.condition_result());
+                if !skipped {
+                    started.push(unit_name.to_string());
                 }
-                log::debug!("Target {} reached", unit_name);
                 Ok(())
             }
-            Err(e) => self.handle_dependency_start_error(root_name, unit_name, e),
+            Err(ManagerError::IsTarget(_)) => self.start_target(unit_name),
+            Err(e) => Err(e),
         }
     }
 
-    fn handle_dependency_start_error(
-        &self,
-        root_name: &str,
-        unit_name: &str,
-        err: ManagerError,
-    ) -> Result<(), ManagerError> {
-        let is_required = self
-            .units
-            .get(root_name)
-            .map(|u| u.unit_section().requires.contains(&unit_name.to_string()))
-            .unwrap_or(false);
-        if is_required {
-            log::error!("Required dependency {} failed: {}", unit_name, err);
-            return Err(err);
-        }
-        log::warn!("Optional dependency {} failed: {}", unit_name, err);
-        Ok(())
-    }
-
     async fn ensure_unit_loaded(&mut self, name: &str) -> Result<(), ManagerError> {
         if self.units.contains_key(name) {
             return Ok(());
@@ -482,6 +715,9 @@ impl Manager {
             Ok(canonical) => Some(canonical),
             Err(e) => {
                 log::warn!("Could not load dependency {}: {}", unit_name, e);
+                let name = self.normalize_name(unit_name);
+                self.states.entry(name.clone()).or_default();
+                self.placeholders.insert(name);
                 None
             }
         }
@@ -498,24 +734,37 @@ impl Manager {
         };
 
         let section = unit.unit_section();
-        if !section.requires.is_empty() || !section.wants.is_empty() || !unit.wants_dir().is_empty() {
+        if !section.requires.is_empty()
+            || !section.wants.is_empty()
+            || !section.wants_dir.is_empty()
+            || !section.requires_dir.is_empty()
+        {
             log::debug!(
-                "{}: Requires={:?}, Wants={:?}, wants_dir={:?}",
+                "{}: Requires={:?}, Wants={:?}, wants_dir={:?}, requires_dir={:?}",
                 actual_name,
                 section.requires,
                 section.wants,
-                unit.wants_dir()
+                section.wants_dir,
+                section.requires_dir
             );
         }
 
-        for dep in &section.requires {
-            queue_dependency(to_load, queued, dep);
-        }
-        for dep in &section.wants {
-            queue_dependency(to_load, queued, dep);
-        }
-        for dep in unit.wants_dir() {
-            queue_dependency(to_load, queued, dep);
+        // Sockets= imply Requires=
+        let sockets = unit
+            .as_service()
+            .map(|s| s.service.sockets.as_slice())
+            .unwrap_or_default();
+        // Queue by canonical name so a unit referenced through several of its
+        // names is loaded and started once
+        let deps = section
+            .requires
+            .iter()
+            .chain(&section.wants)
+            .chain(&section.wants_dir)
+            .chain(&section.requires_dir)
+            .chain(sockets);
+        for dep in deps {
+            queue_dependency(to_load, queued, &self.normalize_name(dep));
         }
     }
 
@@ -525,7 +774,7 @@ impl Manager {
         aliases: &HashMap<String, String>,
     ) -> deps::DepGraph {
         let mut graph = deps::DepGraph::new();
-        for (alias, canonical) in aliases {
+        for (alias, canonical) in self.aliases.iter().chain(aliases) {
             graph.add_alias(alias, canonical);
         }
         for key in self.units.keys().filter(|key| loaded.contains(*key)) {
@@ -538,9 +787,9 @@ impl Manager {
     }
 
     fn log_start_single_request(&self, name: &str) {
-        log::debug!("start_single({})", name);
+        tracing::debug!("start_single({})", name);
         if name.contains("dbus") {
-            log::info!(
+            tracing::info!(
                 ">>> start_single({}) - socket_fds keys: {:?}",
                 name,
                 self.socket_fds.keys().collect::<Vec<_>>()
@@ -631,6 +880,31 @@ ExecStart=/bin/true
         ));
     }
 
+    #[test]
+    fn unit_root_prefixes_unit_search_paths() {
+        let dir = temp_dir("unit-root");
+        let unit_dir = dir.0.join("etc/systemd/system");
+        std::fs::create_dir_all(&unit_dir).unwrap();
+        write_unit(
+            &unit_dir,
+            "payload.service",
+            r#"
+[Service]
+ExecStart=/bin/true
+"#,
+        );
+        let mut manager = Manager::new();
+        manager.set_unit_root(&dir.0);
+
+        assert_eq!(manager.unit_paths[1], dir.0.join("usr/lib/systemd/system"));
+        assert_eq!(
+            manager.find_unit("payload.service").unwrap(),
+            unit_dir.join("payload.service")
+        );
+        assert!(!manager.enable_dir().starts_with(&dir.0));
+        assert!(!crate::root::path("/run/sysd").starts_with(&dir.0));
+    }
+
     #[cfg(unix)]
     #[test]
     fn canonical_unit_name_resolves_symlink_targets_with_unit_names() {
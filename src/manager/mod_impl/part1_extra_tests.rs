@@ -1,5 +1,5 @@
 use super::*;
-use crate::units::Target;
+use crate::units::{Target, UnitSection};
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 struct TempRoot(PathBuf);
@@ -122,6 +122,135 @@ fn canonical_unit_name_rejects_masked_units() {
     ));
 }
 
+#[cfg(unix)]
+#[tokio::test]
+async fn load_resolves_alias_symlink_chains_and_declared_aliases_to_one_unit() {
+    use std::os::unix::fs::symlink;
+
+    let dir = temp_dir("aliases");
+    let real = write_unit(
+        &dir.0,
+        "real.service",
+        r#"
+[Service]
+ExecStart=/bin/true
+
+[Install]
+Alias=declared.service
+"#,
+    );
+    symlink(&real, dir.0.join("nick.service")).unwrap();
+    symlink(dir.0.join("nick.service"), dir.0.join("chained.service")).unwrap();
+    let mut manager = Manager::new_user();
+    manager.unit_paths = vec![dir.0.clone()];
+
+    assert_eq!(manager.load("chained").await.unwrap(), "real.service");
+    assert_eq!(manager.load("nick.service").await.unwrap(), "real.service");
+    assert_eq!(
+        manager.load("declared.service").await.unwrap(),
+        "real.service"
+    );
+    assert_eq!(manager.units.len(), 1);
+    for name in ["chained", "nick.service", "declared.service"] {
+        assert_eq!(manager.normalize_name(name), "real.service");
+    }
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn loading_a_unit_by_its_own_name_lists_its_alias_symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let dir = temp_dir("symlinked-names");
+    let real = write_unit(
+        &dir.0,
+        "real.service",
+        "[Service]\nExecStart=/bin/true\n\n[Install]\nAlias=declared.service\n",
+    );
+    symlink(&real, dir.0.join("nick.service")).unwrap();
+    symlink(dir.0.join("nick.service"), dir.0.join("chained.service")).unwrap();
+    let getty = write_unit(&dir.0, "getty@.service", "[Service]\nExecStart=/bin/true\n");
+    symlink(&getty, dir.0.join("serial@.service")).unwrap();
+    let mut manager = Manager::new_user();
+    manager.unit_paths = vec![dir.0.clone()];
+
+    assert_eq!(manager.load("real").await.unwrap(), "real.service");
+    assert_eq!(
+        manager.unit_names("real"),
+        [
+            "real.service",
+            "chained.service",
+            "declared.service",
+            "nick.service"
+        ]
+    );
+    manager.load("getty@tty1.service").await.unwrap();
+    assert_eq!(
+        manager.unit_names("getty@tty1.service"),
+        ["getty@tty1.service", "serial@tty1.service"]
+    );
+}
+
+#[test]
+fn register_alias_folds_unit_loaded_under_alias_into_canonical_entry() {
+    let mut manager = Manager::new_user();
+    for name in ["real.service", "nick.service"] {
+        manager.units.insert(
+            name.to_string(),
+            Unit::Service(Service::new(name.to_string())),
+        );
+        manager.states.insert(name.to_string(), ServiceState::new());
+    }
+    manager
+        .states
+        .get_mut("nick.service")
+        .unwrap()
+        .set_running(42);
+
+    manager.register_alias("nick.service", "real.service");
+
+    assert!(!manager.units.contains_key("nick.service"));
+    assert!(!manager.states.contains_key("nick.service"));
+    assert!(manager.states["real.service"].is_active());
+    assert_eq!(manager.normalize_name("nick"), "real.service");
+}
+
+#[tokio::test]
+async fn unit_referenced_by_several_names_is_planned_once_and_lists_all_names() {
+    let dir = temp_dir("names");
+    write_unit(
+        &dir.0,
+        "real.target",
+        "[Unit]\nDescription=Real\n\n[Install]\nAlias=nick.target\n",
+    );
+    write_unit(
+        &dir.0,
+        "root.target",
+        "[Unit]\nWants=real.target\nRequires=nick.target\nAfter=nick.target\n",
+    );
+    write_unit(
+        &dir.0,
+        "getty@.service",
+        "[Service]\nExecStart=/bin/true\n\n[Install]\nAlias=tty@.service\n",
+    );
+    let mut manager = Manager::new_user();
+    manager.unit_paths = vec![dir.0.clone()];
+
+    let plan = manager.get_boot_plan("root.target").await.unwrap();
+    assert_eq!(plan, ["real.target", "root.target"]);
+    assert_eq!(
+        manager.unit_names("nick.target"),
+        ["real.target", "nick.target"]
+    );
+
+    manager.load("getty@tty1.service").await.unwrap();
+    assert_eq!(manager.normalize_name("tty@tty1"), "getty@tty1.service");
+    assert_eq!(
+        manager.unit_names("getty@tty1.service"),
+        ["getty@tty1.service", "tty@tty1.service"]
+    );
+}
+
 #[tokio::test]
 async fn dependency_collection_loads_available_units_and_ignores_missing_optional_units() {
     let dir = temp_dir("deps");
@@ -182,7 +311,7 @@ fn queue_unit_dependencies_reads_requires_wants_and_target_wants_dir() {
     let mut target = Target::new("multi-user.target".to_string());
     target.unit.requires = vec!["db.service".to_string()];
     target.unit.wants = vec!["log.service".to_string()];
-    target.wants_dir = vec!["ssh.service".to_string(), "db.service".to_string()];
+    target.unit.wants_dir = vec!["ssh.service".to_string(), "db.service".to_string()];
     manager
         .units
         .insert("multi-user.target".to_string(), Unit::Target(target));
@@ -225,11 +354,11 @@ async fn start_dependency_unit_skips_active_units_and_marks_targets_active() {
     let mut started = Vec::new();
 
     manager
-        .start_dependency_unit("root.service", "active.service", &mut started)
+        .start_dependency_unit("active.service", &mut started)
         .await
         .unwrap();
     manager
-        .start_dependency_unit("root.service", "ready.target", &mut started)
+        .start_dependency_unit("ready.target", &mut started)
         .await
         .unwrap();
 
@@ -237,31 +366,104 @@ async fn start_dependency_unit_skips_active_units_and_marks_targets_active() {
     assert!(manager.states.get("ready.target").unwrap().is_active());
 }
 
-#[test]
-fn dependency_start_errors_fail_required_units_and_ignore_optional_units() {
+fn dependency_failure_manager() -> Manager {
     let mut manager = Manager::new_user();
-    let mut root = Service::new("root.service".to_string());
-    root.unit.requires = vec!["required.service".to_string()];
-    root.unit.wants = vec!["optional.service".to_string()];
+    let target = |name: &str, edit: fn(&mut UnitSection)| {
+        let mut target = Target::new(name.to_string());
+        edit(&mut target.unit);
+        Unit::Target(target)
+    };
+    let units = [
+        Unit::Service(Service::new("broken.service".to_string())),
+        Unit::Service(Service::new("idle.service".to_string())),
+        target("needs-broken.target", |unit| {
+            unit.requires = vec!["broken.service".to_string()];
+        }),
+        target("wants-broken.target", |unit| {
+            unit.wants = vec!["broken.service".to_string()];
+        }),
+        target("needs-idle.target", |unit| {
+            unit.requisite = vec!["idle.service".to_string()];
+        }),
+        target("root.target", |unit| {
+            unit.wants = vec![
+                "needs-broken.target".to_string(),
+                "wants-broken.target".to_string(),
+                "needs-idle.target".to_string(),
+            ];
+        }),
+    ];
+    for unit in units {
+        let name = unit.name().to_string();
+        manager.states.insert(name.clone(), ServiceState::new());
+        manager.units.insert(name, unit);
+    }
     manager
-        .units
-        .insert("root.service".to_string(), Unit::Service(root));
+}
+
+#[tokio::test]
+async fn failed_requires_and_inactive_requisite_mark_units_dependency_failed() {
+    let mut manager = dependency_failure_manager();
+
+    manager.start_with_deps("root.target").await.unwrap();
+
+    let state = |name: &str| manager.states.get(name).unwrap();
+    assert_eq!(
+        state("needs-broken.target").failed_dependency.as_deref(),
+        Some("broken.service")
+    );
+    assert!(!state("needs-broken.target").is_active());
+    assert!(state("wants-broken.target").is_active());
+    assert_eq!(state("wants-broken.target").failed_dependency, None);
+    assert_eq!(
+        state("needs-idle.target").failed_dependency.as_deref(),
+        Some("idle.service")
+    );
+    assert!(!state("idle.service").is_active());
+    assert!(state("root.target").is_active());
+}
+
+#[tokio::test]
+async fn failed_requires_of_requested_unit_is_an_error() {
+    let mut manager = dependency_failure_manager();
+
+    assert!(matches!(
+        manager.start_with_deps("needs-broken.target").await,
+        Err(ManagerError::DependencyFailed(name, dependency))
+            if name == "needs-broken.target" && dependency == "broken.service"
+    ));
+}
+
+#[tokio::test]
+async fn services_are_dependency_failed_when_their_socket_fails() {
+    let dir = temp_dir("failed-socket");
+    for name in ["api.socket", "web.socket"] {
+        write_unit(&dir.0, name, "[Socket]\nListenNetlink=missing-protocol 1\n");
+    }
+    write_unit(
+        &dir.0,
+        "backend.service",
+        "[Service]\nSockets=api.socket\nExecStart=/bin/true\n",
+    );
+    write_unit(&dir.0, "web.service", "[Service]\nExecStart=/bin/true\n");
+    write_unit(
+        &dir.0,
+        "site.target",
+        "[Unit]\nWants=web.socket web.service\n",
+    );
+    let mut manager = Manager::new_user();
+    manager.unit_paths = vec![dir.0.clone()];
 
     assert!(matches!(
-        manager.handle_dependency_start_error(
-            "root.service",
-            "required.service",
-            ManagerError::NotFound("required.service".to_string()),
-        ),
-        Err(ManagerError::NotFound(name)) if name == "required.service"
+        manager.start_with_deps("backend.service").await,
+        Err(ManagerError::DependencyFailed(name, dependency))
+            if name == "backend.service" && dependency == "api.socket"
     ));
-    assert!(manager
-        .handle_dependency_start_error(
-            "root.service",
-            "optional.service",
-            ManagerError::NotFound("optional.service".to_string()),
-        )
-        .is_ok());
+    // The socket of the same name is required once it is part of the job
+    manager.start_with_deps("site.target").await.unwrap();
+    let web = &manager.states["web.service"];
+    assert_eq!(web.failed_dependency.as_deref(), Some("web.socket"));
+    assert!(!web.is_active());
 }
 
 #[test]
@@ -298,3 +500,105 @@ fn user_runtime_and_notify_path_helpers_follow_mode_and_environment() {
         }
     }
 }
+
+#[tokio::test]
+async fn standard_targets_are_built_in_unless_a_unit_file_overrides_them() {
+    let dir = temp_dir("builtin-targets");
+    write_unit(
+        &dir.0,
+        "basic.target",
+        "[Unit]\nDescription=Site basic\nRequires=sysinit.target\n",
+    );
+    let mut manager = Manager::new();
+    manager.unit_paths = vec![dir.0.clone()];
+    assert_eq!(manager.load_state("multi-user.target"), "stub");
+
+    let plan = manager.get_boot_plan("graphical.target").await.unwrap();
+
+    let position = |name: &str| plan.iter().position(|unit| unit == name).unwrap();
+    assert!(position("sysinit.target") < position("basic.target"));
+    assert!(position("basic.target") < position("multi-user.target"));
+    assert!(position("multi-user.target") < position("graphical.target"));
+    assert!(!plan.iter().any(|unit| unit == "sockets.target"));
+    let description = |name: &str| manager.units[name].unit_section().description.clone();
+    assert_eq!(description("basic.target").as_deref(), Some("Site basic"));
+    assert_eq!(
+        description("multi-user.target").as_deref(),
+        Some("Multi-User System")
+    );
+    assert_eq!(manager.fragment_path("multi-user.target"), None);
+    assert_eq!(manager.unit_file_state("multi-user.target"), Some("static"));
+
+    let mut user_manager = Manager::new_user();
+    user_manager.unit_paths = vec![dir.0.clone()];
+    assert!(matches!(
+        user_manager.load("multi-user.target").await,
+        Err(ManagerError::NotFound(_))
+    ));
+}
+
+#[tokio::test]
+async fn missing_dependencies_become_not_found_placeholders_until_reload() {
+    let dir = temp_dir("missing-deps");
+    write_unit(
+        &dir.0,
+        "needs-missing.target",
+        "[Unit]\nRequires=absent.target\n",
+    );
+    write_unit(&dir.0, "wants-missing.target", "[Unit]\nWants=absent.target\n");
+    let mut manager = Manager::new_user();
+    manager.unit_paths = vec![dir.0.clone()];
+
+    assert!(matches!(
+        manager.start_with_deps("needs-missing.target").await,
+        Err(ManagerError::DependencyFailed(name, dependency))
+            if name == "needs-missing.target" && dependency == "absent.target"
+    ));
+    manager.start_with_deps("wants-missing.target").await.unwrap();
+    assert!(manager.states["wants-missing.target"].is_active());
+    assert_eq!(manager.load_state("absent.target"), "not-found");
+    manager.publish_states();
+    let absent = manager.state_view().get("absent.target").unwrap();
+    assert_eq!((absent.unit_type, absent.load_state), ("target", "not-found"));
+
+    write_unit(&dir.0, "absent.target", "[Unit]\nDescription=Arrived\n");
+    manager.reload_units().await.unwrap();
+    assert_eq!(manager.load_state("absent.target"), "loaded");
+    manager.start_with_deps("needs-missing.target").await.unwrap();
+    assert!(manager.states["absent.target"].is_active());
+    manager.publish_states();
+    let arrived = manager.state_view().get("absent.target").unwrap();
+    assert_eq!(arrived.load_state, "loaded");
+    assert_eq!(arrived.description.as_deref(), Some("Arrived"));
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn world_writable_units_are_refused_unless_configured_otherwise() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = temp_dir("world-writable");
+    let path = write_unit(&dir.0, "open.service", "[Service]\nExecStart=/bin/true\n");
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+    let mut manager = Manager::new_user();
+    manager.unit_paths = vec![dir.0.clone()];
+
+    assert!(matches!(
+        manager.load("open.service").await,
+        Err(ManagerError::InsecureUnitFile(message)) if message.contains("world-writable")
+    ));
+    assert_eq!(manager.load_state("open.service"), "error");
+
+    write_unit(&dir.0, "patched.service", "[Service]\nExecStart=/bin/ls\n");
+    let dropin = dir.0.join("patched.service.d/open.conf");
+    std::fs::create_dir_all(dropin.parent().unwrap()).unwrap();
+    std::fs::write(&dropin, "[Service]\nUser=root\n").unwrap();
+    std::fs::set_permissions(&dropin, std::fs::Permissions::from_mode(0o666)).unwrap();
+    assert!(matches!(
+        manager.load("patched.service").await,
+        Err(ManagerError::InsecureUnitFile(message)) if message.contains("open.conf")
+    ));
+
+    manager.config.unit_file_permissions = units::UnitFilePermissions::Warn;
+    assert_eq!(manager.load("open.service").await.unwrap(), "open.service");
+}
@@ -30,9 +30,13 @@ impl Manager {
             self.start_path(actual_name, &path_unit).await?;
             return Ok(true);
         }
-        if let Some(reason) = self.check_conditions(unit) {
-            log::info!("{}: condition failed: {}", actual_name, reason);
-            return Err(ManagerError::ConditionFailed(actual_name.to_string(), reason));
+        if let Some(reason) = self.check_conditions(unit).await {
+            tracing::info!("Skipped {}: {}", actual_name, reason);
+            self.states
+                .entry(actual_name.to_string())
+                .or_default()
+                .set_condition_failed(reason);
+            return Ok(true);
         }
         if let Some(mount) = unit.as_mount().cloned() {
             self.start_mount(actual_name, &mount).await?;
@@ -44,16 +48,20 @@ impl Manager {
     async fn start_service_unit(
         &mut self,
         actual_name: &str,
-        service: Service,
+        mut service: Service,
     ) -> Result<(), ManagerError> {
-        self.mark_service_starting(actual_name)?;
+        if !self.mark_service_starting(actual_name)? {
+            return Ok(());
+        }
+        self.degrade_sandbox(actual_name, &mut service);
         if service.service.service_type == ServiceType::Idle {
             self.wait_for_idle_queue(actual_name).await;
         }
+        let mut profile = spawn_profile::SpawnRecorder::start(!self.executor_path.is_empty());
 
         let (socket_fds, socket_fd_names) = self.prepare_socket_fds(&service, actual_name);
         let (dynamic_uid, dynamic_gid) = self.allocate_dynamic_user(actual_name, &service)?;
-        let options = self.build_spawn_options(
+        let mut options = self.build_spawn_options(
             &service,
             actual_name,
             socket_fds,
@@ -61,15 +69,23 @@ impl Manager {
             dynamic_uid,
             dynamic_gid,
         );
+        options.credentials_directory =
+            self.setup_service_credentials(actual_name, &service, &options)?;
+        profile.mark("prepare");
+        options.profile_fd = profile.child_fd();
 
         if service.service.service_type == ServiceType::Oneshot {
-            return self.start_oneshot_service(actual_name, &service, options);
+            return self.start_oneshot_service(actual_name, &service, options, profile);
         }
 
+        let gate = exec_gate::ExecGate::new(!self.executor_path.is_empty());
+        options.exec_gate_fd = gate.as_ref().map(exec_gate::ExecGate::child_fd);
         let child = process::spawn_service_via_executor(&service, &options, &self.executor_path, 0)?;
+        profile.spawned();
         let pid = self.log_spawned_pid(actual_name, &child);
         let limits = service_cgroup_limits(&service);
         let slice = service.service.slice.as_deref().map(str::to_string);
+        self.enable_accounting(actual_name, &service, slice.as_deref());
         self.setup_cgroup_for_service(
             actual_name,
             pid,
@@ -77,6 +93,15 @@ impl Manager {
             slice.as_deref(),
             service.service.delegate,
         );
+        profile.mark("cgroup");
+        self.attach_ip_firewall(actual_name, &service);
+        self.attach_socket_bind_filter(actual_name, &service);
+        self.apply_restrict_file_systems(actual_name, &service);
+        profile.mark("bpf");
+        if let Some(gate) = gate {
+            gate.release();
+        }
+        self.spawn_profiles.insert(actual_name.to_string(), profile);
 
         self.processes.insert(actual_name.to_string(), child);
         self.pid_to_service.insert(pid, actual_name.to_string());
@@ -84,7 +109,9 @@ impl Manager {
         Ok(())
     }
 
-    fn mark_service_starting(&mut self, actual_name: &str) -> Result<(), ManagerError> {
+    /// Mark `actual_name` as starting; false if it is still stopping, in
+    /// which case it starts once the stop finishes
+    fn mark_service_starting(&mut self, actual_name: &str) -> Result<bool, ManagerError> {
         let state = self
             .states
             .get_mut(actual_name)
@@ -92,14 +119,20 @@ impl Manager {
         if state.is_active() {
             return Err(ManagerError::AlreadyActive(actual_name.to_string()));
         }
+        if state.active == ActiveState::Deactivating || self.stop_post_running.contains(actual_name)
+        {
+            tracing::info!("{} is stopping, starting it afterwards", actual_name);
+            self.queued_starts.insert(actual_name.to_string());
+            return Ok(false);
+        }
         state.set_starting();
         self.active_jobs += 1;
-        Ok(())
+        Ok(true)
     }
 
     fn log_oneshot_start(&self, actual_name: &str, service: &Service) -> usize {
         let num_commands = service.service.exec_start.len();
-        log::info!(
+        tracing::info!(
             "Starting oneshot {} ({} command{})",
             actual_name,
             num_commands,
@@ -109,9 +142,9 @@ impl Manager {
     }
 
     fn log_spawned_pid(&self, actual_name: &str, child: &Child) -> u32 {
-        log::debug!("{}: spawn returned, getting PID", actual_name);
+        tracing::debug!("{}: spawn returned, getting PID", actual_name);
         let pid = child.id().unwrap_or(0);
-        log::debug!("{}: PID is {}", actual_name, pid);
+        tracing::debug!("{}: PID is {}", actual_name, pid);
         pid
     }
 
@@ -141,14 +174,14 @@ impl Manager {
 
     fn mark_notify_start(&mut self, actual_name: &str, pid: u32) {
         self.waiting_ready.insert(pid, actual_name.to_string());
-        log::info!("Started {} (PID {}), waiting for READY", actual_name, pid);
+        tracing::info!("Started {} (PID {}), waiting for READY", actual_name, pid);
     }
 
     fn mark_dbus_start(&mut self, actual_name: &str, pid: u32, service: &Service) {
         if let Some(bus_name) = service.service.bus_name.as_ref() {
             self.waiting_bus_name
                 .insert(bus_name.clone(), actual_name.to_string());
-            log::info!(
+            tracing::info!(
                 "Started {} (PID {}), waiting for D-Bus name {}",
                 actual_name,
                 pid,
@@ -156,7 +189,7 @@ impl Manager {
             );
             return;
         }
-        log::warn!(
+        tracing::warn!(
             "{} is Type=dbus but has no BusName=, treating as simple",
             actual_name
         );
@@ -164,11 +197,11 @@ impl Manager {
     }
 
     fn mark_forking_start(&mut self, actual_name: &str, pid: u32, service: &Service) {
-        log::info!("Started {} (PID {}), waiting for fork", actual_name, pid);
+        tracing::info!("Started {} (PID {}), waiting for fork", actual_name, pid);
         let Some(pid_file) = service.service.pid_file.as_ref() else {
             return;
         };
-        log::debug!("{} will read PID from {}", actual_name, pid_file.display());
+        tracing::debug!("{} will read PID from {}", actual_name, pid_file.display());
         self.pid_files
             .insert(actual_name.to_string(), pid_file.clone());
     }
@@ -182,12 +215,12 @@ impl Manager {
             self.watchdog_deadlines
                 .insert(actual_name.to_string(), std::time::Instant::now() + wd);
         }
-        log::info!("Started {} (PID {})", actual_name, pid);
+        tracing::info!("Started {} (PID {})", actual_name, pid);
     }
 
     fn log_missing_cgroup_support(&self, name: &str, has_resource_limits: bool) {
         if has_resource_limits {
-            log::error!(
+            tracing::error!(
                 "Service {} requests resource limits but cgroups unavailable - limits NOT enforced",
                 name
             );
@@ -196,14 +229,27 @@ impl Manager {
 
     fn log_cgroup_setup_error(&self, name: &str, has_resource_limits: bool, err: std::io::Error) {
         if has_resource_limits {
-            log::error!(
+            tracing::error!(
                 "Failed to set up cgroup for {} (resource limits NOT enforced): {}",
                 name,
                 err
             );
             return;
         }
-        log::warn!("Failed to set up cgroup for {}: {}", name, err);
+        tracing::warn!("Failed to set up cgroup for {}: {}", name, err);
+    }
+
+    fn enable_accounting(&self, name: &str, service: &Service, slice: Option<&str>) {
+        let controllers = accounting_controllers(service, &self.config);
+        let Some(cgroup_mgr) = self.cgroup_manager.as_ref() else {
+            return;
+        };
+        if controllers.is_empty() {
+            return;
+        }
+        if let Err(e) = cgroup_mgr.enable_controllers(slice, &controllers) {
+            tracing::warn!("Failed to enable accounting for {}: {}", name, e);
+        }
     }
 
     fn enable_service_delegation(
@@ -213,7 +259,7 @@ impl Manager {
         cgroup_path: &PathBuf,
     ) {
         if let Err(e) = cgroup_mgr.enable_delegation(cgroup_path) {
-            log::warn!("Failed to enable cgroup delegation for {}: {}", name, e);
+            tracing::warn!("Failed to enable cgroup delegation for {}: {}", name, e);
         }
     }
 
@@ -223,7 +269,7 @@ impl Manager {
             tokio::time::sleep(std::time::Duration::from_millis(100)).await;
         }
         if self.active_jobs > 1 {
-            log::debug!("{}: idle timeout, proceeding anyway", name);
+            tracing::debug!("{}: idle timeout, proceeding anyway", name);
         }
     }
 
@@ -235,14 +281,14 @@ impl Manager {
         let socket_fds = self.get_socket_fds(&service.name);
         let socket_fd_names = self.get_socket_fd_names(&service.name);
         if !socket_fds.is_empty() {
-            log::info!(
+            tracing::info!(
                 "{}: passing socket FDs {:?} names {:?}",
                 actual_name,
                 socket_fds,
                 socket_fd_names
             );
         } else if !service.service.sockets.is_empty() {
-            log::warn!(
+            tracing::warn!(
                 "{}: has Sockets={:?} but got NO socket FDs! socket_fds keys: {:?}",
                 actual_name,
                 service.service.sockets,
@@ -261,11 +307,11 @@ impl Manager {
             match self.dynamic_user_manager.allocate(name) {
                 Ok((uid, gid)) => {
                     self.dynamic_uids.insert(name.to_string(), uid);
-                    log::info!("Allocated dynamic UID/GID {} for {}", uid, name);
+                    tracing::info!("Allocated dynamic UID/GID {} for {}", uid, name);
                     Ok((Some(uid), Some(gid)))
                 }
                 Err(e) => {
-                    log::error!("Failed to allocate dynamic user for {}: {}", name, e);
+                    tracing::error!("Failed to allocate dynamic user for {}: {}", name, e);
                     Err(ManagerError::StartFailed(e.to_string()))
                 }
             }
@@ -304,9 +350,14 @@ impl Manager {
             dynamic_gid,
             stored_fds,
             user_environment: self.user_environment.clone(),
+            inherit_environment: self.user_mode,
+            credentials_directory: None,
+            profile_fd: None,
+            exec_gate_fd: None,
+            remote_address: self.connection_peers.get(actual_name).copied(),
         };
         if is_notify {
-            log::debug!(
+            tracing::debug!(
                 "{}: Type=notify, NOTIFY_SOCKET={:?}",
                 actual_name,
                 options.notify_socket
@@ -319,16 +370,29 @@ impl Manager {
         &mut self,
         actual_name: &str,
         service: &Service,
-        options: SpawnOptions,
+        mut options: SpawnOptions,
+        mut profile: spawn_profile::SpawnRecorder,
     ) -> Result<(), ManagerError> {
         let num_commands = self.log_oneshot_start(actual_name, service);
+        let gate = exec_gate::ExecGate::new(!self.executor_path.is_empty());
+        options.exec_gate_fd = gate.as_ref().map(exec_gate::ExecGate::child_fd);
         let child = process::spawn_service_via_executor(service, &options, &self.executor_path, 0)?;
+        profile.spawned();
         let pid = self.log_spawned_pid(actual_name, &child);
         let limits = service_cgroup_limits(service);
         let slice = service.service.slice.as_deref().map(str::to_string);
         let delegate = service.service.delegate;
         self.setup_cgroup_for_service(actual_name, pid, &limits, slice.as_deref(), delegate);
-        log::info!("Started {} (PID {})", actual_name, pid);
+        profile.mark("cgroup");
+        self.attach_ip_firewall(actual_name, service);
+        self.attach_socket_bind_filter(actual_name, service);
+        self.apply_restrict_file_systems(actual_name, service);
+        profile.mark("bpf");
+        if let Some(gate) = gate {
+            gate.release();
+        }
+        self.spawn_profiles.insert(actual_name.to_string(), profile);
+        tracing::info!("Started {} (PID {})", actual_name, pid);
 
         self.spawn_initial_oneshot_completion_task(
             child,
@@ -340,15 +404,24 @@ impl Manager {
     }
 
     fn configure_post_spawn_state(&mut self, actual_name: &str, pid: u32, service: &Service) {
+        self.begin_endpoint_wait(actual_name, service);
         match service.service.service_type {
             ServiceType::Notify => self.mark_notify_start(actual_name, pid),
             ServiceType::Dbus => self.mark_dbus_start(actual_name, pid, service),
             ServiceType::Forking => self.mark_forking_start(actual_name, pid, service),
+            _ if Self::waits_for_endpoint(service) => {
+                tracing::info!(
+                    "Started {} (PID {}), waiting for its endpoint",
+                    actual_name,
+                    pid
+                )
+            }
             _ => self.mark_running_start(actual_name, pid, service),
         };
     }
 
     /// Start a single unit (internal, assumes already loaded)
+    #[tracing::instrument(name = "job", skip_all, fields(unit = name, job = "start"))]
     async fn start_single(&mut self, name: &str) -> Result<(), ManagerError> {
         self.log_start_single_request(name);
         let actual_name = self.resolve_start_unit_name(name).await?;
@@ -380,6 +453,11 @@ impl Manager {
             || limits.cpu_quota.is_some()
             || limits.tasks_max.is_some();
 
+        if self.take_injected_cgroup_failure(name) {
+            let err = std::io::Error::other("injected fault");
+            self.log_cgroup_setup_error(name, has_resource_limits, err);
+            return;
+        }
         let Some(cgroup_mgr) = self.cgroup_manager.as_ref() else {
             self.log_missing_cgroup_support(name, has_resource_limits);
             return;
@@ -393,7 +471,7 @@ impl Manager {
             }
         };
 
-        log::debug!("Created cgroup {} for {}", cgroup_path.display(), name);
+        tracing::debug!("Created cgroup {} for {}", cgroup_path.display(), name);
         if delegate {
             self.enable_service_delegation(cgroup_mgr, name, &cgroup_path);
         }
@@ -423,37 +501,28 @@ impl Manager {
         }
     }
 
-    async fn wait_for_child_exit(&mut self, name: &str, mut child: Child) {
-        let timeout_sec = self
-            .units
+    fn stop_timeout(&self, name: &str) -> std::time::Duration {
+        self.units
             .get(name)
             .and_then(|u| u.as_service())
             .and_then(|s| s.service.timeout_stop_sec)
-            .unwrap_or(std::time::Duration::from_secs(10));
+            .unwrap_or(std::time::Duration::from_secs(10))
+    }
 
-        match tokio::time::timeout(timeout_sec, child.wait()).await {
-            Ok(Ok(status)) => {
-                let code = status.code().unwrap_or(-1);
-                if let Some(state) = self.states.get_mut(name) {
-                    state.set_stopped(code);
-                }
-                log::info!("Stopped {} (exit code {})", name, code);
-            }
-            Ok(Err(e)) => {
-                if let Some(state) = self.states.get_mut(name) {
-                    state.set_failed(e.to_string());
-                }
-            }
-            Err(_) => {
-                log::warn!("Timeout stopping {}, sending SIGKILL", name);
-                if let Some(pid) = child.id() {
-                    unsafe { libc::kill(pid as i32, libc::SIGKILL) };
-                }
-                let _ = child.wait().await;
-                if let Some(state) = self.states.get_mut(name) {
-                    state.set_stopped(-9);
-                }
-            }
+    fn set_stop_phase(&mut self, name: &str, sub: SubState) {
+        if let Some(state) = self.states.get_mut(name) {
+            state.set_stop_phase(sub);
+        }
+    }
+
+    fn record_stop_outcome(&mut self, name: &str, outcome: stop_job::StopOutcome) {
+        let Some(state) = self.states.get_mut(name) else {
+            return;
+        };
+        match outcome {
+            (_, Some(code)) => state.set_stopped(code),
+            (ServiceResult::Success, None) => state.set_stopped(0),
+            (result, None) => state.set_failed(format!("Stop failed: {}", result.as_str())),
         }
     }
 
@@ -471,16 +540,51 @@ impl Manager {
         }
     }
 
-    async fn run_stop_post_commands(&self, name: &str) {
+    /// Apply TTYReset=, TTYVHangup= and TTYVTDisallocate= once a service is gone
+    fn reset_service_tty(&self, name: &str) {
         let Some(svc) = self.units.get(name).and_then(|unit| unit.as_service()) else {
             return;
         };
-        for cmd_line in &svc.service.exec_stop_post {
-            log::debug!("Running ExecStopPost for {}: {}", name, cmd_line);
-            if let Err(e) = run_simple_command(cmd_line).await {
-                log::warn!("ExecStopPost failed for {}: {}", name, e);
-            }
+        if let Some(path) = &svc.service.tty_path {
+            crate::tty::reset_after_exit(path, process::tty_options(&svc.service));
         }
     }
 
+    /// Run ExecStopPost= commands after the service stopped, successful or
+    /// not, in the background. They get TimeoutStopSec= before the one still
+    /// running is killed; starting the unit meanwhile waits for
+    /// `StopEvent::StopPostFinished`.
+    fn run_stop_post_commands(
+        &mut self,
+        name: &str,
+        result: ServiceResult,
+        exit_code: Option<i32>,
+    ) {
+        let Some(svc) = self.units.get(name).and_then(|unit| unit.as_service()) else {
+            return;
+        };
+        if svc.service.exec_stop_post.is_empty() {
+            return;
+        }
+        let commands = svc.service.exec_stop_post.clone();
+        let env = stop_post_environment(result, exit_code);
+        let timeout = self.stop_timeout(name);
+        let control_pids = self.control_pids.clone();
+        let tx = self.stop_event_tx.clone();
+        let name = name.to_string();
+        self.stop_post_running.insert(name.clone());
+        tokio::spawn(async move {
+            let run = run_command_lines(&name, ExecPhase::StopPost, &commands, &env, &control_pids);
+            if tokio::time::timeout(timeout, run).await.is_err() {
+                log::warn!("ExecStopPost= of {} timed out, sending SIGKILL", name);
+                if let Some(pid) = control_pids.lock().unwrap().remove(&name) {
+                    unsafe { libc::kill(pid as i32, libc::SIGKILL) };
+                }
+            }
+            let _ = tx
+                .send(StopEvent::StopPostFinished { service_name: name })
+                .await;
+        });
+    }
+
 }
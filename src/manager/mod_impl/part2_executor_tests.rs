@@ -1,6 +1,7 @@
 use super::*;
 use crate::manager::state::ServiceState;
-use crate::units::Service;
+use crate::manager::StopEvent;
+use crate::units::{Service, Unit};
 
 fn service(name: &str, configure: impl FnOnce(&mut Service)) -> Service {
     let mut service = Service::new(name.to_string());
@@ -31,6 +32,54 @@ async fn start_service_unit_tracks_real_spawned_executor_child() {
     );
     let mut child = manager.processes.remove("true.service").unwrap();
     assert!(child.wait().await.unwrap().success());
+
+    let (name, profile) = manager.spawn_profile("true").unwrap();
+    assert_eq!(name, "true.service");
+    let steps: Vec<_> = profile.steps.iter().map(|(step, _)| *step).collect();
+    assert!(profile.via_executor && profile.complete);
+    assert_eq!(steps[..2], ["prepare", "fork"]);
+    for step in ["exec", "executor", "credentials", "bpf"] {
+        assert!(steps.contains(&step), "{} missing from {:?}", step, steps);
+    }
+}
+
+#[tokio::test]
+async fn a_start_during_a_stop_runs_once_the_stop_finished() {
+    let Some(executor) = local_executor_path() else {
+        return;
+    };
+    let mut manager = Manager::new_user();
+    manager.executor_path = executor;
+    let svc = service("bg.service", |service| {
+        service.service.exec_start = vec!["/bin/sleep 5".to_string()];
+    });
+    manager.insert_unit("bg.service".to_string(), Unit::Service(svc.clone()));
+    manager
+        .states
+        .insert("bg.service".to_string(), ServiceState::new());
+    manager.start_service_unit("bg.service", svc).await.unwrap();
+    let first_pid = manager.states.get("bg.service").unwrap().main_pid;
+    let mut rx = manager.take_stop_event_rx().unwrap();
+
+    manager.enqueue_stop("bg").await.unwrap();
+    manager.start("bg").await.unwrap();
+    assert_eq!(
+        manager.states.get("bg.service").unwrap().active,
+        ActiveState::Deactivating
+    );
+
+    while let Some(event) = rx.recv().await {
+        let finished = matches!(event, StopEvent::Finished { .. });
+        manager.handle_stop_event(event).await;
+        if finished {
+            break;
+        }
+    }
+    let state = manager.states.get("bg.service").unwrap();
+    assert!(state.is_active());
+    assert_ne!(state.main_pid, first_pid);
+    assert!(manager.queued_starts.is_empty());
+    manager.stop("bg").await.unwrap();
 }
 
 #[tokio::test]
@@ -51,7 +100,12 @@ async fn start_oneshot_service_spawns_completion_task_with_real_executor() {
         manager.build_spawn_options(&svc, "oneshot.service", Vec::new(), Vec::new(), None, None);
 
     manager
-        .start_oneshot_service("oneshot.service", &svc, options)
+        .start_oneshot_service(
+            "oneshot.service",
+            &svc,
+            options,
+            spawn_profile::SpawnRecorder::start(true),
+        )
         .unwrap();
 
     let mut rx = manager.oneshot_completion_rx.take().unwrap();
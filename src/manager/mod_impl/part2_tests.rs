@@ -28,7 +28,7 @@ fn mark_service_starting_updates_state_and_rejects_missing_or_active_units() {
         Err(ManagerError::NotFound(name)) if name == "missing.service"
     ));
 
-    manager.mark_service_starting("demo.service").unwrap();
+    assert!(manager.mark_service_starting("demo.service").unwrap());
     let state = manager.states.get("demo.service").unwrap();
     assert_eq!(state.active, ActiveState::Activating);
     assert_eq!(state.sub, SubState::Starting);
@@ -352,7 +352,7 @@ async fn start_non_service_unit_handles_path_socket_and_mount_branches_safely()
 }
 
 #[tokio::test]
-async fn start_non_service_unit_reports_failed_conditions_before_mounting() {
+async fn start_non_service_unit_skips_units_with_failed_conditions() {
     let mut manager = Manager::new_user();
     let mut mount = Mount::new("tmp-sysd-missing.mount".to_string());
     mount
@@ -364,12 +364,14 @@ async fn start_non_service_unit_reports_failed_conditions_before_mounting() {
         .start_non_service_unit("tmp-sysd-missing.mount", &Unit::Mount(mount))
         .await;
 
-    assert!(matches!(
-        result,
-        Err(ManagerError::ConditionFailed(name, reason))
-            if name == "tmp-sysd-missing.mount"
-                && reason.contains("ConditionPathExists=")
-    ));
+    assert!(matches!(result, Ok(true)));
+    let state = manager.states.get("tmp-sysd-missing.mount").unwrap();
+    assert_eq!(state.active, ActiveState::Inactive);
+    assert!(!state.condition_result());
+    assert!(state
+        .condition_failure
+        .as_deref()
+        .is_some_and(|reason| reason.contains("ConditionPathExists=")));
 }
 
 #[test]
@@ -445,10 +447,11 @@ async fn start_service_unit_reports_executor_spawn_failure_without_tracking_proc
     let result = manager.start_service_unit("broken.service", svc).await;
 
     assert!(matches!(
-        result,
-        Err(ManagerError::Spawn(SpawnError::Spawn(message)))
-            if message.contains("Failed to spawn executor")
+        &result,
+        Err(ManagerError::Spawn(SpawnError::Executor { executor, command, .. }))
+            if executor == "/definitely/missing/sysd-executor" && command == "/bin/true"
     ));
+    assert_eq!(result.unwrap_err().os_error(), Some(libc::ENOENT));
     assert!(!manager.processes.contains_key("broken.service"));
     assert!(!manager.pid_to_service.values().any(|name| name == "broken.service"));
 }
@@ -619,7 +622,7 @@ async fn send_signals_to_child_handles_sighup_and_process_kill_mode() {
 }
 
 #[tokio::test]
-async fn wait_for_child_exit_records_success_status() {
+async fn stop_records_main_process_exit_status() {
     let mut manager = manager_with_state("wait.service");
     manager.units.insert(
         "wait.service".to_string(),
@@ -627,9 +630,11 @@ async fn wait_for_child_exit_records_success_status() {
             service.service.timeout_stop_sec = Some(Duration::from_secs(1));
         })),
     );
+    manager.states.get_mut("wait.service").unwrap().set_running(0);
     let child = tokio::process::Command::new("/bin/true").spawn().unwrap();
+    manager.processes.insert("wait.service".to_string(), child);
 
-    manager.wait_for_child_exit("wait.service", child).await;
+    manager.stop("wait.service").await.unwrap();
 
     let state = manager.states.get("wait.service").unwrap();
     assert_eq!(state.active, ActiveState::Inactive);
@@ -638,20 +643,23 @@ async fn wait_for_child_exit_records_success_status() {
 }
 
 #[tokio::test]
-async fn wait_for_child_exit_kills_process_after_timeout() {
+async fn stop_kills_process_after_timeout() {
     let mut manager = manager_with_state("slow.service");
     manager.units.insert(
         "slow.service".to_string(),
         Unit::Service(service("slow.service", |service| {
             service.service.timeout_stop_sec = Some(Duration::from_millis(10));
+            service.service.kill_mode = KillMode::None;
         })),
     );
+    manager.states.get_mut("slow.service").unwrap().set_running(0);
     let child = tokio::process::Command::new("/bin/sleep")
         .arg("5")
         .spawn()
         .unwrap();
+    manager.processes.insert("slow.service".to_string(), child);
 
-    manager.wait_for_child_exit("slow.service", child).await;
+    manager.stop("slow.service").await.unwrap();
 
     let state = manager.states.get("slow.service").unwrap();
     assert_eq!(state.active, ActiveState::Inactive);
@@ -679,9 +687,87 @@ async fn run_stop_post_commands_runs_successes_and_ignores_failures_or_missing_u
         })),
     );
 
-    manager.run_stop_post_commands("cleanup.service").await;
-    manager.run_stop_post_commands("missing.service").await;
+    let mut stop_events = manager.take_stop_event_rx().unwrap();
 
+    manager.run_stop_post_commands("cleanup.service", ServiceResult::Success, Some(0));
+    manager.run_stop_post_commands("missing.service", ServiceResult::Success, None);
+    assert!(!manager.stop_post_running.contains("missing.service"));
+
+    let event = stop_events.recv().await.unwrap();
+    assert!(matches!(
+        &event,
+        StopEvent::StopPostFinished { service_name } if service_name == "cleanup.service"
+    ));
     assert!(marker.exists());
     let _ = std::fs::remove_file(marker);
 }
+
+#[tokio::test]
+async fn run_stop_post_commands_exports_service_result() {
+    let marker = std::env::temp_dir().join(format!(
+        "sysd-stop-post-env-{}-{}",
+        std::process::id(),
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos()
+    ));
+    let mut manager = Manager::new_user();
+    manager.units.insert(
+        "result.service".to_string(),
+        Unit::Service(service("result.service", |service| {
+            service.service.exec_stop_post = vec![format!(
+                "/bin/sh -c 'echo $SERVICE_RESULT $EXIT_CODE $EXIT_STATUS > {}'",
+                marker.display()
+            )];
+        })),
+    );
+
+    let mut stop_events = manager.take_stop_event_rx().unwrap();
+
+    manager.run_stop_post_commands("result.service", ServiceResult::Signal, Some(-15));
+    stop_events.recv().await.unwrap();
+
+    let contents = std::fs::read_to_string(&marker).unwrap();
+    assert_eq!(contents.trim(), "signal killed TERM");
+    let _ = std::fs::remove_file(marker);
+}
+
+#[tokio::test]
+async fn hung_stop_post_command_is_killed_and_holds_starts_until_then() {
+    let mut manager = manager_with_state("hung.service");
+    manager.units.insert(
+        "hung.service".to_string(),
+        Unit::Service(service("hung.service", |service| {
+            service.service.exec_stop_post = vec!["/bin/sleep 30".to_string()];
+            service.service.timeout_stop_sec = Some(Duration::from_millis(100));
+        })),
+    );
+    let mut stop_events = manager.take_stop_event_rx().unwrap();
+
+    let started = std::time::Instant::now();
+    manager.run_stop_post_commands("hung.service", ServiceResult::ExitCode, Some(1));
+    assert!(!manager.mark_service_starting("hung.service").unwrap());
+    assert!(manager.queued_starts.contains("hung.service"));
+
+    let event = stop_events.recv().await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert!(matches!(event, StopEvent::StopPostFinished { .. }));
+    assert!(manager.control_pids.lock().unwrap().is_empty());
+    manager.stop_post_running.remove("hung.service");
+    assert!(manager.mark_service_starting("hung.service").unwrap());
+}
+
+#[test]
+fn mark_service_starting_queues_unit_that_is_still_stopping() {
+    let mut manager = manager_with_state("busy.service");
+    let state = manager.states.get_mut("busy.service").unwrap();
+    state.set_running(1);
+    state.set_stopping();
+
+    assert!(!manager.mark_service_starting("busy.service").unwrap());
+    assert!(manager.queued_starts.contains("busy.service"));
+    let state = manager.states.get("busy.service").unwrap();
+    assert_eq!(state.active, ActiveState::Deactivating);
+    assert_eq!(manager.active_jobs, 0);
+}
@@ -1,18 +1,195 @@
 impl Manager {
-    /// Stop a service
+    /// Stop a service, waiting for the whole stop sequence to finish
+    #[tracing::instrument(name = "job", skip_all, fields(unit = name, job = "stop"))]
     pub async fn stop(&mut self, name: &str) -> Result<(), ManagerError> {
         let name = self.normalize_name(name);
-        if let Some(result) = self.stop_non_service_unit(&name).await {
+        let part_of = self.active_part_of_dependents(&name);
+        self.stop_one(&name).await?;
+        self.stop_part_of_dependents(&name, part_of).await;
+        Ok(())
+    }
+
+    async fn stop_one(&mut self, name: &str) -> Result<(), ManagerError> {
+        if let Some(result) = self.stop_non_service_unit(name).await {
             return result;
         }
-        self.mark_unit_stopping(&name)?;
-        let (kill_mode, send_sighup) = self.stop_signal_config(&name);
-        self.stop_main_process(&name, &kill_mode, send_sighup).await;
-        self.cleanup_stopped_service(&name);
-        self.run_stop_post_commands(&name).await;
+        self.mark_unit_stopping(name)?;
+        let outcome = self.stop_job(name, None).run().await;
+        self.finish_stop(name, outcome);
         Ok(())
     }
 
+    /// Stop a service without waiting for its processes to exit
+    ///
+    /// The stop sequence runs in a background task; the runtime loop applies its
+    /// progress and completion through `handle_stop_event`.
+    #[tracing::instrument(name = "job", skip_all, fields(unit = name, job = "stop"))]
+    pub async fn enqueue_stop(&mut self, name: &str) -> Result<(), ManagerError> {
+        let name = self.normalize_name(name);
+        let part_of = self.active_part_of_dependents(&name);
+        self.enqueue_stop_one(&name).await?;
+        self.stop_part_of_dependents(&name, part_of).await;
+        Ok(())
+    }
+
+    async fn enqueue_stop_one(&mut self, name: &str) -> Result<(), ManagerError> {
+        if let Some(result) = self.stop_non_service_unit(name).await {
+            return result;
+        }
+        self.mark_unit_stopping(name)?;
+        let job = self.stop_job(name, Some(self.stop_event_tx.clone()));
+        self.spawn_stop_job(name, job);
+        Ok(())
+    }
+
+    /// Stop service `name` in the background because it failed with
+    /// `result` (watchdog, health check, start timeout): it ends failed
+    /// and Restart=on-failure/always restarts it. With `abort`, the main
+    /// process gets SIGABRT and that long to dump core instead of ExecStop=
+    /// and KillSignal=.
+    pub(super) fn enqueue_failure_stop(
+        &mut self,
+        name: &str,
+        result: ServiceResult,
+        abort: Option<std::time::Duration>,
+    ) {
+        self.watchdog_deadlines.remove(name);
+        if let Err(e) = self.mark_unit_stopping(name) {
+            log::debug!("Not stopping {} after {}: {}", name, result.as_str(), e);
+            return;
+        }
+        self.failure_stops.insert(name.to_string());
+        let mut job = self
+            .stop_job(name, Some(self.stop_event_tx.clone()))
+            .failing_with(result);
+        if let Some(timeout) = abort {
+            job = job.aborting(timeout);
+        }
+        self.spawn_stop_job(name, job);
+    }
+
+    fn spawn_stop_job(&self, name: &str, job: stop_job::StopJob) {
+        let tx = self.stop_event_tx.clone();
+        let name = name.to_string();
+        tokio::spawn(async move {
+            let (result, exit_code) = job.run().await;
+            let _ = tx
+                .send(StopEvent::Finished {
+                    service_name: name,
+                    result,
+                    exit_code,
+                })
+                .await;
+        });
+    }
+
+    /// Active units with PartOf= on `name`, directly or through another
+    /// such unit. Only explicit stop and restart jobs reach them; a unit
+    /// that merely exits or crashes leaves them running (unlike BindsTo=).
+    fn active_part_of_dependents(&self, name: &str) -> Vec<String> {
+        let mut found: Vec<String> = Vec::new();
+        let mut queue = vec![name.to_string()];
+        while let Some(unit) = queue.pop() {
+            for dependent in self.dependents(&unit, DependencyType::PartOf) {
+                if dependent != name && !found.contains(&dependent) {
+                    found.push(dependent.clone());
+                    queue.push(dependent);
+                }
+            }
+        }
+        found.retain(|unit| self.states.get(unit).is_some_and(|state| state.is_active()));
+        found.sort();
+        found
+    }
+
+    async fn stop_part_of_dependents(&mut self, name: &str, dependents: Vec<String>) {
+        for dependent in dependents {
+            log::info!("Stopping {} (part of {})", dependent, name);
+            match self.enqueue_stop_one(&dependent).await {
+                Ok(()) | Err(ManagerError::NotActive(_)) => {}
+                Err(e) => log::warn!("Failed to stop {}: {}", dependent, e),
+            }
+        }
+    }
+
+    /// Take the stop event receiver (for use in background task)
+    pub fn take_stop_event_rx(&mut self) -> Option<mpsc::Receiver<StopEvent>> {
+        self.stop_event_rx.take()
+    }
+
+    /// Apply progress of a background stop started by `enqueue_stop`; a
+    /// start requested meanwhile runs once the stop finished
+    pub async fn handle_stop_event(&mut self, event: StopEvent) {
+        match event {
+            StopEvent::Phase { service_name, sub } => {
+                let stopping = self
+                    .states
+                    .get(&service_name)
+                    .is_some_and(|state| state.active == ActiveState::Deactivating);
+                if stopping {
+                    self.set_stop_phase(&service_name, sub);
+                }
+            }
+            StopEvent::Finished {
+                service_name,
+                result,
+                exit_code,
+            } => {
+                self.finish_stop(&service_name, (result, exit_code));
+                self.start_queued(&service_name).await;
+            }
+            StopEvent::StopPostFinished { service_name } => {
+                self.stop_post_running.remove(&service_name);
+                self.start_queued(&service_name).await;
+            }
+        }
+    }
+
+    async fn start_queued(&mut self, name: &str) {
+        if self.queued_starts.remove(name) {
+            if let Err(e) = self.start(name).await {
+                log::warn!("Failed to start {} after its stop: {}", name, e);
+            }
+        }
+    }
+
+    fn finish_stop(&mut self, name: &str, outcome: stop_job::StopOutcome) {
+        match self.failure_stops.remove(name) {
+            true => self.record_failure(name, outcome.0),
+            false => self.record_stop_outcome(name, outcome),
+        }
+        self.cleanup_stopped_service(name);
+        self.socket_service_stopped(name);
+    }
+
+    /// Mark `name` failed with `result` and schedule its restart if
+    /// Restart=on-failure/always says so
+    fn record_failure(&mut self, name: &str, result: ServiceResult) {
+        let restart_sec = self.failure_restart_delay(name);
+        let Some(state) = self.states.get_mut(name) else {
+            return;
+        };
+        state.set_failed(match result {
+            ServiceResult::Watchdog => "Watchdog timeout".to_string(),
+            result => result.as_str().to_string(),
+        });
+        if let Some(restart_sec) = restart_sec {
+            state.set_auto_restart(restart_sec);
+            log::info!("{} scheduling restart in {:?}", name, restart_sec);
+        }
+    }
+
+    fn failure_restart_delay(&self, name: &str) -> Option<std::time::Duration> {
+        let service = self.units.get(name).and_then(|u| u.as_service())?;
+        if matches!(
+            service.service.restart,
+            units::RestartPolicy::Always | units::RestartPolicy::OnFailure
+        ) {
+            return Some(service.service.restart_delay());
+        }
+        None
+    }
+
     async fn stop_non_service_unit(&mut self, name: &str) -> Option<Result<(), ManagerError>> {
         if let Some(mount) = self.units.get(name).and_then(|u| u.as_mount()).cloned() {
             return Some(self.stop_mount(name, &mount).await);
@@ -52,21 +229,13 @@ impl Manager {
             .unwrap_or((KillMode::default(), false))
     }
 
-    async fn stop_main_process(&mut self, name: &str, kill_mode: &KillMode, send_sighup: bool) {
-        if let Some(mut child) = self.processes.remove(name) {
-            Self::send_signals_to_child(&mut child, kill_mode, send_sighup, name).await;
-            self.wait_for_child_exit(name, child).await;
-            return;
-        }
-        if let Some(state) = self.states.get_mut(name) {
-            state.set_stopped(0);
-        }
-    }
-
     fn cleanup_stopped_service(&mut self, name: &str) {
         self.cleanup_service_cgroup_after_stop(name);
         self.cleanup_runtime_dirs(name);
+        self.reset_service_tty(name);
+        self.remove_credentials_after_stop(name);
         self.watchdog_deadlines.remove(name);
+        self.remove_ipc_after_stop(name);
         self.release_dynamic_uid_after_stop(name);
         self.close_stored_fds_after_stop(name);
     }
@@ -106,19 +275,35 @@ impl Manager {
         }
     }
 
-    /// Restart a service (stop then start)
+    /// Restart a service (stop then start; RollingRestart=yes starts first),
+    /// then the active units that are PartOf= it
     pub async fn restart(&mut self, name: &str) -> Result<(), ManagerError> {
         let name = self.normalize_name(name);
+        let part_of = self.active_part_of_dependents(&name);
+        self.restart_one(&name).await?;
+        for dependent in part_of {
+            log::info!("Restarting {} (part of {})", dependent, name);
+            if let Err(e) = self.restart_one(&dependent).await {
+                log::warn!("Failed to restart {}: {}", dependent, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn restart_one(&mut self, name: &str) -> Result<(), ManagerError> {
+        if self.rolls_on_restart(name) {
+            return self.rolling_restart(name).await;
+        }
 
         // Stop if running (ignore NotActive error)
-        match self.stop(&name).await {
+        match self.stop_one(name).await {
             Ok(()) => {}
             Err(ManagerError::NotActive(_)) => {}
             Err(e) => return Err(e),
         }
 
         // Start
-        self.start(&name).await
+        self.start(name).await
     }
 
     /// Get service status
@@ -193,12 +378,42 @@ impl Manager {
     /// Unregister a scope (called when scope is abandoned or empty)
     pub async fn unregister_scope(&mut self, name: &str) -> Result<(), ManagerError> {
         self.states.remove(name);
+        self.close_stored_fds_after_stop(name);
         self.scope_manager.unregister(name).await
     }
 
+    /// Record the D-Bus controller of a scope
+    pub fn set_scope_controller(&mut self, name: &str, controller: &str) {
+        self.scope_manager.set_controller(name, controller);
+    }
+
+    /// Persist scopes and re-adopt those left by a previous sysd instance
+    ///
+    /// Session scopes outlive a daemon restart (e.g. an upgrade); their
+    /// D-Bus objects are registered again once the bus connects.
+    pub fn restore_scopes(&mut self) {
+        self.scope_manager.set_state_dir(self.scope_state_dir());
+        for scope in self.scope_manager.adopt_persisted() {
+            self.states
+                .insert(scope.name, ServiceState::running_scope());
+        }
+    }
+
+    /// Register D-Bus objects for re-adopted scopes
+    pub async fn register_scope_dbus_objects(&self) {
+        self.scope_manager.register_tracked_dbus_objects().await;
+    }
+
+    /// Where scope definitions are persisted (runtime dir, so not across reboots)
+    fn scope_state_dir(&self) -> PathBuf {
+        StateDirs::for_mode(self.user_mode).scopes()
+    }
+
     /// Normalize unit name (add .service suffix if no suffix present)
+    /// Full unit name for `name`, resolved to the canonical name if it is
+    /// an alias of a loaded unit
     fn normalize_name(&self, name: &str) -> String {
-        if name.ends_with(".service")
+        let name = if name.ends_with(".service")
             || name.ends_with(".target")
             || name.ends_with(".mount")
             || name.ends_with(".socket")
@@ -209,7 +424,21 @@ impl Manager {
             name.to_string()
         } else {
             format!("{}.service", name)
-        }
+        };
+        self.aliases.get(&name).cloned().unwrap_or(name)
+    }
+
+    /// All names a unit is known by: the canonical name first, then its aliases
+    pub fn unit_names(&self, name: &str) -> Vec<String> {
+        let canonical = self.normalize_name(name);
+        let mut aliases: Vec<String> = self
+            .aliases
+            .iter()
+            .filter(|(_, target)| **target == canonical)
+            .map(|(alias, _)| alias.clone())
+            .collect();
+        aliases.sort();
+        std::iter::once(canonical).chain(aliases).collect()
     }
 
     /// M20: Get boot plan without starting (for dry-run)
@@ -220,6 +449,10 @@ impl Manager {
 
     /// M20: Reload all unit files from disk
     pub async fn reload_units(&mut self) -> Result<usize, ManagerError> {
+        // Unchanged files are still served from the parse cache
+        units::invalidate_unit_index();
+        units::prune_unit_file_cache().await;
+        self.refresh_host_facts();
         let unit_names: Vec<String> = self.units.keys().cloned().collect();
         let mut reloaded = 0;
 
@@ -232,6 +465,18 @@ impl Manager {
             // Find the unit file
             let path = match self.find_unit(&name) {
                 Ok(p) => p,
+                Err(_) if self.has_builtin_unit(&name) => {
+                    // Picks up new drop-ins, and covers a unit file that went away
+                    match self.parse_builtin_unit(&name).await {
+                        Ok(new_unit) => {
+                            self.insert_unit(name.clone(), new_unit);
+                            self.fragment_paths.remove(&name);
+                            reloaded += 1;
+                        }
+                        Err(e) => log::warn!("Failed to reload {}: {}", name, e),
+                    }
+                    continue;
+                }
                 Err(_) => {
                     log::debug!("Unit {} no longer exists on disk, keeping in memory", name);
                     continue;
@@ -239,9 +484,13 @@ impl Manager {
             };
 
             // Re-parse it
-            match units::load_unit(&path).await {
-                Ok(new_unit) => {
-                    self.units.insert(name.clone(), new_unit);
+            match self.parse_unit_file(&path).await {
+                Ok(mut new_unit) => {
+                    // Instances are re-read from their template
+                    self.apply_canonical_name(&mut new_unit, &name);
+                    self.record_instance_template(&name, &path);
+                    self.insert_unit(name.clone(), new_unit);
+                    self.fragment_paths.insert(name.clone(), path);
                     reloaded += 1;
                     log::debug!("Reloaded {}", name);
                 }
@@ -251,6 +500,26 @@ impl Manager {
             }
         }
 
+        // Missing dependencies whose unit file may have appeared since
+        let placeholders: Vec<String> = self.placeholders.iter().cloned().collect();
+        for name in placeholders {
+            match self.load(&name).await {
+                Ok(_) => reloaded += 1,
+                Err(e) => log::debug!("{} still cannot be loaded: {}", name, e),
+            }
+        }
+
+        let loaded = &self.units;
+        self.aliases
+            .retain(|_, canonical| loaded.contains_key(canonical));
+        let declared: Vec<(String, String)> = self
+            .units
+            .iter()
+            .flat_map(|(name, unit)| declared_aliases(name, unit))
+            .collect();
+        self.register_declared_aliases(declared);
+
+        self.need_daemon_reload.clear();
         log::info!("Reloaded {} unit files", reloaded);
         Ok(reloaded)
     }
@@ -303,8 +572,12 @@ impl Manager {
         }
     }
 
-    /// M20: Switch to target, stopping units not in its dependency tree
+    /// M20: Switch to target, stopping units not in its dependency tree;
+    /// a NAME.snapshot is isolated instead
     pub async fn switch_target(&mut self, target: &str) -> Result<Vec<String>, ManagerError> {
+        if snapshot_units::is_snapshot(target) {
+            return self.isolate_snapshot(target).await;
+        }
         let target = self.normalize_name(target);
 
         // Get all units needed by the target
@@ -325,7 +598,7 @@ impl Manager {
         // Stop unneeded units
         for name in &to_stop {
             log::info!("Stopping {} (not needed by {})", name, target);
-            if let Err(e) = self.stop(name).await {
+            if let Err(e) = self.enqueue_stop(name).await {
                 log::warn!("Failed to stop {}: {}", name, e);
             }
         }
@@ -375,6 +648,34 @@ impl Manager {
         log::info!("Unset {} environment variables", names.len());
     }
 
+    /// Set environment variables from KEY=VALUE assignments (set-environment).
+    /// Nothing is changed if any assignment is malformed.
+    pub fn set_environment(&mut self, assignments: &[String]) -> Result<(), ManagerError> {
+        let vars = assignments
+            .iter()
+            .map(|assignment| {
+                parse_env_assignment(assignment)
+                    .ok_or_else(|| ManagerError::InvalidEnvironment(assignment.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        for (key, value) in vars {
+            log::info!("Setting manager environment {}={}", key, value);
+            self.user_environment.insert(key, value);
+        }
+        Ok(())
+    }
+
+    /// Manager environment as sorted KEY=VALUE strings (show-environment)
+    pub fn show_environment(&self) -> Vec<String> {
+        let mut vars: Vec<String> = self
+            .user_environment
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        vars.sort();
+        vars
+    }
+
     /// Get imported environment variables (to be passed to spawned services)
     pub fn get_user_environment(&self) -> &HashMap<String, String> {
         &self.user_environment
@@ -383,16 +684,26 @@ impl Manager {
     /// Reset failed state of all units
     pub fn reset_failed(&mut self) {
         for (name, state) in self.states.iter_mut() {
-            if state.active == ActiveState::Failed {
-                log::info!("Resetting failed state of {}", name);
-                state.active = ActiveState::Inactive;
-                state.sub = SubState::Dead;
-                state.error = None;
-            }
+            reset_failed_state(name, state);
+        }
+    }
+
+    /// Reset the failed state of one unit; a no-op unless it failed
+    pub fn reset_failed_unit(&mut self, name: &str) {
+        let name = self.normalize_name(name);
+        if let Some(state) = self.states.get_mut(&name) {
+            reset_failed_state(&name, state);
         }
     }
 }
 
+fn reset_failed_state(name: &str, state: &mut ServiceState) {
+    if state.active == ActiveState::Failed {
+        log::info!("Resetting failed state of {}", name);
+        state.reset_failed();
+    }
+}
+
 fn service_cgroup_limits(service: &Service) -> CgroupLimits {
     CgroupLimits {
         memory_max: service.service.memory_max,
@@ -401,6 +712,52 @@ fn service_cgroup_limits(service: &Service) -> CgroupLimits {
     }
 }
 
+/// Controllers to enable for MemoryAccounting=/CPUAccounting=, falling back
+/// to DefaultMemoryAccounting=/DefaultCPUAccounting=
+fn accounting_controllers(service: &Service, config: &units::ManagerConfig) -> Vec<&'static str> {
+    let memory = service
+        .service
+        .memory_accounting
+        .unwrap_or(config.default_memory_accounting);
+    let cpu = service
+        .service
+        .cpu_accounting
+        .unwrap_or(config.default_cpu_accounting);
+    [(memory, "memory"), (cpu, "cpu")]
+        .into_iter()
+        .filter_map(|(enabled, controller)| enabled.then_some(controller))
+        .collect()
+}
+
+/// Split KEY=VALUE, requiring KEY to be a valid variable name
+fn parse_env_assignment(assignment: &str) -> Option<(String, String)> {
+    let (key, value) = assignment.split_once('=')?;
+    let valid_name = key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && key.chars().next().is_some_and(|c| !c.is_ascii_digit());
+    valid_name.then(|| (key.to_string(), value.to_string()))
+}
+
+/// Alias= names a loaded unit declares for itself, paired with its name
+fn declared_aliases(name: &str, unit: &Unit) -> Vec<(String, String)> {
+    let instance = units::extract_instance(name);
+    unit.install_section()
+        .map(|install| {
+            install
+                .alias
+                .iter()
+                // An instance of a template is also known by the aliased template's instance
+                .map(|alias| match &instance {
+                    Some(instance) => units::instantiate_template(alias, instance)
+                        .unwrap_or_else(|| alias.clone()),
+                    None => alias.clone(),
+                })
+                .filter(|alias| !units::is_bare_template(alias))
+                .map(|alias| (alias, name.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn default_instance_for_unit(unit: &Unit) -> Option<String> {
     match unit {
         Unit::Service(s) => s.install.default_instance.clone(),
@@ -419,7 +776,10 @@ fn oneshot_completion_result(
             if code == 0 {
                 (Some(0), None)
             } else {
-                (Some(code), Some(format!("exit code {}", code)))
+                (
+                    Some(code),
+                    Some(format!("exit code {}", exit_code_text(code))),
+                )
             }
         }
         Err(e) => (None, Some(e.to_string())),
@@ -468,7 +828,7 @@ fn cleanup_runtime_directories(service: &crate::units::ServiceSection, service_n
         } else {
             name.as_str()
         };
-        let path = std::path::Path::new("/run").join(dir_name);
+        let path = crate::root::path("/run").join(dir_name);
         if path.exists() {
             if let Err(e) = std::fs::remove_dir_all(&path) {
                 log::warn!(
@@ -486,6 +846,40 @@ fn cleanup_runtime_directories(service: &crate::units::ServiceSection, service_n
 /// Run a simple command (for ExecStopPost, etc.)
 /// Parses the command line and runs it, waiting for completion
 async fn run_simple_command(cmd_line: &str) -> Result<(), std::io::Error> {
+    run_simple_command_with_env(cmd_line, &[]).await
+}
+
+/// Run each command of a directive, logging (and otherwise ignoring) failures.
+/// While a command runs it is the unit's control process (see `KillWhom::Control`).
+async fn run_command_lines(
+    name: &str,
+    phase: ExecPhase,
+    commands: &[String],
+    env: &[(String, String)],
+    control_pids: &ControlPids,
+) {
+    for cmd_line in commands {
+        log::debug!("Running {} for {}: {}", phase, name, cmd_line);
+        if let Err(e) = run_command(cmd_line, env, Some((name, control_pids))).await {
+            log::warn!("{}={} failed for {}: {}", phase, cmd_line, name, e);
+        }
+    }
+}
+
+/// Run a simple command with extra environment variables
+/// `$VAR` words and `${VAR}` references to those variables are expanded like systemd does
+async fn run_simple_command_with_env(
+    cmd_line: &str,
+    env: &[(String, String)],
+) -> Result<(), std::io::Error> {
+    run_command(cmd_line, env, None).await
+}
+
+async fn run_command(
+    cmd_line: &str,
+    env: &[(String, String)],
+    control: Option<(&str, &ControlPids)>,
+) -> Result<(), std::io::Error> {
     use tokio::process::Command;
 
     // Strip leading - (ignore errors) or + (run as root)
@@ -494,16 +888,32 @@ async fn run_simple_command(cmd_line: &str) -> Result<(), std::io::Error> {
         .trim_start_matches('+')
         .trim();
 
-    // Split command line (simple split, doesn't handle quotes properly)
-    let parts: Vec<&str> = cmd_line.split_whitespace().collect();
-    if parts.is_empty() {
+    let parts = shlex::split(cmd_line).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid quoting in command: {}", cmd_line),
+        )
+    })?;
+    let parts: Vec<String> = parts
+        .iter()
+        .map(|part| expand_command_variables(part, env))
+        .collect();
+    let Some((program, args)) = parts.split_first() else {
         return Ok(());
+    };
+
+    let mut child = Command::new(program)
+        .args(args)
+        .envs(env.iter().map(|(key, value)| (key, value)))
+        .spawn()?;
+    if let (Some((name, control_pids)), Some(pid)) = (control, child.id()) {
+        control_pids.lock().unwrap().insert(name.to_string(), pid);
     }
-
-    let program = parts[0];
-    let args = &parts[1..];
-
-    let status = Command::new(program).args(args).status().await?;
+    let status = child.wait().await;
+    if let Some((name, control_pids)) = control {
+        control_pids.lock().unwrap().remove(name);
+    }
+    let status = status?;
 
     if status.success() {
         Ok(())
@@ -515,6 +925,49 @@ async fn run_simple_command(cmd_line: &str) -> Result<(), std::io::Error> {
     }
 }
 
+fn expand_command_variables(word: &str, env: &[(String, String)]) -> String {
+    let mut word = word.to_string();
+    for (key, value) in env {
+        if word.strip_prefix('$') == Some(key.as_str()) {
+            return value.clone();
+        }
+        word = word.replace(&format!("${{{}}}", key), value);
+    }
+    word
+}
+
+/// Environment for ExecStopPost= commands ($SERVICE_RESULT, $EXIT_CODE, $EXIT_STATUS)
+fn stop_post_environment(result: ServiceResult, exit_code: Option<i32>) -> Vec<(String, String)> {
+    let mut env = vec![("SERVICE_RESULT".to_string(), result.as_str().to_string())];
+    let Some(code) = exit_code else {
+        return env;
+    };
+    if code < 0 {
+        env.push(("EXIT_CODE".to_string(), "killed".to_string()));
+        env.push(("EXIT_STATUS".to_string(), signal_name(-code)));
+    } else {
+        env.push(("EXIT_CODE".to_string(), "exited".to_string()));
+        env.push(("EXIT_STATUS".to_string(), code.to_string()));
+    }
+    env
+}
+
+/// Signal name without the SIG prefix (e.g. "TERM"), as systemd reports it
+fn signal_name(signo: i32) -> String {
+    nix::sys::signal::Signal::try_from(signo)
+        .map(|signal| signal.as_str().trim_start_matches("SIG").to_string())
+        .unwrap_or_else(|_| signo.to_string())
+}
+
+/// Exit code of a reaped child, using negative signal numbers for killed processes
+fn exit_status_code(status: std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status
+        .code()
+        .or_else(|| status.signal().map(|signal| -signal))
+        .unwrap_or(-1)
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum ManagerError {
     #[error("Unit not found: {0}")]
@@ -535,11 +988,17 @@ pub enum ManagerError {
     #[error("Dependency cycle detected: {}", .0.join(" -> "))]
     Cycle(Vec<String>),
 
+    #[error("Dependency failed for {0}: {1}")]
+    DependencyFailed(String, String),
+
     #[error("Unit is a target (no process): {0}")]
     IsTarget(String),
 
-    #[error("Condition failed for {0}: {1}")]
-    ConditionFailed(String, String),
+    #[error("Not a target unit: {0}")]
+    NotATarget(String),
+
+    #[error("Not a service unit: {0}")]
+    NotAService(String),
 
     #[error("Unit has no [Install] section: {0}")]
     NoInstallSection(String),
@@ -555,6 +1014,42 @@ pub enum ManagerError {
 
     #[error("Unit is masked: {0}")]
     Masked(String),
+
+    #[error("Invalid signal: {0}")]
+    InvalidSignal(i32),
+
+    #[error("Unit must be stopped before cleaning: {0}")]
+    CleanWhileActive(String),
+
+    #[error("Refusing unsafe path: {0}")]
+    UnsafePath(String),
+
+    #[error("Invalid environment assignment: {0}")]
+    InvalidEnvironment(String),
+
+    #[error("Sleep mode not supported by the kernel: {0}")]
+    SleepUnsupported(String),
+
+    #[error("systemd-logind refused to {0}: {1}")]
+    SleepRefused(String, String),
+
+    #[error("Invalid property assignment: {0}")]
+    InvalidProperty(String),
+
+    #[error("Unit not started since the manager came up: {0}")]
+    NoSpawnProfile(String),
+
+    #[error("Refusing insecure unit file: {0}")]
+    InsecureUnitFile(String),
+
+    #[error("Rolling restart needs a running socket-activated simple or notify service: {0}")]
+    NotRollable(String),
+
+    #[error("sysd was built without the fault-injection feature")]
+    FaultInjectionDisabled,
+
+    #[error("Only the system manager running as PID 1 can schedule a shutdown")]
+    ShutdownNotAllowed,
 }
 
 impl From<std::io::Error> for ManagerError {
@@ -562,3 +1057,13 @@ impl From<std::io::Error> for ManagerError {
         ManagerError::Io(e.to_string())
     }
 }
+
+impl ManagerError {
+    /// errno behind a failed spawn (e.g. ENOENT for a missing binary)
+    pub fn os_error(&self) -> Option<i32> {
+        match self {
+            ManagerError::Spawn(e) => e.os_error(),
+            _ => None,
+        }
+    }
+}
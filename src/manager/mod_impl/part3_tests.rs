@@ -186,6 +186,26 @@ async fn scope_accessors_register_and_unregister_scope_state() {
     assert!(!manager.scope_manager().exists("session-88.scope"));
 }
 
+#[test]
+fn set_environment_validates_assignments_and_show_sorts() {
+    let mut manager = Manager::new();
+    manager
+        .set_environment(&["XDG_SEAT=seat0".to_string(), "DISPLAY=:0=x".to_string()])
+        .unwrap();
+    assert_eq!(
+        manager.show_environment(),
+        ["DISPLAY=:0=x", "XDG_SEAT=seat0"]
+    );
+
+    for bad in ["NOVALUE", "=x", "1X=y", "A-B=c"] {
+        assert!(matches!(
+            manager.set_environment(&["OK=1".to_string(), bad.to_string()]),
+            Err(ManagerError::InvalidEnvironment(_))
+        ));
+    }
+    assert!(!manager.get_user_environment().contains_key("OK"));
+}
+
 #[test]
 fn environment_import_unset_and_reset_failed_update_manager_state() {
     let mut manager = Manager::new();
@@ -243,6 +263,23 @@ fn service_helpers_extract_limits_default_instance_and_hash_changes() {
     assert_ne!(service_config_hash(&demo), before);
 }
 
+#[test]
+fn accounting_controllers_fall_back_to_manager_defaults() {
+    let config = units::ManagerConfig {
+        default_memory_accounting: true,
+        ..units::ManagerConfig::default()
+    };
+    let plain = service("plain.service", |_| {});
+    let opted_out = service("quiet.service", |service| {
+        service.service.memory_accounting = Some(false);
+        service.service.cpu_accounting = Some(true);
+    });
+
+    assert_eq!(accounting_controllers(&plain, &config), ["memory"]);
+    assert_eq!(accounting_controllers(&opted_out, &config), ["cpu"]);
+    assert!(accounting_controllers(&plain, &units::ManagerConfig::default()).is_empty());
+}
+
 #[test]
 fn default_and_error_helpers_cover_remaining_simple_branches() {
     let manager = Manager::default();
@@ -489,3 +526,35 @@ async fn run_simple_command_handles_empty_success_failure_and_missing_commands()
         .unwrap_err();
     assert_eq!(missing.kind(), std::io::ErrorKind::NotFound);
 }
+
+#[test]
+fn stop_post_environment_describes_exit_and_signal() {
+    let env = stop_post_environment(ServiceResult::ExitCode, Some(3));
+    assert!(env.contains(&("SERVICE_RESULT".to_string(), "exit-code".to_string())));
+    assert!(env.contains(&("EXIT_CODE".to_string(), "exited".to_string())));
+    assert!(env.contains(&("EXIT_STATUS".to_string(), "3".to_string())));
+
+    let env = stop_post_environment(ServiceResult::Signal, Some(-9));
+    assert!(env.contains(&("EXIT_CODE".to_string(), "killed".to_string())));
+    assert!(env.contains(&("EXIT_STATUS".to_string(), "KILL".to_string())));
+
+    let env = stop_post_environment(ServiceResult::Watchdog, None);
+    assert_eq!(
+        env,
+        vec![("SERVICE_RESULT".to_string(), "watchdog".to_string())]
+    );
+}
+
+#[tokio::test]
+async fn run_simple_command_with_env_expands_variables() {
+    let env = vec![("EXIT_STATUS".to_string(), "4".to_string())];
+    let failed = run_simple_command_with_env("/bin/sh -c 'exit $1' sh $EXIT_STATUS", &env)
+        .await
+        .unwrap_err();
+    assert!(failed.to_string().contains("status: 4"));
+
+    let failed = run_simple_command_with_env("/bin/sh -c 'exit ${1}' sh 1${EXIT_STATUS}", &env)
+        .await
+        .unwrap_err();
+    assert!(failed.to_string().contains("status: 14"));
+}
@@ -0,0 +1,331 @@
+//! Mount table monitoring
+//!
+//! The kernel signals changes to /proc/self/mountinfo with POLLPRI. Each
+//! change is diffed against the previous table so mounts and unmounts done
+//! outside sysd (an admin running `umount`, udisks, container runtimes) show
+//! up in the state of the matching .mount units. Mounts without a unit get
+//! one created on the fly, like systemd does.
+
+use std::collections::HashMap;
+use std::fs::File;
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::Interest;
+use tokio::sync::mpsc;
+
+use crate::units::{Mount, Unit};
+
+use super::{Manager, ManagerError, ServiceState};
+
+const MOUNTINFO: &str = "/proc/self/mountinfo";
+
+/// One line of /proc/self/mountinfo
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfoEntry {
+    pub mount_point: String,
+    pub source: String,
+    pub fs_type: String,
+    pub options: String,
+}
+
+/// Mount points that appeared or disappeared since the last check
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MountTableChanged {
+    pub mounted: Vec<MountInfoEntry>,
+    pub unmounted: Vec<String>,
+}
+
+impl MountTableChanged {
+    pub fn is_empty(&self) -> bool {
+        self.mounted.is_empty() && self.unmounted.is_empty()
+    }
+}
+
+/// Undo the octal escapes mountinfo uses for space, tab, newline and backslash
+fn unescape_mountinfo(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes.get(i..i + 4) {
+            Some([b'\\', digits @ ..]) if digits.iter().all(|d| (b'0'..=b'7').contains(d)) => {
+                digits
+                    .iter()
+                    .try_fold(0u8, |acc, d| acc.checked_mul(8)?.checked_add(d - b'0'))
+            }
+            _ => None,
+        };
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 4;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Parse one mountinfo line:
+/// `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue`
+fn parse_mountinfo_line(line: &str) -> Option<MountInfoEntry> {
+    let (mount_fields, fs_fields) = line.split_once(" - ")?;
+    let mut mount_fields = mount_fields.split_whitespace();
+    let mount_point = mount_fields.nth(4)?;
+    let options = mount_fields.next()?;
+    let mut fs_fields = fs_fields.split_whitespace();
+    let fs_type = fs_fields.next()?;
+    let source = fs_fields.next().unwrap_or("none");
+    Some(MountInfoEntry {
+        mount_point: unescape_mountinfo(mount_point),
+        source: unescape_mountinfo(source),
+        fs_type: fs_type.to_string(),
+        options: options.to_string(),
+    })
+}
+
+/// Mount table keyed by mount point (the topmost mount wins for overmounts)
+pub fn parse_mountinfo(content: &str) -> HashMap<String, MountInfoEntry> {
+    content
+        .lines()
+        .filter_map(parse_mountinfo_line)
+        .map(|entry| (entry.mount_point.clone(), entry))
+        .collect()
+}
+
+/// Mounts present only in `current`, and mount points gone from it
+pub fn diff_mount_tables(
+    previous: &HashMap<String, MountInfoEntry>,
+    current: &HashMap<String, MountInfoEntry>,
+) -> MountTableChanged {
+    let mut mounted: Vec<MountInfoEntry> = current
+        .iter()
+        .filter(|(path, _)| !previous.contains_key(*path))
+        .map(|(_, entry)| entry.clone())
+        .collect();
+    mounted.sort_by(|a, b| a.mount_point.cmp(&b.mount_point));
+    let mut unmounted: Vec<String> = previous
+        .keys()
+        .filter(|path| !current.contains_key(*path))
+        .cloned()
+        .collect();
+    unmounted.sort();
+    MountTableChanged { mounted, unmounted }
+}
+
+async fn read_mount_table() -> HashMap<String, MountInfoEntry> {
+    match tokio::fs::read_to_string(MOUNTINFO).await {
+        Ok(content) => parse_mountinfo(&content),
+        Err(e) => {
+            log::warn!("Failed to read {}: {}", MOUNTINFO, e);
+            HashMap::new()
+        }
+    }
+}
+
+/// Report mount table changes until the receiver is dropped. The first
+/// message lists every mount present at startup.
+pub async fn watch_mountinfo(tx: mpsc::Sender<MountTableChanged>) {
+    let watched = match File::open(MOUNTINFO)
+        .and_then(|file| AsyncFd::with_interest(file, Interest::PRIORITY))
+    {
+        Ok(watched) => watched,
+        Err(e) => {
+            log::error!("Failed to watch {}: {}", MOUNTINFO, e);
+            return;
+        }
+    };
+
+    let mut known = HashMap::new();
+    loop {
+        let current = read_mount_table().await;
+        let changed = diff_mount_tables(&known, &current);
+        known = current;
+        if !changed.is_empty() && tx.send(changed).await.is_err() {
+            break;
+        }
+
+        match watched.ready(Interest::PRIORITY).await {
+            Ok(mut guard) => guard.clear_ready(),
+            Err(e) => {
+                log::error!("Polling {} failed: {}", MOUNTINFO, e);
+                break;
+            }
+        }
+    }
+}
+
+impl Manager {
+    /// Start watching the kernel mount table (see `handle_mount_table_changed`)
+    pub fn start_mount_monitor(&self) {
+        tokio::spawn(watch_mountinfo(self.mount_table_tx.clone()));
+    }
+
+    /// Take the mount table change receiver (for use in event loops)
+    pub fn take_mount_table_rx(&mut self) -> Option<mpsc::Receiver<MountTableChanged>> {
+        self.mount_table_rx.take()
+    }
+
+    /// Bring .mount unit states in line with mounts done or undone outside sysd
+    pub async fn handle_mount_table_changed(
+        &mut self,
+        changed: MountTableChanged,
+    ) -> Result<(), ManagerError> {
+        for entry in changed.mounted {
+            self.mark_mounted(entry);
+        }
+        for mount_point in changed.unmounted {
+            self.mark_unmounted(&mount_point).await;
+        }
+        Ok(())
+    }
+
+    fn mark_mounted(&mut self, entry: MountInfoEntry) {
+        let name = Mount::name_from_mount_point(&entry.mount_point);
+        if !self.units.contains_key(&name) {
+            log::debug!("Tracking {} for {}", name, entry.mount_point);
+            let mut mount = Mount::new(name.clone());
+            mount.unit.description = Some(entry.mount_point.clone());
+            mount.unit.default_dependencies = false;
+            mount.mount.what = entry.source;
+            mount.mount.r#where = entry.mount_point;
+            mount.mount.fs_type = Some(entry.fs_type);
+            mount.mount.options = Some(entry.options);
+            self.insert_unit(name.clone(), Unit::Mount(mount));
+            self.states.insert(name.clone(), ServiceState::new());
+            self.mountinfo_units.insert(name.clone());
+        }
+
+        if let Some(state) = self.states.get_mut(&name) {
+            if !state.is_active() {
+                log::info!("{} mounted", name);
+                state.set_running(0);
+            }
+        }
+    }
+
+    async fn mark_unmounted(&mut self, mount_point: &str) {
+        let name = Mount::name_from_mount_point(mount_point);
+        let was_active = match self.states.get_mut(&name) {
+            Some(state) if state.is_active() => {
+                log::info!("{} unmounted", name);
+                state.set_stopped(0);
+                true
+            }
+            _ => false,
+        };
+
+        // Units created from the mount table go away with the mount
+        if self.mountinfo_units.remove(&name) {
+            self.remove_unit(&name);
+            self.states.remove(&name);
+        }
+        if was_active {
+            self.propagate_binds_to_stop(&name).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ActiveState;
+    use crate::units::Service;
+
+    const MOUNTINFO_SAMPLE: &str = "\
+22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw
+23 22 0:21 / /proc rw,nosuid,nodev,noexec shared:5 - proc proc rw
+40 22 8:2 / /mnt/my\\040disk rw,noatime shared:20 - ext4 /dev/sdb1 rw
+41 22 0:40 / /srv/data rw master:1 - nfs4 server:/export rw,vers=4.2
+";
+
+    fn entry(mount_point: &str) -> MountInfoEntry {
+        MountInfoEntry {
+            mount_point: mount_point.to_string(),
+            source: "tmpfs".to_string(),
+            fs_type: "tmpfs".to_string(),
+            options: "rw".to_string(),
+        }
+    }
+
+    #[test]
+    fn mountinfo_lines_are_parsed_and_unescaped() {
+        let table = parse_mountinfo(MOUNTINFO_SAMPLE);
+        assert_eq!(table.len(), 4);
+        let disk = &table["/mnt/my disk"];
+        assert_eq!(disk.source, "/dev/sdb1");
+        assert_eq!(disk.fs_type, "ext4");
+        assert_eq!(disk.options, "rw,noatime");
+        assert_eq!(table["/srv/data"].fs_type, "nfs4");
+        assert_eq!(unescape_mountinfo("a\\134b\\011c\\9"), "a\\b\tc\\9");
+        assert!(parse_mountinfo_line("garbage").is_none());
+    }
+
+    #[test]
+    fn diff_reports_new_and_removed_mount_points() {
+        let previous = parse_mountinfo(MOUNTINFO_SAMPLE);
+        let mut current = previous.clone();
+        current.remove("/srv/data");
+        current.insert("/run/media".to_string(), entry("/run/media"));
+
+        let changed = diff_mount_tables(&previous, &current);
+        assert_eq!(changed.mounted, [entry("/run/media")]);
+        assert_eq!(changed.unmounted, ["/srv/data"]);
+        assert!(diff_mount_tables(&current, &current).is_empty());
+    }
+
+    #[tokio::test]
+    async fn external_unmount_deactivates_unit_and_bound_units() {
+        let mut manager = Manager::new_user();
+        let mut mount = Mount::new("srv-data.mount".to_string());
+        mount.mount.r#where = "/srv/data".to_string();
+        manager
+            .units
+            .insert("srv-data.mount".to_string(), Unit::Mount(mount));
+        manager
+            .states
+            .insert("srv-data.mount".to_string(), ServiceState::new());
+        let mut service = Service::new("indexer.service".to_string());
+        service.unit.binds_to = vec!["srv-data.mount".to_string()];
+        manager.insert_unit("indexer.service".to_string(), Unit::Service(service));
+        let mut running = ServiceState::new();
+        running.set_running(0);
+        manager
+            .states
+            .insert("indexer.service".to_string(), running);
+
+        manager
+            .handle_mount_table_changed(MountTableChanged {
+                mounted: vec![entry("/srv/data"), entry("/run/media")],
+                unmounted: vec![],
+            })
+            .await
+            .unwrap();
+        assert_eq!(manager.states["srv-data.mount"].active, ActiveState::Active);
+        assert_eq!(
+            manager.states["run-media.mount"].active,
+            ActiveState::Active
+        );
+
+        manager
+            .handle_mount_table_changed(MountTableChanged {
+                mounted: vec![],
+                unmounted: vec!["/srv/data".to_string(), "/run/media".to_string()],
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            manager.states["srv-data.mount"].active,
+            ActiveState::Inactive
+        );
+        assert_ne!(
+            manager.states["indexer.service"].active,
+            ActiveState::Active
+        );
+        // Units created for foreign mounts are dropped with the mount
+        assert!(!manager.units.contains_key("run-media.mount"));
+    }
+}
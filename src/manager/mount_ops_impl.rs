@@ -5,11 +5,157 @@
 use std::fs;
 use std::io::Write;
 use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
 
 use crate::units::Mount;
 
 use super::{Manager, ManagerError};
 
+/// First delay between network mount attempts (doubled after each failure)
+const MOUNT_RETRY_INITIAL_DELAY: Duration = Duration::from_millis(500);
+const MOUNT_RETRY_MAX_DELAY: Duration = Duration::from_secs(16);
+/// Give up on a network mount without TimeoutSec= after this long
+const MOUNT_RETRY_DEFAULT_TIMEOUT: Duration = Duration::from_secs(90);
+/// How long processes on a ForceUnmount= mount get between SIGTERM and SIGKILL
+const MOUNT_USERS_KILL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Outcome of a background network mount (see `Manager::handle_mount_job_finished`)
+#[derive(Debug)]
+pub struct MountJobFinished {
+    pub name: String,
+    pub what: String,
+    pub mount_point: String,
+    pub result: nix::Result<()>,
+}
+
+/// Everything a background mount attempt needs, owned
+#[derive(Debug, Clone)]
+struct MountJob {
+    name: String,
+    what: String,
+    mount_point: String,
+    fs_type: String,
+    options: String,
+}
+
+impl MountJob {
+    fn attempt(&self) -> nix::Result<()> {
+        let (flags, data_options, graceful_options) = parse_mount_options(&self.options);
+        mount_with_graceful_retry(
+            &self.what,
+            &self.mount_point,
+            &self.fs_type,
+            flags,
+            &data_options,
+            &graceful_options,
+            &self.name,
+        )
+    }
+}
+
+fn next_mount_retry_delay(delay: Duration) -> Duration {
+    (delay * 2).min(MOUNT_RETRY_MAX_DELAY)
+}
+
+/// Retry a network mount with exponential backoff until it succeeds or `timeout` passes
+async fn retry_network_mount(job: MountJob, timeout: Duration, tx: mpsc::Sender<MountJobFinished>) {
+    let deadline = Instant::now() + timeout;
+    let mut delay = MOUNT_RETRY_INITIAL_DELAY;
+    let result = loop {
+        let attempt = job.clone();
+        let result = tokio::task::spawn_blocking(move || attempt.attempt())
+            .await
+            .unwrap_or(Err(nix::errno::Errno::EINTR));
+        let Err(e) = result else {
+            break result;
+        };
+        if Instant::now() + delay >= deadline {
+            log::warn!("{}: giving up after {:?}: {}", job.name, timeout, e);
+            break result;
+        }
+        log::info!(
+            "{}: mount failed ({}), retrying in {:?}",
+            job.name,
+            e,
+            delay
+        );
+        tokio::time::sleep(delay).await;
+        delay = next_mount_retry_delay(delay);
+    };
+    let _ = tx
+        .send(MountJobFinished {
+            name: job.name,
+            what: job.what,
+            mount_point: job.mount_point,
+            result,
+        })
+        .await;
+}
+
+/// PIDs with a cwd, root, executable or open file below `mount_point`, like `fuser -m`
+fn processes_using_path(proc_root: &Path, mount_point: &Path) -> Vec<i32> {
+    // Everything lives below /; never scan for users of the root mount
+    if mount_point == Path::new("/") {
+        return Vec::new();
+    }
+    let Ok(entries) = fs::read_dir(proc_root) else {
+        return Vec::new();
+    };
+    let own_pid = std::process::id() as i32;
+    let mut pids: Vec<i32> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<i32>().ok())
+        .filter(|pid| *pid != own_pid)
+        .filter(|pid| {
+            let dir = proc_root.join(pid.to_string());
+            let uses = |link: &Path| {
+                fs::read_link(link).is_ok_and(|target| target.starts_with(mount_point))
+            };
+            ["cwd", "root", "exe"]
+                .iter()
+                .any(|link| uses(&dir.join(link)))
+                || fs::read_dir(dir.join("fd"))
+                    .map(|fds| fds.flatten().any(|fd| uses(&fd.path())))
+                    .unwrap_or(false)
+        })
+        .collect();
+    pids.sort_unstable();
+    pids
+}
+
+/// SIGTERM the processes keeping `mount_point` busy, then SIGKILL what's left
+async fn kill_mount_users(name: &str, mount_point: &str) {
+    let path = std::path::PathBuf::from(mount_point);
+    let pids = tokio::task::spawn_blocking(move || processes_using_path(Path::new("/proc"), &path))
+        .await
+        .unwrap_or_default();
+    if pids.is_empty() {
+        return;
+    }
+    log::info!(
+        "{}: terminating processes using {}: {:?}",
+        name,
+        mount_point,
+        pids
+    );
+    for pid in &pids {
+        unsafe { libc::kill(*pid, libc::SIGTERM) };
+    }
+
+    let deadline = Instant::now() + MOUNT_USERS_KILL_TIMEOUT;
+    let alive = |pid: i32| unsafe { libc::kill(pid, 0) } == 0;
+    while Instant::now() < deadline && pids.iter().any(|pid| alive(*pid)) {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    for pid in pids.iter().filter(|pid| alive(**pid)) {
+        log::warn!("{}: PID {} ignored SIGTERM, killing", name, pid);
+        unsafe { libc::kill(*pid, libc::SIGKILL) };
+    }
+}
+
 /// Write to kernel log (/dev/kmsg) - survives better than filesystem logs during early boot
 fn mount_kmsg(msg: &str) {
     if let Ok(mut f) = fs::OpenOptions::new().write(true).open("/dev/kmsg") {
@@ -241,7 +387,7 @@ impl Manager {
             ensure_mount_directory(mount_point, mode);
         }
 
-        if is_mounted(mount_point) {
+        if is_mounted(mount_point).await {
             mount_kmsg(&format!(
                 "{} already mounted at {}, skipping",
                 name, mount_point
@@ -265,6 +411,22 @@ impl Manager {
             options
         );
 
+        if mnt.is_network() {
+            // The network may not be usable yet; keep trying in the background
+            let job = MountJob {
+                name: name.to_string(),
+                what: what.clone(),
+                mount_point: mount_point.clone(),
+                fs_type: fs_type.to_string(),
+                options,
+            };
+            let timeout = mnt.mount.timeout_sec.unwrap_or(MOUNT_RETRY_DEFAULT_TIMEOUT);
+            let handle = tokio::spawn(retry_network_mount(job, timeout, self.mount_job_tx.clone()));
+            self.mount_jobs
+                .insert(name.to_string(), handle.abort_handle());
+            return Ok(());
+        }
+
         let (flags, data_options, graceful_options) = parse_mount_options(&options);
 
         let result = mount_with_graceful_retry(
@@ -293,9 +455,14 @@ impl Manager {
 
         state.set_stopping();
 
+        if let Some(job) = self.mount_jobs.remove(name) {
+            log::info!("{}: cancelling pending mount", name);
+            job.abort();
+        }
+
         let mount_point = &mnt.mount.r#where;
 
-        if !is_mounted(mount_point) {
+        if !is_mounted(mount_point).await {
             log::debug!("{} not mounted, marking inactive", name);
             if let Some(state) = self.states.get_mut(name) {
                 state.set_stopped(0);
@@ -315,18 +482,56 @@ impl Manager {
             flags |= MntFlags::MNT_FORCE;
         }
 
+        if mnt.mount.force_unmount {
+            kill_mount_users(name, mount_point).await;
+        }
+
         let result = umount2(mount_point.as_str(), flags);
 
         finalize_umount_result(name, result, &mut self.states)
     }
+
+    /// Take the background mount receiver (for use in event loops)
+    pub fn take_mount_job_rx(&mut self) -> Option<mpsc::Receiver<MountJobFinished>> {
+        self.mount_job_rx.take()
+    }
+
+    /// Record the outcome of a background network mount
+    pub async fn handle_mount_job_finished(
+        &mut self,
+        done: MountJobFinished,
+    ) -> Result<(), ManagerError> {
+        self.mount_jobs.remove(&done.name);
+        let starting = self
+            .states
+            .get(&done.name)
+            .is_some_and(|state| state.active == super::ActiveState::Activating);
+        if !starting {
+            log::debug!(
+                "{}: mount finished after the unit left activating",
+                done.name
+            );
+            return Ok(());
+        }
+        finalize_mount_result(
+            &done.name,
+            &done.what,
+            &done.mount_point,
+            done.result,
+            &mut self.states,
+        )
+    }
 }
 
 /// Check if a path is currently mounted (by reading /proc/mounts)
-pub(super) fn is_mounted(path: &str) -> bool {
-    let Ok(content) = std::fs::read_to_string("/proc/mounts") else {
+pub(super) async fn is_mounted(path: &str) -> bool {
+    let Ok(content) = tokio::fs::read_to_string("/proc/mounts").await else {
         return false;
     };
+    mounts_contain(&content, path)
+}
 
+fn mounts_contain(content: &str, path: &str) -> bool {
     let normalized = if path == "/" {
         path.to_string()
     } else {
@@ -557,8 +762,101 @@ mod tests {
     }
 
     #[test]
-    fn is_mounted_handles_root_and_missing_paths() {
-        assert!(is_mounted("/"));
-        assert!(!is_mounted("/definitely/not/mounted/sysd-test"));
+    fn mount_retry_delay_doubles_up_to_the_cap() {
+        let mut delay = MOUNT_RETRY_INITIAL_DELAY;
+        let mut delays = Vec::new();
+        for _ in 0..8 {
+            delays.push(delay);
+            delay = next_mount_retry_delay(delay);
+        }
+        assert_eq!(delays[1], MOUNT_RETRY_INITIAL_DELAY * 2);
+        assert_eq!(delays.last(), Some(&MOUNT_RETRY_MAX_DELAY));
+    }
+
+    #[test]
+    fn processes_using_path_finds_working_directories_below_the_mount() {
+        let root = temp_dir("fuser");
+        let mut child = std::process::Command::new("sleep")
+            .arg("30")
+            .current_dir(&root.0)
+            .spawn()
+            .unwrap();
+
+        let users = processes_using_path(Path::new("/proc"), &root.0);
+        let other = processes_using_path(Path::new("/proc"), &root.0.join("elsewhere"));
+        let _ = child.kill();
+        let _ = child.wait();
+
+        assert!(users.contains(&(child.id() as i32)));
+        assert!(!users.contains(&(std::process::id() as i32)));
+        assert!(!other.contains(&(child.id() as i32)));
+        assert!(processes_using_path(Path::new("/proc"), Path::new("/")).is_empty());
+    }
+
+    #[tokio::test]
+    async fn network_mount_retries_in_background_until_stopped() {
+        let mut manager = Manager::new_user();
+        manager
+            .states
+            .insert("mnt-nfs.mount".to_string(), ServiceState::new());
+        let mut mount = mount_unit("mnt-nfs.mount", "/definitely/not/mounted/sysd-nfs");
+        mount.mount.what = "server:/export".to_string();
+        mount.mount.fs_type = Some("nfs".to_string());
+        mount.mount.directory_mode = None;
+
+        manager.start_mount("mnt-nfs.mount", &mount).await.unwrap();
+        assert_eq!(
+            manager.states["mnt-nfs.mount"].active,
+            ActiveState::Activating
+        );
+        assert!(manager.mount_jobs.contains_key("mnt-nfs.mount"));
+
+        manager.stop_mount("mnt-nfs.mount", &mount).await.unwrap();
+        assert!(manager.mount_jobs.is_empty());
+        assert_eq!(
+            manager.states["mnt-nfs.mount"].active,
+            ActiveState::Inactive
+        );
+    }
+
+    #[tokio::test]
+    async fn mount_job_results_apply_only_while_activating() {
+        let mut manager = Manager::new_user();
+        manager
+            .states
+            .insert("mnt-nfs.mount".to_string(), ServiceState::new());
+        let finished = |result| MountJobFinished {
+            name: "mnt-nfs.mount".to_string(),
+            what: "server:/export".to_string(),
+            mount_point: "/mnt/nfs".to_string(),
+            result,
+        };
+
+        manager
+            .handle_mount_job_finished(finished(Ok(())))
+            .await
+            .unwrap();
+        assert_eq!(
+            manager.states["mnt-nfs.mount"].active,
+            ActiveState::Inactive
+        );
+
+        manager
+            .states
+            .get_mut("mnt-nfs.mount")
+            .unwrap()
+            .set_starting();
+        let error = manager
+            .handle_mount_job_finished(finished(Err(nix::errno::Errno::EHOSTUNREACH)))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, ManagerError::Io(message) if message.contains("failed")));
+        assert_eq!(manager.states["mnt-nfs.mount"].active, ActiveState::Failed);
+    }
+
+    #[tokio::test]
+    async fn is_mounted_handles_root_and_missing_paths() {
+        assert!(is_mounted("/").await);
+        assert!(!is_mounted("/definitely/not/mounted/sysd-test").await);
     }
 }
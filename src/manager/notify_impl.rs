@@ -12,6 +12,8 @@ use tokio::sync::mpsc;
 
 use nix::sys::socket::{recvmsg, setsockopt, sockopt, ControlMessageOwned, MsgFlags};
 
+pub use crate::state_dir::NOTIFY_SOCKET_PATH;
+
 /// Messages from services via sd_notify
 #[derive(Debug, Clone)]
 pub struct NotifyMessage {
@@ -82,6 +84,8 @@ pub struct AsyncNotifyListener {
     /// Socket kept alive to maintain binding (receiver task has its own Arc)
     _socket: Arc<tokio::net::UnixDatagram>,
     socket_path: PathBuf,
+    /// Receiver task, stopped with the listener so the socket is closed
+    receiver: tokio::task::AbortHandle,
 }
 
 impl AsyncNotifyListener {
@@ -91,12 +95,13 @@ impl AsyncNotifyListener {
         prepare_socket_path(socket_path)?;
         let socket = Arc::new(create_notify_socket(socket_path)?);
         let (tx, rx) = mpsc::channel(64);
-        spawn_notify_receiver(Arc::clone(&socket), tx);
+        let receiver = spawn_notify_receiver(Arc::clone(&socket), tx);
 
         Ok((
             Self {
                 _socket: socket,
                 socket_path: socket_path.to_path_buf(),
+                receiver,
             },
             rx,
         ))
@@ -129,10 +134,14 @@ fn set_notify_socket_permissions(socket_path: &Path) -> std::io::Result<()> {
     std::fs::set_permissions(socket_path, std::fs::Permissions::from_mode(0o777))
 }
 
-fn spawn_notify_receiver(socket: Arc<tokio::net::UnixDatagram>, tx: mpsc::Sender<NotifyMessage>) {
+fn spawn_notify_receiver(
+    socket: Arc<tokio::net::UnixDatagram>,
+    tx: mpsc::Sender<NotifyMessage>,
+) -> tokio::task::AbortHandle {
     tokio::spawn(async move {
         receive_notify_messages(socket, tx).await;
-    });
+    })
+    .abort_handle()
 }
 
 async fn receive_notify_messages(
@@ -209,6 +218,7 @@ fn close_received_fds(fds: Vec<RawFd>) {
 
 impl Drop for AsyncNotifyListener {
     fn drop(&mut self) {
+        self.receiver.abort();
         let _ = std::fs::remove_file(&self.socket_path);
     }
 }
@@ -264,9 +274,6 @@ fn parse_notify_message(msg: &str, pid: u32, fds: Vec<RawFd>) -> NotifyMessage {
     NotifyMessage { pid, fields, fds }
 }
 
-/// Default socket path
-pub const NOTIFY_SOCKET_PATH: &str = "/run/sysd/notify";
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,9 +96,10 @@ impl Manager {
             );
 
             // Spawn path watcher task
-            tokio::spawn(async move {
+            let watcher = tokio::spawn(async move {
                 path_watcher::watch_paths(path_name, service_name, watches, tx).await;
             });
+            self.track_unit_task(name, watcher.abort_handle());
         }
 
         // Mark as active
@@ -125,8 +126,7 @@ impl Manager {
 
         log::info!("Stopping path unit {}", name);
 
-        // Path watcher tasks will complete naturally when their channel is dropped
-        // For now, we just mark the unit as stopped
+        self.abort_unit_tasks(name);
 
         if let Some(state) = self.states.get_mut(name) {
             state.set_stopped(0);
@@ -152,6 +152,9 @@ impl Manager {
             triggered.path_name,
             triggered.triggered_path
         );
+        if !self.trigger_allowed(&triggered.path_name) {
+            return Ok(());
+        }
 
         // Check if service is already running
         if let Some(state) = self.states.get(&triggered.service_name) {
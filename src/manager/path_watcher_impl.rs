@@ -7,6 +7,8 @@ use std::collections::HashMap;
 use std::path::Path;
 use tokio::sync::mpsc;
 
+use crate::units::{glob_base_dir, path_glob_matches_any};
+
 /// Message sent when a path condition is triggered
 #[derive(Debug)]
 pub struct PathTriggered {
@@ -101,9 +103,7 @@ async fn trigger_initial_conditions(
 fn initial_condition_satisfied(watch: &PathWatch) -> bool {
     match watch.watch_type {
         WatchType::Exists => Path::new(&watch.path).exists(),
-        WatchType::ExistsGlob => glob::glob(&watch.path)
-            .map(|paths| paths.into_iter().any(|p| p.is_ok()))
-            .unwrap_or(false),
+        WatchType::ExistsGlob => path_glob_matches_any(&watch.path),
         WatchType::DirectoryNotEmpty => std::fs::read_dir(&watch.path)
             .map(|mut entries| entries.next().is_some())
             .unwrap_or(false),
@@ -304,9 +304,7 @@ async fn handle_event(
 fn watch_condition_satisfied(watched: &WatchedPath) -> bool {
     match watched.watch_type {
         WatchType::Exists => Path::new(&watched.path).exists(),
-        WatchType::ExistsGlob => glob::glob(&watched.path)
-            .map(|paths| paths.into_iter().any(|p| p.is_ok()))
-            .unwrap_or(false),
+        WatchType::ExistsGlob => path_glob_matches_any(&watched.path),
         WatchType::Changed | WatchType::Modified => true,
         WatchType::DirectoryNotEmpty => std::fs::read_dir(&watched.path)
             .map(|mut entries| entries.next().is_some())
@@ -329,18 +327,6 @@ async fn send_trigger(
         .await;
 }
 
-/// Extract base directory from a glob pattern
-fn glob_base_dir(pattern: &str) -> std::path::PathBuf {
-    let path = Path::new(pattern);
-    for ancestor in path.ancestors() {
-        let s = ancestor.to_string_lossy();
-        if !s.contains('*') && !s.contains('?') && !s.contains('[') {
-            return ancestor.to_path_buf();
-        }
-    }
-    std::path::PathBuf::from("/")
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -6,7 +6,8 @@ use tokio::process::Child;
 
 use crate::units::Service;
 
-pub use imp::{SpawnError, SpawnOptions};
+pub(crate) use imp::tty_options;
+pub use imp::{resolve_uid_gid, ExecPhase, SpawnError, SpawnOptions};
 
 pub fn spawn_service_via_executor(
     service: &Service,
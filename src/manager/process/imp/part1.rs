@@ -1,11 +1,13 @@
 // Process spawning and management
 
 use std::collections::HashMap;
-use std::os::unix::io::{AsRawFd, RawFd};
-use std::path::Path;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use tokio::process::{Child, Command};
 
+use crate::executor::{mark_spawn_step, SpawnStep};
+use crate::tty::{TtyAcquire, TtyOptions};
 use crate::units::{Service, StdInput};
 
 /// Options for spawning a service
@@ -29,7 +31,21 @@ pub struct SpawnOptions {
     /// Imported user environment (for user session management)
     /// If provided, these are merged with inherited environment
     pub user_environment: HashMap<String, String>,
-}
+    /// Start from the manager's whole environment (user manager) instead of
+    /// only PassEnvironment= variables (system manager)
+    pub inherit_environment: bool,
+    /// Written credentials, exported as CREDENTIALS_DIRECTORY
+    pub credentials_directory: Option<std::path::PathBuf>,
+    /// Write end of the spawn profile pipe the child reports its setup steps on
+    pub profile_fd: Option<RawFd>,
+    /// Read end of the pipe sysd-executor waits on until its cgroup is ready
+    pub exec_gate_fd: Option<RawFd>,
+    /// Peer of the connection an Accept=yes instance serves (REMOTE_ADDR, REMOTE_PORT)
+    pub remote_address: Option<std::net::SocketAddr>,
+}
+
+/// PATH for system services unless the manager environment or unit sets one
+pub const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
 
 /// Spawn a process for a service with options
 pub fn spawn_service_with_options(
@@ -46,11 +62,18 @@ pub fn spawn_service_with_options(
     let exec_start = substitute_specifiers(exec_start, service);
 
     let (program, args) = parse_command(&exec_start)?;
+    if service.service.private_pids {
+        log::warn!(
+            "{}: PrivatePIDs= needs sysd-executor, running in the manager's PID namespace",
+            service.name
+        );
+    }
 
-    let mut cmd = create_spawn_command(&program, &args, &service.service.working_directory);
+    let working_directory = &service.service.working_directory;
+    let mut cmd = create_spawn_command(&program, &args, working_directory);
     prepare_spawn_settings(&mut cmd, service, options)?;
     configure_service_stdio(&mut cmd, &service.service.standard_input);
-    spawn_command(cmd, &program, &args)
+    spawn_command(cmd, &exec_start, working_directory)
 }
 
 fn create_spawn_command(
@@ -73,14 +96,26 @@ fn prepare_spawn_settings(
 ) -> Result<(), SpawnError> {
     let socket_activation = build_socket_activation(options);
     validate_socket_fds(&socket_activation.fds);
-    let extra_env = build_service_environment(service, options);
-    let unset_vars = service.service.unset_environment.clone();
-    if socket_activation.fds.is_empty() {
-        configure_direct_environment(cmd, &options.user_environment, &extra_env, &unset_vars);
-    }
+    let environment = build_full_environment(service, options);
+    let unset_vars = if socket_activation.fds.is_empty() {
+        configure_direct_environment(cmd, &environment, &service.service.unset_environment);
+        Vec::new()
+    } else {
+        // pre_exec edits the inherited environment in place
+        environment_unset_list(&environment, service)
+    };
     let (uid, gid) = resolve_uid_gid(service, options);
     create_service_directories(&service.service, &service.name, uid, gid)?;
-    install_pre_exec_context(cmd, service, socket_activation, extra_env, unset_vars, uid, gid);
+    install_pre_exec_context(
+        cmd,
+        service,
+        socket_activation,
+        environment,
+        unset_vars,
+        uid,
+        gid,
+        options.profile_fd,
+    );
     Ok(())
 }
 
@@ -92,6 +127,7 @@ fn install_pre_exec_context(
     unset_vars: Vec<String>,
     uid: Option<u32>,
     gid: Option<u32>,
+    profile_fd: Option<RawFd>,
 ) {
     #[cfg(unix)]
     unsafe {
@@ -108,8 +144,9 @@ fn install_pre_exec_context(
             uid,
             gid,
             tty_path: service.service.tty_path.clone(),
-            tty_reset: service.service.tty_reset,
+            tty_options: tty_options(&service.service),
             std_input: service.service.standard_input.clone(),
+            profile_fd,
         };
         cmd.pre_exec(move || run_pre_exec(&pre_exec));
     }
@@ -124,10 +161,18 @@ fn configure_service_stdio(cmd: &mut Command, std_input: &StdInput) {
     });
 }
 
-fn spawn_command(mut cmd: Command, program: &str, args: &[String]) -> Result<Child, SpawnError> {
-    log::debug!("Spawning: {} {:?}", program, args);
-    cmd.spawn()
-        .map_err(|e| SpawnError::Spawn(format!("{}: {} {:?}", e, program, args)))
+fn spawn_command(
+    mut cmd: Command,
+    command: &str,
+    working_directory: &Option<PathBuf>,
+) -> Result<Child, SpawnError> {
+    log::debug!("Spawning: {}", command);
+    cmd.spawn().map_err(|source| SpawnError::Exec {
+        phase: ExecPhase::Start,
+        command: command.to_string(),
+        working_directory: working_directory.clone(),
+        source,
+    })
 }
 
 struct SocketActivation {
@@ -171,8 +216,20 @@ fn build_service_environment(
     env.extend(service.service.environment.clone());
 
     for env_file in &service.service.environment_file {
-        if let Ok(vars) = load_env_file(env_file) {
-            env.extend(vars);
+        // "-" prefix: silently skip files that don't exist
+        let env_file = env_file.to_string_lossy();
+        let (optional, pattern) = match env_file.strip_prefix('-') {
+            Some(pattern) => (true, pattern),
+            None => (false, env_file.as_ref()),
+        };
+        for path in crate::units::expand_path_glob(pattern) {
+            match load_env_file(&path) {
+                Ok(vars) => env.extend(vars),
+                Err(e) if !optional => {
+                    log::warn!("Failed to read EnvironmentFile={}: {}", path.display(), e);
+                }
+                Err(_) => {}
+            }
         }
     }
 
@@ -182,26 +239,72 @@ fn build_service_environment(
     if let Some(usec) = options.watchdog_usec {
         env.insert("WATCHDOG_USEC".to_string(), usec.to_string());
     }
+    if let Some(peer) = options.remote_address {
+        let addr = peer.ip().to_canonical().to_string();
+        env.insert("REMOTE_ADDR".to_string(), addr);
+        env.insert("REMOTE_PORT".to_string(), peer.port().to_string());
+    }
+    if let Some(dir) = &options.credentials_directory {
+        env.insert(
+            "CREDENTIALS_DIRECTORY".to_string(),
+            dir.to_string_lossy().into_owned(),
+        );
+    }
 
     env
 }
 
+/// Complete environment of a service process, lowest precedence first:
+/// 1. the manager's process environment: all of it when `inherit_environment`
+///    is set (user manager), otherwise DEFAULT_PATH plus PassEnvironment=
+/// 2. the manager environment block (import-environment, set-environment)
+/// 3. Environment=, then EnvironmentFile=, then NOTIFY_SOCKET/WATCHDOG_USEC
+///
+/// UnsetEnvironment= is applied after all of these when the process starts.
+fn build_full_environment(service: &Service, options: &SpawnOptions) -> HashMap<String, String> {
+    let mut environment: HashMap<String, String> = if options.inherit_environment {
+        std::env::vars().collect()
+    } else {
+        let mut base = HashMap::from([("PATH".to_string(), DEFAULT_PATH.to_string())]);
+        for name in &service.service.pass_environment {
+            match std::env::var(name) {
+                Ok(value) => {
+                    base.insert(name.clone(), value);
+                }
+                Err(_) => log::debug!("{}: PassEnvironment={} is not set", service.name, name),
+            }
+        }
+        base
+    };
+    environment.extend(options.user_environment.clone());
+    environment.extend(build_service_environment(service, options));
+    environment
+}
+
+/// Variables to remove from the inherited environment so that only
+/// `environment` remains, followed by UnsetEnvironment=
+fn environment_unset_list(environment: &HashMap<String, String>, service: &Service) -> Vec<String> {
+    let mut unset: Vec<String> = std::env::vars()
+        .map(|(key, _)| key)
+        .filter(|key| !environment.contains_key(key))
+        .collect();
+    unset.extend(service.service.unset_environment.iter().cloned());
+    unset
+}
+
 fn configure_direct_environment(
     cmd: &mut Command,
-    user_env: &HashMap<String, String>,
-    extra_env: &HashMap<String, String>,
+    environment: &HashMap<String, String>,
     unset_vars: &[String],
 ) {
     cmd.env_clear();
-    cmd.envs(std::env::vars());
-    cmd.envs(user_env);
-    cmd.envs(extra_env);
+    cmd.envs(environment);
     for var in unset_vars {
         cmd.env_remove(var);
     }
 }
 
-fn resolve_uid_gid(service: &Service, options: &SpawnOptions) -> (Option<u32>, Option<u32>) {
+pub fn resolve_uid_gid(service: &Service, options: &SpawnOptions) -> (Option<u32>, Option<u32>) {
     let uid = options
         .dynamic_uid
         .or_else(|| service.service.user.as_ref().and_then(|u| resolve_user(u)));
@@ -229,21 +332,28 @@ struct PreExecContext {
     uid: Option<u32>,
     gid: Option<u32>,
     tty_path: Option<std::path::PathBuf>,
-    tty_reset: bool,
+    tty_options: TtyOptions,
     std_input: StdInput,
+    profile_fd: Option<RawFd>,
 }
 
 #[cfg(unix)]
 fn run_pre_exec(ctx: &PreExecContext) -> std::io::Result<()> {
+    mark_spawn_step(ctx.profile_fd, SpawnStep::Forked);
     if !ctx.socket_fds.is_empty() {
         apply_pre_exec_socket_activation(ctx)?;
     }
 
     apply_resource_limits(ctx.limit_nofile, ctx.limit_nproc, ctx.limit_core);
     apply_oom_score_adjust(ctx.oom_score_adjust);
+    // Before dropping privileges: stealing a terminal and vhangup need root
+    setup_tty(&ctx.std_input, ctx.tty_path.as_deref(), ctx.tty_options)?;
+    mark_spawn_step(ctx.profile_fd, SpawnStep::Setup);
     apply_sandbox(&ctx.service_section);
+    mark_spawn_step(ctx.profile_fd, SpawnStep::Sandbox);
     drop_privileges(ctx.gid, ctx.uid)?;
-    setup_tty(&ctx.std_input, ctx.tty_path.as_deref(), ctx.tty_reset)?;
+    apply_keyring_mode(&ctx.service_section.keyring_mode);
+    mark_spawn_step(ctx.profile_fd, SpawnStep::Credentials);
     Ok(())
 }
 
@@ -367,6 +477,13 @@ fn apply_sandbox(service_section: &crate::units::ServiceSection) {
     }
 }
 
+#[cfg(unix)]
+fn apply_keyring_mode(mode: &crate::units::KeyringMode) {
+    if let Err(e) = crate::manager::sandbox::apply_keyring_mode(mode) {
+        log::warn!("Keyring setup failed: {}", e);
+    }
+}
+
 #[cfg(unix)]
 fn drop_privileges(gid: Option<u32>, uid: Option<u32>) -> std::io::Result<()> {
     if let Some(gid) = gid {
@@ -380,38 +497,33 @@ fn drop_privileges(gid: Option<u32>, uid: Option<u32>) -> std::io::Result<()> {
     Ok(())
 }
 
+/// TTYReset=, TTYVHangup= and TTYVTDisallocate= of a service
+pub(crate) fn tty_options(service: &crate::units::ServiceSection) -> TtyOptions {
+    TtyOptions {
+        reset: service.tty_reset,
+        vhangup: service.tty_vhangup,
+        vt_disallocate: service.tty_vt_disallocate,
+    }
+}
+
 #[cfg(unix)]
 fn setup_tty(
     std_input: &StdInput,
     tty_path: Option<&std::path::Path>,
-    tty_reset: bool,
+    options: TtyOptions,
 ) -> std::io::Result<()> {
-    if !matches!(std_input, StdInput::Tty | StdInput::TtyForce | StdInput::TtyFail) {
-        return Ok(());
-    }
+    let acquire = match std_input {
+        StdInput::Null => return Ok(()),
+        StdInput::Tty => TtyAcquire::Try,
+        StdInput::TtyForce => TtyAcquire::Force,
+        StdInput::TtyFail => TtyAcquire::Fail,
+    };
     let Some(path) = tty_path else {
         return Ok(());
     };
-    if tty_reset {
-        let _ = std::fs::OpenOptions::new().read(true).write(true).open(path);
-    }
-    attach_controlling_tty(path, std_input)
-}
-
-#[cfg(unix)]
-fn attach_controlling_tty(path: &std::path::Path, std_input: &StdInput) -> std::io::Result<()> {
-    let file = std::fs::OpenOptions::new().read(true).write(true).open(path);
-    match file {
-        Ok(f) => {
-            let fd = f.as_raw_fd();
-            if unsafe { libc::ioctl(fd, libc::TIOCSCTTY, 0) } < 0 && matches!(std_input, StdInput::TtyFail) {
-                return Err(std::io::Error::last_os_error());
-            }
-            duplicate_tty_fds(fd);
-            std::mem::forget(f);
-            Ok(())
-        }
-        Err(e) if matches!(std_input, StdInput::TtyFail) => Err(e),
+    match crate::tty::attach(path, acquire, options) {
+        Ok(()) => Ok(()),
+        Err(e) if acquire == TtyAcquire::Fail => Err(e),
         Err(e) => {
             log::warn!("Failed to open TTY {:?}: {}", path, e);
             Ok(())
@@ -419,18 +531,6 @@ fn attach_controlling_tty(path: &std::path::Path, std_input: &StdInput) -> std::
     }
 }
 
-#[cfg(unix)]
-fn duplicate_tty_fds(fd: RawFd) {
-    unsafe {
-        libc::dup2(fd, 0);
-        libc::dup2(fd, 1);
-        libc::dup2(fd, 2);
-        if fd > 2 {
-            libc::close(fd);
-        }
-    }
-}
-
 /// Parse a command line into program and arguments
 fn parse_command(cmd: &str) -> Result<(String, Vec<String>), SpawnError> {
     // Handle special prefixes (-, @, +, !, !!)
@@ -599,9 +699,8 @@ fn create_service_directory(
     gid: Option<u32>,
 ) -> std::io::Result<()> {
     use std::os::unix::fs::{chown, PermissionsExt};
-    use std::path::Path;
 
-    let path = Path::new(base).join(name);
+    let path = crate::root::path(base).join(name);
     std::fs::create_dir_all(&path)?;
     if uid.is_some() || gid.is_some() {
         chown(&path, uid, gid)?;
@@ -611,6 +710,39 @@ fn create_service_directory(
     Ok(())
 }
 
+/// Which of a service's command lists a command belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecPhase {
+    Condition,
+    Pre,
+    Start,
+    Post,
+    Reload,
+    Stop,
+    StopPost,
+}
+
+impl ExecPhase {
+    /// Unit file directive listing the command (e.g. "ExecStartPre")
+    pub fn directive(self) -> &'static str {
+        match self {
+            Self::Condition => "ExecCondition",
+            Self::Pre => "ExecStartPre",
+            Self::Start => "ExecStart",
+            Self::Post => "ExecStartPost",
+            Self::Reload => "ExecReload",
+            Self::Stop => "ExecStop",
+            Self::StopPost => "ExecStopPost",
+        }
+    }
+}
+
+impl std::fmt::Display for ExecPhase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.directive())
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum SpawnError {
     #[error("Service {0} has no ExecStart")]
@@ -619,8 +751,50 @@ pub enum SpawnError {
     #[error("Invalid command: {0}")]
     InvalidCommand(String),
 
-    #[error("Failed to spawn process: {0}")]
-    Spawn(String),
+    #[error("Failed to execute {phase}={command}{}: {source}", in_directory(.working_directory))]
+    Exec {
+        phase: ExecPhase,
+        command: String,
+        working_directory: Option<PathBuf>,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to spawn executor {executor} for {phase}={command}: {source}")]
+    Executor {
+        executor: String,
+        phase: ExecPhase,
+        command: String,
+        source: std::io::Error,
+    },
+
+    #[error("Failed to pass configuration to the executor: {0}")]
+    Serialize(String),
+}
+
+impl SpawnError {
+    /// errno of the failed fork/exec, if the error came from the OS
+    pub fn os_error(&self) -> Option<i32> {
+        match self {
+            Self::Exec { source, .. } | Self::Executor { source, .. } => source.raw_os_error(),
+            _ => None,
+        }
+    }
+
+    /// Command list the failing command came from
+    pub fn phase(&self) -> Option<ExecPhase> {
+        match self {
+            Self::Exec { phase, .. } | Self::Executor { phase, .. } => Some(*phase),
+            Self::NoExecStart(_) => Some(ExecPhase::Start),
+            _ => None,
+        }
+    }
+}
+
+fn in_directory(working_directory: &Option<PathBuf>) -> String {
+    working_directory
+        .as_ref()
+        .map(|dir| format!(" in {}", dir.display()))
+        .unwrap_or_default()
 }
 
 // ============================================================================
@@ -628,6 +802,6 @@ pub enum SpawnError {
 // ============================================================================
 
 use crate::executor::{
-    DevicePolicyConfig, ExecConfig, ProtectHomeConfig, ProtectProcConfig, ProtectSystemConfig,
-    SandboxConfig, StdInputConfig,
+    DevicePolicyConfig, ExecConfig, KeyringModeConfig, MacLabelConfig, ProtectHomeConfig,
+    ProtectProcConfig, ProtectSystemConfig, SandboxConfig, StdInputConfig,
 };
@@ -114,6 +114,8 @@ fn service_environment_merges_direct_files_and_notify_settings() {
     let options = SpawnOptions {
         notify_socket: Some("/run/sysd/notify.sock".to_string()),
         watchdog_usec: Some(5_000_000),
+        credentials_directory: Some(PathBuf::from("/run/credentials/env.service")),
+        remote_address: Some("[::ffff:192.0.2.7]:40022".parse().unwrap()),
         ..Default::default()
     };
 
@@ -128,6 +130,83 @@ fn service_environment_merges_direct_files_and_notify_settings() {
         Some("/run/sysd/notify.sock")
     );
     assert_eq!(env.get("WATCHDOG_USEC").map(String::as_str), Some("5000000"));
+    assert_eq!(
+        env.get("CREDENTIALS_DIRECTORY").map(String::as_str),
+        Some("/run/credentials/env.service")
+    );
+    assert_eq!(
+        env.get("REMOTE_ADDR").map(String::as_str),
+        Some("192.0.2.7")
+    );
+    assert_eq!(env.get("REMOTE_PORT").map(String::as_str), Some("40022"));
+}
+
+#[test]
+fn service_environment_expands_globs_and_optional_files() {
+    let root = temp_dir("env-glob");
+    let dir = root.0.join("env.d");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("10-a.env"), "ORDER=a\nA=1\n").unwrap();
+    std::fs::write(dir.join("20-b.env"), "ORDER=b\nB=2\n").unwrap();
+    std::fs::write(dir.join("ignored.txt"), "IGNORED=1\n").unwrap();
+
+    let mut service = service("env-glob.service");
+    service.service.environment_file = vec![
+        PathBuf::from(format!("{}/*.env", dir.display())),
+        PathBuf::from(format!("-{}/missing.env", root.0.display())),
+    ];
+
+    let env = build_service_environment(&service, &SpawnOptions::default());
+
+    assert_eq!(env.get("A").map(String::as_str), Some("1"));
+    assert_eq!(env.get("B").map(String::as_str), Some("2"));
+    // Matches are loaded in sorted order, later files win
+    assert_eq!(env.get("ORDER").map(String::as_str), Some("b"));
+    assert!(!env.contains_key("IGNORED"));
+}
+
+#[test]
+fn full_environment_passes_only_listed_variables_for_system_services() {
+    let passed = unique_name("PASSED");
+    let hidden = unique_name("HIDDEN");
+    unsafe {
+        std::env::set_var(&passed, "manager");
+        std::env::set_var(&hidden, "manager");
+    }
+
+    let mut service = service("pass-env.service");
+    service.service.pass_environment = vec![passed.clone(), unique_name("UNSET")];
+    service.service.environment = vec![("BLOCK".to_string(), "unit".to_string())];
+    let mut options = SpawnOptions {
+        user_environment: HashMap::from([
+            ("BLOCK".to_string(), "manager-block".to_string()),
+            ("PATH".to_string(), "/opt/bin".to_string()),
+        ]),
+        ..Default::default()
+    };
+
+    let env = build_full_environment(&service, &options);
+    assert_eq!(env.get(&passed).map(String::as_str), Some("manager"));
+    assert!(!env.contains_key(&hidden));
+    assert_eq!(env.get("BLOCK").map(String::as_str), Some("unit"));
+    assert_eq!(env.get("PATH").map(String::as_str), Some("/opt/bin"));
+    assert!(environment_unset_list(&env, &service).contains(&hidden));
+
+    options.user_environment.clear();
+    assert_eq!(
+        build_full_environment(&service, &options)
+            .get("PATH")
+            .map(String::as_str),
+        Some(DEFAULT_PATH)
+    );
+
+    options.inherit_environment = true;
+    let env = build_full_environment(&service, &options);
+    assert_eq!(env.get(&hidden).map(String::as_str), Some("manager"));
+    unsafe {
+        std::env::remove_var(&passed);
+        std::env::remove_var(&hidden);
+    }
 }
 
 #[test]
@@ -186,11 +265,19 @@ fn spawn_service_reports_missing_exec_and_spawn_failures() {
     missing_binary.service.exec_start =
         vec!["/definitely/not/a/sysd-test-binary".to_string()];
 
+    missing_binary.service.working_directory = Some(PathBuf::from("/"));
+
+    let error = spawn_service_with_options(&missing_binary, &SpawnOptions::default()).unwrap_err();
+    assert_eq!(error.os_error(), Some(libc::ENOENT));
+    assert_eq!(error.phase(), Some(ExecPhase::Start));
     assert!(matches!(
-        spawn_service_with_options(&missing_binary, &SpawnOptions::default()),
-        Err(SpawnError::Spawn(message)) if message.contains("No such file")
-            || message.contains("os error 2")
+        &error,
+        SpawnError::Exec { command, working_directory: Some(dir), .. }
+            if command == "/definitely/not/a/sysd-test-binary" && dir == Path::new("/")
     ));
+    assert!(error
+        .to_string()
+        .starts_with("Failed to execute ExecStart=/definitely/not/a/sysd-test-binary in /: "));
 }
 
 #[tokio::test]
@@ -361,11 +448,15 @@ fn directory_helpers_create_default_and_named_paths_with_permissions() {
 #[test]
 fn tty_setup_ignores_non_tty_and_reports_tty_fail_open_errors() {
     let missing = std::env::temp_dir().join(unique_name("missing-tty"));
+    let reset = TtyOptions {
+        reset: true,
+        ..TtyOptions::default()
+    };
 
-    assert!(setup_tty(&StdInput::Null, Some(&missing), true).is_ok());
-    assert!(setup_tty(&StdInput::Tty, Some(&missing), false).is_ok());
-    assert!(setup_tty(&StdInput::TtyFail, Some(&missing), false).is_err());
-    assert!(setup_tty(&StdInput::TtyForce, None, true).is_ok());
+    assert!(setup_tty(&StdInput::Null, Some(&missing), reset).is_ok());
+    assert!(setup_tty(&StdInput::Tty, Some(&missing), TtyOptions::default()).is_ok());
+    assert!(setup_tty(&StdInput::TtyFail, Some(&missing), TtyOptions::default()).is_err());
+    assert!(setup_tty(&StdInput::TtyForce, None, reset).is_ok());
 }
 
 #[test]
@@ -15,7 +15,7 @@ pub fn build_exec_config(
     let (program, args) = parse_command(&exec_start)?;
 
     let (uid, gid) = resolve_uid_gid(service, options);
-    let environment = build_exec_environment(service, options);
+    let environment = build_full_environment(service, options);
     let socket_activation = build_socket_activation(options);
 
     log::debug!(
@@ -28,7 +28,7 @@ pub fn build_exec_config(
 
     let sandbox = build_sandbox_config(&service.service);
     let std_input = map_std_input(service.service.standard_input.clone());
-    Ok(build_exec_config_output(
+    let mut config = build_exec_config_output(
         service,
         program,
         args,
@@ -38,7 +38,10 @@ pub fn build_exec_config(
         socket_activation,
         std_input,
         sandbox,
-    ))
+    );
+    config.profile_fd = options.profile_fd;
+    config.exec_gate_fd = options.exec_gate_fd;
+    Ok(config)
 }
 
 fn build_exec_config_output(
@@ -52,12 +55,13 @@ fn build_exec_config_output(
     std_input: StdInputConfig,
     sandbox: SandboxConfig,
 ) -> ExecConfig {
+    let unset_environment = environment_unset_list(&environment, service);
     ExecConfig {
         program,
         args,
         working_directory: service.service.working_directory.clone(),
         environment,
-        unset_environment: service.service.unset_environment.clone(),
+        unset_environment,
         uid,
         gid,
         limit_nofile: service.service.limit_nofile,
@@ -69,17 +73,14 @@ fn build_exec_config_output(
         std_input,
         tty_path: service.service.tty_path.clone(),
         tty_reset: service.service.tty_reset,
+        tty_vhangup: service.service.tty_vhangup,
+        tty_vt_disallocate: service.service.tty_vt_disallocate,
         sandbox,
+        profile_fd: None,
+        exec_gate_fd: None,
     }
 }
 
-fn build_exec_environment(service: &Service, options: &SpawnOptions) -> HashMap<String, String> {
-    let mut environment: HashMap<String, String> = std::env::vars().collect();
-    environment.extend(options.user_environment.clone());
-    environment.extend(build_service_environment(service, options));
-    environment
-}
-
 fn map_std_input(std_input: StdInput) -> StdInputConfig {
     match std_input {
         StdInput::Null => StdInputConfig::Null,
@@ -104,10 +105,18 @@ fn fill_sandbox_basic_fields(sandbox: &mut SandboxConfig, service: &crate::units
     sandbox.private_tmp = service.private_tmp;
     sandbox.private_devices = service.private_devices;
     sandbox.private_network = service.private_network;
+    sandbox.private_pids = service.private_pids;
+    sandbox.private_ipc = service.private_ipc;
+    sandbox.network_namespace_path = service.network_namespace_path.clone();
     sandbox.protect_kernel_modules = service.protect_kernel_modules;
     sandbox.protect_proc = map_protect_proc(&service.protect_proc);
+    sandbox.selinux_context = service.selinux_context.as_ref().map(map_mac_label);
+    sandbox.apparmor_profile = service.apparmor_profile.as_ref().map(map_mac_label);
+    sandbox.smack_process_label = service.smack_process_label.as_ref().map(map_mac_label);
     sandbox.capability_bounding_set = service.capability_bounding_set.clone();
     sandbox.ambient_capabilities = service.ambient_capabilities.clone();
+    sandbox.secure_bits = service.secure_bits;
+    sandbox.keyring_mode = map_keyring_mode(&service.keyring_mode);
     sandbox.restrict_namespaces = service.restrict_namespaces.clone();
     sandbox.device_policy = map_device_policy(&service.device_policy);
     sandbox.device_allow = service.device_allow.clone();
@@ -166,6 +175,21 @@ fn map_protect_proc(mode: &crate::units::ProtectProc) -> ProtectProcConfig {
     }
 }
 
+fn map_keyring_mode(mode: &crate::units::KeyringMode) -> KeyringModeConfig {
+    match mode {
+        crate::units::KeyringMode::Inherit => KeyringModeConfig::Inherit,
+        crate::units::KeyringMode::Private => KeyringModeConfig::Private,
+        crate::units::KeyringMode::Shared => KeyringModeConfig::Shared,
+    }
+}
+
+fn map_mac_label(label: &crate::units::MacLabel) -> MacLabelConfig {
+    MacLabelConfig {
+        label: label.label.clone(),
+        ignore_failure: label.ignore_failure,
+    }
+}
+
 fn map_device_policy(policy: &crate::units::DevicePolicy) -> DevicePolicyConfig {
     match policy {
         crate::units::DevicePolicy::Auto => DevicePolicyConfig::Auto,
@@ -193,15 +217,20 @@ pub fn spawn_service_via_executor(
 
     let config = build_exec_config(service, options, command_index)?;
     create_service_directories(&service.service, &service.name, config.uid, config.gid)?;
-    let memfd = crate::executor::serialize_to_memfd(&config)
-        .map_err(|e| SpawnError::Spawn(format!("Failed to serialize config: {}", e)))?;
+    let memfd = crate::executor::serialize_to_memfd(&config).map_err(SpawnError::Serialize)?;
     log::debug!("{}: memfd created at fd {}", service.name, memfd);
 
     let all_fds = build_socket_activation(options).fds;
     let mut cmd = Command::new(executor_path);
     cmd.arg(format!("--deserialize={}", memfd));
     configure_executor_stdio(&mut cmd, &service.service.standard_input);
-    configure_executor_pre_exec(&mut cmd, all_fds, memfd);
+    configure_executor_pre_exec(
+        &mut cmd,
+        all_fds,
+        memfd,
+        options.profile_fd,
+        options.exec_gate_fd,
+    );
 
     log::debug!(
         "Spawning via executor: {} -> {} {}",
@@ -210,9 +239,12 @@ pub fn spawn_service_via_executor(
         config.args.join(" ")
     );
 
-    let result = cmd
-        .spawn()
-        .map_err(|e| SpawnError::Spawn(format!("Failed to spawn executor: {}", e)));
+    let result = cmd.spawn().map_err(|source| SpawnError::Executor {
+        executor: executor_path.to_string(),
+        phase: ExecPhase::Start,
+        command: service.service.exec_start[command_index].clone(),
+        source,
+    });
 
     // Close memfd in parent - child has its own copy after fork
     // This prevents FD leak on repeated spawns (especially during service restarts)
@@ -232,16 +264,36 @@ fn configure_executor_stdio(cmd: &mut Command, std_input: &StdInput) {
     cmd.stderr(Stdio::inherit());
 }
 
-fn configure_executor_pre_exec(cmd: &mut Command, all_fds: Vec<RawFd>, memfd: RawFd) {
+fn configure_executor_pre_exec(
+    cmd: &mut Command,
+    all_fds: Vec<RawFd>,
+    memfd: RawFd,
+    profile_fd: Option<RawFd>,
+    exec_gate_fd: Option<RawFd>,
+) {
     #[cfg(unix)]
     unsafe {
-        cmd.pre_exec(move || prepare_executor_child_fds(&all_fds, memfd));
+        cmd.pre_exec(move || prepare_executor_child_fds(&all_fds, memfd, profile_fd, exec_gate_fd));
     }
 }
 
 #[cfg(unix)]
-fn prepare_executor_child_fds(all_fds: &[RawFd], memfd: RawFd) -> std::io::Result<()> {
+fn prepare_executor_child_fds(
+    all_fds: &[RawFd],
+    memfd: RawFd,
+    profile_fd: Option<RawFd>,
+    exec_gate_fd: Option<RawFd>,
+) -> std::io::Result<()> {
+    mark_spawn_step(profile_fd, SpawnStep::Forked);
     map_socket_fds(all_fds)?;
     clear_cloexec(memfd);
+    // sysd-executor sets it again before exec'ing the service
+    if let Some(fd) = profile_fd {
+        clear_cloexec(fd);
+    }
+    // sysd-executor closes it once it got through
+    if let Some(fd) = exec_gate_fd {
+        clear_cloexec(fd);
+    }
     Ok(())
 }
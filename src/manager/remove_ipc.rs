@@ -0,0 +1,218 @@
+//! RemoveIPC=: drop the IPC objects of a service user once its units are gone
+//!
+//! When a service with RemoveIPC=yes (implied by DynamicUser=yes) stops and
+//! no other running unit uses its user or group, the System V message
+//! queues, semaphore sets and shared memory segments owned by them are
+//! removed, as are their POSIX shared memory objects (/dev/shm) and message
+//! queues (/dev/mqueue). root's objects are never touched.
+
+use std::path::Path;
+
+use crate::units::Service;
+
+use super::{process, Manager, SpawnOptions};
+
+/// The System V IPC tables, with the column of their ids and how to remove one
+const SYSV_TABLES: [(&str, &str, fn(libc::c_int)); 3] = [
+    ("/proc/sysvipc/msg", "msqid", remove_message_queue),
+    ("/proc/sysvipc/sem", "semid", remove_semaphore_set),
+    ("/proc/sysvipc/shm", "shmid", remove_shared_memory),
+];
+
+/// Where POSIX shared memory and message queues live
+const POSIX_IPC_DIRS: [&str; 2] = ["/dev/shm", "/dev/mqueue"];
+
+impl Manager {
+    /// Remove the IPC objects of the user and group the stopped service
+    /// `name` ran as, if it asks for that and nothing else runs as them
+    pub(super) fn remove_ipc_after_stop(&self, name: &str) {
+        let Some(service) = self.units.get(name).and_then(|unit| unit.as_service()) else {
+            return;
+        };
+        if !service.service.remove_ipc && !service.service.dynamic_user {
+            return;
+        }
+        let (uid, gid) = self.service_credentials(name, service);
+        let owner = IpcOwner {
+            uid: uid.filter(|uid| *uid != 0),
+            gid: gid.filter(|gid| *gid != 0),
+        };
+        if owner.uid.is_none() && owner.gid.is_none() {
+            return;
+        }
+        if let Some(other) = self.unit_running_as(name, &owner) {
+            log::debug!(
+                "{}: not removing IPC objects, {} still runs as its user",
+                name,
+                other
+            );
+            return;
+        }
+        let mut removed = 0;
+        for (table, id_column, remove) in SYSV_TABLES {
+            let Ok(contents) = std::fs::read_to_string(table) else {
+                continue;
+            };
+            for id in owned_sysv_ids(&contents, id_column, &owner) {
+                remove(id);
+                removed += 1;
+            }
+        }
+        for dir in POSIX_IPC_DIRS {
+            removed += remove_owned_entries(Path::new(dir), &owner);
+        }
+        if removed > 0 {
+            log::info!("{}: removed {} IPC objects of its user", name, removed);
+        }
+    }
+
+    /// User and group the service `name` runs (or ran) as
+    fn service_credentials(&self, name: &str, service: &Service) -> (Option<u32>, Option<u32>) {
+        let dynamic = self.dynamic_uids.get(name).copied();
+        let options = SpawnOptions {
+            dynamic_uid: dynamic,
+            dynamic_gid: dynamic,
+            ..Default::default()
+        };
+        process::resolve_uid_gid(service, &options)
+    }
+
+    /// Another running service with the user or group of `owner`
+    fn unit_running_as(&self, name: &str, owner: &IpcOwner) -> Option<&str> {
+        self.units.iter().find_map(|(other, unit)| {
+            let service = unit.as_service()?;
+            let running = self
+                .states
+                .get(other)
+                .is_some_and(|state| state.is_active());
+            if other == name || !running {
+                return None;
+            }
+            let (uid, gid) = self.service_credentials(other, service);
+            let shared = (uid.is_some() && uid == owner.uid) || (gid.is_some() && gid == owner.gid);
+            shared.then_some(other.as_str())
+        })
+    }
+}
+
+/// Whose IPC objects go (None: nobody's by that id)
+struct IpcOwner {
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+impl IpcOwner {
+    fn owns(&self, uid: u32, gid: u32) -> bool {
+        self.uid == Some(uid) || self.gid == Some(gid)
+    }
+}
+
+/// Ids in a /proc/sysvipc table whose owner is `owner`
+fn owned_sysv_ids(table: &str, id_column: &str, owner: &IpcOwner) -> Vec<libc::c_int> {
+    let mut lines = table.lines();
+    let Some(header) = lines.next() else {
+        return Vec::new();
+    };
+    let column = |name: &str| header.split_whitespace().position(|field| field == name);
+    let (Some(id), Some(uid), Some(gid)) = (column(id_column), column("uid"), column("gid")) else {
+        return Vec::new();
+    };
+    lines
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let field = |index: usize| fields.get(index)?.parse::<i64>().ok();
+            let owned = owner.owns(field(uid)? as u32, field(gid)? as u32);
+            owned.then_some(field(id)? as libc::c_int)
+        })
+        .collect()
+}
+
+fn remove_message_queue(id: libc::c_int) {
+    unsafe { libc::msgctl(id, libc::IPC_RMID, std::ptr::null_mut()) };
+}
+
+fn remove_semaphore_set(id: libc::c_int) {
+    unsafe { libc::semctl(id, 0, libc::IPC_RMID) };
+}
+
+fn remove_shared_memory(id: libc::c_int) {
+    unsafe { libc::shmctl(id, libc::IPC_RMID, std::ptr::null_mut()) };
+}
+
+/// Remove the entries of `dir` owned by `owner` (directories with all they
+/// hold), returning how many went
+fn remove_owned_entries(dir: &Path, owner: &IpcOwner) -> usize {
+    use std::os::unix::fs::MetadataExt;
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for entry in entries.flatten() {
+        let Ok(metadata) = entry.path().symlink_metadata() else {
+            continue;
+        };
+        if !owner.owns(metadata.uid(), metadata.gid()) {
+            continue;
+        }
+        let result = if metadata.is_dir() {
+            std::fs::remove_dir_all(entry.path())
+        } else {
+            std::fs::remove_file(entry.path())
+        };
+        match result {
+            Ok(()) => removed += 1,
+            Err(e) => log::debug!("Failed to remove {}: {}", entry.path().display(), e),
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::MetadataExt;
+
+    #[test]
+    fn sysv_ids_are_picked_by_owner_from_the_table_header() {
+        let shm =
+            "       key      shmid perms       size  cpid  lpid nattch   uid   gid  cuid  cgid\n\
+                   0 32769  1600  4096 10 11 0  1000  1000  1000  1000\n\
+                   0 32770  1600  4096 10 11 0  0  0  0  0\n\
+                   0 32771  1600  4096 10 11 0  1001  1000  1001  1001\n";
+        let owner = IpcOwner {
+            uid: Some(1000),
+            gid: None,
+        };
+        assert_eq!(owned_sysv_ids(shm, "shmid", &owner), [32769]);
+        let owner = IpcOwner {
+            uid: None,
+            gid: Some(1000),
+        };
+        assert_eq!(owned_sysv_ids(shm, "shmid", &owner), [32769, 32771]);
+        assert!(owned_sysv_ids(shm, "semid", &owner).is_empty());
+        assert!(owned_sysv_ids("", "shmid", &owner).is_empty());
+    }
+
+    #[test]
+    fn only_entries_of_the_owner_are_removed() {
+        let dir = std::env::temp_dir().join(format!("sysd-remove-ipc-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sem.dir")).unwrap();
+        std::fs::write(dir.join("shm.segment"), b"x").unwrap();
+        std::fs::write(dir.join("sem.dir/inner"), b"x").unwrap();
+        let metadata = dir.symlink_metadata().unwrap();
+
+        let stranger = IpcOwner {
+            uid: Some(metadata.uid() + 1),
+            gid: None,
+        };
+        assert_eq!(remove_owned_entries(&dir, &stranger), 0);
+        let owner = IpcOwner {
+            uid: Some(metadata.uid()),
+            gid: None,
+        };
+        assert_eq!(remove_owned_entries(&dir, &owner), 2);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
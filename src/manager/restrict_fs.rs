@@ -0,0 +1,62 @@
+//! RestrictFileSystems= of services
+//!
+//! The BPF LSM program is loaded on the first start of a unit using the
+//! setting and then shared. The cgroup's list is in its map before
+//! sysd-executor passes its exec gate, so the service never opens a file
+//! unrestricted. Without the bpf LSM or kernel BTF (see
+//! `features`) the unit runs unrestricted with a warning; if loading fails
+//! anyway, it is retried at the next start.
+
+use crate::cgroups::FileSystemRestrictor;
+use crate::units::Service;
+
+use super::{Feature, Manager};
+
+impl Manager {
+    /// Put the RestrictFileSystems= list of `service` on its cgroup
+    pub(super) fn apply_restrict_file_systems(&mut self, name: &str, service: &Service) {
+        if let Some(restrictor) = &mut self.file_system_restrictor {
+            restrictor.release(name);
+        }
+        let list = &service.service.restrict_file_systems;
+        if list.is_empty() || !self.can_enforce(name, Feature::BpfLsm, "RestrictFileSystems=") {
+            return;
+        }
+        let Some(cgroup_path) = self.cgroup_paths.get(name) else {
+            log::warn!("{}: no cgroup, RestrictFileSystems= not enforced", name);
+            return;
+        };
+        if self.file_system_restrictor.is_none() {
+            match FileSystemRestrictor::load() {
+                Ok(restrictor) => self.file_system_restrictor = Some(restrictor),
+                Err(e) => {
+                    log::warn!("{}: RestrictFileSystems= not enforced: {}", name, e);
+                    return;
+                }
+            }
+        }
+        let Some(restrictor) = &mut self.file_system_restrictor else {
+            return;
+        };
+        if let Err(e) = restrictor.restrict(name, cgroup_path, list) {
+            log::warn!("{}: RestrictFileSystems= not enforced: {}", name, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{parse_file, parse_service};
+
+    #[test]
+    fn restriction_is_skipped_without_a_cgroup() {
+        let mut manager = Manager::new_user();
+        let parsed =
+            parse_file("[Service]\nExecStart=/bin/true\nRestrictFileSystems=ext4\n").unwrap();
+        let service = parse_service("fs.service", &parsed).unwrap();
+
+        manager.apply_restrict_file_systems("fs.service", &service);
+        assert!(manager.file_system_restrictor.is_none());
+    }
+}
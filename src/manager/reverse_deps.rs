@@ -0,0 +1,74 @@
+//! Units that depend on a unit
+//!
+//! Loaded units go in and out of `self.units` through `insert_unit` and
+//! `remove_unit`, which keep a reverse index (`DepGraph::index_unit`) in
+//! step, so BindsTo=/PartOf= propagation and similar lookups do not scan
+//! every loaded unit.
+
+use crate::units::Unit;
+
+use super::{DependencyType, Manager};
+
+impl Manager {
+    /// Store the loaded `unit` as `name`, replacing a unit loaded under that
+    /// name before
+    pub(super) fn insert_unit(&mut self, name: String, unit: Unit) {
+        self.reverse_deps.index_unit(&name, &unit);
+        self.units.insert(name, unit);
+    }
+
+    /// Forget the loaded unit `name`
+    pub(super) fn remove_unit(&mut self, name: &str) -> Option<Unit> {
+        self.reverse_deps.unindex_unit(name);
+        self.units.remove(name)
+    }
+
+    /// Update the index after the loaded unit `name` changed in place
+    pub(super) fn reindex_unit(&mut self, name: &str) {
+        if let Some(unit) = self.units.get(name) {
+            self.reverse_deps.index_unit(name, unit);
+        }
+    }
+
+    /// Loaded units that depend on `name` (any of its names) as `kind`,
+    /// sorted by name
+    pub fn dependents(&self, name: &str, kind: DependencyType) -> Vec<String> {
+        let name = self.normalize_name(name);
+        self.reverse_deps.dependents(&name, kind).cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Service;
+
+    #[test]
+    fn dependents_follow_inserted_replaced_and_removed_units() {
+        let mut manager = Manager::new_user();
+        let mut web = Service::new("web.service".to_string());
+        web.unit.requires = vec!["db.service".to_string()];
+        web.unit.binds_to = vec!["db.service".to_string()];
+        manager.insert_unit("web.service".to_string(), Unit::Service(web));
+        assert_eq!(
+            manager.dependents("db", DependencyType::BindsTo),
+            ["web.service"]
+        );
+
+        let mut web = Service::new("web.service".to_string());
+        web.unit.wants = vec!["db.service".to_string()];
+        manager.insert_unit("web.service".to_string(), Unit::Service(web));
+        assert!(manager
+            .dependents("db.service", DependencyType::BindsTo)
+            .is_empty());
+        assert_eq!(
+            manager.dependents("db.service", DependencyType::Wants),
+            ["web.service"]
+        );
+
+        assert!(manager.remove_unit("web.service").is_some());
+        assert!(manager
+            .dependents("db.service", DependencyType::Wants)
+            .is_empty());
+    }
+}
@@ -0,0 +1,296 @@
+//! Blue/green restarts of socket-activated services (`restart --rolling`,
+//! RollingRestart=, a sysd extension)
+//!
+//! A plain restart leaves a gap between the old process going away and the
+//! new one taking over the listening sockets. A rolling restart starts the
+//! replacement first, with the same listener FDs, and lets the old main
+//! process keep serving until the new one is ready: READY=1 for
+//! Type=notify, spawned for Type=simple. Only then does the old process get
+//! SIGTERM, and SIGKILL after TimeoutStopSec=; its ExecStop= is not run. If
+//! the new instance fails or is not ready within TimeoutStartSec=, it is
+//! killed and the old process stays the main process.
+
+use std::time::{Duration, Instant};
+
+use tokio::process::Child;
+
+use crate::units::{ServiceType, Unit};
+
+use super::{ActiveState, Manager, ManagerError, SubState};
+
+/// TimeoutStartSec= default, as in systemd
+const DEFAULT_START_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// The main process being replaced
+#[derive(Debug)]
+pub(crate) struct OldInstance {
+    child: Child,
+    pid: u32,
+    /// When the replacement must be ready
+    deadline: Instant,
+}
+
+impl Manager {
+    /// Start a second instance of running service `name` next to the current
+    /// one; `process_rolling_restarts` stops the old one once the new one is
+    /// ready
+    pub async fn rolling_restart(&mut self, name: &str) -> Result<(), ManagerError> {
+        let name = self.normalize_name(name);
+        let service = self
+            .units
+            .get(&name)
+            .and_then(Unit::as_service)
+            .cloned()
+            .ok_or_else(|| ManagerError::NotAService(name.clone()))?;
+        let pid = self
+            .processes
+            .get(&name)
+            .and_then(Child::id)
+            .filter(|_| self.is_running(&name))
+            .ok_or_else(|| ManagerError::NotActive(name.clone()))?;
+        let rollable = matches!(
+            service.service.service_type,
+            ServiceType::Simple | ServiceType::Notify
+        ) && !self.get_socket_fds(&name).is_empty();
+        if !rollable || self.rolling_restarts.contains_key(&name) {
+            return Err(ManagerError::NotRollable(name));
+        }
+
+        let Some(child) = self.processes.remove(&name) else {
+            return Err(ManagerError::NotActive(name));
+        };
+        self.pid_to_service.remove(&pid);
+        self.watchdog_deadlines.remove(&name);
+        if let Some(state) = self.states.get_mut(&name) {
+            state.clear_restart();
+        }
+        log::info!("Rolling restart of {}: starting next to PID {}", name, pid);
+        let start_timeout = service
+            .service
+            .timeout_start_sec
+            .unwrap_or(DEFAULT_START_TIMEOUT);
+        let old = OldInstance {
+            child,
+            pid,
+            deadline: Instant::now() + start_timeout,
+        };
+        match self.start_service_unit(&name, service).await {
+            Ok(()) => {
+                self.rolling_restarts.insert(name, old);
+                Ok(())
+            }
+            Err(e) => {
+                log::warn!(
+                    "{}: new instance failed to start, keeping PID {}",
+                    name,
+                    pid
+                );
+                self.reinstate(&name, old);
+                Err(e)
+            }
+        }
+    }
+
+    /// Whether `restart` of `name` should roll (RollingRestart=yes and running)
+    pub(super) fn rolls_on_restart(&self, name: &str) -> bool {
+        self.units
+            .get(name)
+            .and_then(Unit::as_service)
+            .is_some_and(|s| s.service.rolling_restart)
+            && self.is_running(name)
+    }
+
+    /// Stop old instances whose replacement is ready (or was stopped), and
+    /// fall back to those whose replacement failed or timed out
+    pub async fn process_rolling_restarts(&mut self) {
+        let names: Vec<String> = self.rolling_restarts.keys().cloned().collect();
+        for name in names {
+            let (active, sub) = match self.states.get(&name) {
+                Some(state) => (state.active, state.sub),
+                None => (ActiveState::Inactive, SubState::Dead),
+            };
+            let replaced = matches!(
+                (active, sub),
+                (ActiveState::Active, SubState::Running)
+                    | (ActiveState::Inactive | ActiveState::Deactivating, _)
+            );
+            let failed = active == ActiveState::Failed || sub == SubState::AutoRestart;
+            let timed_out = self.rolling_restarts[&name].deadline <= Instant::now();
+            if !replaced && !failed && !timed_out {
+                continue;
+            }
+            let Some(old) = self.rolling_restarts.remove(&name) else {
+                continue;
+            };
+            if replaced {
+                let timeout = self.stop_timeout(&name);
+                tokio::spawn(retire(name, old, timeout));
+            } else {
+                self.roll_back(&name, old).await;
+            }
+        }
+    }
+
+    fn is_running(&self, name: &str) -> bool {
+        self.states
+            .get(name)
+            .is_some_and(|s| s.active == ActiveState::Active && s.sub == SubState::Running)
+    }
+
+    /// Kill the replacement of `name` that never became ready and make the
+    /// old process the main process again
+    async fn roll_back(&mut self, name: &str, mut old: OldInstance) {
+        if !matches!(old.child.try_wait(), Ok(None)) {
+            log::warn!("{}: old instance (PID {}) is gone too", name, old.pid);
+            return;
+        }
+        let still_starting = self
+            .states
+            .get(name)
+            .is_some_and(|s| s.active == ActiveState::Activating && s.sub != SubState::AutoRestart);
+        if let Some(mut child) = self.processes.remove(name) {
+            if let Some(pid) = child.id() {
+                self.pid_to_service.remove(&pid);
+                self.waiting_ready.remove(&pid);
+            }
+            let _ = child.kill().await;
+        }
+        if still_starting {
+            self.active_jobs = self.active_jobs.saturating_sub(1);
+        }
+        log::warn!(
+            "{}: new instance did not become ready, keeping PID {}",
+            name,
+            old.pid
+        );
+        self.reinstate(name, old);
+    }
+
+    /// Make `old` the running main process of `name` again
+    fn reinstate(&mut self, name: &str, old: OldInstance) {
+        self.pid_to_service.insert(old.pid, name.to_string());
+        if let Some(state) = self.states.get_mut(name) {
+            state.set_running(old.pid);
+            state.error = None;
+        }
+        self.processes.insert(name.to_string(), old.child);
+    }
+}
+
+/// SIGTERM the old instance of `name`, SIGKILL it after `timeout`
+async fn retire(name: String, mut old: OldInstance, timeout: Duration) {
+    log::info!("{}: new instance ready, stopping PID {}", name, old.pid);
+    unsafe {
+        libc::kill(old.pid as i32, libc::SIGTERM);
+    }
+    if tokio::time::timeout(timeout, old.child.wait())
+        .await
+        .is_err()
+    {
+        log::warn!(
+            "{}: PID {} did not stop in time, sending SIGKILL",
+            name,
+            old.pid
+        );
+        let _ = old.child.kill().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+    use crate::units::Service;
+
+    fn sleeper() -> Child {
+        tokio::process::Command::new("/bin/sleep")
+            .arg("5")
+            .spawn()
+            .unwrap()
+    }
+
+    /// web.service replacing `old`, its new instance in the state `new_state` sets
+    fn manager_with_rollout(old: Child, new_state: impl FnOnce(&mut ServiceState)) -> Manager {
+        let mut manager = Manager::new_user();
+        let mut service = Service::new("web.service".to_string());
+        service.service.timeout_stop_sec = Some(Duration::from_millis(200));
+        manager.insert_unit("web.service".into(), Unit::Service(service));
+        let mut state = ServiceState::new();
+        state.set_starting();
+        new_state(&mut state);
+        manager.states.insert("web.service".into(), state);
+        let old = OldInstance {
+            pid: old.id().unwrap(),
+            child: old,
+            deadline: Instant::now() + Duration::from_secs(5),
+        };
+        manager.rolling_restarts.insert("web.service".into(), old);
+        manager
+    }
+
+    #[tokio::test]
+    async fn only_running_socket_activated_services_roll() {
+        let mut manager = Manager::new_user();
+        let service = Service::new("web.service".to_string());
+        manager.insert_unit("web.service".into(), Unit::Service(service));
+        manager
+            .states
+            .insert("web.service".into(), ServiceState::new());
+        assert!(matches!(
+            manager.rolling_restart("web").await,
+            Err(ManagerError::NotActive(_))
+        ));
+
+        manager.processes.insert("web.service".into(), sleeper());
+        manager
+            .states
+            .get_mut("web.service")
+            .unwrap()
+            .set_running(1);
+        assert!(matches!(
+            manager.rolling_restart("web").await,
+            Err(ManagerError::NotRollable(_))
+        ));
+        assert!(manager.processes.contains_key("web.service"));
+        let _ = manager
+            .processes
+            .get_mut("web.service")
+            .unwrap()
+            .kill()
+            .await;
+    }
+
+    #[tokio::test]
+    async fn old_instance_stops_once_the_new_one_is_ready() {
+        let old = sleeper();
+        let old_pid = old.id().unwrap();
+        let mut manager = manager_with_rollout(old, |state| state.set_running(1));
+
+        manager.process_rolling_restarts().await;
+        assert!(manager.rolling_restarts.is_empty());
+        let gone = Instant::now() + Duration::from_secs(2);
+        while std::path::Path::new(&format!("/proc/{}", old_pid)).exists() {
+            assert!(Instant::now() < gone, "old instance still running");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        assert_eq!(manager.states["web.service"].main_pid, Some(1));
+    }
+
+    #[tokio::test]
+    async fn failed_replacement_falls_back_to_the_old_instance() {
+        let old = sleeper();
+        let old_pid = old.id().unwrap();
+        let mut manager = manager_with_rollout(old, |state| state.set_failed("exit-code".into()));
+
+        manager.process_rolling_restarts().await;
+        let state = &manager.states["web.service"];
+        assert_eq!(state.sub, SubState::Running);
+        assert_eq!(state.main_pid, Some(old_pid));
+        assert_eq!(
+            manager.pid_to_service.get(&old_pid).map(String::as_str),
+            Some("web.service")
+        );
+        let mut child = manager.processes.remove("web.service").unwrap();
+        let _ = child.kill().await;
+    }
+}
@@ -1,3 +1,5 @@
 //! Runtime processing for the service manager
 
 mod imp;
+
+pub(super) use imp::store_fd_entries;
@@ -6,12 +6,14 @@
 // - D-Bus name acquisition for Type=dbus services
 // - Watchdog timeouts
 
+use crate::executor::exit_code_text;
 use crate::units::{NotifyAccess, RestartPolicy, ServiceType};
 
+use crate::manager::exec_gate::ExecGate;
 use crate::manager::notify::NotifyMessage;
 use crate::manager::process;
-use crate::manager::state::{ActiveState, SubState};
-use crate::manager::{Manager, ManagerError, OneshotCompletion, SpawnOptions};
+use crate::manager::state::{ActiveState, ServiceResult, SubState};
+use crate::manager::{DependencyType, Manager, ManagerError, OneshotCompletion, SpawnOptions};
 
 
 impl Manager {
@@ -68,6 +70,16 @@ impl Manager {
         if main_pid == Some(pid) {
             return true;
         }
+        // With PrivatePIDs= the main PID is the executor stub; the service
+        // runs below the init stub of its namespace
+        let private_pids = self
+            .units
+            .get(service_name)
+            .and_then(|u| u.as_service())
+            .is_some_and(|s| s.service.private_pids);
+        if private_pids && main_pid.is_some() && grandparent_pid(pid) == main_pid {
+            return true;
+        }
         log::debug!(
             "Rejecting notify from {} (PID {}) - NotifyAccess=main requires main PID {:?}",
             service_name,
@@ -138,7 +150,9 @@ impl Manager {
         );
 
         if let Some(name) = self.resolve_ready_service_name(msg) {
-            self.mark_service_ready(&name);
+            if !self.delay_injected_ready(&name) {
+                self.mark_service_ready(&name);
+            }
         } else {
             log::warn!(
                 "READY message from PID {} (main_pid={:?}) could not be matched to any service",
@@ -156,6 +170,10 @@ impl Manager {
             .map(|(name, _)| name.clone());
 
         if let Some(name) = service_name {
+            if self.drops_watchdog_pings(&name) {
+                log::debug!("{} watchdog ping dropped (injected)", name);
+                return;
+            }
             if self
                 .units
                 .get(&name)
@@ -171,6 +189,9 @@ impl Manager {
 
     /// Process pending notify messages (READY, STOPPING, WATCHDOG, etc.)
     pub async fn process_notify(&mut self) {
+        for name in self.take_due_ready() {
+            self.mark_service_ready(&name);
+        }
         let messages: Vec<_> = {
             let Some(rx) = &mut self.notify_rx else {
                 return;
@@ -347,10 +368,7 @@ impl Manager {
 
         if code == 0 {
             if is_oneshot && remain_after_exit {
-                state.active = ActiveState::Active;
-                state.sub = SubState::Exited;
-                state.main_pid = None;
-                state.exit_code = Some(code);
+                state.set_exited();
                 state.reset_restart_count();
                 log::info!("{} exited (RemainAfterExit=yes)", name);
             } else if should_restart {
@@ -384,21 +402,24 @@ impl Manager {
                 restart_sec
             );
         } else {
-            state.set_failed(format!("Exit code {}", code));
+            state.set_failed(format!("Exit code {}", exit_code_text(code)));
+            state.exit_code = Some(code);
             log::warn!("{} failed with exit code {}", name, code);
         }
     }
 
-    /// Clean up cgroup, watchdog, dynamic UID, and stored FDs after a service exits
+    /// Clean up cgroup, watchdog, TTY, dynamic UID, and stored FDs after a service exits
     async fn cleanup_after_exit(&mut self, name: &str) {
         self.cleanup_service_cgroup(name);
         self.watchdog_deadlines.remove(name);
+        self.reset_service_tty(name);
 
         let is_restarting = self
             .states
             .get(name)
             .is_some_and(|s| s.sub == SubState::AutoRestart);
         if !is_restarting {
+            self.remove_ipc_after_stop(name);
             self.release_dynamic_uid(name);
             self.close_stored_fds(name);
         }
@@ -508,6 +529,7 @@ impl Manager {
     }
 
     async fn handle_reaped_service(&mut self, name: String, code: i32) {
+        let code = self.injected_exit_code(&name, code);
         self.processes.remove(&name);
         let policy = self.read_restart_policy(&name);
 
@@ -527,6 +549,8 @@ impl Manager {
             &policy.restart_prevent_exit_status,
         );
         self.cleanup_after_exit(&name).await;
+        self.run_stop_post_commands(&name, ServiceResult::from_exit_code(code), Some(code));
+        self.socket_service_stopped(&name);
     }
 
     fn read_restart_policy(&self, name: &str) -> RestartDecisionInput {
@@ -535,7 +559,7 @@ impl Manager {
             .and_then(|u| u.as_service())
             .map(|s| RestartDecisionInput {
                 restart_policy: s.service.restart.clone(),
-                restart_sec: s.service.restart_sec,
+                restart_sec: s.service.restart_delay(),
                 remain_after_exit: s.service.remain_after_exit,
                 is_oneshot: s.service.service_type == ServiceType::Oneshot,
                 is_forking: s.service.service_type == ServiceType::Forking,
@@ -547,29 +571,21 @@ impl Manager {
     }
 
     /// M19: BindsTo= stop propagation
-    /// When a unit stops, find all units with BindsTo= pointing to it and stop them
-    async fn propagate_binds_to_stop(&mut self, stopped_unit: &str) {
+    /// When a unit stops, for whatever reason, stop the units with BindsTo=
+    /// pointing to it. PartOf= only follows stop and restart jobs (`stop`,
+    /// `enqueue_stop`, `restart`), so those units survive a crash.
+    pub(super) async fn propagate_binds_to_stop(&mut self, stopped_unit: &str) {
         let units_to_stop: Vec<String> = self
-            .units
-            .iter()
-            .filter_map(|(name, unit)| {
-                let binds_to = &unit.unit_section().binds_to;
-                if binds_to.contains(&stopped_unit.to_string()) {
-                    if let Some(state) = self.states.get(name) {
-                        if state.is_active() {
-                            return Some(name.clone());
-                        }
-                    }
-                }
-                None
-            })
+            .dependents(stopped_unit, DependencyType::BindsTo)
+            .into_iter()
+            .filter(|name| self.states.get(name).is_some_and(|state| state.is_active()))
             .collect();
 
         for name in units_to_stop {
-            log::info!("Stopping {} (BindsTo={} which stopped)", name, stopped_unit);
-            if let Err(e) = self.stop(&name).await {
+            log::info!("Stopping {} (bound to {} which stopped)", name, stopped_unit);
+            if let Err(e) = self.enqueue_stop(&name).await {
                 log::warn!(
-                    "Failed to stop {} after BindsTo dependency stopped: {}",
+                    "Failed to stop {} after bound dependency stopped: {}",
                     name,
                     e
                 );
@@ -582,7 +598,12 @@ impl Manager {
         let due: Vec<String> = self
             .states
             .iter()
-            .filter(|(_, state)| state.sub == SubState::AutoRestart && state.restart_due())
+            // A restart waits for ExecStopPost= of the run before it
+            .filter(|(name, state)| {
+                state.sub == SubState::AutoRestart
+                    && state.restart_due()
+                    && !self.stop_post_running.contains(*name)
+            })
             .map(|(name, _)| name.clone())
             .collect();
 
@@ -601,3 +622,15 @@ impl Manager {
     }
 
 }
+
+/// Parent of the parent of `pid`
+fn grandparent_pid(pid: u32) -> Option<u32> {
+    parent_pid(pid).and_then(parent_pid)
+}
+
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name before the fields may contain spaces and parentheses
+    let fields = &stat[stat.rfind(')')? + 2..];
+    fields.split(' ').nth(1)?.parse().ok()
+}
@@ -12,18 +12,14 @@ fn service_unit(name: &str, configure: impl FnOnce(&mut Service)) -> Unit {
 
 fn manager_with_service(name: &str, configure: impl FnOnce(&mut Service)) -> Manager {
     let mut manager = Manager::new();
-    manager
-        .units
-        .insert(name.to_string(), service_unit(name, configure));
+    manager.insert_unit(name.to_string(), service_unit(name, configure));
     manager.states.insert(name.to_string(), ServiceState::new());
     manager
 }
 
 fn user_manager_with_service(name: &str, configure: impl FnOnce(&mut Service)) -> Manager {
     let mut manager = Manager::new_user();
-    manager
-        .units
-        .insert(name.to_string(), service_unit(name, configure));
+    manager.insert_unit(name.to_string(), service_unit(name, configure));
     manager.states.insert(name.to_string(), ServiceState::new());
     manager
 }
@@ -129,13 +125,59 @@ async fn propagate_binds_to_stop_stops_active_bound_services() {
         .unwrap()
         .set_running(0);
 
+    let mut stop_events = manager.take_stop_event_rx().unwrap();
+
     manager.propagate_binds_to_stop("base.service").await;
 
+    assert_eq!(
+        manager.states.get("bound.service").unwrap().active,
+        ActiveState::Deactivating
+    );
+    while let Some(event) = stop_events.recv().await {
+        let finished = matches!(event, crate::manager::StopEvent::Finished { .. });
+        manager.handle_stop_event(event).await;
+        if finished {
+            break;
+        }
+    }
     let state = manager.states.get("bound.service").unwrap();
     assert_eq!(state.active, ActiveState::Inactive);
     assert_eq!(state.sub, SubState::Exited);
 }
 
+#[tokio::test]
+async fn a_crashed_unit_stops_bound_units_but_not_those_part_of_it() {
+    let mut manager = user_manager_with_service("parent.service", |_| {});
+    manager.insert_unit(
+        "bound.service".to_string(),
+        service_unit("bound.service", |service| {
+            service.unit.binds_to = vec!["parent.service".to_string()];
+        }),
+    );
+    manager.insert_unit(
+        "part.service".to_string(),
+        service_unit("part.service", |service| {
+            service.unit.part_of = vec!["parent.service".to_string()];
+        }),
+    );
+    for name in ["parent.service", "bound.service", "part.service"] {
+        let mut running = ServiceState::new();
+        running.set_running(0);
+        manager.states.insert(name.to_string(), running);
+    }
+
+    manager
+        .handle_reaped_service("parent.service".to_string(), 1)
+        .await;
+
+    assert_eq!(manager.states["parent.service"].active, ActiveState::Failed);
+    assert_eq!(
+        manager.states["bound.service"].active,
+        ActiveState::Deactivating
+    );
+    assert_eq!(manager.states["part.service"].active, ActiveState::Active);
+}
+
 #[tokio::test]
 async fn handle_reaped_service_applies_policy_and_cleans_runtime_state() {
     let mut manager = user_manager_with_service("reaped.service", |service| {
@@ -207,16 +249,36 @@ async fn watchdog_timeout_aborts_running_child_and_marks_failure() {
             .spawn()
             .unwrap(),
     );
+    manager
+        .states
+        .get_mut("watchdog-child.service")
+        .unwrap()
+        .set_running(0);
     manager
         .watchdog_deadlines
         .insert("watchdog-child.service".to_string(), std::time::Instant::now());
+    let mut stop_events = manager.take_stop_event_rx().unwrap();
 
     manager.process_watchdog().await;
+    assert!(!manager.processes.contains_key("watchdog-child.service"));
 
+    let mut phases = Vec::new();
+    while let Some(event) = stop_events.recv().await {
+        match &event {
+            crate::manager::StopEvent::Phase { sub, .. } => phases.push(*sub),
+            crate::manager::StopEvent::Finished { exit_code, .. } => {
+                assert_eq!(*exit_code, Some(-libc::SIGABRT));
+                manager.handle_stop_event(event).await;
+                break;
+            }
+            crate::manager::StopEvent::StopPostFinished { .. } => {}
+        }
+        manager.handle_stop_event(event).await;
+    }
+    assert_eq!(phases[0], SubState::StopWatchdog);
     let state = manager.states.get("watchdog-child.service").unwrap();
     assert_eq!(state.active, ActiveState::Failed);
     assert_eq!(state.error.as_deref(), Some("Watchdog timeout"));
-    assert!(!manager.processes.contains_key("watchdog-child.service"));
 }
 
 fn local_executor_path() -> Option<String> {
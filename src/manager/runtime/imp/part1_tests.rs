@@ -42,9 +42,7 @@ fn runtime_pipe_fds() -> [libc::c_int; 2] {
 
 fn manager_with_service(name: &str, configure: impl FnOnce(&mut Service)) -> Manager {
     let mut manager = Manager::new();
-    manager
-        .units
-        .insert(name.to_string(), service_unit(name, configure));
+    manager.insert_unit(name.to_string(), service_unit(name, configure));
     manager.states.insert(name.to_string(), ServiceState::new());
     manager
 }
@@ -375,7 +373,7 @@ async fn cleanup_after_exit_keeps_restart_resources_for_auto_restart() {
 fn read_restart_policy_returns_service_values_or_defaults() {
     let manager = manager_with_service("custom.service", |service| {
         service.service.restart = RestartPolicy::Always;
-        service.service.restart_sec = Duration::from_secs(9);
+        service.service.restart_sec = Some(Duration::from_secs(9));
         service.service.remain_after_exit = true;
         service.service.service_type = ServiceType::Forking;
         service.service.start_limit_burst = Some(3);
@@ -579,15 +577,32 @@ async fn process_dbus_ready_returns_without_bus_when_no_names_or_no_connection()
 async fn process_watchdog_marks_failure_and_schedules_restart() {
     let mut manager = user_manager_with_service("watch.service", |service| {
         service.service.restart = RestartPolicy::Always;
-        service.service.restart_sec = Duration::from_secs(2);
+        service.service.restart_sec = Some(Duration::from_secs(2));
     });
+    manager
+        .states
+        .get_mut("watch.service")
+        .unwrap()
+        .set_running(0);
     manager
         .watchdog_deadlines
         .insert("watch.service".to_string(), std::time::Instant::now());
+    let mut stop_events = manager.take_stop_event_rx().unwrap();
 
     manager.process_watchdog().await;
 
     assert!(!manager.watchdog_deadlines.contains_key("watch.service"));
+    assert_eq!(
+        manager.states.get("watch.service").unwrap().active,
+        ActiveState::Deactivating
+    );
+    while let Some(event) = stop_events.recv().await {
+        let finished = matches!(event, crate::manager::StopEvent::Finished { .. });
+        manager.handle_stop_event(event).await;
+        if finished {
+            break;
+        }
+    }
     let state = manager.states.get("watch.service").unwrap();
     assert_eq!(state.active, ActiveState::Activating);
     assert_eq!(state.sub, SubState::AutoRestart);
@@ -595,19 +610,19 @@ async fn process_watchdog_marks_failure_and_schedules_restart() {
 }
 
 #[test]
-fn watchdog_restart_delay_respects_restart_policy() {
+fn failure_restart_delay_respects_restart_policy() {
     let manager = user_manager_with_service("no.service", |_| {});
-    assert_eq!(manager.watchdog_restart_delay("no.service"), None);
+    assert_eq!(manager.failure_restart_delay("no.service"), None);
 
     let always = user_manager_with_service("always.service", |service| {
         service.service.restart = RestartPolicy::Always;
-        service.service.restart_sec = Duration::from_secs(4);
+        service.service.restart_sec = Some(Duration::from_secs(4));
     });
     assert_eq!(
-        always.watchdog_restart_delay("always.service"),
+        always.failure_restart_delay("always.service"),
         Some(Duration::from_secs(4))
     );
-    assert_eq!(always.watchdog_restart_delay("missing.service"), None);
+    assert_eq!(always.failure_restart_delay("missing.service"), None);
 }
 
 #[tokio::test]
@@ -723,6 +738,7 @@ fn apply_restart_decision_covers_clean_restart_and_failed_no_restart() {
     assert_eq!(failed.active, ActiveState::Failed);
     assert_eq!(failed.sub, SubState::Failed);
     assert_eq!(failed.error.as_deref(), Some("Exit code 9"));
+    assert_eq!(failed.exit_code, Some(9));
 }
 
 #[tokio::test]
@@ -746,3 +762,14 @@ async fn process_restarts_marks_missing_due_service_failed() {
     );
     assert!(state.restart_at.is_none());
 }
+
+#[test]
+fn parent_pids_come_from_proc() {
+    let pid = std::process::id();
+    assert_eq!(parent_pid(pid), Some(std::os::unix::process::parent_id()));
+    assert_eq!(
+        grandparent_pid(pid),
+        parent_pid(std::os::unix::process::parent_id())
+    );
+    assert_eq!(parent_pid(u32::MAX), None);
+}
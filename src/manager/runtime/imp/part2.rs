@@ -41,6 +41,11 @@ impl Manager {
 
         if let Some(ref error) = completion.error {
             self.handle_oneshot_failure(service_name, error);
+            let result = completion
+                .exit_code
+                .map(ServiceResult::from_exit_code)
+                .unwrap_or(ServiceResult::Resources);
+            self.run_stop_post_commands(service_name, result, completion.exit_code);
             return;
         }
 
@@ -52,6 +57,9 @@ impl Manager {
         }
 
         self.finish_oneshot_success(service_name, completion.remain_after_exit);
+        if !completion.remain_after_exit {
+            self.run_stop_post_commands(service_name, ServiceResult::Success, Some(0));
+        }
     }
 
     fn handle_oneshot_failure(&mut self, service_name: &str, error: &str) {
@@ -110,7 +118,8 @@ impl Manager {
         let service = self.get_oneshot_service(service_name)?;
         let total_cmds = service.service.exec_start.len();
         let remain_after_exit = service.service.remain_after_exit;
-        let child = self.spawn_oneshot_child(&service, cmd_idx)?;
+        let gate = ExecGate::new(!self.executor_path.is_empty());
+        let child = self.spawn_oneshot_child(&service, cmd_idx, gate.as_ref())?;
         let pid = child.id().unwrap_or_default();
 
         log::info!(
@@ -121,6 +130,9 @@ impl Manager {
         );
 
         self.add_oneshot_pid_to_cgroup(service_name, pid);
+        if let Some(gate) = gate {
+            gate.release();
+        }
         self.spawn_oneshot_completion_task(service_name, cmd_idx, total_cmds, remain_after_exit, child);
 
         Ok(())
@@ -138,8 +150,12 @@ impl Manager {
         &self,
         service: &crate::units::Service,
         cmd_idx: usize,
+        gate: Option<&ExecGate>,
     ) -> Result<tokio::process::Child, ManagerError> {
-        let options = SpawnOptions::default();
+        let options = SpawnOptions {
+            exec_gate_fd: gate.map(ExecGate::child_fd),
+            ..SpawnOptions::default()
+        };
         process::spawn_service_via_executor(service, &options, &self.executor_path, cmd_idx)
             .map_err(ManagerError::from)
     }
@@ -201,7 +217,8 @@ impl Manager {
         }
     }
 
-    /// Check for watchdog timeouts and restart services that missed their deadline
+    /// Abort services that missed their watchdog deadline; the stop runs in
+    /// the background and Restart= decides what happens after it
     pub async fn process_watchdog(&mut self) {
         let now = std::time::Instant::now();
         let timed_out: Vec<String> = self
@@ -212,7 +229,9 @@ impl Manager {
             .collect();
 
         for name in timed_out {
-            self.handle_watchdog_timeout(&name).await;
+            log::warn!("{} watchdog timeout - aborting it", name);
+            let timeout = self.abort_timeout(&name);
+            self.enqueue_failure_stop(&name, ServiceResult::Watchdog, Some(timeout));
         }
     }
 
@@ -254,57 +273,13 @@ impl Manager {
         self.arm_watchdog(service_name);
     }
 
-    async fn handle_watchdog_timeout(&mut self, service_name: &str) {
-        self.watchdog_deadlines.remove(service_name);
-        log::warn!("{} watchdog timeout - restarting", service_name);
-        self.abort_watchdog_process(service_name).await;
-        self.mark_watchdog_failure(service_name);
-        self.schedule_watchdog_restart_if_needed(service_name);
-    }
-
-    async fn abort_watchdog_process(&mut self, service_name: &str) {
-        let Some(mut child) = self.processes.remove(service_name) else {
-            return;
-        };
-        let Some(pid) = child.id() else {
-            return;
-        };
-        unsafe {
-            libc::kill(pid as i32, libc::SIGABRT);
-        }
-        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
-        let _ = child.kill().await;
-    }
-
-    fn mark_watchdog_failure(&mut self, service_name: &str) {
-        if let Some(state) = self.states.get_mut(service_name) {
-            state.set_failed("Watchdog timeout".to_string());
-        }
-    }
-
-    fn schedule_watchdog_restart_if_needed(&mut self, service_name: &str) {
-        let Some(restart_sec) = self.watchdog_restart_delay(service_name) else {
-            return;
-        };
-        if let Some(state) = self.states.get_mut(service_name) {
-            state.set_auto_restart(restart_sec);
-            log::info!(
-                "{} scheduling watchdog restart in {:?}",
-                service_name,
-                restart_sec
-            );
-        }
-    }
-
-    fn watchdog_restart_delay(&self, service_name: &str) -> Option<std::time::Duration> {
-        let service = self.units.get(service_name).and_then(|u| u.as_service())?;
-        if matches!(
-            service.service.restart,
-            RestartPolicy::Always | RestartPolicy::OnFailure
-        ) {
-            return Some(service.service.restart_sec);
-        }
-        None
+    /// TimeoutAbortSec=, or TimeoutStopSec= without it
+    fn abort_timeout(&self, service_name: &str) -> std::time::Duration {
+        self.units
+            .get(service_name)
+            .and_then(|u| u.as_service())
+            .and_then(|s| s.service.timeout_abort_sec)
+            .unwrap_or_else(|| self.stop_timeout(service_name))
     }
 }
 struct RestartDecisionInput {
@@ -322,7 +297,7 @@ impl Default for RestartDecisionInput {
     fn default() -> Self {
         Self {
             restart_policy: RestartPolicy::No,
-            restart_sec: std::time::Duration::from_millis(100),
+            restart_sec: units::DEFAULT_RESTART_SEC,
             remain_after_exit: false,
             is_oneshot: false,
             is_forking: false,
@@ -341,7 +316,7 @@ fn close_fds(fds: &[i32]) {
     }
 }
 
-fn store_fd_entries(
+pub(in crate::manager) fn store_fd_entries(
     store: &mut Vec<(String, i32)>,
     service_name: &str,
     fd_name: &str,
@@ -374,7 +349,10 @@ async fn read_oneshot_completion_result(
             if code == 0 {
                 (Some(0), None)
             } else {
-                (Some(code), Some(format!("exit code {}", code)))
+                (
+                    Some(code),
+                    Some(format!("exit code {}", exit_code_text(code))),
+                )
             }
         }
         Err(e) => (None, Some(e.to_string())),
@@ -3,3 +3,4 @@
 mod imp;
 
 pub use imp::apply_sandbox;
+pub(crate) use imp::{has_seccomp_settings, needs_mount_namespace};
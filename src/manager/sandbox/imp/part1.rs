@@ -17,8 +17,14 @@ use seccompiler::{
     SeccompRule, TargetArch,
 };
 
-use crate::sandbox_prctl::{apply_no_new_privileges, apply_private_network};
-use crate::units::{DevicePolicy, ProtectHome, ProtectProc, ProtectSystem, ServiceSection};
+use crate::sandbox_prctl::{
+    apply_no_new_privileges, apply_private_ipc, apply_private_network, apply_secure_bits,
+    apply_session_keyring, join_network_namespace, set_exec_apparmor_profile,
+    set_exec_selinux_context, set_smack_process_label,
+};
+use crate::units::{
+    DevicePolicy, KeyringMode, MacLabel, ProtectHome, ProtectProc, ProtectSystem, ServiceSection,
+};
 
 /// Apply all sandbox settings for a service.
 /// Must be called after fork() but before exec().
@@ -43,12 +49,38 @@ fn apply_basic_sandbox_settings(service: &ServiceSection) -> Result<(), String>
     }
     apply_capability_bounding_set(&service.capability_bounding_set)?;
     apply_ambient_capabilities(&service.ambient_capabilities)?;
-    if service.private_network {
+    // A namespace to join takes precedence over an isolated one
+    if let Some(path) = &service.network_namespace_path {
+        join_network_namespace(path)?;
+    } else if service.private_network {
         apply_private_network()?;
     }
+    if service.private_ipc {
+        apply_private_ipc()?;
+    }
+    if let Some(context) = &service.selinux_context {
+        apply_mac_label(context, set_exec_selinux_context)?;
+    }
+    if let Some(profile) = &service.apparmor_profile {
+        apply_mac_label(profile, set_exec_apparmor_profile)?;
+    }
+    if let Some(label) = &service.smack_process_label {
+        apply_mac_label(label, set_smack_process_label)?;
+    }
     apply_prctl_settings(service)
 }
 
+/// A "-" prefixed label failing to apply only gets a warning
+fn apply_mac_label(label: &MacLabel, apply: fn(&str) -> Result<(), String>) -> Result<(), String> {
+    match apply(&label.label) {
+        Err(e) if label.ignore_failure => {
+            log::warn!("{}, continuing without it", e);
+            Ok(())
+        }
+        result => result,
+    }
+}
+
 fn apply_prctl_settings(service: &ServiceSection) -> Result<(), String> {
     if service.restrict_realtime {
         apply_restrict_realtime()?;
@@ -62,10 +94,25 @@ fn apply_prctl_settings(service: &ServiceSection) -> Result<(), String> {
     if service.ignore_sigpipe {
         apply_ignore_sigpipe()?;
     }
+    // Before the switch to the service user, which keep-caps and
+    // no-setuid-fixup are about
+    if service.secure_bits != 0 {
+        apply_secure_bits(service.secure_bits)?;
+    }
     Ok(())
 }
 
-fn needs_mount_namespace(service: &ServiceSection) -> bool {
+/// Set up the session keyring for KeyringMode=
+/// Must be called after switching to the service user.
+pub fn apply_keyring_mode(mode: &KeyringMode) -> Result<(), String> {
+    match mode {
+        KeyringMode::Inherit => Ok(()),
+        KeyringMode::Private => apply_session_keyring(false),
+        KeyringMode::Shared => apply_session_keyring(true),
+    }
+}
+
+pub(crate) fn needs_mount_namespace(service: &ServiceSection) -> bool {
     let requires_namespace = [
         !matches!(service.protect_system, ProtectSystem::No),
         !matches!(service.protect_home, ProtectHome::No),
@@ -124,7 +171,7 @@ fn apply_mount_protections(service: &ServiceSection) -> Result<(), String> {
     Ok(())
 }
 
-fn has_seccomp_settings(service: &ServiceSection) -> bool {
+pub(crate) fn has_seccomp_settings(service: &ServiceSection) -> bool {
     service.restrict_namespaces.is_some()
         || !service.system_call_filter.is_empty()
         || service.protect_clock
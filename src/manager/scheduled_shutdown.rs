@@ -0,0 +1,204 @@
+//! Scheduled shutdowns (`sysd shutdown +10 "maintenance"`, ScheduleShutdown)
+//!
+//! Like shutdown(8) with logind: until the shutdown is due, logged-in users
+//! get a wall message, more often as it comes closer (hourly, then every 30
+//! and 15 minutes, and every minute in the last 10). /run/nologin goes up 5
+//! minutes before, so only root can log in. A cancelled shutdown says so on
+//! the terminals it was announced on and removes /run/nologin again. Only
+//! one shutdown is pending; scheduling another replaces it.
+
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use crate::pid1::{self, ShutdownType};
+
+use super::{Manager, ManagerError};
+
+const MINUTE: Duration = Duration::from_secs(60);
+/// How long before the shutdown /run/nologin is created, as in logind
+const NOLOGIN_BEFORE: Duration = Duration::from_secs(5 * 60);
+
+/// A pending shutdown
+#[derive(Debug)]
+pub(crate) struct ScheduledShutdown {
+    kind: ShutdownType,
+    at: SystemTime,
+    message: Option<String>,
+    next_wall: SystemTime,
+    /// Whether a wall message went out, so the cancellation is announced
+    announced: bool,
+    /// /run/nologin, once created
+    nologin: Option<PathBuf>,
+}
+
+impl ScheduledShutdown {
+    fn remove_nologin(&mut self) {
+        if let Some(nologin) = self.nologin.take() {
+            let _ = std::fs::remove_file(nologin);
+        }
+    }
+}
+
+impl Manager {
+    /// Let `schedule_shutdown` arm shutdowns (only PID 1 can carry them out)
+    pub fn set_shutdown_allowed(&mut self, allowed: bool) {
+        self.shutdown_allowed = allowed;
+    }
+
+    /// Arm a `kind` shutdown at `at`, announced with `message`; replaces a
+    /// pending one
+    pub fn schedule_shutdown(
+        &mut self,
+        kind: ShutdownType,
+        at: SystemTime,
+        message: Option<String>,
+    ) -> Result<(), ManagerError> {
+        if !self.shutdown_allowed {
+            return Err(ManagerError::ShutdownNotAllowed);
+        }
+        // The new shutdown may be further off; /run/nologin comes back in time
+        let announced = match self.scheduled_shutdown.take() {
+            Some(mut replaced) => {
+                replaced.remove_nologin();
+                replaced.announced
+            }
+            None => false,
+        };
+        log::info!("Scheduled: {}", shutdown_notice(kind, at));
+        self.scheduled_shutdown = Some(ScheduledShutdown {
+            kind,
+            at,
+            message,
+            next_wall: SystemTime::now(),
+            announced,
+            nologin: None,
+        });
+        Ok(())
+    }
+
+    /// Disarm the pending shutdown; false if there was none
+    pub fn cancel_scheduled_shutdown(&mut self) -> bool {
+        let Some(mut shutdown) = self.scheduled_shutdown.take() else {
+            return false;
+        };
+        log::info!("Cancelled the scheduled {}", shutdown.kind.as_str());
+        if shutdown.announced {
+            pid1::wall("The system shutdown has been cancelled");
+        }
+        shutdown.remove_nologin();
+        true
+    }
+
+    /// The pending shutdown and when it is due
+    pub fn scheduled_shutdown(&self) -> Option<(ShutdownType, SystemTime)> {
+        self.scheduled_shutdown
+            .as_ref()
+            .map(|shutdown| (shutdown.kind, shutdown.at))
+    }
+
+    /// Announce the pending shutdown as it comes closer; returns it once due
+    pub fn process_scheduled_shutdown(&mut self) -> Option<ShutdownType> {
+        let now = SystemTime::now();
+        let shutdown = self.scheduled_shutdown.as_mut()?;
+        let Ok(left) = shutdown.at.duration_since(now) else {
+            let kind = shutdown.kind;
+            self.scheduled_shutdown = None;
+            log::info!("Scheduled {} is due", kind.as_str());
+            return Some(kind);
+        };
+        if shutdown.nologin.is_none() && left <= NOLOGIN_BEFORE {
+            let path = crate::root::path("/run/nologin");
+            let text = format!("{}\n", shutdown_notice(shutdown.kind, shutdown.at));
+            match std::fs::write(&path, text) {
+                Ok(()) => shutdown.nologin = Some(path),
+                Err(e) => log::warn!("Failed to create {}: {}", path.display(), e),
+            }
+        }
+        if now >= shutdown.next_wall {
+            let notice = shutdown_notice(shutdown.kind, shutdown.at);
+            match &shutdown.message {
+                Some(message) => pid1::wall(&format!("{}\n\n{}", message, notice)),
+                None => pid1::wall(&notice),
+            }
+            shutdown.announced = true;
+            shutdown.next_wall = now + wall_interval(left);
+        }
+        None
+    }
+}
+
+/// Time between wall messages with `left` to go, as in logind
+fn wall_interval(left: Duration) -> Duration {
+    if left <= 10 * MINUTE {
+        MINUTE
+    } else if left <= 60 * MINUTE {
+        15 * MINUTE
+    } else if left <= 3 * 60 * MINUTE {
+        30 * MINUTE
+    } else {
+        60 * MINUTE
+    }
+}
+
+fn shutdown_notice(kind: ShutdownType, at: SystemTime) -> String {
+    let verb = match kind {
+        ShutdownType::Poweroff => "power off",
+        ShutdownType::Reboot => "reboot",
+        ShutdownType::Halt => "halt",
+    };
+    let at = chrono::DateTime::<chrono::Local>::from(at);
+    format!(
+        "The system will {} at {}!",
+        verb,
+        at.format("%a %Y-%m-%d %H:%M:%S %Z")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wall_messages_come_more_often_as_the_shutdown_nears() {
+        assert_eq!(wall_interval(5 * 60 * MINUTE), 60 * MINUTE);
+        assert_eq!(wall_interval(2 * 60 * MINUTE), 30 * MINUTE);
+        assert_eq!(wall_interval(30 * MINUTE), 15 * MINUTE);
+        assert_eq!(wall_interval(10 * MINUTE), MINUTE);
+        assert_eq!(wall_interval(Duration::ZERO), MINUTE);
+    }
+
+    #[test]
+    fn scheduled_shutdowns_replace_each_other_until_cancelled_or_due() {
+        let mut manager = Manager::new();
+        let in_an_hour = SystemTime::now() + 60 * MINUTE;
+        assert!(matches!(
+            manager.schedule_shutdown(ShutdownType::Reboot, in_an_hour, None),
+            Err(ManagerError::ShutdownNotAllowed)
+        ));
+
+        manager.set_shutdown_allowed(true);
+        manager
+            .schedule_shutdown(ShutdownType::Reboot, in_an_hour, None)
+            .unwrap();
+        manager
+            .schedule_shutdown(ShutdownType::Halt, in_an_hour, Some("disk swap".into()))
+            .unwrap();
+        assert_eq!(
+            manager.scheduled_shutdown(),
+            Some((ShutdownType::Halt, in_an_hour))
+        );
+        assert!(manager.cancel_scheduled_shutdown());
+        assert!(!manager.cancel_scheduled_shutdown());
+        assert_eq!(manager.process_scheduled_shutdown(), None);
+
+        let overdue = SystemTime::now() - MINUTE;
+        manager
+            .schedule_shutdown(ShutdownType::Poweroff, overdue, None)
+            .unwrap();
+        assert_eq!(
+            manager.process_scheduled_shutdown(),
+            Some(ShutdownType::Poweroff)
+        );
+        assert_eq!(manager.scheduled_shutdown(), None);
+    }
+}
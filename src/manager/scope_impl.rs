@@ -2,18 +2,23 @@
 //
 // Handles transient scope units created by logind for session management.
 // Scopes don't have unit files - they're created at runtime via D-Bus.
+// Their definitions are persisted under the runtime directory so a restarted
+// sysd can re-adopt session scopes instead of tearing user sessions down.
 
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use crate::cgroups::{create_session_scope, CgroupManager};
 use crate::dbus::scope::ScopeState;
 use crate::dbus::unit::UnitState;
-use crate::dbus::{unit_object_path, ScopeInterface, UnitInterface};
+use crate::dbus::{unit_object_path, Authorizer, ScopeInterface, UnitInterface};
 use crate::manager::ManagerError;
 
+pub use crate::state_dir::SCOPE_STATE_DIR;
+
 /// Manages transient scope units
 pub struct ScopeManager {
     /// Active scopes (scope name -> cgroup path)
@@ -22,6 +27,20 @@ pub struct ScopeManager {
     dbus_connection: Option<zbus::Connection>,
     /// Cgroup manager reference
     cgroup_manager: Option<Arc<CgroupManager>>,
+    /// Directory scope definitions are persisted in (None = not persisted)
+    state_dir: Option<PathBuf>,
+}
+
+/// Scope definition persisted across sysd restarts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PersistedScope {
+    pub name: String,
+    pub slice: String,
+    pub description: Option<String>,
+    /// D-Bus name of the scope's controller (Controller= property)
+    pub controller: Option<String>,
+    pub cgroup_path: PathBuf,
+    pub pids: Vec<u32>,
 }
 
 impl ScopeManager {
@@ -30,9 +49,15 @@ impl ScopeManager {
             scopes: HashMap::new(),
             dbus_connection: None,
             cgroup_manager: cgroup_manager.map(Arc::new),
+            state_dir: None,
         }
     }
 
+    /// Persist scope definitions in `dir` from now on
+    pub fn set_state_dir(&mut self, dir: PathBuf) {
+        self.state_dir = Some(dir);
+    }
+
     /// Set the D-Bus connection for scope registration
     pub fn set_dbus_connection(&mut self, conn: zbus::Connection) {
         self.dbus_connection = Some(conn);
@@ -87,6 +112,14 @@ impl ScopeManager {
         }
 
         self.scopes.insert(name.to_string(), cgroup_path.clone());
+        self.persist(&PersistedScope {
+            name: name.to_string(),
+            slice: slice.to_string(),
+            description: description.map(str::to_string),
+            controller: None,
+            cgroup_path: cgroup_path.clone(),
+            pids: pids.to_vec(),
+        });
         log::info!("Scope {} created at {}", name, cgroup_path.display());
         Ok(cgroup_path)
     }
@@ -95,6 +128,9 @@ impl ScopeManager {
     pub async fn unregister(&mut self, name: &str) -> Result<(), ManagerError> {
         // Remove from tracking
         self.scopes.remove(name);
+        if let Some(dir) = &self.state_dir {
+            let _ = std::fs::remove_file(dir.join(name));
+        }
 
         // Unregister D-Bus objects
         if let Some(conn) = &self.dbus_connection {
@@ -111,7 +147,7 @@ impl ScopeManager {
 
         // Clean up cgroup if it exists and is empty
         if let Some(cgroup_mgr) = &self.cgroup_manager {
-            let cgroup_path = PathBuf::from(format!("/sys/fs/cgroup/user.slice/{}", name));
+            let cgroup_path = crate::root::path(format!("/sys/fs/cgroup/user.slice/{}", name));
             if cgroup_path.exists() {
                 if let Err(e) = cgroup_mgr.remove_cgroup(&cgroup_path) {
                     log::debug!("Could not remove cgroup for {}: {}", name, e);
@@ -121,6 +157,129 @@ impl ScopeManager {
 
         Ok(())
     }
+
+    /// Record the D-Bus controller of a scope (Controller= property)
+    pub fn set_controller(&mut self, name: &str, controller: &str) {
+        let Some(mut scope) = self
+            .state_dir
+            .as_deref()
+            .and_then(|dir| read_scope(dir, name))
+        else {
+            return;
+        };
+        scope.controller = Some(controller.to_string());
+        self.persist(&scope);
+    }
+
+    /// Re-adopt scopes persisted by a previous sysd instance
+    ///
+    /// A scope is adopted if its cgroup still contains processes; the
+    /// persisted PIDs are refreshed from cgroup membership. Scopes whose
+    /// cgroup is gone or empty are forgotten. Returns the adopted scopes.
+    pub fn adopt_persisted(&mut self) -> Vec<PersistedScope> {
+        let Some(dir) = self.state_dir.clone() else {
+            return Vec::new();
+        };
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            return Vec::new();
+        };
+
+        let mut adopted = Vec::new();
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let Some(mut scope) = read_scope(&dir, &name) else {
+                log::warn!(
+                    "Discarding unreadable scope state {}",
+                    entry.path().display()
+                );
+                let _ = std::fs::remove_file(entry.path());
+                continue;
+            };
+            let pids = cgroup_pids(&scope.cgroup_path);
+            if pids.is_empty() {
+                log::info!("Scope {} has no processes left, not adopting", name);
+                let _ = std::fs::remove_file(entry.path());
+                if let Some(cgroup_mgr) = &self.cgroup_manager {
+                    let _ = cgroup_mgr.remove_cgroup(&scope.cgroup_path);
+                }
+                continue;
+            }
+            scope.pids = pids;
+            self.persist(&scope);
+            self.scopes.insert(name, scope.cgroup_path.clone());
+            log::info!(
+                "Re-adopted scope {} ({} processes)",
+                scope.name,
+                scope.pids.len()
+            );
+            adopted.push(scope);
+        }
+        adopted
+    }
+
+    /// Register D-Bus objects for tracked scopes (after the bus connects)
+    pub async fn register_tracked_dbus_objects(&self) {
+        let Some(conn) = &self.dbus_connection else {
+            return;
+        };
+        for (name, cgroup_path) in &self.scopes {
+            let description = self
+                .state_dir
+                .as_deref()
+                .and_then(|dir| read_scope(dir, name))
+                .and_then(|scope| scope.description);
+            if let Err(e) = register_scope_dbus_objects(
+                conn,
+                &self.cgroup_manager,
+                name,
+                description.as_deref(),
+                cgroup_path,
+            )
+            .await
+            {
+                log::warn!("Failed to register D-Bus objects for scope {}: {}", name, e);
+            }
+        }
+    }
+
+    fn persist(&self, scope: &PersistedScope) {
+        let Some(dir) = &self.state_dir else {
+            return;
+        };
+        if let Err(e) = write_scope(dir, scope) {
+            log::warn!("Failed to persist scope {}: {}", scope.name, e);
+        }
+    }
+}
+
+fn read_scope(dir: &Path, name: &str) -> Option<PersistedScope> {
+    let data = std::fs::read(dir.join(name)).ok()?;
+    rmp_serde::from_slice(&data).ok()
+}
+
+fn write_scope(dir: &Path, scope: &PersistedScope) -> std::io::Result<()> {
+    let data = rmp_serde::to_vec(scope)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    std::fs::create_dir_all(dir)?;
+    // Write-then-rename so a crash never leaves a truncated definition
+    let tmp = dir.join(format!(".{}.tmp", scope.name));
+    std::fs::write(&tmp, data)?;
+    std::fs::rename(&tmp, dir.join(&scope.name))
+}
+
+/// PIDs currently in the cgroup at `path`
+fn cgroup_pids(path: &Path) -> Vec<u32> {
+    std::fs::read_to_string(path.join("cgroup.procs"))
+        .map(|procs| {
+            procs
+                .lines()
+                .filter_map(|l| l.trim().parse().ok())
+                .collect()
+        })
+        .unwrap_or_default()
 }
 
 async fn create_scope_cgroup_path(
@@ -150,7 +309,8 @@ async fn register_scope_dbus_objects(
 ) -> Result<(), ManagerError> {
     let desc = description.unwrap_or(name).to_string();
     let unit_iface = build_scope_unit_interface(name, &desc).await;
-    let scope_iface = build_scope_interface(name, cgroup_path, cgroup_manager);
+    let scope_iface = build_scope_interface(name, cgroup_path, cgroup_manager)
+        .with_authorizer(Authorizer::attached(conn));
     let path = unit_object_path(name);
     let obj_path = zbus::zvariant::ObjectPath::try_from(path.as_str())
         .map_err(|e| ManagerError::StartFailed(e.to_string()))?;
@@ -164,7 +324,11 @@ async fn build_scope_unit_interface(name: &str, description: &str) -> UnitInterf
         name.to_string(),
         description.to_string(),
     )));
-    unit_state.write().await.set_active();
+    {
+        let mut state = unit_state.write().await;
+        state.set_active();
+        state.unit_file_state = "transient".into();
+    }
     UnitInterface::new(unit_state)
 }
 
@@ -327,6 +491,73 @@ mod tests {
         let _scope_iface = build_scope_interface("session-44.scope", &cgroup_path, &None);
     }
 
+    fn temp_state_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sysd-scopes-{}-{}", tag, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn register_persists_scope_and_unregister_forgets_it() {
+        let dir = temp_state_dir("persist");
+        let mut mgr = ScopeManager::new(None);
+        mgr.set_state_dir(dir.clone());
+
+        mgr.register(
+            "session-5.scope",
+            Some("user-1000.slice"),
+            Some("Session 5"),
+            &[42],
+        )
+        .await
+        .unwrap();
+        mgr.set_controller("session-5.scope", ":1.7");
+
+        let scope = read_scope(&dir, "session-5.scope").unwrap();
+        assert_eq!(scope.slice, "user-1000.slice");
+        assert_eq!(scope.description.as_deref(), Some("Session 5"));
+        assert_eq!(scope.controller.as_deref(), Some(":1.7"));
+        assert_eq!(scope.pids, [42]);
+
+        mgr.unregister("session-5.scope").await.unwrap();
+        assert!(read_scope(&dir, "session-5.scope").is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn adopt_persisted_keeps_populated_scopes_only() {
+        let dir = temp_state_dir("adopt");
+        let live = dir.join("cgroup/session-1.scope");
+        std::fs::create_dir_all(&live).unwrap();
+        std::fs::write(live.join("cgroup.procs"), "100\n101\n").unwrap();
+        let scope = |name: &str, cgroup_path: PathBuf| PersistedScope {
+            name: name.to_string(),
+            slice: "user-1000.slice".to_string(),
+            description: None,
+            controller: None,
+            cgroup_path,
+            pids: vec![1],
+        };
+        write_scope(&dir, &scope("session-1.scope", live.clone())).unwrap();
+        write_scope(&dir, &scope("session-2.scope", dir.join("cgroup/gone"))).unwrap();
+
+        let mut mgr = ScopeManager::new(None);
+        mgr.set_state_dir(dir.clone());
+        let adopted = mgr.adopt_persisted();
+
+        assert_eq!(adopted.len(), 1);
+        assert_eq!(adopted[0].name, "session-1.scope");
+        assert_eq!(adopted[0].pids, [100, 101]);
+        assert_eq!(mgr.get_cgroup_path("session-1.scope"), Some(&live));
+        assert!(!mgr.exists("session-2.scope"));
+        assert_eq!(
+            read_scope(&dir, "session-1.scope").unwrap().pids,
+            [100, 101]
+        );
+        assert!(read_scope(&dir, "session-2.scope").is_none());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
     #[tokio::test]
     async fn register_and_unregister_use_existing_dbus_connection_when_available() {
         let Ok(connection) = zbus::Connection::session().await else {
@@ -0,0 +1,85 @@
+//! `sysd analyze security`: exposure reports of services
+//!
+//! Scoring lives in `units::analyze_security`; this loads the services the
+//! way the manager would start them, drop-ins included.
+
+use crate::units::{self, SecurityReport, Unit};
+
+use super::{Manager, ManagerError};
+
+impl Manager {
+    /// Sandboxing report of a service, under its canonical name
+    pub async fn analyze_security(
+        &mut self,
+        name: &str,
+    ) -> Result<(String, SecurityReport), ManagerError> {
+        let name = self.load(name).await?;
+        match self.units.get(&name) {
+            Some(Unit::Service(service)) => {
+                let report = units::analyze_security(&service.service);
+                Ok((name, report))
+            }
+            _ => Err(ManagerError::NotAService(name)),
+        }
+    }
+
+    /// Reports of every installed service that loads
+    pub async fn analyze_security_all(&mut self) -> Vec<(String, SecurityReport)> {
+        let mut reports = Vec::new();
+        for name in self.service_unit_files() {
+            match self.analyze_security(&name).await {
+                Ok(report) => reports.push(report),
+                Err(e) => log::warn!("Skipping {}: {}", name, e),
+            }
+        }
+        reports
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn services_are_reported_with_drop_ins_and_other_units_refused() {
+        let dir = std::env::temp_dir().join(format!("sysd-security-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("web.service.d")).unwrap();
+        std::fs::write(dir.join("web.service"), "[Service]\nExecStart=/bin/true\n").unwrap();
+        std::fs::write(
+            dir.join("web.service.d/harden.conf"),
+            "[Service]\nNoNewPrivileges=yes\nDynamicUser=yes\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("plain.service"),
+            "[Service]\nExecStart=/bin/true\n",
+        )
+        .unwrap();
+        std::fs::write(dir.join("idle.target"), "[Unit]\nDescription=Idle\n").unwrap();
+        let mut manager = Manager::new_user();
+        manager.unit_paths = vec![dir.clone()];
+
+        let reports = manager.analyze_security_all().await;
+        assert!(matches!(
+            manager.analyze_security("idle.target").await,
+            Err(ManagerError::NotAService(_))
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let names: Vec<&str> = reports.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["plain.service", "web.service"]);
+        let (plain, web) = (&reports[0].1, &reports[1].1);
+        assert!(web.exposure < plain.exposure);
+        let privileges = |report: &SecurityReport| {
+            report
+                .checks
+                .iter()
+                .find(|check| check.directive == "NoNewPrivileges=")
+                .unwrap()
+                .exposure
+        };
+        assert_eq!(privileges(web), 0.0);
+        assert!(privileges(plain) > 0.0);
+    }
+}
@@ -0,0 +1,250 @@
+//! Changing resource limits at runtime (`sysdctl set-property`, D-Bus
+//! SetUnitProperties)
+//!
+//! New limits are written to the unit's cgroup right away and recorded in a
+//! `50-<Key>.conf` drop-in below system.control, so they survive
+//! daemon-reload. With --runtime the drop-in goes to /run and is gone after
+//! a reboot.
+
+use std::path::Path;
+
+use crate::cgroups::CgroupWriter;
+use crate::units::{self, ServiceSection, Unit};
+
+use super::{Manager, ManagerError};
+
+/// A resource limit that can be changed while a unit runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitProperty {
+    /// MemoryMax= in bytes, None for no limit
+    MemoryMax(Option<u64>),
+    /// CPUQuota= in percent of one CPU, None for no quota
+    CpuQuota(Option<u32>),
+    /// TasksMax=, None for no limit
+    TasksMax(Option<u32>),
+}
+
+impl UnitProperty {
+    /// Parse a `Key=value` assignment; "infinity" or an empty value lifts the limit
+    pub fn parse(assignment: &str) -> Result<Self, ManagerError> {
+        let invalid = || ManagerError::InvalidProperty(assignment.to_string());
+        let (key, value) = assignment.split_once('=').ok_or_else(invalid)?;
+        let value = value.trim();
+        let unlimited = value.is_empty() || value.eq_ignore_ascii_case("infinity");
+        let property = match key.trim() {
+            "MemoryMax" if unlimited => Self::MemoryMax(None),
+            "MemoryMax" => Self::MemoryMax(Some(units::parse_memory(value).ok_or_else(invalid)?)),
+            "CPUQuota" if unlimited => Self::CpuQuota(None),
+            "CPUQuota" => Self::CpuQuota(Some(units::parse_cpu_quota(value).ok_or_else(invalid)?)),
+            "TasksMax" if unlimited => Self::TasksMax(None),
+            "TasksMax" => Self::TasksMax(Some(value.parse().map_err(|_| invalid())?)),
+            _ => return Err(invalid()),
+        };
+        Ok(property)
+    }
+
+    /// Unit file key
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::MemoryMax(_) => "MemoryMax",
+            Self::CpuQuota(_) => "CPUQuota",
+            Self::TasksMax(_) => "TasksMax",
+        }
+    }
+
+    /// Drop-in contents; the empty assignment resets whatever the unit
+    /// file and earlier drop-ins set
+    fn dropin(&self) -> String {
+        let value = match *self {
+            Self::MemoryMax(bytes) => bytes.map(|bytes| bytes.to_string()),
+            Self::CpuQuota(percent) => percent.map(|percent| format!("{}%", percent)),
+            Self::TasksMax(max) => max.map(|max| max.to_string()),
+        };
+        let key = self.key();
+        let mut contents = format!("# Written by sysdctl set-property\n[Service]\n{}=\n", key);
+        if let Some(value) = value {
+            contents.push_str(&format!("{}={}\n", key, value));
+        }
+        contents
+    }
+
+    fn apply_to(&self, service: &mut ServiceSection) {
+        match *self {
+            Self::MemoryMax(bytes) => service.memory_max = bytes,
+            Self::CpuQuota(percent) => service.cpu_quota = percent,
+            Self::TasksMax(max) => service.tasks_max = max,
+        }
+    }
+
+    fn queue_write(&self, writer: CgroupWriter) -> CgroupWriter {
+        match *self {
+            Self::MemoryMax(Some(bytes)) => writer.memory_max(bytes),
+            Self::MemoryMax(None) => writer.set("memory.max", "max"),
+            Self::CpuQuota(Some(percent)) => writer.cpu_quota(percent),
+            Self::CpuQuota(None) => writer.set("cpu.max", "max 100000"),
+            Self::TasksMax(max) => writer.tasks_max(max.map_or(u64::MAX, u64::from)),
+        }
+    }
+}
+
+impl Manager {
+    /// Change resource limits of a service: update the loaded unit, write
+    /// its cgroup if it is running and record the change in a drop-in
+    pub async fn set_unit_properties(
+        &mut self,
+        name: &str,
+        properties: &[UnitProperty],
+        runtime: bool,
+    ) -> Result<(), ManagerError> {
+        let name = self.load(name).await?;
+        let Some(Unit::Service(service)) = self.units.get_mut(&name) else {
+            return Err(ManagerError::InvalidProperty(format!(
+                "{} has no resource limits",
+                name
+            )));
+        };
+        for property in properties {
+            property.apply_to(&mut service.service);
+        }
+
+        if let Some(cgroup_path) = self.cgroup_paths.get(&name) {
+            properties
+                .iter()
+                .fold(CgroupWriter::new(cgroup_path), |writer, property| {
+                    property.queue_write(writer)
+                })
+                .apply()
+                .map_err(|e| ManagerError::Io(e.to_string()))?;
+        }
+
+        let root = units::control_dropin_root(self.user_mode, runtime)
+            .ok_or_else(|| ManagerError::Io("no directory for runtime drop-ins".to_string()))?;
+        write_property_dropins(&root.join(format!("{}.d", name)), properties).await?;
+        log::info!(
+            "Set properties of {}: {}",
+            name,
+            properties
+                .iter()
+                .map(UnitProperty::key)
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        Ok(())
+    }
+}
+
+/// One drop-in per property, like systemd's 50-<Key>.conf
+async fn write_property_dropins(
+    dir: &Path,
+    properties: &[UnitProperty],
+) -> Result<(), ManagerError> {
+    tokio::fs::create_dir_all(dir).await?;
+    for property in properties {
+        let path = dir.join(format!("50-{}.conf", property.key()));
+        tokio::fs::write(&path, property.dropin()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Service;
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sysd-set-property-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn assignments_are_parsed_with_units_and_infinity() {
+        assert_eq!(
+            UnitProperty::parse("MemoryMax=512M").unwrap(),
+            UnitProperty::MemoryMax(Some(512 * 1024 * 1024))
+        );
+        assert_eq!(
+            UnitProperty::parse("MemoryMax=infinity").unwrap(),
+            UnitProperty::MemoryMax(None)
+        );
+        assert_eq!(
+            UnitProperty::parse("CPUQuota=150%").unwrap(),
+            UnitProperty::CpuQuota(Some(150))
+        );
+        assert_eq!(
+            UnitProperty::parse("TasksMax=").unwrap(),
+            UnitProperty::TasksMax(None)
+        );
+        assert_eq!(
+            UnitProperty::parse("TasksMax=64").unwrap(),
+            UnitProperty::TasksMax(Some(64))
+        );
+        assert!(UnitProperty::parse("CPUQuota=150").is_err());
+        assert!(UnitProperty::parse("MemoryMax").is_err());
+        assert!(UnitProperty::parse("Nice=5").is_err());
+    }
+
+    #[tokio::test]
+    async fn dropins_reset_then_set_each_property() {
+        let dir = temp_dir("dropins");
+        let properties = [
+            UnitProperty::MemoryMax(Some(1024)),
+            UnitProperty::CpuQuota(None),
+        ];
+        write_property_dropins(&dir.join("demo.service.d"), &properties)
+            .await
+            .unwrap();
+
+        let memory = std::fs::read_to_string(dir.join("demo.service.d/50-MemoryMax.conf")).unwrap();
+        assert!(memory.ends_with("[Service]\nMemoryMax=\nMemoryMax=1024\n"));
+        let cpu = std::fs::read_to_string(dir.join("demo.service.d/50-CPUQuota.conf")).unwrap();
+        assert!(cpu.ends_with("[Service]\nCPUQuota=\n"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn properties_update_the_service_and_its_cgroup() {
+        let cgroup = temp_dir("cgroup");
+        let mut service = Service::new("demo.service".to_string());
+        service.service.memory_max = Some(1024);
+        let properties = [
+            UnitProperty::MemoryMax(None),
+            UnitProperty::CpuQuota(Some(50)),
+            UnitProperty::TasksMax(Some(32)),
+        ];
+
+        let writer = properties
+            .iter()
+            .fold(CgroupWriter::new(&cgroup), |writer, property| {
+                property.apply_to(&mut service.service);
+                property.queue_write(writer)
+            });
+        writer.apply().unwrap();
+
+        assert_eq!(service.service.memory_max, None);
+        assert_eq!(service.service.cpu_quota, Some(50));
+        assert_eq!(service.service.tasks_max, Some(32));
+        let read = |file: &str| std::fs::read_to_string(cgroup.join(file)).unwrap();
+        assert_eq!(read("memory.max"), "max");
+        assert_eq!(read("cpu.max"), "50000 100000");
+        assert_eq!(read("pids.max"), "32");
+        let _ = std::fs::remove_dir_all(&cgroup);
+    }
+
+    #[tokio::test]
+    async fn only_services_have_resource_properties() {
+        let mut manager = Manager::new_user();
+        manager.units.insert(
+            "demo.target".to_string(),
+            Unit::Target(crate::units::Target::new("demo.target".to_string())),
+        );
+        assert!(matches!(
+            manager
+                .set_unit_properties("demo.target", &[UnitProperty::TasksMax(Some(4))], true)
+                .await,
+            Err(ManagerError::InvalidProperty(_))
+        ));
+    }
+}
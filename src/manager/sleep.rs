@@ -0,0 +1,272 @@
+//! Suspend and hibernation (`sysdctl suspend`, `hibernate`, `hybrid-sleep`)
+//!
+//! A request goes to login1 when it runs, so its inhibitors and
+//! PrepareForSleep apply: systemd-logind when installed (it starts the mode's
+//! target on sysd, which needs systemd-sleep), else sysd's own login1 (see
+//! `dbus::login`), which comes back to `Manager::sleep_now`.
+//!
+//! When systemd-sleep is installed, the mode's target pulls in
+//! systemd-suspend.service (etc.), which runs the whole sequence. Without it,
+//! sysd sleeps itself: start sleep.target (pre-sleep units), write
+//! /sys/power/state, then start the mode's target (resume units ordered
+//! after it) and stop both targets so the next sleep runs the hooks again.
+//! The manager lock is released while the machine is asleep.
+
+use std::path::Path;
+
+use tokio::sync::RwLock;
+
+use super::{Manager, ManagerError};
+
+const POWER_DIR: &str = "/sys/power";
+const SLEEP_TARGET: &str = "sleep.target";
+const LOGIN1_NAME: &str = "org.freedesktop.login1";
+const LOGIND_SERVICE: &str = "systemd-logind.service";
+
+/// Sleep operation requested with sysdctl
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepMode {
+    Suspend,
+    Hibernate,
+    HybridSleep,
+}
+
+impl SleepMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "suspend" => Some(Self::Suspend),
+            "hibernate" => Some(Self::Hibernate),
+            "hybrid-sleep" => Some(Self::HybridSleep),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Suspend => "suspend",
+            Self::Hibernate => "hibernate",
+            Self::HybridSleep => "hybrid-sleep",
+        }
+    }
+
+    fn target(self) -> String {
+        format!("{}.target", self.as_str())
+    }
+
+    /// systemd-sleep's service for this mode
+    fn sleep_service(self) -> String {
+        format!("systemd-{}.service", self.as_str())
+    }
+
+    /// org.freedesktop.login1.Manager method for this mode
+    fn logind_method(self) -> &'static str {
+        match self {
+            Self::Suspend => "Suspend",
+            Self::Hibernate => "Hibernate",
+            Self::HybridSleep => "HybridSleep",
+        }
+    }
+
+    /// Value written to /sys/power/state
+    fn state(self) -> &'static str {
+        match self {
+            Self::Suspend => "mem",
+            Self::Hibernate | Self::HybridSleep => "disk",
+        }
+    }
+
+    /// Whether the kernel can sleep this way
+    pub fn supported(self) -> bool {
+        can_sleep(Path::new(POWER_DIR), self)
+    }
+
+    /// Value written to /sys/power/disk first, if any
+    fn disk_mode(self) -> Option<&'static str> {
+        match self {
+            Self::HybridSleep => Some("suspend"),
+            Self::Suspend | Self::Hibernate => None,
+        }
+    }
+}
+
+/// Whether the kernel offers `mode` (listed in <power_dir>/state, and for
+/// hybrid sleep the suspend disk mode)
+fn can_sleep(power_dir: &Path, mode: SleepMode) -> bool {
+    let listed = |file: &str, word: &str| {
+        std::fs::read_to_string(power_dir.join(file))
+            .map(|content| {
+                content
+                    .split_whitespace()
+                    .any(|w| w.trim_matches(|c| c == '[' || c == ']') == word)
+            })
+            .unwrap_or(false)
+    };
+    listed("state", mode.state()) && mode.disk_mode().is_none_or(|disk| listed("disk", disk))
+}
+
+/// Hand the request to login1, which takes its inhibitors into account,
+/// sends PrepareForSleep and then sleeps. None if login1 is not running.
+async fn logind_sleep(mode: SleepMode) -> Option<Result<(), ManagerError>> {
+    let conn = zbus::Connection::system().await.ok()?;
+    conn.call_method(
+        Some("org.freedesktop.DBus"),
+        "/org/freedesktop/DBus",
+        Some("org.freedesktop.DBus"),
+        "GetNameOwner",
+        &(LOGIN1_NAME,),
+    )
+    .await
+    .ok()?;
+    log::info!("Asking login1 to {}", mode.as_str());
+    let reply = conn
+        .call_method(
+            Some(LOGIN1_NAME),
+            "/org/freedesktop/login1",
+            Some("org.freedesktop.login1.Manager"),
+            mode.logind_method(),
+            &(false,),
+        )
+        .await;
+    let refused = |e: zbus::Error| ManagerError::SleepRefused(mode.as_str().into(), e.to_string());
+    Some(reply.map(|_| ()).map_err(refused))
+}
+
+/// Put the machine to sleep; returns after resume
+fn enter_sleep(power_dir: &Path, mode: SleepMode) -> std::io::Result<()> {
+    if let Some(disk) = mode.disk_mode() {
+        std::fs::write(power_dir.join("disk"), disk)?;
+    }
+    std::fs::write(power_dir.join("state"), mode.state())
+}
+
+impl Manager {
+    /// Suspend, hibernate or hybrid-sleep the machine, through login1 when
+    /// it runs
+    pub async fn sleep(manager: &RwLock<Manager>, mode: SleepMode) -> Result<(), ManagerError> {
+        let (has_sleep_service, logind_installed) = {
+            let mgr = manager.read().await;
+            (
+                mgr.unit_file_exists(&mode.sleep_service()),
+                mgr.unit_file_exists(LOGIND_SERVICE),
+            )
+        };
+        // systemd-logind can only sleep through systemd-sleep; sysd's login1
+        // sleeps either way
+        if has_sleep_service || !logind_installed {
+            if let Some(result) = logind_sleep(mode).await {
+                return result;
+            }
+        }
+        Self::sleep_now(manager, mode).await
+    }
+
+    /// Sleep without asking login1, i.e. without regard to inhibitors
+    ///
+    /// Takes the lock itself and never holds it while the machine sleeps, so
+    /// whatever the sleep target starts can report back and resume units can
+    /// run.
+    pub async fn sleep_now(manager: &RwLock<Manager>, mode: SleepMode) -> Result<(), ManagerError> {
+        let target = mode.target();
+        let has_sleep_service = manager.read().await.unit_file_exists(&mode.sleep_service());
+        if has_sleep_service {
+            let mut mgr = manager.write().await;
+            mgr.stop_sleep_targets(&target).await;
+            mgr.start_with_deps(&target).await?;
+            return Ok(());
+        }
+
+        if !mode.supported() {
+            return Err(ManagerError::SleepUnsupported(mode.as_str().to_string()));
+        }
+        {
+            let mut mgr = manager.write().await;
+            mgr.start_sleep_target(SLEEP_TARGET).await?;
+            mgr.publish_states();
+        }
+        log::info!("Entering {}", mode.as_str());
+        let result = tokio::task::spawn_blocking(move || enter_sleep(Path::new(POWER_DIR), mode))
+            .await
+            .map_err(|e| ManagerError::Io(e.to_string()))?;
+        log::info!("Resumed from {}", mode.as_str());
+
+        let mut mgr = manager.write().await;
+        if let Err(e) = mgr.start_sleep_target(&target).await {
+            log::warn!("Failed to start {} after resume: {}", target, e);
+        }
+        mgr.stop_sleep_targets(&target).await;
+        result.map_err(ManagerError::from)
+    }
+
+    /// Start a sleep target with its dependencies; a missing target is fine
+    async fn start_sleep_target(&mut self, target: &str) -> Result<(), ManagerError> {
+        match self.start_with_deps(target).await {
+            Ok(_) | Err(ManagerError::NotFound(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn stop_sleep_targets(&mut self, target: &str) {
+        for name in [target, SLEEP_TARGET] {
+            if self.states.get(name).is_some_and(|state| state.is_active()) {
+                let _ = self.stop(name).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modes_parse_and_map_to_kernel_states() {
+        assert_eq!(
+            SleepMode::parse("hybrid-sleep"),
+            Some(SleepMode::HybridSleep)
+        );
+        assert_eq!(SleepMode::parse("poweroff"), None);
+        assert_eq!(SleepMode::Suspend.target(), "suspend.target");
+        assert_eq!(
+            SleepMode::Hibernate.sleep_service(),
+            "systemd-hibernate.service"
+        );
+        assert_eq!(SleepMode::Suspend.state(), "mem");
+        assert_eq!(SleepMode::HybridSleep.disk_mode(), Some("suspend"));
+    }
+
+    #[test]
+    fn sleep_writes_disk_mode_then_state() {
+        let dir = std::env::temp_dir().join(format!("sysd-sleep-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("state"), "freeze mem disk\n").unwrap();
+        std::fs::write(dir.join("disk"), "[platform] shutdown reboot\n").unwrap();
+
+        assert!(can_sleep(&dir, SleepMode::Suspend));
+        assert!(can_sleep(&dir, SleepMode::Hibernate));
+        assert!(!can_sleep(&dir, SleepMode::HybridSleep));
+
+        std::fs::write(dir.join("disk"), "[platform] shutdown suspend\n").unwrap();
+        assert!(can_sleep(&dir, SleepMode::HybridSleep));
+        enter_sleep(&dir, SleepMode::HybridSleep).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("disk")).unwrap(),
+            "suspend"
+        );
+        assert_eq!(std::fs::read_to_string(dir.join("state")).unwrap(), "disk");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn sleep_is_refused_without_kernel_support_or_systemd_sleep() {
+        let mut manager = Manager::new_user();
+        manager.unit_paths.clear();
+        if can_sleep(Path::new(POWER_DIR), SleepMode::HybridSleep) {
+            return;
+        }
+        let manager = RwLock::new(manager);
+        assert!(matches!(
+            Manager::sleep_now(&manager, SleepMode::HybridSleep).await,
+            Err(ManagerError::SleepUnsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,295 @@
+//! Read-only view of unit states
+//!
+//! The manager sits behind a single lock, so a slow mutation (mount, stop with
+//! timeout) would otherwise stall every status query. The manager publishes a
+//! copy of its state table after each change batch; `StateView` readers only
+//! touch that copy and never wait on the manager lock.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use super::{ActiveState, Manager, SubState};
+
+/// Published state of a single unit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitSnapshot {
+    pub unit_type: &'static str,
+    /// "loaded", or "not-found"/"error" for dependencies that failed to load
+    pub load_state: &'static str,
+    pub description: Option<String>,
+    pub active: ActiveState,
+    pub sub: SubState,
+    pub main_pid: Option<u32>,
+    pub exit_code: Option<i32>,
+    /// Unit file or drop-ins changed on disk since the unit was loaded
+    pub need_daemon_reload: bool,
+    /// Unit file the unit was loaded from
+    pub fragment_path: Option<PathBuf>,
+    /// Documentation= URIs
+    pub documentation: Vec<String>,
+    /// Every name the unit is known by, canonical name first
+    pub names: Vec<String>,
+    /// Unmet condition that made the last start a no-op
+    pub condition_failure: Option<String>,
+    /// Dependency whose failure kept the last start from running
+    pub failed_dependency: Option<String>,
+    /// Why the unit failed, while it is failed
+    pub error: Option<String>,
+}
+
+pub(super) type StateTable = BTreeMap<String, UnitSnapshot>;
+
+/// Cheap, cloneable handle for reading the last published unit states
+#[derive(Debug, Clone)]
+pub struct StateView {
+    rx: watch::Receiver<Arc<StateTable>>,
+}
+
+impl StateView {
+    /// State of a unit (names without a suffix are treated as services,
+    /// aliases resolve to the unit they name)
+    pub fn get(&self, name: &str) -> Option<UnitSnapshot> {
+        find_unit(&self.rx.borrow(), name)
+    }
+
+    /// Wait for the published state of `name` to satisfy `done` and return
+    /// it; None once the unit is no longer published or the manager is gone
+    pub async fn wait_until(
+        &mut self,
+        name: &str,
+        done: impl Fn(&UnitSnapshot) -> bool,
+    ) -> Option<UnitSnapshot> {
+        loop {
+            let unit = find_unit(&self.rx.borrow_and_update(), name)?;
+            if done(&unit) {
+                return Some(unit);
+            }
+            self.rx.changed().await.ok()?;
+        }
+    }
+
+    /// Wait for the next publish; false once the manager is gone
+    pub async fn changed(&mut self) -> bool {
+        self.rx.changed().await.is_ok()
+    }
+
+    /// All published units, sorted by name
+    pub fn list(&self) -> Vec<(String, UnitSnapshot)> {
+        self.rx
+            .borrow()
+            .iter()
+            .map(|(name, unit)| (name.clone(), unit.clone()))
+            .collect()
+    }
+}
+
+fn find_unit(table: &StateTable, name: &str) -> Option<UnitSnapshot> {
+    let service = format!("{}.service", name);
+    table
+        .get(name)
+        .or_else(|| table.get(&service))
+        .or_else(|| {
+            table
+                .values()
+                .find(|unit| unit.names.iter().any(|n| *n == name || *n == service))
+        })
+        .cloned()
+}
+
+pub(super) fn state_sender() -> watch::Sender<Arc<StateTable>> {
+    watch::Sender::new(Arc::new(StateTable::new()))
+}
+
+impl Manager {
+    /// Handle for lock-free state queries (IPC status/list, D-Bus reads)
+    pub fn state_view(&self) -> StateView {
+        StateView {
+            rx: self.state_tx.subscribe(),
+        }
+    }
+
+    /// Publish the current unit states to all `StateView`s (no-op when unchanged)
+    pub fn publish_states(&self) {
+        let table = self.build_state_table();
+        self.state_tx.send_if_modified(|current| {
+            if **current == table {
+                return false;
+            }
+            *current = Arc::new(table);
+            true
+        });
+    }
+
+    fn build_state_table(&self) -> StateTable {
+        let mut table = StateTable::new();
+        for (name, unit) in &self.units {
+            let state = self.states.get(name);
+            table.insert(
+                name.clone(),
+                UnitSnapshot {
+                    unit_type: unit.unit_type(),
+                    load_state: "loaded",
+                    description: unit.unit_section().description.clone(),
+                    active: state.map_or(ActiveState::Inactive, |s| s.active),
+                    sub: state.map_or(SubState::Dead, |s| s.sub),
+                    main_pid: state.and_then(|s| s.main_pid),
+                    exit_code: state.and_then(|s| s.exit_code),
+                    need_daemon_reload: self.need_daemon_reload.contains(name),
+                    fragment_path: self.fragment_paths.get(name).cloned(),
+                    documentation: unit.unit_section().documentation.clone(),
+                    names: self.unit_names(name),
+                    condition_failure: state.and_then(|s| s.condition_failure.clone()),
+                    failed_dependency: state.and_then(|s| s.failed_dependency.clone()),
+                    error: state.and_then(|s| s.error.clone()),
+                },
+            );
+        }
+        // Transient scopes only have runtime state, placeholders for missing
+        // dependencies only a name
+        for (name, state) in &self.states {
+            let placeholder = self.placeholders.contains(name);
+            table.entry(name.clone()).or_insert_with(|| UnitSnapshot {
+                unit_type: if placeholder {
+                    unit_type_of(name)
+                } else {
+                    "scope"
+                },
+                load_state: if placeholder {
+                    self.load_state(name)
+                } else {
+                    "loaded"
+                },
+                description: None,
+                active: state.active,
+                sub: state.sub,
+                main_pid: state.main_pid,
+                exit_code: state.exit_code,
+                need_daemon_reload: false,
+                fragment_path: None,
+                documentation: Vec::new(),
+                names: vec![name.clone()],
+                condition_failure: None,
+                failed_dependency: None,
+                error: state.error.clone(),
+            });
+        }
+        table
+    }
+}
+
+/// Unit type a unit name's suffix stands for
+fn unit_type_of(name: &str) -> &'static str {
+    match name.rsplit_once('.').map(|(_, suffix)| suffix) {
+        Some("target") => "target",
+        Some("mount") => "mount",
+        Some("slice") => "slice",
+        Some("socket") => "socket",
+        Some("timer") => "timer",
+        Some("path") => "path",
+        Some("scope") => "scope",
+        _ => "service",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+    use crate::units::{Service, Unit};
+
+    #[test]
+    fn published_states_are_visible_to_views() {
+        let mut manager = Manager::new_user();
+        let view = manager.state_view();
+        manager.units.insert(
+            "demo.service".to_string(),
+            Unit::Service(Service::new("demo.service".to_string())),
+        );
+        let mut state = ServiceState::new();
+        state.set_running(42);
+        manager.states.insert("demo.service".to_string(), state);
+        manager
+            .states
+            .insert("session-1.scope".to_string(), ServiceState::running_scope());
+
+        assert!(view.get("demo").is_none());
+        manager.publish_states();
+
+        let demo = view.get("demo").unwrap();
+        assert_eq!(demo.unit_type, "service");
+        assert_eq!(demo.active, ActiveState::Active);
+        assert_eq!(demo.main_pid, Some(42));
+        let names: Vec<String> = view.list().into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["demo.service", "session-1.scope"]);
+        assert_eq!(view.get("session-1.scope").unwrap().unit_type, "scope");
+    }
+
+    #[test]
+    fn snapshots_list_every_name_and_resolve_aliases() {
+        let mut manager = Manager::new_user();
+        manager.units.insert(
+            "real.service".to_string(),
+            Unit::Service(Service::new("real.service".to_string())),
+        );
+        for alias in ["nick.service", "alt.service"] {
+            manager
+                .aliases
+                .insert(alias.to_string(), "real.service".to_string());
+        }
+        manager.publish_states();
+        let view = manager.state_view();
+
+        let real = view.get("real").unwrap();
+        assert_eq!(real.names, ["real.service", "alt.service", "nick.service"]);
+        assert_eq!(view.get("nick").unwrap(), real);
+        assert_eq!(view.list().len(), 1);
+    }
+
+    #[test]
+    fn views_keep_last_snapshot_until_next_publish() {
+        let mut manager = Manager::new_user();
+        manager.units.insert(
+            "demo.service".to_string(),
+            Unit::Service(Service::new("demo.service".to_string())),
+        );
+        manager.publish_states();
+        let view = manager.state_view();
+
+        manager
+            .states
+            .insert("demo.service".to_string(), ServiceState::running_scope());
+        assert_eq!(view.get("demo").unwrap().active, ActiveState::Inactive);
+
+        manager.publish_states();
+        assert_eq!(view.get("demo").unwrap().active, ActiveState::Active);
+    }
+
+    #[tokio::test]
+    async fn wait_until_follows_published_changes() {
+        let mut manager = Manager::new_user();
+        manager.units.insert(
+            "demo.service".to_string(),
+            Unit::Service(Service::new("demo.service".to_string())),
+        );
+        let mut state = ServiceState::new();
+        state.set_starting();
+        manager.states.insert("demo.service".to_string(), state);
+        manager.publish_states();
+        let mut view = manager.state_view();
+        let waiter = tokio::spawn(async move {
+            view.wait_until("demo", |unit| unit.active != ActiveState::Activating)
+                .await
+        });
+
+        tokio::task::yield_now().await;
+        let state = manager.states.get_mut("demo.service").unwrap();
+        state.set_failed("exit code 3".to_string());
+        manager.publish_states();
+        let demo = waiter.await.unwrap().unwrap();
+        assert_eq!(demo.active, ActiveState::Failed);
+        assert_eq!(demo.error.as_deref(), Some("exit code 3"));
+    }
+}
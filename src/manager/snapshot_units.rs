@@ -0,0 +1,178 @@
+//! Snapshots of the set of active units (`sysdctl snapshot`,
+//! `sysdctl isolate NAME.snapshot`)
+//!
+//! Like the snapshot units systemd had until v228: a snapshot records which
+//! units are active, and isolating it later stops every other unit (but
+//! those with IgnoreOnIsolate=yes) and starts the recorded ones that are
+//! down, e.g. to return to normal after a maintenance window stopped a
+//! bunch of services. Accept=yes connection instances are not recorded;
+//! they cannot be started again. Snapshots live in memory and are gone
+//! once the manager exits; recording one under an existing name replaces
+//! it.
+
+use super::{Manager, ManagerError};
+
+const SUFFIX: &str = ".snapshot";
+
+impl Manager {
+    /// Record the active units as snapshot `name` (".snapshot" is added),
+    /// or as the first free snapshot-N.snapshot; returns its name
+    pub fn create_snapshot(&mut self, name: Option<&str>) -> String {
+        let name = match name {
+            Some(name) if name.ends_with(SUFFIX) => name.to_string(),
+            Some(name) => format!("{}{}", name, SUFFIX),
+            None => (1..)
+                .map(|n| format!("snapshot-{}{}", n, SUFFIX))
+                .find(|name| !self.snapshots.contains_key(name))
+                .unwrap_or_default(),
+        };
+        let mut units: Vec<String> = self
+            .states
+            .iter()
+            .filter(|(unit, state)| {
+                state.is_active()
+                    && self.units.contains_key(*unit)
+                    && !self.connection_instances.contains(*unit)
+            })
+            .map(|(unit, _)| unit.clone())
+            .collect();
+        units.sort();
+        log::info!("Recorded {} active units as {}", units.len(), name);
+        self.snapshots.insert(name.clone(), units);
+        name
+    }
+
+    /// Forget snapshot `name`
+    pub fn delete_snapshot(&mut self, name: &str) -> Result<(), ManagerError> {
+        let name = snapshot_name(name);
+        match self.snapshots.remove(&name) {
+            Some(_) => Ok(()),
+            None => Err(ManagerError::NotFound(name)),
+        }
+    }
+
+    /// Units recorded in snapshot `name`, sorted
+    pub fn snapshot(&self, name: &str) -> Option<&[String]> {
+        self.snapshots.get(&snapshot_name(name)).map(Vec::as_slice)
+    }
+
+    /// Stop the active units snapshot `name` does not have and start those
+    /// it has; returns the units stopped
+    pub(super) async fn isolate_snapshot(
+        &mut self,
+        name: &str,
+    ) -> Result<Vec<String>, ManagerError> {
+        let units = self
+            .snapshot(name)
+            .ok_or_else(|| ManagerError::NotFound(name.to_string()))?
+            .to_vec();
+        let ignored = |manager: &Manager, unit: &str| {
+            manager
+                .units
+                .get(unit)
+                .is_some_and(|u| u.unit_section().ignore_on_isolate)
+        };
+        let mut to_stop: Vec<String> = self
+            .states
+            .iter()
+            .filter(|(unit, state)| {
+                state.is_active() && !units.contains(*unit) && !ignored(self, unit)
+            })
+            .map(|(unit, _)| unit.clone())
+            .collect();
+        to_stop.sort();
+
+        for unit in &to_stop {
+            log::info!("Stopping {} (not in {})", unit, name);
+            if let Err(e) = self.enqueue_stop(unit).await {
+                log::warn!("Failed to stop {}: {}", unit, e);
+            }
+        }
+        for unit in &units {
+            if self.states.get(unit).is_some_and(|state| state.is_active()) {
+                continue;
+            }
+            if let Err(e) = self.start_with_deps(unit).await {
+                log::warn!("Failed to start {} of {}: {}", unit, name, e);
+            }
+        }
+        Ok(to_stop)
+    }
+}
+
+/// Whether `name` names a snapshot rather than a target
+pub(super) fn is_snapshot(name: &str) -> bool {
+    name.ends_with(SUFFIX)
+}
+
+fn snapshot_name(name: &str) -> String {
+    if is_snapshot(name) {
+        name.to_string()
+    } else {
+        format!("{}{}", name, SUFFIX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::{ActiveState, ServiceState};
+    use crate::units::{Target, Timer, Unit};
+
+    fn add(manager: &mut Manager, unit: Unit, active: bool) {
+        let name = unit.name().to_string();
+        manager.insert_unit(name.clone(), unit);
+        let mut state = ServiceState::new();
+        if active {
+            state.set_running(0);
+        }
+        manager.states.insert(name, state);
+    }
+
+    fn timer(name: &str) -> Unit {
+        Unit::Timer(Timer::new(name.to_string()))
+    }
+
+    #[test]
+    fn snapshots_record_active_loaded_units_under_their_name() {
+        let mut manager = Manager::new_user();
+        add(&mut manager, timer("a.timer"), true);
+        add(&mut manager, timer("b.timer"), false);
+        manager
+            .states
+            .insert("session-1.scope".into(), ServiceState::running_scope());
+
+        assert_eq!(manager.create_snapshot(None), "snapshot-1.snapshot");
+        assert_eq!(manager.create_snapshot(None), "snapshot-2.snapshot");
+        assert_eq!(manager.create_snapshot(Some("maint")), "maint.snapshot");
+        assert_eq!(manager.snapshot("maint").unwrap(), ["a.timer"]);
+
+        manager.delete_snapshot("snapshot-1").unwrap();
+        assert!(manager.snapshot("snapshot-1.snapshot").is_none());
+        assert!(manager.delete_snapshot("snapshot-1").is_err());
+        assert_eq!(manager.create_snapshot(None), "snapshot-1.snapshot");
+    }
+
+    #[tokio::test]
+    async fn isolating_a_snapshot_stops_the_others_and_starts_its_units() {
+        let mut manager = Manager::new_user();
+        let app = Unit::Target(Target::new("app.target".to_string()));
+        add(&mut manager, app, true);
+        add(&mut manager, timer("backup.timer"), false);
+        let mut kept = Timer::new("kept.timer".to_string());
+        kept.unit.ignore_on_isolate = true;
+        add(&mut manager, Unit::Timer(kept), true);
+        manager.create_snapshot(Some("normal"));
+
+        let states = &mut manager.states;
+        states.get_mut("app.target").unwrap().set_stopped(0);
+        states.get_mut("backup.timer").unwrap().set_running(0);
+
+        let stopped = manager.switch_target("normal.snapshot").await.unwrap();
+        assert_eq!(stopped, ["backup.timer"]);
+        assert_eq!(manager.states["backup.timer"].active, ActiveState::Inactive);
+        assert_eq!(manager.states["app.target"].active, ActiveState::Active);
+        assert!(manager.states["kept.timer"].is_active());
+        assert!(manager.switch_target("gone.snapshot").await.is_err());
+    }
+}
@@ -0,0 +1,62 @@
+//! SocketBindAllow=/SocketBindDeny= of services
+//!
+//! Attached next to the IP firewall, while sysd-executor still waits at its
+//! exec gate, so the service cannot bind before the filter is in place.
+//! Like the firewall it is skipped with a warning when the service has no
+//! cgroup or the kernel lacks cgroup-bpf.
+
+use crate::cgroups::SocketBindFilter;
+use crate::units::Service;
+
+use super::{Feature, Manager};
+
+impl Manager {
+    /// Attach the bind filter of `service` to its cgroup, replacing the one of
+    /// an earlier run
+    pub(super) fn attach_socket_bind_filter(&mut self, name: &str, service: &Service) {
+        self.socket_bind_filters.remove(name);
+        let allow = &service.service.socket_bind_allow;
+        let deny = &service.service.socket_bind_deny;
+        if allow.is_empty() && deny.is_empty() {
+            return;
+        }
+        if !self.can_enforce(name, Feature::CgroupBpf, "SocketBindAllow=/SocketBindDeny=") {
+            return;
+        }
+        let Some(cgroup_path) = self.cgroup_paths.get(name) else {
+            log::warn!(
+                "{}: no cgroup, SocketBindAllow=/SocketBindDeny= not enforced",
+                name
+            );
+            return;
+        };
+        match SocketBindFilter::attach(cgroup_path, allow, deny) {
+            Ok(filter) => {
+                log::debug!("Attached bind filter to {}", cgroup_path.display());
+                self.socket_bind_filters.insert(name.to_string(), filter);
+            }
+            Err(e) => log::warn!(
+                "{}: SocketBindAllow=/SocketBindDeny= not enforced, cgroup BPF unavailable: {}",
+                name,
+                e
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{parse_file, parse_service};
+
+    #[test]
+    fn bind_filter_is_skipped_without_a_cgroup() {
+        let mut manager = Manager::new_user();
+        let parsed = parse_file("[Service]\nExecStart=/bin/true\nSocketBindDeny=any\n").unwrap();
+        let service = parse_service("bind.service", &parsed).unwrap();
+        assert_eq!(service.service.socket_bind_deny.len(), 1);
+
+        manager.attach_socket_bind_filter("bind.service", &service);
+        assert!(manager.socket_bind_filters.is_empty());
+    }
+}
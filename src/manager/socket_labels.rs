@@ -0,0 +1,246 @@
+//! MAC labels of socket units
+//!
+//! On Smack systems SmackLabel= labels the file of a socket or FIFO, and
+//! SmackLabelIPIn=/SmackLabelIPOut= the packets a socket receives and sends.
+//! Like in systemd a label that cannot be set only gets a warning. With
+//! SELinuxContextFromNet=, an Accept=yes instance runs in the context it
+//! would get anyway, but at the MLS level of the peer it serves.
+
+use std::ffi::CString;
+use std::io::{Read, Write};
+use std::os::unix::io::RawFd;
+
+use crate::units::{ListenType, Listener, MacLabel, Service, Socket, Unit};
+
+use super::{Feature, Manager};
+
+const SMACK_FILE: &str = "security.SMACK64";
+const SMACK_IP_IN: &str = "security.SMACK64IPIN";
+const SMACK_IP_OUT: &str = "security.SMACK64IPOUT";
+const SELINUX_FILE: &str = "security.selinux";
+
+impl Manager {
+    /// Apply the Smack labels of socket unit `name` to the listener just
+    /// created on `fd`
+    pub(super) fn label_listener(
+        &self,
+        name: &str,
+        socket: &Socket,
+        listener: &Listener,
+        fd: RawFd,
+    ) {
+        let section = &socket.socket;
+        let labeled = section.smack_label.is_some()
+            || section.smack_label_ip_in.is_some()
+            || section.smack_label_ip_out.is_some();
+        if !labeled || !self.can_enforce(name, Feature::Smack, "SmackLabel*=") {
+            return;
+        }
+        let mut results = Vec::new();
+        if let (Some(label), Some(path)) = (&section.smack_label, listener_path(listener)) {
+            results.push(set_path_xattr(path, SMACK_FILE, label));
+        }
+        if listener.listen_type != ListenType::Fifo {
+            if let Some(label) = &section.smack_label_ip_in {
+                results.push(set_fd_xattr(fd, SMACK_IP_IN, label));
+            }
+            if let Some(label) = &section.smack_label_ip_out {
+                results.push(set_fd_xattr(fd, SMACK_IP_OUT, label));
+            }
+        }
+        for error in results.into_iter().filter_map(Result::err) {
+            log::warn!("{}: failed to label {}: {}", name, listener.address, error);
+        }
+    }
+
+    /// SELinuxContextFromNet=: have `instance`, started by `socket_name` for
+    /// the connection `fd`, run at the level of the peer
+    pub(super) fn label_connection_instance(
+        &mut self,
+        socket_name: &str,
+        instance: &str,
+        fd: RawFd,
+    ) {
+        let from_net = self
+            .units
+            .get(socket_name)
+            .and_then(|unit| unit.as_socket())
+            .is_some_and(|socket| socket.socket.selinux_context_from_net);
+        if !from_net || !self.can_enforce(instance, Feature::Selinux, "SELinuxContextFromNet=") {
+            return;
+        }
+        let Some(Unit::Service(service)) = self.units.get_mut(instance) else {
+            return;
+        };
+        match connection_context(service, fd) {
+            Ok(label) => {
+                let ignore_failure = service
+                    .service
+                    .selinux_context
+                    .as_ref()
+                    .is_some_and(|context| context.ignore_failure);
+                service.service.selinux_context = Some(MacLabel {
+                    label,
+                    ignore_failure,
+                });
+            }
+            Err(e) => log::warn!("{}: SELinuxContextFromNet= not applied: {}", instance, e),
+        }
+    }
+}
+
+/// The file a listener is bound to, if it has one
+fn listener_path(listener: &Listener) -> Option<&str> {
+    let has_file = matches!(
+        listener.listen_type,
+        ListenType::Stream | ListenType::Datagram | ListenType::Fifo | ListenType::SequentialPacket
+    );
+    (has_file && listener.address.starts_with('/')).then_some(listener.address.as_str())
+}
+
+/// The context of `service` (SELinuxContext=, or the one its program would
+/// get) with the MLS range of the peer on `fd`
+fn connection_context(service: &Service, fd: RawFd) -> Result<String, String> {
+    let peer = peer_context(fd).map_err(|e| format!("no peer context: {}", e))?;
+    let context = match &service.service.selinux_context {
+        Some(context) => context.label.clone(),
+        None => exec_context(service)?,
+    };
+    Ok(with_peer_range(&context, &peer))
+}
+
+/// Replace the range of `context` (user:role:type:range) with the one of
+/// `peer`; without MLS there is none to take
+fn with_peer_range(context: &str, peer: &str) -> String {
+    let fields: Vec<&str> = context.splitn(4, ':').collect();
+    match peer.splitn(4, ':').nth(3) {
+        Some(range) if fields.len() >= 3 => format!("{}:{}", fields[..3].join(":"), range),
+        _ => context.to_string(),
+    }
+}
+
+/// The context the first ExecStart= program runs in when the manager
+/// executes it, as computed by the policy
+fn exec_context(service: &Service) -> Result<String, String> {
+    let program = service
+        .service
+        .exec_start
+        .first()
+        .map(|command| command.trim_start_matches(['-', '@', '+', '!']))
+        .and_then(shlex::split)
+        .and_then(|words| words.into_iter().next())
+        .ok_or_else(|| "no ExecStart= program".to_string())?;
+    let manager = std::fs::read_to_string("/proc/self/attr/current")
+        .map_err(|e| format!("no context of our own: {}", e))?;
+    let file = get_path_xattr(&program, SELINUX_FILE)
+        .map_err(|e| format!("no context of {}: {}", program, e))?;
+    compute_create(&trim_context(&manager), &file, "process")
+        .map_err(|e| format!("policy has no context for {}: {}", program, e))
+}
+
+/// security_compute_create(3) through selinuxfs
+fn compute_create(source: &str, target: &str, class: &str) -> std::io::Result<String> {
+    let index = std::fs::read_to_string(format!("/sys/fs/selinux/class/{}/index", class))?;
+    let mut create = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/sys/fs/selinux/create")?;
+    create.write_all(format!("{} {} {}", source, target, index.trim()).as_bytes())?;
+    let mut context = String::new();
+    create.read_to_string(&mut context)?;
+    Ok(trim_context(&context))
+}
+
+/// SO_PEERSEC: the context of the process on the other end of `fd`
+fn peer_context(fd: RawFd) -> std::io::Result<String> {
+    let mut buffer = [0u8; 256];
+    let mut len = buffer.len() as libc::socklen_t;
+    let rc = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERSEC,
+            buffer.as_mut_ptr().cast(),
+            &mut len,
+        )
+    };
+    if rc < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let context = String::from_utf8_lossy(&buffer[..len as usize]);
+    Ok(trim_context(&context))
+}
+
+fn trim_context(raw: &str) -> String {
+    raw.trim_end_matches(['\0', '\n']).to_string()
+}
+
+fn set_path_xattr(path: &str, name: &str, value: &str) -> std::io::Result<()> {
+    let path = CString::new(path)?;
+    let name = CString::new(name)?;
+    let rc = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    if rc < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn set_fd_xattr(fd: RawFd, name: &str, value: &str) -> std::io::Result<()> {
+    let name = CString::new(name)?;
+    let rc = unsafe { libc::fsetxattr(fd, name.as_ptr(), value.as_ptr().cast(), value.len(), 0) };
+    if rc < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn get_path_xattr(path: &str, name: &str) -> std::io::Result<String> {
+    let path = CString::new(path)?;
+    let name = CString::new(name)?;
+    let mut buffer = [0u8; 256];
+    let len = unsafe {
+        libc::getxattr(
+            path.as_ptr(),
+            name.as_ptr(),
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+        )
+    };
+    if len < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let context = String::from_utf8_lossy(&buffer[..len as usize]);
+    Ok(trim_context(&context))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instances_take_the_range_of_the_peer() {
+        assert_eq!(
+            with_peer_range(
+                "system_u:system_r:demo_t:s0",
+                "user_u:user_r:user_t:s0:c1,c2"
+            ),
+            "system_u:system_r:demo_t:s0:c1,c2"
+        );
+        assert_eq!(
+            with_peer_range("system_u:system_r:demo_t", "user_u:user_r:user_t"),
+            "system_u:system_r:demo_t"
+        );
+        assert_eq!(
+            with_peer_range("system_u:system_r:demo_t:s0", "s1-s2:c0.c1023"),
+            "system_u:system_r:demo_t:s0"
+        );
+    }
+}
@@ -2,16 +2,74 @@
 //
 // Handles creation and management of listening sockets for socket activation.
 
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
 use std::os::unix::fs::PermissionsExt;
 use std::os::unix::io::{AsRawFd, RawFd};
 
 use tokio::sync::mpsc;
 
-use crate::units::{ListenType, Listener, Socket};
+use crate::units::{self, ListenType, Listener, Socket, SocketProtocol};
+
+use super::socket_watcher::{self, AcceptedConnection};
+use super::{ActiveState, Manager, ManagerError, SubState};
+
+/// One listening file descriptor held by a socket unit (see `Manager::list_sockets`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketListing {
+    /// Address as configured (port-only TCP/UDP listeners shown with their bind address)
+    pub listen: String,
+    /// Stream, Datagram, FIFO or Netlink
+    pub socket_type: &'static str,
+    pub unit: String,
+    /// Services started by this socket
+    pub activates: Vec<String>,
+    pub fd: RawFd,
+}
 
-use super::{socket_watcher, Manager, ManagerError};
+fn display_listen_address(listener: &Listener) -> String {
+    let is_inet = matches!(
+        listener.listen_type,
+        ListenType::Stream | ListenType::Datagram | ListenType::SequentialPacket
+    );
+    if is_inet && listener.address.chars().all(|c| c.is_ascii_digit()) {
+        format!("[::]:{}", listener.address)
+    } else {
+        listener.address.clone()
+    }
+}
 
 impl Manager {
+    /// Listening sockets with the units they activate, sorted by address
+    pub fn list_sockets(&self) -> Vec<SocketListing> {
+        let mut listings = Vec::new();
+        for (name, fds) in &self.socket_fds {
+            let Some(socket) = self.units.get(name).and_then(|u| u.as_socket()) else {
+                continue;
+            };
+            let mut activates = vec![socket.activates()];
+            for (service_name, unit) in &self.units {
+                let Some(service) = unit.as_service() else {
+                    continue;
+                };
+                if service.service.sockets.contains(name) && !activates.contains(service_name) {
+                    activates.push(service_name.clone());
+                }
+            }
+            // FDs are created in listener order (see `start_socket`)
+            for (listener, fd) in socket.socket.listeners.iter().zip(fds) {
+                listings.push(SocketListing {
+                    listen: display_listen_address(listener),
+                    socket_type: listener.listen_type.as_str(),
+                    unit: name.clone(),
+                    activates: activates.clone(),
+                    fd: *fd,
+                });
+            }
+        }
+        listings.sort_by(|a, b| a.listen.cmp(&b.listen).then_with(|| a.unit.cmp(&b.unit)));
+        listings
+    }
+
     /// Start a socket unit (create listening sockets)
     pub(super) async fn start_socket(
         &mut self,
@@ -36,6 +94,7 @@ impl Manager {
         for listener in &socket.socket.listeners {
             match self.create_listener(listener, socket) {
                 Ok(fd) => {
+                    self.label_listener(name, socket, listener, fd);
                     log::info!(
                         "{}: created {:?} listener on {} (fd {})",
                         name,
@@ -70,13 +129,7 @@ impl Manager {
         );
         self.socket_fds.insert(name.to_string(), fds.clone());
 
-        // Spawn socket watcher task for activation
-        let service_name = socket.service_name();
-        let socket_name = name.to_string();
-        let tx = self.socket_activation_tx.clone();
-        tokio::spawn(async move {
-            socket_watcher::watch_socket(socket_name, service_name, fds, tx).await;
-        });
+        self.spawn_socket_watcher(name, socket, fds);
 
         // Mark as active
         if let Some(state) = self.states.get_mut(name) {
@@ -98,6 +151,25 @@ impl Manager {
             ListenType::Datagram => self.create_datagram_listener(listener, socket),
             ListenType::Fifo => self.create_fifo(&listener.address, socket),
             ListenType::Netlink => self.create_netlink_socket(&listener.address),
+            ListenType::SequentialPacket => self.create_seqpacket_listener(listener, socket),
+        }
+    }
+
+    /// IP protocol for inet listeners of `sock_type` (0: TCP or UDP)
+    fn inet_protocol(socket: &Socket, sock_type: libc::c_int) -> std::io::Result<libc::c_int> {
+        let Some(protocol) = socket.socket.socket_protocol else {
+            return Ok(0);
+        };
+        let fits = match protocol {
+            SocketProtocol::Sctp => sock_type != libc::SOCK_DGRAM,
+            SocketProtocol::UdpLite => sock_type == libc::SOCK_DGRAM,
+        };
+        match fits {
+            true => Ok(protocol.ip_protocol()),
+            false => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("SocketProtocol={:?} does not fit this listener", protocol),
+            )),
         }
     }
 
@@ -105,7 +177,37 @@ impl Manager {
         if listener.address.starts_with('/') || listener.address.starts_with('@') {
             return self.create_unix_stream_listener(&listener.address, socket);
         }
-        self.create_tcp_socket(&listener.address)
+        if let Some(vsock) = listener.address.strip_prefix("vsock:") {
+            return create_vsock_listener(vsock);
+        }
+        let protocol = Self::inet_protocol(socket, libc::SOCK_STREAM)?;
+        create_inet_socket(&listener.address, libc::SOCK_STREAM, protocol, socket)
+    }
+
+    fn create_seqpacket_listener(
+        &self,
+        listener: &Listener,
+        socket: &Socket,
+    ) -> std::io::Result<RawFd> {
+        let address = &listener.address;
+        if let Some(name) = address.strip_prefix('@') {
+            return create_unix_socket(&format!("\0{}", name), libc::SOCK_SEQPACKET);
+        }
+        if !address.starts_with('/') {
+            let protocol = Self::inet_protocol(socket, libc::SOCK_SEQPACKET)?;
+            return create_inet_socket(address, libc::SOCK_SEQPACKET, protocol, socket);
+        }
+        let _ = std::fs::remove_file(address);
+        if let Some(parent) = std::path::Path::new(address).parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let fd = create_unix_socket(address, libc::SOCK_SEQPACKET)?;
+        let mode = socket.socket.socket_mode.unwrap_or(0o666);
+        if let Err(e) = std::fs::set_permissions(address, std::fs::Permissions::from_mode(mode)) {
+            unsafe { libc::close(fd) };
+            return Err(e);
+        }
+        Ok(fd)
     }
 
     fn create_datagram_listener(
@@ -116,12 +218,13 @@ impl Manager {
         if listener.address.starts_with('/') {
             return self.create_unix_dgram_socket(&listener.address, socket);
         }
-        self.create_udp_socket(&listener.address)
+        let protocol = Self::inet_protocol(socket, libc::SOCK_DGRAM)?;
+        create_inet_socket(&listener.address, libc::SOCK_DGRAM, protocol, socket)
     }
 
     fn create_unix_stream_listener(&self, address: &str, socket: &Socket) -> std::io::Result<RawFd> {
         if address.starts_with('@') {
-            return self.create_abstract_unix_socket(&format!("\0{}", &address[1..]));
+            return create_unix_socket(&format!("\0{}", &address[1..]), libc::SOCK_STREAM);
         }
         self.create_filesystem_unix_listener(address, socket)
     }
@@ -148,86 +251,6 @@ impl Manager {
         Ok(fd)
     }
 
-    fn create_abstract_unix_socket(&self, addr: &str) -> std::io::Result<RawFd> {
-        use std::mem::size_of;
-
-        unsafe {
-            let fd = libc::socket(libc::AF_UNIX, libc::SOCK_STREAM, 0);
-            if fd < 0 {
-                return Err(std::io::Error::last_os_error());
-            }
-
-            // Set SO_REUSEADDR
-            let optval: libc::c_int = 1;
-            libc::setsockopt(
-                fd,
-                libc::SOL_SOCKET,
-                libc::SO_REUSEADDR,
-                &optval as *const _ as *const libc::c_void,
-                size_of::<libc::c_int>() as libc::socklen_t,
-            );
-
-            let mut sockaddr: libc::sockaddr_un = std::mem::zeroed();
-            sockaddr.sun_family = libc::AF_UNIX as u16;
-
-            // Copy address including the null byte for abstract sockets
-            let bytes = addr.as_bytes();
-            let len = std::cmp::min(bytes.len(), sockaddr.sun_path.len());
-            std::ptr::copy_nonoverlapping(
-                bytes.as_ptr(),
-                sockaddr.sun_path.as_mut_ptr() as *mut u8,
-                len,
-            );
-
-            let addr_len = (size_of::<libc::sa_family_t>() + len) as libc::socklen_t;
-
-            if libc::bind(fd, &sockaddr as *const _ as *const libc::sockaddr, addr_len) < 0 {
-                let err = std::io::Error::last_os_error();
-                libc::close(fd);
-                return Err(err);
-            }
-
-            if libc::listen(fd, 128) < 0 {
-                let err = std::io::Error::last_os_error();
-                libc::close(fd);
-                return Err(err);
-            }
-
-            Ok(fd)
-        }
-    }
-
-    fn create_tcp_socket(&self, addr: &str) -> std::io::Result<RawFd> {
-        use std::net::TcpListener;
-
-        // Handle port-only or host:port
-        let bind_addr = if addr.contains(':') {
-            addr.to_string()
-        } else {
-            format!("0.0.0.0:{}", addr)
-        };
-
-        let listener = TcpListener::bind(&bind_addr)?;
-        let fd = listener.as_raw_fd();
-        std::mem::forget(listener);
-        Ok(fd)
-    }
-
-    fn create_udp_socket(&self, addr: &str) -> std::io::Result<RawFd> {
-        use std::net::UdpSocket;
-
-        let bind_addr = if addr.contains(':') {
-            addr.to_string()
-        } else {
-            format!("0.0.0.0:{}", addr)
-        };
-
-        let socket = UdpSocket::bind(&bind_addr)?;
-        let fd = socket.as_raw_fd();
-        std::mem::forget(socket);
-        Ok(fd)
-    }
-
     fn create_unix_dgram_socket(&self, path: &str, socket: &Socket) -> std::io::Result<RawFd> {
         use std::os::unix::net::UnixDatagram;
 
@@ -314,14 +337,27 @@ impl Manager {
 
         log::info!("Stopping socket {}", name);
 
-        // Close all socket FDs
+        self.close_listeners(name, socket);
+
+        if let Some(state) = self.states.get_mut(name) {
+            state.set_stopped(0);
+        }
+
+        log::info!("{} stopped", name);
+        Ok(())
+    }
+
+    /// Cancel the watcher and close the listening sockets of `name`,
+    /// removing socket files if RemoveOnStop=yes
+    pub(super) fn close_listeners(&mut self, name: &str, socket: &Socket) {
+        self.abort_unit_tasks(name);
+        self.armed_sockets.remove(name);
         if let Some(fds) = self.socket_fds.remove(name) {
             for fd in fds {
                 unsafe { libc::close(fd) };
             }
         }
 
-        // Remove socket files if RemoveOnStop=yes
         if socket.socket.remove_on_stop {
             for listener in &socket.socket.listeners {
                 if listener.address.starts_with('/') {
@@ -329,13 +365,6 @@ impl Manager {
                 }
             }
         }
-
-        if let Some(state) = self.states.get_mut(name) {
-            state.set_stopped(0);
-        }
-
-        log::info!("{} stopped", name);
-        Ok(())
     }
 
     fn for_each_service_socket<F>(&self, service_name: &str, mut callback: F)
@@ -349,6 +378,9 @@ impl Manager {
     }
 
     pub fn get_socket_fds(&self, service_name: &str) -> Vec<RawFd> {
+        if let Some(&fd) = self.connection_fds.get(service_name) {
+            return vec![fd];
+        }
         let mut fds = Vec::new();
         self.for_each_service_socket(service_name, |_, socket_fds| {
             fds.extend(socket_fds.iter().copied());
@@ -357,6 +389,9 @@ impl Manager {
     }
 
     pub fn get_socket_fd_names(&self, service_name: &str) -> Vec<String> {
+        if self.connection_fds.contains_key(service_name) {
+            return vec!["connection".to_string()];
+        }
         let resolve_fd_name = |socket_name: &str| -> String {
             if let Some(socket) = self.units.get(socket_name).and_then(|u| u.as_socket()) {
                 socket.socket.fd_name.clone().unwrap_or_else(|| {
@@ -400,6 +435,19 @@ impl Manager {
             activation.service_name,
             activation.socket_name
         );
+        if !self.trigger_allowed(&activation.socket_name) {
+            return Ok(());
+        }
+        if let Some(connection) = activation.connection {
+            return self
+                .start_connection_instance(
+                    &activation.socket_name,
+                    &activation.service_name,
+                    connection,
+                )
+                .await;
+        }
+        self.armed_sockets.remove(&activation.socket_name);
 
         // Resolve alias to canonical name before checking state
         // The service_name might be an alias (e.g., "dbus.service" -> "dbus-broker.service")
@@ -435,6 +483,110 @@ impl Manager {
 }
 
 impl Manager {
+    /// Socket bookkeeping once a service is down
+    ///
+    /// Finished Accept=yes instances are forgotten. Otherwise the service's
+    /// sockets are watched again, so the next connection starts it anew (for
+    /// services that exit when idle).
+    pub(super) fn socket_service_stopped(&mut self, name: &str) {
+        let Some(state) = self.states.get(name) else {
+            return;
+        };
+        let down = matches!(state.active, ActiveState::Inactive | ActiveState::Failed);
+        if !down || state.sub == SubState::AutoRestart {
+            return;
+        }
+        if self.connection_instances.contains(name) {
+            if state.active == ActiveState::Inactive {
+                self.connection_instances.remove(name);
+                self.remove_unit(name);
+                self.states.remove(name);
+                self.fragment_paths.remove(name);
+                self.instance_templates.remove(name);
+            }
+            return;
+        }
+
+        let mut sockets = Vec::new();
+        self.for_each_service_socket(name, |socket_name, fds| {
+            sockets.push((socket_name.to_string(), fds.to_vec()));
+        });
+        for (socket_name, fds) in sockets {
+            if self.armed_sockets.contains(&socket_name) {
+                continue;
+            }
+            let Some(socket) = self
+                .units
+                .get(&socket_name)
+                .and_then(|u| u.as_socket())
+                .cloned()
+            else {
+                continue;
+            };
+            if socket.is_accept_socket() {
+                continue;
+            }
+            log::debug!(
+                "{}: {} is down, watching for connections again",
+                socket_name,
+                name
+            );
+            self.spawn_socket_watcher(&socket_name, &socket, fds);
+        }
+    }
+
+    fn spawn_socket_watcher(&mut self, name: &str, socket: &Socket, fds: Vec<RawFd>) {
+        let accept = socket.is_accept_socket();
+        let service_name = if accept {
+            socket.activates()
+        } else {
+            self.armed_sockets.insert(name.to_string());
+            socket.service_name()
+        };
+        let socket_name = name.to_string();
+        let tx = self.socket_activation_tx.clone();
+        let watcher = tokio::spawn(async move {
+            socket_watcher::watch_socket(socket_name, service_name, fds, accept, tx).await;
+        });
+        self.track_unit_task(name, watcher.abort_handle());
+    }
+
+    /// Accept=yes: start a new instance of the socket's template for one connection
+    async fn start_connection_instance(
+        &mut self,
+        socket_name: &str,
+        template: &str,
+        connection: AcceptedConnection,
+    ) -> Result<(), ManagerError> {
+        let instance = units::instantiate_template(template, &connection.instance())
+            .unwrap_or_else(|| template.to_string());
+        let result = self
+            .start_with_connection(socket_name, &instance, &connection)
+            .await;
+        // The instance holds its own copy; the peer sees EOF once it exits
+        unsafe { libc::close(connection.fd) };
+        result
+    }
+
+    async fn start_with_connection(
+        &mut self,
+        socket_name: &str,
+        instance: &str,
+        connection: &AcceptedConnection,
+    ) -> Result<(), ManagerError> {
+        let name = self.load(instance).await?;
+        self.label_connection_instance(socket_name, &name, connection.fd);
+        self.connection_fds.insert(name.clone(), connection.fd);
+        if let Some(peer) = connection.peer {
+            self.connection_peers.insert(name.clone(), peer);
+        }
+        self.connection_instances.insert(name.clone());
+        let result = self.start(&name).await;
+        self.connection_fds.remove(&name);
+        self.connection_peers.remove(&name);
+        result
+    }
+
     fn for_each_configured_service_socket<F>(&self, service_name: &str, callback: &mut F) -> bool
     where
         F: FnMut(&str, &[RawFd]),
@@ -475,22 +627,26 @@ impl Manager {
         found_any
     }
 
+    /// Every socket whose Service= (or name) is `service_name`, by name so
+    /// the FD order is the same on each start
     fn for_each_reverse_mapped_socket<F>(&self, service_name: &str, callback: &mut F)
     where
         F: FnMut(&str, &[RawFd]),
     {
-        for (socket_name, unit) in &self.units {
-            let Some(socket) = unit.as_socket() else {
-                continue;
-            };
-            if socket.service_name() != service_name {
-                continue;
-            }
-            let Some(socket_fds) = self.socket_fds.get(socket_name) else {
-                continue;
-            };
+        let mut sockets: Vec<(&String, &Vec<RawFd>)> = self
+            .units
+            .iter()
+            .filter_map(|(socket_name, unit)| {
+                let socket = unit.as_socket()?;
+                if socket.service_name() != service_name {
+                    return None;
+                }
+                Some((socket_name, self.socket_fds.get(socket_name)?))
+            })
+            .collect();
+        sockets.sort_by_key(|(socket_name, _)| *socket_name);
+        for (socket_name, socket_fds) in sockets {
             callback(socket_name, socket_fds);
-            return;
         }
     }
 }
@@ -582,3 +738,294 @@ fn bind_netlink_socket(fd: RawFd, groups: u32) -> std::io::Result<()> {
     }
     Ok(())
 }
+
+/// ListenStream=/ListenDatagram= address: a bare port, `host:port` or `[v6]:port`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum InetListenAddress {
+    Port(u16),
+    Addr(SocketAddr),
+}
+
+fn parse_inet_listen_address(addr: &str) -> std::io::Result<InetListenAddress> {
+    let invalid = || {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid listen address: {}", addr),
+        )
+    };
+
+    if let Ok(port) = addr.parse::<u16>() {
+        return Ok(InetListenAddress::Port(port));
+    }
+    // [fe80::1%eth0]:80 - std only accepts numeric scope ids
+    if let Some(rest) = addr.strip_prefix('[') {
+        let (host, port) = rest.split_once("]:").ok_or_else(invalid)?;
+        let port = port.parse::<u16>().map_err(|_| invalid())?;
+        let (ip, scope) = match host.split_once('%') {
+            Some((ip, scope)) => (ip, interface_index(scope).ok_or_else(invalid)?),
+            None => (host, 0),
+        };
+        let ip = ip.parse::<Ipv6Addr>().map_err(|_| invalid())?;
+        return Ok(InetListenAddress::Addr(SocketAddr::V6(
+            std::net::SocketAddrV6::new(ip, port, 0, scope),
+        )));
+    }
+    addr.parse::<SocketAddr>()
+        .map(InetListenAddress::Addr)
+        .map_err(|_| invalid())
+}
+
+fn interface_index(scope: &str) -> Option<u32> {
+    if let Ok(index) = scope.parse::<u32>() {
+        return Some(index);
+    }
+    let name = std::ffi::CString::new(scope).ok()?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => None,
+        index => Some(index),
+    }
+}
+
+/// Bind a TCP, UDP or SCTP listener, honouring BindIPv6Only= and
+/// BindToDevice=. A bare port listens on both stacks through [::], falling
+/// back to 0.0.0.0 on hosts without IPv6.
+fn create_inet_socket(
+    addr: &str,
+    sock_type: libc::c_int,
+    protocol: libc::c_int,
+    socket: &Socket,
+) -> std::io::Result<RawFd> {
+    let kind = InetSocketKind {
+        sock_type,
+        protocol,
+    };
+    let section = &socket.socket;
+    let device = section.bind_to_device.as_deref();
+    match parse_inet_listen_address(addr)? {
+        InetListenAddress::Addr(addr) => {
+            bind_inet_socket(addr, kind, section.bind_ipv6_only.v6only(), device)
+        }
+        InetListenAddress::Port(port) => {
+            let any = SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port);
+            let v6only = section.bind_ipv6_only.v6only().unwrap_or(false);
+            match bind_inet_socket(any, kind, Some(v6only), device) {
+                Err(e) if e.raw_os_error() == Some(libc::EAFNOSUPPORT) => {
+                    let any = SocketAddr::new(IpAddr::from([0, 0, 0, 0]), port);
+                    bind_inet_socket(any, kind, None, device)
+                }
+                result => result,
+            }
+        }
+    }
+}
+
+/// Bind and listen on a unix socket of `sock_type` at `addr` (a path, or
+/// "\0name" for an abstract socket)
+fn create_unix_socket(addr: &str, sock_type: libc::c_int) -> std::io::Result<RawFd> {
+    use std::mem::size_of;
+
+    unsafe {
+        let fd = libc::socket(libc::AF_UNIX, sock_type, 0);
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        // Set SO_REUSEADDR
+        let optval: libc::c_int = 1;
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_REUSEADDR,
+            &optval as *const _ as *const libc::c_void,
+            size_of::<libc::c_int>() as libc::socklen_t,
+        );
+
+        let mut sockaddr: libc::sockaddr_un = std::mem::zeroed();
+        sockaddr.sun_family = libc::AF_UNIX as u16;
+
+        // Copy address including the leading null byte of abstract sockets
+        let bytes = addr.as_bytes();
+        let len = std::cmp::min(bytes.len(), sockaddr.sun_path.len());
+        std::ptr::copy_nonoverlapping(
+            bytes.as_ptr(),
+            sockaddr.sun_path.as_mut_ptr() as *mut u8,
+            len,
+        );
+
+        let addr_len = (size_of::<libc::sa_family_t>() + len) as libc::socklen_t;
+
+        if libc::bind(fd, &sockaddr as *const _ as *const libc::sockaddr, addr_len) < 0 {
+            let err = std::io::Error::last_os_error();
+            libc::close(fd);
+            return Err(err);
+        }
+
+        if libc::listen(fd, 128) < 0 {
+            let err = std::io::Error::last_os_error();
+            libc::close(fd);
+            return Err(err);
+        }
+
+        Ok(fd)
+    }
+}
+
+/// Socket type and protocol passed to socket(2)
+#[derive(Debug, Clone, Copy)]
+struct InetSocketKind {
+    sock_type: libc::c_int,
+    protocol: libc::c_int,
+}
+
+fn bind_inet_socket(
+    addr: SocketAddr,
+    kind: InetSocketKind,
+    v6only: Option<bool>,
+    device: Option<&str>,
+) -> std::io::Result<RawFd> {
+    let domain = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+    let fd = unsafe { libc::socket(domain, kind.sock_type | libc::SOCK_CLOEXEC, kind.protocol) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    if let Err(e) = configure_and_bind_inet_socket(fd, addr, kind.sock_type, v6only, device) {
+        unsafe { libc::close(fd) };
+        return Err(e);
+    }
+    Ok(fd)
+}
+
+fn configure_and_bind_inet_socket(
+    fd: RawFd,
+    addr: SocketAddr,
+    sock_type: libc::c_int,
+    v6only: Option<bool>,
+    device: Option<&str>,
+) -> std::io::Result<()> {
+    use std::mem::size_of;
+
+    set_int_sockopt(fd, libc::SOL_SOCKET, libc::SO_REUSEADDR, 1)?;
+    if let (SocketAddr::V6(_), Some(v6only)) = (addr, v6only) {
+        set_int_sockopt(
+            fd,
+            libc::IPPROTO_IPV6,
+            libc::IPV6_V6ONLY,
+            v6only as libc::c_int,
+        )?;
+    }
+    if let Some(device) = device {
+        let result = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_BINDTODEVICE,
+                device.as_ptr() as *const libc::c_void,
+                device.len() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+
+    let bind_result = match addr {
+        SocketAddr::V4(v4) => {
+            let mut sin: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = v4.port().to_be();
+            sin.sin_addr.s_addr = u32::from(*v4.ip()).to_be();
+            let addr_ptr = &sin as *const libc::sockaddr_in as *const libc::sockaddr;
+            let addr_len = size_of::<libc::sockaddr_in>() as libc::socklen_t;
+            unsafe { libc::bind(fd, addr_ptr, addr_len) }
+        }
+        SocketAddr::V6(v6) => {
+            let mut sin6: libc::sockaddr_in6 = unsafe { std::mem::zeroed() };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = v6.port().to_be();
+            sin6.sin6_addr.s6_addr = v6.ip().octets();
+            sin6.sin6_flowinfo = v6.flowinfo();
+            sin6.sin6_scope_id = v6.scope_id();
+            let addr_ptr = &sin6 as *const libc::sockaddr_in6 as *const libc::sockaddr;
+            let addr_len = size_of::<libc::sockaddr_in6>() as libc::socklen_t;
+            unsafe { libc::bind(fd, addr_ptr, addr_len) }
+        }
+    };
+    if bind_result < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let listens = matches!(sock_type, libc::SOCK_STREAM | libc::SOCK_SEQPACKET);
+    if listens && unsafe { libc::listen(fd, libc::SOMAXCONN) } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn set_int_sockopt(
+    fd: RawFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: libc::c_int,
+) -> std::io::Result<()> {
+    let result = unsafe {
+        libc::setsockopt(
+            fd,
+            level,
+            name,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// `CID:PORT` from a `vsock:CID:PORT` listen address. An empty CID, or
+/// `any`, accepts connections for any CID.
+fn parse_vsock_address(addr: &str) -> std::io::Result<(u32, u32)> {
+    let invalid = || {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid vsock address: vsock:{}", addr),
+        )
+    };
+
+    let (cid, port) = addr.split_once(':').ok_or_else(invalid)?;
+    let cid = match cid {
+        "" | "any" => libc::VMADDR_CID_ANY,
+        cid => cid.parse::<u32>().map_err(|_| invalid())?,
+    };
+    let port = port.parse::<u32>().map_err(|_| invalid())?;
+    Ok((cid, port))
+}
+
+fn create_vsock_listener(addr: &str) -> std::io::Result<RawFd> {
+    use std::mem::size_of;
+
+    let (cid, port) = parse_vsock_address(addr)?;
+    let fd = unsafe { libc::socket(libc::AF_VSOCK, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let mut svm: libc::sockaddr_vm = unsafe { std::mem::zeroed() };
+    svm.svm_family = libc::AF_VSOCK as libc::sa_family_t;
+    svm.svm_cid = cid;
+    svm.svm_port = port;
+    let addr_ptr = &svm as *const libc::sockaddr_vm as *const libc::sockaddr;
+    let addr_len = size_of::<libc::sockaddr_vm>() as libc::socklen_t;
+    if unsafe { libc::bind(fd, addr_ptr, addr_len) } < 0
+        || unsafe { libc::listen(fd, libc::SOMAXCONN) } < 0
+    {
+        let err = std::io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(err);
+    }
+    Ok(fd)
+}
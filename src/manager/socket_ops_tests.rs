@@ -1,6 +1,6 @@
 use super::*;
 use crate::manager::{ActiveState, Manager, ManagerError, ServiceState, SubState};
-use crate::units::{ListenType, Listener, Service, Socket, Unit};
+use crate::units::{ListenType, Listener, Service, Socket, SocketProtocol, Unit};
 use std::os::unix::fs::PermissionsExt;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -107,6 +107,29 @@ fn reverse_socket_mapping_uses_socket_service_name_when_service_has_no_socket_li
     assert_eq!(manager.get_socket_fd_names("worker.service"), ["api"]);
 }
 
+#[test]
+fn all_reverse_mapped_sockets_pass_their_fds_in_socket_name_order() {
+    let mut manager = Manager::new();
+    for (name, fds) in [
+        ("web-https.socket", vec![21]),
+        ("web-http.socket", vec![20, 22]),
+    ] {
+        manager.units.insert(
+            name.to_string(),
+            Unit::Socket(socket(name, |socket| {
+                socket.socket.service = Some("web.service".to_string());
+            })),
+        );
+        manager.socket_fds.insert(name.to_string(), fds);
+    }
+
+    assert_eq!(manager.get_socket_fds("web.service"), [20, 22, 21]);
+    assert_eq!(
+        manager.get_socket_fd_names("web.service"),
+        ["web-http", "web-http", "web-https"]
+    );
+}
+
 #[test]
 fn configured_socket_mapping_reports_missing_fds_without_falling_back() {
     let mut manager = Manager::new();
@@ -122,6 +145,62 @@ fn configured_socket_mapping_reports_missing_fds_without_falling_back() {
     assert!(manager.get_socket_fd_names("api.service").is_empty());
 }
 
+#[test]
+fn accept_instances_get_only_their_connection_fd() {
+    let mut manager = Manager::new();
+    manager.units.insert(
+        "echo.socket".to_string(),
+        Unit::Socket(socket("echo.socket", |socket| socket.socket.accept = true)),
+    );
+    manager
+        .socket_fds
+        .insert("echo.socket".to_string(), vec![12]);
+    manager
+        .connection_fds
+        .insert("echo@0.service".to_string(), 30);
+
+    assert_eq!(manager.get_socket_fds("echo@0.service"), [30]);
+    assert_eq!(manager.get_socket_fd_names("echo@0.service"), ["connection"]);
+}
+
+#[tokio::test]
+async fn stopped_services_rearm_their_sockets_and_finished_instances_are_dropped() {
+    let dir = temp_dir("rearm");
+    let listener = std::os::unix::net::UnixListener::bind(dir.0.join("idle.sock")).unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let mut manager = Manager::new();
+    manager.units.insert(
+        "idle.socket".to_string(),
+        Unit::Socket(socket("idle.socket", |_| {})),
+    );
+    manager
+        .socket_fds
+        .insert("idle.socket".to_string(), vec![listener.as_raw_fd()]);
+    for name in ["idle.service", "echo@0.service", "echo@1.service"] {
+        manager
+            .units
+            .insert(name.to_string(), Unit::Service(service(name, &[])));
+        manager.states.insert(name.to_string(), ServiceState::new());
+    }
+    manager
+        .connection_instances
+        .extend(["echo@0.service".to_string(), "echo@1.service".to_string()]);
+    manager
+        .states
+        .get_mut("echo@1.service")
+        .unwrap()
+        .set_failed("exit code 1".to_string());
+
+    manager.socket_service_stopped("idle.service");
+    manager.socket_service_stopped("echo@0.service");
+    manager.socket_service_stopped("echo@1.service");
+
+    assert!(manager.armed_sockets.contains("idle.socket"));
+    assert!(!manager.units.contains_key("echo@0.service"));
+    assert!(!manager.states.contains_key("echo@0.service"));
+    assert!(manager.states.contains_key("echo@1.service"));
+}
+
 #[test]
 fn take_socket_activation_receiver_returns_receiver_once() {
     let mut manager = Manager::new();
@@ -388,6 +467,7 @@ async fn handle_socket_activation_skips_active_services_and_reports_missing_serv
         .handle_socket_activation(socket_watcher::SocketActivation {
             socket_name: "ready.socket".to_string(),
             service_name: "ready.service".to_string(),
+            connection: None,
         })
         .await
         .unwrap();
@@ -395,6 +475,7 @@ async fn handle_socket_activation_skips_active_services_and_reports_missing_serv
         .handle_socket_activation(socket_watcher::SocketActivation {
             socket_name: "ready.socket".to_string(),
             service_name: "ready".to_string(),
+            connection: None,
         })
         .await
         .unwrap();
@@ -403,6 +484,7 @@ async fn handle_socket_activation_skips_active_services_and_reports_missing_serv
         .handle_socket_activation(socket_watcher::SocketActivation {
             socket_name: "missing.socket".to_string(),
             service_name: "missing.service".to_string(),
+            connection: None,
         })
         .await
         .unwrap_err();
@@ -451,3 +533,154 @@ fn socket_mapping_skips_empty_configured_sockets_nonmatching_sockets_and_missing
     assert!(manager.get_socket_fds("empty.service").is_empty());
     assert!(manager.get_socket_fd_names("empty.service").is_empty());
 }
+
+#[test]
+fn list_sockets_reports_listeners_types_and_activated_units() {
+    let mut manager = Manager::new();
+    manager.units.insert(
+        "web.socket".to_string(),
+        Unit::Socket(socket("web.socket", |socket| {
+            socket.socket.listeners = vec![
+                Listener {
+                    address: "8080".to_string(),
+                    listen_type: ListenType::Stream,
+                },
+                Listener {
+                    address: "/run/web.sock".to_string(),
+                    listen_type: ListenType::Datagram,
+                },
+            ];
+        })),
+    );
+    manager.units.insert(
+        "web-extra.service".to_string(),
+        Unit::Service(service("web-extra.service", &["web.socket"])),
+    );
+    manager.units.insert(
+        "idle.socket".to_string(),
+        Unit::Socket(socket("idle.socket", |socket| {
+            socket.socket.listeners = vec![Listener {
+                address: "/run/idle.sock".to_string(),
+                listen_type: ListenType::Stream,
+            }];
+        })),
+    );
+    manager
+        .socket_fds
+        .insert("web.socket".to_string(), vec![7, 8]);
+
+    let listings = manager.list_sockets();
+
+    assert_eq!(listings.len(), 2);
+    assert_eq!(listings[0].listen, "/run/web.sock");
+    assert_eq!(listings[0].socket_type, "Datagram");
+    assert_eq!(listings[0].fd, 8);
+    assert_eq!(listings[1].listen, "[::]:8080");
+    assert_eq!(listings[1].socket_type, "Stream");
+    assert_eq!(listings[1].unit, "web.socket");
+    assert_eq!(listings[1].activates, ["web.service", "web-extra.service"]);
+}
+
+#[test]
+fn inet_listen_addresses_accept_ports_ipv4_and_bracketed_ipv6() {
+    assert_eq!(
+        parse_inet_listen_address("80").unwrap(),
+        InetListenAddress::Port(80)
+    );
+    assert_eq!(
+        parse_inet_listen_address("0.0.0.0:80").unwrap(),
+        InetListenAddress::Addr("0.0.0.0:80".parse().unwrap())
+    );
+    assert_eq!(
+        parse_inet_listen_address("[::]:80").unwrap(),
+        InetListenAddress::Addr("[::]:80".parse().unwrap())
+    );
+    match parse_inet_listen_address("[fe80::1%3]:80").unwrap() {
+        InetListenAddress::Addr(SocketAddr::V6(addr)) => assert_eq!(addr.scope_id(), 3),
+        other => panic!("unexpected address {other:?}"),
+    }
+    assert!(parse_inet_listen_address("[::1]80").is_err());
+    assert!(parse_inet_listen_address("localhost:80").is_err());
+    assert!(parse_inet_listen_address("[fe80::1%no-such-if0]:80").is_err());
+}
+
+#[test]
+fn inet_sockets_bind_explicit_and_port_only_addresses() {
+    let unit = socket("api.socket", |_| {});
+    for (addr, sock_type) in [
+        ("127.0.0.1:0", libc::SOCK_STREAM),
+        ("0", libc::SOCK_STREAM),
+        ("0", libc::SOCK_DGRAM),
+    ] {
+        let fd = create_inet_socket(addr, sock_type, 0, &unit).unwrap();
+        let mut actual: libc::c_int = 0;
+        let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        let result = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_TYPE,
+                &mut actual as *mut libc::c_int as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        unsafe { libc::close(fd) };
+        assert_eq!(result, 0);
+        assert_eq!(actual, sock_type);
+    }
+}
+
+#[test]
+fn sequential_packet_listeners_bind_seqpacket_sockets() {
+    let root = temp_dir("seqpacket");
+    let manager = Manager::new();
+    let listener = Listener {
+        address: root.0.join("api.sock").display().to_string(),
+        listen_type: ListenType::SequentialPacket,
+    };
+    let unit = socket("api.socket", |socket| {
+        socket.socket.listeners = vec![listener.clone()];
+    });
+
+    let fd = manager.create_listener(&listener, &unit).unwrap();
+    let mut actual: libc::c_int = 0;
+    let mut len = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TYPE,
+            &mut actual as *mut libc::c_int as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    unsafe { libc::close(fd) };
+    assert_eq!(result, 0);
+    assert_eq!(actual, libc::SOCK_SEQPACKET);
+
+    // UDP-Lite only carries datagrams
+    let lite = socket("lite.socket", |socket| {
+        socket.socket.socket_protocol = Some(SocketProtocol::UdpLite);
+    });
+    let stream = Listener {
+        address: "127.0.0.1:0".to_string(),
+        listen_type: ListenType::Stream,
+    };
+    let error = manager.create_listener(&stream, &lite).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn vsock_addresses_take_a_cid_and_port() {
+    assert_eq!(parse_vsock_address("2:1024").unwrap(), (2, 1024));
+    assert_eq!(
+        parse_vsock_address(":1024").unwrap(),
+        (libc::VMADDR_CID_ANY, 1024)
+    );
+    assert_eq!(
+        parse_vsock_address("any:5000").unwrap(),
+        (libc::VMADDR_CID_ANY, 5000)
+    );
+    assert!(parse_vsock_address("1024").is_err());
+    assert!(parse_vsock_address("host:1024").is_err());
+}
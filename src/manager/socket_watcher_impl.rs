@@ -2,6 +2,7 @@
 //
 // Monitors listening sockets and triggers service activation on connection.
 
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::os::unix::io::RawFd;
 use tokio::io::unix::AsyncFd;
 use tokio::io::Interest;
@@ -12,15 +13,53 @@ use tokio::sync::mpsc;
 pub struct SocketActivation {
     /// Name of the socket unit
     pub socket_name: String,
-    /// Name of the service to start
+    /// Name of the service to start (the template for Accept=yes)
     pub service_name: String,
+    /// Connection accepted on behalf of an Accept=yes service instance
+    pub connection: Option<AcceptedConnection>,
+}
+
+/// Connection accepted by an Accept=yes socket
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcceptedConnection {
+    /// Connected socket, owned by the receiver of the activation
+    pub fd: RawFd,
+    /// Per-socket connection counter, used to name the service instance
+    pub number: u64,
+    /// Our end of a TCP/UDP/SCTP connection (None for AF_UNIX and the like)
+    pub local: Option<SocketAddr>,
+    /// The peer's end, exported as REMOTE_ADDR/REMOTE_PORT
+    pub peer: Option<SocketAddr>,
+}
+
+impl AcceptedConnection {
+    /// Instance name of the service for this connection, as systemd names
+    /// it: `N-LOCALIP:PORT-PEERIP:PORT` for IP connections, `N` otherwise
+    pub fn instance(&self) -> String {
+        match (self.local, self.peer) {
+            (Some(local), Some(peer)) => format!(
+                "{}-{}:{}-{}:{}",
+                self.number,
+                local.ip().to_canonical(),
+                local.port(),
+                peer.ip().to_canonical(),
+                peer.port()
+            ),
+            _ => self.number.to_string(),
+        }
+    }
 }
 
 /// Watch a socket for incoming connections and send activation message
+///
+/// Without Accept=, the watcher fires once and the service takes over the
+/// listening socket. With Accept=yes it keeps accepting and sends one
+/// activation per connection.
 pub async fn watch_socket(
     socket_name: String,
     service_name: String,
     fds: Vec<RawFd>,
+    accept: bool,
     tx: mpsc::Sender<SocketActivation>,
 ) {
     let Some(&fd) = fds.first() else {
@@ -37,12 +76,94 @@ pub async fn watch_socket(
     };
 
     log::debug!("{}: watching fd {} for connections", socket_name, fd);
+    if accept {
+        accept_connections(&async_fd, &socket_name, &service_name, &tx).await;
+        return;
+    }
     if let Ok(mut guard) = wait_for_socket_readable(&async_fd, &socket_name).await {
-        send_activation_message(&tx, &socket_name, &service_name).await;
+        send_activation_message(&tx, &socket_name, &service_name, None).await;
         guard.clear_ready();
     }
 }
 
+async fn accept_connections(
+    async_fd: &AsyncFd<RawFd>,
+    socket_name: &str,
+    service_name: &str,
+    tx: &mpsc::Sender<SocketActivation>,
+) {
+    let mut number = 0;
+    while !tx.is_closed() {
+        let Ok(mut guard) = wait_for_socket_readable(async_fd, socket_name).await else {
+            return;
+        };
+        match accept_connection(*async_fd.get_ref()) {
+            Ok(fd) => {
+                let connection = AcceptedConnection {
+                    fd,
+                    number,
+                    local: inet_address(fd, libc::getsockname),
+                    peer: inet_address(fd, libc::getpeername),
+                };
+                number += 1;
+                send_activation_message(tx, socket_name, service_name, Some(connection)).await;
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => guard.clear_ready(),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                log::error!("{}: accept failed: {}", socket_name, e);
+                return;
+            }
+        }
+    }
+}
+
+fn accept_connection(fd: RawFd) -> std::io::Result<RawFd> {
+    let conn = unsafe {
+        libc::accept4(
+            fd,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            libc::SOCK_CLOEXEC,
+        )
+    };
+    if conn < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(conn)
+}
+
+/// Address `get` (getsockname or getpeername) returns for `fd`, if it is
+/// an IPv4 or IPv6 one
+fn inet_address(
+    fd: RawFd,
+    get: unsafe extern "C" fn(
+        libc::c_int,
+        *mut libc::sockaddr,
+        *mut libc::socklen_t,
+    ) -> libc::c_int,
+) -> Option<SocketAddr> {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    let storage_ptr = &mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr;
+    if unsafe { get(fd, storage_ptr, &mut len) } < 0 {
+        return None;
+    }
+    match storage.ss_family as libc::c_int {
+        libc::AF_INET => {
+            let sin = unsafe { &*(storage_ptr as *const libc::sockaddr_in) };
+            let ip = Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr));
+            Some(SocketAddr::new(ip.into(), u16::from_be(sin.sin_port)))
+        }
+        libc::AF_INET6 => {
+            let sin6 = unsafe { &*(storage_ptr as *const libc::sockaddr_in6) };
+            let ip = Ipv6Addr::from(sin6.sin6_addr.s6_addr);
+            Some(SocketAddr::new(ip.into(), u16::from_be(sin6.sin6_port)))
+        }
+        _ => None,
+    }
+}
+
 async fn wait_for_socket_readable<'a>(
     async_fd: &'a AsyncFd<RawFd>,
     socket_name: &str,
@@ -60,6 +181,7 @@ async fn send_activation_message(
     tx: &mpsc::Sender<SocketActivation>,
     socket_name: &str,
     service_name: &str,
+    connection: Option<AcceptedConnection>,
 ) {
     log::info!(
         "{}: connection pending, activating {}",
@@ -69,9 +191,13 @@ async fn send_activation_message(
     let message = SocketActivation {
         socket_name: socket_name.to_string(),
         service_name: service_name.to_string(),
+        connection,
     };
     if let Err(e) = tx.send(message).await {
         log::error!("{}: failed to send activation: {}", socket_name, e);
+        if let Some(connection) = connection {
+            unsafe { libc::close(connection.fd) };
+        }
     }
 }
 
@@ -87,6 +213,7 @@ mod tests {
             "empty.socket".to_string(),
             "empty.service".to_string(),
             Vec::new(),
+            false,
             tx,
         )
         .await;
@@ -98,11 +225,12 @@ mod tests {
     async fn activation_messages_include_socket_and_service_names() {
         let (tx, mut rx) = mpsc::channel(1);
 
-        send_activation_message(&tx, "api.socket", "api.service").await;
+        send_activation_message(&tx, "api.socket", "api.service", None).await;
 
         let message = rx.recv().await.unwrap();
         assert_eq!(message.socket_name, "api.socket");
         assert_eq!(message.service_name, "api.service");
+        assert!(message.connection.is_none());
     }
 
     #[tokio::test]
@@ -110,7 +238,7 @@ mod tests {
         let (tx, rx) = mpsc::channel(1);
         drop(rx);
 
-        send_activation_message(&tx, "closed.socket", "closed.service").await;
+        send_activation_message(&tx, "closed.socket", "closed.service", None).await;
     }
 
     #[tokio::test]
@@ -131,6 +259,7 @@ mod tests {
             "ready.socket".to_string(),
             "ready.service".to_string(),
             vec![listener.as_raw_fd()],
+            false,
             tx,
         ));
         let _client = std::os::unix::net::UnixStream::connect(&socket_path).unwrap();
@@ -146,6 +275,98 @@ mod tests {
         assert_eq!(message.service_name, "ready.service");
     }
 
+    #[test]
+    fn instances_of_ip_connections_are_named_after_both_ends() {
+        let mut connection = AcceptedConnection {
+            fd: -1,
+            number: 3,
+            local: None,
+            peer: None,
+        };
+        assert_eq!(connection.instance(), "3");
+
+        connection.local = Some("[::ffff:10.0.0.1]:22".parse().unwrap());
+        connection.peer = Some("10.0.0.9:41000".parse().unwrap());
+        assert_eq!(connection.instance(), "3-10.0.0.1:22-10.0.0.9:41000");
+        connection.peer = Some("[fe80::1]:41000".parse().unwrap());
+        assert_eq!(connection.instance(), "3-10.0.0.1:22-fe80::1:41000");
+    }
+
+    #[tokio::test]
+    async fn accepted_tcp_connections_carry_both_addresses() {
+        use std::os::unix::io::AsRawFd;
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let local = listener.local_addr().unwrap();
+        let (tx, mut rx) = mpsc::channel(1);
+        let watcher = tokio::spawn(watch_socket(
+            "tcp.socket".to_string(),
+            "tcp@.service".to_string(),
+            vec![listener.as_raw_fd()],
+            true,
+            tx,
+        ));
+        let client = std::net::TcpStream::connect(local).unwrap();
+
+        let message = tokio::time::timeout(std::time::Duration::from_secs(1), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        watcher.abort();
+        let connection = message.connection.unwrap();
+        unsafe { libc::close(connection.fd) };
+        assert_eq!(connection.local, Some(local));
+        assert_eq!(connection.peer, Some(client.local_addr().unwrap()));
+    }
+
+    #[tokio::test]
+    async fn accept_watcher_hands_over_each_connection_with_a_number() {
+        use std::io::Read;
+        use std::os::unix::io::{AsRawFd, FromRawFd};
+
+        let socket_path = std::env::temp_dir().join(format!(
+            "sysd-socket-watcher-{}-{}.sock",
+            std::process::id(),
+            socket_name_suffix()
+        ));
+        let _ = std::fs::remove_file(&socket_path);
+        let listener = std::os::unix::net::UnixListener::bind(&socket_path).unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let (tx, mut rx) = mpsc::channel(4);
+
+        let watcher = tokio::spawn(watch_socket(
+            "echo.socket".to_string(),
+            "echo@.service".to_string(),
+            vec![listener.as_raw_fd()],
+            true,
+            tx,
+        ));
+        let mut clients = Vec::new();
+        let mut numbers = Vec::new();
+        for _ in 0..2 {
+            let mut client = std::os::unix::net::UnixStream::connect(&socket_path).unwrap();
+            let message = tokio::time::timeout(std::time::Duration::from_secs(1), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(message.service_name, "echo@.service");
+            let connection = message.connection.unwrap();
+            numbers.push(connection.number);
+
+            // The handed-over fd is the server end of this client's connection
+            drop(unsafe { std::os::unix::net::UnixStream::from_raw_fd(connection.fd) });
+            let mut buf = [0u8; 1];
+            assert_eq!(client.read(&mut buf).unwrap(), 0);
+            clients.push(client);
+        }
+        drop(rx);
+        watcher.abort();
+        let _ = std::fs::remove_file(&socket_path);
+
+        assert_eq!(numbers, [0, 1]);
+    }
+
     fn socket_name_suffix() -> usize {
         use std::sync::atomic::{AtomicUsize, Ordering};
 
@@ -0,0 +1,193 @@
+//! Units that live through `sysd soft-reboot`
+//!
+//! A soft-reboot stops every unit, kills what is left and re-executes sysd,
+//! in the root file system prepared at /run/nextroot if there is one,
+//! without rebooting the kernel. Units with SurviveFinalKillSignal=yes are
+//! neither stopped nor killed: their main PID and cgroup are written to the
+//! `serialized` file of the runtime dir, and the next sysd adopts them as
+//! running before it boots, so the boot leaves them alone. Their processes
+//! are still children of PID 1 and are reaped as before.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::state_dir::StateDirs;
+
+use super::{Manager, ServiceState};
+
+/// A unit handed from one sysd to the next
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Survivor {
+    name: String,
+    main_pid: Option<u32>,
+    cgroup_path: Option<PathBuf>,
+}
+
+impl Manager {
+    /// Active units that keep running through a soft-reboot
+    pub fn soft_reboot_survivors(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .states
+            .iter()
+            .filter(|(_, state)| state.is_active())
+            .filter(|(name, _)| {
+                self.units
+                    .get(*name)
+                    .is_some_and(|unit| unit.unit_section().survive_final_kill_signal)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Write the survivors down for the next sysd; returns the PIDs the
+    /// final kill must spare
+    pub fn serialize_survivors(&self) -> Vec<u32> {
+        let survivors: Vec<Survivor> = self
+            .soft_reboot_survivors()
+            .into_iter()
+            .map(|name| Survivor {
+                main_pid: self.states.get(&name).and_then(|state| state.main_pid),
+                cgroup_path: self.cgroup_paths.get(&name).cloned(),
+                name,
+            })
+            .collect();
+        let path = self.serialized_path();
+        if let Err(e) = write_survivors(&path, &survivors) {
+            log::error!("Failed to write {}: {}", path.display(), e);
+        }
+        survivors.iter().flat_map(survivor_pids).collect()
+    }
+
+    /// Take over the units a soft-reboot left running; call before booting
+    pub async fn adopt_survivors(&mut self) {
+        let path = self.serialized_path();
+        let survivors = read_survivors(&path);
+        let _ = std::fs::remove_file(&path);
+        for survivor in survivors {
+            if survivor_pids(&survivor).is_empty() {
+                log::info!("{} did not survive the soft-reboot", survivor.name);
+                continue;
+            }
+            let name = match self.load(&survivor.name).await {
+                Ok(name) => name,
+                Err(e) => {
+                    log::warn!("Cannot adopt {}: {}", survivor.name, e);
+                    continue;
+                }
+            };
+            let mut state = ServiceState::new();
+            state.set_starting();
+            state.set_running(survivor.main_pid.unwrap_or(0));
+            self.states.insert(name.clone(), state);
+            if let Some(pid) = survivor.main_pid {
+                self.pid_to_service.insert(pid, name.clone());
+            }
+            if let Some(cgroup_path) = survivor.cgroup_path {
+                self.cgroup_paths.insert(name.clone(), cgroup_path);
+            }
+            log::info!("Adopted {} across soft-reboot", name);
+        }
+    }
+
+    fn serialized_path(&self) -> PathBuf {
+        StateDirs::for_mode(self.user_mode).serialized()
+    }
+}
+
+/// Processes of a survivor still alive: its cgroup, or its main PID
+fn survivor_pids(survivor: &Survivor) -> Vec<u32> {
+    let mut pids: Vec<u32> = survivor
+        .cgroup_path
+        .as_ref()
+        .and_then(|path| std::fs::read_to_string(path.join("cgroup.procs")).ok())
+        .map(|procs| {
+            procs
+                .lines()
+                .filter_map(|l| l.trim().parse().ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    if let Some(pid) = survivor.main_pid {
+        let alive = Path::new("/proc").join(pid.to_string()).exists();
+        if alive && !pids.contains(&pid) {
+            pids.push(pid);
+        }
+    }
+    pids
+}
+
+fn read_survivors(path: &Path) -> Vec<Survivor> {
+    let Ok(data) = std::fs::read(path) else {
+        return Vec::new();
+    };
+    rmp_serde::from_slice(&data).unwrap_or_else(|e| {
+        log::warn!("Discarding unreadable {}: {}", path.display(), e);
+        Vec::new()
+    })
+}
+
+fn write_survivors(path: &Path, survivors: &[Survivor]) -> std::io::Result<()> {
+    let data = rmp_serde::to_vec(survivors)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Service, Unit};
+
+    fn temp_path(test: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sysd-soft-reboot-{}-{}", test, std::process::id()))
+    }
+
+    #[test]
+    fn only_active_units_marked_to_survive_are_survivors() {
+        let mut manager = Manager::new_user();
+        for (name, survive, active) in [
+            ("keep.service", true, true),
+            ("stopped.service", true, false),
+            ("plain.service", false, true),
+        ] {
+            let mut service = Service::new(name.to_string());
+            service.unit.survive_final_kill_signal = survive;
+            manager.insert_unit(name.to_string(), Unit::Service(service));
+            let mut state = ServiceState::new();
+            if active {
+                state.set_running(7);
+            }
+            manager.states.insert(name.to_string(), state);
+        }
+        assert_eq!(manager.soft_reboot_survivors(), ["keep.service"]);
+    }
+
+    #[test]
+    fn survivors_roundtrip_and_dead_ones_have_no_pids() {
+        let path = temp_path("roundtrip");
+        let own = Survivor {
+            name: "keep.service".into(),
+            main_pid: Some(std::process::id()),
+            cgroup_path: None,
+        };
+        let gone = Survivor {
+            name: "gone.service".into(),
+            main_pid: None,
+            cgroup_path: Some(temp_path("no-cgroup")),
+        };
+        write_survivors(&path, &[own.clone(), gone.clone()]).unwrap();
+        assert_eq!(read_survivors(&path), [own.clone(), gone.clone()]);
+        assert_eq!(survivor_pids(&own), [std::process::id()]);
+        assert!(survivor_pids(&gone).is_empty());
+
+        std::fs::write(&path, b"garbage").unwrap();
+        assert!(read_survivors(&path).is_empty());
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_survivors(&path).is_empty());
+    }
+}
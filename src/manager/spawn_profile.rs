@@ -0,0 +1,165 @@
+//! Where the time goes while a service is spawned (`sysd analyze spawn`)
+//!
+//! The manager marks its own steps (preparing the spawn, the exec returning,
+//! cgroup and BPF setup); the child reports its steps over a pipe (see
+//! `executor::spawn_profile`). Both use CLOCK_MONOTONIC, so the profile is
+//! one timeline in which each step lasts from the previous mark to its own.
+//! With sysd-executor the child's steps run after `spawn` returned, once the
+//! manager's cgroup and BPF setup released it.
+
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::io::RawFd;
+use std::time::Duration;
+
+use crate::executor::{monotonic_ns, read_spawn_steps};
+
+use super::{Manager, ManagerError};
+
+/// Keep the pipe clear of the fds a child gets remapped to 3, 4, ...
+const PROFILE_FD_MIN: RawFd = 256;
+
+/// Steps of the last start of a unit
+#[derive(Debug, Clone)]
+pub struct SpawnProfile {
+    /// Step names in the order they ended, with how long each took
+    pub steps: Vec<(&'static str, Duration)>,
+    /// From the start request to the last mark
+    pub total: Duration,
+    /// Spawned through sysd-executor rather than from a pre_exec hook
+    pub via_executor: bool,
+    /// The child exec'd (or died), so no step is missing
+    pub complete: bool,
+}
+
+/// Marks of one start, collected while the manager spawns the unit
+#[derive(Debug)]
+pub(super) struct SpawnRecorder {
+    started: u64,
+    marks: Vec<(&'static str, u64)>,
+    reader: Option<OwnedFd>,
+    writer: Option<OwnedFd>,
+    via_executor: bool,
+}
+
+impl SpawnRecorder {
+    pub(super) fn start(via_executor: bool) -> Self {
+        Self {
+            started: monotonic_ns(),
+            marks: Vec::new(),
+            reader: None,
+            writer: None,
+            via_executor,
+        }
+    }
+
+    /// End the manager-side step `step` now
+    pub(super) fn mark(&mut self, step: &'static str) {
+        self.marks.push((step, monotonic_ns()));
+    }
+
+    /// Write end of a fresh pipe for the child; None if no pipe can be made,
+    /// in which case only the manager's steps are profiled
+    pub(super) fn child_fd(&mut self) -> Option<RawFd> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC | libc::O_NONBLOCK) } != 0 {
+            return None;
+        }
+        let [reader, writer] = fds.map(|fd| unsafe { OwnedFd::from_raw_fd(fd) });
+        let moved =
+            unsafe { libc::fcntl(writer.as_raw_fd(), libc::F_DUPFD_CLOEXEC, PROFILE_FD_MIN) };
+        if moved < 0 {
+            return None;
+        }
+        let writer = unsafe { OwnedFd::from_raw_fd(moved) };
+        let fd = writer.as_raw_fd();
+        self.reader = Some(reader);
+        self.writer = Some(writer);
+        Some(fd)
+    }
+
+    /// `spawn` returned: the child has exec'd its program (sysd-executor or
+    /// the service), so only the child holds the write end now
+    pub(super) fn spawned(&mut self) {
+        self.writer = None;
+        self.mark("exec");
+    }
+
+    /// Take in what the child reported so far
+    fn collect(&mut self) {
+        let Some(reader) = &self.reader else { return };
+        let (steps, done) = read_spawn_steps(reader.as_raw_fd());
+        self.marks
+            .extend(steps.into_iter().map(|(step, at)| (step.name(), at)));
+        if done {
+            self.reader = None;
+        }
+    }
+
+    pub(super) fn profile(&mut self) -> SpawnProfile {
+        self.collect();
+        let mut marks = self.marks.clone();
+        marks.sort_by_key(|&(_, at)| at);
+        let mut previous = self.started;
+        let steps = marks
+            .iter()
+            .map(|&(step, at)| {
+                let took = Duration::from_nanos(at.saturating_sub(previous));
+                previous = previous.max(at);
+                (step, took)
+            })
+            .collect();
+        SpawnProfile {
+            steps,
+            total: Duration::from_nanos(previous - self.started),
+            via_executor: self.via_executor,
+            complete: self.reader.is_none(),
+        }
+    }
+}
+
+impl Manager {
+    /// Profile of the last start of `name` since the manager came up, under
+    /// the unit's canonical name
+    pub fn spawn_profile(&mut self, name: &str) -> Result<(String, SpawnProfile), ManagerError> {
+        let name = self.normalize_name(name);
+        match self.spawn_profiles.get_mut(&name) {
+            Some(recorder) => Ok((name, recorder.profile())),
+            None => Err(ManagerError::NoSpawnProfile(name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::{mark_spawn_step, SpawnStep};
+
+    #[test]
+    fn manager_and_child_steps_form_one_timeline() {
+        let mut recorder = SpawnRecorder::start(false);
+        recorder.mark("prepare");
+        let fd = recorder.child_fd();
+        assert!(fd.is_some_and(|fd| fd >= PROFILE_FD_MIN));
+        mark_spawn_step(fd, SpawnStep::Forked);
+        mark_spawn_step(fd, SpawnStep::Sandbox);
+        assert!(!recorder.profile().complete);
+
+        recorder.spawned();
+        recorder.mark("cgroup");
+        let profile = recorder.profile();
+        let names: Vec<_> = profile.steps.iter().map(|(step, _)| *step).collect();
+        assert_eq!(names, ["prepare", "fork", "sandbox", "exec", "cgroup"]);
+        assert!(profile.complete);
+        let sum: Duration = profile.steps.iter().map(|(_, took)| *took).sum();
+        assert_eq!(sum, profile.total);
+    }
+
+    #[test]
+    fn units_never_started_have_no_profile() {
+        let mut manager = Manager::new_user();
+        assert!(matches!(
+            manager.spawn_profile("never"),
+            Err(ManagerError::NoSpawnProfile(name)) if name == "never.service"
+        ));
+    }
+}
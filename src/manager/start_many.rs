@@ -0,0 +1,85 @@
+//! Starting a batch of units, each result reported as soon as it is in
+//!
+//! Behind D-Bus StartUnits and `sysdctl start` with several units. Units are
+//! started one after the other; the manager lock is released in between, so
+//! status queries and other jobs are not held up for the whole batch.
+
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, RwLock};
+
+use super::{Manager, ManagerError};
+
+/// Outcome of starting one unit of a batch
+#[derive(Debug)]
+pub struct UnitStartResult {
+    /// Canonical unit name
+    pub name: String,
+    pub result: Result<(), ManagerError>,
+}
+
+impl Manager {
+    /// Start `names` in order in a background task; the receiver yields one
+    /// result per unit as its start finishes and closes after the last
+    ///
+    /// Must be called from within the tokio runtime.
+    pub fn start_many(
+        manager: Arc<RwLock<Manager>>,
+        names: Vec<String>,
+    ) -> mpsc::Receiver<UnitStartResult> {
+        // Room for every result, so the batch never waits on its reader
+        let (tx, rx) = mpsc::channel(names.len().max(1));
+        tokio::spawn(async move {
+            for name in names {
+                let mut mgr = manager.write().await;
+                let name = mgr.normalize_name(&name);
+                let result = mgr.start(&name).await;
+                mgr.publish_states();
+                drop(mgr);
+                // A reader that went away does not cancel the batch
+                let _ = tx.send(UnitStartResult { name, result }).await;
+            }
+        });
+        rx
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ActiveState;
+
+    #[tokio::test]
+    async fn results_arrive_in_order_and_failures_do_not_stop_the_batch() {
+        let dir = std::env::temp_dir().join(format!("sysd-start-many-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("first.target"), "[Unit]\nDescription=First\n").unwrap();
+        std::fs::write(dir.join("last.target"), "[Unit]\nDescription=Last\n").unwrap();
+        let mut manager = Manager::new_user();
+        manager.unit_paths = vec![dir.clone()];
+        let manager = Arc::new(RwLock::new(manager));
+
+        let names = ["first.target", "missing", "last.target"].map(String::from);
+        let mut results = Manager::start_many(Arc::clone(&manager), names.to_vec());
+        let mut outcomes = Vec::new();
+        while let Some(started) = results.recv().await {
+            outcomes.push((started.name, started.result.is_ok()));
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(
+            outcomes,
+            [
+                ("first.target".to_string(), true),
+                ("missing.service".to_string(), false),
+                ("last.target".to_string(), true),
+            ]
+        );
+        let states = manager.read().await.state_view();
+        assert_eq!(
+            states.get("last.target").unwrap().active,
+            ActiveState::Active
+        );
+    }
+}
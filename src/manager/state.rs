@@ -22,10 +22,18 @@
 //!     └──────────┘
 //! ```
 
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::time::{Instant, SystemTime};
+
+use serde::{Deserialize, Serialize};
+
+use crate::executor::exit_code_text;
+
+/// Transitions kept in a unit's history
+pub const HISTORY_LEN: usize = 32;
 
 /// High-level service state (maps to systemd's ActiveState)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ActiveState {
     Inactive,
     Activating,
@@ -47,12 +55,18 @@ impl ActiveState {
 }
 
 /// Detailed service state (maps to systemd's SubState)
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SubState {
     Dead,
     Starting,
     Running,
     Stopping,
+    StopWatchdog, // SIGABRT sent after watchdog timeout (TimeoutAbortSec=)
+    StopSigterm,  // SIGTERM sent to main process (TimeoutStopSec=)
+    StopSigkill,  // Main process ignored SIGTERM
+    StopPost,     // Running ExecStopPost=
+    FinalSigterm, // SIGTERM sent to leftover control group processes
+    FinalSigkill, // Leftover processes ignored SIGTERM
     Failed,
     Exited,
     AutoRestart, // Waiting for restart delay
@@ -65,6 +79,12 @@ impl SubState {
             Self::Starting => "start",
             Self::Running => "running",
             Self::Stopping => "stop",
+            Self::StopWatchdog => "stop-watchdog",
+            Self::StopSigterm => "stop-sigterm",
+            Self::StopSigkill => "stop-sigkill",
+            Self::StopPost => "stop-post",
+            Self::FinalSigterm => "final-sigterm",
+            Self::FinalSigkill => "final-sigkill",
             Self::Failed => "failed",
             Self::Exited => "exited",
             Self::AutoRestart => "auto-restart",
@@ -72,6 +92,71 @@ impl SubState {
     }
 }
 
+/// Outcome of the last service run (maps to systemd's Result and $SERVICE_RESULT)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ServiceResult {
+    #[default]
+    Success,
+    Resources,
+    Timeout,
+    ExitCode,
+    Signal,
+    Watchdog,
+    StartLimitHit,
+    /// A socket, path or timer unit activated its unit too often
+    TriggerLimitHit,
+    /// ExecHealthCheck= failed HealthCheckRetries= times in a row
+    HealthCheck,
+}
+
+impl ServiceResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Resources => "resources",
+            Self::Timeout => "timeout",
+            Self::ExitCode => "exit-code",
+            Self::Signal => "signal",
+            Self::Watchdog => "watchdog",
+            Self::StartLimitHit => "start-limit-hit",
+            Self::TriggerLimitHit => "trigger-limit-hit",
+            Self::HealthCheck => "health-check",
+        }
+    }
+
+    /// Classify a main process exit code (negative values are signal numbers)
+    pub fn from_exit_code(code: i32) -> Self {
+        match code {
+            0 => Self::Success,
+            c if c < 0 => Self::Signal,
+            _ => Self::ExitCode,
+        }
+    }
+}
+
+/// When a unit last crossed each edge of its active state, like systemd's
+/// InactiveExit/ActiveEnter/ActiveExit/InactiveEnterTimestamp
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StateTimestamps {
+    /// Left inactive or failed: a start began
+    pub inactive_exit: Option<Instant>,
+    /// Became active
+    pub active_enter: Option<Instant>,
+    /// Stopped being active
+    pub active_exit: Option<Instant>,
+    /// Became inactive or failed
+    pub inactive_enter: Option<Instant>,
+}
+
+/// A state a unit entered, with when and, for exits and failures, why
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transition {
+    pub at: SystemTime,
+    pub active: ActiveState,
+    pub sub: SubState,
+    pub reason: Option<String>,
+}
+
 /// Runtime state of a service
 #[derive(Debug)]
 pub struct ServiceState {
@@ -91,6 +176,14 @@ pub struct ServiceState {
     pub restart_count: u32,
     /// When the current restart interval started
     pub restart_interval_start: Option<Instant>,
+    /// Unmet Condition*= of the last start attempt (ConditionResult=no)
+    pub condition_failure: Option<String>,
+    /// Requires=/Requisite= dependency that kept the last start from running
+    pub failed_dependency: Option<String>,
+    /// When the unit last started, became active and stopped
+    pub timestamps: StateTimestamps,
+    /// The last HISTORY_LEN transitions, oldest first
+    pub history: VecDeque<Transition>,
 }
 
 impl Default for ServiceState {
@@ -105,6 +198,10 @@ impl Default for ServiceState {
             restart_at: None,
             restart_count: 0,
             restart_interval_start: None,
+            condition_failure: None,
+            failed_dependency: None,
+            timestamps: StateTimestamps::default(),
+            history: VecDeque::new(),
         }
     }
 }
@@ -116,58 +213,106 @@ impl ServiceState {
 
     /// Create a state for an active scope (no main PID, scopes contain multiple processes)
     pub fn running_scope() -> Self {
+        let now = Instant::now();
         Self {
             active: ActiveState::Active,
             sub: SubState::Running,
             main_pid: None,
-            state_change_time: Instant::now(),
+            state_change_time: now,
             exit_code: None,
             error: None,
             restart_at: None,
             restart_count: 0,
             restart_interval_start: None,
+            condition_failure: None,
+            failed_dependency: None,
+            timestamps: StateTimestamps {
+                inactive_exit: Some(now),
+                active_enter: Some(now),
+                ..StateTimestamps::default()
+            },
+            history: VecDeque::from([Transition {
+                at: SystemTime::now(),
+                active: ActiveState::Active,
+                sub: SubState::Running,
+                reason: None,
+            }]),
+        }
+    }
+
+    /// Move to `active` and `sub`, recording the edges crossed and the
+    /// transition with `reason`
+    fn enter(&mut self, active: ActiveState, sub: SubState, reason: Option<String>) {
+        let now = Instant::now();
+        let is_down = |state| matches!(state, ActiveState::Inactive | ActiveState::Failed);
+        let stamps = &mut self.timestamps;
+        if is_down(self.active) && !is_down(active) {
+            stamps.inactive_exit = Some(now);
+        }
+        if self.active != ActiveState::Active && active == ActiveState::Active {
+            stamps.active_enter = Some(now);
         }
+        if self.active == ActiveState::Active && active != ActiveState::Active {
+            stamps.active_exit = Some(now);
+        }
+        if !is_down(self.active) && is_down(active) {
+            stamps.inactive_enter = Some(now);
+        }
+        self.active = active;
+        self.sub = sub;
+        self.state_change_time = now;
+        if self.history.len() == HISTORY_LEN {
+            self.history.pop_front();
+        }
+        self.history.push_back(Transition {
+            at: SystemTime::now(),
+            active,
+            sub,
+            reason,
+        });
     }
 
     pub fn set_starting(&mut self) {
-        self.active = ActiveState::Activating;
-        self.sub = SubState::Starting;
-        self.state_change_time = Instant::now();
+        self.enter(ActiveState::Activating, SubState::Starting, None);
         self.exit_code = None;
         self.error = None;
+        self.condition_failure = None;
+        self.failed_dependency = None;
     }
 
     pub fn set_running(&mut self, pid: u32) {
-        self.active = ActiveState::Active;
-        self.sub = SubState::Running;
+        self.enter(ActiveState::Active, SubState::Running, None);
         self.main_pid = Some(pid);
-        self.state_change_time = Instant::now();
     }
 
     pub fn set_stopping(&mut self) {
-        self.active = ActiveState::Deactivating;
-        self.sub = SubState::Stopping;
-        self.state_change_time = Instant::now();
+        self.enter(ActiveState::Deactivating, SubState::Stopping, None);
+    }
+
+    /// Advance through the stop sequence (stop-sigterm → stop-sigkill → final-sigterm → ...)
+    pub fn set_stop_phase(&mut self, sub: SubState) {
+        self.enter(ActiveState::Deactivating, sub, None);
     }
 
     pub fn set_stopped(&mut self, exit_code: i32) {
-        self.active = ActiveState::Inactive;
-        self.sub = if exit_code == 0 {
-            SubState::Exited
-        } else {
-            SubState::Dead
+        let (sub, reason) = match exit_code {
+            0 => (SubState::Exited, None),
+            code if code < 0 => (SubState::Dead, Some(format!("Killed by signal {}", -code))),
+            code => (
+                SubState::Dead,
+                Some(format!("Exit code {}", exit_code_text(code))),
+            ),
         };
+        self.enter(ActiveState::Inactive, sub, reason);
         self.main_pid = None;
         self.exit_code = Some(exit_code);
-        self.state_change_time = Instant::now();
         self.restart_at = None;
     }
 
     /// Schedule an automatic restart after a delay
     /// Returns the new restart count
     pub fn set_auto_restart(&mut self, delay: std::time::Duration) -> u32 {
-        self.active = ActiveState::Activating;
-        self.sub = SubState::AutoRestart;
+        self.enter(ActiveState::Activating, SubState::AutoRestart, None);
         self.main_pid = None;
         self.restart_at = Some(Instant::now() + delay);
 
@@ -176,7 +321,6 @@ impl ServiceState {
             self.restart_interval_start = Some(Instant::now());
         }
         self.restart_count += 1;
-        self.state_change_time = Instant::now();
         self.restart_count
     }
 
@@ -233,28 +377,52 @@ impl ServiceState {
     }
 
     pub fn set_failed(&mut self, error: String) {
-        self.active = ActiveState::Failed;
-        self.sub = SubState::Failed;
+        self.enter(ActiveState::Failed, SubState::Failed, Some(error.clone()));
         self.main_pid = None;
         self.error = Some(error);
-        self.state_change_time = Instant::now();
     }
 
     /// Set state to active (exited) - for oneshot with RemainAfterExit=yes
     pub fn set_exited(&mut self) {
-        self.active = ActiveState::Active;
-        self.sub = SubState::Exited;
+        self.enter(ActiveState::Active, SubState::Exited, None);
         self.main_pid = None;
         self.exit_code = Some(0);
-        self.state_change_time = Instant::now();
     }
 
     /// Set state to inactive (for oneshot with RemainAfterExit=no)
     pub fn set_inactive(&mut self) {
-        self.active = ActiveState::Inactive;
-        self.sub = SubState::Dead;
+        self.enter(ActiveState::Inactive, SubState::Dead, None);
+        self.main_pid = None;
+    }
+
+    /// A start was skipped because a condition was not met; like systemd
+    /// this leaves the unit inactive rather than failed
+    pub fn set_condition_failed(&mut self, reason: String) {
+        self.enter(ActiveState::Inactive, SubState::Dead, Some(reason.clone()));
         self.main_pid = None;
-        self.state_change_time = Instant::now();
+        self.condition_failure = Some(reason);
+    }
+
+    /// The start job was dropped because `dependency` failed or, for
+    /// Requisite=, was not active (systemd's "dependency" job result).
+    /// The unit itself did not fail and stays inactive.
+    pub fn set_dependency_failed(&mut self, dependency: String) {
+        let reason = format!("Dependency {} failed", dependency);
+        self.enter(ActiveState::Inactive, SubState::Dead, Some(reason));
+        self.main_pid = None;
+        self.failed_dependency = Some(dependency);
+    }
+
+    /// Leave the failed state (`sysdctl reset-failed`)
+    pub fn reset_failed(&mut self) {
+        let reason = Some("Failed state reset".to_string());
+        self.enter(ActiveState::Inactive, SubState::Dead, reason);
+        self.error = None;
+    }
+
+    /// ConditionResult: false if the last start was skipped for an unmet condition
+    pub fn condition_result(&self) -> bool {
+        self.condition_failure.is_none()
     }
 
     pub fn is_active(&self) -> bool {
@@ -295,6 +463,62 @@ mod tests {
         assert!(state.is_active());
     }
 
+    #[test]
+    fn transitions_record_systemd_timestamps() {
+        let mut state = ServiceState::new();
+        assert_eq!(state.timestamps, StateTimestamps::default());
+        state.set_starting();
+        let started = state.timestamps.inactive_exit.unwrap();
+        assert_eq!(state.timestamps.active_enter, None);
+        state.set_running(1234);
+        let active = state.timestamps.active_enter.unwrap();
+        assert!(active >= started);
+        state.set_stopping();
+        assert!(state.timestamps.active_exit.unwrap() >= active);
+        assert_eq!(state.timestamps.inactive_enter, None);
+        state.set_stopped(0);
+        assert!(state.timestamps.inactive_enter.is_some());
+        // Deactivating again does not move the start of the last run
+        assert_eq!(state.timestamps.inactive_exit, Some(started));
+    }
+
+    #[test]
+    fn history_keeps_the_last_transitions_with_their_reasons() {
+        let mut state = ServiceState::new();
+        state.set_starting();
+        state.set_running(1234);
+        state.set_stopped(1);
+        let last = state.history.back().unwrap();
+        assert_eq!(
+            (last.active, last.sub),
+            (ActiveState::Inactive, SubState::Dead)
+        );
+        assert_eq!(last.reason.as_deref(), Some("Exit code 1"));
+        state.set_failed("Start limit hit".to_string());
+        state.reset_failed();
+        let reasons: Vec<Option<&str>> = state
+            .history
+            .iter()
+            .map(|transition| transition.reason.as_deref())
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                None,
+                None,
+                Some("Exit code 1"),
+                Some("Start limit hit"),
+                Some("Failed state reset")
+            ]
+        );
+
+        for _ in 0..HISTORY_LEN {
+            state.set_starting();
+        }
+        assert_eq!(state.history.len(), HISTORY_LEN);
+        assert_eq!(state.history[0].sub, SubState::Starting);
+    }
+
     #[test]
     fn test_state_stopping() {
         let mut state = ServiceState::new();
@@ -305,6 +529,19 @@ mod tests {
         assert!(!state.is_active());
     }
 
+    #[test]
+    fn test_state_stop_phases() {
+        let mut state = ServiceState::new();
+        state.set_running(1234);
+        state.set_stopping();
+        state.set_stop_phase(SubState::StopSigkill);
+        assert_eq!(state.active, ActiveState::Deactivating);
+        assert_eq!(state.sub.as_str(), "stop-sigkill");
+        state.set_stop_phase(SubState::FinalSigterm);
+        assert_eq!(state.sub.as_str(), "final-sigterm");
+        assert_eq!(state.main_pid, Some(1234));
+    }
+
     #[test]
     fn test_state_stopped_clean() {
         let mut state = ServiceState::new();
@@ -337,6 +574,18 @@ mod tests {
         assert_eq!(state.error, Some("timeout".to_string()));
     }
 
+    #[test]
+    fn test_state_condition_failed() {
+        let mut state = ServiceState::new();
+        state.set_condition_failed("ConditionPathExists=/x was not met".to_string());
+        assert_eq!(state.active, ActiveState::Inactive);
+        assert_eq!(state.sub, SubState::Dead);
+        assert!(!state.condition_result());
+
+        state.set_starting();
+        assert!(state.condition_result());
+    }
+
     #[test]
     fn test_active_state_as_str() {
         assert_eq!(ActiveState::Inactive.as_str(), "inactive");
@@ -356,6 +605,18 @@ mod tests {
         assert_eq!(SubState::Exited.as_str(), "exited");
     }
 
+    #[test]
+    fn test_service_result_from_exit_code() {
+        assert_eq!(ServiceResult::from_exit_code(0), ServiceResult::Success);
+        assert_eq!(ServiceResult::from_exit_code(3), ServiceResult::ExitCode);
+        assert_eq!(ServiceResult::from_exit_code(-15), ServiceResult::Signal);
+        assert_eq!(ServiceResult::default().as_str(), "success");
+        assert_eq!(ServiceResult::ExitCode.as_str(), "exit-code");
+        assert_eq!(ServiceResult::StartLimitHit.as_str(), "start-limit-hit");
+        assert_eq!(ServiceResult::TriggerLimitHit.as_str(), "trigger-limit-hit");
+        assert_eq!(ServiceResult::HealthCheck.as_str(), "health-check");
+    }
+
     #[test]
     fn test_running_scope() {
         let state = ServiceState::running_scope();
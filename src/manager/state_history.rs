@@ -0,0 +1,154 @@
+//! The last state transitions of each unit (`sysd analyze history`,
+//! GetUnitStateHistory)
+//!
+//! Every unit state keeps its last HISTORY_LEN transitions with when they
+//! happened and, for exits and failures, why, so a unit that flapped or
+//! failed overnight can be looked at afterwards. With
+//! PersistStateHistory=yes in system.conf (user.conf) the histories are
+//! written to the state tree every minute and when the manager exits, shuts
+//! down or soft-reboots, and the next manager puts them in front of the
+//! transitions of its own run.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::state_dir::StateDirs;
+
+use super::state::{Transition, HISTORY_LEN};
+use super::{Manager, ManagerError};
+
+impl Manager {
+    /// Whether histories are kept across restarts (PersistStateHistory=)
+    pub fn persists_state_history(&self) -> bool {
+        self.config.persist_state_history
+    }
+
+    /// The last transitions of unit `name`, oldest first, including those
+    /// a previous manager saved
+    pub fn state_history(&self, name: &str) -> Result<Vec<Transition>, ManagerError> {
+        let name = self.normalize_name(name);
+        if !self.states.contains_key(&name) && !self.restored_history.contains_key(&name) {
+            return Err(ManagerError::NotFound(name));
+        }
+        Ok(self.merged_history(&name))
+    }
+
+    /// Read the histories a previous manager saved; call before booting
+    pub fn restore_state_history(&mut self) {
+        if !self.persists_state_history() {
+            return;
+        }
+        self.restored_history = read_history(&self.state_history_path());
+    }
+
+    /// Write every history down for the next manager
+    pub fn save_state_history(&self) {
+        if !self.persists_state_history() {
+            return;
+        }
+        let path = self.state_history_path();
+        if let Err(e) = write_history(&path, &self.histories_to_save()) {
+            log::warn!("Failed to write {}: {}", path.display(), e);
+        }
+    }
+
+    /// The histories of this run's units and the saved ones of units still
+    /// loaded; those of units removed since are dropped
+    fn histories_to_save(&self) -> HashMap<String, Vec<Transition>> {
+        let restored = self
+            .restored_history
+            .keys()
+            .filter(|name| self.units.contains_key(*name));
+        self.states
+            .keys()
+            .chain(restored)
+            .map(|name| (name.clone(), self.merged_history(name)))
+            .collect()
+    }
+
+    fn merged_history(&self, name: &str) -> Vec<Transition> {
+        let restored = self.restored_history.get(name).into_iter().flatten();
+        let live = self.states.get(name).into_iter().flat_map(|s| &s.history);
+        let mut history: Vec<Transition> = restored.chain(live).cloned().collect();
+        let excess = history.len().saturating_sub(HISTORY_LEN);
+        history.drain(..excess);
+        history
+    }
+
+    fn state_history_path(&self) -> std::path::PathBuf {
+        StateDirs::for_mode(self.user_mode).state_history()
+    }
+}
+
+fn read_history(path: &Path) -> HashMap<String, Vec<Transition>> {
+    let Ok(data) = std::fs::read(path) else {
+        return HashMap::new();
+    };
+    rmp_serde::from_slice(&data).unwrap_or_else(|e| {
+        log::warn!("Discarding unreadable {}: {}", path.display(), e);
+        HashMap::new()
+    })
+}
+
+fn write_history(path: &Path, histories: &HashMap<String, Vec<Transition>>) -> std::io::Result<()> {
+    let data = rmp_serde::to_vec(histories)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    // Written aside and renamed, so a crash never leaves half a file
+    let partial = path.with_extension("tmp");
+    std::fs::write(&partial, data)?;
+    std::fs::rename(partial, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+    use crate::units::{Service, Unit};
+
+    #[test]
+    fn saved_histories_come_before_the_transitions_of_this_run() {
+        let path = std::env::temp_dir().join(format!("sysd-state-history-{}", std::process::id()));
+        let mut before = ServiceState::new();
+        before.set_starting();
+        before.set_failed("Exit code 1".to_string());
+        let saved = HashMap::from([("web.service".to_string(), Vec::from(before.history))]);
+        write_history(&path, &saved).unwrap();
+
+        let mut manager = Manager::new_user();
+        manager.restored_history = read_history(&path);
+        let _ = std::fs::remove_file(&path);
+        let mut state = ServiceState::new();
+        state.set_starting();
+        manager.states.insert("web.service".into(), state);
+
+        let history = manager.state_history("web").unwrap();
+        let reasons: Vec<Option<&str>> = history.iter().map(|t| t.reason.as_deref()).collect();
+        assert_eq!(reasons, [None, Some("Exit code 1"), None]);
+        assert!(matches!(
+            manager.state_history("other.service"),
+            Err(ManagerError::NotFound(_))
+        ));
+        assert!(read_history(&path).is_empty());
+    }
+
+    #[test]
+    fn saved_histories_of_units_no_longer_loaded_are_dropped() {
+        let mut manager = Manager::new_user();
+        let mut before = ServiceState::new();
+        before.set_starting();
+        let saved = Vec::from(before.history);
+        manager.restored_history = HashMap::from([
+            ("kept.service".to_string(), saved.clone()),
+            ("removed.service".to_string(), saved),
+        ]);
+        let kept = Service::new("kept.service".to_string());
+        manager.insert_unit("kept.service".into(), Unit::Service(kept));
+
+        let histories = manager.histories_to_save();
+        assert!(histories.contains_key("kept.service"));
+        assert!(!histories.contains_key("removed.service"));
+    }
+}
@@ -0,0 +1,354 @@
+//! Service stop sequence
+//!
+//! A `StopJob` owns everything needed to take a service down (main process,
+//! ExecStop=/ExecStopPost= commands, TimeoutStopSec= and the service cgroup), so
+//! the sequence can run in a background task without holding the manager.
+//! Progress is reported back as `StopEvent`s and applied by the runtime loop.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::process::Child;
+use tokio::sync::mpsc;
+
+use crate::cgroups::{CgroupManager, EmptyWatcher};
+use crate::units::KillMode;
+
+use super::{
+    exit_status_code, run_command_lines, stop_post_environment, ControlPids, ExecPhase, Manager,
+    ServiceResult, StopEvent, SubState,
+};
+
+/// Result of a stop sequence: how it ended and the main process exit code
+pub(super) type StopOutcome = (ServiceResult, Option<i32>);
+
+pub(super) struct StopJob {
+    name: String,
+    child: Option<Child>,
+    kill_mode: KillMode,
+    send_sighup: bool,
+    timeout: Duration,
+    exec_stop: Vec<String>,
+    exec_stop_post: Vec<String>,
+    cgroup: Option<(CgroupManager, PathBuf)>,
+    control_pids: ControlPids,
+    events: Option<mpsc::Sender<StopEvent>>,
+    /// Shared with the other jobs of a mass stop
+    empty_watcher: Option<Arc<EmptyWatcher>>,
+    /// stop-watchdog: SIGABRT the main process and give it this long
+    abort_timeout: Option<Duration>,
+    /// Reported instead of how the main process went
+    result: Option<ServiceResult>,
+}
+
+impl Manager {
+    /// Collect what the stop sequence of `name` needs, taking ownership of its main process
+    pub(super) fn stop_job(
+        &mut self,
+        name: &str,
+        events: Option<mpsc::Sender<StopEvent>>,
+    ) -> StopJob {
+        let (kill_mode, send_sighup) = self.stop_signal_config(name);
+        let (exec_stop, exec_stop_post) = self
+            .units
+            .get(name)
+            .and_then(|u| u.as_service())
+            .map(|s| (s.service.exec_stop.clone(), s.service.exec_stop_post.clone()))
+            .unwrap_or_default();
+        let child = self.processes.remove(name);
+        // The exit is collected by the stop job, keep reap() from treating it as a crash
+        self.pid_to_service.retain(|_, service| service != name);
+        StopJob {
+            name: name.to_string(),
+            child,
+            kill_mode,
+            send_sighup,
+            timeout: self.stop_timeout(name),
+            exec_stop,
+            exec_stop_post,
+            cgroup: self
+                .cgroup_manager
+                .clone()
+                .zip(self.cgroup_paths.get(name).cloned()),
+            control_pids: self.control_pids.clone(),
+            events,
+            empty_watcher: None,
+            abort_timeout: None,
+            result: None,
+        }
+    }
+}
+
+impl StopJob {
+    /// The service cgroup, if the service has one
+    pub(super) fn cgroup_path(&self) -> Option<&Path> {
+        self.cgroup.as_ref().map(|(_, path)| path.as_path())
+    }
+
+    /// Wait for the cgroup to empty through `watcher` instead of polling it
+    pub(super) fn with_empty_watcher(mut self, watcher: Arc<EmptyWatcher>) -> Self {
+        self.empty_watcher = Some(watcher);
+        self
+    }
+
+    /// SIGABRT the main process (for a core dump) instead of running
+    /// ExecStop= and sending KillSignal=; SIGKILL it after `timeout`
+    pub(super) fn aborting(mut self, timeout: Duration) -> Self {
+        self.exec_stop.clear();
+        self.abort_timeout = Some(timeout);
+        self
+    }
+
+    /// End with `result` (watchdog, health-check, ...), whatever the main
+    /// process exited with
+    pub(super) fn failing_with(mut self, result: ServiceResult) -> Self {
+        self.result = Some(result);
+        self
+    }
+
+    /// ExecStop= → stop-sigterm → stop-sigkill → stop-post → final-sigterm → final-sigkill
+    pub(super) async fn run(mut self) -> StopOutcome {
+        let mut env = Vec::new();
+        if let Some(pid) = self.child.as_ref().and_then(|child| child.id()) {
+            env.push(("MAINPID".to_string(), pid.to_string()));
+        }
+        let commands = &self.exec_stop;
+        run_command_lines(&self.name, ExecPhase::Stop, commands, &env, &self.control_pids).await;
+
+        let outcome = match (self.child.take(), self.abort_timeout) {
+            (Some(child), Some(timeout)) => self.abort_main(child, timeout).await,
+            (Some(mut child), None) => {
+                Manager::send_signals_to_child(
+                    &mut child,
+                    &self.kill_mode,
+                    self.send_sighup,
+                    &self.name,
+                )
+                .await;
+                self.wait_for_main_exit(child).await
+            }
+            (None, _) => (ServiceResult::Success, None),
+        };
+        let outcome = (self.result.unwrap_or(outcome.0), outcome.1);
+
+        self.phase(SubState::StopPost).await;
+        let env = stop_post_environment(outcome.0, outcome.1);
+        let commands = &self.exec_stop_post;
+        run_command_lines(
+            &self.name,
+            ExecPhase::StopPost,
+            commands,
+            &env,
+            &self.control_pids,
+        )
+        .await;
+        self.kill_remaining_processes().await;
+        outcome
+    }
+
+    async fn phase(&self, sub: SubState) {
+        let Some(tx) = self.events.as_ref() else {
+            return;
+        };
+        let _ = tx
+            .send(StopEvent::Phase {
+                service_name: self.name.clone(),
+                sub,
+            })
+            .await;
+    }
+
+    /// stop-sigterm → stop-sigkill: give the main process TimeoutStopSec= to exit, then SIGKILL it
+    async fn wait_for_main_exit(&self, child: Child) -> StopOutcome {
+        self.phase(SubState::StopSigterm).await;
+        self.wait_or_kill(child, self.timeout).await
+    }
+
+    /// stop-watchdog → stop-sigkill: SIGABRT the main process, SIGKILL it
+    /// after TimeoutAbortSec=
+    async fn abort_main(&self, child: Child, timeout: Duration) -> StopOutcome {
+        self.phase(SubState::StopWatchdog).await;
+        if let Some(pid) = child.id() {
+            unsafe { libc::kill(pid as i32, libc::SIGABRT) };
+        }
+        self.wait_or_kill(child, timeout).await
+    }
+
+    async fn wait_or_kill(&self, mut child: Child, timeout: Duration) -> StopOutcome {
+        match tokio::time::timeout(timeout, child.wait()).await {
+            Ok(Ok(status)) => {
+                let code = exit_status_code(status);
+                log::info!("Stopped {} (exit code {})", self.name, code);
+                (ServiceResult::Success, Some(code))
+            }
+            Ok(Err(e)) => {
+                log::warn!("Failed to wait for {}: {}", self.name, e);
+                (ServiceResult::Resources, None)
+            }
+            Err(_) => {
+                log::warn!("Timeout stopping {}, sending SIGKILL", self.name);
+                self.phase(SubState::StopSigkill).await;
+                let _ = child.kill().await;
+                (ServiceResult::Timeout, Some(-9))
+            }
+        }
+    }
+
+    /// final-sigterm → final-sigkill: clean up processes left behind in the service cgroup
+    async fn kill_remaining_processes(&self) {
+        if !matches!(self.kill_mode, KillMode::ControlGroup | KillMode::Mixed) {
+            return;
+        }
+        let Some((cgroup_mgr, cgroup_path)) = self.cgroup.as_ref() else {
+            return;
+        };
+        // KillMode=mixed only sends SIGTERM to the main process
+        if self.kill_mode == KillMode::ControlGroup {
+            let signalled = self
+                .signal_cgroup(cgroup_mgr, cgroup_path, SubState::FinalSigterm, libc::SIGTERM)
+                .await;
+            if !signalled || self.wait_for_cgroup_empty(cgroup_mgr, cgroup_path).await {
+                return;
+            }
+        }
+        if self
+            .signal_cgroup(cgroup_mgr, cgroup_path, SubState::FinalSigkill, libc::SIGKILL)
+            .await
+        {
+            self.wait_for_cgroup_empty(cgroup_mgr, cgroup_path).await;
+        }
+    }
+
+    async fn signal_cgroup(
+        &self,
+        cgroup_mgr: &CgroupManager,
+        cgroup_path: &Path,
+        phase: SubState,
+        signal: i32,
+    ) -> bool {
+        let pids = cgroup_mgr.get_pids(cgroup_path).unwrap_or_default();
+        if pids.is_empty() {
+            return false;
+        }
+        log::info!(
+            "{}: {} remaining process(es), entering {}",
+            self.name,
+            pids.len(),
+            phase.as_str()
+        );
+        self.phase(phase).await;
+        for pid in pids {
+            unsafe { libc::kill(pid as i32, signal) };
+        }
+        true
+    }
+
+    async fn wait_for_cgroup_empty(&self, cgroup_mgr: &CgroupManager, cgroup_path: &Path) -> bool {
+        let deadline = Instant::now() + self.timeout;
+        if let Some(watcher) = self.empty_watcher.as_ref() {
+            return watcher.wait(cgroup_path, deadline).await;
+        }
+        loop {
+            if cgroup_mgr.is_empty(cgroup_path).unwrap_or(true) {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::{ActiveState, ServiceState};
+    use crate::units::{Service, Unit};
+
+    fn manager_with_running(name: &str, configure: impl FnOnce(&mut Service)) -> Manager {
+        let mut manager = Manager::new_user();
+        let mut service = Service::new(name.to_string());
+        configure(&mut service);
+        manager
+            .units
+            .insert(name.to_string(), Unit::Service(service));
+        let mut state = ServiceState::new();
+        state.set_running(0);
+        manager.states.insert(name.to_string(), state);
+        manager
+    }
+
+    #[tokio::test]
+    async fn stop_job_reports_clean_exit() {
+        let mut manager = manager_with_running("wait.service", |service| {
+            service.service.timeout_stop_sec = Some(Duration::from_secs(1));
+        });
+        let child = tokio::process::Command::new("/bin/true").spawn().unwrap();
+        manager.processes.insert("wait.service".to_string(), child);
+
+        let outcome = manager.stop_job("wait.service", None).run().await;
+
+        assert_eq!(outcome, (ServiceResult::Success, Some(0)));
+        assert!(!manager.processes.contains_key("wait.service"));
+    }
+
+    #[tokio::test]
+    async fn stop_job_escalates_to_sigkill_and_reports_phases() {
+        let mut manager = manager_with_running("stuck.service", |service| {
+            service.service.timeout_stop_sec = Some(Duration::from_millis(10));
+            service.service.kill_mode = KillMode::None;
+        });
+        let child = tokio::process::Command::new("/bin/sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        manager.processes.insert("stuck.service".to_string(), child);
+        let (tx, mut rx) = mpsc::channel(8);
+
+        let outcome = manager.stop_job("stuck.service", Some(tx)).run().await;
+
+        assert_eq!(outcome, (ServiceResult::Timeout, Some(-9)));
+        let mut phases = Vec::new();
+        while let Ok(StopEvent::Phase { sub, .. }) = rx.try_recv() {
+            phases.push(sub);
+        }
+        assert_eq!(
+            phases,
+            [
+                SubState::StopSigterm,
+                SubState::StopSigkill,
+                SubState::StopPost
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn enqueued_stop_is_completed_by_stop_events() {
+        let mut manager = manager_with_running("bg.service", |_| {});
+        let child = tokio::process::Command::new("/bin/sleep")
+            .arg("5")
+            .spawn()
+            .unwrap();
+        manager.processes.insert("bg.service".to_string(), child);
+        let mut rx = manager.take_stop_event_rx().unwrap();
+
+        manager.enqueue_stop("bg").await.unwrap();
+        assert_eq!(
+            manager.states.get("bg.service").unwrap().active,
+            ActiveState::Deactivating
+        );
+
+        while let Some(event) = rx.recv().await {
+            let finished = matches!(event, StopEvent::Finished { .. });
+            manager.handle_stop_event(event).await;
+            if finished {
+                break;
+            }
+        }
+        let state = manager.states.get("bg.service").unwrap();
+        assert_eq!(state.active, ActiveState::Inactive);
+        assert_eq!(state.exit_code, Some(-libc::SIGTERM));
+    }
+}
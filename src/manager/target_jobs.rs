@@ -0,0 +1,198 @@
+//! When a target is reached
+//!
+//! A target has no process of its own; its start job finishes when the jobs
+//! of its required members (Requires=, .requires/, BindsTo=) finish. While a
+//! member is still activating (Type=notify waiting for READY=1, a slow
+//! oneshot, ...) the target stays activating and is re-checked on every
+//! maintenance tick; once a required member fails the target's job fails
+//! with the "dependency" result and it stays inactive. Wanted members do not
+//! hold up or fail a target. `sysdctl start --wait` on a target blocks until
+//! it is reached.
+
+use crate::manager::state::ActiveState;
+
+use super::{Manager, ManagerError};
+
+/// Where the start job of a target stands
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TargetProgress {
+    Reached,
+    /// A required member is still activating
+    Waiting,
+    /// This required member failed
+    Failed(String),
+}
+
+impl Manager {
+    /// Run the start job of the target `name`: reach it if its required
+    /// members are up, or leave it activating until they are
+    pub(super) fn start_target(&mut self, name: &str) -> Result<(), ManagerError> {
+        match self.target_progress(name) {
+            TargetProgress::Failed(member) => {
+                self.fail_target(name, member.clone());
+                Err(ManagerError::DependencyFailed(name.to_string(), member))
+            }
+            TargetProgress::Waiting => {
+                log::debug!("Target {} waiting for required units", name);
+                self.states
+                    .entry(name.to_string())
+                    .or_default()
+                    .set_starting();
+                self.pending_targets.insert(name.to_string());
+                Ok(())
+            }
+            TargetProgress::Reached => {
+                self.reach_target(name);
+                Ok(())
+            }
+        }
+    }
+
+    /// Finish the start jobs of activating targets whose required members
+    /// are done (called periodically)
+    pub fn process_pending_targets(&mut self) {
+        let mut pending: Vec<String> = self.pending_targets.iter().cloned().collect();
+        pending.sort();
+        for name in pending {
+            let activating = self
+                .states
+                .get(&name)
+                .is_some_and(|state| state.active == ActiveState::Activating);
+            if !activating {
+                // Stopped (or restarted) while waiting
+                self.pending_targets.remove(&name);
+                continue;
+            }
+            match self.target_progress(&name) {
+                TargetProgress::Waiting => {}
+                TargetProgress::Reached => self.reach_target(&name),
+                TargetProgress::Failed(member) => {
+                    log::warn!("Dependency failed for {}: {}", name, member);
+                    self.fail_target(&name, member);
+                }
+            }
+        }
+    }
+
+    fn target_progress(&self, name: &str) -> TargetProgress {
+        let Some(section) = self.units.get(name).map(|unit| unit.unit_section()) else {
+            return TargetProgress::Reached;
+        };
+        let members = section
+            .requires
+            .iter()
+            .chain(&section.requires_dir)
+            .chain(&section.binds_to)
+            .map(|member| self.normalize_name(member));
+        let mut progress = TargetProgress::Reached;
+        for member in members {
+            if self.placeholders.contains(&member) {
+                return TargetProgress::Failed(member);
+            }
+            let Some(state) = self.states.get(&member) else {
+                continue;
+            };
+            match state.active {
+                ActiveState::Failed => return TargetProgress::Failed(member),
+                ActiveState::Inactive if state.failed_dependency.is_some() => {
+                    return TargetProgress::Failed(member);
+                }
+                ActiveState::Activating => progress = TargetProgress::Waiting,
+                _ => {}
+            }
+        }
+        progress
+    }
+
+    fn reach_target(&mut self, name: &str) {
+        self.pending_targets.remove(name);
+        if let Some(state) = self.states.get_mut(name) {
+            state.set_running(0);
+        }
+        log::debug!("Target {} reached", name);
+    }
+
+    fn fail_target(&mut self, name: &str, member: String) {
+        self.pending_targets.remove(name);
+        self.states
+            .entry(name.to_string())
+            .or_default()
+            .set_dependency_failed(member);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+    use crate::units::{Service, Target, Unit};
+
+    fn manager_with_target(requires: &[&str], wants: &[&str]) -> Manager {
+        let mut manager = Manager::new_user();
+        let mut target = Target::new("app.target".to_string());
+        target.unit.requires = requires.iter().map(|name| name.to_string()).collect();
+        target.unit.wants = wants.iter().map(|name| name.to_string()).collect();
+        manager.insert_unit("app.target".to_string(), Unit::Target(target));
+        manager
+            .states
+            .insert("app.target".to_string(), ServiceState::new());
+        for name in requires.iter().chain(wants) {
+            let service = Service::new(name.to_string());
+            manager.insert_unit(name.to_string(), Unit::Service(service));
+            manager.states.insert(name.to_string(), ServiceState::new());
+        }
+        manager
+    }
+
+    fn active(manager: &Manager, name: &str) -> ActiveState {
+        manager.states[name].active
+    }
+
+    #[test]
+    fn target_waits_for_activating_required_members() {
+        let mut manager = manager_with_target(&["db.service"], &["extra.service"]);
+        manager.states.get_mut("db.service").unwrap().set_starting();
+        manager
+            .states
+            .get_mut("extra.service")
+            .unwrap()
+            .set_failed("exit code 1".to_string());
+
+        manager.start_target("app.target").unwrap();
+        assert_eq!(active(&manager, "app.target"), ActiveState::Activating);
+
+        manager.process_pending_targets();
+        assert_eq!(active(&manager, "app.target"), ActiveState::Activating);
+        manager
+            .states
+            .get_mut("db.service")
+            .unwrap()
+            .set_running(42);
+        manager.process_pending_targets();
+        assert_eq!(active(&manager, "app.target"), ActiveState::Active);
+        assert!(manager.pending_targets.is_empty());
+    }
+
+    #[test]
+    fn target_fails_when_a_required_member_fails() {
+        let mut manager = manager_with_target(&["db.service"], &[]);
+        manager.states.get_mut("db.service").unwrap().set_starting();
+        manager.start_target("app.target").unwrap();
+
+        manager
+            .states
+            .get_mut("db.service")
+            .unwrap()
+            .set_failed("timeout".to_string());
+        manager.process_pending_targets();
+        let state = &manager.states["app.target"];
+        assert_eq!(state.active, ActiveState::Inactive);
+        assert_eq!(state.failed_dependency.as_deref(), Some("db.service"));
+
+        assert!(matches!(
+            manager.start_target("app.target"),
+            Err(ManagerError::DependencyFailed(target, member))
+                if target == "app.target" && member == "db.service"
+        ));
+    }
+}
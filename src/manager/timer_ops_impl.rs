@@ -2,6 +2,8 @@
 //
 // Handles scheduling and activation of timer units.
 
+use std::time::Instant;
+
 use tokio::sync::mpsc;
 
 use crate::units::Timer;
@@ -37,11 +39,14 @@ impl Manager {
             let tx = self.timer_tx.clone();
 
             log::debug!("{}: scheduling to fire in {:?}", name, delay);
+            self.timer_elapse
+                .insert(name.to_string(), Instant::now() + delay);
 
             // Spawn timer watcher task
-            tokio::spawn(async move {
+            let watcher = tokio::spawn(async move {
                 timer_scheduler::watch_timer(timer_name, service_name, delay, tx).await;
             });
+            self.track_unit_task(name, watcher.abort_handle());
         } else {
             log::debug!("{}: no trigger configured, timer idle", name);
         }
@@ -70,8 +75,8 @@ impl Manager {
 
         log::info!("Stopping timer {}", name);
 
-        // Timer tasks will complete naturally or on next fire
-        // For now, we just mark the timer as stopped
+        self.abort_unit_tasks(name);
+        self.timer_elapse.remove(name);
 
         if let Some(state) = self.states.get_mut(name) {
             state.set_stopped(0);
@@ -96,6 +101,10 @@ impl Manager {
             fired.service_name,
             fired.timer_name
         );
+        self.timer_elapse.remove(&fired.timer_name);
+        if !self.trigger_allowed(&fired.timer_name) {
+            return Ok(());
+        }
 
         // Check if service is already running
         if let Some(state) = self.states.get(&fired.service_name) {
@@ -133,7 +142,10 @@ impl Manager {
         let Some(delay) = timer_scheduler::calculate_next_trigger(timer, self.boot_time) else {
             return;
         };
-        schedule_timer_watch(timer_name, timer, delay, self.timer_tx.clone());
+        self.timer_elapse
+            .insert(timer_name.to_string(), Instant::now() + delay);
+        let watcher = schedule_timer_watch(timer_name, timer, delay, self.timer_tx.clone());
+        self.track_unit_task(timer_name, watcher);
     }
 }
 
@@ -146,13 +158,14 @@ fn schedule_timer_watch(
     timer: &Timer,
     delay: std::time::Duration,
     tx: mpsc::Sender<timer_scheduler::TimerFired>,
-) {
+) -> tokio::task::AbortHandle {
     let service_name = timer.service_name();
     let timer_name = timer_name.to_string();
     log::debug!("{}: rescheduling to fire in {:?}", timer_name, delay);
     tokio::spawn(async move {
         timer_scheduler::watch_timer(timer_name, service_name, delay, tx).await;
-    });
+    })
+    .abort_handle()
 }
 
 #[cfg(test)]
@@ -0,0 +1,242 @@
+//! Activation rate limits of socket, path and timer units
+//!
+//! Each trigger unit may activate its unit TriggerLimitBurst= times per
+//! TriggerLimitIntervalSec= (set in [Socket] and [Path]; timers always use
+//! the defaults), and all triggers together as often as the [Manager]
+//! section's TriggerLimitBurst= per TriggerLimitIntervalSec= allows. A
+//! trigger over either limit fails the triggering unit with result
+//! trigger-limit-hit and stops its watchers, so a flapping path, a timer
+//! firing in a loop or a connection storm cannot keep the manager busy.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::units::Unit;
+
+use super::{ActiveState, Manager, ServiceResult};
+
+/// Per-unit window, as in systemd
+const UNIT_INTERVAL: Duration = Duration::from_secs(2);
+/// Activations per window of an Accept=no socket
+const SOCKET_BURST: u32 = 20;
+/// Activations per window of Accept=yes sockets, path and timer units
+const BURST: u32 = 200;
+
+/// Window of all triggers together
+const GLOBAL_INTERVAL: Duration = Duration::from_secs(10);
+const GLOBAL_BURST: u32 = 2000;
+
+/// At most `burst` events per `interval`; 0 for either means no limit
+#[derive(Debug, Clone, Copy)]
+struct RateLimit {
+    interval: Duration,
+    burst: u32,
+    begin: Option<Instant>,
+    count: u32,
+}
+
+impl RateLimit {
+    fn new(interval: Duration, burst: u32) -> Self {
+        Self {
+            interval,
+            burst,
+            begin: None,
+            count: 0,
+        }
+    }
+
+    /// Count one event at `now`; false once the current window is full
+    fn allow(&mut self, now: Instant) -> bool {
+        if self.interval.is_zero() || self.burst == 0 {
+            return true;
+        }
+        if self
+            .begin
+            .is_none_or(|begin| now.duration_since(begin) >= self.interval)
+        {
+            self.begin = Some(now);
+            self.count = 0;
+        }
+        self.count = self.count.saturating_add(1);
+        self.count <= self.burst
+    }
+}
+
+/// Recent activations by trigger units
+#[derive(Debug, Default)]
+pub(crate) struct TriggerLimits {
+    units: HashMap<String, RateLimit>,
+    global: Option<RateLimit>,
+    /// When the global limit was last warned about
+    warned: Option<Instant>,
+    /// Triggers refused by the global limit since that warning
+    suppressed: u32,
+}
+
+impl TriggerLimits {
+    /// Whether a global limit warning is due at `now`, at most one per
+    /// `interval`; counts the refusals it does not log
+    fn warning_due(&mut self, now: Instant, interval: Duration) -> bool {
+        if self
+            .warned
+            .is_some_and(|warned| now.duration_since(warned) < interval)
+        {
+            self.suppressed += 1;
+            return false;
+        }
+        self.warned = Some(now);
+        true
+    }
+}
+
+impl Manager {
+    /// Count one activation by `trigger`. Over its own limit or the global
+    /// one, `trigger` fails with trigger-limit-hit and false is returned;
+    /// so is it for activations still queued from a unit that failed so.
+    pub(super) fn trigger_allowed(&mut self, trigger: &str) -> bool {
+        if self
+            .states
+            .get(trigger)
+            .is_some_and(|state| state.active == ActiveState::Failed)
+        {
+            log::debug!("{} has failed, dropping its activation", trigger);
+            return false;
+        }
+
+        let now = Instant::now();
+        let unit_limit = self.unit_trigger_limit(trigger);
+        let global_interval = self
+            .config
+            .trigger_limit_interval_sec
+            .unwrap_or(GLOBAL_INTERVAL);
+        let global_burst = self.config.trigger_limit_burst.unwrap_or(GLOBAL_BURST);
+        let limits = &mut self.trigger_limits;
+        let unit_allowed = limits
+            .units
+            .entry(trigger.to_string())
+            .or_insert(unit_limit)
+            .allow(now);
+        let global_allowed = limits
+            .global
+            .get_or_insert_with(|| RateLimit::new(global_interval, global_burst))
+            .allow(now);
+        if unit_allowed && global_allowed {
+            return true;
+        }
+
+        if !unit_allowed {
+            log::warn!(
+                "{} activated more than {} times in {:?}, refusing further activation",
+                trigger,
+                unit_limit.burst,
+                unit_limit.interval
+            );
+        } else if limits.warning_due(now, global_interval) {
+            let suppressed = std::mem::take(&mut limits.suppressed);
+            log::warn!(
+                "More than {} activations in {:?}, failing {} ({} more failed since the last warning)",
+                global_burst,
+                global_interval,
+                trigger,
+                suppressed
+            );
+        }
+        self.fail_trigger(trigger);
+        false
+    }
+
+    /// The per-unit limit of trigger unit `name`
+    fn unit_trigger_limit(&self, name: &str) -> RateLimit {
+        let (interval, burst) = match self.units.get(name) {
+            Some(Unit::Socket(socket)) => {
+                let default = if socket.socket.accept {
+                    BURST
+                } else {
+                    SOCKET_BURST
+                };
+                (
+                    socket.socket.trigger_limit_interval_sec,
+                    Some(socket.socket.trigger_limit_burst.unwrap_or(default)),
+                )
+            }
+            Some(Unit::Path(path)) => (
+                path.path.trigger_limit_interval_sec,
+                path.path.trigger_limit_burst,
+            ),
+            _ => (None, None),
+        };
+        RateLimit::new(interval.unwrap_or(UNIT_INTERVAL), burst.unwrap_or(BURST))
+    }
+
+    /// Stop the watchers of trigger unit `name` and mark it failed
+    fn fail_trigger(&mut self, name: &str) {
+        match self.units.get(name).and_then(Unit::as_socket).cloned() {
+            Some(socket) => self.close_listeners(name, &socket),
+            None => self.abort_unit_tasks(name),
+        }
+        self.timer_elapse.remove(name);
+        // A restart begins with a fresh window
+        self.trigger_limits.units.remove(name);
+        if let Some(state) = self.states.get_mut(name) {
+            state.set_failed(ServiceResult::TriggerLimitHit.as_str().to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+    use crate::units::{PathUnit, Timer};
+
+    #[test]
+    fn rate_limit_refuses_beyond_burst_until_window_ends() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(Duration::from_secs(2), 3);
+        assert!((0..3).all(|_| limit.allow(start)));
+        assert!(!limit.allow(start + Duration::from_secs(1)));
+        assert!(limit.allow(start + Duration::from_secs(2)));
+
+        let mut unlimited = RateLimit::new(Duration::from_secs(2), 0);
+        assert!((0..1000).all(|_| unlimited.allow(start)));
+    }
+
+    #[test]
+    fn trigger_over_its_limit_fails_and_stays_refused() {
+        let mut manager = Manager::new_user();
+        let mut path = PathUnit::new("inbox.path".to_string());
+        path.path.trigger_limit_burst = Some(2);
+        manager.insert_unit("inbox.path".into(), Unit::Path(path));
+        let mut state = ServiceState::new();
+        state.set_running(0);
+        manager.states.insert("inbox.path".into(), state);
+
+        assert!(manager.trigger_allowed("inbox.path"));
+        assert!(manager.trigger_allowed("inbox.path"));
+        assert!(!manager.trigger_allowed("inbox.path"));
+        let state = &manager.states["inbox.path"];
+        assert_eq!(state.active, ActiveState::Failed);
+        assert_eq!(state.error.as_deref(), Some("trigger-limit-hit"));
+        assert!(!manager.trigger_allowed("inbox.path"));
+    }
+
+    #[test]
+    fn global_limit_fails_the_trigger_that_exceeds_it() {
+        let mut manager = Manager::new_user();
+        manager.config.trigger_limit_burst = Some(3);
+        for name in ["a.timer", "b.timer"] {
+            let timer = Timer::new(name.to_string());
+            manager.insert_unit(name.into(), Unit::Timer(timer));
+            let mut state = ServiceState::new();
+            state.set_running(0);
+            manager.states.insert(name.into(), state);
+        }
+
+        assert!(manager.trigger_allowed("a.timer"));
+        assert!(manager.trigger_allowed("a.timer"));
+        assert!(manager.trigger_allowed("b.timer"));
+        assert!(!manager.trigger_allowed("b.timer"));
+        assert_eq!(manager.states["b.timer"].active, ActiveState::Failed);
+        assert!(manager.states["a.timer"].is_active());
+    }
+}
@@ -0,0 +1,86 @@
+//! Ownership and mode checks of unit files before they are loaded
+//!
+//! A unit file decides what runs and as whom, so whoever can write one can
+//! run anything as root. Before a unit is loaded, its file and every drop-in
+//! must belong to root or the user the manager runs as, and must not be
+//! writable by everyone. UnitFilePermissions= in system.conf (user.conf)
+//! chooses whether a unit failing the check is refused (the default), loaded
+//! with a warning, or not checked at all.
+
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::units::{FileCheck, UnitFilePermissions};
+
+use super::Manager;
+
+impl Manager {
+    /// Check run by the unit loader on a unit file and its drop-ins (off
+    /// the async threads), failing when one is insecure and the
+    /// configuration refuses such units
+    pub(super) fn unit_file_check(&self) -> Option<FileCheck> {
+        let policy = self.config.unit_file_permissions;
+        if policy == UnitFilePermissions::Ignore {
+            return None;
+        }
+        let owner = nix::unistd::geteuid().as_raw();
+        Some(Arc::new(move |file: &Path| {
+            let Some(problem) = insecure_file(file, owner) else {
+                return Ok(());
+            };
+            let message = format!("{} {}", file.display(), problem);
+            if policy == UnitFilePermissions::Refuse {
+                return Err(message);
+            }
+            log::warn!("{}", message);
+            Ok(())
+        }))
+    }
+}
+
+/// Why the file at `path` could have been written by someone other than root
+/// or `owner`, if it could
+fn insecure_file(path: &Path, owner: u32) -> Option<String> {
+    // Follows symlinks: what counts is the file that is read
+    let metadata = std::fs::metadata(path).ok()?;
+    if metadata.uid() != 0 && metadata.uid() != owner {
+        return Some(format!("is owned by uid {}", metadata.uid()));
+    }
+    if metadata.mode() & 0o002 != 0 {
+        return Some("is world-writable".to_string());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn world_writable_and_foreign_files_are_insecure() {
+        let dir = std::env::temp_dir().join(format!("sysd-unit-perms-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("demo.service");
+        std::fs::write(&file, "[Service]\nExecStart=/bin/true\n").unwrap();
+        let owner = std::fs::metadata(&file).unwrap().uid();
+
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(insecure_file(&file, owner), None);
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o666)).unwrap();
+        assert_eq!(
+            insecure_file(&file, owner).as_deref(),
+            Some("is world-writable")
+        );
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o644)).unwrap();
+        if owner != 0 {
+            assert_eq!(
+                insecure_file(&file, owner + 1),
+                Some(format!("is owned by uid {}", owner))
+            );
+        }
+        assert_eq!(insecure_file(&dir.join("missing.service"), owner), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
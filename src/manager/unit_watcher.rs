@@ -0,0 +1,305 @@
+//! Unit directory watching
+//!
+//! Watches the unit search paths (and the `.d`/`.wants`/`.requires`
+//! directories inside them) with inotify. Loaded units whose file or drop-ins
+//! change are flagged as needing a daemon-reload, like systemd's
+//! NeedDaemonReload= property, or reloaded right away with auto-reload enabled.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use futures_lite::StreamExt;
+use inotify::{Inotify, WatchDescriptor, WatchMask, Watches};
+use tokio::sync::mpsc;
+
+use crate::units;
+
+use super::{Manager, ManagerError};
+
+/// Unit names whose file, drop-ins or dependency directories changed on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnitFilesChanged {
+    pub units: Vec<String>,
+}
+
+const UNIT_SUFFIXES: [&str; 7] = [
+    ".service", ".socket", ".target", ".mount", ".timer", ".path", ".slice",
+];
+
+/// Let editors finish their write/rename dance before reporting
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+fn unit_dir_mask() -> WatchMask {
+    WatchMask::CREATE
+        | WatchMask::DELETE
+        | WatchMask::CLOSE_WRITE
+        | WatchMask::MOVED_FROM
+        | WatchMask::MOVED_TO
+        | WatchMask::ATTRIB
+}
+
+/// Unit a directory entry belongs to: "foo.service", "foo.service.d", "multi-user.target.wants"
+fn unit_for_entry(entry: &str) -> Option<&str> {
+    let unit = [".d", ".wants", ".requires"]
+        .iter()
+        .find_map(|suffix| entry.strip_suffix(suffix))
+        .unwrap_or(entry);
+    let is_unit = !unit.starts_with('.')
+        && UNIT_SUFFIXES
+            .iter()
+            .any(|suffix| unit.len() > suffix.len() && unit.ends_with(suffix));
+    is_unit.then_some(unit)
+}
+
+#[derive(Debug)]
+enum WatchedDir {
+    /// A unit search path
+    Base(PathBuf),
+    /// A drop-in or dependency directory of this unit
+    UnitDir { unit: String, dropin: bool },
+}
+
+struct UnitDirWatcher {
+    watches: Watches,
+    dirs: HashMap<WatchDescriptor, WatchedDir>,
+}
+
+impl UnitDirWatcher {
+    fn add_base(&mut self, dir: &Path) {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            log::debug!("Unit directory {} not present, not watching", dir.display());
+            return;
+        };
+        if !self.add(dir, WatchedDir::Base(dir.to_path_buf())) {
+            return;
+        }
+        for entry in entries.flatten() {
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
+                if let Some(name) = entry.file_name().to_str() {
+                    self.add_unit_dir(dir, name);
+                }
+            }
+        }
+    }
+
+    fn add_unit_dir(&mut self, base: &Path, entry: &str) {
+        let Some(unit) = unit_for_entry(entry).filter(|unit| *unit != entry) else {
+            return;
+        };
+        let watched = WatchedDir::UnitDir {
+            unit: unit.to_string(),
+            dropin: entry.ends_with(".d"),
+        };
+        self.add(&base.join(entry), watched);
+    }
+
+    fn add(&mut self, dir: &Path, watched: WatchedDir) -> bool {
+        match self.watches.add(dir, unit_dir_mask()) {
+            Ok(wd) => {
+                self.dirs.insert(wd, watched);
+                true
+            }
+            Err(e) => {
+                log::warn!("Failed to watch {}: {}", dir.display(), e);
+                false
+            }
+        }
+    }
+
+    /// Unit affected by an event, registering newly created unit directories
+    fn handle_event(&mut self, event: &inotify::Event<std::ffi::OsString>) -> Option<String> {
+        let name = event.name.as_ref().and_then(|n| n.to_str());
+        match self.dirs.get(&event.wd)? {
+            WatchedDir::Base(base) => {
+                let name = name?;
+                let created = event.mask.contains(inotify::EventMask::ISDIR)
+                    && event
+                        .mask
+                        .intersects(inotify::EventMask::CREATE | inotify::EventMask::MOVED_TO);
+                if created {
+                    let base = base.clone();
+                    self.add_unit_dir(&base, name);
+                }
+                unit_for_entry(name).map(String::from)
+            }
+            WatchedDir::UnitDir { unit, dropin } => {
+                // Only *.conf files count as drop-ins
+                if *dropin && !name.is_some_and(|n| n.ends_with(".conf")) {
+                    return None;
+                }
+                Some(unit.clone())
+            }
+        }
+    }
+}
+
+/// Watch unit directories and report changed units until the receiver is dropped
+pub async fn watch_unit_dirs(dirs: Vec<PathBuf>, tx: mpsc::Sender<UnitFilesChanged>) {
+    let inotify = match Inotify::init() {
+        Ok(inotify) => inotify,
+        Err(e) => {
+            log::error!("Failed to initialize inotify for unit directories: {}", e);
+            return;
+        }
+    };
+    let mut watcher = UnitDirWatcher {
+        watches: inotify.watches(),
+        dirs: HashMap::new(),
+    };
+    for dir in &dirs {
+        watcher.add_base(dir);
+    }
+
+    let mut buffer = [0; 4096];
+    let mut stream = match inotify.into_event_stream(&mut buffer) {
+        Ok(stream) => stream,
+        Err(e) => {
+            log::error!("Failed to create unit directory event stream: {}", e);
+            return;
+        }
+    };
+
+    loop {
+        let mut changed = HashSet::new();
+        let Some(Ok(event)) = stream.next().await else {
+            break;
+        };
+        changed.extend(watcher.handle_event(&event));
+        while let Ok(Some(Ok(event))) = tokio::time::timeout(SETTLE_TIME, stream.next()).await {
+            changed.extend(watcher.handle_event(&event));
+        }
+        if changed.is_empty() {
+            continue;
+        }
+
+        let mut units: Vec<String> = changed.into_iter().collect();
+        units.sort();
+        log::debug!("Unit files changed on disk: {}", units.join(", "));
+        if tx.send(UnitFilesChanged { units }).await.is_err() {
+            break;
+        }
+    }
+}
+
+impl Manager {
+    /// Start watching the unit search paths for changes (see `handle_unit_files_changed`)
+    pub fn start_unit_watcher(&self) {
+        tokio::spawn(watch_unit_dirs(
+            self.unit_paths.clone(),
+            self.unit_files_tx.clone(),
+        ));
+    }
+
+    /// Take the unit file change receiver (for use in event loops)
+    pub fn take_unit_files_rx(&mut self) -> Option<mpsc::Receiver<UnitFilesChanged>> {
+        self.unit_files_rx.take()
+    }
+
+    /// Reload units automatically when their files change instead of flagging them
+    pub fn set_auto_reload_units(&mut self, enabled: bool) {
+        self.auto_reload_units = enabled;
+    }
+
+    /// Whether the unit's files changed on disk since it was loaded
+    pub fn needs_daemon_reload(&self, name: &str) -> bool {
+        self.need_daemon_reload.contains(name)
+    }
+
+    /// Flag loaded units affected by a change (templates cover their instances)
+    pub async fn handle_unit_files_changed(
+        &mut self,
+        changed: UnitFilesChanged,
+    ) -> Result<(), ManagerError> {
+        let changed: HashSet<String> = changed.units.into_iter().collect();
+        let affected: Vec<String> = self
+            .units
+            .keys()
+            .filter(|name| {
+                changed.contains(*name)
+                    || units::get_template_name(name).is_some_and(|t| changed.contains(&t))
+            })
+            .cloned()
+            .collect();
+        if affected.is_empty() {
+            return Ok(());
+        }
+
+        if self.auto_reload_units {
+            log::info!("Unit files changed, reloading: {}", affected.join(", "));
+            self.reload_units().await?;
+            return Ok(());
+        }
+        for name in affected {
+            log::info!("{} changed on disk, daemon-reload needed", name);
+            self.need_daemon_reload.insert(name);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{Service, Unit};
+
+    #[test]
+    fn entries_map_to_their_unit() {
+        assert_eq!(unit_for_entry("demo.service"), Some("demo.service"));
+        assert_eq!(unit_for_entry("demo.service.d"), Some("demo.service"));
+        assert_eq!(
+            unit_for_entry("multi-user.target.wants"),
+            Some("multi-user.target")
+        );
+        assert_eq!(unit_for_entry(".demo.service.swp"), None);
+        assert_eq!(unit_for_entry("notes.txt"), None);
+        assert_eq!(unit_for_entry(".service"), None);
+    }
+
+    #[tokio::test]
+    async fn changed_units_and_template_instances_need_reload() {
+        let mut manager = Manager::new_user();
+        for name in ["demo.service", "getty@tty1.service", "other.service"] {
+            manager.units.insert(
+                name.to_string(),
+                Unit::Service(Service::new(name.to_string())),
+            );
+        }
+
+        manager
+            .handle_unit_files_changed(UnitFilesChanged {
+                units: vec!["demo.service".into(), "getty@.service".into()],
+            })
+            .await
+            .unwrap();
+
+        assert!(manager.needs_daemon_reload("demo.service"));
+        assert!(manager.needs_daemon_reload("getty@tty1.service"));
+        assert!(!manager.needs_daemon_reload("other.service"));
+    }
+
+    #[tokio::test]
+    async fn watcher_reports_new_dropins() {
+        let dir = std::env::temp_dir().join(format!(
+            "sysd-unit-watcher-{}-{}",
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        std::fs::create_dir_all(dir.join("demo.service.d")).unwrap();
+        let (tx, mut rx) = mpsc::channel(4);
+        tokio::spawn(watch_unit_dirs(vec![dir.clone()], tx));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        std::fs::write(dir.join("demo.service.d/override.conf"), "[Service]\n").unwrap();
+
+        let changed = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(changed.units, ["demo.service"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,272 @@
+//! Why a unit is in the state it is in (`sysd why UNIT`)
+//!
+//! Puts what the manager knows about a unit into sentences: its state and
+//! since when, which units pull it in (or would, once they start), which
+//! socket, timer or path activates it, and what kept its last start from
+//! running or ended its last run: a masked or broken unit file, an unmet
+//! condition, a failed dependency, the exit status, the start limit. The
+//! state history adds how often it went down lately.
+
+use std::time::{Instant, SystemTime};
+
+use crate::executor::exit_code_text;
+
+use super::dump::span;
+use super::{ActiveState, DependencyType, Manager, ManagerError, SubState};
+
+/// Dependencies that pull a unit in when the dependent starts
+const PULLING: [DependencyType; 4] = [
+    DependencyType::Requires,
+    DependencyType::Requisite,
+    DependencyType::Wants,
+    DependencyType::BindsTo,
+];
+
+impl Manager {
+    /// Sentences explaining the state of `name`, most important first
+    pub fn why(&self, name: &str) -> Result<Vec<String>, ManagerError> {
+        let name = self.normalize_name(name);
+        let load_state = self.load_state(&name);
+        if load_state == "not-found" {
+            return Err(ManagerError::NotFound(name));
+        }
+        let now = Instant::now();
+        let state = self.states.get(&name);
+        let mut why = Vec::new();
+        match state {
+            Some(state) => why.push(format!(
+                "{} is {} ({}) since {} ago.",
+                name,
+                state.active.as_str(),
+                state.sub.as_str(),
+                span(now - state.state_change_time)
+            )),
+            None => why.push(format!(
+                "{} is inactive (dead); it was never started.",
+                name
+            )),
+        }
+        match load_state {
+            "masked" => why.push("It is masked (linked to /dev/null), so it cannot start.".into()),
+            "error" => why.push("Its unit file could not be loaded.".into()),
+            _ => {}
+        }
+
+        let pulled_in = self.why_pulled_in(&name, &mut why);
+        let activated = self.why_activated(&name, &mut why);
+        if let Some(state) = state {
+            if let Some(condition) = &state.condition_failure {
+                why.push(format!("Its last start was skipped: {}.", condition));
+            }
+            if let Some(dependency) = &state.failed_dependency {
+                why.push(format!(
+                    "Its last start was skipped because {} failed.",
+                    dependency
+                ));
+            }
+            if let Some(at) = state.restart_at {
+                why.push(format!(
+                    "It is restarted in {} (restart {}).",
+                    span(at.saturating_duration_since(now)),
+                    state.restart_count + 1
+                ));
+            }
+            match (state.active, &state.error, state.exit_code) {
+                (ActiveState::Failed, Some(error), _) if error.starts_with("Start limit hit") => {
+                    why.push(format!(
+                        "It hit its start limit ({}); `sysdctl reset-failed {}` lets it start again.",
+                        error, name
+                    ))
+                }
+                (ActiveState::Failed, Some(error), _) => why.push(format!("It failed: {}.", error)),
+                (ActiveState::Inactive, _, Some(code)) => {
+                    let stopped = state.timestamps.inactive_enter.map_or(String::new(), |at| {
+                        format!(" {} ago", span(now - at))
+                    });
+                    why.push(format!(
+                        "Its main process last exited{} with status {}.",
+                        stopped,
+                        exit_code_text(code)
+                    ))
+                }
+                _ => {}
+            }
+        }
+        self.why_flapping(&name, &mut why);
+        if !pulled_in && !activated && !state.is_some_and(|state| state.is_active()) {
+            match self.unit_file_state(&name) {
+                Some("disabled") => why.push(
+                    "Nothing pulls it in and it is disabled; it only runs when started by hand."
+                        .into(),
+                ),
+                Some("static") => why.push(
+                    "Nothing pulls it in and it cannot be enabled; another unit has to want it."
+                        .into(),
+                ),
+                _ => {}
+            }
+        }
+        if self.need_daemon_reload.contains(&name) {
+            why.push("Its unit file changed on disk since it was loaded.".into());
+        }
+        Ok(why)
+    }
+
+    /// Explain which units pull `name` in and conflict with it; false if
+    /// none does
+    fn why_pulled_in(&self, name: &str, why: &mut Vec<String>) -> bool {
+        let mut any = false;
+        for kind in PULLING {
+            for dependent in self.dependents(name, kind) {
+                any = true;
+                let directive = directive(kind);
+                if self.unit_is_up(&dependent) {
+                    why.push(format!("Pulled in by {} ({}).", dependent, directive));
+                } else {
+                    why.push(format!(
+                        "Would be pulled in when {} starts ({}).",
+                        dependent, directive
+                    ));
+                }
+            }
+        }
+        let conflicting = self.dependents(name, DependencyType::Conflicts);
+        let own = self
+            .units
+            .get(name)
+            .map(|unit| unit.unit_section().conflicts.clone())
+            .unwrap_or_default();
+        let mut seen = Vec::new();
+        for other in conflicting.into_iter().chain(own) {
+            let other = self.normalize_name(&other);
+            if self.unit_is_up(&other) && !seen.contains(&other) {
+                why.push(format!("Conflicts with {}, which is active.", other));
+                seen.push(other);
+            }
+        }
+        any
+    }
+
+    /// Explain which sockets, timers and paths activate `name`; false if
+    /// none does
+    fn why_activated(&self, name: &str, why: &mut Vec<String>) -> bool {
+        let mut triggers: Vec<&String> = self
+            .units
+            .iter()
+            .filter(|(_, unit)| {
+                let activates = unit
+                    .as_socket()
+                    .map(|socket| socket.service_name())
+                    .or_else(|| unit.as_timer().map(|timer| timer.service_name()))
+                    .or_else(|| unit.as_path().map(|path| path.activated_unit()));
+                activates.is_some_and(|activates| activates == name)
+            })
+            .map(|(trigger, _)| trigger)
+            .collect();
+        triggers.sort();
+        for trigger in &triggers {
+            let state = match self.states.get(*trigger) {
+                Some(state) => state.active.as_str(),
+                None => "inactive",
+            };
+            why.push(format!("Activated by {} ({}).", trigger, state));
+        }
+        !triggers.is_empty()
+    }
+
+    /// Explain how often `name` failed or was restarted after a crash, if
+    /// its history has that more than once
+    fn why_flapping(&self, name: &str, why: &mut Vec<String>) {
+        let history = self.state_history(name).unwrap_or_default();
+        let downs: Vec<SystemTime> = history
+            .iter()
+            .filter(|t| t.active == ActiveState::Failed || t.sub == SubState::AutoRestart)
+            .map(|t| t.at)
+            .collect();
+        let Some(first) = downs.first().filter(|_| downs.len() > 1) else {
+            return;
+        };
+        let since = SystemTime::now().duration_since(*first).unwrap_or_default();
+        why.push(format!(
+            "It failed or was restarted {} times in the last {}; `sysd analyze history {}` lists its transitions.",
+            downs.len(),
+            span(since),
+            name
+        ));
+    }
+
+    fn unit_is_up(&self, name: &str) -> bool {
+        self.states.get(name).is_some_and(|state| state.is_active())
+    }
+}
+
+fn directive(kind: DependencyType) -> &'static str {
+    match kind {
+        DependencyType::Requires => "Requires=",
+        DependencyType::Requisite => "Requisite=",
+        DependencyType::Wants => "Wants=",
+        DependencyType::BindsTo => "BindsTo=",
+        DependencyType::PartOf => "PartOf=",
+        DependencyType::Conflicts => "Conflicts=",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::ServiceState;
+    use crate::units::{Service, Target, Timer, Unit};
+
+    #[test]
+    fn why_names_what_pulls_a_unit_in_and_how_its_last_run_ended() {
+        let mut manager = Manager::new_user();
+        let mut target = Target::new("app.target".to_string());
+        target.unit.wants = vec!["web.service".to_string()];
+        manager.insert_unit("app.target".into(), Unit::Target(target));
+        let web = Service::new("web.service".to_string());
+        manager.insert_unit("web.service".into(), Unit::Service(web));
+        let backup = Timer::new("backup.timer".to_string());
+        manager.insert_unit("backup.timer".into(), Unit::Timer(backup));
+        let backup = Service::new("backup.service".to_string());
+        manager.insert_unit("backup.service".into(), Unit::Service(backup));
+
+        let mut state = ServiceState::new();
+        state.set_failed("Exit code 203/EXEC".into());
+        manager.states.insert("web.service".into(), state);
+        let why = manager.why("web").unwrap();
+        assert!(why[0].starts_with("web.service is failed (failed) since "));
+        assert!(why.contains(&"Would be pulled in when app.target starts (Wants=).".into()));
+        assert!(why.contains(&"It failed: Exit code 203/EXEC.".into()));
+        assert!(!why
+            .iter()
+            .any(|line| line.starts_with("It failed or was restarted")));
+        let state = manager.states.get_mut("web.service").unwrap();
+        state.set_auto_restart(std::time::Duration::from_secs(60));
+        state.set_failed("Exit code 1".into());
+        let why = manager.why("web").unwrap();
+        assert!(why
+            .iter()
+            .any(|line| line.starts_with("It failed or was restarted 3 times in the last ")));
+
+        let mut state = ServiceState::new();
+        state.set_running(0);
+        manager.states.insert("app.target".into(), state);
+        let mut state = ServiceState::new();
+        state.set_stopped(3);
+        manager.states.insert("backup.service".into(), state);
+        assert!(manager
+            .why("web.service")
+            .unwrap()
+            .contains(&"Pulled in by app.target (Wants=).".into()));
+        let why = manager.why("backup.service").unwrap();
+        assert!(why.contains(&"Activated by backup.timer (inactive).".into()));
+        assert!(why
+            .iter()
+            .any(|line| line.starts_with("Its main process last exited") && line.ends_with(" 3.")));
+
+        assert!(matches!(
+            manager.why("missing.service"),
+            Err(ManagerError::NotFound(_))
+        ));
+    }
+}
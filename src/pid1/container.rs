@@ -0,0 +1,49 @@
+//! Running as the init of a container
+//!
+//! Container runtimes announce themselves through the `container=` variable
+//! in PID 1's environment (systemd's container interface) or through marker
+//! files (/.dockerenv, /run/.containerenv). Inside a container the runtime
+//! owns /proc, /sys, /dev and the hardware, so sysd only manages services.
+
+use std::path::Path;
+
+/// Container manager running us, if any
+pub fn detect_container() -> Option<String> {
+    detect_container_in(std::env::var("container").ok(), Path::new("/"))
+}
+
+fn detect_container_in(env: Option<String>, root: &Path) -> Option<String> {
+    if let Some(name) = env.filter(|name| !name.is_empty()) {
+        return Some(name);
+    }
+    if root.join(".dockerenv").exists() {
+        return Some("docker".to_string());
+    }
+    if root.join("run/.containerenv").exists() {
+        return Some("podman".to_string());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn container_is_detected_from_environment_then_marker_files() {
+        let root = std::env::temp_dir().join(format!("sysd-container-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("run")).unwrap();
+
+        assert_eq!(detect_container_in(None, &root), None);
+        assert_eq!(detect_container_in(Some(String::new()), &root), None);
+        std::fs::write(root.join("run/.containerenv"), "").unwrap();
+        assert_eq!(detect_container_in(None, &root).as_deref(), Some("podman"));
+        std::fs::write(root.join(".dockerenv"), "").unwrap();
+        assert_eq!(detect_container_in(None, &root).as_deref(), Some("docker"));
+        assert_eq!(
+            detect_container_in(Some("lxc".to_string()), &root).as_deref(),
+            Some("lxc")
+        );
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
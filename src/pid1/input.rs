@@ -0,0 +1,147 @@
+//! Power button and lid switch events
+//!
+//! Watches the evdev devices (/dev/input/event*) that report KEY_POWER or
+//! SW_LID and forwards presses and lid closes to a channel, like the signal
+//! forwarder. Each device is read on its own blocking thread.
+
+use std::io::Read;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use tokio::sync::mpsc;
+
+const INPUT_DIR: &str = "/dev/input";
+
+const EV_KEY: u16 = 0x01;
+const EV_SW: u16 = 0x05;
+const KEY_POWER: u16 = 116;
+const SW_LID: u16 = 0x00;
+
+/// Input events sysd acts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    /// Power button pressed
+    PowerKey,
+    /// Lid closed
+    LidClosed,
+}
+
+/// Map a raw evdev event to an InputEvent
+fn decode_event(event_type: u16, code: u16, value: i32) -> Option<InputEvent> {
+    match (event_type, code, value) {
+        (EV_KEY, KEY_POWER, 1) => Some(InputEvent::PowerKey),
+        (EV_SW, SW_LID, 1) => Some(InputEvent::LidClosed),
+        _ => None,
+    }
+}
+
+/// EVIOCGBIT(ev, len): read the event codes a device supports
+fn eviocgbit(event_type: u16, len: usize) -> u64 {
+    const IOC_READ: u64 = 2;
+    (IOC_READ << 30) | ((len as u64) << 16) | ((b'E' as u64) << 8) | (0x20 + event_type as u64)
+}
+
+fn has_code(file: &std::fs::File, event_type: u16, code: u16) -> bool {
+    let mut bits = [0u8; 96];
+    let ret = unsafe {
+        libc::ioctl(
+            file.as_raw_fd(),
+            eviocgbit(event_type, bits.len()) as _,
+            bits.as_mut_ptr(),
+        )
+    };
+    let byte = usize::from(code / 8);
+    ret > 0 && byte < bits.len() && bits[byte] & (1 << (code % 8)) != 0
+}
+
+/// Event devices reporting the power key or the lid switch
+fn input_devices(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut devices: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with("event"))
+        })
+        .filter(|path| {
+            std::fs::File::open(path).is_ok_and(|file| {
+                has_code(&file, EV_KEY, KEY_POWER) || has_code(&file, EV_SW, SW_LID)
+            })
+        })
+        .collect();
+    devices.sort();
+    devices
+}
+
+/// Read events from `device` until it goes away
+fn read_device(device: &Path, tx: &mpsc::Sender<InputEvent>) -> std::io::Result<()> {
+    let mut file = std::fs::File::open(device)?;
+    // struct input_event: struct timeval, then u16 type, u16 code, i32 value
+    let mut buf = vec![0u8; std::mem::size_of::<libc::input_event>()];
+    loop {
+        file.read_exact(&mut buf)?;
+        let tail = &buf[buf.len() - 8..];
+        let event_type = u16::from_ne_bytes([tail[0], tail[1]]);
+        let code = u16::from_ne_bytes([tail[2], tail[3]]);
+        let value = i32::from_ne_bytes([tail[4], tail[5], tail[6], tail[7]]);
+        if let Some(event) = decode_event(event_type, code, value) {
+            if tx.blocking_send(event).is_err() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Watch power key and lid switch devices, forwarding events to a channel.
+/// Returns None if no such device exists.
+pub fn spawn_input_watcher() -> Option<mpsc::Receiver<InputEvent>> {
+    let devices = input_devices(Path::new(INPUT_DIR));
+    if devices.is_empty() {
+        log::info!("No power key or lid switch input devices found");
+        return None;
+    }
+
+    let (tx, rx) = mpsc::channel(8);
+    for device in devices {
+        log::info!("Watching {} for power key and lid switch", device.display());
+        let tx = tx.clone();
+        std::thread::spawn(move || {
+            if let Err(e) = read_device(&device, &tx) {
+                log::warn!("Stopped reading {}: {}", device.display(), e);
+            }
+        });
+    }
+    Some(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_power_presses_and_lid_closes_are_decoded() {
+        assert_eq!(
+            decode_event(EV_KEY, KEY_POWER, 1),
+            Some(InputEvent::PowerKey)
+        );
+        assert_eq!(decode_event(EV_KEY, KEY_POWER, 0), None);
+        assert_eq!(decode_event(EV_KEY, KEY_POWER, 2), None);
+        assert_eq!(decode_event(EV_SW, SW_LID, 1), Some(InputEvent::LidClosed));
+        assert_eq!(decode_event(EV_SW, SW_LID, 0), None);
+        assert_eq!(decode_event(EV_KEY, 30, 1), None);
+    }
+
+    #[test]
+    fn eviocgbit_matches_kernel_encoding() {
+        // EVIOCGBIT(EV_KEY, 96) from linux/input.h
+        assert_eq!(eviocgbit(EV_KEY, 96), 0x8060_4521);
+    }
+
+    #[test]
+    fn missing_input_directory_has_no_devices() {
+        assert!(input_devices(Path::new("/nonexistent/input")).is_empty());
+    }
+}
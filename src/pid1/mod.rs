@@ -4,17 +4,35 @@
 //! - Mounting essential filesystems
 //! - Zombie process reaping
 //! - Signal handling
+//! - Power key and lid switch events
+//! - VT switches (getty autospawn)
 //! - Orderly shutdown
+//! - Soft-reboot (re-exec without a kernel reboot)
+//! - Runlevel records in utmp (SysV compatibility)
+//! - Wall messages to logged-in users
+//! - Container payload mode (no mounts, exit instead of reboot)
 
+mod container;
+mod input;
 mod mount;
 mod reaper;
+mod runlevel;
 mod shutdown;
 mod signals;
+mod soft_reboot;
+mod vt;
+mod wall;
 
+pub use container::detect_container;
+pub use input::{spawn_input_watcher, InputEvent};
 pub use mount::{mount_essential_filesystems, MountError};
 pub use reaper::ZombieReaper;
-pub use shutdown::{shutdown, ShutdownType};
+pub use runlevel::{current_runlevel, read_runlevel, runlevel_target, write_runlevel_record};
+pub use shutdown::{exit_container, shutdown, ShutdownType};
 pub use signals::{SignalHandler, SysdSignal};
+pub use soft_reboot::{soft_reboot, NEXTROOT};
+pub use vt::spawn_vt_watcher;
+pub use wall::wall;
 
 use std::process;
 
@@ -46,6 +64,20 @@ pub fn init() -> Result<(), Pid1Error> {
     Ok(())
 }
 
+/// Initialize PID 1 inside a container
+///
+/// The container runtime has already set up /proc, /sys and /dev, and the
+/// kernel settings belong to the host, so nothing is mounted or configured.
+pub fn init_container() {
+    if !is_pid1() {
+        return;
+    }
+    match detect_container() {
+        Some(name) => log::info!("Running as PID 1 in a {} container", name),
+        None => log::info!("Running as PID 1 in container mode"),
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Pid1Error {
     #[error("Mount failed: {0}")]
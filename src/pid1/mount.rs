@@ -220,7 +220,7 @@ fn verify_mount(target: &str) -> bool {
 }
 
 /// Check if a path is a mount point
-fn is_mountpoint(path: &Path) -> bool {
+pub(super) fn is_mountpoint(path: &Path) -> bool {
     // Check /proc/mounts if available
     if let Ok(mounts) = fs::read_to_string("/proc/mounts") {
         let path_str = path.to_string_lossy();
@@ -0,0 +1,128 @@
+//! SysV runlevel emulation
+//!
+//! Targets stand in for runlevels the way systemd maps them: 1 (or S) is
+//! rescue.target, 2 to 4 multi-user.target, 5 graphical.target, 0 and 6 power
+//! off and reboot. When the runlevel changes, a RUN_LVL record goes to utmp,
+//! which is where `runlevel` and `who -r` look for it.
+
+use std::io;
+
+/// Targets that define a runlevel, most specific first
+const RUNLEVEL_TARGETS: [(char, &str); 3] = [
+    ('5', "graphical.target"),
+    ('3', "multi-user.target"),
+    ('1', "rescue.target"),
+];
+
+/// The target a telinit-style runlevel argument switches to
+pub fn runlevel_target(runlevel: char) -> Option<&'static str> {
+    match runlevel {
+        '0' => Some("poweroff.target"),
+        '1' | 's' | 'S' => Some("rescue.target"),
+        '2' | '3' | '4' => Some("multi-user.target"),
+        '5' => Some("graphical.target"),
+        '6' => Some("reboot.target"),
+        _ => None,
+    }
+}
+
+/// The runlevel the system is in, given which targets are active
+pub fn current_runlevel(is_active: impl Fn(&str) -> bool) -> Option<char> {
+    RUNLEVEL_TARGETS
+        .iter()
+        .find(|(_, target)| is_active(target))
+        .map(|(runlevel, _)| *runlevel)
+}
+
+/// The `ut_pid` of a RUN_LVL record: the runlevel in the low byte, the
+/// previous one (or 0) in the next
+fn encode_runlevel(previous: Option<char>, current: char) -> libc::pid_t {
+    let previous = previous.map_or(0, |level| level as u8);
+    libc::pid_t::from(current as u8) | libc::pid_t::from(previous) << 8
+}
+
+fn decode_runlevel(pid: libc::pid_t) -> Option<(Option<char>, char)> {
+    let current = (pid & 0xff) as u8;
+    let previous = ((pid >> 8) & 0xff) as u8;
+    if current == 0 {
+        return None;
+    }
+    Some(((previous != 0).then_some(previous as char), current as char))
+}
+
+/// Record a runlevel change in utmp
+pub fn write_runlevel_record(previous: Option<char>, current: char) -> io::Result<()> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let mut record: libc::utmpx = unsafe { std::mem::zeroed() };
+    record.ut_type = libc::RUN_LVL;
+    record.ut_pid = encode_runlevel(previous, current);
+    copy_field(&mut record.ut_user, "runlevel");
+    copy_field(&mut record.ut_id, "~~");
+    copy_field(&mut record.ut_line, "~");
+    record.ut_tv.tv_sec = now.as_secs() as _;
+    record.ut_tv.tv_usec = now.subsec_micros() as _;
+    let written = unsafe {
+        libc::setutxent();
+        let written = libc::pututxline(&record);
+        libc::endutxent();
+        written
+    };
+    if written.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The previous and current runlevel of the last RUN_LVL record in utmp
+pub fn read_runlevel() -> Option<(Option<char>, char)> {
+    let mut last = None;
+    unsafe {
+        libc::setutxent();
+        loop {
+            let entry = libc::getutxent();
+            if entry.is_null() {
+                break;
+            }
+            if (*entry).ut_type == libc::RUN_LVL {
+                last = Some((*entry).ut_pid);
+            }
+        }
+        libc::endutxent();
+    }
+    decode_runlevel(last?)
+}
+
+fn copy_field(field: &mut [libc::c_char], value: &str) {
+    for (dest, byte) in field.iter_mut().zip(value.bytes()) {
+        *dest = byte as libc::c_char;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runlevels_map_to_targets() {
+        assert_eq!(runlevel_target('3'), Some("multi-user.target"));
+        assert_eq!(runlevel_target('S'), Some("rescue.target"));
+        assert_eq!(runlevel_target('6'), Some("reboot.target"));
+        assert_eq!(runlevel_target('7'), None);
+
+        let active = ["multi-user.target", "graphical.target"];
+        assert_eq!(current_runlevel(|t| active.contains(&t)), Some('5'));
+        assert_eq!(current_runlevel(|t| t == "multi-user.target"), Some('3'));
+        assert_eq!(current_runlevel(|t| t == "basic.target"), None);
+    }
+
+    #[test]
+    fn run_lvl_records_pack_both_runlevels_like_sysvinit() {
+        assert_eq!(encode_runlevel(None, '5'), 0x35);
+        assert_eq!(encode_runlevel(Some('3'), '5'), 0x3335);
+        assert_eq!(decode_runlevel(0x3335), Some((Some('3'), '5')));
+        assert_eq!(decode_runlevel(0x35), Some((None, '5')));
+        assert_eq!(decode_runlevel(0), None);
+    }
+}
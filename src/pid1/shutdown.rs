@@ -29,6 +29,24 @@ pub enum ShutdownType {
 }
 
 impl ShutdownType {
+    /// Name as in logind's ScheduleShutdown ("poweroff", "reboot", "halt")
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ShutdownType::Poweroff => "poweroff",
+            ShutdownType::Reboot => "reboot",
+            ShutdownType::Halt => "halt",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "poweroff" => Some(ShutdownType::Poweroff),
+            "reboot" => Some(ShutdownType::Reboot),
+            "halt" => Some(ShutdownType::Halt),
+            _ => None,
+        }
+    }
+
     fn to_reboot_mode(self) -> RebootMode {
         match self {
             ShutdownType::Poweroff => RebootMode::RB_POWER_OFF,
@@ -36,6 +54,13 @@ impl ShutdownType {
             ShutdownType::Halt => RebootMode::RB_HALT_SYSTEM,
         }
     }
+
+    fn container_exit_code(self) -> i32 {
+        match self {
+            ShutdownType::Poweroff | ShutdownType::Halt => 0,
+            ShutdownType::Reboot => 133,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -57,6 +82,25 @@ mod tests {
             RebootMode::RB_HALT_SYSTEM
         );
     }
+
+    #[test]
+    fn shutdown_types_round_trip_through_their_names() {
+        for kind in [
+            ShutdownType::Poweroff,
+            ShutdownType::Reboot,
+            ShutdownType::Halt,
+        ] {
+            assert_eq!(ShutdownType::from_name(kind.as_str()), Some(kind));
+        }
+        assert_eq!(ShutdownType::from_name("suspend"), None);
+    }
+
+    #[test]
+    fn only_container_reboot_exits_with_nonzero_status() {
+        assert_eq!(ShutdownType::Poweroff.container_exit_code(), 0);
+        assert_eq!(ShutdownType::Halt.container_exit_code(), 0);
+        assert_eq!(ShutdownType::Reboot.container_exit_code(), 133);
+    }
 }
 
 /// Execute shutdown sequence
@@ -87,6 +131,17 @@ pub async fn shutdown(shutdown_type: ShutdownType) -> ! {
     }
 }
 
+/// Shut down a container: stop the remaining processes and exit, leaving
+/// unmounting and the reboot/poweroff decision to the container runtime.
+/// Like systemd-nspawn, a reboot request exits with status 133.
+pub async fn exit_container(shutdown_type: ShutdownType) -> ! {
+    log::info!("Initiating container {:?} sequence", shutdown_type);
+    terminate_all_processes().await;
+    sync();
+    log::info!("Exiting container");
+    std::process::exit(shutdown_type.container_exit_code())
+}
+
 /// Send SIGTERM then SIGKILL to all processes
 async fn terminate_all_processes() {
     log::info!("Sending SIGTERM to all processes");
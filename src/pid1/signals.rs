@@ -4,6 +4,7 @@
 //! - SIGTERM/SIGINT: Initiate shutdown
 //! - SIGCHLD: Reap zombie processes
 //! - SIGUSR1/SIGUSR2: Custom actions (e.g., debug, reload)
+//! - SIGRTMIN+7: Soft-reboot, like systemd
 
 use tokio::signal::unix::{signal, Signal, SignalKind};
 use tokio::sync::mpsc;
@@ -21,6 +22,8 @@ pub enum SysdSignal {
     Hup,
     /// User signal 1 (SIGUSR1) - debug dump
     Usr1,
+    /// SIGRTMIN+7 - soft-reboot
+    SoftReboot,
 }
 
 /// Signal handler for PID 1
@@ -30,6 +33,7 @@ pub struct SignalHandler {
     sigint: Signal,
     sighup: Signal,
     sigusr1: Signal,
+    soft_reboot: Signal,
 }
 
 impl SignalHandler {
@@ -41,6 +45,7 @@ impl SignalHandler {
             sigint: signal(SignalKind::interrupt())?,
             sighup: signal(SignalKind::hangup())?,
             sigusr1: signal(SignalKind::user_defined1())?,
+            soft_reboot: signal(SignalKind::from_raw(libc::SIGRTMIN() + 7))?,
         })
     }
 
@@ -52,6 +57,7 @@ impl SignalHandler {
             _ = self.sigint.recv() => SysdSignal::Int,
             _ = self.sighup.recv() => SysdSignal::Hup,
             _ = self.sigusr1.recv() => SysdSignal::Usr1,
+            _ = self.soft_reboot.recv() => SysdSignal::SoftReboot,
         }
     }
 
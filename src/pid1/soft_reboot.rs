@@ -0,0 +1,118 @@
+//! Userspace-only reboot (`sysd soft-reboot`)
+//!
+//! Once the units are stopped, every process left is killed except those
+//! of units with SurviveFinalKillSignal=yes, and sysd re-executes itself
+//! as PID 1. If a new root file system was mounted at /run/nextroot it
+//! becomes the root first, taking /dev, /proc, /sys and /run along like
+//! switch_root does. The kernel keeps running throughout.
+
+use nix::mount::{mount, MsFlags};
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::{chdir, chroot, sync, Pid};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::sleep;
+
+use super::mount::is_mountpoint;
+use super::shutdown::{shutdown, ShutdownType};
+
+/// Where the root file system to soft-reboot into is prepared
+pub const NEXTROOT: &str = "/run/nextroot";
+
+/// API file systems carried into the new root
+const CARRIED_MOUNTS: [&str; 4] = ["/dev", "/proc", "/sys", "/run"];
+
+/// Kill everything but `spared`, switch to /run/nextroot if it is mounted
+/// and re-execute sysd; reboots for real if the exec fails
+pub async fn soft_reboot(spared: &[u32]) -> ! {
+    log::info!("Initiating soft-reboot");
+    // Before /proc moves away
+    let exe = std::env::current_exe().unwrap_or_else(|_| PathBuf::from("/proc/self/exe"));
+
+    log::info!("Sending SIGTERM to remaining processes");
+    signal_processes(Signal::SIGTERM, spared);
+    sleep(Duration::from_secs(5)).await;
+    log::info!("Sending SIGKILL to remaining processes");
+    signal_processes(Signal::SIGKILL, spared);
+    sleep(Duration::from_millis(100)).await;
+    sync();
+
+    let next_root = Path::new(NEXTROOT);
+    if is_mountpoint(next_root) {
+        match switch_root(next_root) {
+            Ok(()) => log::info!("Switched root to {}", NEXTROOT),
+            Err(e) => log::error!("Cannot switch root to {}: {}, staying", NEXTROOT, e),
+        }
+    }
+
+    log::info!("Re-executing {}", exe.display());
+    let args: Vec<_> = std::env::args_os().skip(1).collect();
+    let e = std::process::Command::new(&exe).args(args).exec();
+    log::error!("Cannot re-execute {}: {}, rebooting", exe.display(), e);
+    shutdown(ShutdownType::Reboot).await
+}
+
+/// Send `signal` to every process but ourselves and `spared`
+fn signal_processes(signal: Signal, spared: &[u32]) {
+    let own = std::process::id();
+    for pid in process_ids(Path::new("/proc")) {
+        if pid != own && !spared.contains(&pid) {
+            let _ = kill(Pid::from_raw(pid as i32), signal);
+        }
+    }
+}
+
+/// PIDs of the processes listed in `proc`
+fn process_ids(proc: &Path) -> Vec<u32> {
+    let Ok(entries) = std::fs::read_dir(proc) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .collect()
+}
+
+/// Make `new_root` the root, moving the API file systems into it. /run is
+/// bound rather than moved since `new_root` lies below it.
+fn switch_root(new_root: &Path) -> nix::Result<()> {
+    for dir in CARRIED_MOUNTS {
+        let target = new_root.join(dir.trim_start_matches('/'));
+        let _ = std::fs::create_dir_all(&target);
+        let flags = if dir == "/run" {
+            MsFlags::MS_BIND | MsFlags::MS_REC
+        } else {
+            MsFlags::MS_MOVE
+        };
+        mount(Some(dir), &target, None::<&str>, flags, None::<&str>)?;
+    }
+    chdir(new_root)?;
+    mount(
+        Some(new_root),
+        "/",
+        None::<&str>,
+        MsFlags::MS_MOVE,
+        None::<&str>,
+    )?;
+    chroot(".")?;
+    chdir("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn process_ids_lists_numeric_entries_only() {
+        let dir = std::env::temp_dir().join(format!("sysd-proc-{}", std::process::id()));
+        for entry in ["1", "42", "self", "sys"] {
+            std::fs::create_dir_all(dir.join(entry)).unwrap();
+        }
+        let mut pids = process_ids(&dir);
+        pids.sort();
+        assert_eq!(pids, [1, 42]);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(process_ids(&dir).is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+//! Virtual terminal switches
+//!
+//! /sys/class/tty/tty0/active names the foreground VT and signals changes
+//! with POLLPRI. Switches to a VT nobody has open are forwarded to a
+//! channel, so sysd can spawn a getty there (logind's autovt).
+
+use std::io::{Read, Seek};
+use std::os::unix::io::AsRawFd;
+
+use tokio::sync::mpsc;
+
+const ACTIVE_VT_PATH: &str = "/sys/class/tty/tty0/active";
+const VT_GETSTATE: u64 = 0x5603;
+
+/// struct vt_stat from linux/vt.h
+#[repr(C)]
+#[derive(Default)]
+struct VtStat {
+    v_active: u16,
+    v_signal: u16,
+    v_state: u16,
+}
+
+/// VT number from the contents of tty0/active ("tty3\n" -> 3)
+fn parse_active_vt(content: &str) -> Option<u32> {
+    content
+        .trim()
+        .strip_prefix("tty")?
+        .parse()
+        .ok()
+        .filter(|&vt| vt >= 1)
+}
+
+/// Whether a process has `vt` open (only VTs 1-15 are reported; higher
+/// ones are treated as unused)
+fn vt_in_use(vt: u32) -> bool {
+    let Ok(console) = std::fs::File::open("/dev/tty0") else {
+        return false;
+    };
+    let mut stat = VtStat::default();
+    if unsafe { libc::ioctl(console.as_raw_fd(), VT_GETSTATE as _, &mut stat) } < 0 {
+        return false;
+    }
+    vt < 16 && stat.v_state & (1 << vt) != 0
+}
+
+/// Forward switches to unused VTs until the channel closes
+fn watch_active_vt(mut file: std::fs::File, tx: &mpsc::Sender<u32>) -> std::io::Result<()> {
+    let mut content = String::new();
+    loop {
+        content.clear();
+        file.rewind()?;
+        file.read_to_string(&mut content)?;
+        if let Some(vt) = parse_active_vt(&content).filter(|&vt| !vt_in_use(vt)) {
+            if tx.blocking_send(vt).is_err() {
+                return Ok(());
+            }
+        }
+
+        let mut poll_fd = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events: libc::POLLPRI | libc::POLLERR,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut poll_fd, 1, -1) } < 0 {
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+    }
+}
+
+/// Watch VT switches, forwarding VTs that need a getty to a channel.
+/// Returns None on systems without virtual terminals.
+pub fn spawn_vt_watcher() -> Option<mpsc::Receiver<u32>> {
+    let file = match std::fs::File::open(ACTIVE_VT_PATH) {
+        Ok(file) => file,
+        Err(e) => {
+            log::info!("No virtual terminals ({}): {}", ACTIVE_VT_PATH, e);
+            return None;
+        }
+    };
+    let (tx, rx) = mpsc::channel(8);
+    std::thread::spawn(move || {
+        if let Err(e) = watch_active_vt(file, &tx) {
+            log::warn!("Stopped watching {}: {}", ACTIVE_VT_PATH, e);
+        }
+    });
+    Some(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn active_vt_is_parsed_from_sysfs_contents() {
+        assert_eq!(parse_active_vt("tty3\n"), Some(3));
+        assert_eq!(parse_active_vt("tty12"), Some(12));
+        assert_eq!(parse_active_vt("tty0\n"), None);
+        assert_eq!(parse_active_vt("ttyS0\n"), None);
+        assert_eq!(parse_active_vt(""), None);
+    }
+}
@@ -0,0 +1,97 @@
+//! Messages to every logged-in user, like wall(1)
+//!
+//! The terminals come from the USER_PROCESS records in utmp. Each is opened
+//! non-blocking, so a stuck terminal cannot hold up PID 1; a terminal that
+//! is not ready for the message just misses it.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+
+/// Write `message` to the terminal of every logged-in user
+pub fn wall(message: &str) {
+    let text = banner(message, &hostname(), chrono::Local::now());
+    for line in user_terminals() {
+        let path = format!("/dev/{}", line);
+        let written = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(&path)
+            .and_then(|mut tty| tty.write_all(text.as_bytes()));
+        if let Err(e) = written {
+            log::debug!("Failed to write wall message to {}: {}", path, e);
+        }
+    }
+}
+
+/// `message` with the header wall(1) puts above it, with CRLF line ends for
+/// terminals in raw mode
+fn banner(message: &str, hostname: &str, now: chrono::DateTime<chrono::Local>) -> String {
+    let mut text = format!(
+        "\r\nBroadcast message from root@{} ({}):\r\n\r\n",
+        hostname,
+        now.format("%a %Y-%m-%d %H:%M:%S %Z")
+    );
+    for line in message.lines() {
+        text.push_str(line);
+        text.push_str("\r\n");
+    }
+    text.push_str("\r\n");
+    text
+}
+
+fn hostname() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_else(|_| "localhost".to_string())
+}
+
+/// ut_line of the USER_PROCESS records in utmp, without duplicates
+fn user_terminals() -> Vec<String> {
+    let mut lines = Vec::new();
+    unsafe {
+        libc::setutxent();
+        loop {
+            let entry = libc::getutxent();
+            if entry.is_null() {
+                break;
+            }
+            if (*entry).ut_type != libc::USER_PROCESS {
+                continue;
+            }
+            let line = field_string(&(*entry).ut_line);
+            // "/dev/../" would escape /dev
+            if !line.is_empty() && !line.contains("..") && !lines.contains(&line) {
+                lines.push(line);
+            }
+        }
+        libc::endutxent();
+    }
+    lines
+}
+
+fn field_string(field: &[libc::c_char]) -> String {
+    let bytes: Vec<u8> = field
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn banner_names_the_sender_and_ends_lines_with_crlf() {
+        let now = chrono::Local
+            .with_ymd_and_hms(2026, 10, 15, 9, 30, 0)
+            .unwrap();
+        let text = banner("going down\nsave your work", "web1", now);
+        let header = "\r\nBroadcast message from root@web1 (Thu 2026-10-15 09:30:00 ";
+        assert!(text.starts_with(header));
+        assert!(text.ends_with("):\r\n\r\ngoing down\r\nsave your work\r\n\r\n"));
+    }
+}
@@ -11,7 +11,7 @@ pub const SOCKET_PATH: &str = "/run/sysd.sock";
 /// Returns /run/user/<uid>/sysd.sock for non-root users
 pub fn user_socket_path() -> String {
     let uid = unsafe { libc::getuid() };
-    format!("/run/user/{}/sysd.sock", uid)
+    rooted(&format!("/run/user/{}/sysd.sock", uid))
 }
 
 /// Get socket path based on mode
@@ -19,26 +19,44 @@ pub fn socket_path(user_mode: bool) -> String {
     if user_mode {
         user_socket_path()
     } else {
-        SOCKET_PATH.to_string()
+        rooted(SOCKET_PATH)
     }
 }
 
+/// The socket below `sysd --root` / SYSD_ROOT, if one is in use
+fn rooted(path: &str) -> String {
+    crate::root::path(path).to_string_lossy().into_owned()
+}
+
 /// Request from CLI to daemon
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Request {
-    /// List all units (optionally filtered by type)
+    /// List all units (optionally filtered by type, and by patterns as for
+    /// ExpandUnits)
     List {
         user: bool,
         unit_type: Option<String>,
+        patterns: Vec<String>,
     },
     /// Start a unit
     Start { name: String },
-    /// Start a unit and wait for it to exit (become inactive/failed)
+    /// Start a unit and answer once the start finished: the unit is active,
+    /// a oneshot exited, or it failed (Response::UnitFailed). A target is
+    /// started with its dependencies and waited for until it is reached.
     StartAndWait { name: String },
     /// Stop a unit
     Stop { name: String },
+    /// Stop a unit and answer once it is inactive or failed
+    StopAndWait { name: String },
     /// Restart a unit
     Restart { name: String },
+    /// Restart a unit and answer as for StartAndWait
+    RestartAndWait { name: String },
+    /// Start a new instance of a running socket-activated service and stop
+    /// the old one once the new one is ready
+    RollingRestart { name: String },
+    /// Roll a service and answer as for StartAndWait
+    RollingRestartAndWait { name: String },
     /// Enable a unit (create symlinks for boot)
     Enable { name: String },
     /// Disable a unit (remove symlinks)
@@ -51,6 +69,8 @@ pub enum Request {
     Deps { name: String },
     /// Get default boot target
     GetBootTarget,
+    /// Point default.target at a target unit
+    SetDefaultTarget { target: String },
     /// Boot to default target
     Boot { dry_run: bool },
     /// Reload unit files from disk
@@ -59,16 +79,88 @@ pub enum Request {
     SyncUnits,
     /// Switch to target (stop unrelated units)
     SwitchTarget { target: String },
+    /// Record the active units as a snapshot (named, or snapshot-N);
+    /// answered with its name
+    Snapshot { name: Option<String> },
+    /// Forget a snapshot
+    DeleteSnapshot { name: String },
     /// Ping (health check)
     Ping,
     /// Import environment variables from the caller
     ImportEnvironment { vars: Vec<(String, String)> },
     /// Unset environment variables
     UnsetEnvironment { names: Vec<String> },
+    /// Set manager environment variables (KEY=VALUE assignments)
+    SetEnvironment { assignments: Vec<String> },
+    /// Show the manager environment block
+    ShowEnvironment,
     /// Reset failed state of all units
     ResetFailed,
     /// Check if unit is active
     IsActive { name: String },
+    /// List listening sockets and the units they activate
+    ListSockets,
+    /// Send a signal to unit processes (whom: main, control or all)
+    Kill {
+        name: String,
+        whom: String,
+        signal: i32,
+    },
+    /// Remove runtime/state/cache/logs/configuration directories of a stopped unit
+    Clean { name: String, what: Vec<String> },
+    /// Suspend, hibernate or hybrid-sleep the machine
+    Sleep { mode: String },
+    /// Power off, reboot or halt ("poweroff", "reboot", "halt") at `usec`
+    /// (wall clock, since the epoch), announcing it to logged-in users with
+    /// `message`
+    ScheduleShutdown {
+        kind: String,
+        usec: u64,
+        message: Option<String>,
+    },
+    /// Disarm the scheduled shutdown, if any
+    CancelShutdown,
+    /// The scheduled shutdown, answered with ScheduledShutdown
+    ShowShutdown,
+    /// Change resource limits (MemoryMax=, CPUQuota=, TasksMax=) of a unit;
+    /// runtime drop-ins are lost on reboot
+    SetProperty {
+        name: String,
+        assignments: Vec<String>,
+        runtime: bool,
+    },
+    /// Where the time went in the last start of a service
+    SpawnProfile { name: String },
+    /// Start several units in the background; answered with a start group
+    /// to poll with StartGroupStatus. Names may be patterns as for
+    /// ExpandUnits
+    StartMany { names: Vec<String> },
+    /// Results of a start group so far
+    StartGroupStatus { id: u32 },
+    /// Optional kernel features the manager found
+    Features,
+    /// Unit names that brace patterns and wildcards stand for
+    /// ("getty@{tty1,tty2}", "getty@*"), answered with UnitNames; with
+    /// `states`, only units in one of those active states ("active", ...)
+    ExpandUnits {
+        patterns: Vec<String>,
+        states: Vec<String>,
+    },
+    /// Reset the failed state of one unit
+    ResetFailedUnit { name: String },
+    /// The manager's internal state as text, for debugging
+    Dump,
+    /// Why a unit is in the state it is in, answered with Why
+    Why { name: String },
+    /// The last state transitions of a unit, answered with StateHistory
+    StateHistory { name: String },
+    /// Firmware, loader and userspace times of the boot, and when each unit
+    /// came up
+    BootTimes,
+    /// Arm faults for a unit ("exit-code=1", "delay-ready=5",
+    /// "drop-watchdog", "fail-cgroup", "clear"); only managers built with
+    /// the fault-injection feature accept them
+    InjectFault { name: String, faults: Vec<String> },
 }
 
 /// Unit info returned by list/status
@@ -78,6 +170,110 @@ pub struct UnitInfo {
     pub unit_type: String,
     pub state: String,
     pub description: Option<String>,
+    /// Unit file or drop-ins changed on disk since the unit was loaded
+    #[serde(default)]
+    pub need_daemon_reload: bool,
+    /// loaded, stub, not-found, masked or error
+    #[serde(default)]
+    pub load_state: String,
+    /// enabled, disabled, static, masked, linked, ... (status only)
+    #[serde(default)]
+    pub unit_file_state: Option<String>,
+    /// Unit file the unit was loaded from
+    #[serde(default)]
+    pub fragment_path: Option<String>,
+    /// Documentation= URIs
+    #[serde(default)]
+    pub documentation: Vec<String>,
+    /// Canonical name followed by the unit's aliases
+    #[serde(default)]
+    pub names: Vec<String>,
+    /// Unmet condition that skipped the last start (not a failure)
+    #[serde(default)]
+    pub condition_failure: Option<String>,
+    /// Dependency that failed, so the last start was skipped
+    #[serde(default)]
+    pub failed_dependency: Option<String>,
+    /// IPIngressBytes: bytes received by a unit with IPAddressAllow=/
+    /// IPAddressDeny= (status only)
+    #[serde(default)]
+    pub ip_ingress_bytes: Option<u64>,
+    /// IPEgressBytes: bytes sent by such a unit (status only)
+    #[serde(default)]
+    pub ip_egress_bytes: Option<u64>,
+    /// Last state transitions, oldest first (status only)
+    #[serde(default)]
+    pub history: Vec<TransitionInfo>,
+}
+
+/// A state a unit entered (`sysd analyze history`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransitionInfo {
+    /// CLOCK_REALTIME, in microseconds since the epoch
+    pub usec: u64,
+    pub active: String,
+    pub sub: String,
+    /// Why, for exits and failures
+    pub reason: Option<String>,
+}
+
+/// Listening socket returned by list-sockets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SocketInfo {
+    pub listen: String,
+    pub socket_type: String,
+    pub unit: String,
+    pub activates: Vec<String>,
+}
+
+/// Timing of the last start of a unit (`sysd analyze spawn`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnProfileInfo {
+    pub name: String,
+    /// Steps in the order they ended, with their duration in microseconds
+    pub steps: Vec<(String, u64)>,
+    pub total_usec: u64,
+    /// Spawned through sysd-executor, whose steps overlap the manager's
+    pub via_executor: bool,
+    /// Every step the child takes was reported
+    pub complete: bool,
+}
+
+/// Phases of the boot (`sysd analyze time`), in microseconds of
+/// CLOCK_MONOTONIC
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootTimesInfo {
+    /// Firmware and loader times, if the boot loader reported them
+    pub firmware_usec: Option<u64>,
+    pub loader_usec: Option<u64>,
+    /// When the manager came up
+    pub userspace_usec: u64,
+    /// When the default target was reached, if it was
+    pub finish_usec: Option<u64>,
+    pub target: Option<String>,
+    pub units: Vec<UnitTimesInfo>,
+}
+
+/// When a unit came up and went down (`sysd analyze plot`), in microseconds
+/// of CLOCK_MONOTONIC; 0 where it never did, like systemd's
+/// *TimestampMonotonic properties
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnitTimesInfo {
+    pub name: String,
+    pub activating: u64,
+    pub activated: u64,
+    /// How long it took to come up, or to fail
+    pub time: u64,
+    pub deactivating: u64,
+    pub deactivated: u64,
+}
+
+/// Progress of a StartMany batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StartGroupInfo {
+    pub total: u32,
+    /// Units whose start finished, in order, with the error of those that failed
+    pub finished: Vec<(String, Option<String>)>,
 }
 
 /// Response from daemon to CLI
@@ -103,6 +299,37 @@ pub enum Response {
     Error(String),
     /// Pong (response to ping)
     Pong,
+    /// Listening sockets
+    Sockets(Vec<SocketInfo>),
+    /// Manager environment as KEY=VALUE lines
+    Environment(Vec<String>),
+    /// Spawn timing of a unit
+    SpawnProfile(SpawnProfileInfo),
+    /// Id of a start group that was set off
+    StartGroup(u32),
+    /// Progress of a start group
+    StartGroupProgress(StartGroupInfo),
+    /// Every optional kernel feature with why it is missing (None: available)
+    Features(Vec<(String, Option<String>)>),
+    /// Expanded unit names
+    UnitNames(Vec<String>),
+    /// A unit waited for failed, with its main process exit code
+    UnitFailed {
+        name: String,
+        reason: String,
+        exit_code: Option<i32>,
+    },
+    /// Text dump of the manager state
+    Dump(String),
+    /// Sentences explaining the state of a unit
+    Why(Vec<String>),
+    /// Last state transitions of a unit, oldest first
+    StateHistory(Vec<TransitionInfo>),
+    /// Boot and unit activation times
+    BootTimes(BootTimesInfo),
+    /// Kind of the scheduled shutdown and when it is due (usec since the
+    /// epoch), if one is
+    ScheduledShutdown(Option<(String, u64)>),
 }
 
 #[cfg(test)]
@@ -115,6 +342,7 @@ mod tests {
             Request::List {
                 user: false,
                 unit_type: None,
+                patterns: vec!["getty@*".into()],
             },
             Request::Start {
                 name: "docker.service".into(),
@@ -122,7 +350,69 @@ mod tests {
             Request::Stop {
                 name: "nginx.service".into(),
             },
+            Request::StopAndWait {
+                name: "nginx.service".into(),
+            },
+            Request::RestartAndWait {
+                name: "job.service".into(),
+            },
+            Request::RollingRestart {
+                name: "web.service".into(),
+            },
+            Request::Snapshot { name: None },
+            Request::ScheduleShutdown {
+                kind: "reboot".into(),
+                usec: 1_700_000_000_000_000,
+                message: Some("kernel update".into()),
+            },
             Request::Ping,
+            Request::SetDefaultTarget {
+                target: "graphical.target".into(),
+            },
+            Request::ListSockets,
+            Request::Kill {
+                name: "nginx.service".into(),
+                whom: "main".into(),
+                signal: 1,
+            },
+            Request::Clean {
+                name: "nginx.service".into(),
+                what: vec!["cache".into(), "state".into()],
+            },
+            Request::SetEnvironment {
+                assignments: vec!["DISPLAY=:0".into()],
+            },
+            Request::ShowEnvironment,
+            Request::Sleep {
+                mode: "suspend".into(),
+            },
+            Request::SpawnProfile {
+                name: "nginx.service".into(),
+            },
+            Request::StartMany {
+                names: vec!["nginx.service".into(), "redis".into()],
+            },
+            Request::StartGroupStatus { id: 7 },
+            Request::Features,
+            Request::ExpandUnits {
+                patterns: vec!["getty@{tty1,tty2}".into(), "vm@*".into()],
+                states: vec!["active".into()],
+            },
+            Request::ResetFailedUnit {
+                name: "nginx.service".into(),
+            },
+            Request::Dump,
+            Request::Why {
+                name: "nginx.service".into(),
+            },
+            Request::StateHistory {
+                name: "nginx.service".into(),
+            },
+            Request::BootTimes,
+            Request::InjectFault {
+                name: "nginx.service".into(),
+                faults: vec!["exit-code=1".into(), "drop-watchdog".into()],
+            },
         ];
 
         for req in requests {
@@ -155,13 +445,79 @@ mod tests {
         let responses = vec![
             Response::Ok,
             Response::Error("test error".into()),
+            Response::UnitFailed {
+                name: "job.service".into(),
+                reason: "exit code 3".into(),
+                exit_code: Some(3),
+            },
             Response::Units(vec![UnitInfo {
                 name: "test.service".into(),
                 unit_type: "service".into(),
                 state: "running".into(),
                 description: Some("Test service".into()),
+                need_daemon_reload: true,
+                load_state: "loaded".into(),
+                unit_file_state: Some("enabled".into()),
+                fragment_path: Some("/etc/systemd/system/test.service".into()),
+                documentation: vec!["man:test(8)".into()],
+                names: vec!["test.service".into(), "alias.service".into()],
+                condition_failure: Some("ConditionPathExists=/etc/test was not met".into()),
+                failed_dependency: Some("network-online.target".into()),
+                ip_ingress_bytes: Some(4096),
+                ip_egress_bytes: Some(512),
+                history: vec![TransitionInfo {
+                    usec: 1_700_000_000_000_000,
+                    active: "failed".into(),
+                    sub: "failed".into(),
+                    reason: Some("Exit code 1".into()),
+                }],
             }]),
             Response::Pong,
+            Response::Sockets(vec![SocketInfo {
+                listen: "/run/demo.sock".into(),
+                socket_type: "Stream".into(),
+                unit: "demo.socket".into(),
+                activates: vec!["demo.service".into()],
+            }]),
+            Response::Environment(vec!["DISPLAY=:0".into()]),
+            Response::SpawnProfile(SpawnProfileInfo {
+                name: "nginx.service".into(),
+                steps: vec![("prepare".into(), 120), ("sandbox".into(), 2400)],
+                total_usec: 2520,
+                via_executor: true,
+                complete: true,
+            }),
+            Response::StartGroup(7),
+            Response::StartGroupProgress(StartGroupInfo {
+                total: 2,
+                finished: vec![
+                    ("nginx.service".into(), None),
+                    ("redis.service".into(), Some("Unit not found".into())),
+                ],
+            }),
+            Response::Features(vec![
+                ("cgroups".into(), None),
+                ("bpf-lsm".into(), Some("the bpf LSM is not enabled".into())),
+            ]),
+            Response::UnitNames(vec!["getty@tty1.service".into()]),
+            Response::Dump("-> Unit nginx.service:\n\tMain PID: 42\n".into()),
+            Response::Why(vec!["Pulled in by multi-user.target (Wants=).".into()]),
+            Response::StateHistory(Vec::new()),
+            Response::BootTimes(BootTimesInfo {
+                firmware_usec: Some(1_500_000),
+                loader_usec: None,
+                userspace_usec: 2_100_000,
+                finish_usec: Some(4_800_000),
+                target: Some("graphical.target".into()),
+                units: vec![UnitTimesInfo {
+                    name: "nginx.service".into(),
+                    activating: 2_300_000,
+                    activated: 2_450_000,
+                    time: 150_000,
+                    deactivating: 0,
+                    deactivated: 0,
+                }],
+            }),
         ];
 
         for resp in responses {
@@ -0,0 +1,169 @@
+//! Alternative root directory (`sysd --root`, SYSD_ROOT=)
+//!
+//! Absolute paths the manager touches on the host (unit directories and
+//! enablement symlinks, drop-ins, /run, cgroups, config files, the IPC and
+//! notify sockets) go through [`path`]. With a root set they all land below
+//! it, so a whole manager can run against a directory tree: integration tests
+//! use a scratch directory, image builders an offline OS image.
+//!
+//! Symlinks inside the root keep their in-image targets (/usr/lib/...), so
+//! they stay valid once the image boots; [`resolve`] follows them below the
+//! root and [`unprefixed`] turns a path back into its in-image form.
+
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Environment variable naming the root when --root is not given
+pub const ROOT_ENV: &str = "SYSD_ROOT";
+
+/// Symlink hops followed before giving up (the kernel's limit)
+const MAX_SYMLINK_HOPS: usize = 40;
+
+static ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+
+/// Use `root` for the rest of the process (takes precedence over SYSD_ROOT)
+///
+/// Returns false if a path was already resolved, since those used the root
+/// from the environment; call this before creating a manager.
+pub fn set(root: &Path) -> bool {
+    ROOT.set(normalize(root.to_path_buf())).is_ok()
+}
+
+/// The root in use, if any
+pub fn get() -> Option<&'static Path> {
+    ROOT.get_or_init(|| std::env::var_os(ROOT_ENV).and_then(|root| normalize(root.into())))
+        .as_deref()
+}
+
+/// `path` below the root (unchanged without a root, for relative paths and
+/// for paths already below it)
+pub fn path(path: impl AsRef<Path>) -> PathBuf {
+    match get() {
+        Some(root) => prefixed(root, path.as_ref()),
+        None => path.as_ref().to_path_buf(),
+    }
+}
+
+/// `path` as seen from inside the root, e.g. for symlink targets written into it
+pub fn unprefixed(path: &Path) -> PathBuf {
+    match get() {
+        Some(root) => stripped(root, path),
+        None => path.to_path_buf(),
+    }
+}
+
+/// Follow a symlink chain, looking absolute targets up below the root
+///
+/// Links to /dev/null (masked units) resolve to /dev/null itself. Without a
+/// root the path is returned as is and the kernel follows links on access.
+pub fn resolve(path: &Path) -> PathBuf {
+    match get() {
+        Some(root) => resolve_below(root, path),
+        None => path.to_path_buf(),
+    }
+}
+
+/// An empty root or / means no root
+fn normalize(root: PathBuf) -> Option<PathBuf> {
+    if root.as_os_str().is_empty() || root == Path::new("/") {
+        return None;
+    }
+    Some(root)
+}
+
+fn prefixed(root: &Path, path: &Path) -> PathBuf {
+    if path.starts_with(root) {
+        return path.to_path_buf();
+    }
+    match path.strip_prefix("/") {
+        Ok(relative) => root.join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+fn stripped(root: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(root) {
+        Ok(relative) => Path::new("/").join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+fn resolve_below(root: &Path, path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for _ in 0..MAX_SYMLINK_HOPS {
+        let Ok(target) = std::fs::read_link(&path) else {
+            return path;
+        };
+        if target == Path::new("/dev/null") {
+            return target;
+        }
+        path = if target.is_absolute() {
+            prefixed(root, &target)
+        } else {
+            path.parent()
+                .map_or_else(|| target.clone(), |parent| parent.join(&target))
+        };
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    #[test]
+    fn paths_move_below_the_root_and_back() {
+        let root = Path::new("/mnt/image");
+
+        assert_eq!(
+            prefixed(root, Path::new("/etc/systemd/system")),
+            Path::new("/mnt/image/etc/systemd/system")
+        );
+        assert_eq!(
+            prefixed(root, Path::new("/mnt/image/run")),
+            Path::new("/mnt/image/run")
+        );
+        assert_eq!(prefixed(root, Path::new("relative")), Path::new("relative"));
+        assert_eq!(
+            stripped(
+                root,
+                Path::new("/mnt/image/usr/lib/systemd/system/a.service")
+            ),
+            Path::new("/usr/lib/systemd/system/a.service")
+        );
+        assert_eq!(
+            stripped(root, Path::new("/dev/null")),
+            Path::new("/dev/null")
+        );
+        assert_eq!(normalize(PathBuf::from("/")), None);
+        assert_eq!(normalize(PathBuf::new()), None);
+    }
+
+    #[test]
+    fn symlink_chains_resolve_inside_the_root() {
+        let root = std::env::temp_dir().join(format!("sysd-root-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let etc = root.join("etc/systemd/system");
+        let lib = root.join("usr/lib/systemd/system");
+        std::fs::create_dir_all(&etc).unwrap();
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::write(lib.join("real.service"), "[Service]\n").unwrap();
+        symlink(
+            "/usr/lib/systemd/system/real.service",
+            etc.join("alias.service"),
+        )
+        .unwrap();
+        symlink("alias.service", etc.join("chained.service")).unwrap();
+        symlink("/dev/null", etc.join("masked.service")).unwrap();
+
+        let resolved = resolve_below(&root, &etc.join("chained.service"));
+        let masked = resolve_below(&root, &etc.join("masked.service"));
+        let plain = resolve_below(&root, &lib.join("real.service"));
+        let _ = std::fs::remove_dir_all(&root);
+
+        assert_eq!(resolved, lib.join("real.service"));
+        assert_eq!(masked, Path::new("/dev/null"));
+        assert_eq!(plain, lib.join("real.service"));
+    }
+}
@@ -1,5 +1,8 @@
 //! Shared low-level sandbox helpers used by manager and executor.
 
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::Path;
+
 /// NoNewPrivileges=yes - prevents privilege escalation via execve().
 pub fn apply_no_new_privileges() -> Result<(), String> {
     unsafe {
@@ -10,12 +13,226 @@ pub fn apply_no_new_privileges() -> Result<(), String> {
     Ok(())
 }
 
-/// PrivateNetwork=yes - create an isolated network namespace.
+/// PrivateNetwork=yes - create an isolated network namespace with only
+/// loopback, which is up like in systemd.
 pub fn apply_private_network() -> Result<(), String> {
     unsafe {
         if libc::unshare(libc::CLONE_NEWNET) != 0 {
             return Err("Failed to create network namespace".to_string());
         }
     }
+    bring_up_loopback()
+}
+
+/// PrivateIPC=yes - System V IPC objects and POSIX message queues of the
+/// service's own
+pub fn apply_private_ipc() -> Result<(), String> {
+    unsafe {
+        if libc::unshare(libc::CLONE_NEWIPC) != 0 {
+            return Err(format!(
+                "Failed to create IPC namespace: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
     Ok(())
 }
+
+/// NetworkNamespacePath= - join the network namespace bound at `path`
+/// (e.g. /run/netns/<name> from `ip netns add`).
+pub fn join_network_namespace(path: &Path) -> Result<(), String> {
+    let namespace = std::fs::File::open(path)
+        .map_err(|e| format!("Failed to open network namespace {}: {}", path.display(), e))?;
+    unsafe {
+        if libc::setns(namespace.as_raw_fd(), libc::CLONE_NEWNET) != 0 {
+            return Err(format!(
+                "Failed to join network namespace {}: {}",
+                path.display(),
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// struct ifinfomsg from <linux/rtnetlink.h>
+#[repr(C)]
+struct IfInfoMsg {
+    ifi_family: u8,
+    ifi_pad: u8,
+    ifi_type: u16,
+    ifi_index: i32,
+    ifi_flags: u32,
+    ifi_change: u32,
+}
+
+#[repr(C)]
+struct LinkRequest {
+    header: libc::nlmsghdr,
+    link: IfInfoMsg,
+}
+
+/// lo is always the first interface of a new network namespace
+const LOOPBACK_INDEX: i32 = 1;
+
+fn bring_up_loopback() -> Result<(), String> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(format!(
+            "Failed to open rtnetlink socket: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+    let result = set_link_up(fd, LOOPBACK_INDEX);
+    unsafe { libc::close(fd) };
+    result.map_err(|e| format!("Failed to bring up loopback: {}", e))
+}
+
+/// RTM_NEWLINK setting IFF_UP on interface `index`, waiting for the kernel's
+/// acknowledgement
+fn set_link_up(fd: RawFd, index: i32) -> Result<(), std::io::Error> {
+    let request = LinkRequest {
+        header: libc::nlmsghdr {
+            nlmsg_len: std::mem::size_of::<LinkRequest>() as u32,
+            nlmsg_type: libc::RTM_NEWLINK,
+            nlmsg_flags: (libc::NLM_F_REQUEST | libc::NLM_F_ACK) as u16,
+            nlmsg_seq: 1,
+            nlmsg_pid: 0,
+        },
+        link: IfInfoMsg {
+            ifi_family: libc::AF_UNSPEC as u8,
+            ifi_pad: 0,
+            ifi_type: 0,
+            ifi_index: index,
+            ifi_flags: libc::IFF_UP as u32,
+            ifi_change: libc::IFF_UP as u32,
+        },
+    };
+    let sent = unsafe {
+        libc::send(
+            fd,
+            (&request as *const LinkRequest).cast(),
+            std::mem::size_of::<LinkRequest>(),
+            0,
+        )
+    };
+    if sent < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    // The acknowledgement is an NLMSG_ERROR whose error field is 0 on success
+    let mut reply = [0u32; 256];
+    let received = unsafe {
+        libc::recv(
+            fd,
+            reply.as_mut_ptr().cast(),
+            std::mem::size_of_val(&reply),
+            0,
+        )
+    };
+    if received < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let header_len = std::mem::size_of::<libc::nlmsghdr>();
+    if (received as usize) < header_len + std::mem::size_of::<i32>() {
+        return Err(std::io::Error::other("short rtnetlink reply"));
+    }
+    let header = unsafe { &*reply.as_ptr().cast::<libc::nlmsghdr>() };
+    if header.nlmsg_type != libc::NLMSG_ERROR as u16 {
+        return Err(std::io::Error::other("unexpected rtnetlink reply"));
+    }
+    let error = reply[header_len / 4] as i32;
+    if error != 0 {
+        return Err(std::io::Error::from_raw_os_error(-error));
+    }
+    Ok(())
+}
+
+/// SecureBits= - set the SECBIT_* flags of the process.
+pub fn apply_secure_bits(bits: u32) -> Result<(), String> {
+    unsafe {
+        if libc::prctl(libc::PR_SET_SECUREBITS, bits as libc::c_ulong, 0, 0, 0) != 0 {
+            return Err(format!(
+                "Failed to set securebits {:#x}: {}",
+                bits,
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+const KEYCTL_JOIN_SESSION_KEYRING: libc::c_long = 1;
+const KEYCTL_LINK: libc::c_long = 8;
+const KEY_SPEC_SESSION_KEYRING: libc::c_long = -3;
+const KEY_SPEC_USER_KEYRING: libc::c_long = -4;
+
+/// KeyringMode=private/shared - join a new anonymous session keyring, for
+/// shared with the user keyring linked into it. Runs after the switch to the
+/// service user so the keyrings belong to that user.
+pub fn apply_session_keyring(link_user_keyring: bool) -> Result<(), String> {
+    unsafe {
+        let null: *const libc::c_char = std::ptr::null();
+        if libc::syscall(libc::SYS_keyctl, KEYCTL_JOIN_SESSION_KEYRING, null) < 0 {
+            return Err(format!(
+                "Failed to create session keyring: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+        if link_user_keyring
+            && libc::syscall(
+                libc::SYS_keyctl,
+                KEYCTL_LINK,
+                KEY_SPEC_USER_KEYRING,
+                KEY_SPEC_SESSION_KEYRING,
+            ) < 0
+        {
+            return Err(format!(
+                "Failed to link user keyring: {}",
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// SELinuxContext= - have the next execve() run in `context`, like
+/// setexeccon(3).
+pub fn set_exec_selinux_context(context: &str) -> Result<(), String> {
+    std::fs::write("/proc/self/attr/exec", context)
+        .map_err(|e| format!("Failed to set SELinux context {}: {}", context, e))
+}
+
+/// AppArmorProfile= - have the next execve() switch to `profile`, like
+/// aa_change_onexec(3). Kernels with LSM stacking keep AppArmor's attribute
+/// in its own directory.
+pub fn set_exec_apparmor_profile(profile: &str) -> Result<(), String> {
+    let command = format!("exec {}", profile);
+    let stacked = Path::new("/proc/self/attr/apparmor/exec");
+    let attr = if stacked.exists() {
+        stacked
+    } else {
+        Path::new("/proc/self/attr/exec")
+    };
+    std::fs::write(attr, command)
+        .map_err(|e| format!("Failed to change to AppArmor profile {}: {}", profile, e))
+}
+
+/// SmackProcessLabel= - move the process to Smack `label`, which the service
+/// keeps across execve(). Needs CAP_MAC_ADMIN, so it runs before the switch
+/// to the service user.
+pub fn set_smack_process_label(label: &str) -> Result<(), String> {
+    let stacked = Path::new("/proc/self/attr/smack/current");
+    let attr = if stacked.exists() {
+        stacked
+    } else {
+        Path::new("/proc/self/attr/current")
+    };
+    std::fs::write(attr, label).map_err(|e| format!("Failed to set Smack label {}: {}", label, e))
+}
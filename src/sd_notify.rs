@@ -0,0 +1,109 @@
+//! sd_notify from sysd itself, for when something supervises sysd
+//!
+//! A container manager, a systemd unit running `sysd --user`, or a test
+//! harness passes NOTIFY_SOCKET (and WATCHDOG_USEC for a watchdog) like to
+//! any Type=notify service. The variables are taken out of the environment so
+//! the units sysd starts do not report to its supervisor.
+
+use std::io;
+use std::os::linux::net::SocketAddrExt;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+
+/// Connection to the supervisor's notification socket
+#[derive(Debug)]
+pub struct Supervisor {
+    socket: UnixDatagram,
+    address: SocketAddr,
+    watchdog: Option<Duration>,
+}
+
+impl Supervisor {
+    /// From NOTIFY_SOCKET, WATCHDOG_USEC and WATCHDOG_PID, which are removed
+    /// from the environment; None without a supervisor to talk to
+    pub fn from_env() -> Option<Self> {
+        let var = |name: &str| {
+            let value = std::env::var(name).ok();
+            std::env::remove_var(name);
+            value
+        };
+        let notify_socket = var("NOTIFY_SOCKET")?;
+        let watchdog_usec = var("WATCHDOG_USEC");
+        let watchdog_pid = var("WATCHDOG_PID");
+        match Self::new(
+            &notify_socket,
+            watchdog_usec.as_deref(),
+            watchdog_pid.as_deref(),
+        ) {
+            Ok(supervisor) => Some(supervisor),
+            Err(e) => {
+                log::warn!("Ignoring NOTIFY_SOCKET={}: {}", notify_socket, e);
+                None
+            }
+        }
+    }
+
+    /// `notify_socket` is a path or, with a leading '@', an abstract name.
+    /// The watchdog applies when WATCHDOG_PID is unset or names this process.
+    fn new(
+        notify_socket: &str,
+        watchdog_usec: Option<&str>,
+        watchdog_pid: Option<&str>,
+    ) -> io::Result<Self> {
+        let address = match notify_socket.strip_prefix('@') {
+            Some(name) => SocketAddr::from_abstract_name(name)?,
+            None => SocketAddr::from_pathname(notify_socket)?,
+        };
+        let ours = watchdog_pid.is_none_or(|pid| pid.parse().ok() == Some(std::process::id()));
+        let watchdog = watchdog_usec
+            .and_then(|usec| usec.parse().ok())
+            .filter(|&usec| usec > 0 && ours)
+            .map(Duration::from_micros);
+        Ok(Self {
+            socket: UnixDatagram::unbound()?,
+            address,
+            watchdog,
+        })
+    }
+
+    /// How often the supervisor wants WATCHDOG=1, if it watches sysd
+    pub fn watchdog_interval(&self) -> Option<Duration> {
+        self.watchdog
+    }
+
+    /// Send newline-separated assignments (READY=1, STATUS=..., WATCHDOG=1)
+    pub fn notify(&self, message: &str) -> io::Result<()> {
+        self.socket
+            .send_to_addr(message.as_bytes(), &self.address)
+            .map(drop)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_reach_the_socket_and_the_watchdog_is_ours_only() {
+        let path = std::env::temp_dir().join(format!("sysd-sd-notify-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixDatagram::bind(&path).unwrap();
+        let socket = path.to_str().unwrap();
+
+        let supervisor = Supervisor::new(socket, Some("4000000"), None).unwrap();
+        assert_eq!(supervisor.watchdog_interval(), Some(Duration::from_secs(4)));
+        supervisor.notify("READY=1\nSTATUS=3 units active").unwrap();
+        let mut buf = [0u8; 64];
+        let n = listener.recv(&mut buf).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(&buf[..n], b"READY=1\nSTATUS=3 units active");
+
+        let pid = std::process::id().to_string();
+        let mine = Supervisor::new(socket, Some("500"), Some(&pid)).unwrap();
+        assert_eq!(mine.watchdog_interval(), Some(Duration::from_micros(500)));
+        let other = Supervisor::new(socket, Some("500"), Some("1")).unwrap();
+        assert_eq!(other.watchdog_interval(), None);
+        let abstract_name = Supervisor::new("@sysd/notify", Some("0"), None).unwrap();
+        assert_eq!(abstract_name.watchdog_interval(), None);
+    }
+}
@@ -0,0 +1,327 @@
+//! Where sysd keeps its own state
+//!
+//! Everything sysd writes for itself lives below two directories, both
+//! resolved through [`crate::root`] so tests and `--root` stay inside their
+//! tree:
+//!
+//! ```text
+//! /run/sysd/                 runtime state, gone at reboot
+//!   notify                   sd_notify socket
+//!   scopes/                  logind scopes, adopted again after a restart
+//!   fdstore/                 file descriptor store entries
+//!   serialized               manager state handed across a re-exec
+//! /var/lib/sysd/             persistent state
+//!   layout-version           version of this layout
+//!   linger/                  users whose manager starts at boot
+//!   timers/                  last trigger of Persistent= timers
+//!   state-history            unit state transitions (PersistStateHistory=)
+//!   credential.secret        host key for encrypted credentials
+//! ```
+//!
+//! The user manager keeps the same trees in /run/user/UID/sysd and
+//! $XDG_STATE_HOME/sysd (~/.local/state/sysd).
+//!
+//! The persistent tree carries a layout version. A sysd finding an older
+//! one runs the migrations in between at startup; one finding a newer one
+//! leaves it alone rather than guess at a format it does not know.
+
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+/// Runtime state of the system manager
+pub const RUNTIME_DIR: &str = "/run/sysd";
+/// Persistent state of the system manager
+pub const STATE_DIR: &str = "/var/lib/sysd";
+/// sd_notify socket of the system manager
+pub const NOTIFY_SOCKET_PATH: &str = "/run/sysd/notify";
+/// Persisted scope definitions of the system manager
+pub const SCOPE_STATE_DIR: &str = "/run/sysd/scopes";
+/// Linger markers written by `sysd login enable-linger` (one file per user)
+pub const LINGER_DIR: &str = "/var/lib/sysd/linger";
+/// Host secret all credentials are sealed with (created on first use)
+pub const HOST_KEY_PATH: &str = "/var/lib/sysd/credential.secret";
+
+/// File in the persistent tree holding its layout version
+const VERSION_FILE: &str = "layout-version";
+
+/// Directories of the runtime tree (relative to it) and their modes
+const RUNTIME_DIRS: [(&str, u32); 3] = [("", 0o755), ("scopes", 0o700), ("fdstore", 0o700)];
+
+/// Directories of the persistent tree (relative to it) and their modes
+const STATE_DIRS: [(&str, u32); 3] = [("", 0o755), ("linger", 0o755), ("timers", 0o755)];
+
+/// Brings the persistent tree from one layout version to the next
+type Migration = fn(&StateDirs) -> io::Result<()>;
+
+/// `MIGRATIONS[n]` turns layout version n + 1 into n + 2
+const MIGRATIONS: [Migration; 0] = [];
+
+/// Version of the layout this sysd writes (trees from before the layout was
+/// versioned are version 1)
+pub const LAYOUT_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StateDirError {
+    #[error("{}: {source}", .path.display())]
+    Io { path: PathBuf, source: io::Error },
+
+    #[error("{}: invalid layout version {content:?}", .path.display())]
+    InvalidVersion { path: PathBuf, content: String },
+
+    #[error(
+        "{} has layout version {found}, newer than version {supported} this sysd knows",
+        .path.display()
+    )]
+    NewerVersion {
+        path: PathBuf,
+        found: u32,
+        supported: u32,
+    },
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> StateDirError + '_ {
+    move |source| StateDirError::Io {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+/// The runtime and persistent state trees of one manager
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateDirs {
+    runtime: PathBuf,
+    state: PathBuf,
+}
+
+impl StateDirs {
+    /// The trees of the system manager
+    pub fn system() -> Self {
+        Self::at(crate::root::path(RUNTIME_DIR), crate::root::path(STATE_DIR))
+    }
+
+    /// The trees of the calling user's manager
+    pub fn user() -> Self {
+        let uid = nix::unistd::getuid().as_raw();
+        let runtime = crate::root::path(format!("/run/user/{}/sysd", uid));
+        let state = match dirs::state_dir() {
+            Some(dir) => crate::root::path(dir.join("sysd")),
+            None => runtime.join("state"),
+        };
+        Self::at(runtime, state)
+    }
+
+    pub fn for_mode(user_mode: bool) -> Self {
+        if user_mode {
+            Self::user()
+        } else {
+            Self::system()
+        }
+    }
+
+    /// Trees at explicit locations (for testing)
+    pub fn at(runtime: PathBuf, state: PathBuf) -> Self {
+        Self { runtime, state }
+    }
+
+    pub fn runtime(&self) -> &Path {
+        &self.runtime
+    }
+
+    pub fn state(&self) -> &Path {
+        &self.state
+    }
+
+    pub fn notify_socket(&self) -> PathBuf {
+        self.runtime.join("notify")
+    }
+
+    pub fn scopes(&self) -> PathBuf {
+        self.runtime.join("scopes")
+    }
+
+    pub fn fd_store(&self) -> PathBuf {
+        self.runtime.join("fdstore")
+    }
+
+    pub fn serialized(&self) -> PathBuf {
+        self.runtime.join("serialized")
+    }
+
+    pub fn linger(&self) -> PathBuf {
+        self.state.join("linger")
+    }
+
+    pub fn timers(&self) -> PathBuf {
+        self.state.join("timers")
+    }
+
+    /// When the Persistent= timer `timer` last triggered (file mtime)
+    pub fn timer_stamp(&self, timer: &str) -> PathBuf {
+        self.timers().join(format!("stamp-{}", timer))
+    }
+
+    /// Unit state histories kept across restarts (PersistStateHistory=)
+    pub fn state_history(&self) -> PathBuf {
+        self.state.join("state-history")
+    }
+
+    pub fn credential_secret(&self) -> PathBuf {
+        self.state.join("credential.secret")
+    }
+
+    /// Create both trees with their modes and bring an older persistent tree
+    /// up to the current layout
+    pub fn prepare(&self) -> Result<(), StateDirError> {
+        create_dirs(&self.runtime, &RUNTIME_DIRS)?;
+        create_dirs(&self.state, &STATE_DIRS)?;
+        let found = self.layout_version()?;
+        self.migrate(found, &MIGRATIONS)
+    }
+
+    fn layout_version(&self) -> Result<u32, StateDirError> {
+        let path = self.state.join(VERSION_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(content) => content
+                .trim()
+                .parse()
+                .map_err(|_| StateDirError::InvalidVersion {
+                    path,
+                    content: content.trim().to_string(),
+                }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(1),
+            Err(e) => Err(io_error(&path)(e)),
+        }
+    }
+
+    fn migrate(&self, found: u32, migrations: &[Migration]) -> Result<(), StateDirError> {
+        let path = self.state.join(VERSION_FILE);
+        let supported = migrations.len() as u32 + 1;
+        if found > supported {
+            return Err(StateDirError::NewerVersion {
+                path,
+                found,
+                supported,
+            });
+        }
+        for version in found.max(1)..supported {
+            migrations[version as usize - 1](self).map_err(io_error(&self.state))?;
+            log::info!(
+                "Migrated {} from layout version {} to {}",
+                self.state.display(),
+                version,
+                version + 1
+            );
+            // After each step, so an interrupted migration resumes there
+            write_version(&path, version + 1)?;
+        }
+        if !path.exists() {
+            write_version(&path, supported)?;
+        }
+        Ok(())
+    }
+}
+
+fn create_dirs(base: &Path, dirs: &[(&str, u32)]) -> Result<(), StateDirError> {
+    for (dir, mode) in dirs {
+        let path = base.join(dir);
+        std::fs::create_dir_all(&path).map_err(io_error(&path))?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(*mode))
+            .map_err(io_error(&path))?;
+    }
+    Ok(())
+}
+
+fn write_version(path: &Path, version: u32) -> Result<(), StateDirError> {
+    std::fs::write(path, format!("{}\n", version)).map_err(io_error(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn temp_dirs(label: &str) -> StateDirs {
+        let base =
+            std::env::temp_dir().join(format!("sysd-state-dir-{}-{}", label, std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        StateDirs::at(base.join("run"), base.join("lib"))
+    }
+
+    fn cleanup(dirs: &StateDirs) {
+        let _ = std::fs::remove_dir_all(dirs.runtime().parent().unwrap());
+    }
+
+    fn mode(path: &Path) -> u32 {
+        std::fs::metadata(path).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn system_layout_matches_the_documented_paths() {
+        if crate::root::get().is_some() {
+            return;
+        }
+        let dirs = StateDirs::system();
+        assert_eq!(dirs.notify_socket(), Path::new(NOTIFY_SOCKET_PATH));
+        assert_eq!(dirs.scopes(), Path::new(SCOPE_STATE_DIR));
+        assert_eq!(dirs.linger(), Path::new(LINGER_DIR));
+        assert_eq!(dirs.credential_secret(), Path::new(HOST_KEY_PATH));
+        assert_eq!(
+            dirs.timer_stamp("backup.timer"),
+            Path::new("/var/lib/sysd/timers/stamp-backup.timer")
+        );
+        assert!(StateDirs::user().runtime().ends_with("sysd"));
+    }
+
+    #[test]
+    fn prepare_creates_the_trees_with_their_modes_and_records_the_version() {
+        let dirs = temp_dirs("prepare");
+        dirs.prepare().unwrap();
+
+        assert_eq!(mode(dirs.runtime()), 0o755);
+        assert_eq!(mode(&dirs.scopes()), 0o700);
+        assert_eq!(mode(&dirs.fd_store()), 0o700);
+        assert!(dirs.linger().is_dir());
+        assert!(dirs.timers().is_dir());
+        let version = std::fs::read_to_string(dirs.state().join(VERSION_FILE)).unwrap();
+        assert_eq!(version.trim(), LAYOUT_VERSION.to_string());
+
+        // Idempotent, also when the modes were changed behind its back
+        std::fs::set_permissions(dirs.scopes(), std::fs::Permissions::from_mode(0o777)).unwrap();
+        dirs.prepare().unwrap();
+        assert_eq!(mode(&dirs.scopes()), 0o700);
+        cleanup(&dirs);
+    }
+
+    #[test]
+    fn migrations_run_in_order_and_newer_layouts_are_refused() {
+        static RUN: AtomicU32 = AtomicU32::new(0);
+        fn second(_: &StateDirs) -> io::Result<()> {
+            assert_eq!(RUN.fetch_add(1, Ordering::SeqCst), 0);
+            Ok(())
+        }
+        fn third(dirs: &StateDirs) -> io::Result<()> {
+            assert_eq!(RUN.fetch_add(1, Ordering::SeqCst), 1);
+            std::fs::write(dirs.state().join("migrated"), "")
+        }
+
+        let dirs = temp_dirs("migrate");
+        dirs.prepare().unwrap();
+        let migrations: [Migration; 2] = [second, third];
+        dirs.migrate(1, &migrations).unwrap();
+        assert_eq!(RUN.load(Ordering::SeqCst), 2);
+        assert!(dirs.state().join("migrated").exists());
+        assert_eq!(dirs.layout_version().unwrap(), 3);
+
+        assert!(matches!(
+            dirs.migrate(3, &MIGRATIONS),
+            Err(StateDirError::NewerVersion { found: 3, .. })
+        ));
+        std::fs::write(dirs.state().join(VERSION_FILE), "two\n").unwrap();
+        assert!(matches!(
+            dirs.prepare(),
+            Err(StateDirError::InvalidVersion { .. })
+        ));
+        cleanup(&dirs);
+    }
+}
@@ -0,0 +1,387 @@
+//! SysV init scripts - generates .service units for /etc/init.d scripts
+//!
+//! Replaces systemd-sysv-generator with built-in parsing, for legacy systems
+//! and appliances that still ship init scripts.
+//!
+//! Scripts with an LSB header and no native unit of the same name become
+//! wrapper services:
+//! ```text
+//! ### BEGIN INIT INFO
+//! # Provides:          foo
+//! # Required-Start:    $network $remote_fs bar
+//! # Default-Start:     2 3 4 5
+//! # Short-Description: Foo daemon
+//! ### END INIT INFO
+//! ```
+//! runs as `Type=forking` with `ExecStart=/etc/init.d/foo start` and
+//! `ExecStop=/etc/init.d/foo stop`, after (and wanting) network-online.target,
+//! remote-fs.target and the service of the script providing `bar`.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::units::{KillMode, Service, ServiceType};
+
+/// Files in /etc/init.d that are not init scripts
+const IGNORED_SCRIPTS: [&str; 6] = ["README", "skeleton", "functions", "rc", "rcS", "rc.local"];
+
+/// LSB facilities and the targets standing in for them (None: always there)
+const FACILITIES: [(&str, Option<&str>); 7] = [
+    ("$local_fs", Some("local-fs.target")),
+    ("$network", Some("network-online.target")),
+    ("$named", Some("nss-lookup.target")),
+    ("$portmap", Some("rpcbind.target")),
+    ("$remote_fs", Some("remote-fs.target")),
+    ("$time", Some("time-sync.target")),
+    ("$syslog", None),
+];
+
+/// The LSB comment block of an init script
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LsbHeader {
+    /// Facilities the script provides (Provides:)
+    pub provides: Vec<String>,
+    /// Facilities that must be started first (Required-Start:)
+    pub required_start: Vec<String>,
+    /// Facilities started first if present (Should-Start:)
+    pub should_start: Vec<String>,
+    /// Runlevels the script starts in (Default-Start:)
+    pub default_start: Vec<String>,
+    /// Short-Description:
+    pub short_description: Option<String>,
+    /// Description:, continuation lines joined
+    pub description: Option<String>,
+}
+
+/// An init script with an LSB header
+#[derive(Debug, Clone)]
+pub struct SysvScript {
+    /// File name in the init.d directory
+    pub name: String,
+    pub path: PathBuf,
+    pub header: LsbHeader,
+}
+
+impl SysvScript {
+    /// Name of the generated service
+    pub fn service_name(&self) -> String {
+        format!("{}.service", self.name)
+    }
+}
+
+/// Parse the LSB header of an init script (None if it has none)
+pub fn parse_lsb_header(script: &str) -> Option<LsbHeader> {
+    let mut header = LsbHeader::default();
+    let mut in_header = false;
+    let mut found = false;
+    let mut last_key = "";
+
+    for line in script.lines() {
+        let line = line.trim_end();
+        if line.starts_with("### BEGIN INIT INFO") {
+            in_header = true;
+            continue;
+        }
+        if line.starts_with("### END INIT INFO") {
+            found = in_header;
+            break;
+        }
+        if !in_header {
+            continue;
+        }
+        let Some(comment) = line.strip_prefix('#') else {
+            continue;
+        };
+        // Description: continues on lines indented after the '#'
+        if comment.starts_with("  ") || comment.starts_with('\t') {
+            if last_key == "Description" {
+                if let Some(description) = header.description.as_mut() {
+                    description.push(' ');
+                    description.push_str(comment.trim());
+                }
+            }
+            continue;
+        }
+        let Some((key, value)) = comment.trim().split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        let words = || value.split_whitespace().map(str::to_string).collect();
+        last_key = key;
+        match key {
+            "Provides" => header.provides = words(),
+            "Required-Start" => header.required_start = words(),
+            "Should-Start" => header.should_start = words(),
+            "Default-Start" => header.default_start = words(),
+            "Short-Description" => header.short_description = Some(value.to_string()),
+            "Description" => header.description = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    found.then_some(header)
+}
+
+/// Init scripts with an LSB header in `dir`, sorted by name
+pub fn find_sysv_scripts(dir: &Path) -> Vec<SysvScript> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut scripts: Vec<SysvScript> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if is_ignored_script(&name) {
+                return None;
+            }
+            let path = entry.path();
+            let metadata = std::fs::metadata(&path).ok()?;
+            if !metadata.is_file() || metadata.permissions().mode() & 0o111 == 0 {
+                return None;
+            }
+            let content = std::fs::read(&path).ok()?;
+            let header = parse_lsb_header(&String::from_utf8_lossy(&content));
+            if header.is_none() {
+                log::debug!("{} has no LSB header, skipping", path.display());
+            }
+            Some(SysvScript {
+                name,
+                path,
+                header: header?,
+            })
+        })
+        .collect();
+    scripts.sort_by(|a, b| a.name.cmp(&b.name));
+    scripts
+}
+
+/// Hidden files, backups and package manager leftovers
+fn is_ignored_script(name: &str) -> bool {
+    IGNORED_SCRIPTS.contains(&name)
+        || name.starts_with('.')
+        || name.ends_with('~')
+        || name.contains(".dpkg-")
+        || name.contains(".rpm")
+}
+
+/// Wrapper services for `scripts`, with Required-Start=/Should-Start=
+/// resolved through the facilities the scripts provide
+pub fn generate_sysv_services(scripts: &[SysvScript]) -> Vec<Service> {
+    let mut providers: HashMap<&str, String> = HashMap::new();
+    for script in scripts {
+        providers.insert(script.name.as_str(), script.service_name());
+        for facility in &script.header.provides {
+            providers
+                .entry(facility.as_str())
+                .or_insert_with(|| script.service_name());
+        }
+    }
+    scripts
+        .iter()
+        .map(|script| script_service(script, &providers))
+        .collect()
+}
+
+fn script_service(script: &SysvScript, providers: &HashMap<&str, String>) -> Service {
+    let name = script.service_name();
+    let mut svc = Service::new(name.clone());
+    let header = &script.header;
+    let description = header
+        .short_description
+        .as_ref()
+        .or(header.description.as_ref())
+        .unwrap_or(&script.name);
+    svc.unit.description = Some(format!("LSB: {}", description));
+    svc.unit.documentation = vec![format!("file:{}", script.path.display())];
+
+    for (facility, wanted) in header
+        .required_start
+        .iter()
+        .map(|facility| (facility, true))
+        .chain(header.should_start.iter().map(|facility| (facility, false)))
+    {
+        let Some(dependency) = facility_unit(facility, providers) else {
+            continue;
+        };
+        if dependency == name || svc.unit.after.contains(&dependency) {
+            continue;
+        }
+        svc.unit.after.push(dependency.clone());
+        if wanted {
+            svc.unit.wants.push(dependency);
+        }
+    }
+
+    let path = script.path.display();
+    svc.service.service_type = ServiceType::Forking;
+    svc.service.exec_start = vec![format!("{} start", path)];
+    svc.service.exec_stop = vec![format!("{} stop", path)];
+    // Like systemd-sysv-generator: scripts that only set something up stay
+    // active, and their daemons are not killed with the script's helpers
+    svc.service.remain_after_exit = true;
+    svc.service.kill_mode = KillMode::Process;
+    svc.service.ignore_sigpipe = false;
+    svc.service.timeout_start_sec = Some(std::time::Duration::from_secs(300));
+    svc
+}
+
+/// The unit an LSB facility or script name in Required-Start= stands for
+fn facility_unit(facility: &str, providers: &HashMap<&str, String>) -> Option<String> {
+    if let Some((_, target)) = FACILITIES.iter().find(|(name, _)| *name == facility) {
+        return target.map(str::to_string);
+    }
+    if facility.starts_with('$') {
+        log::debug!("Unknown LSB facility {}, ignoring", facility);
+        return None;
+    }
+    Some(
+        providers
+            .get(facility)
+            .cloned()
+            .unwrap_or_else(|| format!("{}.service", facility)),
+    )
+}
+
+/// Runlevels `script` starts in: from its S links in `rc_root`/rcN.d when
+/// the system has rc directories, otherwise from Default-Start:
+pub fn script_runlevels(rc_root: &Path, script: &SysvScript) -> Vec<char> {
+    let rc_dirs: Vec<(char, PathBuf)> = ('0'..='6')
+        .map(|runlevel| (runlevel, rc_root.join(format!("rc{}.d", runlevel))))
+        .filter(|(_, dir)| dir.is_dir())
+        .collect();
+    if rc_dirs.is_empty() {
+        return script
+            .header
+            .default_start
+            .iter()
+            .filter_map(|level| level.chars().next().filter(|_| level.len() == 1))
+            .collect();
+    }
+    rc_dirs
+        .into_iter()
+        .filter(|(_, dir)| has_start_link(dir, &script.name))
+        .map(|(runlevel, _)| runlevel)
+        .collect()
+}
+
+/// Whether `dir` has an `S<NN><name>` link
+fn has_start_link(dir: &Path, name: &str) -> bool {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        entry
+            .file_name()
+            .to_str()
+            .and_then(|link| link.strip_prefix('S'))
+            .map(|rest| rest.trim_start_matches(|c: char| c.is_ascii_digit()))
+            .is_some_and(|rest| rest == name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "#!/bin/sh\n\
+        ### BEGIN INIT INFO\n\
+        # Provides:          foo foo-daemon\n\
+        # Required-Start:    $network $syslog bar\n\
+        # Should-Start:      baz\n\
+        # Default-Start:     2 3 4 5\n\
+        # Default-Stop:      0 1 6\n\
+        # Short-Description: Foo daemon\n\
+        # Description:       Runs foo\n\
+        #  in the background\n\
+        ### END INIT INFO\n\
+        case \"$1\" in start) ;; esac\n";
+
+    fn script(name: &str, header: LsbHeader) -> SysvScript {
+        SysvScript {
+            name: name.to_string(),
+            path: PathBuf::from(format!("/etc/init.d/{}", name)),
+            header,
+        }
+    }
+
+    #[test]
+    fn lsb_header_is_parsed_between_the_markers() {
+        let header = parse_lsb_header(HEADER).unwrap();
+        assert_eq!(header.provides, ["foo", "foo-daemon"]);
+        assert_eq!(header.required_start, ["$network", "$syslog", "bar"]);
+        assert_eq!(header.should_start, ["baz"]);
+        assert_eq!(header.default_start, ["2", "3", "4", "5"]);
+        assert_eq!(header.short_description.as_deref(), Some("Foo daemon"));
+        assert_eq!(
+            header.description.as_deref(),
+            Some("Runs foo in the background")
+        );
+
+        assert!(parse_lsb_header("#!/bin/sh\necho hi\n").is_none());
+        assert!(parse_lsb_header("### BEGIN INIT INFO\n# Provides: x\n").is_none());
+    }
+
+    #[test]
+    fn services_wrap_the_script_and_resolve_provided_facilities() {
+        let foo = script("foo", parse_lsb_header(HEADER).unwrap());
+        let bar = script(
+            "bar-init",
+            LsbHeader {
+                provides: vec!["bar".to_string()],
+                ..Default::default()
+            },
+        );
+        let services = generate_sysv_services(&[foo, bar]);
+        let foo = &services[0];
+
+        assert_eq!(foo.name, "foo.service");
+        assert_eq!(foo.unit.description.as_deref(), Some("LSB: Foo daemon"));
+        assert_eq!(foo.service.service_type, ServiceType::Forking);
+        assert_eq!(foo.service.exec_start, ["/etc/init.d/foo start"]);
+        assert_eq!(foo.service.exec_stop, ["/etc/init.d/foo stop"]);
+        assert!(foo.service.remain_after_exit);
+        assert_eq!(
+            foo.unit.after,
+            ["network-online.target", "bar-init.service", "baz.service"]
+        );
+        assert_eq!(
+            foo.unit.wants,
+            ["network-online.target", "bar-init.service"]
+        );
+        assert_eq!(
+            services[1].unit.description.as_deref(),
+            Some("LSB: bar-init")
+        );
+    }
+
+    #[test]
+    fn scripts_are_found_by_header_and_enabled_by_rc_links() {
+        let root = std::env::temp_dir().join(format!("sysd-sysv-{}", std::process::id()));
+        let init_d = root.join("init.d");
+        std::fs::create_dir_all(&init_d).unwrap();
+        let write_script = |name: &str, content: &str, mode: u32| {
+            use std::os::unix::fs::PermissionsExt;
+            let path = init_d.join(name);
+            std::fs::write(&path, content).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        };
+        write_script("foo", HEADER, 0o755);
+        write_script("plain", "#!/bin/sh\n", 0o755);
+        write_script("disabled", HEADER, 0o644);
+        write_script("foo.dpkg-old", HEADER, 0o755);
+
+        let scripts = find_sysv_scripts(&init_d);
+        assert_eq!(scripts.len(), 1);
+        assert_eq!(scripts[0].name, "foo");
+        assert_eq!(script_runlevels(&root, &scripts[0]), ['2', '3', '4', '5']);
+
+        std::fs::create_dir_all(root.join("rc3.d")).unwrap();
+        std::fs::create_dir_all(root.join("rc5.d")).unwrap();
+        std::os::unix::fs::symlink("../init.d/foo", root.join("rc3.d/S20foo")).unwrap();
+        std::os::unix::fs::symlink("../init.d/foo", root.join("rc5.d/K80foo")).unwrap();
+        assert_eq!(script_runlevels(&root, &scripts[0]), ['3']);
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+//! Controlling terminal handling for TTYPath= services, shared by manager and executor.
+//!
+//! `attach` runs in the forked child right before exec: it starts a new
+//! session, takes the terminal as controlling TTY and makes it stdin/stdout/
+//! stderr. The session's process group becomes the terminal's foreground
+//! group, so the kernel delivers ^C, ^Z and hangups from the console straight
+//! to the service. `reset_after_exit` runs in the manager once the service is
+//! gone, so the next getty finds a clean terminal.
+
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
+use std::path::Path;
+
+const TIOCVHANGUP: u64 = 0x5437;
+const VT_DISALLOCATE: u64 = 0x5608;
+
+/// Signals a terminal session relies on; ignored or blocked dispositions
+/// must not leak from the manager into the service
+const JOB_CONTROL_SIGNALS: [libc::c_int; 7] = [
+    libc::SIGHUP,
+    libc::SIGINT,
+    libc::SIGQUIT,
+    libc::SIGPIPE,
+    libc::SIGTSTP,
+    libc::SIGTTIN,
+    libc::SIGTTOU,
+];
+
+/// How the terminal is acquired (StandardInput=tty, tty-force, tty-fail)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TtyAcquire {
+    /// Take the terminal if it is free, carry on without it otherwise
+    Try,
+    /// Steal the terminal from another session
+    Force,
+    /// Fail the service if the terminal cannot be taken
+    Fail,
+}
+
+/// TTYReset=, TTYVHangup= and TTYVTDisallocate=
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TtyOptions {
+    pub reset: bool,
+    pub vhangup: bool,
+    pub vt_disallocate: bool,
+}
+
+/// Make `path` the controlling terminal and standard streams of this process
+pub fn attach(path: &Path, acquire: TtyAcquire, options: TtyOptions) -> std::io::Result<()> {
+    if options.vt_disallocate {
+        disallocate_vt(path);
+    }
+    if options.vhangup {
+        hangup(path);
+    }
+
+    let fd = open_tty(path, false)?.into_raw_fd();
+    // Fails only for process group leaders, which already own their session
+    unsafe { libc::setsid() };
+    if options.reset {
+        reset_terminal(fd);
+    }
+    let steal = libc::c_int::from(acquire == TtyAcquire::Force);
+    if unsafe { libc::ioctl(fd, libc::TIOCSCTTY, steal) } < 0 && acquire == TtyAcquire::Fail {
+        let error = std::io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return Err(error);
+    }
+    unsafe {
+        libc::dup2(fd, 0);
+        libc::dup2(fd, 1);
+        libc::dup2(fd, 2);
+        if fd > 2 {
+            libc::close(fd);
+        }
+    }
+    reset_job_control_signals();
+    Ok(())
+}
+
+/// Clean up the terminal a service used, as configured
+pub fn reset_after_exit(path: &Path, options: TtyOptions) {
+    if options.reset {
+        if let Ok(file) = open_tty(path, true) {
+            reset_terminal(file.as_raw_fd());
+        }
+    }
+    if options.vhangup {
+        hangup(path);
+    }
+    if options.vt_disallocate {
+        disallocate_vt(path);
+    }
+}
+
+/// Open a terminal without making it our controlling TTY
+fn open_tty(path: &Path, nonblock: bool) -> std::io::Result<std::fs::File> {
+    let mut flags = libc::O_NOCTTY;
+    if nonblock {
+        flags |= libc::O_NONBLOCK;
+    }
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(flags)
+        .open(path)
+}
+
+/// Sane line settings, like `stty sane`
+fn reset_terminal(fd: RawFd) {
+    unsafe {
+        libc::ioctl(fd, libc::TIOCNXCL);
+        let mut termios: libc::termios = std::mem::zeroed();
+        if libc::tcgetattr(fd, &mut termios) == 0 {
+            termios.c_iflag &= !(libc::IGNBRK
+                | libc::BRKINT
+                | libc::ISTRIP
+                | libc::INLCR
+                | libc::IGNCR
+                | libc::IUCLC);
+            termios.c_iflag |= libc::ICRNL | libc::IMAXBEL | libc::IUTF8;
+            termios.c_oflag |= libc::ONLCR | libc::OPOST;
+            termios.c_cflag |= libc::CREAD;
+            termios.c_lflag = libc::ISIG
+                | libc::ICANON
+                | libc::IEXTEN
+                | libc::ECHO
+                | libc::ECHOE
+                | libc::ECHOK
+                | libc::ECHOCTL
+                | libc::ECHOKE;
+            termios.c_cc[libc::VINTR] = 0o3;
+            termios.c_cc[libc::VQUIT] = 0o34;
+            termios.c_cc[libc::VERASE] = 0o177;
+            termios.c_cc[libc::VKILL] = 0o25;
+            termios.c_cc[libc::VEOF] = 0o4;
+            termios.c_cc[libc::VSTART] = 0o21;
+            termios.c_cc[libc::VSTOP] = 0o23;
+            termios.c_cc[libc::VSUSP] = 0o32;
+            termios.c_cc[libc::VLNEXT] = 0o26;
+            termios.c_cc[libc::VWERASE] = 0o27;
+            termios.c_cc[libc::VREPRINT] = 0o22;
+            termios.c_cc[libc::VEOL] = 0;
+            termios.c_cc[libc::VEOL2] = 0;
+            termios.c_cc[libc::VTIME] = 0;
+            termios.c_cc[libc::VMIN] = 1;
+            libc::tcsetattr(fd, libc::TCSANOW, &termios);
+        }
+        libc::tcflush(fd, libc::TCIOFLUSH);
+    }
+}
+
+/// Disconnect every other process that has the terminal open
+fn hangup(path: &Path) {
+    if let Ok(file) = open_tty(path, true) {
+        unsafe { libc::ioctl(file.as_raw_fd(), TIOCVHANGUP as _) };
+    }
+}
+
+/// Free a virtual console's scrollback; the active VT cannot be
+/// deallocated, so it is only cleared
+fn disallocate_vt(path: &Path) {
+    let Some(vt) = vt_number(path) else {
+        return;
+    };
+    if let Ok(console) = open_tty(Path::new("/dev/tty0"), true) {
+        let fd = console.as_raw_fd();
+        if unsafe { libc::ioctl(fd, VT_DISALLOCATE as _, vt as libc::c_ulong) } == 0 {
+            return;
+        }
+    }
+    if let Ok(mut file) = open_tty(path, true) {
+        let _ = file.write_all(b"\x1b[r\x1b[H\x1b[3J\x1bc");
+    }
+}
+
+/// Virtual console number of /dev/ttyN (N >= 1)
+fn vt_number(path: &Path) -> Option<u32> {
+    let name = path.to_str()?.strip_prefix("/dev/tty")?;
+    name.parse().ok().filter(|&vt| vt >= 1)
+}
+
+fn reset_job_control_signals() {
+    unsafe {
+        for signal in JOB_CONTROL_SIGNALS {
+            libc::signal(signal, libc::SIG_DFL);
+        }
+        let mut mask: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut mask);
+        libc::sigprocmask(libc::SIG_SETMASK, &mask, std::ptr::null_mut());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_numbered_virtual_consoles_have_a_vt_number() {
+        assert_eq!(vt_number(Path::new("/dev/tty1")), Some(1));
+        assert_eq!(vt_number(Path::new("/dev/tty12")), Some(12));
+        assert_eq!(vt_number(Path::new("/dev/tty0")), None);
+        assert_eq!(vt_number(Path::new("/dev/ttyS0")), None);
+        assert_eq!(vt_number(Path::new("/dev/console")), None);
+    }
+
+    #[test]
+    fn resetting_a_missing_terminal_is_harmless() {
+        let options = TtyOptions {
+            reset: true,
+            vhangup: true,
+            vt_disallocate: true,
+        };
+        reset_after_exit(Path::new("/nonexistent/tty7"), options);
+    }
+}
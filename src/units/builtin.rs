@@ -0,0 +1,166 @@
+//! Standard targets compiled into sysd
+//!
+//! A bare system without systemd's unit files still gets sysinit.target,
+//! basic.target, multi-user.target and the other synchronisation points
+//! services are ordered against. A unit file of the same name anywhere in
+//! the search path replaces the built-in definition; drop-ins and .wants/
+//! directories apply to built-in targets as if they were installed in
+//! /usr/lib/systemd/system. Only the system manager has them.
+//!
+//! The definitions follow systemd's, minus references to units sysd does not
+//! ship (rescue.service, systemd-reboot.service, ...).
+
+/// Target booted when there is no default.target
+pub const BUILTIN_DEFAULT_TARGET: &str = "graphical.target";
+
+const BUILTIN_UNITS: [(&str, &str); 13] = [
+    (
+        "local-fs.target",
+        "[Unit]
+Description=Local File Systems
+Documentation=man:systemd.special(7)
+DefaultDependencies=no
+Conflicts=shutdown.target
+Before=shutdown.target
+",
+    ),
+    (
+        "sysinit.target",
+        "[Unit]
+Description=System Initialization
+Documentation=man:systemd.special(7)
+DefaultDependencies=no
+Wants=local-fs.target
+After=local-fs.target
+Conflicts=shutdown.target
+Before=shutdown.target
+",
+    ),
+    (
+        "sockets.target",
+        "[Unit]
+Description=Socket Units
+Documentation=man:systemd.special(7)
+",
+    ),
+    (
+        "timers.target",
+        "[Unit]
+Description=Timer Units
+Documentation=man:systemd.special(7)
+DefaultDependencies=no
+Conflicts=shutdown.target
+Before=shutdown.target
+",
+    ),
+    (
+        "paths.target",
+        "[Unit]
+Description=Path Units
+Documentation=man:systemd.special(7)
+",
+    ),
+    (
+        "basic.target",
+        "[Unit]
+Description=Basic System
+Documentation=man:systemd.special(7)
+Requires=sysinit.target
+Wants=sockets.target timers.target paths.target
+After=sysinit.target sockets.target paths.target timers.target
+",
+    ),
+    (
+        "network.target",
+        "[Unit]
+Description=Network
+Documentation=man:systemd.special(7)
+",
+    ),
+    (
+        "multi-user.target",
+        "[Unit]
+Description=Multi-User System
+Documentation=man:systemd.special(7)
+Requires=basic.target
+Conflicts=rescue.target
+After=basic.target rescue.target
+",
+    ),
+    (
+        "graphical.target",
+        "[Unit]
+Description=Graphical Interface
+Documentation=man:systemd.special(7)
+Requires=multi-user.target
+Conflicts=rescue.target
+After=multi-user.target rescue.target
+",
+    ),
+    (
+        "rescue.target",
+        "[Unit]
+Description=Rescue Mode
+Documentation=man:systemd.special(7)
+Requires=sysinit.target
+After=sysinit.target
+",
+    ),
+    (
+        "shutdown.target",
+        "[Unit]
+Description=System Shutdown
+Documentation=man:systemd.special(7)
+DefaultDependencies=no
+",
+    ),
+    (
+        "reboot.target",
+        "[Unit]
+Description=System Reboot
+Documentation=man:systemd.special(7)
+DefaultDependencies=no
+Requires=shutdown.target
+After=shutdown.target
+",
+    ),
+    (
+        "poweroff.target",
+        "[Unit]
+Description=System Power Off
+Documentation=man:systemd.special(7)
+DefaultDependencies=no
+Requires=shutdown.target
+After=shutdown.target
+",
+    ),
+];
+
+/// Unit file of the built-in unit `name`, if there is one
+pub fn builtin_unit(name: &str) -> Option<&'static str> {
+    BUILTIN_UNITS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, content)| *content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::{parse_file, parse_target};
+
+    #[test]
+    fn builtin_targets_parse_and_only_reference_each_other() {
+        for (name, content) in BUILTIN_UNITS {
+            let parsed = parse_file(content).unwrap();
+            let target = parse_target(name, &parsed).unwrap();
+            assert!(target.unit.description.is_some(), "{}", name);
+            let section = &target.unit;
+            for dep in section.requires.iter().chain(&section.wants) {
+                assert!(builtin_unit(dep).is_some(), "{} needs {}", name, dep);
+            }
+        }
+        assert!(builtin_unit(BUILTIN_DEFAULT_TARGET).is_some());
+        assert!(builtin_unit("sshd.service").is_none());
+    }
+}
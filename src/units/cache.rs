@@ -0,0 +1,347 @@
+//! Unit file cache
+//!
+//! Boot resolves the same units many times over (dependencies, templates,
+//! reloads). Parsed files are cached by path and reused while their mtime and
+//! size are unchanged; unit directories are listed once into an index that is
+//! only rescanned when one of the directories changes. Daemon-reload drops the
+//! entries of files and directories that no longer exist.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+
+use super::parser::{parse_unit_file, ParseError, ParsedFile};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+        })
+    }
+}
+
+struct CachedFile {
+    stamp: FileStamp,
+    parsed: ParsedFile,
+}
+
+struct CachedDir {
+    stamp: FileStamp,
+    names: Arc<Vec<String>>,
+}
+
+/// Unit name → file path for one list of search directories
+struct UnitIndex {
+    dirs: Vec<(PathBuf, Option<FileStamp>)>,
+    paths: HashMap<String, PathBuf>,
+    /// Unit file name → names of the alias symlinks pointing at it
+    aliases: HashMap<String, Vec<String>>,
+}
+
+fn parsed_files() -> &'static Mutex<HashMap<PathBuf, CachedFile>> {
+    static FILES: OnceLock<Mutex<HashMap<PathBuf, CachedFile>>> = OnceLock::new();
+    FILES.get_or_init(Default::default)
+}
+
+fn directories() -> &'static Mutex<HashMap<PathBuf, CachedDir>> {
+    static DIRS: OnceLock<Mutex<HashMap<PathBuf, CachedDir>>> = OnceLock::new();
+    DIRS.get_or_init(Default::default)
+}
+
+fn unit_index() -> &'static Mutex<Option<UnitIndex>> {
+    static INDEX: OnceLock<Mutex<Option<UnitIndex>>> = OnceLock::new();
+    INDEX.get_or_init(Default::default)
+}
+
+/// Parse a unit file, reusing the previous result if the file is unchanged
+pub async fn parse_unit_file_cached(path: &Path) -> Result<ParsedFile, ParseError> {
+    let resolved = crate::root::resolve(path);
+    let path = resolved.as_path();
+    let Some(stamp) = FileStamp::of(path) else {
+        parsed_files().lock().unwrap().remove(path);
+        return parse_unit_file(path).await;
+    };
+    if let Some(cached) = parsed_files().lock().unwrap().get(path) {
+        if cached.stamp == stamp {
+            return Ok(cached.parsed.clone());
+        }
+    }
+
+    let parsed = parse_unit_file(path).await?;
+    parsed_files().lock().unwrap().insert(
+        path.to_path_buf(),
+        CachedFile {
+            stamp,
+            parsed: parsed.clone(),
+        },
+    );
+    Ok(parsed)
+}
+
+/// Sorted entry names of a directory (None if it can't be read)
+pub fn list_directory(dir: &Path) -> Option<Arc<Vec<String>>> {
+    let stamp = FileStamp::of(dir)?;
+    if let Some(cached) = directories().lock().unwrap().get(dir) {
+        if cached.stamp == stamp {
+            return Some(cached.names.clone());
+        }
+    }
+
+    let mut names: Vec<String> = std::fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+    let names = Arc::new(names);
+    directories().lock().unwrap().insert(
+        dir.to_path_buf(),
+        CachedDir {
+            stamp,
+            names: names.clone(),
+        },
+    );
+    Some(names)
+}
+
+/// Locate `name` in the first search directory that provides it
+pub fn find_unit_file(dirs: &[PathBuf], name: &str) -> Option<PathBuf> {
+    let mut index = unit_index().lock().unwrap();
+    if !index.as_ref().is_some_and(|index| index.is_current(dirs)) {
+        *index = Some(UnitIndex::scan(dirs));
+    }
+    index.as_ref()?.paths.get(name).cloned()
+}
+
+/// Names of the symlinks in the search directories that alias the unit file
+/// named `file_name` (not the links `systemctl link` makes, which keep the
+/// name)
+pub fn unit_file_aliases(dirs: &[PathBuf], file_name: &str) -> Vec<String> {
+    let mut index = unit_index().lock().unwrap();
+    if !index.as_ref().is_some_and(|index| index.is_current(dirs)) {
+        *index = Some(UnitIndex::scan(dirs));
+    }
+    index
+        .as_ref()
+        .and_then(|index| index.aliases.get(file_name).cloned())
+        .unwrap_or_default()
+}
+
+/// Drop the directory index so the next lookup rescans (daemon-reload)
+pub fn invalidate_unit_index() {
+    *unit_index().lock().unwrap() = None;
+}
+
+/// Forget parsed files and directory listings whose path is gone, so the
+/// cache only ever holds what is on disk (daemon-reload)
+pub async fn prune_unit_file_cache() {
+    let files: Vec<PathBuf> = parsed_files().lock().unwrap().keys().cloned().collect();
+    let dirs: Vec<PathBuf> = directories().lock().unwrap().keys().cloned().collect();
+    let gone = tokio::task::spawn_blocking(move || {
+        let gone = |paths: Vec<PathBuf>| -> Vec<PathBuf> {
+            paths.into_iter().filter(|path| !path.exists()).collect()
+        };
+        (gone(files), gone(dirs))
+    })
+    .await;
+    let Ok((gone_files, gone_dirs)) = gone else {
+        return;
+    };
+    parsed_files()
+        .lock()
+        .unwrap()
+        .retain(|path, _| !gone_files.contains(path));
+    directories()
+        .lock()
+        .unwrap()
+        .retain(|path, _| !gone_dirs.contains(path));
+}
+
+impl UnitIndex {
+    fn scan(dirs: &[PathBuf]) -> Self {
+        let mut paths = HashMap::new();
+        let mut aliases: HashMap<String, Vec<String>> = HashMap::new();
+        for dir in dirs {
+            let Some(names) = list_directory(dir) else {
+                continue;
+            };
+            for name in names.iter() {
+                if paths.contains_key(name) {
+                    continue;
+                }
+                let path = dir.join(name);
+                if !is_usable_unit_path(&path) {
+                    continue;
+                }
+                if let Some(target) = alias_target(&path).filter(|target| target != name) {
+                    aliases.entry(target).or_default().push(name.clone());
+                }
+                paths.insert(name.clone(), path);
+            }
+        }
+        Self {
+            dirs: dirs
+                .iter()
+                .map(|dir| (dir.clone(), FileStamp::of(dir)))
+                .collect(),
+            paths,
+            aliases,
+        }
+    }
+
+    fn is_current(&self, dirs: &[PathBuf]) -> bool {
+        self.dirs.len() == dirs.len()
+            && self
+                .dirs
+                .iter()
+                .zip(dirs)
+                .all(|((indexed, stamp), dir)| indexed == dir && *stamp == FileStamp::of(dir))
+    }
+}
+
+/// File name of the unit file the symlink `path` ends at; None for files and
+/// masked units
+fn alias_target(path: &Path) -> Option<String> {
+    if !path.is_symlink() {
+        return None;
+    }
+    let target = match crate::root::get() {
+        Some(_) => crate::root::resolve(path),
+        None => std::fs::canonicalize(path).ok()?,
+    };
+    if target == Path::new("/dev/null") {
+        return None;
+    }
+    Some(target.file_name()?.to_str()?.to_string())
+}
+
+/// Existing files, and symlinks whose target exists (masked units link to /dev/null)
+fn is_usable_unit_path(path: &Path) -> bool {
+    let path = &crate::root::resolve(path);
+    if path.exists() {
+        return true;
+    }
+    path.is_symlink()
+        && std::fs::read_link(path)
+            .map(|target| target.exists())
+            .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn temp_dir(test_name: &str) -> PathBuf {
+        let nonce = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let path = std::env::temp_dir().join(format!(
+            "sysd-unit-cache-{}-{}-{}",
+            test_name,
+            std::process::id(),
+            nonce
+        ));
+        fs::create_dir(&path).unwrap();
+        path
+    }
+
+    fn set_mtime(path: &Path, secs: u64) {
+        let file = fs::File::open(path).unwrap();
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn parsed_file_is_reused_until_it_changes() {
+        let dir = temp_dir("parsed");
+        let path = dir.join("demo.service");
+        fs::write(&path, "[Service]\nExecStart=/bin/a\n").unwrap();
+        set_mtime(&path, 1_000);
+        parse_unit_file_cached(&path).await.unwrap();
+
+        // Same size and mtime: the cached parse is returned
+        fs::write(&path, "[Service]\nExecStart=/bin/b\n").unwrap();
+        set_mtime(&path, 1_000);
+        let parsed = parse_unit_file_cached(&path).await.unwrap();
+        assert_eq!(parsed["[Service]"]["EXECSTART"][0].1, "/bin/a");
+
+        set_mtime(&path, 2_000);
+        let parsed = parse_unit_file_cached(&path).await.unwrap();
+        assert_eq!(parsed["[Service]"]["EXECSTART"][0].1, "/bin/b");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[tokio::test]
+    async fn removed_files_leave_the_cache_on_prune() {
+        let dir = temp_dir("prune");
+        let path = dir.join("gone.service");
+        fs::write(&path, "[Service]\nExecStart=/bin/a\n").unwrap();
+        parse_unit_file_cached(&path).await.unwrap();
+        list_directory(&dir).unwrap();
+        assert!(parsed_files().lock().unwrap().contains_key(&path));
+
+        fs::remove_dir_all(&dir).unwrap();
+        prune_unit_file_cache().await;
+        assert!(!parsed_files().lock().unwrap().contains_key(&path));
+        assert!(!directories().lock().unwrap().contains_key(&dir));
+    }
+
+    #[test]
+    fn unit_index_prefers_earlier_directories_and_sees_new_files() {
+        let first = temp_dir("index-first");
+        let second = temp_dir("index-second");
+        fs::write(second.join("a.service"), "").unwrap();
+        fs::write(second.join("b.service"), "").unwrap();
+        let dirs = vec![first.clone(), second.clone()];
+
+        assert_eq!(
+            find_unit_file(&dirs, "a.service"),
+            Some(second.join("a.service"))
+        );
+        assert_eq!(find_unit_file(&dirs, "c.service"), None);
+
+        fs::write(first.join("a.service"), "").unwrap();
+        // Directory mtime resolution can be coarse; force a visible change
+        set_mtime(&first, 5_000);
+        assert_eq!(
+            find_unit_file(&dirs, "a.service"),
+            Some(first.join("a.service"))
+        );
+        assert_eq!(
+            find_unit_file(&dirs, "b.service"),
+            Some(second.join("b.service"))
+        );
+        let _ = fs::remove_dir_all(first);
+        let _ = fs::remove_dir_all(second);
+    }
+
+    #[test]
+    fn alias_symlinks_are_indexed_by_the_file_they_point_at() {
+        let dir = temp_dir("aliases");
+        fs::write(dir.join("real.service"), "").unwrap();
+        std::os::unix::fs::symlink("real.service", dir.join("nick.service")).unwrap();
+        std::os::unix::fs::symlink("nick.service", dir.join("alt.service")).unwrap();
+        std::os::unix::fs::symlink("/dev/null", dir.join("masked.service")).unwrap();
+        let dirs = vec![dir.clone()];
+
+        assert_eq!(
+            unit_file_aliases(&dirs, "real.service"),
+            ["alt.service", "nick.service"]
+        );
+        assert!(unit_file_aliases(&dirs, "nick.service").is_empty());
+        assert!(unit_file_aliases(&dirs, "null").is_empty());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
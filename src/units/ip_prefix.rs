@@ -0,0 +1,96 @@
+//! Address prefixes of IPAddressAllow=/IPAddressDeny=
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// An address with a prefix length, e.g. 10.0.0.0/8
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpPrefix {
+    pub address: IpAddr,
+    pub prefix_len: u8,
+}
+
+impl IpPrefix {
+    pub fn new(address: IpAddr, prefix_len: u8) -> Self {
+        Self {
+            address,
+            prefix_len,
+        }
+    }
+
+    /// ADDRESS[/PREFIXLEN]; an address without a length is a single host
+    pub fn parse(s: &str) -> Option<Self> {
+        let (address, prefix_len) = match s.split_once('/') {
+            Some((address, len)) => (address.parse::<IpAddr>().ok()?, Some(len.parse().ok()?)),
+            None => (s.parse::<IpAddr>().ok()?, None),
+        };
+        let max_len = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = prefix_len.unwrap_or(max_len);
+        (prefix_len <= max_len).then(|| Self::new(address, prefix_len))
+    }
+
+    /// Prefixes of one IPAddressAllow=/IPAddressDeny= word: a prefix, or one
+    /// of the names any, localhost, link-local and multicast, which cover
+    /// both address families
+    pub fn parse_named(s: &str) -> Option<Vec<Self>> {
+        let v4 = |a, b, c, d, len| Self::new(IpAddr::V4(Ipv4Addr::new(a, b, c, d)), len);
+        let v6 = |first, last, len| {
+            Self::new(
+                IpAddr::V6(Ipv6Addr::new(first, 0, 0, 0, 0, 0, 0, last)),
+                len,
+            )
+        };
+        let prefixes = match s {
+            "any" => vec![v4(0, 0, 0, 0, 0), v6(0, 0, 0)],
+            "localhost" => vec![v4(127, 0, 0, 0, 8), v6(0, 1, 128)],
+            "link-local" => vec![v4(169, 254, 0, 0, 16), v6(0xfe80, 0, 64)],
+            "multicast" => vec![v4(224, 0, 0, 0, 4), v6(0xff00, 0, 8)],
+            _ => vec![Self::parse(s)?],
+        };
+        Some(prefixes)
+    }
+
+    /// Address bytes in network order
+    pub fn octets(&self) -> Vec<u8> {
+        match self.address {
+            IpAddr::V4(address) => address.octets().to_vec(),
+            IpAddr::V6(address) => address.octets().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes_and_names_parse_into_both_families() {
+        assert_eq!(
+            IpPrefix::parse("10.0.0.0/8"),
+            Some(IpPrefix::new("10.0.0.0".parse().unwrap(), 8))
+        );
+        assert_eq!(
+            IpPrefix::parse("192.168.1.7"),
+            Some(IpPrefix::new("192.168.1.7".parse().unwrap(), 32))
+        );
+        assert_eq!(
+            IpPrefix::parse("fd00::/8"),
+            Some(IpPrefix::new("fd00::".parse().unwrap(), 8))
+        );
+        assert_eq!(IpPrefix::parse("10.0.0.0/33"), None);
+        assert_eq!(IpPrefix::parse("example.com"), None);
+
+        assert_eq!(
+            IpPrefix::parse_named("localhost"),
+            Some(vec![
+                IpPrefix::new("127.0.0.0".parse().unwrap(), 8),
+                IpPrefix::new("::1".parse().unwrap(), 128),
+            ])
+        );
+        let any = IpPrefix::parse_named("any").unwrap();
+        assert!(any.iter().all(|prefix| prefix.prefix_len == 0));
+        assert_eq!(IpPrefix::parse_named("nowhere"), None);
+    }
+}
@@ -0,0 +1,125 @@
+//! Power key, lid switch and VT handling from /etc/sysd/logind.conf
+//!
+//! Uses the `[Login]` keys of logind.conf (HandlePowerKey=, HandleLidSwitch=,
+//! NAutoVTs=, ReserveVT=). The file is opt-in: without it sysd leaves these
+//! events to systemd-logind.
+
+use std::path::Path;
+
+use super::{parse_file, parse_login_config};
+
+pub const LOGIN_CONFIG_PATH: &str = "/etc/sysd/logind.conf";
+
+/// Action for a power key press or lid close (Handle*= values)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleAction {
+    Ignore,
+    Poweroff,
+    Reboot,
+    Halt,
+    Suspend,
+    Hibernate,
+    HybridSleep,
+}
+
+impl HandleAction {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "ignore" => Some(Self::Ignore),
+            "poweroff" => Some(Self::Poweroff),
+            "reboot" => Some(Self::Reboot),
+            "halt" => Some(Self::Halt),
+            "suspend" => Some(Self::Suspend),
+            "hibernate" => Some(Self::Hibernate),
+            "hybrid-sleep" => Some(Self::HybridSleep),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed `[Login]` settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoginConfig {
+    pub handle_power_key: HandleAction,
+    pub handle_lid_switch: HandleAction,
+    /// VTs 1..=N get a getty when switched to
+    pub n_auto_vts: u32,
+    /// VT that always gets a getty, started at boot (0 = none)
+    pub reserve_vt: u32,
+}
+
+impl Default for LoginConfig {
+    /// logind's defaults
+    fn default() -> Self {
+        Self {
+            handle_power_key: HandleAction::Poweroff,
+            handle_lid_switch: HandleAction::Suspend,
+            n_auto_vts: 6,
+            reserve_vt: 6,
+        }
+    }
+}
+
+impl LoginConfig {
+    /// Whether switching to `vt` should spawn a getty on it
+    pub fn autovt_allowed(&self, vt: u32) -> bool {
+        vt >= 1 && (vt <= self.n_auto_vts || vt == self.reserve_vt)
+    }
+
+    /// Read the config file at `path`; None if it does not exist, in which
+    /// case sysd does not handle input events at all
+    pub fn load(path: &Path) -> Option<Self> {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        match parse_file(&content) {
+            Ok(parsed) => Some(parse_login_config(&parsed)),
+            Err(e) => {
+                log::warn!("Failed to parse {}: {}", path.display(), e);
+                Some(Self::default())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn actions_parse_logind_names() {
+        assert_eq!(
+            HandleAction::parse("hybrid-sleep"),
+            Some(HandleAction::HybridSleep)
+        );
+        assert_eq!(HandleAction::parse("ignore"), Some(HandleAction::Ignore));
+        assert_eq!(HandleAction::parse("lock"), None);
+    }
+
+    #[test]
+    fn autovt_covers_auto_vts_and_the_reserved_vt() {
+        let config = LoginConfig {
+            n_auto_vts: 2,
+            reserve_vt: 8,
+            ..LoginConfig::default()
+        };
+        assert!(!config.autovt_allowed(0));
+        assert!(config.autovt_allowed(1));
+        assert!(config.autovt_allowed(2));
+        assert!(!config.autovt_allowed(3));
+        assert!(config.autovt_allowed(8));
+    }
+
+    #[test]
+    fn missing_config_file_disables_input_handling() {
+        assert_eq!(
+            LoginConfig::load(Path::new("/nonexistent/sysd/logind.conf")),
+            None
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Manager-wide defaults from system.conf / user.conf
+//!
+//! The `[Manager]` section of /etc/sysd/system.conf (or user.conf for the
+//! user manager) supplies Default*= values that apply to every unit which
+//! leaves the corresponding setting unset.
+
+use std::path::Path;
+use std::time::Duration;
+
+use super::{parse_file, parse_manager_config, Unit};
+
+pub const SYSTEM_CONFIG_PATH: &str = "/etc/sysd/system.conf";
+pub const USER_CONFIG_PATH: &str = "/etc/sysd/user.conf";
+
+/// What to do with a unit file (or drop-in) that someone other than root or
+/// the manager's own user could have written (UnitFilePermissions=)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnitFilePermissions {
+    /// Do not load the unit
+    #[default]
+    Refuse,
+    /// Load it, logging a warning
+    Warn,
+    /// Do not check
+    Ignore,
+}
+
+impl UnitFilePermissions {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "refuse" => Some(Self::Refuse),
+            "warn" => Some(Self::Warn),
+            "ignore" => Some(Self::Ignore),
+            _ => None,
+        }
+    }
+}
+
+/// Parsed `[Manager]` defaults
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ManagerConfig {
+    pub default_timeout_start_sec: Option<Duration>,
+    pub default_restart_sec: Option<Duration>,
+    pub default_memory_accounting: bool,
+    pub default_cpu_accounting: bool,
+    pub default_tasks_max: Option<u32>,
+    pub default_environment: Vec<(String, String)>,
+    pub default_limit_nofile: Option<u64>,
+    pub unit_file_permissions: UnitFilePermissions,
+    /// Window for TriggerLimitBurst= of all socket, path and timer
+    /// activations together
+    pub trigger_limit_interval_sec: Option<Duration>,
+    pub trigger_limit_burst: Option<u32>,
+    /// Slice of units without Slice= (system.slice, or app.slice for the
+    /// user manager, when unset)
+    pub default_slice: Option<String>,
+    /// Keep unit state histories across restarts and reboots
+    /// (PersistStateHistory=)
+    pub persist_state_history: bool,
+}
+
+impl ManagerConfig {
+    /// Read the config file at `path`. A missing file means built-in
+    /// defaults; an unreadable or malformed one is logged and ignored.
+    pub fn load(path: &Path) -> Self {
+        let content = match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+            Err(e) => {
+                log::warn!("Failed to read {}: {}", path.display(), e);
+                return Self::default();
+            }
+        };
+        match parse_file(&content) {
+            Ok(parsed) => parse_manager_config(&parsed),
+            Err(e) => {
+                log::warn!("Failed to parse {}: {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Fill settings `unit` leaves unset with the manager defaults
+    pub fn apply_to(&self, unit: &mut Unit) {
+        let Unit::Service(service) = unit else {
+            return;
+        };
+        let section = &mut service.service;
+        section.timeout_start_sec = section.timeout_start_sec.or(self.default_timeout_start_sec);
+        section.restart_sec = section.restart_sec.or(self.default_restart_sec);
+        section.tasks_max = section.tasks_max.or(self.default_tasks_max);
+        section.limit_nofile = section.limit_nofile.or(self.default_limit_nofile);
+        section.memory_accounting = section
+            .memory_accounting
+            .or(Some(self.default_memory_accounting));
+        section.cpu_accounting = section.cpu_accounting.or(Some(self.default_cpu_accounting));
+
+        // Environment= entries of the unit override DefaultEnvironment=
+        let defaults = self
+            .default_environment
+            .iter()
+            .filter(|(key, _)| !section.environment.iter().any(|(k, _)| k == key))
+            .cloned();
+        section.environment = defaults.chain(section.environment.drain(..)).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Service;
+
+    fn config() -> ManagerConfig {
+        ManagerConfig {
+            default_restart_sec: Some(Duration::from_secs(2)),
+            default_memory_accounting: true,
+            default_tasks_max: Some(512),
+            default_environment: vec![
+                ("LANG".to_string(), "C.UTF-8".to_string()),
+                ("PATH".to_string(), "/usr/bin".to_string()),
+            ],
+            default_limit_nofile: Some(4096),
+            ..ManagerConfig::default()
+        }
+    }
+
+    #[test]
+    fn defaults_fill_only_unset_service_settings() {
+        let mut service = Service::new("web.service".to_string());
+        service.service.tasks_max = Some(16);
+        service.service.memory_accounting = Some(false);
+        service.service.environment = vec![("PATH".to_string(), "/opt/bin".to_string())];
+        let mut unit = Unit::Service(service);
+
+        config().apply_to(&mut unit);
+
+        let section = &unit.as_service().unwrap().service;
+        assert_eq!(section.restart_sec, Some(Duration::from_secs(2)));
+        assert_eq!(section.tasks_max, Some(16));
+        assert_eq!(section.limit_nofile, Some(4096));
+        assert_eq!(section.memory_accounting, Some(false));
+        assert_eq!(section.cpu_accounting, Some(false));
+        assert_eq!(section.timeout_start_sec, None);
+        assert_eq!(
+            section.environment,
+            [
+                ("LANG".to_string(), "C.UTF-8".to_string()),
+                ("PATH".to_string(), "/opt/bin".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_config_file_means_builtin_defaults() {
+        let config = ManagerConfig::load(Path::new("/nonexistent/sysd/system.conf"));
+        assert_eq!(config, ManagerConfig::default());
+    }
+}
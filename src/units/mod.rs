@@ -2,24 +2,51 @@
 //!
 //! Parses systemd .service, .target, and .mount files into typed Rust structures.
 
+mod builtin;
+mod cache;
+mod ip_prefix;
+mod login_config;
+mod manager_config;
 mod mount;
 mod parse_units;
 mod parser;
 mod path;
+mod path_glob;
+mod preset;
+mod security;
 mod service;
 mod slice;
 mod socket;
+mod socket_bind;
 mod target;
 mod timer;
+mod tmpfiles;
 mod unit;
+mod unit_pattern;
 
+pub use builtin::{builtin_unit, BUILTIN_DEFAULT_TARGET};
+pub use cache::{
+    find_unit_file, invalidate_unit_index, list_directory, parse_unit_file_cached,
+    prune_unit_file_cache, unit_file_aliases,
+};
+pub use ip_prefix::IpPrefix;
+pub use login_config::{HandleAction, LoginConfig, LOGIN_CONFIG_PATH};
+pub use manager_config::{
+    ManagerConfig, UnitFilePermissions, SYSTEM_CONFIG_PATH, USER_CONFIG_PATH,
+};
 pub use mount::{Mount, MountSection};
 pub use parse_units::*;
-pub use parser::{parse_file, parse_unit_file, ParseError, ParsedFile};
+pub use parser::{parse_bytes, parse_file, parse_unit_file, ParseError, ParsedFile};
 pub use path::{Path as PathUnit, PathSection};
+pub use path_glob::{expand_path_glob, glob_base_dir, has_glob_chars, path_glob_matches_any};
+pub use preset::{PresetAction, Presets, SYSTEM_PRESET_DIRS, USER_PRESET_DIRS};
+pub use security::{analyze_security, SecurityCheck, SecurityReport};
 pub use service::*;
 pub use slice::Slice;
-pub use socket::{ListenType, Listener, Socket, SocketSection};
+pub use socket::{BindIpv6Only, ListenType, Listener, Socket, SocketProtocol, SocketSection};
+pub use socket_bind::{BindFamily, BindProtocol, SocketBindRule};
 pub use target::Target;
 pub use timer::{CalendarSpec, Timer, TimerSection};
+pub use tmpfiles::{AgeRule, AgeRules, TMPFILES_DIRS};
 pub use unit::Unit;
+pub use unit_pattern::{expand_unit_braces, unit_name_matches};
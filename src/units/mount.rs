@@ -48,6 +48,37 @@ impl Default for MountSection {
     }
 }
 
+/// Filesystem types that need the network to be up
+const NETWORK_FS_TYPES: [&str; 9] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "ncpfs",
+    "glusterfs",
+    "ceph",
+    "fuse.sshfs",
+];
+
+/// Mount unit names for `path` and every directory above it, root first
+fn mount_units_for(path: &str) -> Vec<String> {
+    let mut units = vec![Mount::name_from_mount_point("/")];
+    let mut prefix = String::new();
+    for component in path.split('/').filter(|c| !c.is_empty()) {
+        prefix.push('/');
+        prefix.push_str(component);
+        units.push(Mount::name_from_mount_point(&prefix));
+    }
+    units
+}
+
+fn push_unique(list: &mut Vec<String>, name: &str) {
+    if !list.iter().any(|existing| existing == name) {
+        list.push(name.to_string());
+    }
+}
+
 /// Complete parsed mount unit
 #[derive(Debug, Clone)]
 pub struct Mount {
@@ -67,6 +98,53 @@ impl Mount {
         }
     }
 
+    fn has_option(&self, option: &str) -> bool {
+        self.mount
+            .options
+            .as_deref()
+            .is_some_and(|options| options.split(',').any(|o| o.trim() == option))
+    }
+
+    /// Network filesystem by type, or marked with the _netdev option
+    pub fn is_network(&self) -> bool {
+        self.mount
+            .fs_type
+            .as_deref()
+            .is_some_and(|t| NETWORK_FS_TYPES.contains(&t))
+            || self.has_option("_netdev")
+    }
+
+    /// Mounts whose What= is a path on another mount (bind and loop mounts)
+    pub fn backing_path(&self) -> Option<&str> {
+        let what = self.mount.what.as_str();
+        let path_backed =
+            self.has_option("bind") || self.has_option("rbind") || self.has_option("loop");
+        (path_backed && what.starts_with('/')).then_some(what)
+    }
+
+    /// Add the ordering systemd derives from the mount itself: After= the
+    /// mounts above the mount point, After= the mounts holding a bind/loop
+    /// source, and network ordering for remote filesystems
+    pub fn add_implicit_dependencies(&mut self) {
+        let own_name = self.name.clone();
+        let mut after = mount_units_for(&self.mount.r#where);
+        // The mount point itself is this unit
+        after.pop();
+        if let Some(backing) = self.backing_path() {
+            after.extend(mount_units_for(backing));
+        }
+        for name in after.iter().filter(|name| **name != own_name) {
+            push_unique(&mut self.unit.after, name);
+        }
+
+        if self.is_network() {
+            push_unique(&mut self.unit.after, "remote-fs-pre.target");
+            push_unique(&mut self.unit.after, "network-online.target");
+            push_unique(&mut self.unit.wants, "network-online.target");
+            push_unique(&mut self.unit.before, "remote-fs.target");
+        }
+    }
+
     /// Get the mount point path from the unit name
     /// e.g., "dev-hugepages.mount" → "/dev/hugepages"
     pub fn mount_point_from_name(name: &str) -> String {
@@ -154,6 +232,93 @@ mod tests {
         );
     }
 
+    fn mount_with(name: &str, section: MountSection) -> Mount {
+        let mut mount = Mount::new(name.to_string());
+        mount.mount = section;
+        mount.add_implicit_dependencies();
+        mount
+    }
+
+    #[test]
+    fn implicit_dependencies_order_after_parent_mounts() {
+        let mount = mount_with(
+            "var-lib-data.mount",
+            MountSection {
+                what: "/dev/sdb1".to_string(),
+                r#where: "/var/lib/data".to_string(),
+                ..MountSection::default()
+            },
+        );
+        assert_eq!(mount.unit.after, ["-.mount", "var.mount", "var-lib.mount"]);
+        assert!(mount.unit.wants.is_empty());
+
+        let root = mount_with(
+            "-.mount",
+            MountSection {
+                r#where: "/".to_string(),
+                ..MountSection::default()
+            },
+        );
+        assert!(root.unit.after.is_empty());
+    }
+
+    #[test]
+    fn implicit_dependencies_for_network_and_backed_mounts() {
+        let nfs = mount_with(
+            "mnt-nfs.mount",
+            MountSection {
+                what: "server:/export".to_string(),
+                r#where: "/mnt/nfs".to_string(),
+                fs_type: Some("nfs".to_string()),
+                ..MountSection::default()
+            },
+        );
+        assert!(nfs.is_network());
+        assert!(nfs
+            .unit
+            .after
+            .contains(&"network-online.target".to_string()));
+        assert!(nfs.unit.after.contains(&"remote-fs-pre.target".to_string()));
+        assert_eq!(nfs.unit.wants, ["network-online.target"]);
+        assert_eq!(nfs.unit.before, ["remote-fs.target"]);
+
+        let netdev = mount_with(
+            "srv-iscsi.mount",
+            MountSection {
+                what: "/dev/sdc1".to_string(),
+                r#where: "/srv/iscsi".to_string(),
+                options: Some("noatime,_netdev".to_string()),
+                ..MountSection::default()
+            },
+        );
+        assert!(netdev.is_network());
+
+        let image = mount_with(
+            "mnt-image.mount",
+            MountSection {
+                what: "/data/images/disk.img".to_string(),
+                r#where: "/mnt/image".to_string(),
+                options: Some("loop,ro".to_string()),
+                ..MountSection::default()
+            },
+        );
+        assert_eq!(image.backing_path(), Some("/data/images/disk.img"));
+        assert!(image.unit.after.contains(&"data-images.mount".to_string()));
+        assert!(!image.is_network());
+
+        let tmpfs = mount_with(
+            "tmp.mount",
+            MountSection {
+                what: "tmpfs".to_string(),
+                r#where: "/tmp".to_string(),
+                fs_type: Some("tmpfs".to_string()),
+                ..MountSection::default()
+            },
+        );
+        assert_eq!(tmpfs.backing_path(), None);
+        assert_eq!(tmpfs.unit.after, ["-.mount"]);
+    }
+
     #[test]
     fn test_mount_default() {
         let mount = Mount::new("test.mount".to_string());
@@ -1,6 +1,7 @@
 //! Unit parsing implementation extracted from mod.rs.
 
 use super::*;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
 struct SectionView<'a> {
@@ -15,18 +16,23 @@ impl<'a> SectionView<'a> {
     }
 
     fn has(&self, key: &str) -> bool {
-        self.section
-            .is_some_and(|section| section.contains_key(key))
+        self.values(key).is_some_and(|values| !values.is_empty())
     }
 
+    /// Values of `key`, without the marker an empty assignment leaves in front
     fn values(&self, key: &str) -> Option<&'a [(u32, String)]> {
-        self.section
-            .and_then(|section| section.get(key).map(Vec::as_slice))
+        let values = self.section.and_then(|section| section.get(key))?;
+        match values.split_first() {
+            Some(((_, first), rest)) if first.is_empty() => Some(rest),
+            _ => Some(values),
+        }
     }
 
-    fn first(&self, key: &str) -> Option<&'a str> {
+    /// Value of a single-valued setting: the last assignment wins, so later
+    /// lines and drop-ins override earlier ones
+    fn last(&self, key: &str) -> Option<&'a str> {
         self.values(key)
-            .and_then(|values| values.first().map(|(_, value)| value.as_str()))
+            .and_then(|values| values.last().map(|(_, value)| value.as_str()))
     }
 
     fn strings(&self, key: &str) -> Vec<String> {
@@ -46,23 +52,23 @@ impl<'a> SectionView<'a> {
             .unwrap_or_default()
     }
 
-    fn first_string(&self, key: &str) -> Option<String> {
-        self.first(key).map(String::from)
+    fn last_string(&self, key: &str) -> Option<String> {
+        self.last(key).map(String::from)
     }
 
-    fn first_pathbuf(&self, key: &str) -> Option<PathBuf> {
-        self.first(key).map(PathBuf::from)
+    fn last_pathbuf(&self, key: &str) -> Option<PathBuf> {
+        self.last(key).map(PathBuf::from)
     }
 
-    fn first_bool(&self, key: &str) -> Option<bool> {
-        self.first(key).map(parse_yes_no)
+    fn last_bool(&self, key: &str) -> Option<bool> {
+        self.last(key).map(parse_yes_no)
     }
 
-    fn first_parsed<T, F>(&self, key: &str, parse: F) -> Option<T>
+    fn last_parsed<T, F>(&self, key: &str, parse: F) -> Option<T>
     where
         F: Fn(&str) -> Option<T>,
     {
-        self.first(key).and_then(parse)
+        self.last(key).and_then(parse)
     }
 
     fn parsed_or_default<T, F>(&self, key: &str, parse: F) -> T
@@ -70,7 +76,7 @@ impl<'a> SectionView<'a> {
         T: Default,
         F: Fn(&str) -> Option<T>,
     {
-        self.first_parsed(key, parse).unwrap_or_default()
+        self.last_parsed(key, parse).unwrap_or_default()
     }
 }
 
@@ -104,33 +110,87 @@ fn parse_restrict_namespaces(value: &str) -> Option<Vec<String>> {
     Some(value.split_whitespace().map(String::from).collect())
 }
 
+/// SecureBits= names and the SECBIT_* flag each sets
+const SECURE_BITS: [(&str, u32); 6] = [
+    ("noroot", 1 << 0),
+    ("noroot-locked", 1 << 1),
+    ("no-setuid-fixup", 1 << 2),
+    ("no-setuid-fixup-locked", 1 << 3),
+    ("keep-caps", 1 << 4),
+    ("keep-caps-locked", 1 << 5),
+];
+
+fn parse_secure_bits(names: &[String]) -> u32 {
+    names.iter().fold(0, |bits, name| {
+        match SECURE_BITS.iter().find(|(known, _)| known == name) {
+            Some((_, bit)) => bits | bit,
+            None => {
+                log::warn!("Ignoring unknown SecureBits= flag: {}", name);
+                bits
+            }
+        }
+    })
+}
+
+fn parse_ip_prefixes(words: &[String]) -> Vec<IpPrefix> {
+    words
+        .iter()
+        .flat_map(|word| {
+            IpPrefix::parse_named(word).unwrap_or_else(|| {
+                log::warn!("Ignoring invalid IP address prefix: {}", word);
+                Vec::new()
+            })
+        })
+        .collect()
+}
+
+fn parse_socket_bind_rules(words: &[String]) -> Vec<SocketBindRule> {
+    words
+        .iter()
+        .filter_map(|word| {
+            let rule = SocketBindRule::parse(word);
+            if rule.is_none() {
+                log::warn!("Ignoring invalid socket bind rule: {}", word);
+            }
+            rule
+        })
+        .collect()
+}
+
 fn apply_unit_core(unit: &mut UnitSection, view: &SectionView<'_>) {
-    unit.description = view.first_string("DESCRIPTION");
+    unit.description = view.last_string("DESCRIPTION");
+    unit.documentation = view.words("DOCUMENTATION");
     unit.after = view.strings("AFTER");
     unit.before = view.strings("BEFORE");
     unit.requires = view.strings("REQUIRES");
     unit.wants = view.strings("WANTS");
+    unit.requisite = view.strings("REQUISITE");
     unit.conflicts = view.strings("CONFLICTS");
     unit.default_dependencies = view
-        .first_bool("DEFAULTDEPENDENCIES")
+        .last_bool("DEFAULTDEPENDENCIES")
         .unwrap_or(unit.default_dependencies);
+    unit.survive_final_kill_signal = view
+        .last_bool("SURVIVEFINALKILLSIGNAL")
+        .unwrap_or(unit.survive_final_kill_signal);
 }
 
 fn apply_unit_conditions(unit: &mut UnitSection, view: &SectionView<'_>) {
     unit.condition_path_exists = view.strings("CONDITIONPATHEXISTS");
+    unit.condition_path_exists_glob = view.strings("CONDITIONPATHEXISTSGLOB");
     unit.condition_directory_not_empty = view.strings("CONDITIONDIRECTORYNOTEMPTY");
     unit.condition_virtualization = view.strings("CONDITIONVIRTUALIZATION");
     unit.condition_capability = view.strings("CONDITIONCAPABILITY");
     unit.condition_kernel_command_line = view.strings("CONDITIONKERNELCOMMANDLINE");
     unit.condition_security = view.strings("CONDITIONSECURITY");
-    unit.condition_first_boot = view.first_bool("CONDITIONFIRSTBOOT");
+    unit.condition_first_boot = view.last_bool("CONDITIONFIRSTBOOT");
     unit.condition_needs_update = view.strings("CONDITIONNEEDSUPDATE");
 }
 
 fn apply_unit_service_extras(unit: &mut UnitSection, view: &SectionView<'_>) {
     unit.binds_to = view.strings("BINDSTO");
+    unit.part_of = view.strings("PARTOF");
     unit.ignore_on_isolate = view
-        .first_bool("IGNOREONISOLATE")
+        .last_bool("IGNOREONISOLATE")
         .unwrap_or(unit.ignore_on_isolate);
 }
 
@@ -143,7 +203,7 @@ fn apply_install_extended(install: &mut InstallSection, view: &SectionView<'_>)
     apply_install_core(install, view);
     install.also = view.strings("ALSO");
     install.alias = view.strings("ALIAS");
-    install.default_instance = view.first_string("DEFAULTINSTANCE");
+    install.default_instance = view.last_string("DEFAULTINSTANCE");
 }
 
 fn apply_install_without_default_instance(install: &mut InstallSection, view: &SectionView<'_>) {
@@ -160,25 +220,33 @@ fn apply_service_exec_and_restart(service: &mut ServiceSection, view: &SectionVi
     service.exec_stop = view.strings("EXECSTOP");
     service.exec_reload = view.strings("EXECRELOAD");
     service.restart = view.parsed_or_default("RESTART", RestartPolicy::parse);
-    service.restart_sec = view
-        .first_parsed("RESTARTSEC", parse_duration)
-        .unwrap_or(service.restart_sec);
-    service.timeout_start_sec = view.first_parsed("TIMEOUTSTARTSEC", parse_duration);
-    service.timeout_stop_sec = view.first_parsed("TIMEOUTSTOPSEC", parse_duration);
+    service.restart_sec = view.last_parsed("RESTARTSEC", parse_duration);
+    service.timeout_start_sec = view.last_parsed("TIMEOUTSTARTSEC", parse_duration);
+    service.timeout_stop_sec = view.last_parsed("TIMEOUTSTOPSEC", parse_duration);
+    service.timeout_abort_sec = view.last_parsed("TIMEOUTABORTSEC", parse_duration);
     service.remain_after_exit = view
-        .first_bool("REMAINAFTEREXIT")
+        .last_bool("REMAINAFTEREXIT")
         .unwrap_or(service.remain_after_exit);
+    service.rolling_restart = view.last_bool("ROLLINGRESTART").unwrap_or(false);
 }
 
 fn apply_service_identity(service: &mut ServiceSection, view: &SectionView<'_>) {
-    service.watchdog_sec = view.first_parsed("WATCHDOGSEC", parse_duration);
+    service.watchdog_sec = view.last_parsed("WATCHDOGSEC", parse_duration);
+    service.exec_health_check = view.last_parsed("EXECHEALTHCHECK", HealthCheck::parse);
+    service.health_check_interval_sec = view.last_parsed("HEALTHCHECKINTERVALSEC", parse_duration);
+    service.health_check_retries = view.last_parsed("HEALTHCHECKRETRIES", |raw| {
+        raw.parse().ok().filter(|&n| n > 0)
+    });
+    service.wait_for_endpoint = view.last_parsed("WAITFORENDPOINT", |raw| {
+        HealthCheck::parse(raw).filter(|check| !matches!(check, HealthCheck::Exec(_)))
+    });
     service.notify_access = view.parsed_or_default("NOTIFYACCESS", NotifyAccess::parse);
-    service.pid_file = view.first_pathbuf("PIDFILE");
-    service.bus_name = view.first_string("BUSNAME");
+    service.pid_file = view.last_pathbuf("PIDFILE");
+    service.bus_name = view.last_string("BUSNAME");
     service.kill_mode = view.parsed_or_default("KILLMODE", KillMode::parse);
-    service.user = view.first_string("USER");
-    service.group = view.first_string("GROUP");
-    service.working_directory = view.first_pathbuf("WORKINGDIRECTORY");
+    service.user = view.last_string("USER");
+    service.group = view.last_string("GROUP");
+    service.working_directory = view.last_pathbuf("WORKINGDIRECTORY");
 }
 
 fn apply_service_environment(service: &mut ServiceSection, view: &SectionView<'_>) {
@@ -198,23 +266,69 @@ fn apply_service_environment(service: &mut ServiceSection, view: &SectionView<'_
         .map(PathBuf::from)
         .collect();
     service.unset_environment = view.words("UNSETENVIRONMENT");
+    service.pass_environment = view.words("PASSENVIRONMENT");
+}
+
+fn apply_service_credentials(service: &mut ServiceSection, view: &SectionView<'_>) {
+    service.load_credential = view
+        .strings("LOADCREDENTIAL")
+        .iter()
+        .filter_map(|value| parse_credential_entry(value, false))
+        .collect();
+    service.load_credential_encrypted = view
+        .strings("LOADCREDENTIALENCRYPTED")
+        .iter()
+        .filter_map(|value| parse_credential_entry(value, false))
+        .collect();
+    service.set_credential = view
+        .strings("SETCREDENTIAL")
+        .iter()
+        .filter_map(|value| parse_credential_entry(value, true))
+        .collect();
+    service.import_credential = view.words("IMPORTCREDENTIAL");
+}
+
+/// Split ID[:REST] of LoadCredential=/SetCredential=; the ID must be usable
+/// as a file name. SetCredential= requires the colon.
+fn parse_credential_entry(value: &str, needs_value: bool) -> Option<(String, String)> {
+    let (id, rest) = match value.split_once(':') {
+        Some((id, rest)) => (id, rest),
+        None if needs_value => return None,
+        None => (value, ""),
+    };
+    let id = id.trim();
+    if id.is_empty() || id == "." || id == ".." || id.contains('/') {
+        log::warn!("Ignoring credential with invalid ID: {}", value);
+        return None;
+    }
+    Some((id.to_string(), rest.to_string()))
 }
 
 fn apply_service_stdio(service: &mut ServiceSection, view: &SectionView<'_>) {
     service.standard_output = view.parsed_or_default("STANDARDOUTPUT", StdOutput::parse);
     service.standard_error = view.parsed_or_default("STANDARDERROR", StdOutput::parse);
     service.standard_input = view.parsed_or_default("STANDARDINPUT", StdInput::parse);
-    service.tty_path = view.first_pathbuf("TTYPATH");
-    service.tty_reset = view.first_bool("TTYRESET").unwrap_or(service.tty_reset);
+    service.tty_path = view.last_pathbuf("TTYPATH");
+    service.tty_reset = view.last_bool("TTYRESET").unwrap_or(service.tty_reset);
+    service.tty_vhangup = view.last_bool("TTYVHANGUP").unwrap_or(service.tty_vhangup);
+    service.tty_vt_disallocate = view
+        .last_bool("TTYVTDISALLOCATE")
+        .unwrap_or(service.tty_vt_disallocate);
 }
 
 fn apply_service_limits(service: &mut ServiceSection, view: &SectionView<'_>) {
-    service.memory_max = view.first_parsed("MEMORYMAX", parse_memory);
-    service.cpu_quota = view.first_parsed("CPUQUOTA", parse_cpu_quota);
-    service.tasks_max = view.first_parsed("TASKSMAX", |raw| raw.parse().ok());
-    service.limit_nofile = view.first_parsed("LIMITNOFILE", parse_limit);
-    service.limit_nproc = view.first_parsed("LIMITNPROC", parse_limit);
-    service.limit_core = view.first_parsed("LIMITCORE", parse_limit);
+    service.memory_max = view.last_parsed("MEMORYMAX", parse_memory);
+    service.cpu_quota = view.last_parsed("CPUQUOTA", parse_cpu_quota);
+    service.tasks_max = view.last_parsed("TASKSMAX", |raw| raw.parse().ok());
+    service.memory_accounting = view.last_bool("MEMORYACCOUNTING");
+    service.cpu_accounting = view.last_bool("CPUACCOUNTING");
+    service.ip_address_allow = parse_ip_prefixes(&view.words("IPADDRESSALLOW"));
+    service.ip_address_deny = parse_ip_prefixes(&view.words("IPADDRESSDENY"));
+    service.socket_bind_allow = parse_socket_bind_rules(&view.words("SOCKETBINDALLOW"));
+    service.socket_bind_deny = parse_socket_bind_rules(&view.words("SOCKETBINDDENY"));
+    service.limit_nofile = view.last_parsed("LIMITNOFILE", parse_limit);
+    service.limit_nproc = view.last_parsed("LIMITNPROC", parse_limit);
+    service.limit_core = view.last_parsed("LIMITCORE", parse_limit);
     service.state_directory = view.words("STATEDIRECTORY");
     service.runtime_directory = view.words("RUNTIMEDIRECTORY");
     service.configuration_directory = view.words("CONFIGURATIONDIRECTORY");
@@ -223,32 +337,45 @@ fn apply_service_limits(service: &mut ServiceSection, view: &SectionView<'_>) {
     service.runtime_directory_preserve =
         view.parsed_or_default("RUNTIMEDIRECTORYPRESERVE", RuntimeDirectoryPreserve::parse);
     service.dynamic_user = view
-        .first_bool("DYNAMICUSER")
+        .last_bool("DYNAMICUSER")
         .unwrap_or(service.dynamic_user);
+    service.remove_ipc = view.last_bool("REMOVEIPC").unwrap_or(service.remove_ipc);
 }
 
 fn apply_service_security_core(service: &mut ServiceSection, view: &SectionView<'_>) {
-    service.oom_score_adjust = view.first_parsed("OOMSCOREADJUST", |raw| raw.parse().ok());
+    service.oom_score_adjust = view.last_parsed("OOMSCOREADJUST", |raw| raw.parse().ok());
     service.no_new_privileges = view
-        .first_bool("NONEWPRIVILEGES")
+        .last_bool("NONEWPRIVILEGES")
         .unwrap_or(service.no_new_privileges);
     service.protect_system = view.parsed_or_default("PROTECTSYSTEM", ProtectSystem::parse);
     service.protect_home = view.parsed_or_default("PROTECTHOME", ProtectHome::parse);
-    service.private_tmp = view.first_bool("PRIVATETMP").unwrap_or(service.private_tmp);
+    service.private_tmp = view.last_bool("PRIVATETMP").unwrap_or(service.private_tmp);
     service.private_devices = view
-        .first_bool("PRIVATEDEVICES")
+        .last_bool("PRIVATEDEVICES")
         .unwrap_or(service.private_devices);
     service.private_network = view
-        .first_bool("PRIVATENETWORK")
+        .last_bool("PRIVATENETWORK")
         .unwrap_or(service.private_network);
+    service.private_pids = view
+        .last_bool("PRIVATEPIDS")
+        .unwrap_or(service.private_pids);
+    service.private_ipc = view.last_bool("PRIVATEIPC").unwrap_or(service.private_ipc);
+    service.network_namespace_path = view
+        .last_pathbuf("NETWORKNAMESPACEPATH")
+        .filter(|path| path.is_absolute());
     service.protect_kernel_modules = view
-        .first_bool("PROTECTKERNELMODULES")
+        .last_bool("PROTECTKERNELMODULES")
         .unwrap_or(service.protect_kernel_modules);
     service.protect_proc = view.parsed_or_default("PROTECTPROC", ProtectProc::parse);
+    service.selinux_context = view.last("SELINUXCONTEXT").and_then(MacLabel::parse);
+    service.apparmor_profile = view.last("APPARMORPROFILE").and_then(MacLabel::parse);
+    service.smack_process_label = view.last("SMACKPROCESSLABEL").and_then(MacLabel::parse);
     service.capability_bounding_set = view.words("CAPABILITYBOUNDINGSET");
     service.ambient_capabilities = view.words("AMBIENTCAPABILITIES");
+    service.secure_bits = parse_secure_bits(&view.words("SECUREBITS"));
+    service.keyring_mode = view.parsed_or_default("KEYRINGMODE", KeyringMode::parse);
     service.restrict_namespaces = view
-        .first("RESTRICTNAMESPACES")
+        .last("RESTRICTNAMESPACES")
         .and_then(parse_restrict_namespaces);
 }
 
@@ -275,34 +402,34 @@ fn apply_service_security_paths(service: &mut ServiceSection, view: &SectionView
 
 fn apply_service_security_extended(service: &mut ServiceSection, view: &SectionView<'_>) {
     service.restrict_realtime = view
-        .first_bool("RESTRICTREALTIME")
+        .last_bool("RESTRICTREALTIME")
         .unwrap_or(service.restrict_realtime);
     service.protect_control_groups = view
-        .first_bool("PROTECTCONTROLGROUPS")
+        .last_bool("PROTECTCONTROLGROUPS")
         .unwrap_or(service.protect_control_groups);
     service.memory_deny_write_execute = view
-        .first_bool("MEMORYDENYWRITEEXECUTE")
+        .last_bool("MEMORYDENYWRITEEXECUTE")
         .unwrap_or(service.memory_deny_write_execute);
     service.lock_personality = view
-        .first_bool("LOCKPERSONALITY")
+        .last_bool("LOCKPERSONALITY")
         .unwrap_or(service.lock_personality);
     service.protect_kernel_tunables = view
-        .first_bool("PROTECTKERNELTUNABLES")
+        .last_bool("PROTECTKERNELTUNABLES")
         .unwrap_or(service.protect_kernel_tunables);
     service.protect_kernel_logs = view
-        .first_bool("PROTECTKERNELLOGS")
+        .last_bool("PROTECTKERNELLOGS")
         .unwrap_or(service.protect_kernel_logs);
     service.protect_clock = view
-        .first_bool("PROTECTCLOCK")
+        .last_bool("PROTECTCLOCK")
         .unwrap_or(service.protect_clock);
     service.protect_hostname = view
-        .first_bool("PROTECTHOSTNAME")
+        .last_bool("PROTECTHOSTNAME")
         .unwrap_or(service.protect_hostname);
     service.ignore_sigpipe = view
-        .first_bool("IGNORESIGPIPE")
+        .last_bool("IGNORESIGPIPE")
         .unwrap_or(service.ignore_sigpipe);
     service.restrict_suid_sgid = view
-        .first_bool("RESTRICTSUIDSGID")
+        .last_bool("RESTRICTSUIDSGID")
         .unwrap_or(service.restrict_suid_sgid);
 }
 
@@ -310,18 +437,19 @@ fn apply_service_process_control(service: &mut ServiceSection, view: &SectionVie
     if view.has("RESTRICTADDRESSFAMILIES") {
         service.restrict_address_families = Some(view.words("RESTRICTADDRESSFAMILIES"));
     }
+    service.restrict_file_systems = view.words("RESTRICTFILESYSTEMS");
     service.system_call_error_number =
-        view.first_parsed("SYSTEMCALLERRORNUMBER", |raw| raw.parse().ok());
+        view.last_parsed("SYSTEMCALLERRORNUMBER", |raw| raw.parse().ok());
     service.system_call_architectures = view.words("SYSTEMCALLARCHITECTURES");
-    service.start_limit_burst = view.first_parsed("STARTLIMITBURST", |raw| raw.parse().ok());
-    service.start_limit_interval_sec = view.first_parsed("STARTLIMITINTERVALSEC", parse_duration);
+    service.start_limit_burst = view.last_parsed("STARTLIMITBURST", |raw| raw.parse().ok());
+    service.start_limit_interval_sec = view.last_parsed("STARTLIMITINTERVALSEC", parse_duration);
     service.sockets = view.words("SOCKETS");
-    service.send_sighup = view.first_bool("SENDSIGHUP").unwrap_or(service.send_sighup);
-    service.slice = view.first_string("SLICE");
-    service.delegate = view.first_bool("DELEGATE").unwrap_or(service.delegate);
+    service.send_sighup = view.last_bool("SENDSIGHUP").unwrap_or(service.send_sighup);
+    service.slice = view.last_string("SLICE");
+    service.delegate = view.last_bool("DELEGATE").unwrap_or(service.delegate);
     service.exec_stop_post = view.strings("EXECSTOPPOST");
     service.file_descriptor_store_max =
-        view.first_parsed("FILEDESCRIPTORSTOREMAX", |raw| raw.parse().ok());
+        view.last_parsed("FILEDESCRIPTORSTOREMAX", |raw| raw.parse().ok());
     service.restart_prevent_exit_status = view
         .words("RESTARTPREVENTEXITSTATUS")
         .into_iter()
@@ -330,22 +458,22 @@ fn apply_service_process_control(service: &mut ServiceSection, view: &SectionVie
 }
 
 fn apply_mount_section(mount: &mut MountSection, view: &SectionView<'_>) {
-    mount.what = view.first_string("WHAT").unwrap_or_default();
-    mount.r#where = view.first_string("WHERE").unwrap_or_default();
-    mount.fs_type = view.first_string("TYPE");
-    mount.options = view.first_string("OPTIONS");
+    mount.what = view.last_string("WHAT").unwrap_or_default();
+    mount.r#where = view.last_string("WHERE").unwrap_or_default();
+    mount.fs_type = view.last_string("TYPE");
+    mount.options = view.last_string("OPTIONS");
     mount.sloppy_options = view
-        .first_bool("SLOPPYOPTIONS")
+        .last_bool("SLOPPYOPTIONS")
         .unwrap_or(mount.sloppy_options);
-    mount.lazy_unmount = view.first_bool("LAZYUNMOUNT").unwrap_or(mount.lazy_unmount);
+    mount.lazy_unmount = view.last_bool("LAZYUNMOUNT").unwrap_or(mount.lazy_unmount);
     mount.force_unmount = view
-        .first_bool("FORCEUNMOUNT")
+        .last_bool("FORCEUNMOUNT")
         .unwrap_or(mount.force_unmount);
     mount.read_write_only = view
-        .first_bool("READWRITEONLY")
+        .last_bool("READWRITEONLY")
         .unwrap_or(mount.read_write_only);
-    mount.directory_mode = view.first_parsed("DIRECTORYMODE", parse_octal);
-    mount.timeout_sec = view.first_parsed("TIMEOUTSEC", parse_duration);
+    mount.directory_mode = view.last_parsed("DIRECTORYMODE", parse_octal);
+    mount.timeout_sec = view.last_parsed("TIMEOUTSEC", parse_duration);
 }
 
 fn apply_socket_listeners(socket: &mut SocketSection, view: &SectionView<'_>) {
@@ -380,33 +508,52 @@ fn apply_socket_listeners(socket: &mut SocketSection, view: &SectionView<'_>) {
                     listen_type: ListenType::Netlink,
                 }),
         )
+        .chain(
+            view.strings("LISTENSEQUENTIALPACKET")
+                .into_iter()
+                .map(|address| Listener {
+                    address,
+                    listen_type: ListenType::SequentialPacket,
+                }),
+        )
         .collect();
 }
 
 fn apply_socket_fields(socket: &mut SocketSection, view: &SectionView<'_>) {
-    socket.accept = view.first_bool("ACCEPT").unwrap_or(socket.accept);
-    socket.service = view.first_string("SERVICE");
-    socket.socket_mode = view.first_parsed("SOCKETMODE", parse_octal);
-    socket.socket_user = view.first_string("SOCKETUSER");
-    socket.socket_group = view.first_string("SOCKETGROUP");
-    socket.fd_name = view.first_string("FILEDESCRIPTORNAME");
+    socket.accept = view.last_bool("ACCEPT").unwrap_or(socket.accept);
+    socket.service = view.last_string("SERVICE");
+    socket.socket_mode = view.last_parsed("SOCKETMODE", parse_octal);
+    socket.socket_user = view.last_string("SOCKETUSER");
+    socket.socket_group = view.last_string("SOCKETGROUP");
+    socket.fd_name = view.last_string("FILEDESCRIPTORNAME");
     socket.remove_on_stop = view
-        .first_bool("REMOVEONSTOP")
+        .last_bool("REMOVEONSTOP")
         .unwrap_or(socket.remove_on_stop);
     socket.max_connections_per_source =
-        view.first_parsed("MAXCONNECTIONSPERSOURCE", |raw| raw.parse().ok());
-    socket.receive_buffer = view.first_parsed("RECEIVEBUFFER", parse_memory);
-    socket.send_buffer = view.first_parsed("SENDBUFFER", parse_memory);
+        view.last_parsed("MAXCONNECTIONSPERSOURCE", |raw| raw.parse().ok());
+    socket.receive_buffer = view.last_parsed("RECEIVEBUFFER", parse_memory);
+    socket.send_buffer = view.last_parsed("SENDBUFFER", parse_memory);
     socket.pass_credentials = view
-        .first_bool("PASSCREDENTIALS")
+        .last_bool("PASSCREDENTIALS")
         .unwrap_or(socket.pass_credentials);
     socket.pass_security = view
-        .first_bool("PASSSECURITY")
+        .last_bool("PASSSECURITY")
         .unwrap_or(socket.pass_security);
     socket.symlinks = view.words("SYMLINKS");
     socket.defer_trigger = view
-        .first_bool("DEFERTRIGGER")
+        .last_bool("DEFERTRIGGER")
         .unwrap_or(socket.defer_trigger);
+    socket.trigger_limit_interval_sec = view.last_parsed("TRIGGERLIMITINTERVALSEC", parse_duration);
+    socket.trigger_limit_burst = view.last_parsed("TRIGGERLIMITBURST", |raw| raw.parse().ok());
+    socket.bind_ipv6_only = view.parsed_or_default("BINDIPV6ONLY", BindIpv6Only::parse);
+    socket.socket_protocol = view.last_parsed("SOCKETPROTOCOL", SocketProtocol::parse);
+    socket.bind_to_device = view.last_string("BINDTODEVICE");
+    socket.smack_label = view.last_string("SMACKLABEL");
+    socket.smack_label_ip_in = view.last_string("SMACKLABELIPIN");
+    socket.smack_label_ip_out = view.last_string("SMACKLABELIPOUT");
+    socket.selinux_context_from_net = view
+        .last_bool("SELINUXCONTEXTFROMNET")
+        .unwrap_or(socket.selinux_context_from_net);
 }
 
 fn apply_timer_section(timer: &mut TimerSection, view: &SectionView<'_>) {
@@ -415,24 +562,24 @@ fn apply_timer_section(timer: &mut TimerSection, view: &SectionView<'_>) {
         .into_iter()
         .map(|raw| CalendarSpec::parse(&raw))
         .collect();
-    timer.on_boot_sec = view.first_parsed("ONBOOTSEC", parse_duration);
-    timer.on_startup_sec = view.first_parsed("ONSTARTUPSEC", parse_duration);
-    timer.on_active_sec = view.first_parsed("ONACTIVESEC", parse_duration);
-    timer.on_unit_active_sec = view.first_parsed("ONUNITACTIVESEC", parse_duration);
-    timer.on_unit_inactive_sec = view.first_parsed("ONUNITINACTIVESEC", parse_duration);
+    timer.on_boot_sec = view.last_parsed("ONBOOTSEC", parse_duration);
+    timer.on_startup_sec = view.last_parsed("ONSTARTUPSEC", parse_duration);
+    timer.on_active_sec = view.last_parsed("ONACTIVESEC", parse_duration);
+    timer.on_unit_active_sec = view.last_parsed("ONUNITACTIVESEC", parse_duration);
+    timer.on_unit_inactive_sec = view.last_parsed("ONUNITINACTIVESEC", parse_duration);
     timer.accuracy_sec = view
-        .first_parsed("ACCURACYSEC", parse_duration)
+        .last_parsed("ACCURACYSEC", parse_duration)
         .unwrap_or(timer.accuracy_sec);
-    timer.randomized_delay_sec = view.first_parsed("RANDOMIZEDDELAYSEC", parse_duration);
-    timer.persistent = view.first_bool("PERSISTENT").unwrap_or(timer.persistent);
-    timer.wake_system = view.first_bool("WAKESYSTEM").unwrap_or(timer.wake_system);
+    timer.randomized_delay_sec = view.last_parsed("RANDOMIZEDDELAYSEC", parse_duration);
+    timer.persistent = view.last_bool("PERSISTENT").unwrap_or(timer.persistent);
+    timer.wake_system = view.last_bool("WAKESYSTEM").unwrap_or(timer.wake_system);
     timer.on_clock_change = view
-        .first_bool("ONCLOCKCHANGE")
+        .last_bool("ONCLOCKCHANGE")
         .unwrap_or(timer.on_clock_change);
     timer.on_timezone_change = view
-        .first_bool("ONTIMEZONECHANGE")
+        .last_bool("ONTIMEZONECHANGE")
         .unwrap_or(timer.on_timezone_change);
-    timer.unit = view.first_string("UNIT");
+    timer.unit = view.last_string("UNIT");
 }
 
 pub fn parse_service(name: &str, parsed: &ParsedFile) -> Result<Service, ParseError> {
@@ -446,6 +593,7 @@ pub fn parse_service(name: &str, parsed: &ParsedFile) -> Result<Service, ParseEr
     apply_service_exec_and_restart(&mut service.service, &service_view);
     apply_service_identity(&mut service.service, &service_view);
     apply_service_environment(&mut service.service, &service_view);
+    apply_service_credentials(&mut service.service, &service_view);
     apply_service_stdio(&mut service.service, &service_view);
     apply_service_limits(&mut service.service, &service_view);
     apply_service_security_core(&mut service.service, &service_view);
@@ -477,11 +625,15 @@ pub fn parse_path_unit(name: &str, parsed: &ParsedFile) -> Result<path::Path, Pa
     path_unit.path.path_changed = path_view.strings("PATHCHANGED");
     path_unit.path.path_modified = path_view.strings("PATHMODIFIED");
     path_unit.path.directory_not_empty = path_view.strings("DIRECTORYNOTEMPTY");
-    path_unit.path.unit = path_view.first_string("UNIT");
+    path_unit.path.unit = path_view.last_string("UNIT");
     path_unit.path.make_directory = path_view
-        .first_bool("MAKEDIRECTORY")
+        .last_bool("MAKEDIRECTORY")
         .unwrap_or(path_unit.path.make_directory);
-    path_unit.path.directory_mode = path_view.first_parsed("DIRECTORYMODE", parse_octal);
+    path_unit.path.directory_mode = path_view.last_parsed("DIRECTORYMODE", parse_octal);
+    path_unit.path.trigger_limit_interval_sec =
+        path_view.last_parsed("TRIGGERLIMITINTERVALSEC", parse_duration);
+    path_unit.path.trigger_limit_burst =
+        path_view.last_parsed("TRIGGERLIMITBURST", |raw| raw.parse().ok());
 
     let install_view = SectionView::from(parsed, "[Install]");
     apply_install_without_default_instance(&mut path_unit.install, &install_view);
@@ -507,6 +659,7 @@ pub fn parse_mount(name: &str, parsed: &ParsedFile) -> Result<Mount, ParseError>
     if mount.mount.r#where.is_empty() {
         mount.mount.r#where = Mount::mount_point_from_name(name);
     }
+    mount.add_implicit_dependencies();
 
     let install_view = SectionView::from(parsed, "[Install]");
     apply_install_core(&mut mount.install, &install_view);
@@ -542,6 +695,53 @@ pub fn parse_timer(name: &str, parsed: &ParsedFile) -> Result<Timer, ParseError>
     Ok(timer)
 }
 
+/// Parse the `[Manager]` section of system.conf / user.conf
+pub fn parse_manager_config(parsed: &ParsedFile) -> ManagerConfig {
+    let view = SectionView::from(parsed, "[Manager]");
+    ManagerConfig {
+        default_timeout_start_sec: view.last_parsed("DEFAULTTIMEOUTSTARTSEC", parse_duration),
+        default_restart_sec: view.last_parsed("DEFAULTRESTARTSEC", parse_duration),
+        default_memory_accounting: view.last_bool("DEFAULTMEMORYACCOUNTING").unwrap_or(false),
+        default_cpu_accounting: view.last_bool("DEFAULTCPUACCOUNTING").unwrap_or(false),
+        default_tasks_max: view.last_parsed("DEFAULTTASKSMAX", |raw| raw.parse().ok()),
+        default_environment: view
+            .strings("DEFAULTENVIRONMENT")
+            .iter()
+            .filter_map(|value| parser::parse_environment(value).ok())
+            .flatten()
+            .collect(),
+        default_limit_nofile: view.last_parsed("DEFAULTLIMITNOFILE", parse_limit),
+        unit_file_permissions: view
+            .last_parsed("UNITFILEPERMISSIONS", UnitFilePermissions::parse)
+            .unwrap_or_default(),
+        trigger_limit_interval_sec: view.last_parsed("TRIGGERLIMITINTERVALSEC", parse_duration),
+        trigger_limit_burst: view.last_parsed("TRIGGERLIMITBURST", |raw| raw.parse().ok()),
+        default_slice: view
+            .last_string("DEFAULTSLICE")
+            .filter(|slice| slice.ends_with(".slice")),
+        persist_state_history: view.last_bool("PERSISTSTATEHISTORY").unwrap_or(false),
+    }
+}
+
+pub fn parse_login_config(parsed: &ParsedFile) -> LoginConfig {
+    let view = SectionView::from(parsed, "[Login]");
+    let defaults = LoginConfig::default();
+    LoginConfig {
+        handle_power_key: view
+            .last_parsed("HANDLEPOWERKEY", HandleAction::parse)
+            .unwrap_or(defaults.handle_power_key),
+        handle_lid_switch: view
+            .last_parsed("HANDLELIDSWITCH", HandleAction::parse)
+            .unwrap_or(defaults.handle_lid_switch),
+        n_auto_vts: view
+            .last_parsed("NAUTOVTS", |value| value.parse().ok())
+            .unwrap_or(defaults.n_auto_vts),
+        reserve_vt: view
+            .last_parsed("RESERVEVT", |value| value.parse().ok())
+            .unwrap_or(defaults.reserve_vt),
+    }
+}
+
 fn fallback_unit_name(path: &Path) -> String {
     path.file_name()
         .and_then(|name| name.to_str())
@@ -561,49 +761,130 @@ fn resolve_service_name(path: &Path) -> String {
         .unwrap_or_else(|| fallback_unit_name(path))
 }
 
+/// Where `set-property` keeps its drop-ins: system.control below /etc
+/// (~/.config for user units), or below /run (XDG_RUNTIME_DIR) for --runtime
+pub fn control_dropin_root(user_mode: bool, runtime: bool) -> Option<PathBuf> {
+    match (user_mode, runtime) {
+        (false, false) => Some(crate::root::path("/etc/systemd/system.control")),
+        (false, true) => Some(crate::root::path("/run/systemd/system.control")),
+        (true, false) => dirs::config_dir().map(|dir| dir.join("systemd/user.control")),
+        (true, true) => std::env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("systemd/user.control")),
+    }
+}
+
 fn dropin_directories(unit_path: &Path) -> Vec<PathBuf> {
     let Some(unit_name) = unit_path.file_name().and_then(|name| name.to_str()) else {
         return Vec::new();
     };
+    dropin_directories_named(unit_path, &format!("{}.d", unit_name))
+}
 
-    let mut directories = vec![
-        Path::new("/etc/systemd/system").join(format!("{}.d", unit_name)),
-        Path::new("/usr/lib/systemd/system").join(format!("{}.d", unit_name)),
-    ];
+/// Directories called `dir_name` in every drop-in root, highest priority first
+fn dropin_directories_named(unit_path: &Path, dir_name: &str) -> Vec<PathBuf> {
+    let user_unit = unit_path
+        .parent()
+        .is_some_and(|parent| parent.ends_with("systemd/user"));
+    let mut directories: Vec<PathBuf> = [false, true]
+        .into_iter()
+        .filter_map(|runtime| control_dropin_root(user_unit, runtime))
+        .map(|root| root.join(dir_name))
+        .collect();
+    directories.extend([
+        crate::root::path("/etc/systemd/system").join(dir_name),
+        crate::root::path("/usr/lib/systemd/system").join(dir_name),
+    ]);
 
     if let Some(parent) = unit_path.parent() {
-        directories.push(parent.join(format!("{}.d", unit_name)));
+        directories.push(parent.join(dir_name));
     }
 
     directories
 }
 
-fn collect_dropin_files(directories: &[PathBuf]) -> Vec<PathBuf> {
-    let mut files = Vec::new();
+/// Drop-in directories shared with other units, least specific first: the
+/// unit type's (service.d) and one per dash-separated name prefix
+/// (foo-.service.d and foo-bar-.service.d for foo-bar-baz.service)
+fn shared_dropin_dir_names(unit_name: &str) -> Vec<String> {
+    let Some((stem, unit_type)) = unit_name.rsplit_once('.') else {
+        return Vec::new();
+    };
+    let mut names = vec![format!("{}.d", unit_type)];
+    names.extend(
+        stem.match_indices('-')
+            .filter(|(index, _)| *index > 0)
+            .map(|(index, _)| format!("{}-.{}.d", &stem[..index], unit_type)),
+    );
+    names
+}
 
-    for directory in directories {
-        let Ok(entries) = std::fs::read_dir(directory) else {
-            continue;
-        };
+/// Drop-in files in the order they are applied
+///
+/// As in systemd, a file in an earlier (higher priority) directory masks a
+/// file of the same name further down the list, so /etc overrides /usr/lib,
+/// and the remaining files are applied sorted by file name regardless of
+/// their directory.
+fn collect_dropin_files(directories: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files: BTreeMap<&str, PathBuf> = BTreeMap::new();
+    let listings: Vec<_> = directories
+        .iter()
+        .filter_map(|directory| Some((directory, list_directory(directory)?)))
+        .collect();
 
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.extension().is_some_and(|ext| ext == "conf") {
-                files.push(path);
-            }
+    for (directory, names) in &listings {
+        for name in names.iter().filter(|name| name.ends_with(".conf")) {
+            files
+                .entry(name.as_str())
+                .or_insert_with(|| directory.join(name));
         }
     }
 
-    files.sort();
-    files
+    files.into_values().collect()
+}
+
+/// Every drop-in of the unit file at `unit_path`, in the order they apply
+pub fn dropin_files(unit_path: &Path) -> Vec<PathBuf> {
+    let unit_name = unit_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    // Shared drop-ins go first so the unit's own drop-ins override them
+    let mut levels: Vec<Vec<PathBuf>> = shared_dropin_dir_names(unit_name)
+        .iter()
+        .map(|dir_name| dropin_directories_named(unit_path, dir_name))
+        .collect();
+    levels.push(dropin_directories(unit_path));
+    levels
+        .iter()
+        .flat_map(|directories| collect_dropin_files(directories))
+        .collect()
 }
 
-async fn load_dropins(unit_path: &Path, parsed: &mut ParsedFile) {
-    let directories = dropin_directories(unit_path);
-    let files = collect_dropin_files(&directories);
+/// Called on a unit file and each of its drop-ins from the blocking task
+/// that lists the drop-ins; an error refuses the unit
+pub type FileCheck = std::sync::Arc<dyn Fn(&Path) -> Result<(), String> + Send + Sync>;
+
+async fn load_dropins(
+    unit_path: &Path,
+    parsed: &mut ParsedFile,
+    check: Option<&FileCheck>,
+) -> Result<(), ParseError> {
+    let path = unit_path.to_path_buf();
+    let check = check.cloned();
+    let files = tokio::task::spawn_blocking(move || {
+        let files = dropin_files(&path);
+        if let Some(check) = check {
+            for file in std::iter::once(&path).chain(&files) {
+                check(file).map_err(ParseError::Refused)?;
+            }
+        }
+        Ok::<_, ParseError>(files)
+    })
+    .await
+    .unwrap_or_else(|_| Ok(Vec::new()))?;
 
     for conf_path in files {
-        match parse_unit_file(&conf_path).await {
+        match parse_unit_file_cached(&conf_path).await {
             Ok(dropin) => {
                 log::debug!("Loaded drop-in: {}", conf_path.display());
                 merge_parsed_files(parsed, &dropin);
@@ -613,6 +894,7 @@ async fn load_dropins(unit_path: &Path, parsed: &mut ParsedFile) {
             }
         }
     }
+    Ok(())
 }
 
 fn merge_parsed_files(base: &mut ParsedFile, dropin: &ParsedFile) {
@@ -620,17 +902,10 @@ fn merge_parsed_files(base: &mut ParsedFile, dropin: &ParsedFile) {
         let base_section = base.entry(section_name.clone()).or_default();
 
         for (key, values) in section_values {
-            let has_reset = values.iter().any(|(_, value)| value.is_empty());
-            if has_reset {
-                base_section.remove(key);
-                let non_empty: Vec<_> = values
-                    .iter()
-                    .filter(|(_, value)| !value.is_empty())
-                    .cloned()
-                    .collect();
-                if !non_empty.is_empty() {
-                    base_section.insert(key.clone(), non_empty);
-                }
+            // A drop-in starting with `Key=` replaces what came before
+            let resets = values.first().is_some_and(|(_, value)| value.is_empty());
+            if resets {
+                base_section.insert(key.clone(), values.clone());
                 continue;
             }
 
@@ -642,9 +917,12 @@ fn merge_parsed_files(base: &mut ParsedFile, dropin: &ParsedFile) {
     }
 }
 
-async fn load_parsed_with_dropins(path: &Path) -> Result<ParsedFile, ParseError> {
-    let mut parsed = parse_unit_file(path).await?;
-    load_dropins(path, &mut parsed).await;
+async fn load_parsed_with_dropins(
+    path: &Path,
+    check: Option<&FileCheck>,
+) -> Result<ParsedFile, ParseError> {
+    let mut parsed = parse_unit_file_cached(path).await?;
+    load_dropins(path, &mut parsed, check).await?;
     Ok(parsed)
 }
 
@@ -652,14 +930,15 @@ async fn load_with_parser<T>(
     path: &Path,
     name_resolver: fn(&Path) -> String,
     parser: fn(&str, &ParsedFile) -> Result<T, ParseError>,
+    check: Option<&FileCheck>,
 ) -> Result<T, ParseError> {
     let name = name_resolver(path);
-    let parsed = load_parsed_with_dropins(path).await?;
+    let parsed = load_parsed_with_dropins(path, check).await?;
     parser(&name, &parsed)
 }
 
 pub async fn load_service(path: &Path) -> Result<Service, ParseError> {
-    load_with_parser(path, resolve_service_name, parse_service).await
+    load_with_parser(path, resolve_service_name, parse_service, None).await
 }
 
 fn read_wants_dir(path: &Path) -> Vec<String> {
@@ -682,66 +961,123 @@ fn read_wants_dir(path: &Path) -> Vec<String> {
         .collect()
 }
 
-fn collect_target_wants(path: &Path, name: &str) -> Vec<String> {
-    let mut wants = Vec::new();
-
-    let local_wants_dir = path.with_extension("target.wants");
-    if local_wants_dir.is_dir() {
-        wants.extend(read_wants_dir(&local_wants_dir));
+/// Units linked into `<name>.<kind>` directories (kind "wants" or
+/// "requires") next to the unit file and in the drop-in roots, where
+/// `enable` puts them; ~/.config/systemd/user too for user units
+fn collect_dependency_dir(path: &Path, name: &str, kind: &str) -> Vec<String> {
+    let dir_name = format!("{}.{}", name, kind);
+    let mut directories = dropin_directories_named(path, &dir_name);
+    let user_unit = path
+        .parent()
+        .is_some_and(|parent| parent.ends_with("systemd/user"));
+    if let Some(config) = dirs::config_dir().filter(|_| user_unit) {
+        directories.push(config.join("systemd/user").join(&dir_name));
     }
 
-    let etc_wants_dir = Path::new("/etc/systemd/system").join(format!("{}.wants", name));
-    if etc_wants_dir.is_dir() {
-        wants.extend(read_wants_dir(&etc_wants_dir));
+    let mut units: Vec<String> = Vec::new();
+    for directory in directories.iter().filter(|directory| directory.is_dir()) {
+        for unit in read_wants_dir(directory) {
+            if !units.contains(&unit) {
+                units.push(unit);
+            }
+        }
     }
+    units
+}
 
-    wants
+/// Fill in the .wants/ and .requires/ directories of the unit `name`
+async fn load_dependency_dirs(path: &Path, name: &str, section: &mut UnitSection) {
+    let (path, name) = (path.to_path_buf(), name.to_string());
+    let (wants, requires) = tokio::task::spawn_blocking(move || {
+        (
+            collect_dependency_dir(&path, &name, "wants"),
+            collect_dependency_dir(&path, &name, "requires"),
+        )
+    })
+    .await
+    .unwrap_or_default();
+    section.wants_dir = wants;
+    section.requires_dir = requires;
 }
 
 pub async fn load_target(path: &Path) -> Result<Target, ParseError> {
+    load_target_checked(path, None).await
+}
+
+async fn load_target_checked(path: &Path, check: Option<&FileCheck>) -> Result<Target, ParseError> {
     let name = fallback_unit_name(path);
-    let parsed = load_parsed_with_dropins(path).await?;
-    let mut target = parse_target(&name, &parsed)?;
-    target.wants_dir = collect_target_wants(path, &name);
+    let parsed = load_parsed_with_dropins(path, check).await?;
+    finish_target(path, &name, &parsed).await
+}
+
+/// A built-in target (see `builtin_unit`), with drop-ins and .wants/ applied
+/// as if it were installed in /usr/lib/systemd/system
+pub async fn load_builtin_target(name: &str, content: &str) -> Result<Target, ParseError> {
+    let path = crate::root::path("/usr/lib/systemd/system").join(name);
+    let mut parsed = parse_file(content)?;
+    load_dropins(&path, &mut parsed, None).await?;
+    finish_target(&path, name, &parsed).await
+}
+
+async fn finish_target(path: &Path, name: &str, parsed: &ParsedFile) -> Result<Target, ParseError> {
+    let mut target = parse_target(name, parsed)?;
+    load_dependency_dirs(path, name, &mut target.unit).await;
     Ok(target)
 }
 
 pub async fn load_path(path: &Path) -> Result<path::Path, ParseError> {
-    load_with_parser(path, fallback_unit_name, parse_path_unit).await
+    load_with_parser(path, fallback_unit_name, parse_path_unit, None).await
 }
 
 pub async fn load_slice(path: &Path) -> Result<Slice, ParseError> {
-    load_with_parser(path, fallback_unit_name, parse_slice).await
+    load_with_parser(path, fallback_unit_name, parse_slice, None).await
 }
 
 pub async fn load_mount(path: &Path) -> Result<Mount, ParseError> {
-    load_with_parser(path, fallback_unit_name, parse_mount).await
+    load_with_parser(path, fallback_unit_name, parse_mount, None).await
 }
 
 pub async fn load_socket(path: &Path) -> Result<Socket, ParseError> {
-    load_with_parser(path, fallback_unit_name, parse_socket).await
+    load_with_parser(path, fallback_unit_name, parse_socket, None).await
 }
 
 pub async fn load_timer(path: &Path) -> Result<Timer, ParseError> {
-    load_with_parser(path, fallback_unit_name, parse_timer).await
+    load_with_parser(path, fallback_unit_name, parse_timer, None).await
 }
 
 pub async fn load_unit(path: &Path) -> Result<Unit, ParseError> {
-    let extension = path.extension().and_then(|ext| ext.to_str());
-
-    match extension {
-        Some("service") => load_service(path).await.map(Unit::Service),
-        Some("target") => load_target(path).await.map(Unit::Target),
-        Some("mount") => load_mount(path).await.map(Unit::Mount),
-        Some("slice") => load_slice(path).await.map(Unit::Slice),
-        Some("socket") => load_socket(path).await.map(Unit::Socket),
-        Some("timer") => load_timer(path).await.map(Unit::Timer),
-        Some("path") => load_path(path).await.map(Unit::Path),
-        _ => Err(ParseError::Io(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            format!("Unknown unit type: {:?}", path),
-        ))),
-    }
+    load_unit_checked(path, None).await
+}
+
+/// `load_unit`, refusing the unit when `check` fails on its file or a drop-in
+pub async fn load_unit_checked(path: &Path, check: Option<FileCheck>) -> Result<Unit, ParseError> {
+    let check = check.as_ref();
+    let parser: fn(&str, &ParsedFile) -> Result<Unit, ParseError> =
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("service") => |name, parsed| parse_service(name, parsed).map(Unit::Service),
+            // finish_target reads them, for built-in targets too
+            Some("target") => return load_target_checked(path, check).await.map(Unit::Target),
+            Some("mount") => |name, parsed| parse_mount(name, parsed).map(Unit::Mount),
+            Some("slice") => |name, parsed| parse_slice(name, parsed).map(Unit::Slice),
+            Some("socket") => |name, parsed| parse_socket(name, parsed).map(Unit::Socket),
+            Some("timer") => |name, parsed| parse_timer(name, parsed).map(Unit::Timer),
+            Some("path") => |name, parsed| parse_path_unit(name, parsed).map(Unit::Path),
+            _ => {
+                return Err(ParseError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Unknown unit type: {:?}", path),
+                )))
+            }
+        };
+    let name_resolver: fn(&Path) -> String = if path.extension().is_some_and(|e| e == "service") {
+        resolve_service_name
+    } else {
+        fallback_unit_name
+    };
+    let mut unit = load_with_parser(path, name_resolver, parser, check).await?;
+    let name = unit.name().to_string();
+    load_dependency_dirs(path, &name, unit.unit_section_mut()).await;
+    Ok(unit)
 }
 
 #[cfg(test)]
@@ -24,11 +24,15 @@ fn temp_unit_dir(test_name: &str) -> PathBuf {
 const SERVICE_UNIT_FIXTURE: &str = r#"
 [Unit]
 Description=Demo worker
+Documentation=man:demo(8) https://example.org/demo
 After=network-online.target remote-fs.target
 Requires=network-online.target
 Wants=metrics.target audit.target
 BindsTo=dbus.socket
+Requisite=local-fs.target
+PartOf=graphical.target
 ConditionPathExists=/etc/demo.conf
+ConditionPathExistsGlob=/etc/demo.d/*.conf
 ConditionDirectoryNotEmpty=/var/lib/demo
 ConditionVirtualization=!container
 ConditionCapability=CAP_NET_BIND_SERVICE
@@ -38,6 +42,7 @@ ConditionFirstBoot=no
 ConditionNeedsUpdate=/etc
 DefaultDependencies=no
 IgnoreOnIsolate=yes
+SurviveFinalKillSignal=yes
 
 [Service]
 Type=notify-reload
@@ -50,8 +55,14 @@ Restart=on-failure
 RestartSec=5s
 TimeoutStartSec=30s
 TimeoutStopSec=45s
+TimeoutAbortSec=2min
 RemainAfterExit=yes
+RollingRestart=yes
 WatchdogSec=20s
+ExecHealthCheck=tcp://127.0.0.1:8080
+HealthCheckIntervalSec=10s
+HealthCheckRetries=5
+WaitForEndpoint=http://localhost:8080/ready
 NotifyAccess=all
 PIDFile=/run/demo.pid
 BusName=com.example.Demo
@@ -62,14 +73,21 @@ WorkingDirectory=/var/lib/demo
 Environment=MODE=prod "GREETING=hello world"
 EnvironmentFile=/etc/demo.env
 UnsetEnvironment=DEBUG
+PassEnvironment=TERM LANG
 StandardOutput=null
 StandardError=inherit
 StandardInput=tty-force
 TTYPath=/dev/tty1
 TTYReset=yes
+TTYVHangup=yes
+TTYVTDisallocate=yes
 MemoryMax=128M
 CPUQuota=250%
 TasksMax=64
+IPAddressDeny=any
+IPAddressAllow=localhost 10.0.0.0/8 not-an-address
+SocketBindDeny=any
+SocketBindAllow=tcp:8080 ipv6:udp:5000-5010 quic:443
 LimitNOFILE=infinity
 LimitNPROC=512
 LimitCORE=0
@@ -80,6 +98,7 @@ LogsDirectory=demo
 CacheDirectory=demo
 RuntimeDirectoryPreserve=restart
 DynamicUser=yes
+RemoveIPC=yes
 OOMScoreAdjust=-100
 NoNewPrivileges=yes
 ProtectSystem=strict
@@ -87,10 +106,19 @@ ProtectHome=read-only
 PrivateTmp=yes
 PrivateDevices=yes
 PrivateNetwork=yes
+PrivatePIDs=yes
+PrivateIPC=yes
+NetworkNamespacePath=/run/netns/vpn
 ProtectKernelModules=yes
 ProtectProc=invisible
+SELinuxContext=system_u:system_r:demo_t:s0
+AppArmorProfile=-demo
+SmackProcessLabel=Demo
 CapabilityBoundingSet=CAP_NET_BIND_SERVICE CAP_CHOWN
 AmbientCapabilities=CAP_NET_BIND_SERVICE
+SecureBits=keep-caps noroot
+SecureBits=noroot-locked bogus
+KeyringMode=shared
 RestrictNamespaces=~user pid
 ReadWritePaths=/var/lib/demo /run/demo
 ReadOnlyPaths=/etc/demo
@@ -109,6 +137,8 @@ ProtectHostname=yes
 IgnoreSIGPIPE=no
 RestrictSUIDSGID=yes
 RestrictAddressFamilies=AF_UNIX AF_INET
+RestrictFileSystems=@basic-api ext4
+RestrictFileSystems=tmpfs
 SystemCallErrorNumber=13
 SystemCallArchitectures=native
 StartLimitBurst=3
@@ -138,6 +168,10 @@ fn parse_service_maps_unit_service_and_install_sections() {
     assert_eq!(service.name, "demo@main.service");
     assert_eq!(service.instance.as_deref(), Some("main"));
     assert_eq!(service.unit.description.as_deref(), Some("Demo worker"));
+    assert_eq!(
+        service.unit.documentation,
+        ["man:demo(8)", "https://example.org/demo"]
+    );
     assert_eq!(
         service.unit.after,
         ["network-online.target", "remote-fs.target"]
@@ -145,7 +179,13 @@ fn parse_service_maps_unit_service_and_install_sections() {
     assert_eq!(service.unit.requires, ["network-online.target"]);
     assert_eq!(service.unit.wants, ["metrics.target", "audit.target"]);
     assert_eq!(service.unit.binds_to, ["dbus.socket"]);
+    assert_eq!(service.unit.requisite, ["local-fs.target"]);
+    assert_eq!(service.unit.part_of, ["graphical.target"]);
     assert_eq!(service.unit.condition_path_exists, ["/etc/demo.conf"]);
+    assert_eq!(
+        service.unit.condition_path_exists_glob,
+        ["/etc/demo.d/*.conf"]
+    );
     assert_eq!(
         service.unit.condition_directory_not_empty,
         ["/var/lib/demo"]
@@ -161,6 +201,7 @@ fn parse_service_maps_unit_service_and_install_sections() {
     assert_eq!(service.unit.condition_needs_update, ["/etc"]);
     assert!(!service.unit.default_dependencies);
     assert!(service.unit.ignore_on_isolate);
+    assert!(service.unit.survive_final_kill_signal);
 
     assert_eq!(service.service.service_type, ServiceType::Notify);
     assert_eq!(
@@ -172,7 +213,7 @@ fn parse_service_maps_unit_service_and_install_sections() {
     assert_eq!(service.service.exec_reload, ["/bin/kill -HUP $MAINPID"]);
     assert_eq!(service.service.exec_stop, ["/usr/bin/demo-stop"]);
     assert_eq!(service.service.restart, RestartPolicy::OnFailure);
-    assert_eq!(service.service.restart_sec, Duration::from_secs(5));
+    assert_eq!(service.service.restart_sec, Some(Duration::from_secs(5)));
     assert_eq!(
         service.service.timeout_start_sec,
         Some(Duration::from_secs(30))
@@ -181,8 +222,30 @@ fn parse_service_maps_unit_service_and_install_sections() {
         service.service.timeout_stop_sec,
         Some(Duration::from_secs(45))
     );
+    assert_eq!(
+        service.service.timeout_abort_sec,
+        Some(Duration::from_secs(120))
+    );
     assert!(service.service.remain_after_exit);
+    assert!(service.service.rolling_restart);
     assert_eq!(service.service.watchdog_sec, Some(Duration::from_secs(20)));
+    assert_eq!(
+        service.service.exec_health_check,
+        Some(HealthCheck::Tcp("127.0.0.1:8080".to_string()))
+    );
+    assert_eq!(
+        service.service.health_check_interval_sec,
+        Some(Duration::from_secs(10))
+    );
+    assert_eq!(service.service.health_check_retries, Some(5));
+    assert_eq!(
+        service.service.wait_for_endpoint,
+        Some(HealthCheck::Http {
+            address: "localhost:8080".into(),
+            host: "localhost:8080".into(),
+            path: "/ready".into(),
+        })
+    );
     assert_eq!(service.service.notify_access, NotifyAccess::All);
     assert_eq!(
         service.service.pid_file.as_deref(),
@@ -211,6 +274,7 @@ fn parse_service_maps_unit_service_and_install_sections() {
         [PathBuf::from("/etc/demo.env")]
     );
     assert_eq!(service.service.unset_environment, ["DEBUG"]);
+    assert_eq!(service.service.pass_environment, ["TERM", "LANG"]);
     assert_eq!(service.service.standard_output, StdOutput::Null);
     assert_eq!(service.service.standard_error, StdOutput::Inherit);
     assert_eq!(service.service.standard_input, StdInput::TtyForce);
@@ -219,9 +283,39 @@ fn parse_service_maps_unit_service_and_install_sections() {
         Some(Path::new("/dev/tty1"))
     );
     assert!(service.service.tty_reset);
+    assert!(service.service.tty_vhangup);
+    assert!(service.service.tty_vt_disallocate);
     assert_eq!(service.service.memory_max, Some(128 * 1024 * 1024));
     assert_eq!(service.service.cpu_quota, Some(250));
     assert_eq!(service.service.tasks_max, Some(64));
+    assert_eq!(service.service.ip_address_deny.len(), 2);
+    assert_eq!(
+        service.service.ip_address_allow,
+        [
+            IpPrefix::new("127.0.0.0".parse().unwrap(), 8),
+            IpPrefix::new("::1".parse().unwrap(), 128),
+            IpPrefix::new("10.0.0.0".parse().unwrap(), 8),
+        ]
+    );
+    assert_eq!(
+        service.service.socket_bind_deny,
+        [SocketBindRule::default()]
+    );
+    assert_eq!(
+        service.service.socket_bind_allow,
+        [
+            SocketBindRule {
+                protocol: Some(BindProtocol::Tcp),
+                ports: Some((8080, 8080)),
+                ..Default::default()
+            },
+            SocketBindRule {
+                family: Some(BindFamily::Ipv6),
+                protocol: Some(BindProtocol::Udp),
+                ports: Some((5000, 5010)),
+            },
+        ]
+    );
     assert_eq!(service.service.limit_nofile, Some(u64::MAX));
     assert_eq!(service.service.limit_nproc, Some(512));
     assert_eq!(service.service.limit_core, Some(0));
@@ -235,6 +329,7 @@ fn parse_service_maps_unit_service_and_install_sections() {
         RuntimeDirectoryPreserve::Restart
     );
     assert!(service.service.dynamic_user);
+    assert!(service.service.remove_ipc);
     assert_eq!(service.service.oom_score_adjust, Some(-100));
     assert!(service.service.no_new_privileges);
     assert_eq!(service.service.protect_system, ProtectSystem::Strict);
@@ -242,8 +337,35 @@ fn parse_service_maps_unit_service_and_install_sections() {
     assert!(service.service.private_tmp);
     assert!(service.service.private_devices);
     assert!(service.service.private_network);
+    assert!(service.service.private_pids);
+    assert!(service.service.private_ipc);
+    assert_eq!(
+        service.service.network_namespace_path,
+        Some(PathBuf::from("/run/netns/vpn"))
+    );
     assert!(service.service.protect_kernel_modules);
     assert_eq!(service.service.protect_proc, ProtectProc::Invisible);
+    assert_eq!(
+        service.service.selinux_context,
+        Some(MacLabel {
+            label: "system_u:system_r:demo_t:s0".to_string(),
+            ignore_failure: false,
+        })
+    );
+    assert_eq!(
+        service.service.apparmor_profile,
+        Some(MacLabel {
+            label: "demo".to_string(),
+            ignore_failure: true,
+        })
+    );
+    assert_eq!(
+        service.service.smack_process_label,
+        Some(MacLabel {
+            label: "Demo".to_string(),
+            ignore_failure: false,
+        })
+    );
     assert_eq!(
         service.service.capability_bounding_set,
         ["CAP_NET_BIND_SERVICE", "CAP_CHOWN"]
@@ -252,6 +374,8 @@ fn parse_service_maps_unit_service_and_install_sections() {
         service.service.ambient_capabilities,
         ["CAP_NET_BIND_SERVICE"]
     );
+    assert_eq!(service.service.secure_bits, 0b10011);
+    assert_eq!(service.service.keyring_mode, KeyringMode::Shared);
     assert_eq!(
         service.service.restrict_namespaces,
         Some(vec!["~user".to_string(), "pid".to_string()])
@@ -282,6 +406,10 @@ fn parse_service_maps_unit_service_and_install_sections() {
         service.service.restrict_address_families,
         Some(vec!["AF_UNIX".to_string(), "AF_INET".to_string()])
     );
+    assert_eq!(
+        service.service.restrict_file_systems,
+        ["@basic-api", "ext4", "tmpfs"]
+    );
     assert_eq!(service.service.system_call_error_number, Some(13));
     assert_eq!(service.service.system_call_architectures, ["native"]);
     assert_eq!(service.service.start_limit_burst, Some(3));
@@ -328,7 +456,7 @@ ConditionFirstBoot=yes
     assert_eq!(target.unit.conflicts, ["rescue.target"]);
     assert_eq!(target.unit.condition_first_boot, Some(true));
     assert!(!target.unit.default_dependencies);
-    assert!(target.wants_dir.is_empty());
+    assert!(target.unit.wants_dir.is_empty());
 
     let slice = parse_slice("system-app.slice", &unit).expect("slice should parse");
     assert_eq!(slice.name, "system-app.slice");
@@ -356,6 +484,8 @@ DirectoryNotEmpty=/srv/inbox/pending
 Unit=ingest.service
 MakeDirectory=yes
 DirectoryMode=0750
+TriggerLimitIntervalSec=5s
+TriggerLimitBurst=50
 
 [Install]
 WantedBy=multi-user.target
@@ -380,6 +510,11 @@ DefaultInstance=ignored
     assert_eq!(path_unit.path.unit.as_deref(), Some("ingest.service"));
     assert!(path_unit.path.make_directory);
     assert_eq!(path_unit.path.directory_mode, Some(0o750));
+    assert_eq!(
+        path_unit.path.trigger_limit_interval_sec,
+        Some(Duration::from_secs(5))
+    );
+    assert_eq!(path_unit.path.trigger_limit_burst, Some(50));
     assert_eq!(path_unit.install.wanted_by, ["multi-user.target"]);
     assert_eq!(path_unit.install.required_by, ["paths.target"]);
     assert_eq!(path_unit.install.also, ["ingest.service"]);
@@ -440,6 +575,8 @@ What=/dev/sdb1
     );
     let mount = parse_mount("mnt-data.mount", &fallback).expect("mount should parse");
     assert_eq!(mount.mount.r#where, "/mnt/data");
+    // Ordered after the mounts above the fallback mount point
+    assert_eq!(mount.unit.after, ["-.mount", "mnt.mount"]);
 }
 
 #[test]
@@ -455,6 +592,8 @@ ListenStream=127.0.0.1:8080
 ListenDatagram=/run/demo.dgram
 ListenFIFO=/run/demo.fifo
 ListenNetlink=audit 1
+ListenSequentialPacket=[::1]:3868
+SocketProtocol=sctp
 Accept=yes
 Service=demo@.service
 SocketMode=0660
@@ -469,6 +608,14 @@ PassCredentials=yes
 PassSecurity=yes
 Symlinks=/run/demo.sock /run/demo-api.sock
 DeferTrigger=yes
+TriggerLimitIntervalSec=1s
+TriggerLimitBurst=500
+BindIPv6Only=ipv6-only
+BindToDevice=eth0
+SmackLabel=Demo
+SmackLabelIPIn=DemoIn
+SmackLabelIPOut=DemoOut
+SELinuxContextFromNet=yes
 
 [Install]
 WantedBy=sockets.target
@@ -483,7 +630,7 @@ DefaultInstance=main
     assert_eq!(socket.name, "demo.socket");
     assert_eq!(socket.unit.description.as_deref(), Some("Demo socket"));
     assert_eq!(socket.unit.after, ["network.target"]);
-    assert_eq!(socket.socket.listeners.len(), 4);
+    assert_eq!(socket.socket.listeners.len(), 5);
     assert_eq!(socket.socket.listeners[0].address, "127.0.0.1:8080");
     assert_eq!(socket.socket.listeners[0].listen_type, ListenType::Stream);
     assert_eq!(socket.socket.listeners[1].address, "/run/demo.dgram");
@@ -492,6 +639,12 @@ DefaultInstance=main
     assert_eq!(socket.socket.listeners[2].listen_type, ListenType::Fifo);
     assert_eq!(socket.socket.listeners[3].address, "audit 1");
     assert_eq!(socket.socket.listeners[3].listen_type, ListenType::Netlink);
+    assert_eq!(socket.socket.listeners[4].address, "[::1]:3868");
+    assert_eq!(
+        socket.socket.listeners[4].listen_type,
+        ListenType::SequentialPacket
+    );
+    assert_eq!(socket.socket.socket_protocol, Some(SocketProtocol::Sctp));
     assert!(socket.socket.accept);
     assert_eq!(socket.socket.service.as_deref(), Some("demo@.service"));
     assert_eq!(socket.socket.socket_mode, Some(0o660));
@@ -509,6 +662,17 @@ DefaultInstance=main
         ["/run/demo.sock", "/run/demo-api.sock"]
     );
     assert!(socket.socket.defer_trigger);
+    assert_eq!(
+        socket.socket.trigger_limit_interval_sec,
+        Some(Duration::from_secs(1))
+    );
+    assert_eq!(socket.socket.trigger_limit_burst, Some(500));
+    assert_eq!(socket.socket.bind_ipv6_only, BindIpv6Only::Ipv6Only);
+    assert_eq!(socket.socket.bind_to_device.as_deref(), Some("eth0"));
+    assert_eq!(socket.socket.smack_label.as_deref(), Some("Demo"));
+    assert_eq!(socket.socket.smack_label_ip_in.as_deref(), Some("DemoIn"));
+    assert_eq!(socket.socket.smack_label_ip_out.as_deref(), Some("DemoOut"));
+    assert!(socket.socket.selinux_context_from_net);
     assert_eq!(socket.service_name(), "demo@.service");
     assert!(socket.is_accept_socket());
     assert_eq!(socket.install.wanted_by, ["sockets.target"]);
@@ -659,7 +823,7 @@ WantedBy=default.target
         service.unit.description.as_deref(),
         Some("Base description")
     );
-    assert_eq!(service.unit.after, ["network.target", "dbus.service"]);
+    assert_eq!(service.unit.after, ["dbus.service"]);
     assert_eq!(service.service.exec_start, ["/usr/bin/demo --override"]);
     assert_eq!(
         service.service.environment,
@@ -668,14 +832,142 @@ WantedBy=default.target
             ("EXTRA".to_string(), "1".to_string())
         ]
     );
+    assert_eq!(service.install.wanted_by, ["default.target"]);
+
+    fs::remove_dir_all(&dir).expect("temp unit directory should be removed");
+}
+
+#[test]
+fn shared_dropin_dir_names_cover_type_and_name_prefixes() {
+    assert_eq!(
+        shared_dropin_dir_names("foo-bar-baz.service"),
+        ["service.d", "foo-.service.d", "foo-bar-.service.d"]
+    );
+    assert_eq!(shared_dropin_dir_names("sshd.socket"), ["socket.d"]);
+    assert_eq!(shared_dropin_dir_names("-.slice"), ["slice.d"]);
+}
+
+#[tokio::test]
+async fn load_unit_applies_type_and_prefix_dropins_before_unit_dropins() {
+    let dir = temp_unit_dir("shared-dropins");
+    let unit_path = dir.join("web-api.service");
+    fs::write(
+        &unit_path,
+        "[Service]\nExecStart=/usr/bin/api\nLimitNOFILE=1024\n",
+    )
+    .expect("unit should be written");
+    for (dropin_dir, content) in [
+        (
+            "service.d",
+            "[Service]\nLimitNOFILE=65536\nEnvironment=FLEET=1\n",
+        ),
+        (
+            "web-.service.d",
+            "[Service]\nEnvironment=TIER=web\nOOMScoreAdjust=100\n",
+        ),
+        ("web-api.service.d", "[Service]\nOOMScoreAdjust=-500\n"),
+        ("db-.service.d", "[Service]\nOOMScoreAdjust=900\n"),
+    ] {
+        fs::create_dir(dir.join(dropin_dir)).expect("drop-in directory should be created");
+        // Same file name everywhere: the unit's own drop-in must still win
+        fs::write(dir.join(dropin_dir).join("50-defaults.conf"), content)
+            .expect("drop-in should be written");
+    }
+
+    let unit = load_unit(&unit_path).await.expect("unit should load");
+    let Unit::Service(service) = unit else {
+        panic!("expected loaded service");
+    };
+
+    assert_eq!(service.service.limit_nofile, Some(65536));
+    assert_eq!(service.service.oom_score_adjust, Some(-500));
+    assert_eq!(
+        service.service.environment,
+        [
+            ("FLEET".to_string(), "1".to_string()),
+            ("TIER".to_string(), "web".to_string())
+        ]
+    );
+
+    fs::remove_dir_all(&dir).expect("temp unit directory should be removed");
+}
+
+#[test]
+fn dropin_directories_include_set_property_control_directories() {
+    let directories = dropin_directories(Path::new("/usr/lib/systemd/system/demo.service"));
+
     assert_eq!(
-        service.install.wanted_by,
-        ["multi-user.target", "default.target"]
+        directories[..2],
+        [
+            PathBuf::from("/etc/systemd/system.control/demo.service.d"),
+            PathBuf::from("/run/systemd/system.control/demo.service.d"),
+        ]
     );
+    assert!(directories.contains(&PathBuf::from("/usr/lib/systemd/system/demo.service.d")));
+}
+
+#[test]
+fn collect_dropin_files_masks_lower_priority_names_and_sorts_by_file_name() {
+    let dir = temp_unit_dir("dropin-priority");
+    let etc = dir.join("etc/demo.service.d");
+    let usr = dir.join("usr/demo.service.d");
+    fs::create_dir_all(&etc).expect("etc drop-in directory should be created");
+    fs::create_dir_all(&usr).expect("usr drop-in directory should be created");
+    for path in [
+        etc.join("10-limits.conf"),
+        etc.join("30-env.conf"),
+        usr.join("10-limits.conf"),
+        usr.join("20-exec.conf"),
+        usr.join("README"),
+    ] {
+        fs::write(path, "[Service]\n").expect("drop-in should be written");
+    }
+
+    let files = collect_dropin_files(&[etc.clone(), usr.clone()]);
 
+    assert_eq!(
+        files,
+        [
+            etc.join("10-limits.conf"),
+            usr.join("20-exec.conf"),
+            etc.join("30-env.conf"),
+        ]
+    );
     fs::remove_dir_all(&dir).expect("temp unit directory should be removed");
 }
 
+#[test]
+fn later_dropins_override_scalars_and_extend_lists() {
+    let mut base = parsed(
+        r#"
+[Unit]
+Description=Base
+[Service]
+Type=simple
+Environment=A=1
+ExecStartPre=/bin/base-pre
+"#,
+    );
+    for dropin in [
+        "[Unit]\nDescription=First drop-in\n[Service]\nType=oneshot\nEnvironment=B=2\n",
+        "[Unit]\nDescription=Second drop-in\n[Service]\nExecStartPre=\n",
+    ] {
+        merge_parsed_files(&mut base, &parsed(dropin));
+    }
+    let service = parse_service("demo.service", &base).expect("service should parse");
+
+    assert_eq!(service.unit.description.as_deref(), Some("Second drop-in"));
+    assert_eq!(service.service.service_type, ServiceType::Oneshot);
+    assert_eq!(
+        service.service.environment,
+        [
+            ("A".to_string(), "1".to_string()),
+            ("B".to_string(), "2".to_string())
+        ]
+    );
+    assert!(service.service.exec_start_pre.is_empty());
+}
+
 #[test]
 fn merge_parsed_files_resets_keys_when_dropin_contains_empty_value() {
     let mut base = parsed(
@@ -705,6 +997,31 @@ Environment=MODE=override
     );
 }
 
+#[test]
+fn parse_service_applies_continuations_and_empty_assignments_within_one_file() {
+    let service = parse_service(
+        "demo.service",
+        &parsed(
+            r#"
+[service]
+ExecStart=/usr/bin/demo \
+    --flag
+RestrictAddressFamilies=AF_UNIX
+RestrictAddressFamilies=
+[Unit]
+Wants=a.service
+Wants=
+Wants=b.service
+"#,
+        ),
+    )
+    .expect("service should parse");
+
+    assert_eq!(service.service.exec_start, ["/usr/bin/demo  --flag"]);
+    assert_eq!(service.service.restrict_address_families, None);
+    assert_eq!(service.unit.wants, ["b.service"]);
+}
+
 #[tokio::test]
 async fn load_target_collects_local_wants_directory_units() {
     let dir = temp_unit_dir("target-wants");
@@ -728,7 +1045,146 @@ Description=Demo target
 
     assert_eq!(target.name, "demo.target");
     assert_eq!(target.unit.description.as_deref(), Some("Demo target"));
-    assert_eq!(target.wants_dir, ["alpha.service", "beta.timer"]);
+    assert_eq!(target.unit.wants_dir, ["alpha.service", "beta.timer"]);
 
     fs::remove_dir_all(&dir).expect("temp target directory should be removed");
 }
+
+#[tokio::test]
+async fn load_unit_collects_wants_and_requires_directories_of_any_type() {
+    let dir = temp_unit_dir("service-wants");
+    fs::write(dir.join("app.service"), "[Service]\nExecStart=/bin/true\n").unwrap();
+    fs::write(
+        dir.join("app.socket"),
+        "[Socket]\nListenStream=/run/app.sock\n",
+    )
+    .unwrap();
+    for (link_dir, unit) in [
+        ("app.service.wants", "metrics.service"),
+        ("app.service.requires", "db.service"),
+        ("app.socket.wants", "warmup.timer"),
+    ] {
+        fs::create_dir(dir.join(link_dir)).unwrap();
+        fs::write(dir.join(link_dir).join(unit), "").unwrap();
+    }
+
+    let service = load_unit(&dir.join("app.service")).await.unwrap();
+    let socket = load_unit(&dir.join("app.socket")).await.unwrap();
+    fs::remove_dir_all(&dir).expect("temp unit directory should be removed");
+
+    assert_eq!(service.wants_dir(), ["metrics.service"]);
+    assert_eq!(service.requires_dir(), ["db.service"]);
+    assert_eq!(socket.wants_dir(), ["warmup.timer"]);
+    assert!(socket.requires_dir().is_empty());
+}
+
+#[test]
+fn parse_manager_config_reads_defaults() {
+    let config = parse_manager_config(&parsed(
+        r#"
+[Manager]
+DefaultTimeoutStartSec=45s
+DefaultRestartSec=1s
+DefaultMemoryAccounting=yes
+DefaultTasksMax=4096
+DefaultEnvironment="LANG=C.UTF-8" EDITOR=vi
+DefaultLimitNOFILE=infinity
+UnitFilePermissions=warn
+TriggerLimitIntervalSec=30s
+TriggerLimitBurst=5000
+DefaultSlice=services.slice
+PersistStateHistory=yes
+"#,
+    ));
+
+    assert_eq!(
+        config.default_timeout_start_sec,
+        Some(Duration::from_secs(45))
+    );
+    assert_eq!(config.default_restart_sec, Some(Duration::from_secs(1)));
+    assert!(config.default_memory_accounting);
+    assert!(!config.default_cpu_accounting);
+    assert_eq!(config.default_tasks_max, Some(4096));
+    assert_eq!(
+        config.default_environment,
+        [
+            ("LANG".to_string(), "C.UTF-8".to_string()),
+            ("EDITOR".to_string(), "vi".to_string()),
+        ]
+    );
+    assert_eq!(config.default_limit_nofile, Some(u64::MAX));
+    assert_eq!(config.unit_file_permissions, UnitFilePermissions::Warn);
+    assert_eq!(
+        config.trigger_limit_interval_sec,
+        Some(Duration::from_secs(30))
+    );
+    assert_eq!(config.trigger_limit_burst, Some(5000));
+    assert_eq!(config.default_slice.as_deref(), Some("services.slice"));
+    assert!(config.persist_state_history);
+}
+
+#[test]
+fn parse_login_config_reads_handle_actions() {
+    let config = parse_login_config(&parsed("[Login]\nHandleLidSwitch=ignore\n"));
+    assert_eq!(config.handle_power_key, HandleAction::Poweroff);
+    assert_eq!(config.handle_lid_switch, HandleAction::Ignore);
+
+    let config = parse_login_config(&parsed(
+        "[Login]\nHandlePowerKey=hibernate\nHandleLidSwitch=bogus\n",
+    ));
+    assert_eq!(config.handle_power_key, HandleAction::Hibernate);
+    assert_eq!(config.handle_lid_switch, HandleAction::Suspend);
+}
+
+#[test]
+fn parse_login_config_reads_autovt_settings() {
+    let config = parse_login_config(&parsed("[Login]\n"));
+    assert_eq!((config.n_auto_vts, config.reserve_vt), (6, 6));
+
+    let config = parse_login_config(&parsed("[Login]\nNAutoVTs=2\nReserveVT=0\n"));
+    assert_eq!((config.n_auto_vts, config.reserve_vt), (2, 0));
+}
+
+#[test]
+fn parse_service_reads_credentials() {
+    let service = parse_service(
+        "creds.service",
+        &parsed(
+            r#"
+[Service]
+ExecStart=/bin/true
+LoadCredential=tls.key:/etc/ssl/private/demo.key
+LoadCredential=token
+LoadCredential=../escape:/etc/passwd
+LoadCredentialEncrypted=db.password:/etc/credstore.encrypted/db
+SetCredential=banner:hello: world
+SetCredential=novalue
+ImportCredential=demo.* shared
+"#,
+        ),
+    )
+    .expect("service should parse");
+
+    assert_eq!(
+        service.service.load_credential,
+        [
+            (
+                "tls.key".to_string(),
+                "/etc/ssl/private/demo.key".to_string()
+            ),
+            ("token".to_string(), String::new()),
+        ]
+    );
+    assert_eq!(
+        service.service.load_credential_encrypted,
+        [(
+            "db.password".to_string(),
+            "/etc/credstore.encrypted/db".to_string()
+        )]
+    );
+    assert_eq!(
+        service.service.set_credential,
+        [("banner".to_string(), "hello: world".to_string())]
+    );
+    assert_eq!(service.service.import_credential, ["demo.*", "shared"]);
+}
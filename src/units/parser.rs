@@ -15,8 +15,12 @@ pub type ParsedFile = HashMap<String, ParsedSection>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum ParseError {
-    #[error("Section '{0}' appears more than once")]
-    DuplicateSection(String),
+    #[error("line {line}, column {column}: {message}")]
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
 
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
@@ -32,54 +36,149 @@ pub enum ParseError {
 
     #[error("Parse error: {0}")]
     Generic(String),
+
+    /// A `FileCheck` turned the unit file or one of its drop-ins down
+    #[error("{0}")]
+    Refused(String),
+}
+
+impl ParseError {
+    /// 1-based line and column of the problem, if known
+    pub fn location(&self) -> Option<(usize, usize)> {
+        match self {
+            ParseError::Syntax { line, column, .. } => Some((*line, *column)),
+            _ => None,
+        }
+    }
 }
 
+/// Section names the unit loaders look up; headers spelling one of them in
+/// another case are mapped to this spelling
+const KNOWN_SECTIONS: &[&str] = &[
+    "[Unit]",
+    "[Service]",
+    "[Install]",
+    "[Socket]",
+    "[Timer]",
+    "[Mount]",
+    "[Path]",
+    "[Slice]",
+    "[Scope]",
+    "[Manager]",
+    "[Login]",
+];
+
 /// Parse a unit file from a string
+///
+/// Follows systemd.syntax(7): a trailing backslash continues an assignment
+/// on the next line (comment lines in between are skipped), `#`/`;` only
+/// start a comment at the beginning of a line, and a section that appears
+/// again continues where it left off.
 pub fn parse_file(content: &str) -> Result<ParsedFile, ParseError> {
-    let mut sections = HashMap::new();
-    let lines: Vec<&str> = content.lines().map(|s| s.trim()).collect();
+    let mut section_lines: HashMap<String, Vec<String>> = HashMap::new();
+    let mut current: Option<String> = None;
+    // Assignment continued from previous lines, backslashes replaced by spaces
+    let mut continued: Option<String> = None;
+
+    for (index, raw) in content.lines().enumerate() {
+        let line_number = index + 1;
+        if let Some(byte) = raw.find('\0') {
+            return Err(syntax_error(
+                raw,
+                line_number,
+                byte,
+                "NUL byte in unit file",
+            ));
+        }
+        let line = raw.trim();
+        if line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
 
-    let mut lines_iter = lines.iter().peekable();
+        let line = match continued.take() {
+            Some(mut pending) => {
+                pending.push_str(line);
+                pending
+            }
+            None if line.starts_with('[') => {
+                if !line.ends_with(']') {
+                    let end = raw.trim_end().len();
+                    return Err(syntax_error(
+                        raw,
+                        line_number,
+                        end,
+                        "missing ']' after section name",
+                    ));
+                }
+                let name = section_name(line);
+                section_lines.entry(name.clone()).or_default();
+                current = Some(name);
+                continue;
+            }
+            None => line.to_string(),
+        };
 
-    // Skip lines before the first section
-    while lines_iter.peek().map_or(false, |l| !l.starts_with('[')) {
-        lines_iter.next();
+        if let Some(head) = line.strip_suffix('\\') {
+            continued = Some(format!("{} ", head));
+            continue;
+        }
+        // Lines before the first section are skipped
+        if let Some(name) = &current {
+            section_lines.entry(name.clone()).or_default().push(line);
+        }
     }
 
-    // Get first section name
-    let Some(first_section) = lines_iter.next() else {
-        return Ok(sections); // Empty file
-    };
+    // A continuation running into the end of the file ends there
+    if let (Some(line), Some(name)) = (continued, &current) {
+        section_lines
+            .entry(name.clone())
+            .or_default()
+            .push(line.trim_end().to_string());
+    }
 
-    let mut current_section_name = first_section.to_string();
-    let mut current_section_lines = Vec::new();
+    Ok(section_lines
+        .into_iter()
+        .map(|(name, lines)| {
+            let section = parse_section(&lines);
+            (name, section)
+        })
+        .collect())
+}
 
-    for line in lines_iter {
-        if line.starts_with('[') {
-            // New section - store current one
-            if sections.contains_key(&current_section_name) {
-                return Err(ParseError::DuplicateSection(current_section_name));
-            }
-            sections.insert(
-                current_section_name.clone(),
-                parse_section(&current_section_lines),
-            );
-            current_section_name = line.to_string();
-            current_section_lines.clear();
-        } else {
-            current_section_lines.push(*line);
-        }
-    }
+/// Canonical spelling of a section header like `[service]`
+fn section_name(header: &str) -> String {
+    KNOWN_SECTIONS
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(header))
+        .map_or_else(|| header.to_string(), |known| known.to_string())
+}
 
-    // Insert last section
-    if !current_section_name.is_empty() {
-        if sections.contains_key(&current_section_name) {
-            return Err(ParseError::DuplicateSection(current_section_name));
+/// Parse a unit file from raw bytes, rejecting invalid UTF-8 with its position
+pub fn parse_bytes(content: &[u8]) -> Result<ParsedFile, ParseError> {
+    let text = std::str::from_utf8(content).map_err(|e| {
+        let valid = &content[..e.valid_up_to()];
+        let line_start = valid.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        // The valid prefix is UTF-8, so the column can be counted in chars
+        let column = String::from_utf8_lossy(&valid[line_start..])
+            .chars()
+            .count()
+            + 1;
+        ParseError::Syntax {
+            line: valid.iter().filter(|&&b| b == b'\n').count() + 1,
+            column,
+            message: "invalid UTF-8".to_string(),
         }
-        sections.insert(current_section_name, parse_section(&current_section_lines));
-    }
+    })?;
+    parse_file(text)
+}
 
-    Ok(sections)
+/// Syntax error at byte offset `byte` of `raw`, reported as a 1-based column
+fn syntax_error(raw: &str, line: usize, byte: usize, message: &str) -> ParseError {
+    ParseError::Syntax {
+        line,
+        column: raw[..byte].chars().count() + 1,
+        message: message.to_string(),
+    }
 }
 
 /// Keys that accept space-separated multiple values
@@ -128,13 +227,17 @@ const SINGLE_VALUE_KEYS: &[&str] = &[
 ];
 
 /// Parse a single section's lines into key-value pairs
-fn parse_section(lines: &[&str]) -> ParsedSection {
+///
+/// An empty assignment (`Key=`) drops the values assigned before it and
+/// leaves an empty value in front as a reset marker, so that merging a
+/// drop-in also clears the values from the files before it.
+fn parse_section(lines: &[String]) -> ParsedSection {
     let mut entries: ParsedSection = HashMap::new();
     let mut entry_number = 0u32;
 
     for line in lines {
-        // Skip comments and empty lines
-        if line.starts_with('#') || line.starts_with(';') || line.is_empty() {
+        // Comments were dropped by parse_file; skip empty lines
+        if line.is_empty() {
             continue;
         }
 
@@ -147,6 +250,14 @@ fn parse_section(lines: &[&str]) -> ParsedSection {
         let value = value.trim_start_matches('=').trim();
         let name = name.trim().to_uppercase();
 
+        let vec = entries.entry(name.clone()).or_default();
+        if value.is_empty() {
+            vec.clear();
+            vec.push((entry_number, String::new()));
+            entry_number += 1;
+            continue;
+        }
+
         // Determine separator: space for dependency keys, single value for paths/commands, comma otherwise
         let values: Vec<String> = if SPACE_SEPARATED_KEYS.contains(&name.as_str()) {
             // Split on whitespace for dependency keys
@@ -156,14 +267,14 @@ fn parse_section(lines: &[&str]) -> ParsedSection {
             vec![value.to_string()]
         } else {
             // Split on comma for other keys (like Environment=)
-            value.split(',').map(|x| x.trim().to_string()).collect()
+            value
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect()
         };
 
-        let vec = entries.entry(name).or_default();
         for v in values {
-            // Keep ALL values including empty ones.
-            // Empty values (Key=) signal "clear all previous values" in systemd drop-ins.
-            // The merge function will handle the reset logic.
             vec.push((entry_number, v));
             entry_number += 1;
         }
@@ -174,8 +285,8 @@ fn parse_section(lines: &[&str]) -> ParsedSection {
 
 /// Parse an async unit file from disk
 pub async fn parse_unit_file(path: &Path) -> Result<ParsedFile, ParseError> {
-    let content = tokio::fs::read_to_string(path).await?;
-    parse_file(&content)
+    let content = tokio::fs::read(crate::root::resolve(path)).await?;
+    parse_bytes(&content)
 }
 
 /// Parse Environment= values using shell-like quoting
@@ -381,20 +492,114 @@ After    =    network.target
     }
 
     #[test]
-    fn test_duplicate_section_error() {
+    fn test_repeated_sections_are_merged() {
         let content = r#"
 [Unit]
 Description=First
 
+[Install]
+WantedBy=multi-user.target
+
 [Unit]
-Description=Second
+After=network.target
 "#;
-        let result = parse_file(content);
-        assert!(result.is_err());
-        assert!(matches!(
-            result.unwrap_err(),
-            ParseError::DuplicateSection(_)
-        ));
+        let parsed = parse_file(content).unwrap();
+        let unit = &parsed["[Unit]"];
+        assert_eq!(extract_values(unit["DESCRIPTION"].clone()), vec!["First"]);
+        assert_eq!(
+            extract_values(unit["AFTER"].clone()),
+            vec!["network.target"]
+        );
+        assert_eq!(parsed.len(), 2);
+    }
+
+    #[test]
+    fn test_section_names_are_case_insensitive() {
+        let content = "[service]\nExecStart=/bin/true\n[X-Custom]\nFoo=bar\n";
+        let parsed = parse_file(content).unwrap();
+        assert!(parsed.contains_key("[Service]"));
+        assert!(parsed.contains_key("[X-Custom]"));
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        let content = r#"
+[Service]
+ExecStart=/usr/bin/daemon \
+    --foreground \
+# comment lines inside a continuation are skipped
+    --verbose
+Environment=A=1 \
+  B=2
+"#;
+        let parsed = parse_file(content).unwrap();
+        let service = &parsed["[Service]"];
+        assert_eq!(
+            extract_values(service["EXECSTART"].clone()),
+            vec!["/usr/bin/daemon  --foreground  --verbose"]
+        );
+        assert_eq!(
+            extract_values(service["ENVIRONMENT"].clone()),
+            vec!["A=1  B=2"]
+        );
+    }
+
+    #[test]
+    fn test_continuation_at_end_of_file() {
+        let parsed = parse_file("[Unit]\nDescription=Trailing \\").unwrap();
+        assert_eq!(
+            extract_values(parsed["[Unit]"]["DESCRIPTION"].clone()),
+            vec!["Trailing"]
+        );
+    }
+
+    #[test]
+    fn test_comment_characters_after_value_are_kept() {
+        let content = "[Service]\nExecStart=/bin/echo #not-a-comment ;nor-this\n";
+        let parsed = parse_file(content).unwrap();
+        assert_eq!(
+            extract_values(parsed["[Service]"]["EXECSTART"].clone()),
+            vec!["/bin/echo #not-a-comment ;nor-this"]
+        );
+    }
+
+    #[test]
+    fn test_empty_assignment_resets_earlier_values() {
+        let content = r#"
+[Service]
+ExecStartPre=/bin/one
+ExecStartPre=
+ExecStartPre=/bin/two
+[Unit]
+After=a.target
+After=
+"#;
+        let parsed = parse_file(content).unwrap();
+        assert_eq!(
+            extract_values(parsed["[Service]"]["EXECSTARTPRE"].clone()),
+            vec!["", "/bin/two"]
+        );
+        assert_eq!(extract_values(parsed["[Unit]"]["AFTER"].clone()), vec![""]);
+    }
+
+    #[test]
+    fn test_unterminated_section_header_reports_position() {
+        let err = parse_file("[Unit]\nDescription=Test\n  [Service\n").unwrap_err();
+        assert_eq!(err.location(), Some((3, 11)));
+        assert_eq!(
+            err.to_string(),
+            "line 3, column 11: missing ']' after section name"
+        );
+    }
+
+    #[test]
+    fn test_nul_and_invalid_utf8_report_position() {
+        let err = parse_file("[Unit]\nDescription=a\0b\n").unwrap_err();
+        assert_eq!(err.location(), Some((2, 14)));
+
+        let err = parse_bytes(b"[Unit]\nDescription=caf\xc3\xa9 \xff\n").unwrap_err();
+        assert_eq!(err.location(), Some((2, 18)));
+        assert!(err.to_string().ends_with("invalid UTF-8"));
     }
 
     #[test]
@@ -33,7 +33,7 @@ pub struct PathSection {
     pub make_directory: bool,
     /// Mode for created directory
     pub directory_mode: Option<u32>,
-    /// Trigger regardless of any other dependency
+    /// Window for TriggerLimitBurst= (TriggerLimitIntervalSec=)
     pub trigger_limit_interval_sec: Option<std::time::Duration>,
     /// Maximum triggers within the interval
     pub trigger_limit_burst: Option<u32>,
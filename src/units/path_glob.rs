@@ -0,0 +1,153 @@
+//! Glob expansion for path-like directives
+//!
+//! ConditionPathExistsGlob=, EnvironmentFile= and PathExistsGlob= take
+//! fnmatch-style wildcards. A backslash escapes the next character, so
+//! `/etc/foo\*` names a file literally called `foo*`. Like glob(3), `*` and
+//! `?` don't match a leading dot.
+
+use std::path::{Path, PathBuf};
+
+const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+
+/// Whether the pattern contains an unescaped wildcard
+pub fn has_glob_chars(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if GLOB_CHARS.contains(&c) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Drop escaping backslashes from a pattern without wildcards
+fn unescape(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push(chars.next().unwrap_or('\\')),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Translate backslash escapes to the `glob` crate's `[x]` form
+fn to_glob_pattern(pattern: &str) -> String {
+    let mut out = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let escaped = chars.next().unwrap_or('\\');
+                out.push_str(&glob::Pattern::escape(&escaped.to_string()));
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn match_options() -> glob::MatchOptions {
+    glob::MatchOptions {
+        case_sensitive: true,
+        require_literal_separator: true,
+        require_literal_leading_dot: true,
+    }
+}
+
+/// Paths matching `pattern`, sorted; a pattern without wildcards yields itself
+pub fn expand_path_glob(pattern: &str) -> Vec<PathBuf> {
+    if !has_glob_chars(pattern) {
+        return vec![PathBuf::from(unescape(pattern))];
+    }
+    let Ok(paths) = glob::glob_with(&to_glob_pattern(pattern), match_options()) else {
+        log::warn!("Invalid glob pattern: {}", pattern);
+        return Vec::new();
+    };
+    let mut matched: Vec<PathBuf> = paths.flatten().collect();
+    matched.sort();
+    matched
+}
+
+/// Whether at least one existing path matches `pattern`
+pub fn path_glob_matches_any(pattern: &str) -> bool {
+    expand_path_glob(pattern).iter().any(|path| path.exists())
+}
+
+/// Deepest ancestor directory of `pattern` that contains no wildcards
+pub fn glob_base_dir(pattern: &str) -> PathBuf {
+    for ancestor in Path::new(pattern).ancestors() {
+        if !has_glob_chars(&ancestor.to_string_lossy()) {
+            return PathBuf::from(unescape(&ancestor.to_string_lossy()));
+        }
+    }
+    PathBuf::from("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "sysd-path-glob-{}-{}-{}",
+            label,
+            std::process::id(),
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn escaped_wildcards_are_literal() {
+        assert!(has_glob_chars("/etc/*.conf"));
+        assert!(has_glob_chars("/dev/tty[0-9]"));
+        assert!(!has_glob_chars("/etc/foo\\*"));
+        assert!(!has_glob_chars("/etc/plain"));
+        assert_eq!(
+            expand_path_glob("/etc/foo\\*bar"),
+            [PathBuf::from("/etc/foo*bar")]
+        );
+        assert_eq!(
+            glob_base_dir("/var/lib/a\\*b/*.conf"),
+            Path::new("/var/lib/a*b")
+        );
+    }
+
+    #[test]
+    fn expansion_sorts_matches_and_skips_hidden_files() {
+        let dir = temp_dir("expand");
+        for name in ["b.env", "a.env", ".hidden.env", "star*.env", "other.txt"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let base = dir.to_string_lossy();
+
+        assert_eq!(
+            expand_path_glob(&format!("{}/*.env", base)),
+            [dir.join("a.env"), dir.join("b.env"), dir.join("star*.env")]
+        );
+        assert_eq!(
+            expand_path_glob(&format!("{}/star\\*.env", base)),
+            [dir.join("star*.env")]
+        );
+        assert_eq!(
+            expand_path_glob(&format!("{}/\\[ab].env", base)),
+            [dir.join("[ab].env")]
+        );
+        assert!(path_glob_matches_any(&format!("{}/?.env", base)));
+        assert!(!path_glob_matches_any(&format!("{}/*.missing", base)));
+        assert!(!path_glob_matches_any(&format!("{}/[xy].env", base)));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
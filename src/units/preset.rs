@@ -0,0 +1,169 @@
+//! Preset policy from *.preset files (systemd.preset(5))
+//!
+//! Decides what `preset` and `preset-all` do with a unit. Files come from the
+//! preset directories below; a file masks one with the same name in a later
+//! directory, and the rest are read in file name order. The first
+//! `enable PATTERN`, `disable PATTERN` or `ignore PATTERN` line whose glob
+//! matches the unit name decides. Units no line matches are enabled, as in
+//! systemd.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Preset directories of the system manager, highest priority first
+pub const SYSTEM_PRESET_DIRS: [&str; 4] = [
+    "/etc/systemd/system-preset",
+    "/run/systemd/system-preset",
+    "/usr/local/lib/systemd/system-preset",
+    "/usr/lib/systemd/system-preset",
+];
+
+/// Preset directories of user managers, highest priority first
+pub const USER_PRESET_DIRS: [&str; 4] = [
+    "/etc/systemd/user-preset",
+    "/run/systemd/user-preset",
+    "/usr/local/lib/systemd/user-preset",
+    "/usr/lib/systemd/user-preset",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresetAction {
+    Enable,
+    Disable,
+    /// Leave the unit's links as they are
+    Ignore,
+}
+
+impl PresetAction {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "enable" => Some(Self::Enable),
+            "disable" => Some(Self::Disable),
+            "ignore" => Some(Self::Ignore),
+            _ => None,
+        }
+    }
+}
+
+/// Preset rules in the order they are tried
+#[derive(Debug, Clone, Default)]
+pub struct Presets {
+    rules: Vec<(glob::Pattern, PresetAction)>,
+}
+
+impl Presets {
+    /// Rules from the system or user preset directories (below `--root`)
+    pub fn load(user_mode: bool) -> Self {
+        let dirs = if user_mode {
+            USER_PRESET_DIRS
+        } else {
+            SYSTEM_PRESET_DIRS
+        };
+        let dirs: Vec<PathBuf> = dirs.iter().map(crate::root::path).collect();
+        Self::from_dirs(&dirs)
+    }
+
+    /// Rules from the *.preset files in `dirs` (highest priority first)
+    pub fn from_dirs(dirs: &[PathBuf]) -> Self {
+        let mut files: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for dir in dirs {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if name.ends_with(".preset") {
+                    files.entry(name).or_insert_with(|| entry.path());
+                }
+            }
+        }
+
+        let mut presets = Self::default();
+        for path in files.values() {
+            match std::fs::read_to_string(path) {
+                Ok(content) => presets.add_rules(path, &content),
+                Err(e) => log::warn!("Failed to read {}: {}", path.display(), e),
+            }
+        }
+        presets
+    }
+
+    /// Rules of one preset file, after any already known
+    pub fn add_rules(&mut self, path: &Path, content: &str) {
+        for (number, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let (Some(verb), Some(pattern)) = (words.next(), words.next()) else {
+                log::warn!("{}:{}: missing unit pattern", path.display(), number + 1);
+                continue;
+            };
+            let Some(action) = PresetAction::parse(verb) else {
+                log::warn!("{}:{}: unknown verb {}", path.display(), number + 1, verb);
+                continue;
+            };
+            match glob::Pattern::new(pattern) {
+                Ok(pattern) => self.rules.push((pattern, action)),
+                Err(e) => log::warn!("{}:{}: {}", path.display(), number + 1, e),
+            }
+        }
+    }
+
+    /// What presetting `name` does
+    pub fn action(&self, name: &str) -> PresetAction {
+        self.rules
+            .iter()
+            .find(|(pattern, _)| pattern.matches(name))
+            .map_or(PresetAction::Enable, |(_, action)| *action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn first_matching_rule_wins_and_unmatched_units_are_enabled() {
+        let mut presets = Presets::default();
+        presets.add_rules(
+            Path::new("90-default.preset"),
+            "# comment\nenable sshd.service\n\ndisable *\n",
+        );
+        presets.add_rules(Path::new("99-late.preset"), "enable *.socket\n");
+
+        assert_eq!(presets.action("sshd.service"), PresetAction::Enable);
+        assert_eq!(presets.action("cups.service"), PresetAction::Disable);
+        assert_eq!(presets.action("cups.socket"), PresetAction::Disable);
+        assert_eq!(
+            Presets::default().action("any.service"),
+            PresetAction::Enable
+        );
+    }
+
+    #[test]
+    fn files_are_read_in_name_order_and_masked_by_higher_priority_dirs() {
+        let root = std::env::temp_dir().join(format!("sysd-preset-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let etc = root.join("etc");
+        let lib = root.join("lib");
+        fs::create_dir_all(&etc).unwrap();
+        fs::create_dir_all(&lib).unwrap();
+        fs::write(lib.join("50-vendor.preset"), "enable cups.service\n").unwrap();
+        fs::write(lib.join("90-default.preset"), "disable *\n").unwrap();
+        fs::write(etc.join("10-admin.preset"), "ignore keep.service\n").unwrap();
+        fs::write(etc.join("90-default.preset"), "enable *\n").unwrap();
+        fs::write(lib.join("README"), "disable cups.service\n").unwrap();
+
+        let presets = Presets::from_dirs(&[etc, lib]);
+        let _ = fs::remove_dir_all(&root);
+
+        assert_eq!(presets.action("keep.service"), PresetAction::Ignore);
+        assert_eq!(presets.action("cups.service"), PresetAction::Enable);
+        assert_eq!(presets.action("other.service"), PresetAction::Enable);
+    }
+}
@@ -0,0 +1,373 @@
+//! Exposure score of a service's sandboxing (`sysd analyze security`)
+//!
+//! Each directive below is weighted by how much of the system a service
+//! can reach without it. A directive that is not set counts fully, a partial
+//! setting (ProtectSystem=yes rather than strict, a deny list rather than an
+//! allow list) counts in part. The weighted sum is scaled to 0-10 and named
+//! with the levels systemd-analyze uses, from PERFECT to UNSAFE.
+
+use super::{DevicePolicy, ProtectHome, ProtectProc, ProtectSystem, ServiceSection};
+
+/// One directive of the report
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityCheck {
+    /// Directive, e.g. "NoNewPrivileges="
+    pub directive: &'static str,
+    /// What the directive protects
+    pub description: &'static str,
+    /// Part of the overall exposure (0-10) this directive accounts for
+    pub exposure: f64,
+    /// Setting that would remove the exposure; None when there is none
+    pub recommendation: Option<&'static str>,
+}
+
+/// Sandboxing report of one service
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecurityReport {
+    pub checks: Vec<SecurityCheck>,
+    /// 0.0 (fully sandboxed) to 10.0 (no sandboxing at all)
+    pub exposure: f64,
+}
+
+impl SecurityReport {
+    /// "PERFECT", "SAFE", "OK", "MEDIUM", "EXPOSED" or "UNSAFE"
+    pub fn level(&self) -> &'static str {
+        match self.exposure {
+            e if e < 1.0 => "PERFECT",
+            e if e < 2.0 => "SAFE",
+            e if e < 5.0 => "OK",
+            e if e < 7.0 => "MEDIUM",
+            e if e < 9.0 => "EXPOSED",
+            _ => "UNSAFE",
+        }
+    }
+}
+
+struct Rule {
+    directive: &'static str,
+    description: &'static str,
+    recommendation: &'static str,
+    weight: u32,
+    /// 0.0 when the service is fully protected, 1.0 when not at all
+    exposed: fn(&ServiceSection) -> f64,
+}
+
+const RULES: [Rule; 24] = [
+    Rule {
+        directive: "User=/DynamicUser=",
+        description: "Service runs as an unprivileged user",
+        recommendation: "User= or DynamicUser=yes",
+        weight: 2000,
+        exposed: |s| {
+            let unprivileged = s.user.as_deref().is_some_and(|u| u != "root" && u != "0");
+            unset(s.dynamic_user || unprivileged)
+        },
+    },
+    Rule {
+        directive: "NoNewPrivileges=",
+        description: "Service cannot gain privileges through setuid or file capabilities",
+        recommendation: "NoNewPrivileges=yes",
+        weight: 1000,
+        exposed: |s| unset(s.no_new_privileges),
+    },
+    Rule {
+        directive: "CapabilityBoundingSet=",
+        description: "Service cannot acquire capabilities, CAP_SYS_ADMIN above all",
+        recommendation: "CapabilityBoundingSet= listing only the capabilities needed",
+        weight: 1500,
+        exposed: |s| capability_exposure(&s.capability_bounding_set),
+    },
+    Rule {
+        directive: "AmbientCapabilities=",
+        description: "Service processes start without extra capabilities",
+        recommendation: "AmbientCapabilities= left empty",
+        weight: 500,
+        exposed: |s| unset(s.ambient_capabilities.is_empty()),
+    },
+    Rule {
+        directive: "ProtectSystem=",
+        description: "Service cannot modify the operating system",
+        recommendation: "ProtectSystem=strict",
+        weight: 1000,
+        exposed: |s| match s.protect_system {
+            ProtectSystem::Strict => 0.0,
+            ProtectSystem::Full => 0.3,
+            ProtectSystem::Yes => 0.5,
+            ProtectSystem::No => 1.0,
+        },
+    },
+    Rule {
+        directive: "ProtectHome=",
+        description: "Service cannot access home directories",
+        recommendation: "ProtectHome=yes",
+        weight: 1000,
+        exposed: |s| match s.protect_home {
+            ProtectHome::Yes | ProtectHome::Tmpfs => 0.0,
+            ProtectHome::ReadOnly => 0.3,
+            ProtectHome::No => 1.0,
+        },
+    },
+    Rule {
+        directive: "PrivateTmp=",
+        description: "Service cannot see other software's temporary files",
+        recommendation: "PrivateTmp=yes",
+        weight: 500,
+        exposed: |s| unset(s.private_tmp),
+    },
+    Rule {
+        directive: "PrivateDevices=",
+        description: "Service cannot access hardware devices",
+        recommendation: "PrivateDevices=yes or DevicePolicy=closed",
+        weight: 1000,
+        exposed: |s| unset(s.private_devices || s.device_policy != DevicePolicy::Auto),
+    },
+    Rule {
+        directive: "PrivateNetwork=",
+        description: "Service has no access to the host's network",
+        recommendation: "PrivateNetwork=yes, if it needs no network",
+        weight: 500,
+        exposed: |s| unset(s.private_network || s.network_namespace_path.is_some()),
+    },
+    Rule {
+        directive: "ProtectKernelModules=",
+        description: "Service cannot load or unload kernel modules",
+        recommendation: "ProtectKernelModules=yes",
+        weight: 1000,
+        exposed: |s| unset(s.protect_kernel_modules),
+    },
+    Rule {
+        directive: "ProtectKernelTunables=",
+        description: "Service cannot change kernel tunables in /proc/sys and /sys",
+        recommendation: "ProtectKernelTunables=yes",
+        weight: 1000,
+        exposed: |s| unset(s.protect_kernel_tunables),
+    },
+    Rule {
+        directive: "ProtectKernelLogs=",
+        description: "Service cannot read or write the kernel log",
+        recommendation: "ProtectKernelLogs=yes",
+        weight: 500,
+        exposed: |s| unset(s.protect_kernel_logs),
+    },
+    Rule {
+        directive: "ProtectControlGroups=",
+        description: "Service cannot modify the control group tree",
+        recommendation: "ProtectControlGroups=yes",
+        weight: 1000,
+        exposed: |s| unset(s.protect_control_groups),
+    },
+    Rule {
+        directive: "ProtectClock=",
+        description: "Service cannot set the system clock",
+        recommendation: "ProtectClock=yes",
+        weight: 500,
+        exposed: |s| unset(s.protect_clock),
+    },
+    Rule {
+        directive: "ProtectHostname=",
+        description: "Service cannot change the hostname",
+        recommendation: "ProtectHostname=yes",
+        weight: 500,
+        exposed: |s| unset(s.protect_hostname),
+    },
+    Rule {
+        directive: "ProtectProc=",
+        description: "Service cannot see other users' processes",
+        recommendation: "ProtectProc=invisible",
+        weight: 500,
+        exposed: |s| unset(s.protect_proc != ProtectProc::Default),
+    },
+    Rule {
+        directive: "RestrictNamespaces=",
+        description: "Service cannot create namespaces",
+        recommendation: "RestrictNamespaces=yes",
+        weight: 1000,
+        exposed: |s| match &s.restrict_namespaces {
+            None => 1.0,
+            Some(allowed) if allowed.is_empty() => 0.0,
+            Some(_) => 0.3,
+        },
+    },
+    Rule {
+        directive: "RestrictAddressFamilies=",
+        description: "Service can only use the socket address families it needs",
+        recommendation: "RestrictAddressFamilies= listing the families needed",
+        weight: 1000,
+        exposed: |s| match &s.restrict_address_families {
+            None => 1.0,
+            Some(families) if is_deny_list(families) => 0.5,
+            Some(_) => 0.0,
+        },
+    },
+    Rule {
+        directive: "RestrictRealtime=",
+        description: "Service cannot take over the CPU with realtime scheduling",
+        recommendation: "RestrictRealtime=yes",
+        weight: 500,
+        exposed: |s| unset(s.restrict_realtime),
+    },
+    Rule {
+        directive: "RestrictSUIDSGID=",
+        description: "Service cannot create setuid or setgid files",
+        recommendation: "RestrictSUIDSGID=yes",
+        weight: 500,
+        exposed: |s| unset(s.restrict_suid_sgid),
+    },
+    Rule {
+        directive: "MemoryDenyWriteExecute=",
+        description: "Service cannot create writable and executable memory",
+        recommendation: "MemoryDenyWriteExecute=yes",
+        weight: 500,
+        exposed: |s| unset(s.memory_deny_write_execute),
+    },
+    Rule {
+        directive: "LockPersonality=",
+        description: "Service cannot change its execution domain",
+        recommendation: "LockPersonality=yes",
+        weight: 100,
+        exposed: |s| unset(s.lock_personality),
+    },
+    Rule {
+        directive: "SystemCallFilter=",
+        description: "Service can only use the system calls it needs",
+        recommendation: "SystemCallFilter=@system-service",
+        weight: 1500,
+        exposed: |s| match s.system_call_filter.as_slice() {
+            [] => 1.0,
+            filter if is_deny_list(filter) => 0.5,
+            _ => 0.0,
+        },
+    },
+    Rule {
+        directive: "SystemCallArchitectures=",
+        description: "Service cannot use system calls of foreign architectures",
+        recommendation: "SystemCallArchitectures=native",
+        weight: 500,
+        exposed: |s| unset(s.system_call_architectures.iter().any(|a| a == "native")),
+    },
+];
+
+/// Score the sandboxing directives of a service
+pub fn analyze_security(service: &ServiceSection) -> SecurityReport {
+    let total: u32 = RULES.iter().map(|rule| rule.weight).sum();
+    let checks: Vec<SecurityCheck> = RULES
+        .iter()
+        .map(|rule| {
+            let exposed = (rule.exposed)(service);
+            SecurityCheck {
+                directive: rule.directive,
+                description: rule.description,
+                exposure: exposed * f64::from(rule.weight) * 10.0 / f64::from(total),
+                recommendation: (exposed > 0.0).then_some(rule.recommendation),
+            }
+        })
+        .collect();
+    let exposure = checks.iter().map(|check| check.exposure).sum();
+    SecurityReport { checks, exposure }
+}
+
+fn unset(protected: bool) -> f64 {
+    if protected {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+/// A leading "~" turns a list into the entries to deny
+fn is_deny_list(list: &[String]) -> bool {
+    list.first().is_some_and(|first| first.starts_with('~'))
+}
+
+fn capability_exposure(bounding_set: &[String]) -> f64 {
+    if bounding_set.is_empty() {
+        return 1.0;
+    }
+    let sys_admin = bounding_set
+        .iter()
+        .any(|cap| cap.trim_start_matches('~') == "CAP_SYS_ADMIN");
+    match (is_deny_list(bounding_set), sys_admin) {
+        (false, false) => 0.0,
+        (false, true) => 0.5,
+        (true, true) => 0.3,
+        (true, false) => 0.8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::units::Service;
+
+    #[test]
+    fn unsandboxed_services_are_unsafe_and_hardened_ones_perfect() {
+        let plain = Service::new("plain.service".to_string());
+        let report = analyze_security(&plain.service);
+        assert_eq!(report.level(), "UNSAFE");
+        assert!((report.exposure - 10.0).abs() < 1e-9);
+        assert!(report
+            .checks
+            .iter()
+            .all(|check| check.recommendation.is_some()));
+
+        let mut hardened = Service::new("hardened.service".to_string());
+        let s = &mut hardened.service;
+        s.dynamic_user = true;
+        s.no_new_privileges = true;
+        s.capability_bounding_set = vec!["CAP_NET_BIND_SERVICE".to_string()];
+        s.protect_system = ProtectSystem::Strict;
+        s.protect_home = ProtectHome::Yes;
+        s.private_tmp = true;
+        s.private_devices = true;
+        s.private_network = true;
+        s.protect_kernel_modules = true;
+        s.protect_kernel_tunables = true;
+        s.protect_kernel_logs = true;
+        s.protect_control_groups = true;
+        s.protect_clock = true;
+        s.protect_hostname = true;
+        s.protect_proc = ProtectProc::Invisible;
+        s.restrict_namespaces = Some(Vec::new());
+        s.restrict_address_families = Some(vec!["AF_UNIX".to_string()]);
+        s.restrict_realtime = true;
+        s.restrict_suid_sgid = true;
+        s.memory_deny_write_execute = true;
+        s.lock_personality = true;
+        s.system_call_filter = vec!["@system-service".to_string()];
+        s.system_call_architectures = vec!["native".to_string()];
+        let report = analyze_security(&hardened.service);
+        assert_eq!(report.level(), "PERFECT");
+        assert_eq!(report.exposure, 0.0);
+        assert!(report
+            .checks
+            .iter()
+            .all(|check| check.recommendation.is_none()));
+    }
+
+    #[test]
+    fn partial_settings_count_in_part() {
+        let mut service = Service::new("partial.service".to_string());
+        service.service.user = Some("www-data".to_string());
+        service.service.protect_system = ProtectSystem::Full;
+        service.service.system_call_filter = vec!["~@mount".to_string()];
+        let report = analyze_security(&service.service);
+
+        let check = |directive: &str| {
+            report
+                .checks
+                .iter()
+                .find(|check| check.directive == directive)
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(check("User=/DynamicUser=").exposure, 0.0);
+        assert!(check("ProtectSystem=").exposure > 0.0);
+        assert!(check("ProtectSystem=").exposure < check("ProtectHome=").exposure);
+        assert_eq!(
+            check("SystemCallFilter=").recommendation,
+            Some("SystemCallFilter=@system-service")
+        );
+        assert!(report.exposure < 10.0);
+        assert_eq!(report.level(), "EXPOSED");
+    }
+}
@@ -5,6 +5,8 @@
 use std::path::PathBuf;
 use std::time::Duration;
 
+use super::{IpPrefix, SocketBindRule};
+
 /// Service type determines startup notification
 #[derive(Debug, Clone, Default, PartialEq)]
 pub enum ServiceType {
@@ -105,6 +107,56 @@ impl NotifyAccess {
     }
 }
 
+/// ExecHealthCheck= (a sysd extension): how a running service is probed
+#[derive(Debug, Clone, PartialEq)]
+pub enum HealthCheck {
+    /// A command line that exits 0 while the service is healthy
+    Exec(String),
+    /// tcp://HOST:PORT accepts connections
+    Tcp(String),
+    /// http://HOST[:PORT]/PATH answers GET with a 2xx status
+    Http {
+        /// HOST:PORT to connect to (port 80 unless given)
+        address: String,
+        /// Host header
+        host: String,
+        path: String,
+    },
+}
+
+impl HealthCheck {
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        if let Some(address) = s.strip_prefix("tcp://") {
+            return (!address.is_empty()).then(|| Self::Tcp(address.to_string()));
+        }
+        let Some(rest) = s.strip_prefix("http://") else {
+            return (!s.is_empty()).then(|| Self::Exec(s.to_string()));
+        };
+        let (host, path) = match rest.find('/') {
+            Some(i) => rest.split_at(i),
+            None => (rest, "/"),
+        };
+        if host.is_empty() {
+            return None;
+        }
+        // A colon after the host (or after a bracketed IPv6 address) starts the port
+        let has_port = host
+            .rfind(':')
+            .is_some_and(|i| !host[..i].contains(':') || host[..i].ends_with(']'));
+        let address = if has_port {
+            host.to_string()
+        } else {
+            format!("{}:80", host)
+        };
+        Some(Self::Http {
+            address,
+            host: host.to_string(),
+            path: path.to_string(),
+        })
+    }
+}
+
 /// DevicePolicy= controls device access restrictions
 #[derive(Debug, Clone, Default, PartialEq)]
 pub enum DevicePolicy {
@@ -217,18 +269,77 @@ impl ProtectProc {
     }
 }
 
+/// KeyringMode= settings for the session keyring
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum KeyringMode {
+    #[default]
+    Inherit, // Keep the manager's session keyring
+    Private, // Fresh anonymous session keyring
+    Shared,  // Fresh session keyring linked to the user keyring
+}
+
+impl KeyringMode {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "inherit" => Some(Self::Inherit),
+            "private" => Some(Self::Private),
+            "shared" => Some(Self::Shared),
+            _ => None,
+        }
+    }
+}
+
+/// SELinuxContext= / AppArmorProfile= / SmackProcessLabel= - the MAC label
+/// the service is executed with
+#[derive(Debug, Clone, PartialEq)]
+pub struct MacLabel {
+    pub label: String,
+    /// "-" prefix: run without the label when it cannot be applied
+    pub ignore_failure: bool,
+}
+
+impl MacLabel {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (label, ignore_failure) = match s.strip_prefix('-') {
+            Some(rest) => (rest.trim(), true),
+            None => (s.trim(), false),
+        };
+        if label.is_empty() {
+            return None;
+        }
+        Some(Self {
+            label: label.to_string(),
+            ignore_failure,
+        })
+    }
+}
+
 /// [Unit] section
 #[derive(Debug, Clone)]
 pub struct UnitSection {
     pub description: Option<String>,
+    /// Documentation= - man:, http(s):, file: or info: URIs
+    pub documentation: Vec<String>,
     pub after: Vec<String>,
     pub before: Vec<String>,
     pub requires: Vec<String>,
     pub wants: Vec<String>,
+    /// Units linked into a `<name>.wants/` directory (`enable` of a unit
+    /// with WantedBy= this one), pulled in like Wants=
+    pub wants_dir: Vec<String>,
+    /// Units linked into a `<name>.requires/` directory, like Requires=
+    pub requires_dir: Vec<String>,
+    /// Requisite= - like Requires=, but the unit must already be active;
+    /// it is never started for us
+    pub requisite: Vec<String>,
     pub conflicts: Vec<String>,
     /// BindsTo= - Hard dependency, stop this unit when bound unit stops
     pub binds_to: Vec<String>,
+    /// PartOf= - stop this unit when the listed unit stops
+    pub part_of: Vec<String>,
     pub condition_path_exists: Vec<String>,
+    /// ConditionPathExistsGlob= - at least one path matching the pattern exists
+    pub condition_path_exists_glob: Vec<String>,
     pub condition_directory_not_empty: Vec<String>,
     /// ConditionVirtualization= - check for VM/container environment
     pub condition_virtualization: Vec<String>,
@@ -246,19 +357,28 @@ pub struct UnitSection {
     pub default_dependencies: bool,
     /// IgnoreOnIsolate= - Don't stop this unit during isolate operations
     pub ignore_on_isolate: bool,
+    /// SurviveFinalKillSignal= - keep running through `sysd soft-reboot`:
+    /// neither stopped nor killed, and adopted by the next sysd
+    pub survive_final_kill_signal: bool,
 }
 
 impl Default for UnitSection {
     fn default() -> Self {
         Self {
             description: None,
+            documentation: Vec::new(),
             after: Vec::new(),
             before: Vec::new(),
             requires: Vec::new(),
             wants: Vec::new(),
+            wants_dir: Vec::new(),
+            requires_dir: Vec::new(),
+            requisite: Vec::new(),
             conflicts: Vec::new(),
             binds_to: Vec::new(),
+            part_of: Vec::new(),
             condition_path_exists: Vec::new(),
+            condition_path_exists_glob: Vec::new(),
             condition_directory_not_empty: Vec::new(),
             condition_virtualization: Vec::new(),
             condition_capability: Vec::new(),
@@ -268,6 +388,7 @@ impl Default for UnitSection {
             condition_needs_update: Vec::new(),
             default_dependencies: true, // systemd default
             ignore_on_isolate: false,
+            survive_final_kill_signal: false,
         }
     }
 }
@@ -286,14 +407,22 @@ pub struct ServiceSection {
 
     // Restart
     pub restart: RestartPolicy,
-    pub restart_sec: Duration, // Default: 100ms per systemd docs
+    pub restart_sec: Option<Duration>, // RestartSec=, see restart_delay()
     pub timeout_start_sec: Option<Duration>,
     pub timeout_stop_sec: Option<Duration>,
+    pub timeout_abort_sec: Option<Duration>,
     pub remain_after_exit: bool, // For Type=oneshot: stay active after exit
+    pub rolling_restart: bool,   // RollingRestart= (sysd extension): restart is blue/green
 
     // Watchdog
     pub watchdog_sec: Option<Duration>, // Watchdog timeout (service must ping)
 
+    // Health checks (sysd extension)
+    pub exec_health_check: Option<HealthCheck>, // ExecHealthCheck=
+    pub health_check_interval_sec: Option<Duration>, // Between checks, and their timeout
+    pub health_check_retries: Option<u32>,      // Failed checks in a row that fail the service
+    pub wait_for_endpoint: Option<HealthCheck>, // WaitForEndpoint=, tcp:// or http:// only
+
     // Notification
     pub notify_access: NotifyAccess, // Who can send sd_notify messages
 
@@ -315,6 +444,13 @@ pub struct ServiceSection {
     pub environment: Vec<(String, String)>,
     pub environment_file: Vec<PathBuf>,
     pub unset_environment: Vec<String>, // UnsetEnvironment=
+    pub pass_environment: Vec<String>,  // PassEnvironment= (system manager variables to keep)
+
+    // Credentials ($CREDENTIALS_DIRECTORY)
+    pub load_credential: Vec<(String, String)>, // LoadCredential=ID[:PATH] (empty path: ID itself)
+    pub load_credential_encrypted: Vec<(String, String)>, // LoadCredentialEncrypted=ID[:PATH]
+    pub set_credential: Vec<(String, String)>,  // SetCredential=ID:VALUE
+    pub import_credential: Vec<String>,         // ImportCredential=GLOB
 
     // I/O
     pub standard_output: StdOutput,
@@ -324,11 +460,19 @@ pub struct ServiceSection {
     // TTY handling (for getty and similar)
     pub tty_path: Option<PathBuf>,
     pub tty_reset: bool,
+    pub tty_vhangup: bool,
+    pub tty_vt_disallocate: bool,
 
     // Resource limits (cgroup v2)
     pub memory_max: Option<u64>, // bytes
     pub cpu_quota: Option<u32>,  // percentage (100 = 1 core)
     pub tasks_max: Option<u32>,
+    pub memory_accounting: Option<bool>, // MemoryAccounting= - enable the memory controller
+    pub cpu_accounting: Option<bool>,    // CPUAccounting= - enable the cpu controller
+    pub ip_address_allow: Vec<IpPrefix>, // IPAddressAllow= - peers let through
+    pub ip_address_deny: Vec<IpPrefix>,  // IPAddressDeny= - peers dropped unless allowed
+    pub socket_bind_allow: Vec<SocketBindRule>, // SocketBindAllow= - binds permitted
+    pub socket_bind_deny: Vec<SocketBindRule>, // SocketBindDeny= - binds refused unless allowed
 
     // Process limits (setrlimit)
     pub limit_nofile: Option<u64>, // LimitNOFILE= (max open files)
@@ -343,23 +487,32 @@ pub struct ServiceSection {
     pub cache_directory: Vec<String>, // CacheDirectory= (/var/cache/<name>)
     pub runtime_directory_preserve: RuntimeDirectoryPreserve, // RuntimeDirectoryPreserve=
     pub dynamic_user: bool,           // DynamicUser= (allocate ephemeral UID/GID)
+    pub remove_ipc: bool,             // RemoveIPC= (drop the user's IPC objects on stop)
 
     // OOM killer
     pub oom_score_adjust: Option<i32>, // OOMScoreAdjust= (-1000 to 1000)
 
     // Security sandboxing
-    pub no_new_privileges: bool,       // NoNewPrivileges=
-    pub protect_system: ProtectSystem, // ProtectSystem=
-    pub protect_home: ProtectHome,     // ProtectHome=
-    pub private_tmp: bool,             // PrivateTmp=
-    pub private_devices: bool,         // PrivateDevices=
-    pub private_network: bool,         // PrivateNetwork=
-    pub protect_kernel_modules: bool,  // ProtectKernelModules=
-    pub protect_proc: ProtectProc,     // ProtectProc=
+    pub no_new_privileges: bool,                 // NoNewPrivileges=
+    pub protect_system: ProtectSystem,           // ProtectSystem=
+    pub protect_home: ProtectHome,               // ProtectHome=
+    pub private_tmp: bool,                       // PrivateTmp=
+    pub private_devices: bool,                   // PrivateDevices=
+    pub private_network: bool,                   // PrivateNetwork=
+    pub private_pids: bool,                      // PrivatePIDs=
+    pub private_ipc: bool,                       // PrivateIPC=
+    pub network_namespace_path: Option<PathBuf>, // NetworkNamespacePath=
+    pub protect_kernel_modules: bool,            // ProtectKernelModules=
+    pub protect_proc: ProtectProc,               // ProtectProc=
+    pub selinux_context: Option<MacLabel>,       // SELinuxContext=
+    pub apparmor_profile: Option<MacLabel>,      // AppArmorProfile=
+    pub smack_process_label: Option<MacLabel>,   // SmackProcessLabel=
 
     // Capabilities
     pub capability_bounding_set: Vec<String>, // CapabilityBoundingSet=
     pub ambient_capabilities: Vec<String>,    // AmbientCapabilities=
+    pub secure_bits: u32,                     // SecureBits= (SECBIT_* flags)
+    pub keyring_mode: KeyringMode,            // KeyringMode=
 
     // Namespace restrictions (None = not set, Some(empty) = all blocked)
     pub restrict_namespaces: Option<Vec<String>>, // RestrictNamespaces=
@@ -390,6 +543,7 @@ pub struct ServiceSection {
     pub ignore_sigpipe: bool,    // IgnoreSIGPIPE= - set SIG_IGN for SIGPIPE
     pub restrict_suid_sgid: bool, // RestrictSUIDSGID= - block setuid/setgid files
     pub restrict_address_families: Option<Vec<String>>, // RestrictAddressFamilies=
+    pub restrict_file_systems: Vec<String>, // RestrictFileSystems= - types or @groups, ~ to deny
 
     // M18: Process control & dependencies
     pub start_limit_burst: Option<u32>, // StartLimitBurst= - max restarts in interval
@@ -413,11 +567,17 @@ macro_rules! default_service_section {
             exec_stop: Vec::new(),
             exec_reload: Vec::new(),
             restart: RestartPolicy::default(),
-            restart_sec: Duration::from_millis(100), // systemd default
+            restart_sec: None,
             timeout_start_sec: None,
             timeout_stop_sec: None,
+            timeout_abort_sec: None,
             remain_after_exit: false,
+            rolling_restart: false,
             watchdog_sec: None,
+            exec_health_check: None,
+            health_check_interval_sec: None,
+            health_check_retries: None,
+            wait_for_endpoint: None,
             notify_access: NotifyAccess::default(),
             pid_file: None,
             bus_name: None,
@@ -428,14 +588,27 @@ macro_rules! default_service_section {
             environment: Vec::new(),
             environment_file: Vec::new(),
             unset_environment: Vec::new(),
+            pass_environment: Vec::new(),
+            load_credential: Vec::new(),
+            load_credential_encrypted: Vec::new(),
+            set_credential: Vec::new(),
+            import_credential: Vec::new(),
             standard_output: StdOutput::default(),
             standard_error: StdOutput::default(),
             standard_input: StdInput::default(),
             tty_path: None,
             tty_reset: false,
+            tty_vhangup: false,
+            tty_vt_disallocate: false,
             memory_max: None,
             cpu_quota: None,
             tasks_max: None,
+            memory_accounting: None,
+            cpu_accounting: None,
+            ip_address_allow: Vec::new(),
+            ip_address_deny: Vec::new(),
+            socket_bind_allow: Vec::new(),
+            socket_bind_deny: Vec::new(),
             limit_nofile: None,
             limit_nproc: None,
             limit_core: None,
@@ -446,6 +619,7 @@ macro_rules! default_service_section {
             cache_directory: Vec::new(),
             runtime_directory_preserve: RuntimeDirectoryPreserve::No,
             dynamic_user: false,
+            remove_ipc: false,
             oom_score_adjust: None,
             no_new_privileges: false,
             protect_system: ProtectSystem::default(),
@@ -453,10 +627,18 @@ macro_rules! default_service_section {
             private_tmp: false,
             private_devices: false,
             private_network: false,
+            private_pids: false,
+            private_ipc: false,
+            network_namespace_path: None,
             protect_kernel_modules: false,
             protect_proc: ProtectProc::default(),
+            selinux_context: None,
+            apparmor_profile: None,
+            smack_process_label: None,
             capability_bounding_set: Vec::new(),
             ambient_capabilities: Vec::new(),
+            secure_bits: 0,
+            keyring_mode: KeyringMode::default(),
             restrict_namespaces: None,
             read_write_paths: Vec::new(),
             read_only_paths: Vec::new(),
@@ -477,6 +659,7 @@ macro_rules! default_service_section {
             ignore_sigpipe: false,
             restrict_suid_sgid: false,
             restrict_address_families: None,
+            restrict_file_systems: Vec::new(),
             start_limit_burst: None,
             start_limit_interval_sec: None,
             sockets: Vec::new(),
@@ -496,6 +679,16 @@ impl Default for ServiceSection {
     }
 }
 
+/// RestartSec= when neither the unit nor DefaultRestartSec= set one
+pub const DEFAULT_RESTART_SEC: Duration = Duration::from_millis(100);
+
+impl ServiceSection {
+    /// Delay before an automatic restart
+    pub fn restart_delay(&self) -> Duration {
+        self.restart_sec.unwrap_or(DEFAULT_RESTART_SEC)
+    }
+}
+
 /// [Install] section
 #[derive(Debug, Clone, Default)]
 pub struct InstallSection {
@@ -620,48 +813,53 @@ impl StdOutput {
 }
 
 /// Parse duration from systemd format (e.g., "5s", "100ms", "1min", "1d", "1w")
+///
+/// Values too large for a u64 number of seconds are rejected.
 pub fn parse_duration(s: &str) -> Option<Duration> {
     let s = s.trim();
+    let scaled = |n: &str, factor: u64| {
+        n.parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(factor))
+            .map(Duration::from_secs)
+    };
 
     // Try common suffixes (order matters: check longer suffixes first)
     if let Some(n) = s.strip_suffix("ms") {
         n.parse().ok().map(Duration::from_millis)
     } else if let Some(n) = s.strip_suffix("min") {
-        n.parse::<u64>().ok().map(|m| Duration::from_secs(m * 60))
+        scaled(n, 60)
     } else if let Some(n) = s.strip_suffix("sec") {
-        n.parse().ok().map(Duration::from_secs)
+        scaled(n, 1)
     } else if let Some(n) = s.strip_suffix("week") {
-        n.parse::<u64>()
-            .ok()
-            .map(|w| Duration::from_secs(w * 7 * 86400))
+        scaled(n, 7 * 86400)
     } else if let Some(n) = s.strip_suffix('s') {
-        n.parse().ok().map(Duration::from_secs)
+        scaled(n, 1)
     } else if let Some(n) = s.strip_suffix('h') {
-        n.parse::<u64>().ok().map(|h| Duration::from_secs(h * 3600))
+        scaled(n, 3600)
     } else if let Some(n) = s.strip_suffix('d') {
-        n.parse::<u64>()
-            .ok()
-            .map(|d| Duration::from_secs(d * 86400))
+        scaled(n, 86400)
     } else if let Some(n) = s.strip_suffix('w') {
-        n.parse::<u64>()
-            .ok()
-            .map(|w| Duration::from_secs(w * 7 * 86400))
+        scaled(n, 7 * 86400)
     } else {
         // Bare number = seconds
-        s.parse().ok().map(Duration::from_secs)
+        scaled(s, 1)
     }
 }
 
 /// Parse memory size (e.g., "512M", "1G", "1073741824")
+///
+/// Sizes that do not fit in a u64 are rejected.
 pub fn parse_memory(s: &str) -> Option<u64> {
     let s = s.trim();
+    let scaled = |n: &str, factor: u64| n.parse::<u64>().ok()?.checked_mul(factor);
 
     if let Some(n) = s.strip_suffix('G') {
-        n.parse::<u64>().ok().map(|g| g * 1024 * 1024 * 1024)
+        scaled(n, 1024 * 1024 * 1024)
     } else if let Some(n) = s.strip_suffix('M') {
-        n.parse::<u64>().ok().map(|m| m * 1024 * 1024)
+        scaled(n, 1024 * 1024)
     } else if let Some(n) = s.strip_suffix('K') {
-        n.parse::<u64>().ok().map(|k| k * 1024)
+        scaled(n, 1024)
     } else {
         s.parse().ok()
     }
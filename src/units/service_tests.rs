@@ -93,6 +93,37 @@ fn kill_input_device_and_proc_modes_parse_all_values() {
     assert_eq!(ProtectProc::parse("unknown"), None);
 }
 
+#[test]
+fn health_checks_parse_commands_tcp_and_http_probes() {
+    assert_eq!(
+        HealthCheck::parse("/usr/bin/curl -sf localhost"),
+        Some(HealthCheck::Exec("/usr/bin/curl -sf localhost".to_string()))
+    );
+    assert_eq!(
+        HealthCheck::parse("tcp://127.0.0.1:5432"),
+        Some(HealthCheck::Tcp("127.0.0.1:5432".to_string()))
+    );
+    assert_eq!(
+        HealthCheck::parse("http://localhost:8080/healthz"),
+        Some(HealthCheck::Http {
+            address: "localhost:8080".to_string(),
+            host: "localhost:8080".to_string(),
+            path: "/healthz".to_string(),
+        })
+    );
+    assert_eq!(
+        HealthCheck::parse("http://[::1]"),
+        Some(HealthCheck::Http {
+            address: "[::1]:80".to_string(),
+            host: "[::1]".to_string(),
+            path: "/".to_string(),
+        })
+    );
+    assert_eq!(HealthCheck::parse("tcp://"), None);
+    assert_eq!(HealthCheck::parse("http:///x"), None);
+    assert_eq!(HealthCheck::parse(" "), None);
+}
+
 #[test]
 fn test_std_output_default() {
     assert_eq!(StdOutput::default(), StdOutput::Journal);
@@ -127,6 +158,8 @@ fn test_parse_duration_edge_cases() {
     assert_eq!(parse_duration("invalid"), None);
     assert_eq!(parse_duration(""), None);
     assert_eq!(parse_duration("5x"), None);
+    assert_eq!(parse_duration("18446744073709551615w"), None);
+    assert_eq!(parse_duration("307445734561825861min"), None);
 }
 
 // Memory parsing tests
@@ -136,6 +169,8 @@ fn test_parse_memory() {
     assert_eq!(parse_memory("512M"), Some(512 * 1024 * 1024));
     assert_eq!(parse_memory("1024K"), Some(1024 * 1024));
     assert_eq!(parse_memory("1048576"), Some(1048576));
+    assert_eq!(parse_memory("18446744073709551615K"), None);
+    assert_eq!(parse_memory("17179869184G"), None);
 }
 
 #[test]
@@ -164,7 +199,8 @@ fn test_service_section_default() {
     let section = ServiceSection::default();
     assert_eq!(section.service_type, ServiceType::Simple);
     assert_eq!(section.restart, RestartPolicy::No);
-    assert_eq!(section.restart_sec, Duration::from_millis(100));
+    assert_eq!(section.restart_sec, None);
+    assert_eq!(section.restart_delay(), Duration::from_millis(100));
     assert!(section.exec_start.is_empty());
     assert!(section.user.is_none());
 }
@@ -16,6 +16,82 @@ pub enum ListenType {
     Fifo,
     /// Netlink socket (ListenNetlink=)
     Netlink,
+    /// Unix or, with SocketProtocol=sctp, SCTP seqpacket socket
+    /// (ListenSequentialPacket=)
+    SequentialPacket,
+}
+
+impl ListenType {
+    /// Name shown in the TYPE column of list-sockets
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stream => "Stream",
+            Self::Datagram => "Datagram",
+            Self::Fifo => "FIFO",
+            Self::Netlink => "Netlink",
+            Self::SequentialPacket => "SequentialPacket",
+        }
+    }
+}
+
+/// SocketProtocol= - the IP protocol of ListenStream=, ListenDatagram= and
+/// ListenSequentialPacket= listeners instead of TCP or UDP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketProtocol {
+    /// SCTP, for ListenStream= and ListenSequentialPacket=
+    Sctp,
+    /// UDP-Lite, for ListenDatagram=
+    UdpLite,
+}
+
+impl SocketProtocol {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "sctp" => Some(Self::Sctp),
+            "udplite" => Some(Self::UdpLite),
+            _ => None,
+        }
+    }
+
+    /// Protocol number passed to socket(2)
+    pub fn ip_protocol(&self) -> i32 {
+        match self {
+            Self::Sctp => libc::IPPROTO_SCTP,
+            Self::UdpLite => libc::IPPROTO_UDPLITE,
+        }
+    }
+}
+
+/// BindIPv6Only= - whether IPv6 listeners also accept IPv4 connections
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BindIpv6Only {
+    /// Kernel default (net.ipv6.bindv6only); port-only listeners use both stacks
+    #[default]
+    Default,
+    /// Accept IPv4 and IPv6 on the same socket
+    Both,
+    /// IPv6 only
+    Ipv6Only,
+}
+
+impl BindIpv6Only {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "default" => Some(Self::Default),
+            "both" => Some(Self::Both),
+            "ipv6-only" => Some(Self::Ipv6Only),
+            _ => None,
+        }
+    }
+
+    /// IPV6_V6ONLY value to set, or None to leave the kernel default
+    pub fn v6only(&self) -> Option<bool> {
+        match self {
+            Self::Default => None,
+            Self::Both => Some(false),
+            Self::Ipv6Only => Some(true),
+        }
+    }
 }
 
 /// A single listener configuration
@@ -75,6 +151,34 @@ pub struct SocketSection {
 
     /// Defer service activation (DeferTrigger=)
     pub defer_trigger: bool,
+
+    /// Window for TriggerLimitBurst= (TriggerLimitIntervalSec=)
+    pub trigger_limit_interval_sec: Option<std::time::Duration>,
+
+    /// Maximum activations within the window (TriggerLimitBurst=)
+    pub trigger_limit_burst: Option<u32>,
+
+    /// IPv6 listeners: dual-stack or v6-only (BindIPv6Only=)
+    pub bind_ipv6_only: BindIpv6Only,
+
+    /// IP protocol other than TCP/UDP (SocketProtocol=)
+    pub socket_protocol: Option<SocketProtocol>,
+
+    /// Only receive traffic from this network interface (BindToDevice=)
+    pub bind_to_device: Option<String>,
+
+    /// Smack label of the socket or FIFO file (SmackLabel=)
+    pub smack_label: Option<String>,
+
+    /// Smack label of incoming packets (SmackLabelIPIn=)
+    pub smack_label_ip_in: Option<String>,
+
+    /// Smack label of outgoing packets (SmackLabelIPOut=)
+    pub smack_label_ip_out: Option<String>,
+
+    /// Accept=yes instances run at the SELinux level of the peer
+    /// (SELinuxContextFromNet=)
+    pub selinux_context_from_net: bool,
 }
 
 /// Represents a parsed .socket unit file
@@ -115,6 +219,15 @@ impl Socket {
         }
     }
 
+    /// Unit started when the socket sees traffic (the template for Accept=yes)
+    pub fn activates(&self) -> String {
+        match &self.socket.service {
+            Some(svc) => svc.clone(),
+            None if self.socket.accept => self.name.replace(".socket", "@.service"),
+            None => self.service_name(),
+        }
+    }
+
     /// Check if this is an Accept= socket (spawns per-connection instances)
     pub fn is_accept_socket(&self) -> bool {
         self.socket.accept
@@ -168,6 +281,10 @@ mod tests {
         assert!(socket.socket.pass_security);
         assert_eq!(socket.socket.symlinks, vec!["/run/api-link.sock"]);
         assert!(socket.socket.defer_trigger);
+        assert_eq!(socket.activates(), "api-worker.service");
+
+        socket.socket.service = None;
+        assert_eq!(socket.activates(), "api@.service");
     }
 
     #[test]
@@ -0,0 +1,113 @@
+//! Rules of SocketBindAllow=/SocketBindDeny=
+
+/// Address family a rule applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindFamily {
+    Ipv4,
+    Ipv6,
+}
+
+/// Transport protocol a rule applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindProtocol {
+    Tcp,
+    Udp,
+}
+
+/// One [FAMILY:][PROTOCOL:]PORTS rule; None fields match anything
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketBindRule {
+    pub family: Option<BindFamily>,
+    pub protocol: Option<BindProtocol>,
+    /// Inclusive port range
+    pub ports: Option<(u16, u16)>,
+}
+
+impl SocketBindRule {
+    /// ipv4|ipv6 and tcp|udp prefixes, then a port, a LOW-HIGH range or any;
+    /// a bare "any" matches every bind
+    pub fn parse(s: &str) -> Option<Self> {
+        let mut rule = Self::default();
+        let mut rest = s;
+        if let Some((family, tail)) = rest.split_once(':') {
+            rule.family = match family {
+                "ipv4" => Some(BindFamily::Ipv4),
+                "ipv6" => Some(BindFamily::Ipv6),
+                _ => None,
+            };
+            if rule.family.is_some() {
+                rest = tail;
+            }
+        }
+        if let Some((protocol, tail)) = rest.split_once(':') {
+            rule.protocol = Some(match protocol {
+                "tcp" => BindProtocol::Tcp,
+                "udp" => BindProtocol::Udp,
+                _ => return None,
+            });
+            rest = tail;
+        }
+        rule.ports = match rest {
+            "any" => None,
+            _ => Some(parse_ports(rest)?),
+        };
+        Some(rule)
+    }
+}
+
+fn parse_ports(s: &str) -> Option<(u16, u16)> {
+    let (low, high) = match s.split_once('-') {
+        Some((low, high)) => (low.parse().ok()?, high.parse().ok()?),
+        None => {
+            let port = s.parse().ok()?;
+            (port, port)
+        }
+    };
+    (low <= high).then_some((low, high))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_parse_with_optional_family_and_protocol() {
+        assert_eq!(
+            SocketBindRule::parse("any"),
+            Some(SocketBindRule::default())
+        );
+        assert_eq!(
+            SocketBindRule::parse("8080"),
+            Some(SocketBindRule {
+                ports: Some((8080, 8080)),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            SocketBindRule::parse("ipv6:udp:5000-5010"),
+            Some(SocketBindRule {
+                family: Some(BindFamily::Ipv6),
+                protocol: Some(BindProtocol::Udp),
+                ports: Some((5000, 5010)),
+            })
+        );
+        assert_eq!(
+            SocketBindRule::parse("tcp:any"),
+            Some(SocketBindRule {
+                protocol: Some(BindProtocol::Tcp),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            SocketBindRule::parse("ipv4:any"),
+            Some(SocketBindRule {
+                family: Some(BindFamily::Ipv4),
+                ..Default::default()
+            })
+        );
+        assert_eq!(SocketBindRule::parse("sctp:80"), None);
+        assert_eq!(SocketBindRule::parse("ipv4:90-80"), None);
+        assert_eq!(SocketBindRule::parse("ipv4:http"), None);
+        assert_eq!(SocketBindRule::parse("70000"), None);
+    }
+}
@@ -10,8 +10,6 @@ use super::service::UnitSection;
 pub struct Target {
     pub name: String,
     pub unit: UnitSection,
-    /// Services/targets pulled in by .wants directory
-    pub wants_dir: Vec<String>,
 }
 
 impl Target {
@@ -19,7 +17,6 @@ impl Target {
         Self {
             name,
             unit: UnitSection::default(),
-            wants_dir: Vec::new(),
         }
     }
 }
@@ -0,0 +1,283 @@
+//! Age-based cleanup from tmpfiles.d (the `--clean` half of systemd-tmpfiles)
+//!
+//! Lines of type d, D, e, v, q, Q and C with an age, e.g. `d /var/tmp 1777
+//! root root 30d`, name directories whose contents are removed once they
+//! have not been used for that long: an entry is old when its access,
+//! modification and change times all lie before the cutoff. Subdirectories
+//! are emptied first and removed when they end up empty and were old
+//! themselves. Symlinks are not followed and other file systems are not
+//! entered. An age starting with `~` spares the entries directly inside the
+//! directory and only cleans below them. Paths may be globs (`e
+//! /var/cache/app-* - - - 7d`).
+//!
+//! Files come from the directories below; a file masks one with the same
+//! name in a later directory, and lines for the same path in later files
+//! are ignored, as in systemd-tmpfiles. Creating files and directories and
+//! the other line types are left to systemd-tmpfiles.
+
+use std::collections::{BTreeMap, HashSet};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::path_glob::expand_path_glob;
+use super::service::parse_duration;
+
+/// tmpfiles.d directories, highest priority first
+pub const TMPFILES_DIRS: [&str; 4] = [
+    "/etc/tmpfiles.d",
+    "/run/tmpfiles.d",
+    "/usr/local/lib/tmpfiles.d",
+    "/usr/lib/tmpfiles.d",
+];
+
+/// Line types whose age field asks for cleanup
+const AGED_TYPES: [char; 7] = ['d', 'D', 'e', 'v', 'q', 'Q', 'C'];
+
+/// One directory (or glob of directories) to clean
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgeRule {
+    pub path: String,
+    pub age: Duration,
+    /// `~` age: leave the directory's immediate entries alone
+    pub keep_first_level: bool,
+}
+
+impl AgeRule {
+    /// The rule of one tmpfiles.d line, if it has an age
+    fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let kind = fields.first()?.chars().next()?;
+        if !AGED_TYPES.contains(&kind) {
+            return None;
+        }
+        let path = *fields.get(1)?;
+        let age = *fields.get(5)?;
+        if age == "-" {
+            return None;
+        }
+        // "abcm:10d" picks which timestamps count; all of them do here
+        let age = age.rsplit(':').next().unwrap_or(age);
+        let (keep_first_level, age) = match age.strip_prefix('~') {
+            Some(age) => (true, age),
+            None => (false, age),
+        };
+        Some(Self {
+            path: path.to_string(),
+            age: parse_duration(age)?,
+            keep_first_level,
+        })
+    }
+}
+
+/// Cleanup rules in the order they are applied
+#[derive(Debug, Clone, Default)]
+pub struct AgeRules {
+    pub rules: Vec<AgeRule>,
+}
+
+impl AgeRules {
+    /// Rules from the tmpfiles.d directories (below `--root`)
+    pub fn load() -> Self {
+        let dirs: Vec<PathBuf> = TMPFILES_DIRS.iter().map(crate::root::path).collect();
+        Self::from_dirs(&dirs)
+    }
+
+    /// Rules from the *.conf files in `dirs` (highest priority first)
+    pub fn from_dirs(dirs: &[PathBuf]) -> Self {
+        let mut files: BTreeMap<String, PathBuf> = BTreeMap::new();
+        for dir in dirs {
+            let Ok(entries) = std::fs::read_dir(dir) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(name) = entry.file_name().into_string() else {
+                    continue;
+                };
+                if name.ends_with(".conf") {
+                    files.entry(name).or_insert_with(|| entry.path());
+                }
+            }
+        }
+        let mut rules = Self::default();
+        for path in files.values() {
+            match std::fs::read_to_string(path) {
+                Ok(content) => rules.add(&content),
+                Err(e) => log::warn!("Failed to read {}: {}", path.display(), e),
+            }
+        }
+        rules
+    }
+
+    /// Add the aged lines of one tmpfiles.d file
+    pub fn add(&mut self, content: &str) {
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some(rule) = AgeRule::parse(line) else {
+                continue;
+            };
+            if !self.rules.iter().any(|known| known.path == rule.path) {
+                self.rules.push(rule);
+            }
+        }
+    }
+
+    /// Remove what has aged out as of `now`; returns how many entries went
+    pub fn clean(&self, now: SystemTime) -> usize {
+        let mut removed = 0;
+        for rule in &self.rules {
+            let Some(cutoff) = now.checked_sub(rule.age) else {
+                continue;
+            };
+            for dir in expand_path_glob(&rule.path) {
+                let Ok(meta) = std::fs::symlink_metadata(&dir) else {
+                    continue;
+                };
+                if meta.is_dir() {
+                    removed += clean_dir(&dir, cutoff, meta.dev(), rule.keep_first_level);
+                }
+            }
+        }
+        removed
+    }
+}
+
+/// Latest of the access, modification and change times
+fn last_used(meta: &std::fs::Metadata) -> SystemTime {
+    let secs = meta.atime().max(meta.mtime()).max(meta.ctime());
+    UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+}
+
+/// Remove the entries of `dir` last used before `cutoff`, staying on device
+/// `dev`; with `keep` the entries themselves stay and only their contents
+/// are cleaned
+fn clean_dir(dir: &Path, cutoff: SystemTime, dev: u64, keep: bool) -> usize {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return 0;
+    };
+    let mut removed = 0;
+    let mut seen = HashSet::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if meta.dev() != dev || !seen.insert(meta.ino()) {
+            continue;
+        }
+        // Emptying a directory touches it, so decide before that
+        let old = last_used(&meta) < cutoff;
+        if meta.is_dir() {
+            removed += clean_dir(&path, cutoff, dev, false);
+            if !keep && old && std::fs::remove_dir(&path).is_ok() {
+                removed += 1;
+            }
+        } else if !keep && old {
+            match std::fs::remove_file(&path) {
+                Ok(()) => removed += 1,
+                Err(e) => log::debug!("Failed to remove {}: {}", path.display(), e),
+            }
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_dir(label: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "sysd-tmpfiles-{}-{}-{}",
+            label,
+            std::process::id(),
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_nanos()
+        ));
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn only_aged_lines_become_rules() {
+        let mut rules = AgeRules::default();
+        rules.add(
+            "# comment\n\
+             d /var/tmp 1777 root root 30d\n\
+             D /run/app 0755 - - ~1h\n\
+             e /var/cache/app-* - - - m:7d\n\
+             d /var/tmp 1777 root root 1d\n\
+             f /etc/motd 0644 - - 1d\n\
+             d /srv - - - -\n",
+        );
+        assert_eq!(
+            rules.rules,
+            [
+                AgeRule {
+                    path: "/var/tmp".to_string(),
+                    age: Duration::from_secs(30 * 86400),
+                    keep_first_level: false,
+                },
+                AgeRule {
+                    path: "/run/app".to_string(),
+                    age: Duration::from_secs(3600),
+                    keep_first_level: true,
+                },
+                AgeRule {
+                    path: "/var/cache/app-*".to_string(),
+                    age: Duration::from_secs(7 * 86400),
+                    keep_first_level: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn cleanup_removes_what_aged_out_in_globbed_dirs() {
+        let base = temp_dir("clean");
+        let cache = base.join("app-1");
+        fs::create_dir_all(cache.join("sub")).unwrap();
+        fs::write(cache.join("old"), "").unwrap();
+        fs::write(cache.join("sub/old"), "").unwrap();
+        let other = base.join("other");
+        fs::create_dir_all(&other).unwrap();
+        fs::write(other.join("old"), "").unwrap();
+
+        let mut rules = AgeRules::default();
+        rules.add(&format!("e {}/app-* - - - 1h\n", base.display()));
+        // Nothing is an hour old yet
+        assert_eq!(rules.clean(SystemTime::now()), 0);
+        assert!(cache.join("old").exists());
+
+        // Two hours on, everything below the matched directory has aged out
+        let later = SystemTime::now() + Duration::from_secs(2 * 3600);
+        assert_eq!(rules.clean(later), 3);
+        assert!(cache.exists());
+        assert!(!cache.join("sub").exists());
+        assert!(!cache.join("old").exists());
+        assert!(other.join("old").exists());
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn tilde_age_keeps_the_first_level() {
+        let base = temp_dir("keep");
+        fs::create_dir_all(base.join("home/cache")).unwrap();
+        fs::write(base.join("home/cache/old"), "").unwrap();
+        fs::write(base.join("top"), "").unwrap();
+
+        let mut rules = AgeRules::default();
+        rules.add(&format!("d {} - - - ~1h\n", base.display()));
+        let later = SystemTime::now() + Duration::from_secs(2 * 3600);
+        assert_eq!(rules.clean(later), 2);
+        assert!(base.join("top").exists());
+        assert!(base.join("home").exists());
+        assert!(!base.join("home/cache").exists());
+        let _ = fs::remove_dir_all(&base);
+    }
+}
@@ -41,6 +41,19 @@ impl Unit {
         }
     }
 
+    /// Get the [Unit] section for modification
+    pub fn unit_section_mut(&mut self) -> &mut UnitSection {
+        match self {
+            Unit::Service(s) => &mut s.unit,
+            Unit::Target(t) => &mut t.unit,
+            Unit::Mount(m) => &mut m.unit,
+            Unit::Slice(s) => &mut s.unit,
+            Unit::Socket(s) => &mut s.unit,
+            Unit::Timer(t) => &mut t.unit,
+            Unit::Path(p) => &mut p.unit,
+        }
+    }
+
     /// Get the [Install] section
     pub fn install_section(&self) -> Option<&InstallSection> {
         match self {
@@ -167,17 +180,14 @@ impl Unit {
             .collect()
     }
 
-    /// Get units from .wants directory (for targets)
+    /// Get units from the .wants directory
     pub fn wants_dir(&self) -> &[String] {
-        match self {
-            Unit::Target(t) => &t.wants_dir,
-            Unit::Service(_)
-            | Unit::Mount(_)
-            | Unit::Slice(_)
-            | Unit::Socket(_)
-            | Unit::Timer(_)
-            | Unit::Path(_) => &[],
-        }
+        &self.unit_section().wants_dir
+    }
+
+    /// Get units from the .requires directory
+    pub fn requires_dir(&self) -> &[String] {
+        &self.unit_section().requires_dir
     }
 
     /// Set the unit name (used for template instantiation)
@@ -302,12 +312,16 @@ mod tests {
     }
 
     #[test]
-    fn wants_dir_only_reports_target_wants() {
+    fn wants_and_requires_dirs_are_reported_for_every_unit_type() {
         let mut target = Target::new("multi-user.target".to_string());
-        target.wants_dir = vec!["ssh.service".to_string()];
+        target.unit.wants_dir = vec!["ssh.service".to_string()];
+        let mut service = Service::new("api.service".to_string());
+        service.unit.requires_dir = vec!["db.service".to_string()];
 
         assert_eq!(Unit::Target(target).wants_dir(), ["ssh.service"]);
-        assert!(service_unit("api.service").wants_dir().is_empty());
+        let service = Unit::Service(service);
+        assert!(service.wants_dir().is_empty());
+        assert_eq!(service.requires_dir(), ["db.service"]);
     }
 
     #[test]
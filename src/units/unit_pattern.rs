@@ -0,0 +1,122 @@
+//! Unit name patterns given on the command line and over D-Bus
+//!
+//! `foo@{a,b}.service` expands to one name per alternative, like the shell
+//! would, so instances of a template can be named in one argument even where
+//! no shell expands it (D-Bus callers, quoted arguments). Wildcards then
+//! match loaded units, as `systemctl start 'getty@*'` does.
+
+use super::path_glob::has_glob_chars;
+
+/// The names `pattern` stands for after brace expansion, in order; braces
+/// without a comma or without a closing brace are kept literally
+pub fn expand_unit_braces(pattern: &str) -> Vec<String> {
+    let Some((open, close)) = first_brace_group(pattern) else {
+        return vec![pattern.to_string()];
+    };
+    let (prefix, suffix) = (&pattern[..open], &pattern[close + 1..]);
+    split_alternatives(&pattern[open + 1..close])
+        .into_iter()
+        .flat_map(|alternative| expand_unit_braces(&format!("{}{}{}", prefix, alternative, suffix)))
+        .collect()
+}
+
+/// Byte offsets of the first `{...}` group holding a top-level comma
+fn first_brace_group(pattern: &str) -> Option<(usize, usize)> {
+    let bytes = pattern.as_bytes();
+    for open in (0..bytes.len()).filter(|&i| bytes[i] == b'{') {
+        let mut depth = 0;
+        let mut has_comma = false;
+        for (i, &byte) in bytes.iter().enumerate().skip(open) {
+            match byte {
+                b'{' => depth += 1,
+                b'}' if depth == 1 => {
+                    if has_comma {
+                        return Some((open, i));
+                    }
+                    break;
+                }
+                b'}' => depth -= 1,
+                b',' if depth == 1 => has_comma = true,
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+/// Split the inside of a brace group at its top-level commas
+fn split_alternatives(inner: &str) -> Vec<&str> {
+    let mut alternatives = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                alternatives.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    alternatives.push(&inner[start..]);
+    alternatives
+}
+
+/// Whether the unit `name` matches the wildcard `pattern`; without a type
+/// suffix in the pattern, the name's suffix is ignored (`getty@*` matches
+/// getty@tty1.service)
+pub fn unit_name_matches(pattern: &str, name: &str) -> bool {
+    if !has_glob_chars(pattern) {
+        return pattern == name;
+    }
+    let Ok(glob) = glob::Pattern::new(pattern) else {
+        return false;
+    };
+    if glob.matches(name) {
+        return true;
+    }
+    let has_suffix = pattern.rsplit_once('.').is_some_and(|(_, suffix)| {
+        suffix.chars().all(|c| c.is_ascii_lowercase()) && !suffix.is_empty()
+    });
+    !has_suffix
+        && name
+            .rsplit_once('.')
+            .is_some_and(|(stem, _)| glob.matches(stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braces_expand_in_order_and_nest() {
+        assert_eq!(
+            expand_unit_braces("foo@{a,b,c}.service"),
+            ["foo@a.service", "foo@b.service", "foo@c.service"]
+        );
+        assert_eq!(
+            expand_unit_braces("{x,y}@{1,2}"),
+            ["x@1", "x@2", "y@1", "y@2"]
+        );
+        assert_eq!(
+            expand_unit_braces("vm@{a,b{1,2}}"),
+            ["vm@a", "vm@b1", "vm@b2"]
+        );
+        assert_eq!(expand_unit_braces("foo@{a}.service"), ["foo@{a}.service"]);
+        assert_eq!(expand_unit_braces("foo@{a,b"), ["foo@{a,b"]);
+        assert_eq!(expand_unit_braces("plain"), ["plain"]);
+    }
+
+    #[test]
+    fn wildcards_match_with_or_without_a_suffix() {
+        assert!(unit_name_matches("getty@*", "getty@tty1.service"));
+        assert!(unit_name_matches("getty@*.service", "getty@tty1.service"));
+        assert!(!unit_name_matches("getty@*.socket", "getty@tty1.service"));
+        assert!(unit_name_matches("*.timer", "backup.timer"));
+        assert!(unit_name_matches("vm@[ab]", "vm@a.service"));
+        assert!(!unit_name_matches("vm@[ab]", "vm@c.service"));
+        assert!(unit_name_matches("demo.service", "demo.service"));
+        assert!(!unit_name_matches("demo", "demo.service"));
+    }
+}
@@ -0,0 +1,85 @@
+//! Property tests for the unit file parser
+//!
+//! Generated inputs, including pathological ones (huge lines, trailing
+//! backslashes, NULs, invalid UTF-8, nested quotes), must never make the
+//! parser panic, and syntax errors must point at a real line and column.
+
+use proptest::prelude::*;
+use sysd::units::{self, ParseError};
+
+/// Section headers, assignments, comments and junk, joined into a file
+fn unit_file() -> impl Strategy<Value = String> {
+    let line = prop_oneof![
+        Just("[Unit]".to_string()),
+        Just("[Service]".to_string()),
+        Just("[Install]".to_string()),
+        "\\[[A-Za-z]{0,12}\\]?",
+        "[A-Za-z]{1,20}=[^\n]{0,80}",
+        "(ExecStart|Environment|Description)=[\"' \\\\a-z$%=-]{0,60}",
+        "[#;][^\n]{0,40}",
+        "[ \t]{0,4}",
+        "[^\n]{0,40}\\\\",
+    ];
+    prop::collection::vec(line, 0..40).prop_map(|lines| lines.join("\n"))
+}
+
+/// Check a syntax error against the input it came from
+fn assert_located(err: &ParseError, input: &[u8]) {
+    if let Some((line, column)) = err.location() {
+        let lines: Vec<&[u8]> = input.split(|&b| b == b'\n').collect();
+        assert!(
+            line >= 1 && line <= lines.len(),
+            "line {} out of range",
+            line
+        );
+        assert!(column >= 1 && column <= lines[line - 1].len() + 1);
+    }
+}
+
+/// Every unit type's loader must cope with whatever the parser returned
+fn load_all(input: &[u8]) {
+    let parsed = match units::parse_bytes(input) {
+        Ok(parsed) => parsed,
+        Err(e) => return assert_located(&e, input),
+    };
+    let _ = units::parse_service("prop.service", &parsed);
+    let _ = units::parse_socket("prop.socket", &parsed);
+    let _ = units::parse_timer("prop.timer", &parsed);
+    let _ = units::parse_mount("prop.mount", &parsed);
+    let _ = units::parse_path_unit("prop.path", &parsed);
+}
+
+proptest! {
+    #[test]
+    fn arbitrary_bytes_never_panic(input in prop::collection::vec(any::<u8>(), 0..2048)) {
+        load_all(&input);
+    }
+
+    #[test]
+    fn generated_unit_files_never_panic(content in unit_file()) {
+        load_all(content.as_bytes());
+    }
+
+    #[test]
+    fn nul_bytes_are_reported_where_they_are(prefix in "[a-zA-Z=]{0,30}", suffix in "[a-z]{0,10}") {
+        let content = format!("[Service]\n{}\0{}\n", prefix, suffix);
+        let err = units::parse_file(&content).unwrap_err();
+        prop_assert_eq!(err.location(), Some((2, prefix.len() + 1)));
+    }
+
+    #[test]
+    fn huge_values_are_kept_whole(len in 0usize..100_000) {
+        let description = "x".repeat(len);
+        let content = format!("[Unit]\nDescription={}\n", description);
+        let service = units::parse_service("huge.service", &units::parse_file(&content).unwrap())
+            .unwrap();
+        prop_assert_eq!(service.unit.description.unwrap_or_default(), description);
+    }
+
+    #[test]
+    fn nested_quotes_in_environment_never_panic(depth in 0usize..64) {
+        let value = format!("A={}b{}", "\"'".repeat(depth), "'\"".repeat(depth));
+        let content = format!("[Service]\nEnvironment={}\nExecStart=/bin/echo {}\n", value, value);
+        load_all(content.as_bytes());
+    }
+}
@@ -0,0 +1,143 @@
+//! Whole-manager tests against a fake root (`sysd --root`, SYSD_ROOT)
+//!
+//! The root is process-wide, so this binary sets it once and every test
+//! works below the same directory, using its own unit names.
+
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use sysd::manager::Manager;
+use sysd::units::PresetAction;
+
+fn root() -> &'static Path {
+    static ROOT: OnceLock<PathBuf> = OnceLock::new();
+    ROOT.get_or_init(|| {
+        let root = PathBuf::from(format!("/tmp/sysd-root-mode-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["etc/systemd/system", "usr/lib/systemd/system", "run"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        assert!(
+            sysd::root::set(&root),
+            "root resolved before the test set it"
+        );
+        root
+    })
+}
+
+fn write(path: &str, content: &str) -> PathBuf {
+    let path = root().join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(&path, content).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn units_dropins_and_aliases_are_read_from_the_root() {
+    write(
+        "usr/lib/systemd/system/rooted-web.service",
+        "[Unit]\nDescription=Vendor\n\n[Service]\nExecStart=/bin/true\n",
+    );
+    write(
+        "etc/systemd/system/rooted-web.service.d/override.conf",
+        "[Unit]\nDescription=Admin\n",
+    );
+    // Absolute link targets are in-image paths and must not escape the root
+    symlink(
+        "/usr/lib/systemd/system/rooted-web.service",
+        root().join("etc/systemd/system/rooted-www.service"),
+    )
+    .unwrap();
+    let mut manager = Manager::new();
+
+    assert_eq!(
+        manager.load("rooted-www").await.unwrap(),
+        "rooted-web.service"
+    );
+    let unit = manager.get_unit("rooted-web.service").unwrap();
+    assert_eq!(unit.unit_section().description.as_deref(), Some("Admin"));
+}
+
+#[tokio::test]
+async fn enable_links_live_in_the_root_and_point_at_image_paths() {
+    write(
+        "usr/lib/systemd/system/rooted-sshd.service",
+        "[Service]\nExecStart=/bin/true\n\n\
+         [Install]\nWantedBy=multi-user.target\nAlias=rooted-ssh.service\n",
+    );
+    let mut manager = Manager::new();
+
+    let links = manager.enable("rooted-sshd").await.unwrap();
+    let wants = root().join("etc/systemd/system/multi-user.target.wants/rooted-sshd.service");
+    let alias = root().join("etc/systemd/system/rooted-ssh.service");
+    assert_eq!(links, [wants.clone(), alias.clone()]);
+    for link in [&wants, &alias] {
+        assert_eq!(
+            fs::read_link(link).unwrap(),
+            Path::new("/usr/lib/systemd/system/rooted-sshd.service")
+        );
+    }
+    assert_eq!(manager.is_enabled("rooted-sshd").await.unwrap(), "enabled");
+
+    manager.disable("rooted-sshd").await.unwrap();
+    assert!(wants.symlink_metadata().is_err());
+    assert_eq!(manager.is_enabled("rooted-sshd").await.unwrap(), "disabled");
+}
+
+#[tokio::test]
+async fn runtime_paths_are_created_below_the_root() {
+    write(
+        "etc/systemd/system/rooted-runtime.service",
+        "[Service]\nType=oneshot\nExecStart=/bin/true\nRuntimeDirectory=rooted-runtime\n",
+    );
+    let mut manager = Manager::new();
+    manager.init_notify_socket().unwrap();
+    assert!(root().join("run/sysd/notify").exists());
+    assert!(!manager.cgroups_available());
+
+    manager.start("rooted-runtime").await.unwrap();
+    assert!(root().join("run/rooted-runtime").is_dir());
+    assert_eq!(
+        sysd::protocol::socket_path(false),
+        root().join("run/sysd.sock").to_string_lossy()
+    );
+}
+
+#[tokio::test]
+async fn presets_and_default_target_are_applied_inside_the_root() {
+    let install = "[Service]\nExecStart=/bin/true\n\n[Install]\nWantedBy=multi-user.target\n";
+    write("usr/lib/systemd/system/rooted-keep.service", install);
+    write("usr/lib/systemd/system/rooted-drop.service", install);
+    write(
+        "usr/lib/systemd/system/rooted-graphical.target",
+        "[Unit]\nDescription=Graphical\n",
+    );
+    write(
+        "usr/lib/systemd/system-preset/90-rooted.preset",
+        "disable rooted-drop.service\n",
+    );
+    let mut manager = Manager::new();
+
+    let (action, _) = manager.preset("rooted-keep").await.unwrap();
+    assert_eq!(action, PresetAction::Enable);
+    let (action, _) = manager.preset("rooted-drop").await.unwrap();
+    assert_eq!(action, PresetAction::Disable);
+    assert_eq!(manager.is_enabled("rooted-keep").await.unwrap(), "enabled");
+    assert_eq!(manager.is_enabled("rooted-drop").await.unwrap(), "disabled");
+
+    let link = manager
+        .set_default("rooted-graphical.target")
+        .await
+        .unwrap();
+    assert_eq!(link, root().join("etc/systemd/system/default.target"));
+    assert_eq!(
+        fs::read_link(&link).unwrap(),
+        Path::new("/usr/lib/systemd/system/rooted-graphical.target")
+    );
+    assert_eq!(
+        manager.get_default_target().unwrap(),
+        "rooted-graphical.target"
+    );
+}
@@ -0,0 +1,214 @@
+//! End-to-end socket activation tests
+//!
+//! Each test drives a real manager against the bundled echo service
+//! (examples/socket_echo.rs): the manager listens, a client connects, the
+//! activation is handled and the client talks to the spawned service. Use
+//! these as a template when testing other activation paths.
+//!
+//! The echo service is built as an example, which `cargo test` does by
+//! default. With a target filter run `cargo build --examples` first;
+//! otherwise the tests are skipped.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use sysd::manager::{Manager, SocketActivation};
+use tokio::sync::mpsc;
+
+static TEST_COUNTER: AtomicU32 = AtomicU32::new(0);
+
+/// Reaping waits for any child, so tests in this binary must not overlap
+static SERIAL: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+struct TestDir(PathBuf);
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn unique_test_dir() -> TestDir {
+    let id = TEST_COUNTER.fetch_add(1, Ordering::SeqCst);
+    let dir = PathBuf::from(format!(
+        "/tmp/sysd-activation-{}-{}",
+        std::process::id(),
+        id
+    ));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    TestDir(dir)
+}
+
+/// Path of the echo example next to this test binary (target/<profile>/examples)
+fn echo_binary() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let path = exe.parent()?.parent()?.join("examples/socket_echo");
+    if path.exists() {
+        Some(path)
+    } else {
+        eprintln!(
+            "skipping: {} not built (cargo build --examples)",
+            path.display()
+        );
+        None
+    }
+}
+
+/// Write the units below `root` and load them into a fresh manager
+async fn manager_with_units(root: &Path, units: &[(&str, String)]) -> Manager {
+    let unit_dir = root.join("etc/systemd/system");
+    fs::create_dir_all(&unit_dir).unwrap();
+    for (name, body) in units {
+        fs::write(unit_dir.join(name), body).unwrap();
+    }
+    let mut manager = Manager::new();
+    manager.set_unit_root(root);
+    for (name, _) in units {
+        if !name.contains("@.") {
+            manager.load(name).await.unwrap();
+        }
+    }
+    manager
+}
+
+async fn next_activation(rx: &mut mpsc::Receiver<SocketActivation>) -> SocketActivation {
+    tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .expect("activation within 5 seconds")
+        .expect("activation channel open")
+}
+
+/// Read the greeting, then check one line makes the round trip
+fn converse(stream: &UnixStream, line: &str) -> String {
+    stream
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut greeting = String::new();
+    reader.read_line(&mut greeting).unwrap();
+    writeln!(&*stream, "{}", line).unwrap();
+    let mut echoed = String::new();
+    reader.read_line(&mut echoed).unwrap();
+    assert_eq!(echoed.trim_end(), line);
+    greeting.trim_end().to_string()
+}
+
+/// Reap until the unit is no longer active (or gone)
+async fn wait_until_down(manager: &mut Manager, name: &str) {
+    for _ in 0..100 {
+        manager.reap().await;
+        if !manager.status(name).is_some_and(|state| state.is_active()) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("{} still active", name);
+}
+
+#[tokio::test]
+async fn listen_fds_reach_the_activated_service_and_idle_exit_rearms_the_socket() {
+    let Some(echo) = echo_binary() else {
+        return;
+    };
+    let _serial = SERIAL.lock().await;
+    let dir = unique_test_dir();
+    let socket_path = dir.0.join("echo.sock");
+    let mut manager = manager_with_units(
+        &dir.0,
+        &[
+            (
+                "echo.socket",
+                format!(
+                    "[Socket]\nListenStream={}\nFileDescriptorName=echo\n",
+                    socket_path.display()
+                ),
+            ),
+            (
+                "echo.service",
+                format!("[Service]\nExecStart={} --idle-sec 1\n", echo.display()),
+            ),
+        ],
+    )
+    .await;
+    let mut rx = manager.take_socket_activation_rx().unwrap();
+    manager.start("echo.socket").await.unwrap();
+
+    for round in 0..2 {
+        let client = UnixStream::connect(&socket_path).unwrap();
+        let activation = next_activation(&mut rx).await;
+        assert_eq!(activation.service_name, "echo.service");
+        assert!(activation.connection.is_none());
+        manager.handle_socket_activation(activation).await.unwrap();
+
+        let greeting = converse(&client, &format!("round {}", round));
+        assert_eq!(greeting, "LISTEN_FDS=1 LISTEN_FDNAMES=echo");
+        drop(client);
+
+        // The service exits after a second without connections; the socket
+        // must pick up the next one
+        wait_until_down(&mut manager, "echo.service").await;
+    }
+
+    manager.stop("echo.socket").await.unwrap();
+}
+
+#[tokio::test]
+async fn accept_yes_starts_one_instance_per_connection() {
+    let Some(echo) = echo_binary() else {
+        return;
+    };
+    let _serial = SERIAL.lock().await;
+    let dir = unique_test_dir();
+    let socket_path = dir.0.join("echo.sock");
+    let mut manager = manager_with_units(
+        &dir.0,
+        &[
+            (
+                "echo.socket",
+                format!(
+                    "[Socket]\nListenStream={}\nAccept=yes\n",
+                    socket_path.display()
+                ),
+            ),
+            (
+                "echo@.service",
+                format!("[Service]\nExecStart={}\n", echo.display()),
+            ),
+        ],
+    )
+    .await;
+    let mut rx = manager.take_socket_activation_rx().unwrap();
+    manager.start("echo.socket").await.unwrap();
+
+    let mut clients = Vec::new();
+    for expected in ["echo@0.service", "echo@1.service"] {
+        let client = UnixStream::connect(&socket_path).unwrap();
+        let activation = next_activation(&mut rx).await;
+        assert_eq!(activation.service_name, "echo@.service");
+        assert!(activation.connection.is_some());
+        manager.handle_socket_activation(activation).await.unwrap();
+        assert!(manager.status(expected).unwrap().is_active());
+        clients.push(client);
+    }
+
+    // Both instances serve their own connection at the same time
+    for (i, client) in clients.iter().enumerate() {
+        let greeting = converse(client, &format!("client {}", i));
+        assert_eq!(greeting, "LISTEN_FDS=1 LISTEN_FDNAMES=connection");
+    }
+
+    // Hanging up ends the instance, which is then forgotten
+    drop(clients);
+    for name in ["echo@0.service", "echo@1.service"] {
+        wait_until_down(&mut manager, name).await;
+        assert!(manager.get_unit(name).is_none());
+    }
+    assert!(manager.status("echo.socket").unwrap().is_active());
+
+    manager.stop("echo.socket").await.unwrap();
+}